@@ -0,0 +1,311 @@
+//! 月灵聊天的压力测试工具
+//!
+//! 打开 N 个 WebSocket 连接，按总速率发送消息，接收方根据消息内容中的发送时间计算端到端延迟，
+//! 结束后统计延迟分位数和丢失的推送，用于衡量连接登记和广播路径的性能变化
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures_util::{StreamExt, TryStreamExt};
+use serde_json::json;
+use tokio::sync::watch;
+use tokio::time::MissedTickBehavior;
+use yueling_client::{ApiClient, Event, EventStream, Result};
+
+mod stats;
+
+pub use stats::Histogram;
+
+// 压测消息内容的前缀，格式为 "yueling-bench 发送者序号 消息序号 发送时间（微秒）"
+const MARKER: &str = "yueling-bench";
+// 私聊模式下注册账号的密码
+const PASSWORD: &str = "yueling-bench";
+// 同时进行的注册请求数
+const REGISTER_CONCURRENCY: usize = 16;
+
+/// 压测的消息路径
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// 每个客户端给下一个客户端发私聊：注册账号，消息写入数据库后推送，接收方确认送达
+    Private,
+    /// 所有客户端订阅同一个群聊广播通道，每条消息推送给全部 N 个连接，不写数据库
+    Group,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Mode::Private => "private",
+            Mode::Group => "group",
+        })
+    }
+}
+
+/// 压测参数
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub server: String,
+    pub clients: usize,
+    /// 所有客户端合计每秒发送的消息数
+    pub rate: f64,
+    /// 发送持续的时间
+    pub duration: Duration,
+    /// 停止发送后继续等待推送的时间，之后仍未收到的计为丢失
+    pub drain: Duration,
+    pub mode: Mode,
+    /// 长时间运行时每隔多久回调一次阶段统计，None 表示只在结束时统计
+    pub report_interval: Option<Duration>,
+}
+
+/// 压测结果；阶段统计中的 latency 只包含本阶段的样本
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub mode: Option<Mode>,
+    pub clients: usize,
+    pub elapsed: Duration,
+    pub sent: u64,
+    pub send_errors: u64,
+    /// 应当收到的推送数：私聊每条一次，群聊每条推送给全部连接
+    pub expected: u64,
+    pub received: u64,
+    pub duplicates: u64,
+    /// 中途断开的连接数
+    pub disconnected: u64,
+    pub latency: Histogram,
+}
+
+impl Report {
+    /// 没有收到的推送数（运行中的阶段统计包含尚在路上的消息）
+    pub fn dropped(&self) -> u64 {
+        self.expected.saturating_sub(self.received)
+    }
+
+    /// 实际的发送速率（条/秒）
+    pub fn send_rate(&self) -> f64 {
+        self.sent as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(mode) = self.mode {
+            writeln!(f, "模式 {}，{} 个连接，用时 {:.1?}", mode, self.clients, self.elapsed)?;
+        }
+        writeln!(f, "发送 {} 条（{:.1} 条/秒），发送失败 {}", self.sent, self.send_rate(), self.send_errors)?;
+        writeln!(
+            f,
+            "应收 {}，收到 {}，丢失 {}，重复 {}，断开 {}",
+            self.expected, self.received, self.dropped(), self.duplicates, self.disconnected,
+        )?;
+        write!(f, "延迟 {}", self.latency)
+    }
+}
+
+// 所有连接共享的计数，latency 只保存当前阶段的样本
+#[derive(Default)]
+struct Shared {
+    sent: u64,
+    send_errors: u64,
+    received: u64,
+    duplicates: u64,
+    disconnected: u64,
+    latency: Histogram,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Sending,
+    Draining,
+    Stopped,
+}
+
+// 发送目标
+enum Target {
+    User(String),
+    Group(String),
+}
+
+// 一个压测连接
+struct Peer {
+    index: usize,
+    user_id: String,
+    events: EventStream,
+}
+
+/// 按参数运行一次压测，每个统计阶段结束时以该阶段的结果调用 progress
+pub async fn run(options: &Options, mut progress: impl FnMut(&Report)) -> Result<Report> {
+    let run_id = unix_micros();
+    let client = ApiClient::new(options.server.clone());
+    let group_id = format!("{MARKER}-{run_id}");
+    let user_ids = match options.mode {
+        // 群聊广播不校验用户，省去注册
+        Mode::Group => (0..options.clients).map(|i| format!("{MARKER}-{run_id}-{i}")).collect(),
+        Mode::Private => register_users(&client, run_id, options.clients).await?,
+    };
+
+    let groups: Vec<&str> = match options.mode {
+        Mode::Group => vec![group_id.as_str()],
+        Mode::Private => Vec::new(),
+    };
+    let mut peers = Vec::with_capacity(options.clients);
+    for (index, user_id) in user_ids.iter().enumerate() {
+        let events = client.connect_events(user_id, &groups).await?;
+        peers.push(Peer { index, user_id: user_id.clone(), events });
+    }
+    // identify 在服务器的连接任务中异步处理
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let (phase_tx, phase_rx) = watch::channel(Phase::Sending);
+    // 每个连接的发送间隔，起始时间错开，使总速率均匀
+    let period = Duration::from_secs_f64(options.clients as f64 / options.rate);
+    let started = Instant::now();
+    let tasks: Vec<_> = peers.into_iter().map(|peer| {
+        let target = match options.mode {
+            Mode::Private => Target::User(user_ids[(peer.index + 1) % user_ids.len()].clone()),
+            Mode::Group => Target::Group(group_id.clone()),
+        };
+        let offset = period.mul_f64(peer.index as f64 / options.clients as f64);
+        tokio::spawn(drive(peer, target, started + offset, period, phase_rx.clone(), shared.clone()))
+    }).collect();
+
+    // 发送阶段：按间隔输出阶段统计
+    let deadline = tokio::time::Instant::from_std(started + options.duration);
+    let mut total = Histogram::default();
+    if let Some(interval) = options.report_interval {
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let report = snapshot(&shared, options, started, &mut total);
+                    progress(&report);
+                }
+                _ = tokio::time::sleep_until(deadline) => break,
+            }
+        }
+    } else {
+        tokio::time::sleep_until(deadline).await;
+    }
+    let _ = phase_tx.send(Phase::Draining);
+    let sending_time = started.elapsed();
+    tokio::time::sleep(options.drain).await;
+    let _ = phase_tx.send(Phase::Stopped);
+    for task in tasks {
+        let _ = task.await;
+    }
+
+    let mut report = snapshot(&shared, options, started, &mut total);
+    report.elapsed = sending_time;
+    report.latency = total;
+    Ok(report)
+}
+
+// 注册 count 个压测账号，返回用户ID
+async fn register_users(client: &ApiClient, run_id: u64, count: usize) -> Result<Vec<String>> {
+    futures_util::stream::iter(0..count)
+        .map(|i| async move { client.register(&format!("bench{run_id}n{i}"), PASSWORD, None).await })
+        .buffered(REGISTER_CONCURRENCY)
+        .try_collect()
+        .await
+}
+
+// 取出当前阶段的统计，并把本阶段的延迟样本并入 total
+fn snapshot(shared: &Mutex<Shared>, options: &Options, started: Instant, total: &mut Histogram) -> Report {
+    let mut shared = shared.lock().unwrap();
+    let latency = std::mem::take(&mut shared.latency);
+    total.merge(&latency);
+    let fan_out = match options.mode {
+        Mode::Private => 1,
+        Mode::Group => options.clients as u64,
+    };
+    Report {
+        mode: Some(options.mode),
+        clients: options.clients,
+        elapsed: started.elapsed(),
+        sent: shared.sent,
+        send_errors: shared.send_errors,
+        expected: shared.sent * fan_out,
+        received: shared.received,
+        duplicates: shared.duplicates,
+        disconnected: shared.disconnected,
+        latency,
+    }
+}
+
+// 单个连接的收发循环：发送阶段按间隔发送，直到停止前一直接收
+async fn drive(mut peer: Peer, target: Target, start: Instant, period: Duration, mut phase: watch::Receiver<Phase>, shared: Arc<Mutex<Shared>>) {
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::from_std(start), period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut sending = true;
+    let mut seq: u64 = 0;
+    // 每个发送者已收到的最大消息序号，用于识别重复推送（同一发送者的推送按顺序到达）
+    let mut last_seen: HashMap<usize, u64> = HashMap::new();
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if sending => {
+                seq += 1;
+                let content = format!("{MARKER} {} {} {}", peer.index, seq, unix_micros());
+                let frame = match &target {
+                    Target::User(receiver) => json!({ "type": "message", "sender_id": peer.user_id, "receiver_id": receiver, "content": content }),
+                    Target::Group(group) => json!({ "type": "group_chat", "group_id": group, "content": content }),
+                };
+                let ok = peer.events.send(&frame).await.is_ok();
+                let mut shared = shared.lock().unwrap();
+                if ok { shared.sent += 1 } else { shared.send_errors += 1 }
+            }
+            changed = phase.changed() => {
+                match changed.map(|_| *phase.borrow()) {
+                    Ok(Phase::Sending) => {}
+                    Ok(Phase::Draining) => sending = false,
+                    Ok(Phase::Stopped) | Err(_) => break,
+                }
+            }
+            event = peer.events.next() => {
+                let received_at = unix_micros();
+                let (content, message_id) = match event {
+                    Some(Ok(Event::Text(text))) => (text, None),
+                    Some(Ok(Event::Json(value))) if value["type"] == "message" => (
+                        value["content"].as_str().unwrap_or_default().to_string(),
+                        value["message_id"].as_str().map(str::to_string),
+                    ),
+                    Some(Ok(Event::Json(_))) => continue,
+                    Some(Err(_)) | None => {
+                        shared.lock().unwrap().disconnected += 1;
+                        break;
+                    }
+                };
+                let Some((sender, seq, sent_at)) = parse_marker(&content) else { continue };
+                // 私聊推送确认送达，避免发件箱重发
+                if let Some(message_id) = message_id {
+                    let _ = peer.events.ack_delivered(&[&message_id]).await;
+                }
+                let last = last_seen.entry(sender).or_default();
+                let mut shared = shared.lock().unwrap();
+                if seq <= *last {
+                    shared.duplicates += 1;
+                    continue;
+                }
+                *last = seq;
+                shared.received += 1;
+                shared.latency.record(received_at.saturating_sub(sent_at));
+            }
+        }
+    }
+    let _ = peer.events.close().await;
+}
+
+// 解析压测消息内容，返回 (发送者序号, 消息序号, 发送时间)
+fn parse_marker(content: &str) -> Option<(usize, u64, u64)> {
+    let mut parts = content.strip_prefix(MARKER)?.split_whitespace();
+    let sender = parts.next()?.parse().ok()?;
+    let seq = parts.next()?.parse().ok()?;
+    let sent_at = parts.next()?.parse().ok()?;
+    Some((sender, seq, sent_at))
+}
+
+fn unix_micros() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_micros() as u64
+}
@@ -0,0 +1,79 @@
+//! 月灵聊天的压力测试和长时间稳定性测试工具
+//!
+//! 打开 `--clients` 个 WebSocket 连接，合计每秒发送 `--rate` 条消息，持续 `--duration` 秒，
+//! 结束时输出延迟分位数和丢失的推送；`--report-every` 用于长时间运行时定期输出阶段统计
+
+use std::error::Error;
+use std::time::Duration;
+
+use clap::{Parser, ValueEnum};
+use yueling_bench::{Mode, Options, Report};
+
+/// 月灵聊天压力测试
+#[derive(Parser)]
+#[command(name = "yueling-bench", version, about)]
+struct Cli {
+    /// 服务器地址
+    #[arg(long, env = "YUELING_SERVER", default_value = "http://localhost:2025")]
+    server: String,
+    /// WebSocket 连接数
+    #[arg(short = 'n', long, default_value_t = 50, value_parser = clap::value_parser!(u32).range(1..))]
+    clients: u32,
+    /// 所有连接合计每秒发送的消息数
+    #[arg(short = 'r', long, default_value_t = 100.0)]
+    rate: f64,
+    /// 发送持续的秒数
+    #[arg(short = 'd', long, default_value_t = 30)]
+    duration: u64,
+    /// 停止发送后继续等待推送的秒数
+    #[arg(long, default_value_t = 2)]
+    drain: u64,
+    /// 消息路径：private 为私聊（写数据库、发件箱），group 为群聊广播
+    #[arg(long, value_enum, default_value_t = PathArg::Group)]
+    mode: PathArg,
+    /// 每隔多少秒输出一次阶段统计，用于长时间运行
+    #[arg(long)]
+    report_every: Option<u64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PathArg {
+    Private,
+    Group,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    if !(cli.rate.is_finite() && cli.rate > 0.0) {
+        return Err("--rate 必须大于 0".into());
+    }
+    let mode = match cli.mode {
+        PathArg::Private => Mode::Private,
+        PathArg::Group => Mode::Group,
+    };
+    if mode == Mode::Private && cli.clients < 2 {
+        return Err("私聊模式至少需要 2 个连接".into());
+    }
+    let options = Options {
+        server: cli.server,
+        clients: cli.clients as usize,
+        rate: cli.rate,
+        duration: Duration::from_secs(cli.duration),
+        drain: Duration::from_secs(cli.drain),
+        mode,
+        report_interval: cli.report_every.filter(|secs| *secs > 0).map(Duration::from_secs),
+    };
+
+    let report = yueling_bench::run(&options, print_progress).await?;
+    println!("{report}");
+    Ok(())
+}
+
+// 阶段统计输出到标准错误，标准输出只留最终结果
+fn print_progress(report: &Report) {
+    eprintln!(
+        "[{:>6.0?}] 发送 {}，收到 {}，未到 {}，断开 {}，本阶段 {}",
+        report.elapsed, report.sent, report.received, report.dropped(), report.disconnected, report.latency,
+    );
+}
@@ -0,0 +1,73 @@
+//! 延迟统计：按三位有效数字分桶的直方图，长时间运行时内存占用也不会随消息数增长
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// 延迟直方图，单位为微秒
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    buckets: BTreeMap<u64, u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Histogram {
+    pub fn record(&mut self, micros: u64) {
+        *self.buckets.entry(bucket(micros)).or_default() += 1;
+        self.count += 1;
+        self.max = self.max.max(micros);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (bucket, count) in &other.buckets {
+            *self.buckets.entry(*bucket).or_default() += count;
+        }
+        self.count += other.count;
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max)
+    }
+
+    /// 第 p 百分位（0 到 100）的延迟，没有样本时为 0
+    pub fn percentile(&self, p: f64) -> Duration {
+        let rank = ((p / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count.max(1));
+        let mut seen = 0;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                return Duration::from_micros((*bucket).min(self.max));
+            }
+        }
+        Duration::ZERO
+    }
+}
+
+// 保留三位有效数字，向上取整，误差不超过 1%
+fn bucket(micros: u64) -> u64 {
+    let mut scale = 1;
+    while micros / scale >= 1000 {
+        scale *= 10;
+    }
+    micros.div_ceil(scale) * scale
+}
+
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p50 {:?}  p90 {:?}  p99 {:?}  p99.9 {:?}  max {:?}",
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.percentile(99.9),
+            self.max(),
+        )
+    }
+}
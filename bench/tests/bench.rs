@@ -0,0 +1,54 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use server::{router, settings::Settings, AppState, DbPool};
+use yueling_bench::{Mode, Options, Report};
+
+// 在本地随机端口上启动使用内存数据库的服务器
+async fn start_server() -> String {
+    let state = AppState::new(DbPool::in_memory().unwrap(), Settings::default());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = router(state).into_make_service_with_connect_info::<SocketAddr>();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+fn options(server: String, mode: Mode, clients: usize) -> Options {
+    Options {
+        server,
+        clients,
+        rate: 20.0,
+        duration: Duration::from_secs(1),
+        drain: Duration::from_millis(500),
+        mode,
+        report_interval: Some(Duration::from_millis(400)),
+    }
+}
+
+#[tokio::test]
+async fn group_broadcast_reaches_every_connection() {
+    let server = start_server().await;
+    let mut stages = Vec::new();
+    let report = yueling_bench::run(&options(server, Mode::Group, 4), |stage: &Report| stages.push(stage.clone())).await.unwrap();
+
+    assert!((15..=25).contains(&report.sent), "{report}");
+    assert_eq!((report.expected, report.received, report.dropped()), (report.sent * 4, report.sent * 4, 0), "{report}");
+    assert_eq!((report.send_errors, report.duplicates, report.disconnected), (0, 0, 0));
+    // 最终的延迟统计包含各阶段的全部样本
+    assert_eq!(report.latency.count(), report.received);
+    assert!(report.latency.percentile(50.0) <= report.latency.percentile(99.0));
+    assert!(report.latency.percentile(99.0) <= report.latency.max());
+    assert_eq!(stages.len(), 2);
+    assert!(stages.iter().map(|stage| stage.latency.count()).sum::<u64>() < report.received);
+}
+
+#[tokio::test]
+async fn private_messages_are_pushed_and_acknowledged() {
+    let server = start_server().await;
+    let report = yueling_bench::run(&options(server, Mode::Private, 3), |_: &Report| {}).await.unwrap();
+
+    assert!(report.sent > 0);
+    assert_eq!((report.expected, report.received, report.duplicates), (report.sent, report.sent, 0), "{report}");
+    assert_eq!(report.latency.count(), report.sent);
+}
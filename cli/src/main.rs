@@ -0,0 +1,241 @@
+//! 月灵聊天的命令行客户端
+//!
+//! `login` 把会话令牌保存到本地会话文件，之后的 `send`、`listen`、`history`、`chat` 都以该用户身份调用服务器；
+//! `listen` 把推送逐行输出为 JSON，便于脚本和无界面机器人处理
+
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use clap::{Parser, Subcommand};
+use futures_util::{StreamExt, TryStreamExt};
+use serde_json::json;
+use yueling_client::{ApiClient, ClientError, Event, Message, SystemEvent};
+
+mod session;
+mod tui;
+
+use session::SavedSession;
+
+// 未指定服务器也没有登录过时使用的地址
+const DEFAULT_SERVER: &str = "http://localhost:2025";
+
+/// 月灵聊天命令行客户端
+#[derive(Parser)]
+#[command(name = "yueling-cli", version, about)]
+struct Cli {
+    /// 服务器地址，省略时使用登录时保存的地址
+    #[arg(long, global = true, env = "YUELING_SERVER")]
+    server: Option<String>,
+    /// 会话文件，默认 ~/.yueling-cli.json
+    #[arg(long, global = true, env = "YUELING_SESSION")]
+    session: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 登录并把会话令牌保存到会话文件
+    Login {
+        username: String,
+        /// 密码，省略时从标准输入读取一行
+        #[arg(long, env = "YUELING_PASSWORD", hide_env_values = true)]
+        password: Option<String>,
+        /// 新设备验证：上次登录返回的设备ID
+        #[arg(long, requires = "device_code")]
+        device_id: Option<String>,
+        /// 新设备验证：邮件中的验证码
+        #[arg(long, requires = "device_id")]
+        device_code: Option<String>,
+    },
+    /// 注销会话并删除会话文件
+    Logout,
+    /// 发送一条消息
+    Send {
+        /// 接收者的用户ID（或 --group 时的群ID）
+        peer: String,
+        content: String,
+        /// 发送到群聊
+        #[arg(long)]
+        group: bool,
+    },
+    /// 连接 WebSocket，把收到的推送逐行输出为 JSON（自动订阅已有会话的群聊）
+    Listen,
+    /// 查看与 peer 的最近消息，从早到晚输出
+    History {
+        /// 私聊对象的用户ID或群ID
+        peer: String,
+        /// 最多输出多少条
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+        /// 每条消息输出为一行 JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// 与 peer 聊天的终端界面（Enter 发送，Esc 退出）
+    Chat {
+        /// 私聊对象的用户ID（或 --group 时的群ID）
+        peer: String,
+        /// 在群聊中聊天
+        #[arg(long)]
+        group: bool,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let session_path = cli.session.unwrap_or_else(session::default_path);
+
+    match cli.command {
+        Command::Login { username, password, device_id, device_code } => {
+            let server = cli.server.unwrap_or_else(|| DEFAULT_SERVER.to_string());
+            let password = match password {
+                Some(password) => password,
+                None => read_password()?,
+            };
+            let mut client = ApiClient::new(server.clone());
+            let result = match (device_id, device_code) {
+                (Some(device_id), Some(device_code)) => {
+                    client.login_with_device_code(&username, &password, &device_id, &device_code).await
+                }
+                _ => client.login(&username, &password).await,
+            };
+            let logged_in = match result {
+                Ok(session) => session,
+                Err(ClientError::DeviceVerificationRequired { device_id }) => {
+                    return Err(format!("新设备需要验证：请带上 --device-id {} --device-code <邮件中的验证码> 重新登录", device_id).into());
+                }
+                Err(e) => return Err(e.into()),
+            };
+            session::save(&session_path, &SavedSession {
+                server,
+                user_id: logged_in.user_id.clone(),
+                username: logged_in.username,
+                token: logged_in.token,
+            })?;
+            println!("{}", logged_in.user_id);
+        }
+        Command::Logout => {
+            let (mut client, _) = connect(cli.server, &session_path)?;
+            client.logout().await?;
+            std::fs::remove_file(&session_path)?;
+        }
+        Command::Send { peer, content, group } => {
+            let (client, saved) = connect(cli.server, &session_path)?;
+            let message_type = if group { "group" } else { "private" };
+            println!("{}", client.send_message(&saved.user_id, &peer, &content, message_type).await?);
+        }
+        Command::Listen => {
+            let (client, saved) = connect(cli.server, &session_path)?;
+            let groups = group_ids(&client).await?;
+            let groups: Vec<&str> = groups.iter().map(String::as_str).collect();
+            let mut events = client.connect_events(&saved.user_id, &groups).await?;
+            let mut stdout = std::io::stdout();
+            while let Some(event) = events.next().await {
+                // 群聊广播是纯文本，包成 JSON 保证每行都能解析
+                let line = match event? {
+                    Event::Json(value) => value,
+                    Event::Text(text) => json!({ "type": "text", "content": text }),
+                };
+                writeln!(stdout, "{}", line)?;
+                stdout.flush()?;
+            }
+        }
+        Command::History { peer, limit, json } => {
+            let (client, saved) = connect(cli.server, &session_path)?;
+            let mut messages: Vec<Message> = client.message_history(&saved.user_id, &peer)
+                .take(limit)
+                .try_collect()
+                .await?;
+            messages.reverse();
+            for message in &messages {
+                if json {
+                    println!("{}", serde_json::to_string(message)?);
+                } else {
+                    println!("{}", format_message(message));
+                }
+            }
+        }
+        Command::Chat { peer, group } => {
+            let (client, saved) = connect(cli.server, &session_path)?;
+            tui::run(&client, &saved.user_id, &peer, group).await?;
+        }
+    }
+    Ok(())
+}
+
+// 读取会话文件，构造带会话令牌的客户端；--server 优先于登录时保存的地址
+fn connect(server: Option<String>, session_path: &Path) -> Result<(ApiClient, SavedSession), Box<dyn Error>> {
+    let saved = session::load(session_path)?;
+    let mut client = ApiClient::new(server.unwrap_or_else(|| saved.server.clone()));
+    client.set_token(Some(saved.token.clone()));
+    Ok((client, saved))
+}
+
+// 当前用户有消息往来的群聊
+async fn group_ids(client: &ApiClient) -> Result<Vec<String>, ClientError> {
+    client.conversations()
+        .try_filter(|c| std::future::ready(c.conversation_type == "group"))
+        .map_ok(|c| c.peer_id)
+        .try_collect()
+        .await
+}
+
+fn read_password() -> std::io::Result<String> {
+    eprint!("密码: ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// 一行消息："[时间] 发送者: 内容"，系统消息显示为 "[时间] * 事件描述"
+pub(crate) fn format_message(message: &Message) -> String {
+    let time = format_time(message.created_at);
+    match message.system_event() {
+        Some(event) => format!("[{}] * {}", time, describe_system_event(&event)),
+        None => format!("[{}] {}: {}", time, message.sender_id, message.content),
+    }
+}
+
+pub(crate) fn describe_system_event(event: &SystemEvent) -> String {
+    match event {
+        SystemEvent::MemberJoined { user_id } => format!("{} 加入了群聊", user_id),
+        SystemEvent::NameChanged { old_name, new_name, .. } => format!("{} 改名为 {}", old_name, new_name),
+        SystemEvent::MessagePinned { message_id, pinned_by } => format!("{} 置顶了消息 {}", pinned_by, message_id),
+        SystemEvent::CallStarted { started_by, .. } => format!("{} 发起了通话", started_by),
+        SystemEvent::OwnerChanged { old_owner, new_owner } => format!("{} 把群主转让给了 {}", old_owner, new_owner),
+        SystemEvent::GroupDeleted { deleted_by } => format!("{} 删除了群聊", deleted_by),
+        SystemEvent::NewDeviceLogin { device_name, platform, .. } => {
+            format!("账号在新设备（{}）上登录", device_label(device_name, platform))
+        }
+        SystemEvent::DuplicateLogin { device_name, platform, replaced_old: true, .. } => {
+            format!("账号在设备（{}）上重新连接，原有的连接已断开", device_label(device_name, platform))
+        }
+        SystemEvent::DuplicateLogin { device_name, platform, replaced_old: false, .. } => {
+            format!("设备（{}）已经在线，新的连接已被拒绝", device_label(device_name, platform))
+        }
+    }
+}
+
+fn device_label<'a>(name: &'a str, platform: &'a str) -> &'a str {
+    [name, platform].into_iter().find(|label| !label.is_empty()).unwrap_or("未知设备")
+}
+
+/// Unix 时间戳格式化为 UTC 的 "YYYY-MM-DD HH:MM"
+pub(crate) fn format_time(secs: i64) -> String {
+    let (days, secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // 由 1970-01-01 起的天数推算公历日期（以 3 月为一年的开始，闰日落在年末）
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02} {:02}:{:02}", year, month, day, secs / 3_600, secs % 3_600 / 60)
+}
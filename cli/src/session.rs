@@ -0,0 +1,39 @@
+//! 登录后保存在本地的会话，之后的子命令从这里读取服务器地址、用户ID和会话令牌
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// 会话文件的内容
+#[derive(Serialize, Deserialize)]
+pub struct SavedSession {
+    pub server: String,
+    pub user_id: String,
+    pub username: String,
+    pub token: String,
+}
+
+/// 默认的会话文件：$HOME/.yueling-cli.json，没有 HOME 时放在当前目录
+pub fn default_path() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(".yueling-cli.json")
+}
+
+pub fn load(path: &Path) -> Result<SavedSession, Box<dyn std::error::Error>> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("读取会话文件 {} 失败（请先执行 login）: {}", path.display(), e))?;
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// 写入会话文件；令牌等同于密码，Unix 上只允许所有者读写
+pub fn save(path: &Path, session: &SavedSession) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write(path, serde_json::to_string_pretty(session)?)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,171 @@
+//! 终端聊天界面：上方是与对方的消息，下方是输入框
+//!
+//! 私聊消息经 WebSocket 发送（服务器保存后实时推送给对方）；群消息先经 HTTP 保存，
+//! 再在群聊广播中发出内容，自己的消息由广播回显
+
+use std::error::Error;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures_util::{StreamExt, TryStreamExt};
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::json;
+use tokio::sync::mpsc;
+use yueling_client::{ApiClient, Event, EventStream, Message};
+
+use crate::{describe_system_event, format_message, format_time};
+
+// 打开界面时加载的历史条数
+const HISTORY_SIZE: usize = 50;
+
+struct Chat<'a> {
+    client: &'a ApiClient,
+    user_id: &'a str,
+    peer: &'a str,
+    group: bool,
+    lines: Vec<String>,
+    input: String,
+}
+
+pub async fn run(client: &ApiClient, user_id: &str, peer: &str, group: bool) -> Result<(), Box<dyn Error>> {
+    let mut history: Vec<Message> = client.message_history(user_id, peer)
+        .take(HISTORY_SIZE)
+        .try_collect()
+        .await?;
+    history.reverse();
+    let groups = if group { vec![peer] } else { Vec::new() };
+    let events = client.connect_events(user_id, &groups).await?;
+
+    // crossterm 读取按键会阻塞，放在单独的线程里
+    let (key_tx, key_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = event::read() {
+            if key_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut chat = Chat {
+        client,
+        user_id,
+        peer,
+        group,
+        lines: history.iter().map(format_message).collect(),
+        input: String::new(),
+    };
+    let mut terminal = ratatui::init();
+    let result = chat.event_loop(&mut terminal, events, key_rx).await;
+    ratatui::restore();
+    result
+}
+
+fn now() -> String {
+    format_time(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or_default())
+}
+
+impl Chat<'_> {
+    async fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        mut events: EventStream,
+        mut keys: mpsc::UnboundedReceiver<TermEvent>,
+    ) -> Result<(), Box<dyn Error>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(event) => {
+                        let event = event?;
+                        // 确认收到私聊推送，服务器不再重发
+                        if let Event::Json(value) = &event
+                            && value["type"] == "message"
+                            && let Some(message_id) = value["message_id"].as_str()
+                        {
+                            events.ack_delivered(&[message_id]).await?;
+                        }
+                        self.on_event(event);
+                    }
+                    None => return Err("连接已断开".into()),
+                },
+                key = keys.recv() => {
+                    let Some(TermEvent::Key(key)) = key else {
+                        // 窗口大小变化等：下一轮重绘即可
+                        continue;
+                    };
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    match key.code {
+                        KeyCode::Esc => return Ok(()),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                        KeyCode::Enter if !self.input.trim().is_empty() => {
+                            let content = std::mem::take(&mut self.input);
+                            self.send(&mut events, &content).await?;
+                        }
+                        KeyCode::Backspace => {
+                            self.input.pop();
+                        }
+                        KeyCode::Char(c) => self.input.push(c),
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    async fn send(&mut self, events: &mut EventStream, content: &str) -> Result<(), Box<dyn Error>> {
+        if self.group {
+            self.client.send_message(self.user_id, self.peer, content, "group").await?;
+            events.send(&json!({ "type": "group_chat", "group_id": self.peer, "content": content })).await?;
+        } else {
+            events.send(&json!({
+                "type": "message",
+                "sender_id": self.user_id,
+                "receiver_id": self.peer,
+                "content": content,
+            })).await?;
+            self.lines.push(format!("[{}] {}: {}", now(), self.user_id, content));
+        }
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: Event) {
+        let line = match event {
+            Event::Text(text) if self.group => format!("[{}] {}", now(), text),
+            Event::Json(value) if value["type"] == "message" && value["sender_id"] == self.peer => {
+                format!("[{}] {}: {}", now(), self.peer, value["content"].as_str().unwrap_or_default())
+            }
+            Event::Json(value) if value["type"] == "system_message" && value["receiver_id"] == self.peer => {
+                match serde_json::from_value(value["event"].clone()) {
+                    Ok(event) => format!("[{}] * {}", now(), describe_system_event(&event)),
+                    Err(_) => format!("[{}] ({})", now(), value),
+                }
+            }
+            // 其他会话的消息和通知只显示一行摘要
+            Event::Json(value) => format!("[{}] ({})", now(), value),
+            Event::Text(text) => format!("[{}] ({})", now(), text),
+        };
+        self.lines.push(line);
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [messages_area, input_area] = Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+        // 只显示放得下的最后几行
+        let visible = messages_area.height.saturating_sub(2) as usize;
+        let lines: Vec<Line> = self.lines[self.lines.len().saturating_sub(visible)..]
+            .iter()
+            .map(|line| Line::from(line.as_str()))
+            .collect();
+        let title = if self.group { format!(" 群聊 {} ", self.peer) } else { format!(" 与 {} 的私聊 ", self.peer) };
+        frame.render_widget(Paragraph::new(lines).block(Block::bordered().title(title)), messages_area);
+
+        let input = Line::from(self.input.as_str());
+        let cursor_x = input_area.x + 1 + input.width() as u16;
+        frame.render_widget(Paragraph::new(input).block(Block::bordered().title(" Enter 发送，Esc 退出 ")), input_area);
+        frame.set_cursor_position((cursor_x, input_area.y + 1));
+    }
+}
@@ -0,0 +1,86 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread::JoinHandle;
+
+use serde_json::{json, Value};
+
+// 按顺序应答固定几次请求的模拟服务器，结束后返回收到的请求行和 JSON 请求体
+fn mock_server(responses: Vec<Value>) -> (String, JoinHandle<Vec<(String, Value)>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = std::thread::spawn(move || {
+        responses.into_iter().map(|response| {
+            let (socket, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(socket);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let response = response.to_string();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                response.len(),
+                response,
+            ).unwrap();
+            (request_line, serde_json::from_slice(&body).unwrap_or(Value::Null))
+        }).collect()
+    });
+    (url, handle)
+}
+
+fn cli(session: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_yueling-cli"))
+        .env("YUELING_SESSION", session)
+        .env_remove("YUELING_SERVER")
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn login_saves_the_session_used_by_later_commands() {
+    let session = std::env::temp_dir().join(format!("yueling-cli-{}.json", std::process::id()));
+    let (url, server) = mock_server(vec![
+        json!({ "success": true, "message": "登录成功", "user_id": "u1", "username": "alice", "token": "t1", "device_id": null }),
+        json!({ "success": true, "message": "消息发送成功", "message_id": "m1" }),
+    ]);
+
+    let output = cli(&session, &["login", "alice", "--password", "secret", "--server", &url]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "u1");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&session).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+
+    // 之后的命令使用保存的服务器地址和用户ID
+    let output = cli(&session, &["send", "u2", "你好"]);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "m1");
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].0.starts_with("POST /login "));
+    assert!(requests[1].0.starts_with("POST /send-message "));
+    assert_eq!(requests[1].1["sender_id"], "u1");
+    assert_eq!(requests[1].1["content"], "你好");
+    std::fs::remove_file(&session).unwrap();
+
+    // 没有会话文件时提示先登录
+    let output = cli(&session, &["history", "u2"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("login"));
+}
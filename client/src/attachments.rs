@@ -0,0 +1,128 @@
+//! 附件上传和下载（仅原生平台，需要读写本地文件）
+//!
+//! 上传以 multipart 流式发送，不把整个文件读进内存；下载时目标文件已存在则用 Range 从已有长度继续。
+//! 附件上传后内容不会改变，续传不需要校验 ETag
+
+use std::io::ErrorKind;
+use std::path::Path;
+
+use futures_util::stream;
+use reqwest::{header, multipart, Body, Method, Response, StatusCode};
+use serde::Deserialize;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::client::{api_error, ApiClient};
+use crate::error::Result;
+use crate::types::{Attachment, Progress};
+
+// 上传时每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Content-Range: bytes 0-99/200 或 bytes */200 中的总长度
+fn content_range_total(response: &Response) -> Option<u64> {
+    response.headers().get(header::CONTENT_RANGE)?
+        .to_str().ok()?
+        .rsplit_once('/')?.1
+        .parse().ok()
+}
+
+impl ApiClient {
+    /// 上传本地文件作为附件，文件名取路径的最后一段
+    pub async fn upload_attachment(&self, path: impl AsRef<Path>) -> Result<Attachment> {
+        self.upload_attachment_with_progress(path, |_| {}).await
+    }
+
+    /// 上传附件，每读出一块交给连接时调用一次 on_progress
+    pub async fn upload_attachment_with_progress(
+        &self,
+        path: impl AsRef<Path>,
+        on_progress: impl FnMut(Progress) + Send + 'static,
+    ) -> Result<Attachment> {
+        #[derive(Deserialize)]
+        struct UploadBody {
+            attachment: Attachment,
+        }
+        let path = path.as_ref();
+        let file = File::open(path).await?;
+        let total = file.metadata().await?.len();
+        let filename = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+
+        let chunks = stream::try_unfold((file, 0u64, on_progress), move |(mut file, transferred, mut on_progress)| async move {
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                return Ok::<_, std::io::Error>(None);
+            }
+            buf.truncate(n);
+            let transferred = transferred + n as u64;
+            on_progress(Progress { transferred, total });
+            Ok(Some((buf, (file, transferred, on_progress))))
+        });
+        let part = multipart::Part::stream_with_length(Body::wrap_stream(chunks), total).file_name(filename);
+        let form = multipart::Form::new().part("file", part);
+        let (_, body): (_, UploadBody) = Self::send(self.authorized(Method::POST, "/attachments").multipart(form)).await?;
+        Ok(body.attachment)
+    }
+
+    /// 下载附件到 dest，返回文件总字节数；dest 已存在时从其长度处续传
+    pub async fn download_attachment(&self, id: &str, dest: impl AsRef<Path>) -> Result<u64> {
+        self.download_attachment_with_progress(id, dest, |_| {}).await
+    }
+
+    /// 下载附件，每写入一块调用一次 on_progress
+    pub async fn download_attachment_with_progress(
+        &self,
+        id: &str,
+        dest: impl AsRef<Path>,
+        mut on_progress: impl FnMut(Progress),
+    ) -> Result<u64> {
+        let dest = dest.as_ref();
+        let mut offset = match tokio::fs::metadata(dest).await {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == ErrorKind::NotFound => 0,
+            Err(e) => return Err(e.into()),
+        };
+        let path = format!("/attachments/{}", id);
+
+        let response = loop {
+            let mut request = self.authorized(Method::GET, &path);
+            if offset > 0 {
+                request = request.header(header::RANGE, format!("bytes={}-", offset));
+            }
+            let response = request.send().await?;
+            if response.status() == StatusCode::RANGE_NOT_SATISFIABLE && offset > 0 {
+                // 已经下载完整
+                if content_range_total(&response) == Some(offset) {
+                    on_progress(Progress { transferred: offset, total: offset });
+                    return Ok(offset);
+                }
+                // 本地文件比附件还长，不是同一个文件，从头下载
+                offset = 0;
+                continue;
+            }
+            break response;
+        };
+
+        let (mut file, mut transferred, total) = match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let total = content_range_total(&response)
+                    .unwrap_or(offset + response.content_length().unwrap_or_default());
+                (OpenOptions::new().append(true).open(dest).await?, offset, total)
+            }
+            status if status.is_success() => {
+                let total = response.content_length().unwrap_or_default();
+                (File::create(dest).await?, 0, total)
+            }
+            _ => return Err(api_error(response).await),
+        };
+        let mut response = response;
+        while let Some(chunk) = response.chunk().await? {
+            file.write_all(&chunk).await?;
+            transferred += chunk.len() as u64;
+            on_progress(Progress { transferred, total });
+        }
+        file.flush().await?;
+        Ok(transferred)
+    }
+}
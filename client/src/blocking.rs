@@ -0,0 +1,291 @@
+//! 同步客户端：在内部的单线程 tokio 运行时上执行 `ApiClient` 的请求
+//!
+//! 不能在 async 上下文（已有运行时的线程）中调用，否则 tokio 会 panic；async 代码请直接使用 `ApiClient`
+
+use std::path::Path;
+use std::pin::Pin;
+
+use futures_util::{Stream, StreamExt};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::client::ApiClient;
+use crate::error::Result;
+use crate::types::{Attachment, ContactMatch, Conversation, CustomEmoji, DisplayNameChange, GroupDeletionToken, GroupJoinRequest, GroupListing, GroupMember, IceServers, Invite, JoinResult, Message, MessageAck, MessageReceipts, Page, Presence, Profile, Progress, RelayInfo, SeqRange, ServerInfo, ServerTime, Session, SyncResult, Translation};
+
+/// 自动翻页的同步迭代器，每取一项可能发出一次请求
+pub struct PageIter<'a, T> {
+    runtime: &'a Runtime,
+    stream: Pin<Box<dyn Stream<Item = Result<T>> + 'a>>,
+}
+
+impl<T> Iterator for PageIter<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+/// `ApiClient` 的同步版本
+pub struct BlockingClient {
+    inner: ApiClient,
+    runtime: Runtime,
+}
+
+impl BlockingClient {
+    /// base_url 如 `http://localhost:2025`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::from_async(ApiClient::new(base_url))
+    }
+
+    /// 包装已配置好的异步客户端
+    pub fn from_async(inner: ApiClient) -> Self {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("创建 tokio 运行时失败");
+        Self { inner, runtime }
+    }
+
+    /// 取出内部的异步客户端（共享连接池和会话令牌）
+    pub fn as_async(&self) -> &ApiClient {
+        &self.inner
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.inner.token()
+    }
+
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.inner.set_token(token);
+    }
+
+    #[cfg(feature = "cache")]
+    pub fn set_cache(&mut self, cache: Option<std::sync::Arc<crate::cache::MessageCache>>) {
+        self.inner.set_cache(cache);
+    }
+
+    pub fn register(&self, username: &str, password: &str, email: Option<&str>) -> Result<String> {
+        self.runtime.block_on(self.inner.register(username, password, email))
+    }
+
+    pub fn register_with_invite(&self, username: &str, password: &str, email: Option<&str>, invite_code: &str) -> Result<String> {
+        self.runtime.block_on(self.inner.register_with_invite(username, password, email, invite_code))
+    }
+
+    pub fn create_invite(&self, expires_in_secs: Option<i64>) -> Result<Invite> {
+        self.runtime.block_on(self.inner.create_invite(expires_in_secs))
+    }
+
+    pub fn login(&mut self, username: &str, password: &str) -> Result<Session> {
+        self.runtime.block_on(self.inner.login(username, password))
+    }
+
+    pub fn login_with_device_code(&mut self, username: &str, password: &str, device_id: &str, device_code: &str) -> Result<Session> {
+        self.runtime.block_on(self.inner.login_with_device_code(username, password, device_id, device_code))
+    }
+
+    pub fn logout(&mut self) -> Result<()> {
+        self.runtime.block_on(self.inner.logout())
+    }
+
+    pub fn send_message(&self, sender_id: &str, receiver_id: &str, content: &str, message_type: &str) -> Result<String> {
+        self.runtime.block_on(self.inner.send_message(sender_id, receiver_id, content, message_type))
+    }
+
+    pub fn send_markdown_message(&self, sender_id: &str, receiver_id: &str, content: &str, message_type: &str) -> Result<String> {
+        self.runtime.block_on(self.inner.send_markdown_message(sender_id, receiver_id, content, message_type))
+    }
+
+    pub fn send_sticker(&self, sender_id: &str, receiver_id: &str, name: &str, message_type: &str) -> Result<String> {
+        self.runtime.block_on(self.inner.send_sticker(sender_id, receiver_id, name, message_type))
+    }
+
+    pub fn custom_emoji(&self) -> Result<Vec<CustomEmoji>> {
+        self.runtime.block_on(self.inner.custom_emoji())
+    }
+
+    pub fn send_message_with_client_id(
+        &self,
+        sender_id: &str,
+        receiver_id: &str,
+        content: &str,
+        message_type: &str,
+        client_message_id: &str,
+    ) -> Result<MessageAck> {
+        self.runtime.block_on(self.inner.send_message_with_client_id(sender_id, receiver_id, content, message_type, client_message_id))
+    }
+
+    pub fn server_time(&self) -> Result<ServerTime> {
+        self.runtime.block_on(self.inner.server_time())
+    }
+
+    pub fn server_info(&self) -> Result<ServerInfo> {
+        self.runtime.block_on(self.inner.server_info())
+    }
+
+    pub fn presence(&self, user_id: &str) -> Result<Presence> {
+        self.runtime.block_on(self.inner.presence(user_id))
+    }
+
+    pub fn update_display_name(&self, user_id: &str, display_name: Option<&str>) -> Result<Profile> {
+        self.runtime.block_on(self.inner.update_display_name(user_id, display_name))
+    }
+
+    pub fn display_name_history(&self, user_id: &str) -> Result<Vec<DisplayNameChange>> {
+        self.runtime.block_on(self.inner.display_name_history(user_id))
+    }
+
+    #[cfg(feature = "crypto")]
+    pub fn send_encrypted_message(
+        &self,
+        crypto: &crate::crypto::ClientCrypto,
+        sender_id: &str,
+        receiver_id: &str,
+        content: &str,
+        message_type: &str,
+    ) -> Result<String> {
+        self.runtime.block_on(self.inner.send_encrypted_message(crypto, sender_id, receiver_id, content, message_type))
+    }
+
+    pub fn unread_messages(&self, user_id: &str) -> Result<Vec<Message>> {
+        self.runtime.block_on(self.inner.unread_messages(user_id))
+    }
+
+    pub fn mark_delivered(&self, message_ids: &[&str]) -> Result<()> {
+        self.runtime.block_on(self.inner.mark_delivered(message_ids))
+    }
+
+    pub fn mark_read(&self, message_ids: &[&str]) -> Result<()> {
+        self.runtime.block_on(self.inner.mark_read(message_ids))
+    }
+
+    pub fn translate_message(&self, message_id: &str, lang: &str) -> Result<Translation> {
+        self.runtime.block_on(self.inner.translate_message(message_id, lang))
+    }
+
+    pub fn message_receipts(&self, message_id: &str, limit: Option<i64>) -> Result<MessageReceipts> {
+        self.runtime.block_on(self.inner.message_receipts(message_id, limit))
+    }
+
+    pub fn ice_servers(&self) -> Result<IceServers> {
+        self.runtime.block_on(self.inner.ice_servers())
+    }
+
+    pub fn create_relay(&self, peer_id: &str) -> Result<RelayInfo> {
+        self.runtime.block_on(self.inner.create_relay(peer_id))
+    }
+
+    pub fn sync_messages(&self, user_id: &str, last_sync_time: i64, limit: i64) -> Result<SyncResult> {
+        self.runtime.block_on(self.inner.sync_messages(user_id, last_sync_time, limit))
+    }
+
+    pub fn conversation_history(&self, user_id: &str, peer_id: &str, before: Option<i64>, limit: i64) -> Result<Vec<Message>> {
+        self.runtime.block_on(self.inner.conversation_history(user_id, peer_id, before, limit))
+    }
+
+    pub fn messages_from_seq(&self, peer_id: &str, from_seq: i64, limit: i64) -> Result<SeqRange> {
+        self.runtime.block_on(self.inner.messages_from_seq(peer_id, from_seq, limit))
+    }
+
+    pub fn message_history_page(&self, user_id: &str, peer_id: &str, cursor: Option<&str>, limit: i64) -> Result<Page<Message>> {
+        self.runtime.block_on(self.inner.message_history_page(user_id, peer_id, cursor, limit))
+    }
+
+    pub fn message_history(&self, user_id: &str, peer_id: &str) -> PageIter<'_, Message> {
+        self.iter(self.inner.message_history(user_id, peer_id))
+    }
+
+    pub fn conversations_page(&self, cursor: Option<&str>, limit: i64) -> Result<Page<Conversation>> {
+        self.runtime.block_on(self.inner.conversations_page(cursor, limit))
+    }
+
+    pub fn conversations(&self) -> PageIter<'_, Conversation> {
+        self.iter(self.inner.conversations())
+    }
+
+    pub fn lookup_contacts(&self, usernames: &[&str], emails: &[&str]) -> Result<Vec<ContactMatch>> {
+        self.runtime.block_on(self.inner.lookup_contacts(usernames, emails))
+    }
+
+    pub fn add_group_member(&self, group_id: &str, user_id: &str) -> Result<bool> {
+        self.runtime.block_on(self.inner.add_group_member(group_id, user_id))
+    }
+
+    pub fn transfer_group_owner(&self, group_id: &str, user_id: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.transfer_group_owner(group_id, user_id))
+    }
+
+    pub fn request_group_deletion(&self, group_id: &str) -> Result<GroupDeletionToken> {
+        self.runtime.block_on(self.inner.request_group_deletion(group_id))
+    }
+
+    pub fn delete_group(&self, group_id: &str, confirmation_token: &str) -> Result<Option<i64>> {
+        self.runtime.block_on(self.inner.delete_group(group_id, confirmation_token))
+    }
+
+    pub fn mute_group_member(&self, group_id: &str, user_id: &str, duration_secs: i64) -> Result<i64> {
+        self.runtime.block_on(self.inner.mute_group_member(group_id, user_id, duration_secs))
+    }
+
+    pub fn unmute_group_member(&self, group_id: &str, user_id: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.unmute_group_member(group_id, user_id))
+    }
+
+    pub fn set_group_visibility(&self, group_id: &str, public: bool, join_approval: bool) -> Result<()> {
+        self.runtime.block_on(self.inner.set_group_visibility(group_id, public, join_approval))
+    }
+
+    pub fn join_group(&self, group_id: &str) -> Result<JoinResult> {
+        self.runtime.block_on(self.inner.join_group(group_id))
+    }
+
+    pub fn group_join_requests(&self, group_id: &str) -> Result<Vec<GroupJoinRequest>> {
+        self.runtime.block_on(self.inner.group_join_requests(group_id))
+    }
+
+    pub fn resolve_join_request(&self, group_id: &str, user_id: &str, approve: bool) -> Result<()> {
+        self.runtime.block_on(self.inner.resolve_join_request(group_id, user_id, approve))
+    }
+
+    pub fn group_members_page(&self, group_id: &str, cursor: Option<&str>, limit: i64) -> Result<Page<GroupMember>> {
+        self.runtime.block_on(self.inner.group_members_page(group_id, cursor, limit))
+    }
+
+    pub fn search_group_members_page(&self, group_id: &str, query: &str, cursor: Option<&str>, limit: i64) -> Result<Page<GroupMember>> {
+        self.runtime.block_on(self.inner.search_group_members_page(group_id, query, cursor, limit))
+    }
+
+    pub fn discover_groups_page(&self, query: &str, cursor: Option<&str>, limit: i64) -> Result<Page<GroupListing>> {
+        self.runtime.block_on(self.inner.discover_groups_page(query, cursor, limit))
+    }
+
+    pub fn group_members(&self, group_id: &str) -> PageIter<'_, GroupMember> {
+        self.iter(self.inner.group_members(group_id))
+    }
+
+    fn iter<'a, T>(&'a self, stream: impl Stream<Item = Result<T>> + 'a) -> PageIter<'a, T> {
+        PageIter { runtime: &self.runtime, stream: Box::pin(stream) }
+    }
+
+    #[cfg(feature = "cache")]
+    pub fn sync_cache(&self) -> Result<usize> {
+        self.runtime.block_on(self.inner.sync_cache())
+    }
+
+    pub fn upload_attachment(&self, path: impl AsRef<Path>) -> Result<Attachment> {
+        self.runtime.block_on(self.inner.upload_attachment(path))
+    }
+
+    pub fn upload_attachment_with_progress(&self, path: impl AsRef<Path>, on_progress: impl FnMut(Progress) + Send + 'static) -> Result<Attachment> {
+        self.runtime.block_on(self.inner.upload_attachment_with_progress(path, on_progress))
+    }
+
+    pub fn download_attachment(&self, id: &str, dest: impl AsRef<Path>) -> Result<u64> {
+        self.runtime.block_on(self.inner.download_attachment(id, dest))
+    }
+
+    pub fn download_attachment_with_progress(&self, id: &str, dest: impl AsRef<Path>, on_progress: impl FnMut(Progress)) -> Result<u64> {
+        self.runtime.block_on(self.inner.download_attachment_with_progress(id, dest, on_progress))
+    }
+}
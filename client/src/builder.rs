@@ -0,0 +1,205 @@
+//! 连接自建服务器的选项：自定义 CA、证书指纹固定、代理和超时（仅原生平台，浏览器中由浏览器负责 TLS 和代理）
+//!
+//! 配置了 CA 或指纹时，HTTP 请求和 WebSocket 连接使用同一份 rustls 配置：
+//! - 只配置 CA：只信任这些 CA 签发的证书（不再信任系统证书），照常校验主机名；
+//! - 只配置指纹：服务器证书的 SHA-256 指纹必须与其中之一相同，适合自签名证书，不校验证书链和主机名；
+//! - 两者都配置：先按 CA 校验，再要求指纹相符
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+
+use crate::client::ApiClient;
+use crate::error::{ClientError, Result};
+
+fn config_error(e: impl std::fmt::Display) -> ClientError {
+    ClientError::Config(e.to_string())
+}
+
+// 十六进制的 SHA-256 指纹，允许 openssl 输出的冒号分隔格式
+fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32]> {
+    let hex: String = fingerprint.chars().filter(|c| *c != ':').collect();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return Err(ClientError::Config(format!("证书指纹应为 64 位十六进制 SHA-256: {}", fingerprint)));
+    }
+    let mut pin = [0u8; 32];
+    for (i, byte) in pin.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| ClientError::Config(format!("证书指纹应为 64 位十六进制 SHA-256: {}", fingerprint)))?;
+    }
+    Ok(pin)
+}
+
+// 按指纹固定服务器证书，配置了 CA 时先校验证书链
+#[derive(Debug)]
+struct PinnedVerifier {
+    pins: Vec<[u8; 32]>,
+    roots: Option<Arc<WebPkiServerVerifier>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        if let Some(roots) = &self.roots {
+            roots.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        }
+        let fingerprint: [u8; 32] = Sha256::digest(end_entity.as_ref()).into();
+        if self.pins.contains(&fingerprint) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(rustls::Error::General("服务器证书与固定的指纹不符".into()))
+        }
+    }
+
+    // 握手签名照常校验，确认对方持有证书的私钥
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.provider.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// `ApiClient` 的构建器，错误在 `build` 时统一返回
+pub struct ApiClientBuilder {
+    base_url: String,
+    ca_pems: Vec<Vec<u8>>,
+    ca_files: Vec<PathBuf>,
+    pins: Vec<String>,
+    proxy: Option<String>,
+    timeout: Option<Duration>,
+}
+
+impl ApiClientBuilder {
+    /// 信任 PEM 格式的 CA 证书（可以包含多张）
+    pub fn ca_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.ca_pems.push(pem.into());
+        self
+    }
+
+    /// 信任 PEM 文件中的 CA 证书
+    pub fn ca_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_files.push(path.into());
+        self
+    }
+
+    /// 固定服务器证书的 SHA-256 指纹（十六进制，可带冒号），可以调用多次以便换证书时过渡
+    pub fn pin_sha256(mut self, fingerprint: impl Into<String>) -> Self {
+        self.pins.push(fingerprint.into());
+        self
+    }
+
+    /// 所有 HTTP 请求经过代理，如 `http://proxy:3128`（WebSocket 连接不经过代理）
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// 单个 HTTP 请求的超时时间
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<ApiClient> {
+        let tls = self.tls_config()?.map(Arc::new);
+        let mut http = reqwest::Client::builder();
+        if let Some(tls) = &tls {
+            http = http.tls_backend_preconfigured(ClientConfig::clone(tls));
+        }
+        if let Some(proxy) = &self.proxy {
+            http = http.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+        let mut client = ApiClient::with_http_client(self.base_url, http.build()?);
+        client.tls = tls;
+        Ok(client)
+    }
+
+    // 没有配置 CA 和指纹时返回 None，使用 reqwest 和 tungstenite 默认的证书校验
+    fn tls_config(&self) -> Result<Option<ClientConfig>> {
+        let mut pems = self.ca_pems.clone();
+        for path in &self.ca_files {
+            pems.push(std::fs::read(path)?);
+        }
+        if pems.is_empty() && self.pins.is_empty() {
+            return Ok(None);
+        }
+
+        let provider = Arc::new(rustls::crypto::aws_lc_rs::default_provider());
+        let roots = if pems.is_empty() {
+            None
+        } else {
+            let mut store = RootCertStore::empty();
+            for pem in &pems {
+                for cert in CertificateDer::pem_slice_iter(pem) {
+                    store.add(cert.map_err(config_error)?).map_err(config_error)?;
+                }
+            }
+            if store.is_empty() {
+                return Err(ClientError::Config("CA 文件中没有证书".into()));
+            }
+            Some(WebPkiServerVerifier::builder_with_provider(Arc::new(store), provider.clone()).build().map_err(config_error)?)
+        };
+
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(config_error)?;
+        let config = match roots {
+            Some(roots) if self.pins.is_empty() => builder.with_webpki_verifier(roots).with_no_client_auth(),
+            roots => {
+                let pins = self.pins.iter().map(|pin| parse_fingerprint(pin)).collect::<Result<_>>()?;
+                builder.dangerous()
+                    .with_custom_certificate_verifier(Arc::new(PinnedVerifier { pins, roots, provider }))
+                    .with_no_client_auth()
+            }
+        };
+        Ok(Some(config))
+    }
+}
+
+impl ApiClient {
+    /// 需要自定义 CA、证书固定、代理或超时时使用构建器
+    pub fn builder(base_url: impl Into<String>) -> ApiClientBuilder {
+        ApiClientBuilder {
+            base_url: base_url.into(),
+            ca_pems: Vec::new(),
+            ca_files: Vec::new(),
+            pins: Vec::new(),
+            proxy: None,
+            timeout: None,
+        }
+    }
+}
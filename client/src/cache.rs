@@ -0,0 +1,338 @@
+//! 本地消息缓存（`cache` 特性，仅原生平台）
+//!
+//! 一个缓存文件只属于一个用户，保存收到的消息和会话列表。消息ID是 UUIDv7、按时间有序，
+//! 缓存以它为序号去重，重复同步或历史与同步重叠时不会出现两条相同的消息，也不会重复累计未读数。
+//! `ApiClient::sync_cache` 从上次同步的位置增量拉取；连不上服务器时，历史消息和会话列表的分页接口改为从缓存读取
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::client::ApiClient;
+use crate::error::{ClientError, Result};
+use crate::types::{Conversation, Message, Page, Tombstone};
+
+// 增量同步每批请求的条数
+const SYNC_BATCH_SIZE: i64 = 200;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS meta (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        id TEXT PRIMARY KEY,
+        peer_id TEXT NOT NULL,
+        sender_id TEXT NOT NULL,
+        receiver_id TEXT NOT NULL,
+        content TEXT NOT NULL,
+        message_type TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        is_read INTEGER NOT NULL,
+        workspace_id TEXT NOT NULL,
+        sent_at INTEGER,
+        delivered_at INTEGER,
+        read_at INTEGER,
+        seq INTEGER,
+        entities TEXT
+    );
+    CREATE INDEX IF NOT EXISTS idx_messages_peer ON messages(peer_id, created_at DESC, id DESC);
+    CREATE TABLE IF NOT EXISTS conversations (
+        peer_id TEXT PRIMARY KEY,
+        conversation_type TEXT NOT NULL,
+        last_message_at INTEGER NOT NULL,
+        unread_count INTEGER NOT NULL
+    );";
+
+const MESSAGE_COLUMNS: &str = "id, sender_id, receiver_id, content, message_type, created_at, status, is_read, workspace_id, sent_at, delivered_at, read_at, seq, entities";
+
+// 缓存文件格式的版本（PRAGMA user_version）；版本 1 给消息加上服务器记录的三个时间，版本 2 加上会话内序号，
+// 版本 3 加上格式区间
+const SCHEMA_VERSION: i64 = 3;
+
+fn cache_error(e: rusqlite::Error) -> ClientError {
+    ClientError::Cache(e.to_string())
+}
+
+// 格式区间按 JSON 保存，纯文本消息为 NULL
+fn entities_json(message: &Message) -> Option<String> {
+    (!message.entities.is_empty()).then(|| serde_json::to_string(&message.entities).expect("格式区间可以序列化"))
+}
+
+fn message_from_row(row: &Row) -> rusqlite::Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        sender_id: row.get(1)?,
+        receiver_id: row.get(2)?,
+        content: row.get(3)?,
+        message_type: row.get(4)?,
+        created_at: row.get(5)?,
+        status: row.get(6)?,
+        is_read: row.get(7)?,
+        workspace_id: row.get(8)?,
+        sent_at: row.get(9)?,
+        delivered_at: row.get(10)?,
+        read_at: row.get(11)?,
+        seq: row.get(12)?,
+        entities: row.get::<_, Option<String>>(13)?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default(),
+    })
+}
+
+// 与服务器相同的游标格式 "时间戳:ID"，缓存按同样的键倒序分页，在线和离线取到的游标可以互相接续
+fn decode_cursor(cursor: Option<&str>) -> Result<(i64, String)> {
+    let Some(cursor) = cursor else {
+        return Ok((i64::MAX, String::new()));
+    };
+    cursor.split_once(':')
+        .and_then(|(at, id)| Some((at.parse().ok()?, id.to_string())))
+        .ok_or_else(|| ClientError::Cache(format!("分页游标无效: {}", cursor)))
+}
+
+fn page<T>(items: Vec<T>, limit: i64, key: impl Fn(&T) -> (i64, &str)) -> Page<T> {
+    let next_cursor = (items.len() as i64 == limit)
+        .then(|| items.last().map(|item| {
+            let (at, id) = key(item);
+            format!("{}:{}", at, id)
+        }))
+        .flatten();
+    Page { items, next_cursor }
+}
+
+/// 一个用户的本地消息缓存，可以在多个 `ApiClient` 之间共享
+pub struct MessageCache {
+    conn: Mutex<Connection>,
+    user_id: String,
+}
+
+impl MessageCache {
+    /// 打开（或创建）user_id 的缓存文件；文件属于其他用户时返回错误
+    pub fn open(path: impl AsRef<Path>, user_id: &str) -> Result<Self> {
+        Self::init(Connection::open(path).map_err(cache_error)?, user_id)
+    }
+
+    /// 只保存在内存中的缓存，进程退出后丢失
+    pub fn open_in_memory(user_id: &str) -> Result<Self> {
+        Self::init(Connection::open_in_memory().map_err(cache_error)?, user_id)
+    }
+
+    fn init(conn: Connection, user_id: &str) -> Result<Self> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0)).map_err(cache_error)?;
+        let legacy: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'messages')", [], |row| row.get(0))
+            .map_err(cache_error)?;
+        // 旧版本创建的缓存文件：补上新增的列，已有消息的时间在下次取到时更新
+        if legacy && version < 1 {
+            conn.execute_batch(
+                "ALTER TABLE messages ADD COLUMN sent_at INTEGER;
+                 ALTER TABLE messages ADD COLUMN delivered_at INTEGER;
+                 ALTER TABLE messages ADD COLUMN read_at INTEGER;",
+            ).map_err(cache_error)?;
+        }
+        if legacy && version < 2 {
+            conn.execute_batch("ALTER TABLE messages ADD COLUMN seq INTEGER;").map_err(cache_error)?;
+        }
+        if legacy && version < 3 {
+            conn.execute_batch("ALTER TABLE messages ADD COLUMN entities TEXT;").map_err(cache_error)?;
+        }
+        conn.execute_batch(SCHEMA).map_err(cache_error)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION).map_err(cache_error)?;
+        conn.execute("INSERT OR IGNORE INTO meta (key, value) VALUES ('user_id', ?1)", params![user_id])
+            .map_err(cache_error)?;
+        let owner: String = conn.query_row("SELECT value FROM meta WHERE key = 'user_id'", [], |row| row.get(0))
+            .map_err(cache_error)?;
+        if owner != user_id {
+            return Err(ClientError::Cache(format!("缓存属于用户 {}", owner)));
+        }
+        Ok(Self { conn: Mutex::new(conn), user_id: user_id.to_string() })
+    }
+
+    /// 缓存所属的用户
+    pub fn user_id(&self) -> &str {
+        &self.user_id
+    }
+
+    /// 上次增量同步到的时间戳，从未同步时为 0
+    pub fn last_sync_time(&self) -> Result<i64> {
+        let conn = self.conn.lock().unwrap();
+        let value: Option<String> = conn.query_row("SELECT value FROM meta WHERE key = 'last_sync_time'", [], |row| row.get(0))
+            .optional()
+            .map_err(cache_error)?;
+        Ok(value.and_then(|value| value.parse().ok()).unwrap_or(0))
+    }
+
+    fn set_last_sync_time(&self, at: i64) -> Result<()> {
+        self.conn.lock().unwrap()
+            .execute("INSERT OR REPLACE INTO meta (key, value) VALUES ('last_sync_time', ?1)", params![at.to_string()])
+            .map_err(cache_error)?;
+        Ok(())
+    }
+
+    /// 保存消息，返回其中新消息的条数；已缓存的消息只更新状态和内容（编辑、已读）
+    pub fn insert_messages(&self, messages: &[Message]) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(cache_error)?;
+        let mut added = 0;
+        for message in messages {
+            let exists = tx.query_row("SELECT 1 FROM messages WHERE id = ?1", params![message.id], |_| Ok(()))
+                .optional()
+                .map_err(cache_error)?
+                .is_some();
+            if exists {
+                tx.execute(
+                    "UPDATE messages SET content = ?2, status = ?3, is_read = ?4,
+                         sent_at = ?5, delivered_at = ?6, read_at = ?7, seq = COALESCE(?8, seq), entities = ?9 WHERE id = ?1",
+                    params![
+                        message.id, message.content, message.status, message.is_read,
+                        message.sent_at, message.delivered_at, message.read_at, message.seq, entities_json(message)
+                    ],
+                ).map_err(cache_error)?;
+                continue;
+            }
+
+            // 群消息和群内系统消息的会话对象是群，私聊是对方
+            let peer_id = if message.message_type != "private" || message.sender_id == self.user_id {
+                &message.receiver_id
+            } else {
+                &message.sender_id
+            };
+            tx.execute(
+                &format!("INSERT INTO messages (peer_id, {}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)", MESSAGE_COLUMNS),
+                params![
+                    peer_id, message.id, message.sender_id, message.receiver_id, message.content,
+                    message.message_type, message.created_at, message.status, message.is_read, message.workspace_id,
+                    message.sent_at, message.delivered_at, message.read_at, message.seq, entities_json(message)
+                ],
+            ).map_err(cache_error)?;
+            let conversation_type = if message.message_type == "private" { "private" } else { "group" };
+            let unread = message.message_type == "private" && message.receiver_id == self.user_id && !message.is_read;
+            tx.execute(
+                "INSERT INTO conversations (peer_id, conversation_type, last_message_at, unread_count) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(peer_id) DO UPDATE SET
+                     last_message_at = MAX(last_message_at, excluded.last_message_at),
+                     unread_count = unread_count + excluded.unread_count",
+                params![peer_id, conversation_type, message.created_at, unread as i64],
+            ).map_err(cache_error)?;
+            added += 1;
+        }
+        tx.commit().map_err(cache_error)?;
+        Ok(added)
+    }
+
+    /// 删除服务器上已删除的消息
+    pub fn apply_tombstones(&self, tombstones: &[Tombstone]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(cache_error)?;
+        for tombstone in tombstones {
+            tx.execute("DELETE FROM messages WHERE id = ?1", params![tombstone.message_id]).map_err(cache_error)?;
+        }
+        tx.commit().map_err(cache_error)
+    }
+
+    /// 用服务器返回的会话覆盖本地记录（未读数以服务器为准）
+    pub fn save_conversations(&self, conversations: &[Conversation]) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction().map_err(cache_error)?;
+        for c in conversations {
+            tx.execute(
+                "INSERT OR REPLACE INTO conversations (peer_id, conversation_type, last_message_at, unread_count) VALUES (?1, ?2, ?3, ?4)",
+                params![c.peer_id, c.conversation_type, c.last_message_at, c.unread_count],
+            ).map_err(cache_error)?;
+        }
+        tx.commit().map_err(cache_error)
+    }
+
+    /// 缓存中与 peer_id 的一页历史消息，按时间倒序；游标与服务器的格式相同
+    pub fn history_page(&self, peer_id: &str, cursor: Option<&str>, limit: i64) -> Result<Page<Message>> {
+        let (before, before_id) = decode_cursor(cursor)?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM messages
+             WHERE peer_id = ?1 AND (created_at < ?2 OR (created_at = ?2 AND id < ?3))
+             ORDER BY created_at DESC, id DESC LIMIT ?4",
+            MESSAGE_COLUMNS,
+        )).map_err(cache_error)?;
+        let messages = stmt.query_map(params![peer_id, before, before_id, limit], message_from_row)
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(cache_error)?;
+        Ok(page(messages, limit, |m| (m.created_at, &m.id)))
+    }
+
+    /// 缓存中的一页会话，按最后一条消息时间倒序
+    pub fn conversations_page(&self, cursor: Option<&str>, limit: i64) -> Result<Page<Conversation>> {
+        let (before, before_id) = decode_cursor(cursor)?;
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT peer_id, conversation_type, last_message_at, unread_count FROM conversations
+             WHERE last_message_at < ?1 OR (last_message_at = ?1 AND peer_id < ?2)
+             ORDER BY last_message_at DESC, peer_id DESC LIMIT ?3",
+        ).map_err(cache_error)?;
+        let conversations = stmt.query_map(params![before, before_id, limit], |row| Ok(Conversation {
+            peer_id: row.get(0)?,
+            conversation_type: row.get(1)?,
+            last_message_at: row.get(2)?,
+            unread_count: row.get(3)?,
+        }))
+            .and_then(|rows| rows.collect::<rusqlite::Result<Vec<_>>>())
+            .map_err(cache_error)?;
+        Ok(page(conversations, limit, |c| (c.last_message_at, &c.peer_id)))
+    }
+}
+
+impl ApiClient {
+    /// 设置本地缓存；之后取到的历史消息和会话写入缓存，离线时从缓存读取
+    pub fn set_cache(&mut self, cache: Option<Arc<MessageCache>>) {
+        self.cache = cache;
+    }
+
+    /// 当前使用的本地缓存
+    pub fn cache(&self) -> Option<&Arc<MessageCache>> {
+        self.cache.as_ref()
+    }
+
+    /// 从上次同步的位置拉取新消息和删除记录写入缓存，直到追上服务器，返回新消息的条数；
+    /// 重新连上服务器（包括 WebSocket 重连）后调用即可补齐离线期间的消息
+    pub async fn sync_cache(&self) -> Result<usize> {
+        let cache = self.cache.as_ref().ok_or_else(|| ClientError::Cache("未设置本地缓存".into()))?;
+        let mut since = cache.last_sync_time()?;
+        // 同步按秒比较时间戳，往回多取一秒，与上次同一秒到达的消息不会漏掉，重复的由缓存去重
+        let mut overlap = true;
+        let mut added = 0;
+        loop {
+            let from = if overlap { (since - 1).max(0) } else { since };
+            let result = self.sync_messages(cache.user_id(), from, SYNC_BATCH_SIZE).await?;
+            let new = cache.insert_messages(&result.messages)?;
+            cache.apply_tombstones(&result.tombstones)?;
+            since = since.max(result.last_sync_time);
+            cache.set_last_sync_time(since)?;
+            added += new;
+            if (result.messages.len() as i64) < SYNC_BATCH_SIZE {
+                return Ok(added);
+            }
+            // 整页都是已缓存的消息时不再重叠，否则会反复取到同一页
+            overlap = new > 0;
+        }
+    }
+
+    // 请求成功时把结果写入缓存；连不上服务器时改为从缓存读取
+    pub(crate) fn through_cache<T>(
+        &self,
+        result: Result<T>,
+        store: impl FnOnce(&MessageCache, &T) -> Result<()>,
+        offline: impl FnOnce(&MessageCache) -> Result<T>,
+    ) -> Result<T> {
+        let Some(cache) = &self.cache else {
+            return result;
+        };
+        match result {
+            Ok(value) => {
+                store(cache, &value)?;
+                Ok(value)
+            }
+            Err(e) if e.is_offline() => offline(cache),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,451 @@
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::error::{ClientError, Result};
+use crate::types::{ContactMatch, CustomEmoji, DisplayNameChange, GroupDeletionToken, GroupJoinRequest, IceServers, Invite, JoinResult, Message, MessageAck, MessageReceipts, Presence, Profile, RelayInfo, SeqRange, ServerInfo, ServerTime, Session, SyncResult, Translation};
+
+/// 月灵 HTTP 接口客户端，克隆开销很小（共享连接池）
+#[derive(Clone)]
+pub struct ApiClient {
+    http: reqwest::Client,
+    pub(crate) base_url: String,
+    token: Option<String>,
+    // 自定义 CA 或证书固定时的 TLS 配置，WebSocket 连接也使用它
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) tls: Option<std::sync::Arc<rustls::ClientConfig>>,
+    #[cfg(feature = "cache")]
+    pub(crate) cache: Option<std::sync::Arc<crate::cache::MessageCache>>,
+}
+
+// 错误响应体
+#[derive(Deserialize)]
+struct ErrorBody {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    muted_until: Option<i64>,
+}
+
+// 把错误响应转换为 ClientError::Api（带禁言结束时间的转换为 ClientError::Muted），响应体不是 JSON 时 code 和 message 为空
+pub(crate) async fn api_error(response: Response) -> ClientError {
+    let status = response.status().as_u16();
+    let body: ErrorBody = response.json().await.unwrap_or(ErrorBody { code: String::new(), message: String::new(), muted_until: None });
+    match body.muted_until {
+        Some(muted_until) => ClientError::Muted { muted_until, message: body.message },
+        None => ClientError::Api { status, code: body.code, message: body.message },
+    }
+}
+
+// 登录响应体
+#[derive(Deserialize)]
+struct LoginBody {
+    user_id: Option<String>,
+    username: Option<String>,
+    token: Option<String>,
+    device_id: Option<String>,
+    #[serde(default)]
+    device_verification_required: bool,
+}
+
+// 登录请求中的设备信息
+#[derive(Serialize, Default)]
+struct LoginRequest<'a> {
+    username: &'a str,
+    password: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_id: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_code: Option<&'a str>,
+}
+
+impl ApiClient {
+    /// base_url 如 `http://localhost:2025`
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// 使用自行配置的 reqwest 客户端（需要自定义 CA 或证书固定时请用 `builder`，WebSocket 连接也会使用同样的配置）
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            tls: None,
+            #[cfg(feature = "cache")]
+            cache: None,
+        }
+    }
+
+    /// 当前会话令牌
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    /// 直接设置会话令牌（例如从本地恢复登录状态）
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    // 带上会话令牌的请求
+    pub(crate) fn authorized(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    // 发送请求，非 2xx 响应转换为 ClientError::Api
+    pub(crate) async fn send<T: DeserializeOwned>(request: RequestBuilder) -> Result<(StatusCode, T)> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(api_error(response).await);
+        }
+        Ok((status, response.json().await?))
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: Method, path: &str, body: Option<&Value>) -> Result<(StatusCode, T)> {
+        let mut request = self.authorized(method, path);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        Self::send(request).await
+    }
+
+    pub(crate) async fn post<T: DeserializeOwned>(&self, path: &str, body: Value) -> Result<T> {
+        Ok(self.request(Method::POST, path, Some(&body)).await?.1)
+    }
+
+    /// 注册新用户，返回用户ID；email 为 None 时使用占位邮箱
+    pub async fn register(&self, username: &str, password: &str, email: Option<&str>) -> Result<String> {
+        let body: Value = self.post("/register", json!({
+            "username": username,
+            "password": password,
+            "email": email.unwrap_or_default(),
+        })).await?;
+        Ok(body["user_id"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// 持邀请码注册（服务器关闭了开放注册时），返回用户ID
+    pub async fn register_with_invite(&self, username: &str, password: &str, email: Option<&str>, invite_code: &str) -> Result<String> {
+        let body: Value = self.post("/register", json!({
+            "username": username,
+            "password": password,
+            "email": email.unwrap_or_default(),
+            "invite_code": invite_code,
+        })).await?;
+        Ok(body["user_id"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// 以当前用户的名额签发注册邀请码，expires_in_secs 为 None 时使用服务器默认的有效期（7 天）
+    pub async fn create_invite(&self, expires_in_secs: Option<i64>) -> Result<Invite> {
+        self.post("/invites", json!({ "expires_in_secs": expires_in_secs })).await
+    }
+
+    /// 登录并保存会话令牌
+    pub async fn login(&mut self, username: &str, password: &str) -> Result<Session> {
+        self.login_request(LoginRequest { username, password, ..Default::default() }).await
+    }
+
+    /// 新设备验证：带上 device_id 和邮件中的验证码登录
+    pub async fn login_with_device_code(&mut self, username: &str, password: &str, device_id: &str, device_code: &str) -> Result<Session> {
+        self.login_request(LoginRequest { username, password, device_id: Some(device_id), device_code: Some(device_code) }).await
+    }
+
+    async fn login_request(&mut self, request: LoginRequest<'_>) -> Result<Session> {
+        let body = serde_json::to_value(&request).expect("登录请求可以序列化");
+        let (status, login): (_, LoginBody) = self.request(Method::POST, "/login", Some(&body)).await?;
+        if status == StatusCode::ACCEPTED || login.device_verification_required {
+            return Err(ClientError::DeviceVerificationRequired { device_id: login.device_id.unwrap_or_default() });
+        }
+        let session = Session {
+            user_id: login.user_id.unwrap_or_default(),
+            username: login.username.unwrap_or_default(),
+            token: login.token.unwrap_or_default(),
+            device_id: login.device_id,
+        };
+        self.token = Some(session.token.clone());
+        Ok(session)
+    }
+
+    /// 退出登录并清除本地令牌
+    pub async fn logout(&mut self) -> Result<()> {
+        let _: Value = self.post("/logout", json!({})).await?;
+        self.token = None;
+        Ok(())
+    }
+
+    /// 发送消息，message_type 为 "private" 或 "group"，返回消息ID
+    pub async fn send_message(&self, sender_id: &str, receiver_id: &str, content: &str, message_type: &str) -> Result<String> {
+        let body: Value = self.post("/send-message", json!({
+            "sender_id": sender_id,
+            "receiver_id": receiver_id,
+            "content": content,
+            "message_type": message_type,
+        })).await?;
+        Ok(body["message_id"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// 按 markdown 子集（`**粗体**`、`*斜体*`、`` `代码` ``、`[文字](链接)`）发送消息，返回消息ID；
+    /// 服务器保存去掉标记的纯文本和格式区间，链接不是 http、https 或 mailto 时拒绝
+    pub async fn send_markdown_message(&self, sender_id: &str, receiver_id: &str, content: &str, message_type: &str) -> Result<String> {
+        let body: Value = self.post("/send-message", json!({
+            "sender_id": sender_id,
+            "receiver_id": receiver_id,
+            "content": content,
+            "message_type": message_type,
+            "format": "markdown",
+        })).await?;
+        Ok(body["message_id"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// 发送贴纸，name 为工作区自定义表情的名称，返回消息ID
+    pub async fn send_sticker(&self, sender_id: &str, receiver_id: &str, name: &str, message_type: &str) -> Result<String> {
+        let body: Value = self.post("/send-message", json!({
+            "sender_id": sender_id,
+            "receiver_id": receiver_id,
+            "content": name,
+            "message_type": message_type,
+            "format": "sticker",
+        })).await?;
+        Ok(body["message_id"].as_str().unwrap_or_default().to_string())
+    }
+
+    /// 当前工作区的自定义表情（默认工作区的也在 server_info 中）
+    pub async fn custom_emoji(&self) -> Result<Vec<CustomEmoji>> {
+        #[derive(Deserialize)]
+        struct Body {
+            emoji: Vec<CustomEmoji>,
+        }
+        let body: Body = self.request(Method::GET, "/emoji", None).await?.1;
+        Ok(body.emoji)
+    }
+
+    /// 带客户端临时ID发送消息（最长 64 个字符，同一发送者内唯一），没收到确认时可以用同一ID安全地重发
+    pub async fn send_message_with_client_id(
+        &self,
+        sender_id: &str,
+        receiver_id: &str,
+        content: &str,
+        message_type: &str,
+        client_message_id: &str,
+    ) -> Result<MessageAck> {
+        self.post("/send-message", json!({
+            "sender_id": sender_id,
+            "receiver_id": receiver_id,
+            "content": content,
+            "message_type": message_type,
+            "client_message_id": client_message_id,
+        })).await
+    }
+
+    /// 读取服务器时钟：记下请求前后的本机毫秒时间 t0、t1，本机时钟偏差约为 `server_time_ms - (t0 + t1) / 2`
+    pub async fn server_time(&self) -> Result<ServerTime> {
+        Ok(self.request(Method::GET, "/time", None).await?.1)
+    }
+
+    /// 服务器版本、协议版本和本实例开启的功能，不需要登录
+    pub async fn server_info(&self) -> Result<ServerInfo> {
+        Ok(self.request(Method::GET, "/server-info", None).await?.1)
+    }
+
+    /// 用户的在线状态，包括是否处于免打扰时段
+    pub async fn presence(&self, user_id: &str) -> Result<Presence> {
+        Ok(self.request(Method::GET, &format!("/user/{}/presence", user_id), None).await?.1)
+    }
+
+    /// 修改当前用户的显示名称，None 清除；冷却中返回 429（`user.display_name_cooldown`）
+    pub async fn update_display_name(&self, user_id: &str, display_name: Option<&str>) -> Result<Profile> {
+        let body = json!({ "display_name": display_name });
+        Ok(self.request(Method::PATCH, &format!("/user/{}/profile", user_id), Some(&body)).await?.1)
+    }
+
+    /// 用户显示名称的修改记录，最近的在前
+    pub async fn display_name_history(&self, user_id: &str) -> Result<Vec<DisplayNameChange>> {
+        #[derive(Deserialize)]
+        struct Body {
+            history: Vec<DisplayNameChange>,
+        }
+        let body: Body = self.request(Method::GET, &format!("/user/{}/display-names", user_id), None).await?.1;
+        Ok(body.history)
+    }
+
+    /// 通讯录匹配（需要会话令牌）：返回其中已注册、且隐私设置允许被找到的用户。
+    /// 邮箱在本地计算哈希后再上传，服务器收不到原始邮箱；一次最多 1000 个
+    pub async fn lookup_contacts(&self, usernames: &[&str], emails: &[&str]) -> Result<Vec<ContactMatch>> {
+        #[derive(Deserialize)]
+        struct Body {
+            users: Vec<ContactMatch>,
+        }
+        let hashes: Vec<String> = emails.iter().map(|email| email_hash(email)).collect();
+        let body: Body = self.post("/users/lookup", json!({
+            "usernames": usernames,
+            "email_hashes": hashes,
+        })).await?;
+        // 邮箱的匹配结果换回调用方传入的邮箱
+        Ok(body.users.into_iter().map(|mut user| {
+            if let Some(index) = hashes.iter().position(|hash| *hash == user.handle) {
+                user.handle = emails[index].to_string();
+            }
+            user
+        }).collect())
+    }
+
+    /// 把同一工作区的用户加入群聊（当前用户须是群成员），返回是否新加入；对方的隐私设置不允许时返回 403
+    pub async fn add_group_member(&self, group_id: &str, user_id: &str) -> Result<bool> {
+        let body: Value = self.post(&format!("/groups/{}/members", group_id), json!({ "user_id": user_id })).await?;
+        Ok(body["joined"].as_bool().unwrap_or_default())
+    }
+
+    /// 把群主转让给其他群成员（当前用户须是群主）
+    pub async fn transfer_group_owner(&self, group_id: &str, user_id: &str) -> Result<()> {
+        let body = json!({ "user_id": user_id });
+        let _: Value = self.request(Method::PUT, &format!("/groups/{}/owner", group_id), Some(&body)).await?.1;
+        Ok(())
+    }
+
+    /// 申请删除群聊的确认令牌，有效期 5 分钟
+    pub async fn request_group_deletion(&self, group_id: &str) -> Result<GroupDeletionToken> {
+        self.post(&format!("/groups/{}/deletion-token", group_id), json!({})).await
+    }
+
+    /// 带确认令牌删除群聊，返回消息彻底清除的时间（服务器不清除已删除数据时为 None）
+    pub async fn delete_group(&self, group_id: &str, confirmation_token: &str) -> Result<Option<i64>> {
+        let body = json!({ "confirmation_token": confirmation_token });
+        let body: Value = self.request(Method::DELETE, &format!("/groups/{}", group_id), Some(&body)).await?.1;
+        Ok(body["purge_at"].as_i64())
+    }
+
+    /// 禁言群成员 duration_secs 秒（当前用户须是群主），返回禁言结束时间
+    pub async fn mute_group_member(&self, group_id: &str, user_id: &str, duration_secs: i64) -> Result<i64> {
+        let body = json!({ "duration_secs": duration_secs });
+        let body: Value = self.request(Method::PUT, &format!("/groups/{}/members/{}/mute", group_id, user_id), Some(&body)).await?.1;
+        Ok(body["muted_until"].as_i64().unwrap_or_default())
+    }
+
+    /// 解除群成员的禁言（当前用户须是群主）
+    pub async fn unmute_group_member(&self, group_id: &str, user_id: &str) -> Result<()> {
+        let _: Value = self.request(Method::DELETE, &format!("/groups/{}/members/{}/mute", group_id, user_id), None).await?.1;
+        Ok(())
+    }
+
+    /// 设置群是否出现在群目录中，以及加入是否需要群主批准（当前用户须是群主）
+    pub async fn set_group_visibility(&self, group_id: &str, public: bool, join_approval: bool) -> Result<()> {
+        let body = json!({ "visibility": if public { "public" } else { "private" }, "join_approval": join_approval });
+        let _: Value = self.request(Method::PUT, &format!("/groups/{}/visibility", group_id), Some(&body)).await?.1;
+        Ok(())
+    }
+
+    /// 加入公开群；需要审批的群返回 pending，群主批准后收到 group_join_resolved 事件
+    pub async fn join_group(&self, group_id: &str) -> Result<JoinResult> {
+        self.post(&format!("/groups/{}/join", group_id), json!({})).await
+    }
+
+    /// 群的待批准加群申请（当前用户须是群主）
+    pub async fn group_join_requests(&self, group_id: &str) -> Result<Vec<GroupJoinRequest>> {
+        #[derive(Deserialize)]
+        struct Body {
+            requests: Vec<GroupJoinRequest>,
+        }
+        let body: Body = self.request(Method::GET, &format!("/groups/{}/join-requests", group_id), None).await?.1;
+        Ok(body.requests)
+    }
+
+    /// 批准或拒绝加群申请（当前用户须是群主）
+    pub async fn resolve_join_request(&self, group_id: &str, user_id: &str, approve: bool) -> Result<()> {
+        let _: Value = self.post(&format!("/groups/{}/join-requests/{}", group_id, user_id), json!({ "approve": approve })).await?;
+        Ok(())
+    }
+
+    /// 获取未读消息
+    pub async fn unread_messages(&self, user_id: &str) -> Result<Vec<Message>> {
+        #[derive(Deserialize)]
+        struct Body {
+            messages: Vec<Message>,
+        }
+        let body: Body = self.post("/messages/unread", json!({ "user_id": user_id })).await?;
+        Ok(body.messages)
+    }
+
+    /// 标记消息已送达，第一次标记时服务器记录送达时间，并不再重发这些消息的推送
+    pub async fn mark_delivered(&self, message_ids: &[&str]) -> Result<()> {
+        let _: Value = self.post("/messages/delivered", json!({ "message_ids": message_ids })).await?;
+        Ok(())
+    }
+
+    /// 标记消息已读（同时视为已送达）
+    pub async fn mark_read(&self, message_ids: &[&str]) -> Result<()> {
+        let _: Value = self.post("/messages/read", json!({ "message_ids": message_ids })).await?;
+        Ok(())
+    }
+
+    /// 把消息翻译为 lang（如 en、zh-CN），服务器按消息和语言缓存译文；未启用翻译的服务器返回 404
+    pub async fn translate_message(&self, message_id: &str, lang: &str) -> Result<Translation> {
+        let request = self.authorized(Method::POST, &format!("/messages/{}/translate", message_id))
+            .query(&[("lang", lang)]);
+        Ok(Self::send(request).await?.1)
+    }
+
+    /// 群消息的送达和已读情况（需要会话令牌，发送者或群成员可以查看），每类最多返回 limit 个成员
+    pub async fn message_receipts(&self, message_id: &str, limit: Option<i64>) -> Result<MessageReceipts> {
+        let mut request = self.authorized(Method::GET, &format!("/messages/{}/receipts", message_id));
+        if let Some(limit) = limit {
+            request = request.query(&[("limit", limit)]);
+        }
+        Ok(Self::send(request).await?.1)
+    }
+
+    /// 发起或接听通话前获取 STUN 服务器和 TURN 临时凭据（需要会话令牌）
+    pub async fn ice_servers(&self) -> Result<IceServers> {
+        Ok(Self::send(self.authorized(Method::GET, "/calls/ice-servers")).await?.1)
+    }
+
+    /// 创建与 peer_id 之间的二进制中转（需要会话令牌，服务器未开启时返回 404），
+    /// 把 relay_id 告诉对方后双方各自 connect_relay（`ws` 特性）
+    pub async fn create_relay(&self, peer_id: &str) -> Result<RelayInfo> {
+        self.post("/relay", json!({ "peer_id": peer_id })).await
+    }
+
+    /// 增量同步 last_sync_time 之后的消息和删除记录
+    pub async fn sync_messages(&self, user_id: &str, last_sync_time: i64, limit: i64) -> Result<SyncResult> {
+        self.post("/messages/sync", json!({
+            "user_id": user_id,
+            "last_sync_time": last_sync_time,
+            "limit": limit,
+        })).await
+    }
+
+    /// 会话中 seq >= from_seq 的至多 limit 条消息（需要会话令牌）。peer_id 是私聊对象或群ID；
+    /// 收到的消息序号跳号时用它补齐，返回的 next_seq 不为空说明还有更多
+    pub async fn messages_from_seq(&self, peer_id: &str, from_seq: i64, limit: i64) -> Result<SeqRange> {
+        let request = self.authorized(Method::GET, &format!("/conversations/{}/messages", peer_id))
+            .query(&[("from_seq", from_seq), ("limit", limit)]);
+        Ok(Self::send(request).await?.1)
+    }
+
+    /// 与 peer_id 的私聊历史（before 之前，按时间倒序）
+    pub async fn conversation_history(&self, user_id: &str, peer_id: &str, before: Option<i64>, limit: i64) -> Result<Vec<Message>> {
+        #[derive(Deserialize)]
+        struct Body {
+            messages: Vec<Message>,
+        }
+        let body: Body = self.post("/messages/history", json!({
+            "user_id": user_id,
+            "peer_id": peer_id,
+            "before": before,
+            "limit": limit,
+        })).await?;
+        Ok(body.messages)
+    }
+}
+
+// 通讯录匹配上传的邮箱哈希：去掉首尾空白并转为小写后的 SHA-256（十六进制）
+fn email_hash(email: &str) -> String {
+    Sha256::digest(email.trim().to_lowercase().as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,141 @@
+//! 客户端加密：与服务器 `CryptoService` 相同的密文格式（AES-256-GCM，12 字节 nonce，
+//! 末尾附覆盖整个容器的 HMAC-SHA256 完整性标签），密钥由客户端自己持有的主密钥派生
+//!
+//! 消息内容加密后以 `yle2e1:` 前缀加 base64 文本发送，附加数据绑定发送者ID，
+//! 服务器不能把一条密文改成别人发的。服务器不配置消息解密时即为零知识部署
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::client::ApiClient;
+use crate::error::Result;
+use crate::types::Message;
+
+// 与服务器相同的密钥派生前缀
+const DATA_KEY_CONTEXT: &[u8] = b"yueling-data-v1:";
+const DATA_MAC_CONTEXT: &[u8] = b"yueling-data-mac-v1:";
+
+/// 客户端加密的消息内容前缀，没有该前缀的消息视为明文
+pub const E2E_PREFIX: &str = "yle2e1:";
+
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 32;
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("密文格式无效")]
+    Malformed,
+    #[error("解密失败（密钥错误或数据被篡改）")]
+    Decrypt,
+    #[error("密文完整性校验失败（被截断、篡改或密钥不匹配）")]
+    Integrity,
+}
+
+fn derive(context: &[u8], master_key: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(context);
+    hasher.update(master_key.as_bytes());
+    hasher.finalize().into()
+}
+
+// 完整性标签覆盖容器长度、容器本身和附加数据
+fn container_mac(mac_key: &[u8], container: &[u8], aad: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(mac_key).expect("HMAC 接受任意长度的密钥");
+    mac.update(&(container.len() as u64).to_be_bytes());
+    mac.update(container);
+    mac.update(aad);
+    mac
+}
+
+/// 客户端加解密，克隆开销很小
+#[derive(Clone)]
+pub struct ClientCrypto {
+    cipher: Aes256Gcm,
+    mac_key: [u8; 32],
+}
+
+impl ClientCrypto {
+    /// 由客户端持有的主密钥派生加密密钥和完整性标签密钥
+    pub fn new(master_key: &str) -> Self {
+        let key = derive(DATA_KEY_CONTEXT, master_key);
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            mac_key: derive(DATA_MAC_CONTEXT, master_key),
+        }
+    }
+
+    /// 加密明文，aad 为解密时必须一致的附加数据
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+            .expect("AES-GCM 加密不会因输入长度以外的原因失败");
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len() + TAG_LEN);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        let tag = container_mac(&self.mac_key, &out, aad).finalize().into_bytes();
+        out.extend_from_slice(&tag);
+        out
+    }
+
+    /// 解密 encrypt（或服务器 CryptoService::encrypt）的输出：先校验完整性标签，再解密
+    pub fn decrypt(&self, data: &[u8], aad: &[u8]) -> std::result::Result<Vec<u8>, CryptoError> {
+        if data.len() < TAG_LEN {
+            return Err(CryptoError::Malformed);
+        }
+        let (container, tag) = data.split_at(data.len() - TAG_LEN);
+        container_mac(&self.mac_key, container, aad)
+            .verify_slice(tag)
+            .map_err(|_| CryptoError::Integrity)?;
+        if container.len() < NONCE_LEN {
+            return Err(CryptoError::Malformed);
+        }
+        let (nonce, ciphertext) = container.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| CryptoError::Decrypt)
+    }
+
+    /// 加密消息内容，得到可以直接作为 content 发送的文本
+    pub fn seal_text(&self, sender_id: &str, plaintext: &str) -> String {
+        format!("{}{}", E2E_PREFIX, BASE64.encode(self.encrypt(plaintext.as_bytes(), sender_id.as_bytes())))
+    }
+
+    /// 解密 seal_text 的输出；没有前缀的内容原样返回
+    pub fn open_text(&self, sender_id: &str, content: &str) -> std::result::Result<String, CryptoError> {
+        let Some(encoded) = content.strip_prefix(E2E_PREFIX) else {
+            return Ok(content.to_string());
+        };
+        let data = BASE64.decode(encoded).map_err(|_| CryptoError::Malformed)?;
+        let plaintext = self.decrypt(&data, sender_id.as_bytes())?;
+        String::from_utf8(plaintext).map_err(|_| CryptoError::Malformed)
+    }
+
+    /// 就地解密收到的消息
+    pub fn open_message(&self, message: &mut Message) -> std::result::Result<(), CryptoError> {
+        message.content = self.open_text(&message.sender_id, &message.content)?;
+        Ok(())
+    }
+}
+
+impl ApiClient {
+    /// 加密后发送消息，返回消息ID
+    pub async fn send_encrypted_message(
+        &self,
+        crypto: &ClientCrypto,
+        sender_id: &str,
+        receiver_id: &str,
+        content: &str,
+        message_type: &str,
+    ) -> Result<String> {
+        self.send_message(sender_id, receiver_id, &crypto.seal_text(sender_id, content), message_type).await
+    }
+}
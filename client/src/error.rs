@@ -0,0 +1,42 @@
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("请求失败: {0}")]
+    Http(#[from] reqwest::Error),
+    /// 服务器返回的错误，code 为语言目录中的提示语键（如 user.not_found）
+    #[error("{message} ({code}, HTTP {status})")]
+    Api { status: u16, code: String, message: String },
+    /// 读写本地文件（附件）失败
+    #[error("文件读写失败: {0}")]
+    Io(#[from] std::io::Error),
+    /// 构建客户端的选项无效（CA 证书、证书指纹等）
+    #[error("客户端配置无效: {0}")]
+    Config(String),
+    /// 新设备需要验证：带上 device_id 和邮件中的验证码重新登录
+    #[error("新设备需要验证")]
+    DeviceVerificationRequired { device_id: String },
+    /// 在群里被禁言，muted_until 之前发送的群消息都会被拒绝
+    #[error("{message} (禁言至 {muted_until})")]
+    Muted { muted_until: i64, message: String },
+    #[cfg(feature = "ws")]
+    #[error("WebSocket 错误: {0}")]
+    WebSocket(String),
+    #[cfg(feature = "crypto")]
+    #[error(transparent)]
+    Crypto(#[from] crate::crypto::CryptoError),
+    /// 读写本地消息缓存失败
+    #[cfg(feature = "cache")]
+    #[error("本地缓存错误: {0}")]
+    Cache(String),
+}
+
+impl ClientError {
+    /// 是否因为连不上服务器（连接失败或超时）而失败
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn is_offline(&self) -> bool {
+        matches!(self, ClientError::Http(e) if e.is_connect() || e.is_timeout())
+    }
+}
+
+pub type Result<T> = std::result::Result<T, ClientError>;
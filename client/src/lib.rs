@@ -0,0 +1,48 @@
+//! 月灵聊天服务器的 Rust 客户端
+//!
+//! `ApiClient` 封装注册、登录、发送和同步消息、上传下载附件等 HTTP 接口，列表接口可以按页读取，也可以作为自动翻页的 `Stream` 读取；开启 `crypto` 特性后，
+//! 可以在发送前用 `ClientCrypto` 加密消息内容，服务器不需要（也无法）解密；
+//! 开启 `blocking` 特性后，`BlockingClient` 提供同样的同步接口；开启 `ws` 特性后，
+//! `ApiClient::connect_events` 连接 WebSocket 接收实时推送；开启 `cache` 特性后，`MessageCache` 在本地保存消息，离线时也能查看历史
+//!
+//! 可以编译到 `wasm32-unknown-unknown`：HTTP 请求自动改用浏览器的 fetch，WebSocket 使用浏览器的实现，
+//! 浏览器前端可以直接复用同一套类型化接口（附件的上传下载需要本地文件、`blocking` 特性需要阻塞线程、`cache` 特性需要 SQLite，三者除外）
+
+#[cfg(not(target_arch = "wasm32"))]
+mod attachments;
+#[cfg(not(target_arch = "wasm32"))]
+mod builder;
+mod client;
+mod error;
+mod pagination;
+mod types;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "ws")]
+pub mod ws;
+
+// 浏览器中不能阻塞主线程
+#[cfg(all(feature = "blocking", target_arch = "wasm32"))]
+compile_error!("blocking 特性不支持 wasm32，请直接使用 ApiClient");
+#[cfg(all(feature = "cache", target_arch = "wasm32"))]
+compile_error!("cache 特性需要 SQLite，不支持 wasm32");
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use builder::ApiClientBuilder;
+pub use client::ApiClient;
+pub use error::{ClientError, Result};
+pub use types::{Attachment, ContactMatch, Conversation, CustomEmoji, DisplayNameChange, EntityKind, GroupDeletionToken, GroupJoinRequest, GroupListing, GroupMember, IceServer, IceServers, Invite, JoinResult, Message, MessageAck, MessageReceipts, Page, Presence, Profile, Progress, ReceiptUser, RegistrationInfo, RelayInfo, SeqRange, ServerFeatures, ServerInfo, ServerTime, Session, SyncResult, SystemEvent, TextEntity, Tombstone, Translation, PROTOCOL_VERSION};
+
+#[cfg(feature = "blocking")]
+pub use blocking::{BlockingClient, PageIter};
+#[cfg(feature = "cache")]
+pub use cache::MessageCache;
+#[cfg(feature = "crypto")]
+pub use crypto::ClientCrypto;
+#[cfg(feature = "ws")]
+pub use ws::{Event, EventStream, RelayStream, CLOSE_BY_ADMIN, CLOSE_DUPLICATE_LOGIN, CLOSE_MAINTENANCE, CLOSE_RELAY_CHUNK_TOO_LARGE, CLOSE_RELAY_PEER_LEFT, CLOSE_RELAY_TIMEOUT, CLOSE_REPLACED_BY_NEW_LOGIN};
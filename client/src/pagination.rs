@@ -0,0 +1,155 @@
+//! 按游标分页的列表：消息历史、会话列表、群成员和群目录
+//!
+//! `*_page` 方法取单独一页，返回的 `next_cursor` 原样传回即可取下一页；
+//! 不带 `_page` 的方法返回 `Stream`，内部自动沿游标翻页，调用方逐项读取即可
+
+use std::future::Future;
+
+use futures_util::{stream, Stream, TryStreamExt};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::client::ApiClient;
+use crate::error::{ClientError, Result};
+use crate::types::{Conversation, GroupListing, GroupMember, Message, Page};
+
+// Stream 每次请求的条数
+const STREAM_PAGE_SIZE: i64 = 50;
+
+// 沿着 next_cursor 逐页请求，逐项产出；某一页出错时产出该错误后结束
+pub(crate) fn paginate<'a, T, F, Fut>(fetch: F) -> impl Stream<Item = Result<T>> + 'a
+where
+    T: 'a,
+    F: FnMut(Option<String>) -> Fut + 'a,
+    Fut: Future<Output = Result<Page<T>>> + 'a,
+{
+    stream::try_unfold((fetch, Some(None)), |(mut fetch, cursor)| async move {
+        let Some(cursor) = cursor else {
+            return Ok::<_, ClientError>(None);
+        };
+        let page = fetch(cursor).await?;
+        let next = page.next_cursor.map(Some);
+        Ok(Some((stream::iter(page.items.into_iter().map(Ok)), (fetch, next))))
+    })
+    .try_flatten()
+}
+
+// GET 列表接口的查询参数，首页不带 cursor
+fn page_query(cursor: Option<&str>, limit: i64) -> Vec<(&'static str, String)> {
+    let mut query = vec![("limit", limit.to_string())];
+    query.extend(cursor.map(|cursor| ("cursor", cursor.to_string())));
+    query
+}
+
+// 列表接口的响应体，items 按接口改名
+#[derive(Deserialize)]
+struct MessagesBody {
+    messages: Vec<Message>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ConversationsBody {
+    conversations: Vec<Conversation>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DirectoryBody {
+    groups: Vec<GroupListing>,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MembersBody {
+    members: Vec<GroupMember>,
+    next_cursor: Option<String>,
+}
+
+impl ApiClient {
+    /// 与 peer_id（私聊对象或所在的群）的一页历史消息，按时间倒序；cursor 为空时从最新消息开始。
+    /// 设置了本地缓存时，取到的消息写入缓存，连不上服务器时从缓存读取
+    pub async fn message_history_page(&self, user_id: &str, peer_id: &str, cursor: Option<&str>, limit: i64) -> Result<Page<Message>> {
+        let result = self.post("/messages/history", json!({
+            "user_id": user_id,
+            "peer_id": peer_id,
+            "cursor": cursor,
+            "limit": limit,
+        })).await.map(|body: MessagesBody| Page { items: body.messages, next_cursor: body.next_cursor });
+        #[cfg(feature = "cache")]
+        let result = self.through_cache(
+            result,
+            |cache, page| cache.insert_messages(&page.items).map(drop),
+            |cache| cache.history_page(peer_id, cursor, limit),
+        );
+        result
+    }
+
+    /// 与 peer_id 的全部历史消息，从最新到最早，自动翻页
+    pub fn message_history<'a>(&'a self, user_id: &str, peer_id: &str) -> impl Stream<Item = Result<Message>> + 'a {
+        let (user_id, peer_id) = (user_id.to_string(), peer_id.to_string());
+        paginate(move |cursor| {
+            let (user_id, peer_id) = (user_id.clone(), peer_id.clone());
+            async move { self.message_history_page(&user_id, &peer_id, cursor.as_deref(), STREAM_PAGE_SIZE).await }
+        })
+    }
+
+    /// 当前登录用户的一页会话，按最后一条消息时间倒序（需要会话令牌）
+    pub async fn conversations_page(&self, cursor: Option<&str>, limit: i64) -> Result<Page<Conversation>> {
+        let request = self.authorized(Method::GET, "/conversations")
+            .query(&page_query(cursor, limit));
+        let result = Self::send(request).await
+            .map(|(_, body): (_, ConversationsBody)| Page { items: body.conversations, next_cursor: body.next_cursor });
+        #[cfg(feature = "cache")]
+        let result = self.through_cache(
+            result,
+            |cache, page| cache.save_conversations(&page.items),
+            |cache| cache.conversations_page(cursor, limit),
+        );
+        result
+    }
+
+    /// 当前登录用户的全部会话，自动翻页
+    pub fn conversations(&self) -> impl Stream<Item = Result<Conversation>> + '_ {
+        paginate(move |cursor| async move { self.conversations_page(cursor.as_deref(), STREAM_PAGE_SIZE).await })
+    }
+
+    /// 群的一页成员，按用户ID排序（需要会话令牌，且本人是群成员）
+    pub async fn group_members_page(&self, group_id: &str, cursor: Option<&str>, limit: i64) -> Result<Page<GroupMember>> {
+        let request = self.authorized(Method::GET, &format!("/groups/{}/members", group_id))
+            .query(&page_query(cursor, limit));
+        let (_, body): (_, MembersBody) = Self::send(request).await?;
+        Ok(Page { items: body.members, next_cursor: body.next_cursor })
+    }
+
+    /// 按用户名或显示名称搜索群成员（包含关键词、不区分大小写），返回一页，按用户ID排序
+    pub async fn search_group_members_page(&self, group_id: &str, query: &str, cursor: Option<&str>, limit: i64) -> Result<Page<GroupMember>> {
+        let mut params = page_query(cursor, limit);
+        params.push(("query", query.to_string()));
+        let request = self.authorized(Method::GET, &format!("/groups/{}/members", group_id))
+            .query(&params);
+        let (_, body): (_, MembersBody) = Self::send(request).await?;
+        Ok(Page { items: body.members, next_cursor: body.next_cursor })
+    }
+
+    /// 群的全部成员，自动翻页
+    pub fn group_members<'a>(&'a self, group_id: &str) -> impl Stream<Item = Result<GroupMember>> + 'a {
+        let group_id = group_id.to_string();
+        paginate(move |cursor| {
+            let group_id = group_id.clone();
+            async move { self.group_members_page(&group_id, cursor.as_deref(), STREAM_PAGE_SIZE).await }
+        })
+    }
+
+    /// 工作区群目录的一页，query 不为空时按群名搜索（包含关键词、不区分大小写），按创建时间倒序
+    pub async fn discover_groups_page(&self, query: &str, cursor: Option<&str>, limit: i64) -> Result<Page<GroupListing>> {
+        let mut params = page_query(cursor, limit);
+        if !query.is_empty() {
+            params.push(("query", query.to_string()));
+        }
+        let request = self.authorized(Method::GET, "/groups/discover").query(&params);
+        let (_, body): (_, DirectoryBody) = Self::send(request).await?;
+        Ok(Page { items: body.groups, next_cursor: body.next_cursor })
+    }
+}
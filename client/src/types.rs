@@ -0,0 +1,374 @@
+use serde::{Deserialize, Serialize};
+
+/// 服务器返回的消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: String,           // UUIDv7，按时间有序
+    pub sender_id: String,
+    pub receiver_id: String,  // 用户或群ID
+    pub content: String,
+    pub message_type: String, // "private" 或 "group"
+    pub created_at: i64,
+    pub status: String,       // "sent"、"delivered" 或 "read"
+    pub is_read: bool,
+    pub workspace_id: String,
+    // 服务器记录的发送、送达和已读时间（旧版服务器不返回）
+    #[serde(default)]
+    pub sent_at: Option<i64>,
+    #[serde(default)]
+    pub delivered_at: Option<i64>,
+    #[serde(default)]
+    pub read_at: Option<i64>,
+    // 会话内的连续序号，从 1 开始；跳号说明中间有消息没收到
+    #[serde(default)]
+    pub seq: Option<i64>,
+    // 按 markdown 发送的消息中，服务器解析出的格式区间；content 始终按纯文本显示
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<TextEntity>,
+}
+
+/// 格式类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Bold,
+    Italic,
+    Code,
+    Link,
+    Sticker,  // 整条消息是一个贴纸
+    Call,     // 整条消息是一条未接来电记录
+}
+
+/// 消息内容中的一段格式，offset 和 length 按 Unicode 字符（不是字节）计
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEntity {
+    #[serde(rename = "type")]
+    pub kind: EntityKind,
+    pub offset: usize,
+    pub length: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,  // 仅链接有，服务器只接受 http、https 和 mailto
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,  // 仅贴纸有：自定义表情的名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment_id: Option<String>,  // 仅贴纸有：表情图片，用 download_attachment 下载
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>,  // 仅未接来电有：主叫方生成的通话ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>,  // 仅未接来电有：audio 或 video
+}
+
+/// 工作区的自定义表情，也用作贴纸
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CustomEmoji {
+    pub name: String,
+    pub attachment_id: String,
+    pub content_type: String,
+    pub created_at: i64,
+}
+
+impl Message {
+    /// 系统消息（message_type 为 "system"，接收者是群或用户本人）的结构化内容；普通消息或无法识别的事件返回 None
+    pub fn system_event(&self) -> Option<SystemEvent> {
+        if self.message_type != "system" {
+            return None;
+        }
+        serde_json::from_str(&self.content).ok()
+    }
+}
+
+/// 服务器在群聊中生成的系统事件，以及发给用户本人的安全提醒，按 event 字段区分
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SystemEvent {
+    MemberJoined { user_id: String },
+    NameChanged { user_id: String, old_name: String, new_name: String },
+    MessagePinned { message_id: String, pinned_by: String },
+    CallStarted { call_id: String, started_by: String },
+    OwnerChanged { old_owner: String, new_owner: String },
+    GroupDeleted { deleted_by: String },
+    NewDeviceLogin { device_id: String, device_name: String, platform: String },
+    DuplicateLogin { device_id: String, device_name: String, platform: String, replaced_old: bool },
+}
+
+/// 被删除的消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub message_id: String,
+    pub deleted_at: i64,
+}
+
+/// 一次增量同步的结果，下次同步时传入 last_sync_time
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyncResult {
+    pub messages: Vec<Message>,
+    pub tombstones: Vec<Tombstone>,
+    pub last_sync_time: i64,
+}
+
+/// 发送确认：服务器回显客户端生成的临时ID，界面据此把乐观显示的消息换成正式的消息ID和时间。
+/// 同一临时ID重发时得到的是第一次保存的消息；WebSocket 推送的 `message_ack` 事件也是这个结构
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageAck {
+    pub client_message_id: String,
+    pub message_id: String,
+    pub created_at: i64,
+    #[serde(default)]
+    pub sent_at: Option<i64>,
+    #[serde(default)]
+    pub seq: Option<i64>,
+}
+
+/// 会话内按序号读取的一段消息，用于补齐缺口
+#[derive(Debug, Clone, Deserialize)]
+pub struct SeqRange {
+    pub messages: Vec<Message>,      // 未删除的消息，按 seq 升序
+    #[serde(default)]
+    pub deleted_seqs: Vec<i64>,      // 范围内已删除消息的序号，这些位置不是缺口
+    pub last_seq: i64,               // 会话当前最大的序号
+    pub next_seq: Option<i64>,       // 还有更多消息时，下一次请求的 from_seq
+}
+
+/// 消息的译文
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Translation {
+    pub message_id: String,
+    pub lang: String,
+    pub translated: String,
+    pub cached: bool,                // 译文来自服务器缓存
+}
+
+/// 服务器时钟，用于估算本机时钟的偏差
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ServerTime {
+    pub server_time: i64,     // 秒
+    pub server_time_ms: i64,  // 毫秒
+}
+
+/// 本客户端实现的协议版本，与服务器返回的 `protocol_version` 不同时部分接口可能不兼容
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 服务器版本和本实例开启的功能，用于调整界面
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ServerInfo {
+    pub version: String,
+    pub protocol_version: u32,
+    pub features: ServerFeatures,
+    pub cipher_suites: Vec<String>,
+    pub registration: RegistrationInfo,
+    pub maintenance: bool,
+    #[serde(default)]
+    pub custom_emoji: Vec<CustomEmoji>,  // 默认工作区的自定义表情
+}
+
+impl ServerInfo {
+    /// 服务器的协议版本与本客户端一致
+    pub fn is_compatible(&self) -> bool {
+        self.protocol_version == PROTOCOL_VERSION
+    }
+}
+
+/// 服务器开启的功能
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ServerFeatures {
+    pub e2ee: bool,                     // 可以发送客户端加密的消息（`crypto` 特性）
+    pub encryption_at_rest: bool,       // 服务器加密保存消息内容
+    pub attachments_max_bytes: u64,
+    pub federation: bool,
+    pub federation_server_name: Option<String>,
+    pub grpc: bool,
+    pub oauth_providers: Vec<String>,
+    pub device_verification: bool,      // 新设备登录需要邮箱验证码
+    #[serde(default)]
+    pub translation: bool,              // 可以用 translate_message 翻译消息
+    #[serde(default)]
+    pub relay: bool,                    // 可以用 create_relay 经服务器中转二进制数据
+}
+
+/// 注册相关配置
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RegistrationInfo {
+    pub open: bool,                     // 默认工作区允许不带邀请码注册
+    pub challenge: String,              // "off"、"pow" 或 "hcaptcha"
+    pub email_required: bool,
+    pub email_verification: bool,
+}
+
+/// 用户的在线状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Presence {
+    pub online: bool,
+    pub last_seen_at: Option<i64>,
+    pub dnd: bool,               // 是否处于免打扰时段（期间服务器不推送通知）
+    pub dnd_until: Option<i64>,  // 免打扰时段结束的时间
+}
+
+/// 新签发的注册邀请码，明文只返回这一次
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Invite {
+    pub invite_code: String,
+    pub expires_at: i64,
+}
+
+/// 删除群聊前申请的一次性确认令牌
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GroupDeletionToken {
+    pub confirmation_token: String,
+    pub expires_at: i64,
+}
+
+/// 群目录中的公开群
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GroupListing {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub join_approval: bool,   // 加入是否需要群主批准
+    pub member_count: i64,
+}
+
+/// 加入公开群的结果：joined 表示已新加入，pending 表示在等待群主批准
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct JoinResult {
+    pub joined: bool,
+    pub pending: bool,
+}
+
+/// 等待群主批准的加群申请
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct GroupJoinRequest {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub requested_at: i64,
+}
+
+/// 修改显示名称后的个人资料
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Profile {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub next_change_at: Option<i64>,  // 冷却结束、可以再次修改显示名称的时间，服务器不限制时为空
+}
+
+/// 群消息的送达和已读汇总，read_by 和 delivered_to 只是第一页
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MessageReceipts {
+    pub message_id: String,
+    pub member_count: i64,    // 发送者以外的群成员数
+    pub delivered_count: i64, // 已送达（含已读）的成员数
+    pub read_count: i64,      // 能看到的已读成员数，不公开已读的成员只算作已送达
+    pub read_by: Vec<ReceiptUser>,
+    pub delivered_to: Vec<ReceiptUser>,
+}
+
+/// 回执中的一个成员，at 为已读或送达时间
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ReceiptUser {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub at: i64,
+}
+
+/// WebRTC 连接使用的 STUN 或 TURN 服务器，可以直接用作浏览器的 RTCIceServer
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(default)]
+    pub username: Option<String>,    // 仅 TURN 有，为临时凭据
+    #[serde(default)]
+    pub credential: Option<String>,
+}
+
+/// 服务器下发的 ICE 服务器，ttl 秒后 TURN 凭据失效，需要重新获取
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct IceServers {
+    pub ice_servers: Vec<IceServer>,
+    pub ttl: u64,
+}
+
+/// 新建的中转，双方需要在 expires_in 秒内用 connect_relay 连上
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct RelayInfo {
+    pub relay_id: String,
+    pub expires_in: u64,
+    pub max_chunk_bytes: usize,   // 每次 send 的数据不能超过该大小
+    pub bytes_per_sec: u64,       // 每个方向的带宽上限
+}
+
+/// 通讯录匹配到的用户，handle 为调用时传入的用户名或邮箱
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ContactMatch {
+    pub handle: String,
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+}
+
+/// 一次显示名称修改，名称为空表示没有显示名称
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DisplayNameChange {
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+    pub changed_at: i64,
+}
+
+/// 登录会话
+#[derive(Debug, Clone, Deserialize)]
+pub struct Session {
+    pub user_id: String,
+    pub username: String,
+    pub token: String,
+    pub device_id: Option<String>,
+}
+
+/// 已上传的附件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub uploader_id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: u64,
+    pub sha256: String,       // 文件内容的 SHA-256（十六进制）
+    pub created_at: i64,
+}
+
+/// 附件上传或下载的进度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    pub transferred: u64,     // 已传输的字节数（续传时包含之前已下载的部分）
+    pub total: u64,
+}
+
+/// 会话列表中的一项
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub peer_id: String,           // 私聊对象的用户ID或群ID
+    pub conversation_type: String, // "private" 或 "group"
+    pub last_message_at: i64,
+    pub unread_count: i64,         // 未读私聊消息数（群聊不统计）
+}
+
+/// 群成员
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMember {
+    pub user_id: String,
+    pub role: String,         // "owner" 或 "member"
+    pub joined_at: i64,
+    #[serde(default)]
+    pub username: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub muted_until: Option<i64>,   // 禁言结束时间，未被禁言时为空
+}
+
+/// 列表接口的一页；next_cursor 为 None 表示已经是最后一页
+#[derive(Debug, Clone)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
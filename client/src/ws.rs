@@ -0,0 +1,298 @@
+//! 实时事件：连接服务器的 `/ws`，先发送 identify 帧（附带所在群聊）登记连接，之后接收推送
+//!
+//! 服务器开启中转时，`connect_relay` 连接 `/relay/{relay_id}`，与对方经服务器收发二进制数据
+//!
+//! 原生平台使用 tokio-tungstenite，须在 tokio 运行时中调用；wasm32 上使用浏览器的 WebSocket（gloo-net），
+//! 浏览器前端可以和原生客户端共用同一套接口
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+
+use crate::client::ApiClient;
+use crate::error::{ClientError, Result};
+
+/// 服务器推送的一帧
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// JSON 帧，按 type 字段区分（message、call_offer、call_end、ice_candidate 等，多数原样转发自发送方）；
+    /// 本连接发出的帧格式错误或处理失败时为 error，带有 code、message 和出错请求的 client_message_id
+    Json(Value),
+    /// 非 JSON 的文本帧：群聊广播只推送消息内容
+    Text(String),
+}
+
+impl Event {
+    fn parse(text: String) -> Self {
+        match serde_json::from_str(&text) {
+            Ok(value) => Event::Json(value),
+            Err(_) => Event::Text(text),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod transport {
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{connect_async_tls_with_config, tungstenite::Message, Connector, MaybeTlsStream, WebSocketStream};
+
+    use super::ws_error;
+    use crate::client::ApiClient;
+    use crate::error::Result;
+
+    pub type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+    pub type Frame = Message;
+
+    // 构建器配置了 CA 或证书固定时使用同一份 rustls 配置，否则使用内置的根证书
+    pub async fn connect(client: &ApiClient, url: &str) -> Result<Socket> {
+        let connector = client.tls.clone().map(Connector::Rustls);
+        let (socket, _) = connect_async_tls_with_config(url, None, false, connector).await.map_err(ws_error)?;
+        Ok(socket)
+    }
+
+    pub fn text(text: String) -> Frame {
+        Message::Text(text.into())
+    }
+
+    pub fn binary(data: Vec<u8>) -> Frame {
+        Message::Binary(data.into())
+    }
+
+    // 中转的数据帧，文本帧按 UTF-8 字节返回
+    pub fn into_bytes(frame: Frame) -> Option<Vec<u8>> {
+        match frame {
+            Message::Binary(data) => Some(data.to_vec()),
+            Message::Text(text) => Some(text.as_bytes().to_vec()),
+            _ => None,
+        }
+    }
+
+    // 只关心文本帧，ping/pong 由 tungstenite 自动应答
+    pub fn into_text(frame: Frame) -> Option<String> {
+        match frame {
+            Message::Text(text) => Some(text.to_string()),
+            _ => None,
+        }
+    }
+
+    // 收到关闭帧后对方随即断开，不再继续读取（否则得到的是连接已关闭的错误）
+    pub fn is_close(frame: &Frame) -> bool {
+        frame.is_close()
+    }
+
+    // 关闭帧中的关闭码和原因
+    pub fn close_reason(frame: &Frame) -> Option<(u16, String)> {
+        match frame {
+            Message::Close(Some(close)) => Some((close.code.into(), close.reason.to_string())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod transport {
+    use gloo_net::websocket::{futures::WebSocket, Message};
+
+    use super::ws_error;
+    use crate::client::ApiClient;
+    use crate::error::Result;
+
+    pub type Socket = WebSocket;
+    pub type Frame = Message;
+
+    // 浏览器的 WebSocket 在打开前就能写入，发送会等到连接建立；证书由浏览器校验
+    pub async fn connect(_: &ApiClient, url: &str) -> Result<Socket> {
+        WebSocket::open(url).map_err(ws_error)
+    }
+
+    pub fn text(text: String) -> Frame {
+        Message::Text(text)
+    }
+
+    pub fn binary(data: Vec<u8>) -> Frame {
+        Message::Bytes(data)
+    }
+
+    pub fn into_bytes(frame: Frame) -> Option<Vec<u8>> {
+        match frame {
+            Message::Bytes(data) => Some(data),
+            Message::Text(text) => Some(text.into_bytes()),
+        }
+    }
+
+    pub fn into_text(frame: Frame) -> Option<String> {
+        match frame {
+            Message::Text(text) => Some(text),
+            Message::Bytes(_) => None,
+        }
+    }
+
+    // 浏览器在关闭时直接结束流，不会交出关闭帧
+    pub fn is_close(_: &Frame) -> bool {
+        false
+    }
+
+    pub fn close_reason(_: &Frame) -> Option<(u16, String)> {
+        None
+    }
+}
+
+fn ws_error(e: impl std::fmt::Display) -> ClientError {
+    ClientError::WebSocket(e.to_string())
+}
+
+/// 同一设备的新连接顶替了本连接（`[devices] duplicate_login = "kick_old"`）
+pub const CLOSE_REPLACED_BY_NEW_LOGIN: u16 = 4001;
+/// 同一设备已经在线，服务器拒绝了本连接（`[devices] duplicate_login = "deny_new"`）
+pub const CLOSE_DUPLICATE_LOGIN: u16 = 4002;
+/// 管理员通过 `DELETE /admin/connections/{client_id}` 关闭了本连接
+pub const CLOSE_BY_ADMIN: u16 = 4003;
+/// 服务器进入维护模式，本连接的用户不在豁免名单中（断开前会先收到 `maintenance` 事件）
+pub const CLOSE_MAINTENANCE: u16 = 4004;
+/// 中转的一帧超过了服务器的 `max_chunk_bytes`，双方都被断开
+pub const CLOSE_RELAY_CHUNK_TOO_LARGE: u16 = 1009;
+/// 对方没有在时限内连上中转
+pub const CLOSE_RELAY_TIMEOUT: u16 = 4005;
+/// 对方断开，中转结束
+pub const CLOSE_RELAY_PEER_LEFT: u16 = 4006;
+
+/// 已登记的 WebSocket 连接
+pub struct EventStream {
+    socket: transport::Socket,
+    closed_by: Option<(u16, String)>,
+}
+
+impl EventStream {
+    /// 下一帧推送，连接关闭后返回 None
+    pub async fn next(&mut self) -> Option<Result<Event>> {
+        loop {
+            match self.socket.next().await? {
+                Ok(frame) if transport::is_close(&frame) => {
+                    self.closed_by = transport::close_reason(&frame);
+                    return None;
+                }
+                Ok(frame) => {
+                    if let Some(text) = transport::into_text(frame) {
+                        return Some(Ok(Event::parse(text)));
+                    }
+                }
+                Err(e) => return Some(Err(ws_error(e))),
+            }
+        }
+    }
+
+    /// 发送一帧 JSON（如 message、call_offer），服务器按 type 字段处理
+    pub async fn send(&mut self, frame: &Value) -> Result<()> {
+        self.socket.send(transport::text(frame.to_string())).await.map_err(ws_error)
+    }
+
+    /// 确认收到 type 为 message 的推送（私聊），服务器据此记录送达时间，不再重发
+    pub async fn ack_delivered(&mut self, message_ids: &[&str]) -> Result<()> {
+        self.send(&json!({ "type": "delivered", "message_ids": message_ids })).await
+    }
+
+    /// 服务器关闭连接时给出的关闭码和原因（如 `CLOSE_REPLACED_BY_NEW_LOGIN`），`next` 返回 None 之后可用；浏览器上始终为 None
+    pub fn close_reason(&self) -> Option<(u16, &str)> {
+        self.closed_by.as_ref().map(|(code, reason)| (*code, reason.as_str()))
+    }
+
+    /// 关闭连接
+    pub async fn close(mut self) -> Result<()> {
+        SinkExt::close(&mut self.socket).await.map_err(ws_error)
+    }
+}
+
+/// 已连上的中转，收发的每一帧原样转给对方；服务器按 `bytes_per_sec` 限速，对方读得慢时 `send` 也会变慢
+pub struct RelayStream {
+    socket: transport::Socket,
+    closed_by: Option<(u16, String)>,
+}
+
+impl RelayStream {
+    /// 对方发来的下一块数据，中转结束后返回 None
+    pub async fn next(&mut self) -> Option<Result<Vec<u8>>> {
+        loop {
+            match self.socket.next().await? {
+                Ok(frame) if transport::is_close(&frame) => {
+                    self.closed_by = transport::close_reason(&frame);
+                    return None;
+                }
+                Ok(frame) => {
+                    if let Some(data) = transport::into_bytes(frame) {
+                        return Some(Ok(data));
+                    }
+                }
+                Err(e) => return Some(Err(ws_error(e))),
+            }
+        }
+    }
+
+    /// 发送一块数据，不能超过创建中转时返回的 `max_chunk_bytes`
+    pub async fn send(&mut self, chunk: &[u8]) -> Result<()> {
+        self.socket.send(transport::binary(chunk.to_vec())).await.map_err(ws_error)
+    }
+
+    /// 中转结束的原因（如 `CLOSE_RELAY_PEER_LEFT`），`next` 返回 None 之后可用；浏览器上始终为 None
+    pub fn close_reason(&self) -> Option<(u16, &str)> {
+        self.closed_by.as_ref().map(|(code, reason)| (*code, reason.as_str()))
+    }
+
+    /// 结束中转，对方随之断开
+    pub async fn close(mut self) -> Result<()> {
+        SinkExt::close(&mut self.socket).await.map_err(ws_error)
+    }
+}
+
+impl ApiClient {
+    // base_url 的 http/https 换成 ws/wss
+    fn ws_base(&self) -> String {
+        let url = &self.base_url;
+        match url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{rest}"),
+            Some(("http", rest)) => format!("ws://{rest}"),
+            _ => url.clone(),
+        }
+    }
+
+    /// WebSocket 地址：base_url 的 http/https 换成 ws/wss，路径为 /ws
+    pub fn ws_url(&self) -> String {
+        format!("{}/ws", self.ws_base())
+    }
+
+    /// 连接 `create_relay` 创建的中转（需要会话令牌），双方都连上后才开始转发
+    pub async fn connect_relay(&self, relay_id: &str) -> Result<RelayStream> {
+        // 浏览器的 WebSocket 不能设置请求头，会话令牌放在查询参数中
+        let url = format!("{}/relay/{}?token={}", self.ws_base(), relay_id, self.token().unwrap_or_default());
+        let socket = transport::connect(self, &url).await?;
+        Ok(RelayStream { socket, closed_by: None })
+    }
+
+    /// 连接 /ws 并以 user_id 登记（已登录时附带会话令牌，服务器据此确认身份），group_ids 为要接收广播的群聊
+    pub async fn connect_events(&self, user_id: &str, group_ids: &[&str]) -> Result<EventStream> {
+        self.identify(json!({
+            "type": "identify",
+            "user_id": user_id,
+            "list_of_group_chats": group_ids,
+        })).await
+    }
+
+    /// 同 `connect_events`，并带上登录时返回的设备ID：同一设备只保留一个连接，
+    /// 被顶替或被拒绝时 `next` 返回 None，关闭码见 `EventStream::close_reason`
+    pub async fn connect_device_events(&self, user_id: &str, device_id: &str, group_ids: &[&str]) -> Result<EventStream> {
+        self.identify(json!({
+            "type": "identify",
+            "user_id": user_id,
+            "device_id": device_id,
+            "list_of_group_chats": group_ids,
+        })).await
+    }
+
+    async fn identify(&self, mut frame: Value) -> Result<EventStream> {
+        if let Some(token) = self.token() {
+            frame["token"] = json!(token);
+        }
+        let socket = transport::connect(self, &self.ws_url()).await?;
+        let mut events = EventStream { socket, closed_by: None };
+        events.send(&frame).await?;
+        Ok(events)
+    }
+}
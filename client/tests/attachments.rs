@@ -0,0 +1,93 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use common::{mock_server, mock_server_with, Reply};
+use serde_json::json;
+use yueling_client::{ApiClient, Progress};
+
+fn temp_path(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yueling-client-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+// 可打印的测试内容，便于在记录的请求体中查找
+fn content(len: usize) -> Vec<u8> {
+    (0..len).map(|i| b'a' + (i % 26) as u8).collect()
+}
+
+#[tokio::test]
+async fn upload_streams_the_file_as_multipart() {
+    let path = temp_path("notes.txt");
+    let data = content(200_000);
+    std::fs::write(&path, &data).unwrap();
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "附件上传成功", "attachment": {
+            "id": "a1", "uploader_id": "u1", "filename": "notes.txt", "content_type": "text/plain",
+            "size": 200_000, "sha256": "00", "created_at": 1
+        } }).to_string()),
+    ]).await;
+
+    let mut client = ApiClient::new(url);
+    client.set_token(Some("t1".into()));
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let seen = progress.clone();
+    let attachment = client
+        .upload_attachment_with_progress(&path, move |p| seen.lock().unwrap().push(p))
+        .await
+        .unwrap();
+    assert_eq!((attachment.id.as_str(), attachment.size), ("a1", 200_000));
+
+    // 按块报告进度，最后一次为全部字节
+    let progress = progress.lock().unwrap().clone();
+    assert!(progress.len() > 1);
+    assert_eq!(progress.last(), Some(&Progress { transferred: 200_000, total: 200_000 }));
+
+    let request = &server.await.unwrap()[0];
+    assert!(request.head.starts_with("POST /attachments "));
+    assert!(request.head.contains("authorization: Bearer t1"));
+    assert!(request.head.contains("multipart/form-data"));
+    assert!(request.body.contains("name=\"file\"; filename=\"notes.txt\""));
+    assert!(request.body.contains(std::str::from_utf8(&data).unwrap()));
+}
+
+#[tokio::test]
+async fn download_resumes_from_the_existing_length() {
+    let path = temp_path("report.bin");
+    let data = content(200);
+    let _ = std::fs::remove_file(&path);
+    let (url, server) = mock_server_with(vec![
+        // 第一次完整下载
+        Reply { status: 200, headers: vec![], body: data.clone() },
+        // 续传：本地已有前 120 字节
+        Reply { status: 206, headers: vec![("content-range", "bytes 120-199/200".into())], body: data[120..].to_vec() },
+        // 已经下载完整
+        Reply { status: 416, headers: vec![("content-range", "bytes */200".into())], body: Vec::new() },
+        Reply::json(404, json!({ "success": false, "code": "attachment.not_found", "message": "附件不存在" }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+
+    assert_eq!(client.download_attachment("a1", &path).await.unwrap(), 200);
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+
+    std::fs::write(&path, &data[..120]).unwrap();
+    let mut progress = Vec::new();
+    assert_eq!(client.download_attachment_with_progress("a1", &path, |p| progress.push(p)).await.unwrap(), 200);
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+    assert_eq!(progress.last(), Some(&Progress { transferred: 200, total: 200 }));
+
+    assert_eq!(client.download_attachment("a1", &path).await.unwrap(), 200);
+    assert_eq!(std::fs::read(&path).unwrap(), data);
+
+    std::fs::remove_file(&path).unwrap();
+    let err = client.download_attachment("missing", &path).await.unwrap_err();
+    assert!(matches!(err, yueling_client::ClientError::Api { status: 404, ref code, .. } if code == "attachment.not_found"));
+
+    let requests = server.await.unwrap();
+    assert!(!requests[0].head.contains("range:"));
+    assert!(requests[1].head.contains("range: bytes=120-"));
+    assert!(requests[2].head.contains("range: bytes=200-"));
+    assert!(requests[3].head.starts_with("GET /attachments/missing "));
+}
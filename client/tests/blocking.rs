@@ -0,0 +1,37 @@
+#![cfg(feature = "blocking")]
+
+mod common;
+
+use common::mock_server;
+use serde_json::json;
+use yueling_client::BlockingClient;
+
+#[test]
+fn blocking_client_calls_the_api_without_a_runtime() {
+    // 模拟服务器跑在独立的运行时上，测试线程本身不在 async 上下文中
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (url, server) = runtime.block_on(mock_server(vec![
+        (200, json!({ "success": true, "message": "登录成功", "user_id": "u1", "username": "alice", "token": "t1", "device_id": null, "device_verification_required": false }).to_string()),
+        (200, json!({ "success": true, "message": "消息发送成功", "message_id": "m1" }).to_string()),
+    ]));
+
+    let mut client = BlockingClient::new(url);
+    assert_eq!(client.login("alice", "secret").unwrap().user_id, "u1");
+    assert_eq!(client.send_message("u1", "u2", "你好", "private").unwrap(), "m1");
+    let requests = runtime.block_on(server).unwrap();
+    assert!(requests[1].head.starts_with("POST /send-message "));
+}
+
+#[test]
+fn blocking_page_iterator_fetches_pages_lazily() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let (url, server) = runtime.block_on(mock_server(vec![
+        (200, json!({ "success": true, "message": "获取群成员成功", "next_cursor": "1:u1", "members": [{ "user_id": "u1", "role": "owner", "joined_at": 1 }] }).to_string()),
+        (200, json!({ "success": true, "message": "获取群成员成功", "next_cursor": null, "members": [{ "user_id": "u2", "role": "member", "joined_at": 2 }] }).to_string()),
+    ]));
+
+    let client = BlockingClient::new(url);
+    let members: Vec<String> = client.group_members("g1").map(|m| m.unwrap().user_id).collect();
+    assert_eq!(members, ["u1", "u2"]);
+    assert_eq!(runtime.block_on(server).unwrap().len(), 2);
+}
@@ -0,0 +1,142 @@
+#![cfg(feature = "cache")]
+
+mod common;
+
+use std::sync::Arc;
+
+use common::mock_server;
+use futures_util::{StreamExt, TryStreamExt};
+use serde_json::{json, Value};
+use yueling_client::{ApiClient, EntityKind, Message, MessageCache, SystemEvent, TextEntity};
+
+fn message(id: &str, sender: &str, receiver: &str, created_at: i64, is_read: bool) -> Value {
+    json!({
+        "id": id, "sender_id": sender, "receiver_id": receiver, "content": id, "message_type": "private",
+        "created_at": created_at, "status": "sent", "is_read": is_read, "workspace_id": "default"
+    })
+}
+
+fn delivered(mut message: Value, at: i64) -> Value {
+    message["status"] = json!("delivered");
+    message["delivered_at"] = json!(at);
+    message
+}
+
+fn with_seq(mut message: Value, seq: i64) -> Value {
+    message["seq"] = json!(seq);
+    message
+}
+
+fn sync_reply(messages: Vec<Value>, tombstones: Value, last_sync_time: i64) -> (u16, String) {
+    (200, json!({ "success": true, "message": "消息同步成功", "messages": messages, "tombstones": tombstones, "last_sync_time": last_sync_time }).to_string())
+}
+
+#[tokio::test]
+async fn sync_cache_pulls_deltas_and_deduplicates_overlapping_messages() {
+    let (url, server) = mock_server(vec![
+        sync_reply(vec![message("m1", "u2", "u1", 100, true), message("m2", "u1", "u2", 101, false)], json!([]), 101),
+        // 往回多取的一秒里包含已缓存的 m2，期间对方已收到
+        sync_reply(vec![delivered(message("m2", "u1", "u2", 101, false), 103), with_seq(message("m3", "u2", "u1", 105, false), 3)],
+            json!([{ "message_id": "m1", "deleted_at": 104 }]), 105),
+    ]).await;
+    let cache = Arc::new(MessageCache::open_in_memory("u1").unwrap());
+    let mut client = ApiClient::new(url);
+    client.set_cache(Some(cache.clone()));
+
+    assert_eq!(client.sync_cache().await.unwrap(), 2);
+    assert_eq!(client.sync_cache().await.unwrap(), 1);
+    assert_eq!(cache.last_sync_time().unwrap(), 105);
+
+    let history = cache.history_page("u2", None, 10).unwrap().items;
+    let ids: Vec<&str> = history.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, ["m3", "m2"]);
+    // 重复的消息不计数，但更新状态和送达时间
+    assert_eq!((history[1].status.as_str(), history[1].delivered_at), ("delivered", Some(103)));
+    assert_eq!((history[0].seq, history[1].seq), (Some(3), None));
+    let conversations = cache.conversations_page(None, 10).unwrap().items;
+    assert_eq!((conversations[0].peer_id.as_str(), conversations[0].last_message_at, conversations[0].unread_count), ("u2", 105, 1));
+
+    let requests = server.await.unwrap();
+    let since: Vec<Value> = requests.iter()
+        .map(|r| serde_json::from_str::<Value>(&r.body).unwrap()["last_sync_time"].clone())
+        .collect();
+    assert_eq!(since, [json!(0), json!(100)]);
+}
+
+#[tokio::test]
+async fn history_is_served_from_the_cache_when_the_server_is_unreachable() {
+    let cache = Arc::new(MessageCache::open_in_memory("u1").unwrap());
+    let messages: Vec<Message> = (1..=3)
+        .map(|i| serde_json::from_value(message(&format!("m{}", i), "u2", "u1", 100, false)).unwrap())
+        .collect();
+    assert_eq!(cache.insert_messages(&messages).unwrap(), 3);
+    assert_eq!(cache.insert_messages(&messages).unwrap(), 0);
+
+    // 绑定后立即释放的端口，连接会被拒绝
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    drop(listener);
+    let mut client = ApiClient::new(url);
+    client.set_cache(Some(cache));
+
+    // 同一秒内的消息按ID翻页
+    let page = client.message_history_page("u1", "u2", None, 2).await.unwrap();
+    assert_eq!(page.items.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), ["m3", "m2"]);
+    let rest = client.message_history_page("u1", "u2", page.next_cursor.as_deref(), 2).await.unwrap();
+    assert_eq!((rest.items[0].id.as_str(), rest.next_cursor), ("m1", None));
+
+    let all: Vec<Message> = client.message_history("u1", "u2").try_collect().await.unwrap();
+    assert_eq!(all.len(), 3);
+    let conversations: Vec<_> = client.conversations().collect().await;
+    assert_eq!(conversations[0].as_ref().unwrap().unread_count, 3);
+
+    // 没有缓存时照常返回连接错误
+    client.set_cache(None);
+    assert!(client.message_history_page("u1", "u2", None, 2).await.unwrap_err().is_offline());
+}
+
+#[test]
+fn cache_file_belongs_to_one_user() {
+    let path = std::env::temp_dir().join(format!("yueling-client-cache-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    drop(MessageCache::open(&path, "u1").unwrap());
+    assert!(MessageCache::open(&path, "u1").is_ok());
+    assert!(MessageCache::open(&path, "u2").is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn system_messages_belong_to_the_group_conversation() {
+    let cache = MessageCache::open_in_memory("u1").unwrap();
+    let mut joined = message("m1", "u2", "g1", 100, false);
+    joined["message_type"] = json!("system");
+    joined["content"] = json!(json!({ "event": "member_joined", "user_id": "u2" }).to_string());
+    let joined: Message = serde_json::from_value(joined).unwrap();
+    assert_eq!(joined.system_event(), Some(SystemEvent::MemberJoined { user_id: "u2".into() }));
+    cache.insert_messages(&[joined]).unwrap();
+
+    let conversations = cache.conversations_page(None, 10).unwrap().items;
+    assert_eq!((conversations[0].peer_id.as_str(), conversations[0].conversation_type.as_str()), ("g1", "group"));
+    assert_eq!(conversations[0].unread_count, 0);
+    assert!(cache.history_page("g1", None, 10).unwrap().items[0].system_event().is_some());
+}
+
+#[test]
+fn formatted_messages_keep_their_entities() {
+    let cache = MessageCache::open_in_memory("u1").unwrap();
+    let mut formatted = message("m1", "u2", "u1", 100, false);
+    formatted["content"] = json!("发布 见 说明");
+    formatted["entities"] = json!([
+        { "type": "bold", "offset": 0, "length": 2 },
+        { "type": "link", "offset": 5, "length": 2, "url": "https://example.com" },
+    ]);
+    let formatted: Message = serde_json::from_value(formatted).unwrap();
+    cache.insert_messages(&[formatted, serde_json::from_value(message("m2", "u2", "u1", 101, false)).unwrap()]).unwrap();
+
+    let history = cache.history_page("u2", None, 10).unwrap().items;
+    assert!(history[0].entities.is_empty());
+    assert_eq!(history[1].entities, [
+        TextEntity { kind: EntityKind::Bold, offset: 0, length: 2, url: None, emoji: None, attachment_id: None, call_id: None, media: None },
+        TextEntity { kind: EntityKind::Link, offset: 5, length: 2, url: Some("https://example.com".into()), emoji: None, attachment_id: None, call_id: None, media: None },
+    ]);
+}
@@ -0,0 +1,348 @@
+mod common;
+
+use common::mock_server;
+use serde_json::json;
+use yueling_client::{ApiClient, ClientError, ContactMatch, CustomEmoji, DisplayNameChange, GroupJoinRequest, IceServer, Invite, JoinResult, MessageAck, Presence, Profile, ReceiptUser, RelayInfo, Translation, PROTOCOL_VERSION};
+
+#[tokio::test]
+async fn login_stores_the_session_token() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "登录成功", "user_id": "u1", "username": "alice", "token": "t1", "device_id": null, "device_verification_required": false }).to_string()),
+        (200, json!({ "success": true, "message": "消息发送成功", "message_id": "m1" }).to_string()),
+    ]).await;
+    let mut client = ApiClient::new(format!("{}/", url));
+    let session = client.login("alice", "secret").await.unwrap();
+    assert_eq!((session.user_id.as_str(), client.token()), ("u1", Some("t1")));
+    assert_eq!(client.send_message("u1", "u2", "你好", "private").await.unwrap(), "m1");
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("POST /login "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({ "username": "alice", "password": "secret" }));
+    assert!(requests[1].head.to_ascii_lowercase().contains("authorization: bearer t1"));
+}
+
+#[tokio::test]
+async fn client_message_id_is_sent_and_acknowledged() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "消息发送成功", "message_id": "m1", "client_message_id": "tmp-1", "created_at": 1700000000 }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    let ack = client.send_message_with_client_id("u1", "u2", "你好", "private", "tmp-1").await.unwrap();
+    assert_eq!(ack, MessageAck { client_message_id: "tmp-1".into(), message_id: "m1".into(), created_at: 1700000000, sent_at: None, seq: None });
+
+    let requests = server.await.unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap()["client_message_id"], "tmp-1");
+}
+
+#[tokio::test]
+async fn server_time_is_read_from_the_time_endpoint() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取服务器时间成功", "server_time": 1700000000, "server_time_ms": 1700000000123i64 }).to_string()),
+    ]).await;
+    let time = ApiClient::new(url).server_time().await.unwrap();
+    assert_eq!((time.server_time, time.server_time_ms), (1700000000, 1700000000123));
+    assert!(server.await.unwrap()[0].head.starts_with("GET /time "));
+}
+
+#[tokio::test]
+async fn server_info_reports_protocol_compatibility() {
+    let (url, server) = mock_server(vec![
+        (200, json!({
+            "success": true,
+            "message": "获取服务器信息成功",
+            "version": "0.2.0",
+            "protocol_version": PROTOCOL_VERSION + 1,
+            "features": {
+                "e2ee": true, "encryption_at_rest": false, "attachments_max_bytes": 1024, "federation": true,
+                "federation_server_name": "chat.example.com", "grpc": false, "oauth_providers": [], "device_verification": false
+            },
+            "cipher_suites": ["aes-256-gcm-hmac-sha256"],
+            "registration": { "open": false, "challenge": "off", "email_required": false, "email_verification": false },
+            "maintenance": false
+        }).to_string()),
+    ]).await;
+    let info = ApiClient::new(url).server_info().await.unwrap();
+    assert!(!info.is_compatible());
+    assert_eq!(info.features.federation_server_name.as_deref(), Some("chat.example.com"));
+    assert!(!info.registration.open);
+    assert!(server.await.unwrap()[0].head.starts_with("GET /server-info "));
+}
+
+#[tokio::test]
+async fn invites_are_created_with_the_session_token() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "邀请码已生成", "invite_code": "ylw_abc", "expires_at": 1700003600 }).to_string()),
+        (200, json!({ "success": true, "message": "注册成功", "user_id": "u2" }).to_string()),
+    ]).await;
+    let mut client = ApiClient::new(url);
+    client.set_token(Some("t1".into()));
+    let invite = client.create_invite(Some(3600)).await.unwrap();
+    assert_eq!(invite, Invite { invite_code: "ylw_abc".into(), expires_at: 1700003600 });
+    assert_eq!(client.register_with_invite("bob", "secret", None, &invite.invite_code).await.unwrap(), "u2");
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("POST /invites "));
+    assert!(requests[0].head.to_lowercase().contains("authorization: bearer t1"));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[1].body).unwrap()["invite_code"], "ylw_abc");
+}
+
+#[tokio::test]
+async fn display_name_is_patched_and_history_is_listed() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "个人资料已更新", "user_id": "u1", "username": "alice", "display_name": "小爱", "next_change_at": 1700086400 }).to_string()),
+        (200, json!({ "success": true, "message": "获取显示名称修改记录成功", "history": [{ "old_name": null, "new_name": "小爱", "changed_at": 1700000000 }] }).to_string()),
+    ]).await;
+    let mut client = ApiClient::new(url);
+    client.set_token(Some("t1".into()));
+    let profile = client.update_display_name("u1", Some("小爱")).await.unwrap();
+    assert_eq!(profile, Profile { user_id: "u1".into(), username: "alice".into(), display_name: Some("小爱".into()), next_change_at: Some(1700086400) });
+    let history = client.display_name_history("u1").await.unwrap();
+    assert_eq!(history, [DisplayNameChange { old_name: None, new_name: Some("小爱".into()), changed_at: 1700000000 }]);
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("PATCH /user/u1/profile "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({ "display_name": "小爱" }));
+    assert!(requests[1].head.starts_with("GET /user/u1/display-names "));
+}
+
+#[tokio::test]
+async fn group_members_are_added() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "已加入群聊", "joined": true }).to_string()),
+        (403, json!({ "success": false, "code": "user.group_add_forbidden", "message": "对方的隐私设置不允许你把其加入群聊" }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    assert!(client.add_group_member("g1", "u2").await.unwrap());
+    match client.add_group_member("g1", "u3").await {
+        Err(ClientError::Api { status, code, .. }) => assert_eq!((status, code.as_str()), (403, "user.group_add_forbidden")),
+        other => panic!("应当被拒绝: {other:?}"),
+    }
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("POST /groups/g1/members "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({ "user_id": "u2" }));
+}
+
+#[tokio::test]
+async fn presence_includes_dnd_state() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取在线状态成功", "online": false, "last_seen_at": 1700000000, "dnd": true, "dnd_until": 1700003600 }).to_string()),
+    ]).await;
+    let presence = ApiClient::new(url).presence("u2").await.unwrap();
+    assert_eq!(presence, Presence { online: false, last_seen_at: Some(1700000000), dnd: true, dnd_until: Some(1700003600) });
+    assert!(server.await.unwrap()[0].head.starts_with("GET /user/u2/presence "));
+}
+
+#[tokio::test]
+async fn gaps_are_filled_from_a_seq() {
+    let (url, server) = mock_server(vec![
+        (200, json!({
+            "success": true, "message": "获取会话消息成功", "last_seq": 9, "next_seq": 6, "deleted_seqs": [4],
+            "messages": [{
+                "id": "m3", "sender_id": "u2", "receiver_id": "u1", "content": "hi", "message_type": "private",
+                "created_at": 1700000000, "status": "sent", "is_read": false, "workspace_id": "default", "seq": 3
+            }],
+        }).to_string()),
+    ]).await;
+    let mut client = ApiClient::new(url);
+    client.set_token(Some("t1".into()));
+    let range = client.messages_from_seq("u2", 3, 3).await.unwrap();
+    assert_eq!((range.messages[0].seq, range.deleted_seqs, range.last_seq, range.next_seq), (Some(3), vec![4], 9, Some(6)));
+    assert!(server.await.unwrap()[0].head.starts_with("GET /conversations/u2/messages?from_seq=3&limit=3 "));
+}
+
+#[tokio::test]
+async fn error_responses_carry_the_code() {
+    let (url, _server) = mock_server(vec![
+        (404, json!({ "success": false, "code": "user.not_found", "message": "用户不存在" }).to_string()),
+        (202, json!({ "success": false, "message": "新设备需要验证", "device_id": "d1", "device_verification_required": true }).to_string()),
+    ]).await;
+    let mut client = ApiClient::new(url);
+    match client.send_message("u1", "u2", "hi", "private").await {
+        Err(ClientError::Api { status, code, .. }) => assert_eq!((status, code.as_str()), (404, "user.not_found")),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    match client.login("alice", "secret").await {
+        Err(ClientError::DeviceVerificationRequired { device_id }) => assert_eq!(device_id, "d1"),
+        other => panic!("unexpected {:?}", other.map(|_| ())),
+    }
+    assert_eq!(client.token(), None);
+}
+
+#[tokio::test]
+async fn groups_are_transferred_and_deleted() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "群主已转让" }).to_string()),
+        (200, json!({ "success": true, "message": "请在有效期内确认删除", "confirmation_token": "ylg_abc", "expires_at": 1700000300 }).to_string()),
+        (200, json!({ "success": true, "message": "群聊已删除", "purge_at": 1702592000 }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    client.transfer_group_owner("g1", "u2").await.unwrap();
+    let token = client.request_group_deletion("g1").await.unwrap();
+    assert_eq!((token.confirmation_token.as_str(), token.expires_at), ("ylg_abc", 1700000300));
+    assert_eq!(client.delete_group("g1", &token.confirmation_token).await.unwrap(), Some(1702592000));
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("PUT /groups/g1/owner "));
+    assert!(requests[1].head.starts_with("POST /groups/g1/deletion-token "));
+    assert!(requests[2].head.starts_with("DELETE /groups/g1 "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[2].body).unwrap(), json!({ "confirmation_token": "ylg_abc" }));
+}
+
+#[tokio::test]
+async fn public_groups_are_joined_after_approval() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "群设置已更新" }).to_string()),
+        (200, json!({ "success": true, "message": "已提交加群申请，等待群主批准", "joined": false, "pending": true }).to_string()),
+        (200, json!({ "success": true, "message": "获取加群申请成功", "requests": [{ "user_id": "u2", "username": "bob", "display_name": null, "requested_at": 1700000000 }] }).to_string()),
+        (200, json!({ "success": true, "message": "已批准加群申请" }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    client.set_group_visibility("g1", true, true).await.unwrap();
+    assert_eq!(client.join_group("g1").await.unwrap(), JoinResult { joined: false, pending: true });
+    let requests = client.group_join_requests("g1").await.unwrap();
+    assert_eq!(requests, [GroupJoinRequest { user_id: "u2".into(), username: "bob".into(), display_name: None, requested_at: 1700000000 }]);
+    client.resolve_join_request("g1", "u2", true).await.unwrap();
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("PUT /groups/g1/visibility "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({ "visibility": "public", "join_approval": true }));
+    assert!(requests[1].head.starts_with("POST /groups/g1/join "));
+    assert!(requests[2].head.starts_with("GET /groups/g1/join-requests "));
+    assert!(requests[3].head.starts_with("POST /groups/g1/join-requests/u2 "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[3].body).unwrap(), json!({ "approve": true }));
+}
+
+#[tokio::test]
+async fn muted_sends_report_the_unmute_time() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "已禁言该成员", "muted_until": 1700000600 }).to_string()),
+        (403, json!({ "success": false, "code": "group.member_muted", "message": "你已被禁言，1700000600 之前不能在群里发言", "muted_until": 1700000600 }).to_string()),
+        (200, json!({ "success": true, "message": "已解除禁言", "muted_until": null }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    assert_eq!(client.mute_group_member("g1", "u2", 600).await.unwrap(), 1700000600);
+    match client.send_message("u2", "g1", "大家好", "group").await {
+        Err(ClientError::Muted { muted_until, .. }) => assert_eq!(muted_until, 1700000600),
+        other => panic!("应当被禁言: {other:?}"),
+    }
+    client.unmute_group_member("g1", "u2").await.unwrap();
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("PUT /groups/g1/members/u2/mute "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({ "duration_secs": 600 }));
+    assert!(requests[2].head.starts_with("DELETE /groups/g1/members/u2/mute "));
+}
+
+#[tokio::test]
+async fn messages_are_translated_on_request() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "翻译成功", "message_id": "m1", "lang": "en", "translated": "Hello", "cached": false }).to_string()),
+        (404, json!({ "success": false, "code": "translation.disabled", "message": "本服务器未启用消息翻译" }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    let translation = client.translate_message("m1", "en").await.unwrap();
+    assert_eq!(translation, Translation { message_id: "m1".into(), lang: "en".into(), translated: "Hello".into(), cached: false });
+    assert!(matches!(client.translate_message("m1", "en").await, Err(ClientError::Api { status: 404, .. })));
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("POST /messages/m1/translate?lang=en "));
+}
+
+#[tokio::test]
+async fn markdown_messages_are_sent_with_their_format() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "消息发送成功", "message_id": "m1", "created_at": 1700000000, "sent_at": 1700000000, "seq": 1 }).to_string()),
+        (400, json!({ "success": false, "code": "message.unsafe_link", "message": "链接只支持 http、https 和 mailto: javascript:void" }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    assert_eq!(client.send_markdown_message("u1", "u2", "**你好**", "private").await.unwrap(), "m1");
+    match client.send_markdown_message("u1", "u2", "[点我](javascript:void)", "private").await {
+        Err(ClientError::Api { status: 400, code, .. }) => assert_eq!(code, "message.unsafe_link"),
+        other => panic!("应当被拒绝: {other:?}"),
+    }
+    let requests = server.await.unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({
+        "sender_id": "u1", "receiver_id": "u2", "content": "**你好**", "message_type": "private", "format": "markdown",
+    }));
+}
+
+#[tokio::test]
+async fn stickers_are_sent_by_emoji_name() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取自定义表情成功", "emoji": [
+            { "name": "party_parrot", "attachment_id": "a1", "content_type": "image/gif", "created_at": 1700000000 },
+        ] }).to_string()),
+        (200, json!({ "success": true, "message": "消息发送成功", "message_id": "m1", "created_at": 1700000000, "sent_at": 1700000000, "seq": 1 }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    let emoji = client.custom_emoji().await.unwrap();
+    assert_eq!(emoji, [CustomEmoji { name: "party_parrot".into(), attachment_id: "a1".into(), content_type: "image/gif".into(), created_at: 1700000000 }]);
+    assert_eq!(client.send_sticker("u1", "g1", &emoji[0].name, "group").await.unwrap(), "m1");
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("GET /emoji "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[1].body).unwrap()["format"], "sticker");
+}
+
+#[tokio::test]
+async fn contacts_are_looked_up_with_hashed_emails() {
+    let bob_hash = "5ff860bf1190596c7188ab851db691f0f3169c453936e9e1eba2f9a47f7a0018";
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "通讯录匹配完成", "users": [
+            { "handle": "carol", "user_id": "u3", "username": "carol", "display_name": "Carol" },
+            { "handle": bob_hash, "user_id": "u2", "username": "bob", "display_name": null },
+        ] }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    let contacts = client.lookup_contacts(&["carol", "dave"], &[" Bob@Example.com"]).await.unwrap();
+    assert_eq!(contacts, [
+        ContactMatch { handle: "carol".into(), user_id: "u3".into(), username: "carol".into(), display_name: Some("Carol".into()) },
+        ContactMatch { handle: " Bob@Example.com".into(), user_id: "u2".into(), username: "bob".into(), display_name: None },
+    ]);
+    // 上传的只有邮箱哈希
+    let requests = server.await.unwrap();
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({
+        "usernames": ["carol", "dave"], "email_hashes": [bob_hash],
+    }));
+}
+
+#[tokio::test]
+async fn group_message_receipts_are_fetched() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取消息回执成功", "message_id": "m1",
+            "member_count": 3, "delivered_count": 2, "read_count": 1,
+            "read_by": [{ "user_id": "u2", "username": "bob", "display_name": null, "at": 1700000100 }],
+            "delivered_to": [{ "user_id": "u3", "username": "carol", "display_name": "Carol", "at": 1700000050 }],
+        }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    let receipts = client.message_receipts("m1", Some(10)).await.unwrap();
+    assert_eq!((receipts.member_count, receipts.delivered_count, receipts.read_count), (3, 2, 1));
+    assert_eq!(receipts.read_by, [ReceiptUser { user_id: "u2".into(), username: "bob".into(), display_name: None, at: 1700000100 }]);
+    assert_eq!(receipts.delivered_to[0].display_name.as_deref(), Some("Carol"));
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("GET /messages/m1/receipts?limit=10 "));
+}
+
+#[tokio::test]
+async fn ice_servers_are_fetched_with_the_session() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取 ICE 服务器成功", "ttl": 600, "ice_servers": [
+            { "urls": ["stun:stun.example.com"] },
+            { "urls": ["turn:turn.example.com"], "username": "1700000600:u1", "credential": "c2VjcmV0" },
+        ] }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+    let servers = client.ice_servers().await.unwrap();
+    assert_eq!(servers.ttl, 600);
+    assert_eq!(servers.ice_servers[0], IceServer { urls: vec!["stun:stun.example.com".into()], username: None, credential: None });
+    assert_eq!(servers.ice_servers[1].username.as_deref(), Some("1700000600:u1"));
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("GET /calls/ice-servers "));
+}
+
+#[tokio::test]
+async fn relays_are_created_for_a_peer() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "中转已创建", "relay_id": "r1", "expires_in": 60,
+            "max_chunk_bytes": 65536, "bytes_per_sec": 1048576 }).to_string()),
+    ]).await;
+    let relay = ApiClient::new(url).create_relay("u2").await.unwrap();
+    assert_eq!(relay, RelayInfo { relay_id: "r1".into(), expires_in: 60, max_chunk_bytes: 65536, bytes_per_sec: 1048576 });
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("POST /relay "));
+    assert_eq!(serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap(), json!({ "peer_id": "u2" }));
+}
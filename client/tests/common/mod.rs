@@ -0,0 +1,69 @@
+#![allow(dead_code)]
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// 收到的请求：请求行、请求头和请求体
+pub struct Recorded {
+    pub head: String,
+    pub body: String,
+}
+
+/// 模拟服务器的一次应答
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn json(status: u16, body: String) -> Self {
+        Self { status, headers: vec![("content-type", "application/json".into())], body: body.into_bytes() }
+    }
+}
+
+/// 只应答固定几次请求的模拟服务器，按顺序返回 responses 中的 (状态码, JSON)，结束后返回收到的请求
+pub async fn mock_server(responses: Vec<(u16, String)>) -> (String, JoinHandle<Vec<Recorded>>) {
+    mock_server_with(responses.into_iter().map(|(status, body)| Reply::json(status, body)).collect()).await
+}
+
+/// 按顺序返回任意应答（自定义响应头、非 JSON 响应体）的模拟服务器
+pub async fn mock_server_with(replies: Vec<Reply>) -> (String, JoinHandle<Vec<Recorded>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = tokio::spawn(async move {
+        let mut recorded = Vec::new();
+        for reply in replies {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = Vec::new();
+            let mut chunk = [0u8; 4096];
+            // 读完请求头和 content-length 指定长度的请求体
+            let (head, body_start, length) = loop {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+                if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = String::from_utf8_lossy(&buf[..end]).to_string();
+                    let length = head.lines()
+                        .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                        .unwrap_or(0);
+                    break (head, end + 4, length);
+                }
+            };
+            while buf.len() < body_start + length {
+                let n = socket.read(&mut chunk).await.unwrap();
+                buf.extend_from_slice(&chunk[..n]);
+            }
+            recorded.push(Recorded { head, body: String::from_utf8_lossy(&buf[body_start..body_start + length]).to_string() });
+            let mut response = format!("HTTP/1.1 {} X\r\ncontent-length: {}\r\nconnection: close\r\n", reply.status, reply.body.len());
+            for (name, value) in &reply.headers {
+                response.push_str(&format!("{}: {}\r\n", name, value));
+            }
+            response.push_str("\r\n");
+            socket.write_all(response.as_bytes()).await.unwrap();
+            socket.write_all(&reply.body).await.unwrap();
+        }
+        recorded
+    });
+    (url, handle)
+}
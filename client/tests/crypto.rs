@@ -0,0 +1,25 @@
+#![cfg(feature = "crypto")]
+
+use yueling_client::{crypto::{CryptoError, E2E_PREFIX}, ClientCrypto};
+
+// 服务器 CryptoService::new("client-test-key").encrypt("月灵 hello", b"alice") 的输出
+const SERVER_CIPHERTEXT: &str = "8cb3fb7ad584ed4b87b0d5f7dfc7002cf0e6164cc9d514e8b7ec857d573478f09d7eba94459cc71bdeb9d05865a71f2175c466f765d78a2d992d957f1a77f735fc6d6532ea7545f3";
+
+#[test]
+fn decrypts_server_ciphertext() {
+    let crypto = ClientCrypto::new("client-test-key");
+    let data = hex::decode(SERVER_CIPHERTEXT).unwrap();
+    assert_eq!(crypto.decrypt(&data, b"alice").unwrap(), "月灵 hello".as_bytes());
+    assert!(matches!(crypto.decrypt(&data, b"bob"), Err(CryptoError::Integrity)));
+    assert!(matches!(ClientCrypto::new("other").decrypt(&data, b"alice"), Err(CryptoError::Integrity)));
+}
+
+#[test]
+fn sealed_text_is_bound_to_the_sender() {
+    let crypto = ClientCrypto::new("client-test-key");
+    let sealed = crypto.seal_text("alice", "你好");
+    assert!(sealed.starts_with(E2E_PREFIX) && !sealed.contains("你好"));
+    assert_eq!(crypto.open_text("alice", &sealed).unwrap(), "你好");
+    assert!(crypto.open_text("mallory", &sealed).is_err());
+    assert_eq!(crypto.open_text("alice", "明文").unwrap(), "明文");
+}
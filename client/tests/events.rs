@@ -0,0 +1,114 @@
+#![cfg(feature = "ws")]
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+use tokio_tungstenite::{
+    accept_async,
+    accept_hdr_async,
+    tungstenite::{handshake::server::{Callback, ErrorResponse, Request, Response}, protocol::CloseFrame, Message},
+};
+use yueling_client::{ApiClient, Event, CLOSE_RELAY_PEER_LEFT, CLOSE_REPLACED_BY_NEW_LOGIN};
+
+#[tokio::test]
+async fn events_identify_and_receive_pushes() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    // 模拟 /ws：记下 identify 帧，推送一条私聊消息和一条群聊广播，再收下客户端发来的一帧
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = accept_async(stream).await.unwrap();
+        let identify = socket.next().await.unwrap().unwrap().into_text().unwrap().to_string();
+        socket.send(Message::Text(json!({ "type": "message", "sender_id": "u2", "content": "你好" }).to_string().into())).await.unwrap();
+        socket.send(Message::Ping(Vec::new().into())).await.unwrap();
+        socket.send(Message::Text("群聊消息".into())).await.unwrap();
+        // 跳过客户端对 ping 的自动应答
+        let sent = loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                break text.to_string();
+            }
+        };
+        socket.close(None).await.unwrap();
+        (identify, sent)
+    });
+
+    let client = ApiClient::new(&url);
+    assert_eq!(client.ws_url(), format!("{}/ws", url.replace("http://", "ws://")));
+    let mut events = client.connect_events("u1", &["g1"]).await.unwrap();
+    assert_eq!(events.next().await.unwrap().unwrap(), Event::Json(json!({ "type": "message", "sender_id": "u2", "content": "你好" })));
+    // ping 帧不作为事件返回
+    assert_eq!(events.next().await.unwrap().unwrap(), Event::Text("群聊消息".into()));
+    events.send(&json!({ "type": "voice_call_end", "remote_user_id": "u2" })).await.unwrap();
+    assert!(events.next().await.is_none());
+
+    let (identify, sent) = server.await.unwrap();
+    let identify: Value = serde_json::from_str(&identify).unwrap();
+    assert_eq!(identify, json!({ "type": "identify", "user_id": "u1", "list_of_group_chats": ["g1"] }));
+    assert_eq!(serde_json::from_str::<Value>(&sent).unwrap()["type"], "voice_call_end");
+    assert_eq!(ApiClient::new("https://chat.example.com/").ws_url(), "wss://chat.example.com/ws");
+}
+
+#[tokio::test]
+async fn device_connections_ack_pushes_and_report_why_they_closed() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    // 收下送达确认后模拟同一设备在别处重新连接：服务器以 4001 关闭本连接
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = accept_async(stream).await.unwrap();
+        let identify = socket.next().await.unwrap().unwrap().into_text().unwrap().to_string();
+        let ack = socket.next().await.unwrap().unwrap().into_text().unwrap().to_string();
+        let frame = CloseFrame { code: CLOSE_REPLACED_BY_NEW_LOGIN.into(), reason: "replaced_by_new_login".into() };
+        socket.close(Some(frame)).await.unwrap();
+        (identify, ack)
+    });
+
+    let mut events = ApiClient::new(&url).connect_device_events("u1", "d1", &[]).await.unwrap();
+    events.ack_delivered(&["m1"]).await.unwrap();
+    assert!(events.close_reason().is_none());
+    assert!(events.next().await.is_none());
+    assert_eq!(events.close_reason(), Some((CLOSE_REPLACED_BY_NEW_LOGIN, "replaced_by_new_login")));
+
+    let (identify, ack) = server.await.unwrap();
+    let identify: Value = serde_json::from_str(&identify).unwrap();
+    assert_eq!(identify, json!({ "type": "identify", "user_id": "u1", "device_id": "d1", "list_of_group_chats": [] }));
+    assert_eq!(serde_json::from_str::<Value>(&ack).unwrap(), json!({ "type": "delivered", "message_ids": ["m1"] }));
+}
+
+// 记下升级请求的路径（含查询参数）
+struct RecordPath(tokio::sync::oneshot::Sender<String>);
+
+impl Callback for RecordPath {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        let _ = self.0.send(request.uri().to_string());
+        Ok(response)
+    }
+}
+
+#[tokio::test]
+async fn relay_streams_send_and_receive_binary_chunks() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    // 模拟 /relay/{id}：记下升级请求的路径，回送对方的一块数据，收下客户端的一块后以 4006 结束
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (path_tx, path_rx) = tokio::sync::oneshot::channel();
+        let mut socket = accept_hdr_async(stream, RecordPath(path_tx)).await.unwrap();
+        socket.send(Message::Binary(vec![1u8, 2, 3].into())).await.unwrap();
+        let sent = socket.next().await.unwrap().unwrap().into_data().to_vec();
+        let frame = CloseFrame { code: CLOSE_RELAY_PEER_LEFT.into(), reason: "peer_left".into() };
+        socket.close(Some(frame)).await.unwrap();
+        (path_rx.await.unwrap(), sent)
+    });
+
+    let mut client = ApiClient::new(&url);
+    client.set_token(Some("t1".into()));
+    let mut relay = client.connect_relay("r1").await.unwrap();
+    assert_eq!(relay.next().await.unwrap().unwrap(), [1, 2, 3]);
+    relay.send(&[4, 5]).await.unwrap();
+    assert!(relay.next().await.is_none());
+    assert_eq!(relay.close_reason(), Some((CLOSE_RELAY_PEER_LEFT, "peer_left")));
+
+    let (path, sent) = server.await.unwrap();
+    assert_eq!((path.as_str(), sent), ("/relay/r1?token=t1", vec![4, 5]));
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::mock_server;
+use futures_util::{StreamExt, TryStreamExt};
+use serde_json::{json, Value};
+use yueling_client::ApiClient;
+
+fn message(id: &str) -> Value {
+    json!({
+        "id": id, "sender_id": "u2", "receiver_id": "u1", "content": id, "message_type": "private",
+        "created_at": 100, "status": "sent", "is_read": false, "workspace_id": "default"
+    })
+}
+
+#[tokio::test]
+async fn message_history_follows_next_cursor_until_the_last_page() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取历史消息成功", "messages": [message("m3"), message("m2")], "next_cursor": "100:m2" }).to_string()),
+        (200, json!({ "success": true, "message": "获取历史消息成功", "messages": [message("m1")], "next_cursor": null }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+
+    let ids: Vec<String> = client.message_history("u1", "u2").map_ok(|m| m.id).try_collect().await.unwrap();
+    assert_eq!(ids, ["m3", "m2", "m1"]);
+
+    let requests = server.await.unwrap();
+    let first: Value = serde_json::from_str(&requests[0].body).unwrap();
+    let second: Value = serde_json::from_str(&requests[1].body).unwrap();
+    assert_eq!((first["peer_id"].as_str(), &first["cursor"]), (Some("u2"), &Value::Null));
+    assert_eq!(second["cursor"], "100:m2");
+}
+
+#[tokio::test]
+async fn conversations_and_members_send_the_cursor_as_a_query_parameter() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取会话列表成功", "next_cursor": "200:g1", "conversations": [
+            { "peer_id": "g1", "conversation_type": "group", "last_message_at": 200, "unread_count": 0 }
+        ] }).to_string()),
+        (200, json!({ "success": true, "message": "获取会话列表成功", "next_cursor": null, "conversations": [] }).to_string()),
+        (200, json!({ "success": true, "message": "获取群成员成功", "next_cursor": "1:u1", "members": [
+            { "user_id": "u1", "role": "owner", "joined_at": 1 }
+        ] }).to_string()),
+        (404, json!({ "success": false, "code": "group.not_found", "message": "群聊不存在" }).to_string()),
+    ]).await;
+    let mut client = ApiClient::new(url);
+    client.set_token(Some("t1".into()));
+
+    let peers: Vec<String> = client.conversations().map_ok(|c| c.peer_id).try_collect().await.unwrap();
+    assert_eq!(peers, ["g1"]);
+
+    // 某一页出错时产出错误后结束
+    let members: Vec<_> = client.group_members("g1").collect().await;
+    assert_eq!(members.len(), 2);
+    assert_eq!(members[0].as_ref().unwrap().role, "owner");
+    assert!(members[1].is_err());
+
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("GET /conversations?limit=50 "));
+    assert!(requests[0].head.contains("authorization: Bearer t1"));
+    assert!(requests[1].head.starts_with("GET /conversations?limit=50&cursor=200%3Ag1 "));
+    assert!(requests[3].head.starts_with("GET /groups/g1/members?limit=50&cursor=1%3Au1 "));
+}
+
+#[tokio::test]
+async fn member_search_sends_the_query() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取群成员成功", "next_cursor": null, "members": [
+            { "user_id": "u2", "role": "member", "joined_at": 5, "username": "carol", "display_name": "小卡" }
+        ] }).to_string()),
+    ]).await;
+    let client = ApiClient::new(url);
+
+    let page = client.search_group_members_page("g1", "卡 ol", None, 20).await.unwrap();
+    assert_eq!((page.items[0].username.as_str(), page.items[0].display_name.as_deref()), ("carol", Some("小卡")));
+    assert!(page.next_cursor.is_none());
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("GET /groups/g1/members?limit=20&query=%E5%8D%A1+ol "));
+}
+
+#[tokio::test]
+async fn group_directory_is_searched_by_name() {
+    let (url, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取群目录成功", "groups": [{ "id": "g1", "name": "Rust 学习群", "created_at": 100, "join_approval": true, "member_count": 3 }], "next_cursor": null }).to_string()),
+    ]).await;
+    let page = ApiClient::new(url).discover_groups_page("rust", None, 20).await.unwrap();
+    assert_eq!((page.items[0].id.as_str(), page.items[0].join_approval, page.items[0].member_count), ("g1", true, 3));
+    assert!(page.next_cursor.is_none());
+    assert!(server.await.unwrap()[0].head.starts_with("GET /groups/discover?limit=20&query=rust "));
+}
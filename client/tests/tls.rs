@@ -0,0 +1,141 @@
+mod common;
+
+use std::sync::Arc;
+
+use common::mock_server;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::{crypto::aws_lc_rs, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use yueling_client::{ApiClient, ClientError};
+
+// 测试 CA 签发的 localhost / 127.0.0.1 证书（有效期一百年）
+const CA_PEM: &[u8] = include_bytes!("fixtures/ca.pem");
+const SERVER_PEM: &[u8] = include_bytes!("fixtures/server.pem");
+const SERVER_KEY: &[u8] = include_bytes!("fixtures/server.key");
+const SERVER_FINGERPRINT: &str = "AA:44:70:88:CF:A8:4F:10:C8:0F:73:D0:43:53:9F:6B:09:83:F9:27:C6:D3:AE:C2:F0:DC:7C:CE:B6:B4:DF:63";
+
+fn acceptor() -> TlsAcceptor {
+    let certs = CertificateDer::pem_slice_iter(SERVER_PEM).collect::<Result<Vec<_>, _>>().unwrap();
+    let key = PrivateKeyDer::from_pem_slice(SERVER_KEY).unwrap();
+    let config = ServerConfig::builder_with_provider(Arc::new(aws_lc_rs::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .unwrap();
+    TlsAcceptor::from(Arc::new(config))
+}
+
+// 一直应答的 HTTPS 服务器，每个请求都返回空的未读消息列表；握手失败的连接直接丢弃
+async fn tls_server() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let acceptor = acceptor();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(mut stream) = acceptor.accept(stream).await else {
+                    return;
+                };
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                while !buf.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                let body = json!({ "success": true, "message": "获取未读消息成功", "messages": [] }).to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body,
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            });
+        }
+    });
+    port
+}
+
+#[tokio::test]
+async fn pinned_fingerprint_accepts_only_the_matching_certificate() {
+    let port = tls_server().await;
+
+    // 只固定指纹时不校验证书链和主机名
+    let client = ApiClient::builder(format!("https://127.0.0.1:{}", port)).pin_sha256(SERVER_FINGERPRINT).build().unwrap();
+    assert!(client.unread_messages("u1").await.unwrap().is_empty());
+
+    let other = "00".repeat(32);
+    let client = ApiClient::builder(format!("https://127.0.0.1:{}", port)).pin_sha256(other).build().unwrap();
+    assert!(matches!(client.unread_messages("u1").await, Err(ClientError::Http(_))));
+
+    let err = ApiClient::builder("https://localhost").pin_sha256("not-a-fingerprint").build().err().unwrap();
+    assert!(matches!(err, ClientError::Config(_)));
+}
+
+#[tokio::test]
+async fn custom_ca_is_trusted_instead_of_the_system_roots() {
+    let port = tls_server().await;
+    let url = format!("https://localhost:{}", port);
+
+    assert!(ApiClient::new(&url).unread_messages("u1").await.is_err());
+    let client = ApiClient::builder(&url).ca_pem(CA_PEM).build().unwrap();
+    assert!(client.unread_messages("u1").await.unwrap().is_empty());
+
+    // CA 和指纹同时配置时两者都要满足
+    let client = ApiClient::builder(&url)
+        .ca_file(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/ca.pem"))
+        .pin_sha256("11".repeat(32))
+        .build()
+        .unwrap();
+    assert!(client.unread_messages("u1").await.is_err());
+
+    let err = ApiClient::builder(&url).ca_pem(b"not a certificate".to_vec()).build().err().unwrap();
+    assert!(matches!(err, ClientError::Config(_)));
+}
+
+#[tokio::test]
+async fn requests_go_through_the_configured_proxy() {
+    let (proxy, server) = mock_server(vec![
+        (200, json!({ "success": true, "message": "获取未读消息成功", "messages": [] }).to_string()),
+    ]).await;
+    let client = ApiClient::builder("http://yueling.test").proxy(proxy).build().unwrap();
+    assert!(client.unread_messages("u1").await.unwrap().is_empty());
+
+    // 经过 HTTP 代理的请求行使用完整地址
+    let requests = server.await.unwrap();
+    assert!(requests[0].head.starts_with("POST http://yueling.test/messages/unread "));
+}
+
+#[cfg(feature = "ws")]
+#[tokio::test]
+async fn websocket_uses_the_same_tls_configuration() {
+    use futures_util::StreamExt;
+    use yueling_client::Event;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let acceptor = acceptor();
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let stream = acceptor.accept(stream).await.unwrap();
+        let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+        let identify = socket.next().await.unwrap().unwrap();
+        futures_util::SinkExt::send(&mut socket, identify).await.unwrap();
+    });
+
+    let client = ApiClient::builder(format!("https://127.0.0.1:{}", port)).pin_sha256(SERVER_FINGERPRINT).build().unwrap();
+    assert_eq!(client.ws_url(), format!("wss://127.0.0.1:{}/ws", port));
+    let mut events = client.connect_events("u1", &[]).await.unwrap();
+    let Some(Ok(Event::Json(identify))) = events.next().await else {
+        panic!("没有收到回显的 identify 帧");
+    };
+    assert_eq!(identify["user_id"], "u1");
+    server.await.unwrap();
+}
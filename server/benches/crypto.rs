@@ -0,0 +1,115 @@
+//! 应用层加密的基准测试：`cargo bench --bench crypto`
+//!
+//! 覆盖账号设置等数据使用的 CryptoService 和按会话派生密钥的消息加密，负载从一条短消息到 1 MiB。
+//! 加密使用 AES-256-GCM（CTR 模式的流密码加 GHASH），aes-gcm 运行时检测 AES-NI 并自动选用硬件实现；
+//! 需要在编译期直接内联硬件指令时使用 `RUSTFLAGS="-C target-cpu=native" cargo bench --bench crypto`，
+//! 对比软件实现时使用 `RUSTFLAGS="--cfg aes_force_soft --cfg polyval_force_soft"`。
+//! message_history 对比读取加密会话历史时开启和关闭解密缓存（`[security] message_cache_bytes`）的开销。
+//! message_payload 对比 1 MiB 消息内容以 base64 文本和以 BLOB 保存的加解密开销，以及从数据库读取并解密的整条路径。
+//! 参考数据见 README 的“加密性能”一节。
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use server::{hardware_accelerated, workspaces::DEFAULT_WORKSPACE, CryptoService, ConversationKeys, DbPool, NewMessage};
+use std::hint::black_box;
+
+const MASTER_KEY: &str = "bench-master-key";
+const SIZES: [usize; 4] = [64, 1024, 64 * 1024, 1024 * 1024];
+
+fn crypto_service(c: &mut Criterion) {
+    // 强制软件实现（--cfg aes_force_soft）时检测结果仍为 true，以实际编译参数为准
+    eprintln!("CPU 支持 AES 硬件指令: {}", hardware_accelerated());
+    let crypto = CryptoService::new(MASTER_KEY);
+    let mut group = c.benchmark_group("crypto_service");
+    for size in SIZES {
+        let plaintext = vec![0x5a; size];
+        let sealed = crypto.encrypt(&plaintext, b"user-1");
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::new("encrypt", size), &plaintext, |b, plaintext| {
+            b.iter(|| crypto.encrypt(black_box(plaintext), b"user-1"))
+        });
+        group.bench_with_input(BenchmarkId::new("decrypt", size), &sealed, |b, sealed| {
+            b.iter(|| crypto.decrypt(black_box(sealed), b"user-1").unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn conversation_keys(c: &mut Criterion) {
+    let keys = ConversationKeys::new(MASTER_KEY, 0);
+    let mut group = c.benchmark_group("conversation_keys");
+    // 消息内容是文本，取短消息和消息长度上限附近两种
+    for size in [64, 16 * 1024] {
+        let content = "月".repeat(size / 3);
+        let sealed = keys.seal("private:a:b", 3, "m1", &content);
+        group.throughput(Throughput::Bytes(content.len() as u64));
+        group.bench_with_input(BenchmarkId::new("seal", size), &content, |b, content| {
+            b.iter(|| keys.seal("private:a:b", 3, "m1", black_box(content)))
+        });
+        group.bench_with_input(BenchmarkId::new("open", size), &sealed, |b, sealed| {
+            b.iter(|| keys.open("private:a:b", "m1", black_box(sealed)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn field_encryption(c: &mut Criterion) {
+    let crypto = CryptoService::new(MASTER_KEY);
+    let sealed = crypto.seal_field("email", "alice@example.com");
+    c.bench_function("seal_field/email", |b| b.iter(|| crypto.seal_field("email", black_box("alice@example.com"))));
+    c.bench_function("open_field/email", |b| b.iter(|| crypto.open_field("email", black_box(&sealed)).unwrap()));
+}
+
+// 反复读取同一段加密的会话历史，对比开启和关闭解密缓存
+fn message_history(c: &mut Criterion) {
+    let mut group = c.benchmark_group("message_history");
+    for cache_bytes in [0, 32 * 1024 * 1024] {
+        let db = DbPool::in_memory().unwrap().with_message_keys(ConversationKeys::new(MASTER_KEY, 0), cache_bytes);
+        let alice = db.register_user("alice", "", "secret").unwrap().id;
+        let bob = db.register_user("bob", "", "secret").unwrap().id;
+        let messages: Vec<_> = (0..200)
+            .map(|i| NewMessage {
+                sender_id: alice.clone(),
+                receiver_id: bob.clone(),
+                content: format!("第 {} 条消息：{}", i, "月".repeat(100)),
+                entities: Vec::new(),
+                message_type: "private".into(),
+            })
+            .collect();
+        db.send_messages_batch(DEFAULT_WORKSPACE, &messages).unwrap();
+        group.bench_function(BenchmarkId::new("sync_200", format!("cache_bytes={}", cache_bytes)), |b| {
+            b.iter(|| db.sync_messages(DEFAULT_WORKSPACE, &bob, 0, 200).unwrap())
+        });
+    }
+    group.finish();
+}
+
+// 1 MiB 的消息内容：旧的 base64 文本格式和 BLOB 格式，以及从数据库读出并解密（不走解密缓存）
+fn message_payload(c: &mut Criterion) {
+    const SIZE: usize = 1024 * 1024;
+    let keys = ConversationKeys::new(MASTER_KEY, 0);
+    let content = "月".repeat(SIZE / 3);
+    let sealed_text = keys.seal("private:a:b", 0, "m1", &content);
+    let sealed_blob = keys.seal_bytes("private:a:b", 0, "m1", content.as_bytes());
+    let mut group = c.benchmark_group("message_payload");
+    group.throughput(Throughput::Bytes(content.len() as u64));
+    group.bench_function(BenchmarkId::new("seal_text", SIZE), |b| b.iter(|| keys.seal("private:a:b", 0, "m1", black_box(&content))));
+    group.bench_function(BenchmarkId::new("seal_blob", SIZE), |b| {
+        b.iter(|| keys.seal_bytes("private:a:b", 0, "m1", black_box(content.as_bytes())))
+    });
+    group.bench_function(BenchmarkId::new("open_text", SIZE), |b| b.iter(|| keys.open("private:a:b", "m1", black_box(&sealed_text)).unwrap()));
+    group.bench_function(BenchmarkId::new("open_blob", SIZE), |b| {
+        b.iter(|| keys.open_bytes("private:a:b", "m1", black_box(&sealed_blob)).unwrap())
+    });
+
+    let db = DbPool::in_memory().unwrap().with_message_keys(ConversationKeys::new(MASTER_KEY, 0), 0);
+    let alice = db.register_user("alice", "", "secret").unwrap().id;
+    let bob = db.register_user("bob", "", "secret").unwrap().id;
+    db.send_message(DEFAULT_WORKSPACE, &alice, &bob, &content, "private").unwrap();
+    group.bench_function(BenchmarkId::new("read_from_db", SIZE), |b| {
+        b.iter(|| db.sync_messages(DEFAULT_WORKSPACE, &bob, 0, 1).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, crypto_service, conversation_keys, field_encryption, message_history, message_payload);
+criterion_main!(benches);
@@ -0,0 +1,7 @@
+// 编译 gRPC 接口定义（使用纯 Rust 的 protox，构建环境不需要安装 protoc）
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=proto");
+    let descriptors = protox::compile(["proto/yueling.proto"], ["proto"])?;
+    tonic_prost_build::configure().compile_fds(descriptors)?;
+    Ok(())
+}
@@ -0,0 +1,28 @@
+//! 对任意字节调用解密：只允许返回错误，不能崩溃、越界或长时间运行
+//!
+//! 第一个字节决定附加数据的长度，其余为密文；同样的字节也作为字段密文和会话消息密文（文本和 BLOB 两种格式）解析
+#![no_main]
+
+use std::sync::LazyLock;
+
+use base64::{engine::general_purpose, Engine};
+use libfuzzer_sys::fuzz_target;
+use server::{conversation::SEALED_PREFIX, ConversationKeys, CryptoService, SEALED_FIELD_PREFIX};
+
+static CRYPTO: LazyLock<CryptoService> = LazyLock::new(|| CryptoService::new("fuzz-master-key"));
+static KEYS: LazyLock<ConversationKeys> = LazyLock::new(|| ConversationKeys::new("fuzz-master-key", 0));
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&aad_len, rest)) = data.split_first() else {
+        return;
+    };
+    let (aad, ciphertext) = rest.split_at((aad_len as usize).min(rest.len()));
+    let _ = CRYPTO.decrypt(ciphertext, aad);
+
+    let field = format!("{}{}", SEALED_FIELD_PREFIX, general_purpose::URL_SAFE_NO_PAD.encode(ciphertext));
+    let _ = CRYPTO.open_field("email", &field);
+    let message = format!("{}{}", SEALED_PREFIX, general_purpose::STANDARD.encode(ciphertext));
+    let _ = KEYS.open("private:a:b", &String::from_utf8_lossy(aad), &message);
+    // 以 BLOB 保存的消息内容不经过 base64
+    let _ = KEYS.open_bytes("private:a:b", &String::from_utf8_lossy(aad), ciphertext);
+});
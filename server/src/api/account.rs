@@ -0,0 +1,99 @@
+//! 账号邮箱验证：注册或修改邮箱后通过任务队列发送验证邮件，用户凭邮件中的令牌完成验证
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::post,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::error::AppError;
+use crate::storage::jobs::JOB_VERIFY_EMAIL;
+
+// 共享应用状态
+use super::AppState;
+use super::user::SuccessResponse;
+use crate::core::datetime::unix_now;
+
+// 邮箱验证请求体
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
+// 邮箱验证响应体
+#[derive(Serialize)]
+pub struct VerifyEmailResponse {
+    pub success: bool,
+    pub message: String,
+    pub user_id: String,
+}
+
+/// 粗略校验邮箱格式（是否真实可用由验证邮件确认）
+pub(crate) fn check_email(email: &str) -> Result<(), AppError> {
+    let valid = email.len() <= 254
+        && !email.chars().any(char::is_whitespace)
+        && email.split_once('@').is_some_and(|(local, domain)| {
+            !local.is_empty() && domain.contains('.') && !domain.starts_with('.') && !domain.ends_with('.') && !domain.contains('@')
+        });
+    if valid { Ok(()) } else { Err(AppError::InvalidInput("邮箱格式无效".into())) }
+}
+
+/// 开启邮箱验证时把验证邮件加入任务队列
+pub(crate) fn request_email_verification(state: &AppState, user_id: &str) {
+    if state.settings.registration.email_verification {
+        crate::tasks::jobs::enqueue(state, JOB_VERIFY_EMAIL, json!({ "user_id": user_id }));
+    }
+}
+
+/// 开启 require_verified_email 时，未验证邮箱的用户不能发消息
+pub(crate) fn require_verified_email(state: &AppState, user_id: &str) -> Result<(), AppError> {
+    if !state.settings.registration.require_verified_email {
+        return Ok(());
+    }
+    if state.db_pool.needs_email_verification(user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::Forbidden("请先验证邮箱".into()));
+    }
+    Ok(())
+}
+
+// 凭验证邮件中的令牌完成邮箱验证
+pub async fn verify_email_handler(
+    State(state): State<AppState>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> Result<Json<VerifyEmailResponse>, AppError> {
+    let user_id = state.db_pool.verify_email(req.token.trim(), unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::InvalidInput("验证令牌无效或已过期".into()))?;
+
+    Ok(Json(VerifyEmailResponse {
+        success: true,
+        message: "邮箱已验证".into(),
+        user_id,
+    }))
+}
+
+// 重新发送验证邮件（需要登录会话）
+pub async fn resend_verification_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<SuccessResponse>, AppError> {
+    if !state.settings.registration.email_verification {
+        return Err(AppError::InvalidInput("未开启邮箱验证".into()));
+    }
+    let user_id = super::user::session_user(&state, &headers)?;
+    request_email_verification(&state, &user_id);
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "验证邮件已加入发送队列".into(),
+    }))
+}
+
+/// 注册账号相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/verify-email", post(verify_email_handler))
+        .route("/account/verify-email/resend", post(resend_verification_handler))
+}
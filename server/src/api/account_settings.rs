@@ -0,0 +1,111 @@
+//! 账号设置同步：客户端保存任意 JSON 设置，服务器用 CryptoService 加密后落库
+//!
+//! 每次写入都要带上读取时的 version，版本不一致说明其他设备已经修改过，返回 409 让客户端重新读取合并，
+//! 避免多设备同时编辑时互相覆盖
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::crypto::CryptoService;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+// 加密前 JSON 的最大字节数
+const MAX_SETTINGS_BYTES: usize = 64 * 1024;
+
+// 保存账号设置请求体
+#[derive(Deserialize)]
+pub struct PutAccountSettingsRequest {
+    pub version: i64,    // 读取时拿到的版本号，从未保存过为 0
+    pub settings: Value,
+}
+
+// 账号设置响应体
+#[derive(Serialize)]
+pub struct AccountSettingsResponse {
+    pub success: bool,
+    pub message: String,
+    pub version: i64,    // 从未保存过为 0
+    pub settings: Value, // 从未保存过为 null
+}
+
+fn crypto(state: &AppState) -> Result<&CryptoService, AppError> {
+    state.crypto.as_ref()
+        .ok_or_else(|| AppError::Internal("未配置主密钥，无法保存账号设置".into()))
+}
+
+// 获取当前用户的账号设置
+pub async fn get_account_settings_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<AccountSettingsResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let crypto = crypto(&state)?;
+    let stored = state.db_pool.get_account_settings(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let (version, settings) = match stored {
+        None => (0, Value::Null),
+        Some((ciphertext, version)) => {
+            let plaintext = crypto.decrypt(&ciphertext, user_id.as_bytes()).map_err(|e| {
+                println!("解密用户 {} 的账号设置失败: {}", user_id, e);
+                AppError::Internal("账号设置解密失败".into())
+            })?;
+            let settings = serde_json::from_slice(&plaintext)
+                .map_err(|_| AppError::Internal("账号设置解密失败".into()))?;
+            (version, settings)
+        }
+    };
+
+    Ok(Json(AccountSettingsResponse {
+        success: true,
+        message: "获取账号设置成功".into(),
+        version,
+        settings,
+    }))
+}
+
+// 保存当前用户的账号设置（整体替换），version 与服务器上的版本不一致时返回 409
+pub async fn put_account_settings_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<PutAccountSettingsRequest>,
+) -> Result<Json<AccountSettingsResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let crypto = crypto(&state)?;
+    if req.version < 0 {
+        return Err(AppError::InvalidInput("版本号不能为负数".into()));
+    }
+    let plaintext = serde_json::to_vec(&req.settings)
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    if plaintext.len() > MAX_SETTINGS_BYTES {
+        return Err(AppError::InvalidInput(format!("账号设置不能超过 {} 字节", MAX_SETTINGS_BYTES)));
+    }
+
+    let ciphertext = crypto.encrypt(&plaintext, user_id.as_bytes());
+    let saved = state.db_pool.put_account_settings(&user_id, &ciphertext, req.version, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !saved {
+        return Err(AppError::Conflict("账号设置已在其他设备上修改，请重新获取后再保存".into()));
+    }
+
+    Ok(Json(AccountSettingsResponse {
+        success: true,
+        message: "账号设置已保存".into(),
+        version: req.version + 1,
+        settings: req.settings,
+    }))
+}
+
+/// 注册账号设置路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/settings", get(get_account_settings_handler).put(put_account_settings_handler))
+}
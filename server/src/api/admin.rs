@@ -0,0 +1,255 @@
+use axum::{
+    extract::{FromRequestParts, Path as UrlPath, State},
+    http::request::Parts,
+    response::Json,
+    routing::{get, post, put},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use crate::crypto::escrow::{self, KeyringBundle};
+use crate::error::AppError;
+use crate::storage::{cipher, retention::RetentionOverride};
+use crate::tasks::supervisor::TaskStatus;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+/// 管理员身份校验提取器
+///
+/// 请求需携带 `Authorization: Bearer <admin.token>`，配置中令牌为空时管理接口整体禁用
+pub struct AdminAuth;
+
+impl FromRequestParts<AppState> for AdminAuth {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let expected = &state.settings.admin.token;
+        if expected.is_empty() {
+            return Err(AppError::Forbidden("管理接口未启用".into()));
+        }
+
+        if token_matches(expected, super::user::bearer_token(&parts.headers)) {
+            Ok(AdminAuth)
+        } else {
+            Err(AppError::InvalidCredentials("管理令牌无效".into()))
+        }
+    }
+}
+
+/// 以常量时间比较请求携带的令牌与配置的令牌，避免通过响应耗时逐字节猜出令牌；
+/// 配置的令牌为空时任何令牌都不匹配
+pub(crate) fn token_matches(expected: &str, provided: Option<&str>) -> bool {
+    match provided {
+        Some(token) if !expected.is_empty() => bool::from(token.as_bytes().ct_eq(expected.as_bytes())),
+        _ => false,
+    }
+}
+
+// 备份响应体
+#[derive(Serialize)]
+pub struct BackupResponse {
+    pub success: bool,
+    pub message: String,
+    pub path: Option<String>,
+}
+
+// 立即执行一次数据库备份
+pub async fn backup_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<BackupResponse>, AppError> {
+    let key = cipher::resolve_key(&state.settings).map_err(AppError::Internal)?;
+    let path = crate::tasks::backup::run_once(&state.db_pool, &state.settings.backup, key)
+        .await
+        .map_err(|e| AppError::Internal(format!("备份失败: {}", e)))?;
+
+    Ok(Json(BackupResponse {
+        success: true,
+        message: "备份成功".into(),
+        path: Some(path.display().to_string()),
+    }))
+}
+
+// 保留期覆盖请求体
+#[derive(Deserialize)]
+pub struct RetentionOverrideRequest {
+    pub message_days: i64, // 0 表示永久保留
+}
+
+// 保留期覆盖列表响应体
+#[derive(Serialize)]
+pub struct RetentionOverridesResponse {
+    pub success: bool,
+    pub message: String,
+    pub overrides: Vec<RetentionOverride>,
+}
+
+// 通用成功响应体
+#[derive(Serialize)]
+pub struct AdminResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+// 列出会话保留期覆盖
+pub async fn list_retention_overrides_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<RetentionOverridesResponse>, AppError> {
+    let overrides = state.db_pool.list_retention_overrides()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(RetentionOverridesResponse {
+        success: true,
+        message: "获取保留策略成功".into(),
+        overrides,
+    }))
+}
+
+// 设置会话（群聊）的保留期覆盖
+pub async fn set_retention_override_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(conversation_id): UrlPath<String>,
+    Json(req): Json<RetentionOverrideRequest>,
+) -> Result<Json<AdminResponse>, AppError> {
+    if req.message_days < 0 {
+        return Err(AppError::InvalidInput("保留天数不能为负数".into()));
+    }
+    state.db_pool.set_retention_override(&conversation_id, req.message_days)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "保留策略已更新".into(),
+    }))
+}
+
+// 删除会话的保留期覆盖
+pub async fn remove_retention_override_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(conversation_id): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let removed = state.db_pool.remove_retention_override(&conversation_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !removed {
+        return Err(AppError::NotFound("该会话没有单独的保留策略".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "保留策略已恢复默认".into(),
+    }))
+}
+
+// 导出密钥包请求体
+#[derive(Deserialize)]
+pub struct ExportKeyringRequest {
+    pub passphrase: String,
+}
+
+// 导出密钥包响应体
+#[derive(Serialize)]
+pub struct ExportKeyringResponse {
+    pub success: bool,
+    pub message: String,
+    pub keyring: KeyringBundle,
+}
+
+// 校验密钥包请求体
+#[derive(Deserialize)]
+pub struct VerifyKeyringRequest {
+    pub keyring: KeyringBundle,
+    pub passphrase: String,
+}
+
+// 校验密钥包响应体
+#[derive(Serialize)]
+pub struct VerifyKeyringResponse {
+    pub success: bool,
+    pub message: String,
+    pub fingerprint: String,
+    pub matches_current: bool, // 是否与本机正在使用的主密钥相同
+}
+
+// 用口令包装主密钥导出为密钥包（在新主机上用 import-keyring 命令恢复）
+pub async fn export_keyring_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<ExportKeyringRequest>,
+) -> Result<Json<ExportKeyringResponse>, AppError> {
+    let master_key = &state.settings.security.master_key;
+    if master_key.is_empty() {
+        return Err(AppError::InvalidInput("未配置主密钥，没有可导出的密钥".into()));
+    }
+    let now = unix_now();
+    let passphrase = req.passphrase;
+    let master_key = master_key.clone();
+    // Argon2 派生耗时较长，放到阻塞线程池
+    let keyring = tokio::task::spawn_blocking(move || escrow::export(&master_key, &passphrase, now))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::InvalidInput(e.to_string()))?;
+
+    Ok(Json(ExportKeyringResponse {
+        success: true,
+        message: "密钥包已导出，请连同口令妥善保管".into(),
+        keyring,
+    }))
+}
+
+// 校验密钥包能否用口令解开，并与本机主密钥比对
+//
+// 主密钥在启动时读取，运行中的服务器不能替换；在新主机上恢复请使用 import-keyring 命令
+pub async fn verify_keyring_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<VerifyKeyringRequest>,
+) -> Result<Json<VerifyKeyringResponse>, AppError> {
+    let VerifyKeyringRequest { keyring, passphrase } = req;
+    let master_key = tokio::task::spawn_blocking(move || escrow::import(&keyring, &passphrase))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::InvalidInput(e.to_string()))?;
+
+    Ok(Json(VerifyKeyringResponse {
+        success: true,
+        message: "密钥包校验通过".into(),
+        fingerprint: escrow::fingerprint(&master_key),
+        matches_current: master_key == state.settings.security.master_key,
+    }))
+}
+
+// 后台任务列表响应体
+#[derive(Serialize)]
+pub struct TasksResponse {
+    pub success: bool,
+    pub message: String,
+    pub tasks: Vec<TaskStatus>,
+}
+
+// 查看本实例上各个后台任务的运行状态和最近一次崩溃
+pub async fn list_tasks_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<TasksResponse>, AppError> {
+    Ok(Json(TasksResponse {
+        success: true,
+        message: "获取后台任务成功".into(),
+        tasks: state.tasks.statuses(),
+    }))
+}
+
+/// 注册管理相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/backup", post(backup_handler))
+        .route("/admin/retention", get(list_retention_overrides_handler))
+        .route("/admin/retention/{conversation_id}", put(set_retention_override_handler).delete(remove_retention_override_handler))
+        .route("/admin/keyring/export", post(export_keyring_handler))
+        .route("/admin/keyring/verify", post(verify_keyring_handler))
+        .route("/admin/tasks", get(list_tasks_handler))
+}
@@ -0,0 +1,545 @@
+//! 聊天附件：登录用户用 multipart 上传文件，拿到附件ID后放进消息内容里发送；
+//! 下载支持 `Range` 请求，客户端中断后可以从已下载的位置继续；
+//! 也可以签发带过期时间的 HMAC 签名链接，`<img>` 和链接预览不带会话令牌也能下载
+//!
+//! 上传和下载都边读边写，不把整个文件放进内存；大小上限默认由 [attachments] max_bytes 控制，
+//! 管理员可以为单个用户覆盖，用户的附件总量另受 [quotas] max_storage_bytes 限制
+//!
+//! 文件按内容的带密钥哈希（HMAC-SHA256）命名，同一文件转发到多个会话时只保存一份；
+//! 每次上传仍是独立的附件记录并计入上传者的用量，没有附件记录引用的文件由回收任务删除
+//!
+//! 配置了 [scan] 时文件写完后先扫描再登记：检出病毒的按 action 拒绝上传或隔离，扫描失败的一律隔离；
+//! 隔离的附件不能下载，由管理员审核后放行或删除
+//!
+//! 以图片上传的 JPEG、PNG 和 WebP 默认在登记前去掉 EXIF 等元数据（见 core::media），避免照片泄露拍摄位置
+
+use std::io::{BufRead, ErrorKind, Read, SeekFrom, Write};
+use std::path::{Path as FilePath, PathBuf};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    response::{IntoResponse, Json, Response},
+    routing::{delete, get, post},
+    Router
+};
+use futures_util::stream;
+use hmac::{Hmac, Mac};
+use http::{header, HeaderMap, HeaderValue, StatusCode};
+use mime_guess::from_path;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+use crate::core::media::{self, ImageFormat};
+use crate::error::AppError;
+use crate::scan::{Verdict, ACTION_REJECT};
+use crate::storage::attachments::{Attachment, SCAN_CLEAN, SCAN_QUARANTINED, SCAN_UNSCANNED};
+use crate::tasks::attachment_gc::{self, GcReport};
+
+// 共享应用状态
+use super::AppState;
+use super::admin::AdminAuth;
+use super::user::SuccessResponse;
+use crate::core::datetime::unix_now;
+
+// 下载时每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// 上传附件响应体
+#[derive(Serialize)]
+pub struct AttachmentResponse {
+    pub success: bool,
+    pub message: String,
+    pub attachment: Attachment,
+}
+
+pub(super) fn attachment_path(state: &AppState, blob: &str) -> PathBuf {
+    FilePath::new(&state.settings.attachments.dir).join(blob)
+}
+
+// 把写完的临时文件放到共用的文件名下：内容相同的文件已经存在时丢弃临时文件
+fn place_blob(temp: &FilePath, blob: &FilePath) -> std::io::Result<()> {
+    if blob.exists() {
+        std::fs::remove_file(temp)
+    } else {
+        std::fs::rename(temp, blob)
+    }
+}
+
+// 只保留文件名部分，客户端传来的路径不能影响保存位置
+fn clean_filename(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() { "attachment".into() } else { name.to_string() }
+}
+
+// 把 multipart 字段写入文件，超过 max_bytes 时报错，返回 (字节数, SHA-256, 以 blob_key 计算的 HMAC)
+async fn write_field(
+    field: &mut axum::extract::multipart::Field<'_>,
+    path: &FilePath,
+    max_bytes: u64,
+    blob_key: &[u8],
+) -> Result<(u64, String, String), AppError> {
+    let mut file = File::create(path).await.map_err(|e| AppError::Internal(e.to_string()))?;
+    let mut hasher = Sha256::new();
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(blob_key).expect("HMAC 接受任意长度的密钥");
+    let mut size = 0u64;
+    while let Some(chunk) = field.chunk().await.map_err(|e| AppError::InvalidInput(e.to_string()))? {
+        size += chunk.len() as u64;
+        if size > max_bytes {
+            return Err(AppError::InvalidInput(format!("附件不能超过 {} 字节", max_bytes)));
+        }
+        hasher.update(&chunk);
+        mac.update(&chunk);
+        file.write_all(&chunk).await.map_err(|e| AppError::Internal(e.to_string()))?;
+    }
+    file.flush().await.map_err(|e| AppError::Internal(e.to_string()))?;
+    Ok((size, hex::encode(hasher.finalize()), hex::encode(mac.finalize().into_bytes())))
+}
+
+// 重新计算文件的 (字节数, SHA-256, HMAC)
+fn hash_file(path: &FilePath, blob_key: &[u8]) -> std::io::Result<(u64, String, String)> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(blob_key).expect("HMAC 接受任意长度的密钥");
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut size = 0u64;
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            return Ok((size, hex::encode(hasher.finalize()), hex::encode(mac.finalize().into_bytes())));
+        }
+        size += n as u64;
+        hasher.update(&buf[..n]);
+        mac.update(&buf[..n]);
+    }
+}
+
+// 去掉图片的元数据（在阻塞线程池中调用），返回清理后文件的 (字节数, SHA-256, HMAC)；
+// 不是支持的图片格式时返回 None，保留原文件
+fn strip_image_metadata(path: &FilePath, blob_key: &[u8]) -> Result<Option<(u64, String, String)>, AppError> {
+    let internal = |e: std::io::Error| AppError::Internal(e.to_string());
+    let mut input = std::io::BufReader::new(std::fs::File::open(path).map_err(internal)?);
+    let Some(format) = ImageFormat::sniff(input.fill_buf().map_err(internal)?) else {
+        return Ok(None);
+    };
+    let stripped = path.with_extension("stripped");
+    let mut output = std::io::BufWriter::new(std::fs::File::create(&stripped).map_err(internal)?);
+    let result = media::strip_metadata(format, &mut input, &mut output).and_then(|()| output.flush());
+    drop(output);
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&stripped);
+        return Err(match e.kind() {
+            ErrorKind::InvalidData | ErrorKind::UnexpectedEof => AppError::InvalidInput("图片文件无效或已损坏".into()),
+            _ => internal(e),
+        });
+    }
+    std::fs::rename(&stripped, path).map_err(internal)?;
+    hash_file(path, blob_key).map(Some).map_err(internal)
+}
+
+// 扫描写完的临时文件，返回 (扫描状态, 隔离原因)；检出病毒且配置为拒绝时删除临时文件并报错
+async fn scan_upload(state: &AppState, path: &FilePath) -> Result<(&'static str, String), AppError> {
+    let Some(scanner) = &state.scanner else {
+        return Ok((SCAN_UNSCANNED, String::new()));
+    };
+    match scanner.scan(path).await {
+        Ok(Verdict::Clean) => Ok((SCAN_CLEAN, String::new())),
+        Ok(Verdict::Infected(name)) if state.settings.scan.action == ACTION_REJECT => {
+            let _ = tokio::fs::remove_file(path).await;
+            Err(AppError::InvalidInput(format!("附件未通过病毒扫描: {}", name)))
+        }
+        Ok(Verdict::Infected(name)) => Ok((SCAN_QUARANTINED, name)),
+        Err(e) => {
+            println!("扫描附件失败，已隔离: {}", e);
+            Ok((SCAN_QUARANTINED, format!("扫描失败: {}", e)))
+        }
+    }
+}
+
+// 把 multipart 中名为 file 的字段保存为 uploader_id 的附件：单个文件超过 max_bytes（0 为不限制）、
+// 上传者的附件总量超过 max_storage_bytes（0 为不限制）或类型不是 content_type_prefix 开头时报错
+pub(super) async fn save_upload(
+    state: &AppState,
+    multipart: &mut Multipart,
+    uploader_id: &str,
+    max_bytes: u64,
+    max_storage_bytes: i64,
+    content_type_prefix: &str,
+) -> Result<Attachment, AppError> {
+    let max_bytes = if max_bytes > 0 { max_bytes } else { u64::MAX };
+    tokio::fs::create_dir_all(&state.settings.attachments.dir).await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+    let blob_key = state.db_pool.attachment_blob_key()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    while let Some(mut field) = multipart.next_field().await.map_err(|e| AppError::InvalidInput(e.to_string()))? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        let filename = clean_filename(field.file_name().unwrap_or_default());
+        let content_type = field.content_type()
+            .map(str::to_string)
+            .unwrap_or_else(|| from_path(&filename).first_or_octet_stream().to_string());
+        if !content_type.starts_with(content_type_prefix) {
+            return Err(AppError::InvalidInput(format!("附件类型必须是 {}*", content_type_prefix)));
+        }
+        let id = Uuid::new_v4().to_string();
+        // 先写到临时文件，算出内容的哈希后再放到共用的文件名下；残留的临时文件由回收任务删除
+        let path = attachment_path(state, &format!(".upload-{}", id));
+
+        let (size, sha256, blob) = match write_field(&mut field, &path, max_bytes, &blob_key).await {
+            Ok(written) => written,
+            Err(e) => {
+                // 写了一半的文件不保留
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(e);
+            }
+        };
+        // 以图片上传的先去掉元数据，保存和扫描的都是清理后的文件
+        let (size, sha256, blob) = if state.settings.attachments.strip_image_metadata && content_type.starts_with("image/") {
+            let (temp, key) = (path.clone(), blob_key.clone());
+            let stripped = tokio::task::spawn_blocking(move || strip_image_metadata(&temp, &key)).await
+                .map_err(|e| AppError::Internal(e.to_string()))
+                .and_then(|stripped| stripped);
+            match stripped {
+                Ok(Some(hashed)) => hashed,
+                Ok(None) => (size, sha256, blob),
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&path).await;
+                    return Err(e);
+                }
+            }
+        } else {
+            (size, sha256, blob)
+        };
+        let (scan_status, scan_detail) = scan_upload(state, &path).await?;
+        let blob_path = attachment_path(state, &blob);
+        let attachment = Attachment {
+            id,
+            uploader_id: uploader_id.to_string(),
+            filename,
+            content_type,
+            size: size as i64,
+            sha256,
+            created_at: unix_now(),
+            blob,
+            scan_status: scan_status.into(),
+            scan_detail,
+        };
+        match state.db_pool.insert_attachment(&attachment, max_storage_bytes, || place_blob(&path, &blob_path)) {
+            Ok(true) => {}
+            Ok(false) => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(AppError::InvalidInput(format!("附件存储空间不足，上限为 {} 字节", max_storage_bytes)));
+            }
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(AppError::Database(e.to_string()));
+            }
+        }
+        return Ok(attachment);
+    }
+
+    Err(AppError::InvalidInput("未找到附件文件".into()))
+}
+
+// 上传附件：multipart 中名为 file 的字段
+pub async fn upload_attachment_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<AttachmentResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let quota = super::quota::quota_for(&state, &user_id, None)?;
+    // 0 表示不限制单个附件的大小
+    let max_bytes = quota.max_attachment_bytes.max(0) as u64;
+    let attachment = save_upload(&state, &mut multipart, &user_id, max_bytes, quota.max_storage_bytes, "").await?;
+
+    Ok(Json(AttachmentResponse {
+        success: true,
+        message: "附件上传成功".into(),
+        attachment,
+    }))
+}
+
+// 解析 Range: bytes=start-、bytes=start-end 或 bytes=-suffix（最后 suffix 个字节，播放器常用来读取
+// 放在文件末尾的索引），只支持单个区间，返回闭区间；格式不认识时返回 None，按完整下载处理
+fn parse_range(value: &str, size: u64) -> Option<Result<(u64, u64), ()>> {
+    let (start, end) = value.strip_prefix("bytes=")?.split_once('-')?;
+    if start.trim().is_empty() {
+        let suffix: u64 = end.trim().parse().ok()?;
+        return Some(if suffix == 0 || size == 0 { Err(()) } else { Ok((size - suffix.min(size), size - 1)) });
+    }
+    let start: u64 = start.trim().parse().ok()?;
+    let end = match end.trim() {
+        "" => size.saturating_sub(1),
+        end => end.parse::<u64>().ok()?.min(size.saturating_sub(1)),
+    };
+    Some(if start >= size || start > end { Err(()) } else { Ok((start, end)) })
+}
+
+// 把 Content-Disposition 中的文件名按 RFC 5987 编码
+pub(super) fn encode_filename(name: &str) -> String {
+    name.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+// 签名下载链接的查询参数
+#[derive(Deserialize)]
+pub struct SignedUrlQuery {
+    pub expires: Option<i64>,
+    pub signature: Option<String>,
+}
+
+// 签名下载链接响应体
+#[derive(Serialize)]
+pub struct SignedUrlResponse {
+    pub success: bool,
+    pub message: String,
+    pub url: String,          // 相对于服务器地址的下载路径
+    pub expires_at: i64,
+}
+
+// 附件ID和过期时间的 HMAC
+fn url_mac(key: &[u8], id: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC 接受任意长度的密钥");
+    mac.update(format!("{}:{}", id, expires).as_bytes());
+    mac
+}
+
+// 校验签名下载链接，返回距过期的秒数
+fn verify_signed_url(state: &AppState, id: &str, expires: i64, signature: &str) -> Result<i64, AppError> {
+    if state.settings.attachments.signed_url_ttl_secs == 0 {
+        return Err(AppError::Forbidden("下载链接无效".into()));
+    }
+    let key = state.db_pool.attachment_url_key()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let signature = hex::decode(signature).map_err(|_| AppError::Forbidden("下载链接无效".into()))?;
+    url_mac(&key, id, expires).verify_slice(&signature)
+        .map_err(|_| AppError::Forbidden("下载链接无效".into()))?;
+    let remaining = expires - unix_now();
+    if remaining <= 0 {
+        return Err(AppError::Forbidden("下载链接已过期".into()));
+    }
+    Ok(remaining)
+}
+
+// 只有能看到附件的用户（见 DbPool::can_read_attachment）可以下载或签发链接，其他人看到的与附件不存在相同
+fn require_readable(state: &AppState, id: &str, user_id: &str) -> Result<(), AppError> {
+    let readable = state.db_pool.can_read_attachment(id, user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if readable { Ok(()) } else { Err(AppError::NotFound("附件不存在".into())) }
+}
+
+// 为附件签发有效期为 signed_url_ttl_secs 的下载链接，持有链接即可下载，不需要会话令牌
+pub async fn sign_attachment_url_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<SignedUrlResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let ttl = state.settings.attachments.signed_url_ttl_secs;
+    if ttl == 0 {
+        return Err(AppError::Forbidden("未开启签名下载链接".into()));
+    }
+    require_readable(&state, &id, &user_id)?;
+    let attachment = state.db_pool.get_attachment(&id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("附件不存在".into()))?;
+    if attachment.scan_status == SCAN_QUARANTINED {
+        return Err(AppError::Forbidden("附件已被隔离，等待管理员审核".into()));
+    }
+    let key = state.db_pool.attachment_url_key()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let expires_at = unix_now() + ttl as i64;
+    let signature = hex::encode(url_mac(&key, &attachment.id, expires_at).finalize().into_bytes());
+
+    Ok(Json(SignedUrlResponse {
+        success: true,
+        message: "下载链接已生成".into(),
+        url: format!("/attachments/{}?expires={}&signature={}", attachment.id, expires_at, signature),
+        expires_at,
+    }))
+}
+
+// 下载附件，带 Range 时返回 206 和对应区间；If-Range 与 ETag 不符（文件已变化）时返回完整文件。
+// 带 expires 和 signature 的签名链接不需要会话令牌
+pub async fn download_attachment_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<SignedUrlQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let signed_for = match (query.expires, &query.signature) {
+        (Some(expires), Some(signature)) => Some(verify_signed_url(&state, &id, expires, signature)?),
+        _ => {
+            let user_id = super::user::session_user(&state, &headers)?;
+            require_readable(&state, &id, &user_id)?;
+            None
+        }
+    };
+    let attachment = state.db_pool.get_attachment(&id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("附件不存在".into()))?;
+    if attachment.scan_status == SCAN_QUARANTINED {
+        return Err(AppError::Forbidden("附件已被隔离，等待管理员审核".into()));
+    }
+    let size = attachment.size as u64;
+    // ETag 用带密钥的文件名哈希，不暴露文件内容的 SHA-256
+    let etag = format!("\"{}\"", attachment.blob);
+
+    let if_range_matches = headers.get(header::IF_RANGE)
+        .is_none_or(|value| value.to_str().is_ok_and(|value| value == etag));
+    let range = headers.get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| if_range_matches)
+        .and_then(|value| parse_range(value, size));
+    let (status, start, end) = match range {
+        Some(Ok((start, end))) => (StatusCode::PARTIAL_CONTENT, start, end),
+        Some(Err(())) => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", size))],
+            ).into_response());
+        }
+        None => (StatusCode::OK, 0, size.saturating_sub(1)),
+    };
+    let length = if size == 0 { 0 } else { end - start + 1 };
+
+    let mut file = File::open(attachment_path(&state, &attachment.blob)).await
+        .map_err(|_| AppError::NotFound("附件不存在".into()))?;
+    file.seek(SeekFrom::Start(start)).await.map_err(|e| AppError::Internal(e.to_string()))?;
+    let body = Body::from_stream(stream::try_unfold(file.take(length), |mut reader| async move {
+        let mut buf = vec![0u8; CHUNK_SIZE];
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            return Ok::<_, std::io::Error>(None);
+        }
+        buf.truncate(n);
+        Ok(Some((Bytes::from(buf), reader)))
+    }));
+
+    let mut response = (status, body).into_response();
+    let response_headers = response.headers_mut();
+    let header_value = |value: String| HeaderValue::from_str(&value).map_err(|e| AppError::Internal(e.to_string()));
+    response_headers.insert(header::CONTENT_TYPE, header_value(attachment.content_type.clone())?);
+    response_headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+    response_headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    response_headers.insert(header::ETAG, header_value(etag)?);
+    response_headers.insert(
+        header::CONTENT_DISPOSITION,
+        header_value(format!("attachment; filename*=UTF-8''{}", encode_filename(&attachment.filename)))?,
+    );
+    if let Some(remaining) = signed_for {
+        // 链接过期前浏览器可以缓存，但共享缓存不能保存
+        response_headers.insert(header::CACHE_CONTROL, header_value(format!("private, max-age={}", remaining))?);
+    }
+    if status == StatusCode::PARTIAL_CONTENT {
+        response_headers.insert(header::CONTENT_RANGE, header_value(format!("bytes {}-{}/{}", start, end, size))?);
+    }
+    Ok(response)
+}
+
+// 附件回收响应体
+#[derive(Serialize)]
+pub struct GcResponse {
+    pub success: bool,
+    pub message: String,
+    pub report: GcReport,
+}
+
+// 立即执行一轮附件回收
+pub async fn collect_attachments_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<GcResponse>, AppError> {
+    let report = attachment_gc::collect(&state.db_pool, &state.settings.attachments).await
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(GcResponse {
+        success: true,
+        message: "附件回收完成".into(),
+        report,
+    }))
+}
+
+// 隔离附件列表响应体
+#[derive(Serialize)]
+pub struct QuarantineResponse {
+    pub success: bool,
+    pub message: String,
+    pub attachments: Vec<Attachment>,
+}
+
+// 等待审核的隔离附件
+pub async fn list_quarantine_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<QuarantineResponse>, AppError> {
+    let attachments = state.db_pool.quarantined_attachments()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(QuarantineResponse {
+        success: true,
+        message: "获取隔离附件成功".into(),
+        attachments,
+    }))
+}
+
+// 审核后放行隔离的附件（如误报），之后可以正常下载
+pub async fn release_quarantined_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let released = state.db_pool.release_attachment(&id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !released {
+        return Err(AppError::NotFound("隔离区中没有该附件".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "附件已放行".into(),
+    }))
+}
+
+// 删除隔离的附件；没有其他附件共用时同时删除文件
+pub async fn delete_quarantined_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let db_pool = state.db_pool.clone();
+    let dir = FilePath::new(&state.settings.attachments.dir).to_path_buf();
+    let deleted = tokio::task::spawn_blocking(move || {
+        db_pool.delete_quarantined_attachment(&id, |blob| attachment_gc::remove_file(&dir.join(blob)))
+    }).await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if deleted.is_none() {
+        return Err(AppError::NotFound("隔离区中没有该附件".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "隔离的附件已删除".into(),
+    }))
+}
+
+/// 注册附件相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        // 上传大小由 [attachments] max_bytes 在写入时检查，不受默认的请求体上限约束
+        .route("/attachments", post(upload_attachment_handler).layer(DefaultBodyLimit::disable()))
+        .route("/attachments/{attachment_id}", get(download_attachment_handler))
+        .route("/attachments/{attachment_id}/url", post(sign_attachment_url_handler))
+        .route("/admin/attachments/gc", post(collect_attachments_handler))
+        .route("/admin/attachments/quarantine", get(list_quarantine_handler))
+        .route("/admin/attachments/quarantine/{attachment_id}", delete(delete_quarantined_handler))
+        .route("/admin/attachments/quarantine/{attachment_id}/release", post(release_quarantined_handler))
+}
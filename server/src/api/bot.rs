@@ -0,0 +1,336 @@
+use axum::{
+    extract::{FromRequestParts, Path as UrlPath, Query, State},
+    http::request::Parts,
+    response::Json,
+    routing::{delete, get, post, put},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::error::AppError;
+use crate::storage::{
+    bots::{AuthenticatedKey, Bot, BOT_SCOPES, SCOPE_READ, SCOPE_SEND},
+    system_messages::SystemEvent,
+    Message
+};
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use super::workspace::WorkspaceScope;
+use crate::core::datetime::unix_now;
+
+/// 机器人身份校验提取器
+///
+/// 请求需携带 `Authorization: Bearer <API 密钥>`，每个密钥单独限流
+pub struct BotAuth(pub AuthenticatedKey);
+
+impl FromRequestParts<AppState> for BotAuth {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let provided = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or_else(|| AppError::InvalidCredentials("缺少 API 密钥".into()))?;
+
+        let key = state.db_pool.authenticate_bot_key(provided, unix_now())
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .ok_or_else(|| AppError::InvalidCredentials("API 密钥无效或已吊销".into()))?;
+
+        state.rate_limits.check_request(super::rate_limit::Tier::Bot, &key.key_id)
+            .map_err(|retry_after| AppError::RateLimited(format!("请在 {} 秒后重试", retry_after)))?;
+        Ok(BotAuth(key))
+    }
+}
+
+impl BotAuth {
+    // 校验密钥权限范围
+    fn require_scope(&self, scope: &str) -> Result<(), AppError> {
+        if self.0.scope == scope {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!("该密钥没有 {} 权限", scope)))
+        }
+    }
+}
+
+// 创建机器人请求体
+#[derive(Deserialize)]
+pub struct CreateBotRequest {
+    pub name: String,
+    pub scope: String, // "send" 或 "read"
+}
+
+// 签发密钥请求体
+#[derive(Deserialize)]
+pub struct IssueKeyRequest {
+    pub scope: String,
+}
+
+// 签发密钥响应体（api_key 只返回这一次）
+#[derive(Serialize)]
+pub struct IssueKeyResponse {
+    pub success: bool,
+    pub message: String,
+    pub bot_id: String,
+    pub key_id: String,
+    pub api_key: String,
+}
+
+// 机器人列表响应体
+#[derive(Serialize)]
+pub struct BotsResponse {
+    pub success: bool,
+    pub message: String,
+    pub bots: Vec<Bot>,
+}
+
+// 机器人发送消息请求体
+#[derive(Deserialize)]
+pub struct BotSendMessageRequest {
+    pub receiver_id: String,
+    pub content: String,
+    pub message_type: String, // "private"或"group"
+}
+
+// 机器人发送消息响应体
+#[derive(Serialize)]
+pub struct BotSendMessageResponse {
+    pub success: bool,
+    pub message: String,
+    pub message_id: Option<String>,
+}
+
+// 机器人读取消息的查询参数（peer_id 为用户或群ID）
+#[derive(Deserialize)]
+pub struct BotMessagesQuery {
+    pub peer_id: String,
+    pub before: Option<i64>,
+    pub cursor: Option<String>, // 上一页返回的 next_cursor，优先于 before
+    pub limit: Option<i64>,
+}
+
+// 机器人读取消息响应体
+#[derive(Serialize)]
+pub struct BotMessagesResponse {
+    pub success: bool,
+    pub message: String,
+    pub messages: Vec<Message>,
+    pub next_cursor: Option<String>, // 为空表示没有更早的消息
+}
+
+fn validate_scope(scope: &str) -> Result<(), AppError> {
+    if BOT_SCOPES.contains(&scope) {
+        Ok(())
+    } else {
+        Err(AppError::InvalidInput(format!("不支持的权限范围 {}，可选: {}", scope, BOT_SCOPES.join(", "))))
+    }
+}
+
+// 创建机器人并签发第一个密钥
+pub async fn create_bot_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<CreateBotRequest>,
+) -> Result<Json<IssueKeyResponse>, AppError> {
+    validate_scope(&req.scope)?;
+    let (bot_id, key) = state.db_pool.create_bot(&req.name, &req.scope, unix_now())
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                AppError::UserExists("该名称已被使用".into()),
+            _ => AppError::Database(e.to_string()),
+        })?;
+
+    Ok(Json(IssueKeyResponse {
+        success: true,
+        message: "机器人已创建".into(),
+        bot_id,
+        key_id: key.key_id,
+        api_key: key.api_key,
+    }))
+}
+
+// 列出机器人
+pub async fn list_bots_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<BotsResponse>, AppError> {
+    let bots = state.db_pool.list_bots()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(BotsResponse {
+        success: true,
+        message: "获取机器人成功".into(),
+        bots,
+    }))
+}
+
+// 为机器人签发新密钥
+pub async fn issue_key_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(bot_id): UrlPath<String>,
+    Json(req): Json<IssueKeyRequest>,
+) -> Result<Json<IssueKeyResponse>, AppError> {
+    validate_scope(&req.scope)?;
+    let key = state.db_pool.issue_bot_key(&bot_id, &req.scope, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("机器人不存在".into()))?;
+
+    Ok(Json(IssueKeyResponse {
+        success: true,
+        message: "密钥已签发".into(),
+        bot_id,
+        key_id: key.key_id,
+        api_key: key.api_key,
+    }))
+}
+
+// 吊销密钥
+pub async fn revoke_key_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(key_id): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let revoked = state.db_pool.revoke_bot_key(&key_id, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !revoked {
+        return Err(AppError::NotFound("密钥不存在或已吊销".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "密钥已吊销".into(),
+    }))
+}
+
+// 把机器人加入群聊，之后即可向该群发送消息
+pub async fn join_group_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath((bot_id, group_id)): UrlPath<(String, String)>,
+) -> Result<Json<AdminResponse>, AppError> {
+    if !state.db_pool.user_exists_by_id(&bot_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("机器人不存在".into()));
+    }
+    let joined = state.db_pool.add_group_member(&group_id, &bot_id, "member")
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::ConstraintViolation =>
+                AppError::NotFound("群聊不存在".into()),
+            _ => AppError::Database(e.to_string()),
+        })?;
+    // 群成员在群聊中看到机器人加入的系统消息
+    if joined {
+        let workspace_id = state.db_pool.group_workspace(&group_id)
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .ok_or_else(|| AppError::NotFound("群聊不存在".into()))?;
+        super::message::post_system_message(&state, &workspace_id, &group_id, SystemEvent::MemberJoined { user_id: bot_id })?;
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "机器人已加入群聊".into(),
+    }))
+}
+
+// 机器人发送消息：私聊直接发给用户，群聊要求机器人已是群成员
+pub async fn bot_send_message_handler(
+    auth: BotAuth,
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    Json(req): Json<BotSendMessageRequest>,
+) -> Result<Json<BotSendMessageResponse>, AppError> {
+    auth.require_scope(SCOPE_SEND)?;
+    let bot_id = &auth.0.bot_id;
+
+    // 群聊的推送目标（不含机器人自己），私聊保存时已经推送给接收者
+    let recipients: Vec<String> = match req.message_type.as_str() {
+        "private" => {
+            if !state.db_pool.user_exists_by_id(&req.receiver_id).map_err(|e| AppError::Database(e.to_string()))? {
+                return Err(AppError::NotFound("接收用户不存在".into()));
+            }
+            Vec::new()
+        }
+        "group" => {
+            let members = state.db_pool.get_group_members(&req.receiver_id)
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            if !members.iter().any(|m| &m.user_id == bot_id) {
+                return Err(AppError::Forbidden("机器人不是该群成员".into()));
+            }
+            members.into_iter().map(|m| m.user_id).filter(|id| id != bot_id).collect()
+        }
+        _ => return Err(AppError::InvalidInput("message_type 必须是 private 或 group".into())),
+    };
+
+    let message = super::message::send_message(&state, workspace.id(), bot_id, &req.receiver_id, &req.content, &req.message_type)?;
+
+    // 尝试推送给在线的群成员（若其已通过 WebSocket 标识并连接）
+    let notify = json!({
+        "type": "message",
+        "message_id": message.id,
+        "sender_id": message.sender_id,
+        "receiver_id": message.receiver_id,
+        "content": message.content,
+        "message_type": message.message_type,
+        "created_at": message.created_at
+    })
+    .to_string();
+    for user_id in &recipients {
+        state.send_to_user(user_id, notify.clone());
+    }
+
+    Ok(Json(BotSendMessageResponse {
+        success: true,
+        message: "消息发送成功".into(),
+        message_id: Some(message.id),
+    }))
+}
+
+// 机器人读取与某个用户的私聊，或所在群聊的历史消息
+pub async fn bot_messages_handler(
+    auth: BotAuth,
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    Query(query): Query<BotMessagesQuery>,
+) -> Result<Json<BotMessagesResponse>, AppError> {
+    auth.require_scope(SCOPE_READ)?;
+    let bot_id = &auth.0.bot_id;
+    super::workspace::require_member(&state, workspace.id(), bot_id)?;
+    let (before, before_id) = match &query.cursor {
+        Some(cursor) => super::conversation::decode_cursor(cursor)?,
+        None => (query.before.unwrap_or(i64::MAX), String::new()),
+    };
+    let limit = query.limit.unwrap_or(50).clamp(1, 200);
+
+    let is_member = state.db_pool.is_group_member(&query.peer_id, bot_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let messages = if is_member {
+        state.db_pool.get_group_history(workspace.id(), &query.peer_id, before, &before_id, limit)
+    } else {
+        state.db_pool.get_conversation_history(workspace.id(), bot_id, &query.peer_id, before, &before_id, limit)
+    }
+    .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let next_cursor = (messages.len() as i64 == limit)
+        .then(|| messages.last().map(|m| super::conversation::encode_cursor(m.created_at, &m.id)))
+        .flatten();
+    Ok(Json(BotMessagesResponse {
+        success: true,
+        message: "获取消息成功".into(),
+        messages,
+        next_cursor,
+    }))
+}
+
+/// 注册机器人相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/bots", get(list_bots_handler).post(create_bot_handler))
+        .route("/admin/bots/{bot_id}/keys", post(issue_key_handler))
+        .route("/admin/bots/keys/{key_id}", delete(revoke_key_handler))
+        .route("/admin/bots/{bot_id}/groups/{group_id}", put(join_group_handler))
+        .route("/bot/messages", get(bot_messages_handler).post(bot_send_message_handler))
+}
@@ -0,0 +1,338 @@
+//! 一对一语音和视频通话的信令：WebSocket 上的 call_offer、call_answer、ice_candidate 和 call_end
+//! 在通话双方之间转发，服务器只转交 SDP 和 ICE 候选，不经手媒体流
+//!
+//! 服务器记录每个通话处于响铃还是接通状态，每个用户同时只能有一个通话。对方不在线、正在通话、
+//! 超时未接或接通前挂断的通话，在双方的私聊中留下一条未接来电消息。通话状态保存在处理 call_offer 的
+//! 实例的内存中，多实例部署时双方需要连接到同一个实例
+//!
+//! GET /calls/ice-servers 下发配置的 STUN 服务器和 TURN 临时凭据（TURN REST API 的用户名加 HMAC-SHA1 的做法），
+//! 客户端据此在 NAT 后建立连接
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::get,
+    Router
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha1::Sha1;
+use crate::core::markdown::{EntityKind, FormattedText, TextEntity};
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+// 通话ID（由主叫方生成）的最大长度
+const MAX_CALL_ID_LEN: usize = 64;
+
+// 通话的媒体类型
+const MEDIA_AUDIO: &str = "audio";
+const MEDIA_VIDEO: &str = "video";
+
+// 通话结束的原因，随 call_end 事件发给双方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EndReason {
+    Hangup,       // 接通后任一方挂断
+    Cancelled,    // 主叫方在接通前挂断
+    Declined,     // 被叫方拒接
+    Timeout,      // 响铃超时
+    Busy,         // 被叫方正在通话
+    Unavailable,  // 被叫方不在线
+    Disconnected, // 一方断开了所有连接
+}
+
+impl EndReason {
+    fn as_str(self) -> &'static str {
+        match self {
+            EndReason::Hangup => "hangup",
+            EndReason::Cancelled => "cancelled",
+            EndReason::Declined => "declined",
+            EndReason::Timeout => "timeout",
+            EndReason::Busy => "busy",
+            EndReason::Unavailable => "unavailable",
+            EndReason::Disconnected => "disconnected",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Call {
+    workspace_id: String,
+    caller: String,
+    callee: String,
+    media: &'static str,
+    answered: bool,
+}
+
+impl Call {
+    fn peer_of(&self, user_id: &str) -> Option<&str> {
+        if user_id == self.caller {
+            Some(&self.callee)
+        } else if user_id == self.callee {
+            Some(&self.caller)
+        } else {
+            None
+        }
+    }
+}
+
+/// 本实例上进行中的通话，克隆开销很小
+#[derive(Clone, Default)]
+pub struct CallRegistry {
+    calls: Arc<Mutex<HashMap<String, Call>>>,
+}
+
+impl CallRegistry {
+    // 用户是否正在通话（包括响铃中）
+    fn busy(calls: &HashMap<String, Call>, user_id: &str) -> bool {
+        calls.values().any(|call| call.peer_of(user_id).is_some())
+    }
+
+    // 参与者结束通话，不是参与者时不结束
+    fn remove(&self, call_id: &str, user_id: &str) -> Option<Call> {
+        let mut calls = self.calls.lock().unwrap();
+        calls.get(call_id).and_then(|call| call.peer_of(user_id))?;
+        calls.remove(call_id)
+    }
+}
+
+fn send_event(state: &AppState, user_id: &str, event: &Value) {
+    state.send_to_user(user_id, event.to_string());
+}
+
+fn end_event(call_id: &str, reason: EndReason) -> Value {
+    json!({ "type": "call_end", "call_id": call_id, "reason": reason.as_str() })
+}
+
+// 未接来电：在私聊中以主叫方的名义保存一条消息，文字供不认识 call 区间的旧客户端显示
+fn post_missed_call(state: &AppState, call_id: &str, call: &Call) {
+    let text = if call.media == MEDIA_VIDEO { "未接视频通话" } else { "未接语音通话" };
+    let entity = TextEntity {
+        call_id: Some(call_id.to_string()),
+        media: Some(call.media.to_string()),
+        ..TextEntity::new(EntityKind::Call, 0, text.chars().count())
+    };
+    let formatted = FormattedText { text: text.to_string(), entities: vec![entity] };
+    match super::message::send_server_message(state, &call.workspace_id, &call.caller, &call.callee, formatted) {
+        Ok(message) => super::message::echo_to_own_devices(state, &message, None, None),
+        Err(e) => println!("保存未接来电失败: {}", e),
+    }
+}
+
+// 通话结束：通知双方的所有设备（响铃中的其他设备随之停止），没有接通且不是被拒接时留下未接来电
+fn finish(state: &AppState, call_id: &str, call: &Call, reason: EndReason) {
+    let event = end_event(call_id, reason);
+    send_event(state, &call.caller, &event);
+    send_event(state, &call.callee, &event);
+    if !call.answered && reason != EndReason::Declined {
+        post_missed_call(state, call_id, call);
+    }
+}
+
+fn str_field<'a>(v: &'a Value, field: &str) -> Result<&'a str, AppError> {
+    v.get(field).and_then(|x| x.as_str()).ok_or_else(|| AppError::InvalidInput(format!("帧缺少字段: {}", field)))
+}
+
+fn call_not_found() -> AppError {
+    AppError::NotFound("通话不存在或已经结束".into())
+}
+
+/// 处理通话信令帧，user_id 为本连接标识的用户，client_id 为本连接
+pub(crate) fn handle_frame(state: &AppState, client_id: &str, user_id: &str, msg_type: &str, v: &Value) -> Result<(), AppError> {
+    let call_id = str_field(v, "call_id")?;
+    match msg_type {
+        "call_offer" => offer(state, user_id, call_id, v),
+        "call_answer" => {
+            let sdp = v.get("sdp").cloned().unwrap_or(Value::Null);
+            let caller = {
+                let mut calls = state.calls.calls.lock().unwrap();
+                let call = calls.get_mut(call_id)
+                    .filter(|call| call.callee == user_id && !call.answered)
+                    .ok_or_else(call_not_found)?;
+                call.answered = true;
+                call.caller.clone()
+            };
+            send_event(state, &caller, &json!({ "type": "call_answer", "call_id": call_id, "sdp": sdp }));
+            // 被叫方的其他设备停止响铃
+            state.send_to_user_except(user_id, client_id, json!({ "type": "call_answered_elsewhere", "call_id": call_id }).to_string());
+            Ok(())
+        }
+        "ice_candidate" => {
+            let peer = state.calls.calls.lock().unwrap().get(call_id)
+                .and_then(|call| call.peer_of(user_id).map(str::to_string))
+                .ok_or_else(call_not_found)?;
+            let candidate = v.get("candidate").cloned().unwrap_or(Value::Null);
+            send_event(state, &peer, &json!({ "type": "ice_candidate", "call_id": call_id, "candidate": candidate }));
+            Ok(())
+        }
+        "call_end" => {
+            let call = state.calls.remove(call_id, user_id).ok_or_else(call_not_found)?;
+            let reason = match (call.answered, user_id == call.caller) {
+                (true, _) => EndReason::Hangup,
+                (false, true) => EndReason::Cancelled,
+                (false, false) => EndReason::Declined,
+            };
+            finish(state, call_id, &call, reason);
+            Ok(())
+        }
+        other => Err(AppError::InvalidInput(format!("不支持的帧类型: {}", other))),
+    }
+}
+
+// 发起通话：对方不在线或正在通话时立即结束，否则转发给对方所有设备并开始响铃计时
+fn offer(state: &AppState, caller: &str, call_id: &str, v: &Value) -> Result<(), AppError> {
+    let callee = str_field(v, "receiver_id")?;
+    if call_id.is_empty() || call_id.len() > MAX_CALL_ID_LEN {
+        return Err(AppError::InvalidInput(format!("通话ID应为 1 到 {} 个字符", MAX_CALL_ID_LEN)));
+    }
+    let media = match v.get("media").and_then(|x| x.as_str()).unwrap_or(MEDIA_AUDIO) {
+        MEDIA_AUDIO => MEDIA_AUDIO,
+        MEDIA_VIDEO => MEDIA_VIDEO,
+        other => return Err(AppError::InvalidInput(format!("通话类型只能是 audio 或 video: {}", other))),
+    };
+    if callee == caller {
+        return Err(AppError::InvalidInput("不能呼叫自己".into()));
+    }
+    // 可选的 workspace 字段为工作区 slug，省略时为默认工作区；未接来电保存在该工作区的私聊中
+    let workspace = super::workspace::resolve_workspace(state, v.get("workspace").and_then(|x| x.as_str()).unwrap_or_default())?;
+    super::workspace::require_member(state, &workspace.id, caller)?;
+    super::workspace::require_member(state, &workspace.id, callee)?;
+    super::privacy::require_dm_allowed(state, caller, callee)?;
+
+    let call = Call {
+        workspace_id: workspace.id,
+        caller: caller.to_string(),
+        callee: callee.to_string(),
+        media,
+        answered: false,
+    };
+    let outcome = {
+        let mut calls = state.calls.calls.lock().unwrap();
+        if calls.contains_key(call_id) {
+            return Err(AppError::InvalidInput(format!("通话ID已被使用: {}", call_id)));
+        }
+        if CallRegistry::busy(&calls, caller) {
+            return Err(AppError::Conflict("你已经在通话中".into()));
+        }
+        if CallRegistry::busy(&calls, callee) {
+            Some(EndReason::Busy)
+        } else if !state.is_online(callee) {
+            Some(EndReason::Unavailable)
+        } else {
+            calls.insert(call_id.to_string(), call.clone());
+            None
+        }
+    };
+    if let Some(reason) = outcome {
+        send_event(state, caller, &end_event(call_id, reason));
+        post_missed_call(state, call_id, &call);
+        return Ok(());
+    }
+
+    send_event(state, callee, &json!({
+        "type": "call_offer",
+        "call_id": call_id,
+        "caller_id": caller,
+        "media": media,
+        "sdp": v.get("sdp").cloned().unwrap_or(Value::Null),
+    }));
+    // 响铃超时仍未接通时由服务器结束
+    let state = state.clone();
+    let call_id = call_id.to_string();
+    let timeout = std::time::Duration::from_secs(state.settings.calls.ring_timeout_secs);
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        let call = {
+            let mut calls = state.calls.calls.lock().unwrap();
+            match calls.get(&call_id) {
+                Some(call) if !call.answered => calls.remove(&call_id),
+                _ => None,
+            }
+        };
+        if let Some(call) = call {
+            finish(&state, &call_id, &call, EndReason::Timeout);
+        }
+    });
+    Ok(())
+}
+
+/// 用户的所有连接都已断开时结束其通话
+pub(crate) fn end_calls_of(state: &AppState, user_id: &str) {
+    let ended: Vec<(String, Call)> = {
+        let mut calls = state.calls.calls.lock().unwrap();
+        let ids: Vec<String> = calls.iter()
+            .filter(|(_, call)| call.peer_of(user_id).is_some())
+            .map(|(id, _)| id.clone())
+            .collect();
+        ids.into_iter().filter_map(|id| calls.remove(&id).map(|call| (id, call))).collect()
+    };
+    for (call_id, call) in ended {
+        finish(state, &call_id, &call, EndReason::Disconnected);
+    }
+}
+
+// 一组 ICE 服务器，格式与浏览器的 RTCIceServer 相同
+#[derive(Serialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub credential: Option<String>,
+}
+
+// ICE 服务器响应体
+#[derive(Serialize)]
+pub struct IceServersResponse {
+    pub success: bool,
+    pub message: String,
+    pub ice_servers: Vec<IceServer>,
+    pub ttl: u64,                       // TURN 凭据的有效期（秒），没有 TURN 服务器时为 0
+}
+
+// TURN 临时凭据：用户名为 "过期时间:用户ID"，密码为 base64(HMAC-SHA1(共享密钥, 用户名))，TURN 服务器用同一密钥验证
+fn turn_credential(secret: &str, user_id: &str, expires_at: i64) -> (String, String) {
+    let username = format!("{}:{}", expires_at, user_id);
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(username.as_bytes());
+    (username, BASE64.encode(mac.finalize().into_bytes()))
+}
+
+// 获取本用户可用的 STUN 和 TURN 服务器，需要登录
+pub async fn ice_servers_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<IceServersResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let settings = &state.settings.calls;
+    let mut ice_servers = Vec::new();
+    if !settings.stun_urls.is_empty() {
+        ice_servers.push(IceServer { urls: settings.stun_urls.clone(), username: None, credential: None });
+    }
+    let mut ttl = 0;
+    if !settings.turn_urls.is_empty() && !settings.turn_secret.is_empty() {
+        ttl = settings.turn_ttl_secs;
+        let (username, credential) = turn_credential(&settings.turn_secret, &user_id, unix_now() + ttl as i64);
+        ice_servers.push(IceServer { urls: settings.turn_urls.clone(), username: Some(username), credential: Some(credential) });
+    }
+
+    Ok(Json(IceServersResponse {
+        success: true,
+        message: "获取 ICE 服务器成功".into(),
+        ice_servers,
+        ttl,
+    }))
+}
+
+/// 注册通话路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/calls/ice-servers", get(ice_servers_handler))
+}
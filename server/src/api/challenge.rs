@@ -0,0 +1,155 @@
+//! 注册防刷：服务器出题的工作量证明（pow）或 hCaptcha 校验，由 `[registration] challenge` 选择
+//!
+//! 工作量证明的做法：客户端先 `GET /register/challenge` 取得题目和难度，
+//! 找到一个 nonce 使 `SHA-256("<challenge>:<nonce>")` 的前导零位数不少于难度，再随注册请求一起提交
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+pub const CHALLENGE_OFF: &str = "off";
+pub const CHALLENGE_POW: &str = "pow";
+pub const CHALLENGE_HCAPTCHA: &str = "hcaptcha";
+
+/// 随注册请求提交的防刷凭据
+#[derive(Deserialize, Default)]
+pub struct ChallengeProof {
+    #[serde(default)]
+    pub pow_challenge: Option<String>,
+    #[serde(default)]
+    pub pow_nonce: Option<String>,
+    #[serde(default)]
+    pub captcha_token: Option<String>,
+}
+
+// 注册题目响应体，字段随 kind 不同而不同
+#[derive(Serialize)]
+pub struct ChallengeResponse {
+    pub success: bool,
+    pub message: String,
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sitekey: Option<String>,
+}
+
+// hCaptcha siteverify 的响应（只关心是否通过）
+#[derive(Deserialize)]
+struct SiteVerifyResponse {
+    success: bool,
+}
+
+/// 哈希的前导零位数
+pub fn leading_zero_bits(hash: &[u8]) -> u32 {
+    let mut bits = 0;
+    for byte in hash {
+        if *byte == 0 {
+            bits += 8;
+        } else {
+            return bits + byte.leading_zeros();
+        }
+    }
+    bits
+}
+
+fn pow_solved(challenge: &str, nonce: &str, difficulty: u32) -> bool {
+    leading_zero_bits(&Sha256::digest(format!("{}:{}", challenge, nonce))) >= difficulty
+}
+
+fn challenge_failed() -> AppError {
+    AppError::Forbidden("人机验证未通过".into())
+}
+
+async fn verify_hcaptcha(state: &AppState, token: &str) -> Result<bool, AppError> {
+    let settings = &state.settings.registration;
+    let response = reqwest::Client::new()
+        .post(&settings.hcaptcha_verify_url)
+        .form(&[("secret", settings.hcaptcha_secret.as_str()), ("response", token), ("sitekey", settings.hcaptcha_sitekey.as_str())])
+        .send()
+        .await
+        .map_err(|e| AppError::Internal(format!("hCaptcha 校验请求失败: {}", e)))?;
+    let verdict: SiteVerifyResponse = response
+        .json()
+        .await
+        .map_err(|e| AppError::Internal(format!("hCaptcha 校验响应无效: {}", e)))?;
+    Ok(verdict.success)
+}
+
+/// 按配置校验注册请求携带的防刷凭据（未开启时直接通过）
+pub(crate) async fn verify(state: &AppState, proof: &ChallengeProof) -> Result<(), AppError> {
+    let settings = &state.settings.registration;
+    match settings.challenge.as_str() {
+        CHALLENGE_POW => {
+            let (Some(challenge), Some(nonce)) = (&proof.pow_challenge, &proof.pow_nonce) else {
+                return Err(challenge_failed());
+            };
+            // 先校验答案再核销，答错不会消耗题目
+            if !pow_solved(challenge, nonce, settings.pow_difficulty) {
+                return Err(challenge_failed());
+            }
+            let consumed = state.db_pool.consume_registration_challenge(challenge, unix_now())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            if consumed { Ok(()) } else { Err(challenge_failed()) }
+        }
+        CHALLENGE_HCAPTCHA => {
+            let Some(token) = proof.captcha_token.as_deref().filter(|t| !t.is_empty()) else {
+                return Err(challenge_failed());
+            };
+            if verify_hcaptcha(state, token).await? { Ok(()) } else { Err(challenge_failed()) }
+        }
+        _ => Ok(()),
+    }
+}
+
+// 获取注册前需要完成的人机验证
+pub async fn get_challenge_handler(
+    State(state): State<AppState>,
+) -> Result<Json<ChallengeResponse>, AppError> {
+    let settings = &state.settings.registration;
+    let mut response = ChallengeResponse {
+        success: true,
+        message: "获取注册验证成功".into(),
+        kind: CHALLENGE_OFF.into(),
+        challenge: None,
+        difficulty: None,
+        expires_at: None,
+        sitekey: None,
+    };
+    match settings.challenge.as_str() {
+        CHALLENGE_POW => {
+            let (challenge, expires_at) = state.db_pool.issue_registration_challenge(settings.pow_ttl_secs, unix_now())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            response.kind = CHALLENGE_POW.into();
+            response.challenge = Some(challenge);
+            response.difficulty = Some(settings.pow_difficulty);
+            response.expires_at = Some(expires_at);
+        }
+        CHALLENGE_HCAPTCHA => {
+            response.kind = CHALLENGE_HCAPTCHA.into();
+            response.sitekey = Some(settings.hcaptcha_sitekey.clone());
+        }
+        _ => {}
+    }
+    Ok(Json(response))
+}
+
+/// 注册人机验证路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/register/challenge", get(get_challenge_handler))
+}
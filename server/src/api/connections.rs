@@ -0,0 +1,212 @@
+//! WebSocket 连接的元数据：来源 IP、建立时间、标识的用户和设备、收发帧数、最后活动时间和发送队列长度
+//!
+//! 只记录本实例上的连接；管理员可以查看连接列表，并强制关闭某个连接（用于处理滥用和排查问题）
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    extract::ws::CloseFrame,
+    response::Json,
+    routing::{delete, get},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use crate::core::datetime::unix_now;
+
+/// 管理员强制关闭连接时的关闭码
+pub const CLOSE_BY_ADMIN: u16 = 4003;
+
+/// 一个连接的元数据，收发计数在连接任务中直接更新
+pub struct Connection {
+    client_id: String,
+    ip: Option<String>,
+    connected_at: i64,
+    // 最近一次 identify 的用户和设备
+    identity: Mutex<(Option<String>, Option<String>)>,
+    frames_received: AtomicU64,
+    frames_sent: AtomicU64,
+    // 最后一次收到客户端帧的时间
+    last_activity_at: AtomicI64,
+    events: super::outbound::Sender,
+    close: mpsc::Sender<CloseFrame>,
+}
+
+impl Connection {
+    /// 记录收到客户端的一帧
+    pub fn received(&self) {
+        self.frames_received.fetch_add(1, Ordering::Relaxed);
+        self.last_activity_at.store(unix_now(), Ordering::Relaxed);
+    }
+
+    /// 记录向客户端发出的一帧
+    pub fn sent(&self) {
+        self.frames_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> ConnectionSnapshot {
+        let (user_id, device_id) = self.identity.lock().unwrap().clone();
+        ConnectionSnapshot {
+            client_id: self.client_id.clone(),
+            user_id,
+            device_id,
+            ip: self.ip.clone(),
+            connected_at: self.connected_at,
+            frames_received: self.frames_received.load(Ordering::Relaxed),
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            last_activity_at: self.last_activity_at.load(Ordering::Relaxed),
+            queued_events: self.events.len(),
+        }
+    }
+}
+
+/// 管理接口返回的连接信息
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionSnapshot {
+    pub client_id: String,
+    pub user_id: Option<String>,   // 尚未 identify 时为空
+    pub device_id: Option<String>,
+    pub ip: Option<String>,
+    pub connected_at: i64,
+    pub frames_received: u64,
+    pub frames_sent: u64,
+    pub last_activity_at: i64,
+    pub queued_events: usize,     // 发送队列中等待写给客户端的事件数
+}
+
+/// 本实例上的全部连接，克隆开销很小
+#[derive(Clone, Default)]
+pub struct ConnectionRegistry {
+    connections: Arc<Mutex<HashMap<String, Arc<Connection>>>>,
+}
+
+impl ConnectionRegistry {
+    /// 登记新连接，events 用于向所有连接推送通知，close 用于管理员强制关闭
+    pub fn register(
+        &self,
+        client_id: &str,
+        ip: Option<String>,
+        events: super::outbound::Sender,
+        close: mpsc::Sender<CloseFrame>,
+    ) -> Arc<Connection> {
+        let now = unix_now();
+        let connection = Arc::new(Connection {
+            client_id: client_id.to_string(),
+            ip,
+            connected_at: now,
+            identity: Mutex::new((None, None)),
+            frames_received: AtomicU64::new(0),
+            frames_sent: AtomicU64::new(0),
+            last_activity_at: AtomicI64::new(now),
+            events,
+            close,
+        });
+        self.connections.lock().unwrap().insert(client_id.to_string(), connection.clone());
+        connection
+    }
+
+    /// 连接标识为某个用户（及设备）
+    pub fn identify(&self, client_id: &str, user_id: &str, device_id: Option<&str>) {
+        if let Some(connection) = self.connections.lock().unwrap().get(client_id) {
+            *connection.identity.lock().unwrap() = (Some(user_id.to_string()), device_id.map(str::to_string));
+        }
+    }
+
+    pub fn remove(&self, client_id: &str) {
+        self.connections.lock().unwrap().remove(client_id);
+    }
+
+    /// 全部连接，user_id 不为空时只返回该用户的连接，按建立时间排序
+    pub fn list(&self, user_id: Option<&str>) -> Vec<ConnectionSnapshot> {
+        let mut connections: Vec<_> = self.connections.lock().unwrap()
+            .values()
+            .map(|connection| connection.snapshot())
+            .filter(|snapshot| user_id.is_none() || snapshot.user_id.as_deref() == user_id)
+            .collect();
+        connections.sort_by(|a, b| a.connected_at.cmp(&b.connected_at).then_with(|| a.client_id.cmp(&b.client_id)));
+        connections
+    }
+
+    /// 以 CLOSE_BY_ADMIN 关闭连接，连接不存在时返回 false
+    pub fn close(&self, client_id: &str) -> bool {
+        let Some(connection) = self.connections.lock().unwrap().get(client_id).cloned() else {
+            return false;
+        };
+        let _ = connection.close.try_send(CloseFrame { code: CLOSE_BY_ADMIN, reason: "closed_by_admin".into() });
+        true
+    }
+
+    /// 向每个连接推送 payload，keep 返回 false 的连接（参数为标识的用户，尚未 identify 时为 None）随后以 frame 关闭；
+    /// 返回关闭的连接数
+    pub fn notify_all(&self, payload: &str, keep: impl Fn(Option<&str>) -> bool, frame: impl Fn() -> CloseFrame) -> usize {
+        let connections: Vec<_> = self.connections.lock().unwrap().values().cloned().collect();
+        let mut closed = 0;
+        for connection in connections {
+            let _ = connection.events.send(payload.to_string());
+            let user_id = connection.identity.lock().unwrap().0.clone();
+            if !keep(user_id.as_deref()) {
+                let _ = connection.close.try_send(frame());
+                closed += 1;
+            }
+        }
+        closed
+    }
+}
+
+// 连接列表的查询参数
+#[derive(Deserialize)]
+pub struct ConnectionsQuery {
+    pub user_id: Option<String>,
+}
+
+// 连接列表响应体
+#[derive(Serialize)]
+pub struct ConnectionsResponse {
+    pub success: bool,
+    pub message: String,
+    pub connections: Vec<ConnectionSnapshot>,
+}
+
+// 列出本实例上的 WebSocket 连接
+pub async fn list_connections_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Query(query): Query<ConnectionsQuery>,
+) -> Result<Json<ConnectionsResponse>, AppError> {
+    Ok(Json(ConnectionsResponse {
+        success: true,
+        message: "获取连接列表成功".into(),
+        connections: state.connections.list(query.user_id.as_deref()),
+    }))
+}
+
+// 强制关闭连接；客户端可以重新连接，需要阻止时配合禁用账号或访问控制
+pub async fn close_connection_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(client_id): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    if !state.connections.close(&client_id) {
+        return Err(AppError::NotFound("连接不存在".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "连接已关闭".into(),
+    }))
+}
+
+/// 注册连接管理路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/connections", get(list_connections_handler))
+        .route("/admin/connections/{client_id}", delete(close_connection_handler))
+}
@@ -0,0 +1,243 @@
+//! 会话列表和群成员列表，按游标分页；群成员可以把其他用户加入群聊
+//!
+//! 游标由上一页最后一项的排序键拼成，对客户端不透明，原样传回 `cursor` 即可取下一页；
+//! `next_cursor` 为空表示已经是最后一页。
+//!
+//! 会话内的消息另有连续的 `seq`，客户端发现序号缺口时按 `from_seq` 补齐
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::storage::{Conversation, GroupMemberEntry, Message};
+use crate::storage::system_messages::SystemEvent;
+
+// 共享应用状态
+use super::AppState;
+use super::policy::{authorize, Check};
+use super::workspace::{require_member, WorkspaceScope};
+use crate::core::datetime::unix_now;
+
+// 默认和最大的每页条数
+pub(super) const DEFAULT_PAGE_SIZE: i64 = 50;
+pub(super) const MAX_PAGE_SIZE: i64 = 200;
+
+// 游标：排序用的时间戳和ID，中间用冒号分隔
+pub(super) fn encode_cursor(at: i64, id: &str) -> String {
+    format!("{}:{}", at, id)
+}
+
+pub(super) fn decode_cursor(cursor: &str) -> Result<(i64, String), AppError> {
+    cursor.split_once(':')
+        .and_then(|(at, id)| Some((at.parse().ok()?, id.to_string())))
+        .ok_or_else(|| AppError::InvalidInput("分页游标无效".into()))
+}
+
+// 分页查询参数
+#[derive(Deserialize)]
+pub struct PageQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl PageQuery {
+    fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+// 会话列表响应
+#[derive(Serialize)]
+pub struct ConversationsResponse {
+    pub success: bool,
+    pub message: String,
+    pub conversations: Vec<Conversation>,
+    pub next_cursor: Option<String>,
+}
+
+// 按序号补齐的查询参数
+#[derive(Deserialize)]
+pub struct SeqQuery {
+    pub from_seq: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+// 按序号补齐的响应：messages 与 deleted_seqs 合起来覆盖 from_seq 起连续的一段序号
+#[derive(Serialize)]
+pub struct SeqMessagesResponse {
+    pub success: bool,
+    pub message: String,
+    pub messages: Vec<Message>,
+    pub deleted_seqs: Vec<i64>,
+    pub last_seq: i64,
+    pub next_seq: Option<i64>,
+}
+
+// 群成员列表的查询参数，query 为按用户名或显示名称搜索的关键词
+#[derive(Deserialize)]
+pub struct MembersQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub query: Option<String>,
+}
+
+// 搜索关键词的最大长度
+const MAX_MEMBER_QUERY_CHARS: usize = 64;
+
+// 群成员列表响应
+#[derive(Serialize)]
+pub struct GroupMembersResponse {
+    pub success: bool,
+    pub message: String,
+    pub members: Vec<GroupMemberEntry>,
+    pub next_cursor: Option<String>,
+}
+
+// 加入群成员的请求体
+#[derive(Deserialize)]
+pub struct AddGroupMemberRequest {
+    pub user_id: String,
+}
+
+// 加入群成员的响应
+#[derive(Serialize)]
+pub struct AddGroupMemberResponse {
+    pub success: bool,
+    pub message: String,
+    pub joined: bool,   // 是否新加入，已是成员时为 false
+}
+
+// 当前用户在工作区内的会话列表，按最后一条消息时间倒序
+pub async fn list_conversations_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
+    Query(query): Query<PageQuery>,
+) -> Result<Json<ConversationsResponse>, AppError> {
+    let user_id = authorize(&state, &headers, &[Check::WorkspaceMember(workspace.id())])?;
+    let after = match &query.cursor {
+        Some(cursor) => decode_cursor(cursor)?,
+        None => (i64::MAX, String::new()),
+    };
+    let limit = query.limit();
+    let conversations = state.db_pool.get_conversations_after(workspace.id(), &user_id, (after.0, &after.1), limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let next_cursor = (conversations.len() as i64 == limit)
+        .then(|| conversations.last().map(|c| encode_cursor(c.last_message_at, &c.peer_id)))
+        .flatten();
+    Ok(Json(ConversationsResponse {
+        success: true,
+        message: "获取会话列表成功".into(),
+        conversations,
+        next_cursor,
+    }))
+}
+
+// 会话中 seq >= from_seq 的消息，按序号升序；peer_id 与会话列表中的相同（私聊对方的用户ID或群ID）。
+// next_seq 不为空时还有后续，原样作为 from_seq 继续读取
+pub async fn messages_from_seq_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    Path(peer_id): Path<String>,
+    headers: http::HeaderMap,
+    Query(query): Query<SeqQuery>,
+) -> Result<Json<SeqMessagesResponse>, AppError> {
+    let user_id = authorize(&state, &headers, &[Check::WorkspaceMember(workspace.id())])?;
+    let (_, conversation) = super::policy::conversation(&state, &user_id, &peer_id)?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let from_seq = query.from_seq.unwrap_or(1).max(1);
+    let range = state.db_pool.messages_from_seq(workspace.id(), &conversation, from_seq, limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let returned = range.messages.len() + range.deleted_seqs.len();
+    let max_seq = range.messages.iter().filter_map(|m| m.seq).chain(range.deleted_seqs.iter().copied()).max();
+    let next_seq = max_seq.filter(|_| returned as i64 == limit).map(|seq| seq + 1);
+    Ok(Json(SeqMessagesResponse {
+        success: true,
+        message: "获取会话消息成功".into(),
+        messages: range.messages,
+        deleted_seqs: range.deleted_seqs,
+        last_seq: range.last_seq,
+        next_seq,
+    }))
+}
+
+// 群成员列表，按用户ID排序，每页直接从数据库按索引读取；只有群成员可以查看
+pub async fn list_group_members_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+    Query(query): Query<MembersQuery>,
+) -> Result<Json<GroupMembersResponse>, AppError> {
+    authorize(&state, &headers, &[Check::GroupMember(&group_id)])?;
+    let search = query.query.as_deref().unwrap_or_default().trim();
+    if search.chars().count() > MAX_MEMBER_QUERY_CHARS {
+        return Err(AppError::InvalidInput(format!("搜索关键词不能超过 {} 个字符", MAX_MEMBER_QUERY_CHARS)));
+    }
+    let after = match &query.cursor {
+        Some(cursor) => decode_cursor(cursor)?.1,
+        None => String::new(),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let mut page = state.db_pool.group_members_page(&group_id, &after, search, limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    // 已到期的禁言不再显示
+    let now = unix_now();
+    for member in &mut page {
+        member.muted_until = member.muted_until.filter(|&until| until > now);
+    }
+
+    let next_cursor = (page.len() as i64 == limit)
+        .then(|| page.last().map(|m| encode_cursor(m.joined_at, &m.user_id)))
+        .flatten();
+    Ok(Json(GroupMembersResponse {
+        success: true,
+        message: "获取群成员成功".into(),
+        members: page,
+        next_cursor,
+    }))
+}
+
+// 群成员把同一工作区的其他用户加入群聊，受被加入用户的 group_add_privacy 设置限制
+pub async fn add_group_member_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<AddGroupMemberRequest>,
+) -> Result<Json<AddGroupMemberResponse>, AppError> {
+    let adder_id = authorize(&state, &headers, &[Check::GroupMember(&group_id)])?;
+    let workspace_id = state.db_pool.group_workspace(&group_id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("群聊不存在".into()))?;
+    if !state.db_pool.user_exists_by_id(&req.user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("用户不存在".into()));
+    }
+    require_member(&state, &workspace_id, &req.user_id)?;
+    super::privacy::require_group_add_allowed(&state, &adder_id, &req.user_id)?;
+
+    let joined = state.db_pool.add_group_member(&group_id, &req.user_id, "member")
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if joined {
+        super::message::post_system_message(&state, &workspace_id, &group_id, SystemEvent::MemberJoined { user_id: req.user_id })?;
+    }
+
+    Ok(Json(AddGroupMemberResponse {
+        success: true,
+        message: "已加入群聊".into(),
+        joined,
+    }))
+}
+
+/// 注册会话和群成员列表路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/conversations", get(list_conversations_handler))
+        .route("/conversations/{peer_id}/messages", get(messages_from_seq_handler))
+        .route("/groups/{group_id}/members", get(list_group_members_handler).post(add_group_member_handler))
+}
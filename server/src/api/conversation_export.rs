@@ -0,0 +1,306 @@
+//! 会话导出：把一个私聊或群聊中全部未删除的消息导出为单个文件，供用户自己备份
+//!
+//! `format=html`（默认）生成可以直接用浏览器打开的独立网页：时间按用户设置的时区显示，带发送者名称，
+//! 不超过 `export_inline_max_bytes` 的图片以 data URI 内嵌为缩略图，其他附件显示为下载链接；
+//! `format=json` 导出原始的消息和附件元数据。端到端加密的消息服务器无法解密，按密文原样导出
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use axum::{
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router
+};
+use base64::Engine;
+use http::header;
+use serde::Deserialize;
+use crate::core::datetime::{format_local, format_offset};
+use crate::core::markdown::{self, EntityKind, TextEntity};
+use crate::error::AppError;
+use crate::storage::attachments::{Attachment, SCAN_QUARANTINED};
+use crate::storage::export::ConversationExport;
+use crate::storage::system_messages::{SystemEvent, SYSTEM_MESSAGE_TYPE};
+use crate::storage::user_settings::{parse_utc_offset, SETTING_UTC_OFFSET};
+use crate::storage::Message;
+
+// 共享应用状态
+use super::AppState;
+use super::policy::{authorize, Check};
+use super::workspace::WorkspaceScope;
+
+// 可以内嵌的图片类型，浏览器都能直接显示，且不会执行脚本（SVG 不内嵌）
+const INLINE_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp"];
+
+const STYLE: &str = "
+body { font-family: system-ui, sans-serif; max-width: 760px; margin: 2em auto; padding: 0 1em; color: #222; }
+header { border-bottom: 1px solid #ddd; margin-bottom: 1em; }
+header p { color: #777; font-size: 0.9em; }
+.message { margin: 0.8em 0; }
+.meta { font-size: 0.85em; color: #777; }
+.sender { font-weight: bold; color: #245; margin-right: 0.5em; }
+.content { white-space: pre-wrap; word-wrap: break-word; }
+.system { text-align: center; color: #888; font-size: 0.85em; }
+.attachments { list-style: none; padding: 0; margin: 0.3em 0; }
+.attachments img { max-width: 240px; max-height: 240px; border-radius: 4px; }
+code { background: #f3f3f3; padding: 0 0.2em; }
+";
+
+// 导出参数
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+}
+
+fn escape_into(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    escape_into(&mut out, text);
+    out
+}
+
+fn format_size(bytes: i64) -> String {
+    const UNITS: &[&str] = &["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+// 用户ID到显示名称（没有时为用户名）的缓存，已删除的用户显示ID
+struct Names<'a> {
+    state: &'a AppState,
+    cache: HashMap<String, String>,
+}
+
+impl Names<'_> {
+    fn get(&mut self, user_id: &str) -> String {
+        if let Some(name) = self.cache.get(user_id) {
+            return name.clone();
+        }
+        let db = &self.state.db_pool;
+        let name = db.display_name(user_id).ok().flatten()
+            .filter(|name| !name.is_empty())
+            .or_else(|| db.get_user_by_id(user_id).ok().map(|user| user.username))
+            .unwrap_or_else(|| user_id.to_string());
+        self.cache.insert(user_id.to_string(), name.clone());
+        name
+    }
+}
+
+// 按格式区间渲染消息正文；贴纸和通话记录按纯文本显示，链接只渲染允许的协议
+fn render_text(out: &mut String, text: &str, entities: &[TextEntity]) {
+    let chars: Vec<char> = text.chars().collect();
+    let slice = |start: usize, end: usize| chars[start..end].iter().collect::<String>();
+    let mut pos = 0;
+    for entity in entities {
+        let start = entity.offset.clamp(pos, chars.len());
+        let end = entity.offset.saturating_add(entity.length).min(chars.len());
+        if start >= end {
+            continue;
+        }
+        escape_into(out, &slice(pos, start));
+        let inner = escape(&slice(start, end));
+        match (entity.kind, &entity.url) {
+            (EntityKind::Bold, _) => { let _ = write!(out, "<strong>{}</strong>", inner); }
+            (EntityKind::Italic, _) => { let _ = write!(out, "<em>{}</em>", inner); }
+            (EntityKind::Code, _) => { let _ = write!(out, "<code>{}</code>", inner); }
+            (EntityKind::Link, Some(url)) if markdown::check_url(url).is_ok() => {
+                let _ = write!(out, "<a href=\"{}\" rel=\"noreferrer\">{}</a>", escape(url), inner);
+            }
+            _ => out.push_str(&inner),
+        }
+        pos = end;
+    }
+    escape_into(out, &slice(pos, chars.len()));
+}
+
+// 系统消息的说明文字（已转义）
+fn describe_event(event: &SystemEvent, names: &mut Names) -> String {
+    let text = match event {
+        SystemEvent::MemberJoined { user_id } => format!("{} 加入了群聊", names.get(user_id)),
+        SystemEvent::NameChanged { old_name, new_name, .. } => format!("{} 改名为 {}", old_name, new_name),
+        SystemEvent::MessagePinned { pinned_by, .. } => format!("{} 置顶了一条消息", names.get(pinned_by)),
+        SystemEvent::CallStarted { started_by, .. } => format!("{} 发起了通话", names.get(started_by)),
+        SystemEvent::OwnerChanged { old_owner, new_owner } => {
+            format!("{} 把群转让给了 {}", names.get(old_owner), names.get(new_owner))
+        }
+        SystemEvent::GroupDeleted { deleted_by } => format!("{} 删除了群聊", names.get(deleted_by)),
+        SystemEvent::NewDeviceLogin { device_name, platform, .. } => {
+            format!("账号在新设备（{}）上登录", device_label(device_name, platform))
+        }
+        SystemEvent::DuplicateLogin { device_name, platform, replaced_old, .. } => {
+            let label = device_label(device_name, platform);
+            if *replaced_old {
+                format!("账号在设备（{}）上重新连接，原有的连接已断开", label)
+            } else {
+                format!("设备（{}）已经在线，新的连接已被拒绝", label)
+            }
+        }
+    };
+    escape(&text)
+}
+
+fn device_label<'a>(name: &'a str, platform: &'a str) -> &'a str {
+    [name, platform].into_iter().find(|label| !label.is_empty()).unwrap_or("未知设备")
+}
+
+// 附件：小图片内嵌为缩略图并链接到原图，其他附件显示文件名和大小；隔离的附件不提供链接
+async fn render_attachment(out: &mut String, state: &AppState, attachment: &Attachment) {
+    let name = escape(&attachment.filename);
+    if attachment.scan_status == SCAN_QUARANTINED {
+        let _ = write!(out, "<li>{}（已隔离）</li>", name);
+        return;
+    }
+    let link = escape(&format!(
+        "{}/attachments/{}",
+        state.settings.server.public_url.trim_end_matches('/'),
+        attachment.id,
+    ));
+    let max_inline = state.settings.attachments.export_inline_max_bytes;
+    let inline = INLINE_TYPES.contains(&attachment.content_type.as_str())
+        && attachment.size > 0
+        && attachment.size as u64 <= max_inline;
+    // 文件读取失败时退回为链接
+    if inline && let Ok(bytes) = tokio::fs::read(super::attachment::attachment_path(state, &attachment.blob)).await {
+        let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+        let _ = write!(
+            out,
+            "<li><a href=\"{}\"><img src=\"data:{};base64,{}\" alt=\"{}\"></a></li>",
+            link, attachment.content_type, data, name,
+        );
+        return;
+    }
+    let _ = write!(out, "<li><a href=\"{}\">{}</a>（{}）</li>", link, name, format_size(attachment.size));
+}
+
+// 一条消息
+async fn render_message(
+    out: &mut String,
+    state: &AppState,
+    message: &Message,
+    attachments: &[&Attachment],
+    names: &mut Names<'_>,
+    utc_offset: i64,
+) {
+    let time = format_local(message.created_at, utc_offset);
+    if message.message_type == SYSTEM_MESSAGE_TYPE {
+        let text = match serde_json::from_str::<SystemEvent>(&message.content) {
+            Ok(event) => describe_event(&event, names),
+            Err(_) => escape(&message.content),
+        };
+        let _ = writeln!(out, "<p class=\"system\">{} · {}</p>", text, time);
+        return;
+    }
+    let _ = write!(
+        out,
+        "<article class=\"message\" id=\"m-{}\"><div class=\"meta\"><span class=\"sender\">{}</span><time>{}</time></div><div class=\"content\">",
+        escape(&message.id), escape(&names.get(&message.sender_id)), time,
+    );
+    render_text(out, &message.content, &message.entities);
+    out.push_str("</div>");
+    if !attachments.is_empty() {
+        out.push_str("<ul class=\"attachments\">");
+        for attachment in attachments {
+            render_attachment(out, state, attachment).await;
+        }
+        out.push_str("</ul>");
+    }
+    out.push_str("</article>\n");
+}
+
+// 整个会话的 HTML 文档
+async fn render_html(state: &AppState, export: &ConversationExport, title: &str, utc_offset: i64) -> String {
+    let mut by_message: HashMap<&str, Vec<&Attachment>> = HashMap::new();
+    for item in &export.attachments {
+        by_message.entry(item.message_id.as_str()).or_default().push(&item.attachment);
+    }
+    let mut names = Names { state, cache: HashMap::new() };
+
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html lang=\"zh-CN\">\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n\
+         <header><h1>{title}</h1><p>导出于 {} （{}），共 {} 条消息</p></header>\n<main>\n",
+        format_local(export.exported_at, utc_offset),
+        format_offset(utc_offset),
+        export.messages.len(),
+        title = escape(title),
+    );
+    for message in &export.messages {
+        let attachments = by_message.get(message.id.as_str()).map(Vec::as_slice).unwrap_or_default();
+        render_message(&mut out, state, message, attachments, &mut names, utc_offset).await;
+    }
+    out.push_str("</main>\n</body>\n</html>\n");
+    out
+}
+
+// 导出会话，peer_id 与会话列表中的相同（私聊对方的用户ID或群ID）；以附件形式下载
+pub async fn export_conversation_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    Path(peer_id): Path<String>,
+    headers: http::HeaderMap,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, AppError> {
+    let user_id = authorize(&state, &headers, &[Check::WorkspaceMember(workspace.id())])?;
+    let format = query.format.as_deref().unwrap_or("html");
+    if format != "html" && format != "json" {
+        return Err(AppError::InvalidInput("导出格式只能是 html 或 json".into()));
+    }
+    let (conversation_type, conversation) = super::policy::conversation(&state, &user_id, &peer_id)?;
+
+    let db = state.db_pool.clone();
+    let (workspace_id, peer) = (workspace.id().to_string(), peer_id.clone());
+    let export = tokio::task::spawn_blocking(move || db.export_conversation(&workspace_id, &peer, conversation_type, &conversation))
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let filename = super::attachment::encode_filename(&format!("yueling-{}.{}", peer_id, format));
+    let disposition = format!("attachment; filename*=UTF-8''{}", filename);
+    if format == "json" {
+        let body = serde_json::to_vec_pretty(&export).map_err(|e| AppError::Internal(e.to_string()))?;
+        return Ok(([(header::CONTENT_TYPE, "application/json".to_string()), (header::CONTENT_DISPOSITION, disposition)], body).into_response());
+    }
+
+    let settings = state.db_pool.get_user_settings(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let utc_offset = settings.get(SETTING_UTC_OFFSET).and_then(|value| parse_utc_offset(value)).unwrap_or(0);
+    let title = if conversation_type == "group" {
+        let name = state.db_pool.group_name(&peer_id)
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .unwrap_or_else(|| peer_id.clone());
+        format!("群聊「{}」的聊天记录", name)
+    } else {
+        let mut names = Names { state: &state, cache: HashMap::new() };
+        format!("与 {} 的聊天记录", names.get(&peer_id))
+    };
+    let html = render_html(&state, &export, &title, utc_offset).await;
+    Ok(([(header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()), (header::CONTENT_DISPOSITION, disposition)], html).into_response())
+}
+
+/// 注册会话导出路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/conversations/{peer_id}/export", get(export_conversation_handler))
+}
@@ -0,0 +1,255 @@
+//! 登录设备管理：记录用户登录过的设备，新设备可要求邮箱验证码（`[devices] verify_new_devices`），
+//! 新设备登录或同一设备重复连接后通过系统消息提醒用户，用户可以查看、重命名和移除设备
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::{get, put},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::email::Email;
+use crate::error::AppError;
+use crate::storage::devices::Device;
+use crate::storage::system_messages::SystemEvent;
+use crate::storage::email_verification::is_placeholder_email;
+
+// 共享应用状态
+use super::AppState;
+use super::ws::DeviceLoginOutcome;
+use super::user::SuccessResponse;
+use crate::core::datetime::unix_now;
+
+const MAX_DEVICE_NAME_CHARS: usize = 64;
+
+// 登录时客户端上报的设备信息（均可省略）
+#[derive(Deserialize, Default)]
+pub struct DeviceInfo {
+    #[serde(default)]
+    pub device_id: Option<String>,   // 上次登录返回的设备 ID，首次登录省略
+    #[serde(default)]
+    pub device_name: String,         // 如 "小明的 iPhone"
+    #[serde(default)]
+    pub platform: String,            // 如 ios、android、web
+    #[serde(default)]
+    pub push_token: Option<String>,
+    #[serde(default)]
+    pub device_code: Option<String>, // 新设备验证码
+}
+
+impl DeviceInfo {
+    // 旧客户端不上报任何设备信息
+    fn is_empty(&self) -> bool {
+        self.device_id.is_none() && self.device_name.is_empty() && self.platform.is_empty() && self.push_token.is_none()
+    }
+}
+
+// 设备检查结果
+pub(crate) enum DeviceLogin {
+    Untracked,       // 旧客户端且未开启新设备验证，不记录设备
+    Ready(String),   // 可以签发会话的设备
+    Pending(String), // 新设备，验证码已发送到邮箱
+}
+
+// 设备列表响应体
+#[derive(Serialize)]
+pub struct DevicesResponse {
+    pub success: bool,
+    pub message: String,
+    pub devices: Vec<Device>,
+}
+
+// 重命名设备请求体
+#[derive(Deserialize)]
+pub struct RenameDeviceRequest {
+    pub name: String,
+}
+
+fn check_device_name(name: &str) -> Result<(), AppError> {
+    if name.chars().count() > MAX_DEVICE_NAME_CHARS {
+        return Err(AppError::InvalidInput(format!("设备名称不能超过 {} 个字符", MAX_DEVICE_NAME_CHARS)));
+    }
+    Ok(())
+}
+
+// 开启新设备验证且能发送邮件时，返回用户的真实邮箱
+fn verification_email(state: &AppState, user_id: &str) -> Result<Option<String>, AppError> {
+    if !state.settings.devices.verify_new_devices || state.mailer.is_none() {
+        return Ok(None);
+    }
+    let user = state.db_pool.get_user_by_id(user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok((!is_placeholder_email(&user.email)).then_some(user.email))
+}
+
+/// 密码验证通过后检查登录设备：已信任的设备直接放行；新设备在开启验证时需要邮箱验证码，
+/// 验证通过（或无需验证）后成为可信设备，并在用户已有其他设备时发送新设备提醒
+pub(crate) async fn check_device(state: &AppState, user_id: &str, info: &DeviceInfo) -> Result<DeviceLogin, AppError> {
+    let email = verification_email(state, user_id)?;
+    if info.is_empty() && email.is_none() {
+        return Ok(DeviceLogin::Untracked);
+    }
+    check_device_name(&info.device_name)?;
+
+    let now = unix_now();
+    let db = |e: rusqlite::Error| AppError::Database(e.to_string());
+    let existing = match &info.device_id {
+        Some(device_id) => state.db_pool.find_device(user_id, device_id).map_err(db)?,
+        None => None,
+    };
+    let push_token = info.push_token.as_deref();
+    let device = match existing {
+        Some(device) if device.trusted => {
+            state.db_pool.touch_device(&device.id, push_token, false, now).map_err(db)?;
+            return Ok(DeviceLogin::Ready(device.id));
+        }
+        Some(device) => device,
+        None => state.db_pool
+            .create_device(user_id, info.device_name.trim(), info.platform.trim(), push_token, false, now)
+            .map_err(db)?,
+    };
+
+    if let Some(email) = email {
+        let settings = &state.settings.devices;
+        match &info.device_code {
+            None => {
+                let code = state.db_pool.issue_device_code(&device.id, settings.code_ttl_secs, now).map_err(db)?;
+                send_device_code(state, email, &device, &code).await?;
+                return Ok(DeviceLogin::Pending(device.id));
+            }
+            Some(code) => {
+                if !state.db_pool.check_device_code(&device.id, code, settings.max_code_attempts, now).map_err(db)? {
+                    return Err(AppError::InvalidCredentials("设备验证码错误或已过期".into()));
+                }
+            }
+        }
+    }
+
+    state.db_pool.touch_device(&device.id, push_token, true, now).map_err(db)?;
+    if state.settings.devices.notify_new_device
+        && state.db_pool.has_other_trusted_devices(user_id, &device.id).map_err(db)?
+    {
+        notify_new_device(state, user_id, &device);
+    }
+    Ok(DeviceLogin::Ready(device.id))
+}
+
+fn device_label(device: &Device) -> &str {
+    [device.name.as_str(), device.platform.as_str()]
+        .into_iter()
+        .find(|label| !label.is_empty())
+        .unwrap_or("未知设备")
+}
+
+async fn send_device_code(state: &AppState, to: String, device: &Device, code: &str) -> Result<(), AppError> {
+    let Some(mailer) = &state.mailer else {
+        return Ok(());
+    };
+    let email = Email {
+        to,
+        subject: "月灵新设备登录验证".into(),
+        body: format!(
+            "你好！\n\n你的账号正在新设备（{}）上登录，验证码：\n\n{}\n\n验证码 {} 分钟内有效。如果这不是你本人的操作，请尽快修改密码。\n",
+            device_label(device),
+            code,
+            state.settings.devices.code_ttl_secs / 60,
+        ),
+    };
+    mailer.send(&email).await.map_err(|e| {
+        println!("发送设备验证码失败: {}", e);
+        AppError::Internal("验证码邮件发送失败".into())
+    })
+}
+
+fn notify_new_device(state: &AppState, user_id: &str, device: &Device) {
+    send_security_notice(state, user_id, SystemEvent::NewDeviceLogin {
+        device_id: device.id.clone(),
+        device_name: device.name.clone(),
+        platform: device.platform.clone(),
+    });
+}
+
+// 同一设备重复建立 WebSocket 连接时提醒用户
+pub(crate) fn notify_duplicate_login(state: &AppState, user_id: &str, device: &Device, outcome: DeviceLoginOutcome) {
+    let replaced_old = match outcome {
+        DeviceLoginOutcome::Attached => return,
+        DeviceLoginOutcome::ReplacedOld => true,
+        DeviceLoginOutcome::DeniedNew => false,
+    };
+    send_security_notice(state, user_id, SystemEvent::DuplicateLogin {
+        device_id: device.id.clone(),
+        device_name: device.name.clone(),
+        platform: device.platform.clone(),
+        replaced_old,
+    });
+}
+
+// 以系统账号给用户发一条安全提醒（系统消息，客户端按事件类型渲染）；失败只打印日志，不影响登录
+fn send_security_notice(state: &AppState, user_id: &str, event: SystemEvent) {
+    if let Err(e) = super::message::post_user_system_message(state, user_id, event) {
+        println!("发送安全提醒失败: {}", e);
+    }
+}
+
+// 列出当前用户的设备
+pub async fn list_devices_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<DevicesResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let devices = state.db_pool.list_devices(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(DevicesResponse {
+        success: true,
+        message: "获取设备列表成功".into(),
+        devices,
+    }))
+}
+
+// 重命名设备
+pub async fn rename_device_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<RenameDeviceRequest>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let name = req.name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("设备名称不能为空".into()));
+    }
+    check_device_name(name)?;
+    if !state.db_pool.rename_device(&user_id, &device_id, name).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("设备不存在".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "设备已重命名".into(),
+    }))
+}
+
+// 移除设备：该设备上的会话立即失效，再次登录时按新设备处理
+pub async fn revoke_device_handler(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    if !state.db_pool.revoke_device(&user_id, &device_id, unix_now()).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("设备不存在".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "设备已移除".into(),
+    }))
+}
+
+/// 注册设备管理路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/devices", get(list_devices_handler))
+        .route("/account/devices/{id}", put(rename_device_handler).delete(revoke_device_handler))
+}
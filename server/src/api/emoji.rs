@@ -0,0 +1,142 @@
+//! 自定义表情和贴纸：管理员按工作区上传表情图片，成员在 `GET /emoji` 和 `/server-info` 中取得表情列表，
+//! 发送 format 为 sticker 的消息时按名称引用
+
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path as UrlPath, State},
+    response::Json,
+    routing::{get, put},
+    Router
+};
+use serde::Serialize;
+use crate::core::markdown::{EntityKind, FormattedText, TextEntity};
+use crate::error::AppError;
+use crate::storage::devices::SYSTEM_USER_ID;
+use crate::storage::emoji::CustomEmoji;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use super::workspace::{require_member, resolve_workspace, WorkspaceScope};
+use crate::core::datetime::unix_now;
+
+// 表情名称的长度范围
+const MIN_NAME_LEN: usize = 2;
+const MAX_NAME_LEN: usize = 32;
+
+// 保存表情响应体
+#[derive(Serialize)]
+pub struct EmojiResponse {
+    pub success: bool,
+    pub message: String,
+    pub emoji: CustomEmoji,
+}
+
+// 表情列表响应体
+#[derive(Serialize)]
+pub struct EmojiListResponse {
+    pub success: bool,
+    pub message: String,
+    pub emoji: Vec<CustomEmoji>,
+}
+
+// 表情名称只能包含小写字母、数字、_ 和 -
+fn check_name(name: &str) -> Result<(), AppError> {
+    let valid = (MIN_NAME_LEN..=MAX_NAME_LEN).contains(&name.len())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err(AppError::InvalidInput(format!(
+            "表情名称只能包含小写字母、数字、_ 和 -，长度 {} 到 {}", MIN_NAME_LEN, MAX_NAME_LEN,
+        )))
+    }
+}
+
+// 贴纸消息：content 为表情名称（可以带两侧的冒号），保存为 :name: 和一个覆盖整条消息的 sticker 区间
+pub(crate) fn sticker_content(state: &AppState, workspace_id: &str, content: &str) -> Result<FormattedText, AppError> {
+    let name = content.trim().trim_matches(':');
+    let emoji = state.db_pool.custom_emoji_by_name(workspace_id, name)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("自定义表情 {} 不存在", name)))?;
+    let text = format!(":{}:", emoji.name);
+    let sticker = TextEntity {
+        emoji: Some(emoji.name),
+        attachment_id: Some(emoji.attachment_id),
+        ..TextEntity::new(EntityKind::Sticker, 0, text.chars().count())
+    };
+    Ok(FormattedText { text, entities: vec![sticker] })
+}
+
+// 上传或替换自定义表情：multipart 中名为 file 的图片
+pub async fn put_emoji_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath((slug, name)): UrlPath<(String, String)>,
+    mut multipart: Multipart,
+) -> Result<Json<EmojiResponse>, AppError> {
+    let workspace = resolve_workspace(&state, &slug)?;
+    check_name(&name)?;
+    // 表情图片由系统账号持有，不计入任何用户的附件配额
+    let attachment = super::attachment::save_upload(
+        &state, &mut multipart, SYSTEM_USER_ID, state.settings.attachments.max_bytes, 0, "image/",
+    ).await?;
+    let created = state.db_pool.put_custom_emoji(&workspace.id, &name, &attachment.id, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(EmojiResponse {
+        success: true,
+        message: if created { "自定义表情已添加" } else { "自定义表情已更新" }.into(),
+        emoji: CustomEmoji {
+            name,
+            attachment_id: attachment.id,
+            content_type: attachment.content_type,
+            created_at: attachment.created_at,
+        },
+    }))
+}
+
+// 删除自定义表情
+pub async fn delete_emoji_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath((slug, name)): UrlPath<(String, String)>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let workspace = resolve_workspace(&state, &slug)?;
+    if !state.db_pool.delete_custom_emoji(&workspace.id, &name).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound(format!("自定义表情 {} 不存在", name)));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "自定义表情已删除".into(),
+    }))
+}
+
+// 当前工作区的自定义表情
+pub async fn list_emoji_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
+) -> Result<Json<EmojiListResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    require_member(&state, workspace.id(), &user_id)?;
+    let emoji = state.db_pool.custom_emoji(workspace.id())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(EmojiListResponse {
+        success: true,
+        message: "获取自定义表情成功".into(),
+        emoji,
+    }))
+}
+
+/// 注册自定义表情路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        // 图片大小由 [attachments] max_bytes 在写入时检查
+        .route(
+            "/admin/workspaces/{slug}/emoji/{name}",
+            put(put_emoji_handler).delete(delete_emoji_handler).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/emoji", get(list_emoji_handler))
+}
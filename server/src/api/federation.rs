@@ -0,0 +1,305 @@
+use axum::{
+    body::Bytes,
+    extract::{Path as UrlPath, State},
+    http::HeaderMap,
+    response::Json,
+    routing::{delete, get, post},
+    Router
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use crate::error::AppError;
+use crate::federation::{self, FederatedMessage, Federation, MAX_CLOCK_SKEW_SECS, ORIGIN_HEADER, SIGNATURE_HEADER};
+use crate::storage::federation::FederationPeer;
+use crate::storage::Message;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use crate::core::datetime::unix_now;
+
+// 转发失败时的重试间隔（依次使用）
+const RELAY_RETRY_DELAYS: &[Duration] = &[Duration::from_secs(2), Duration::from_secs(10), Duration::from_secs(60)];
+
+fn enabled(state: &AppState) -> Result<&Federation, AppError> {
+    state.federation.as_ref().ok_or_else(|| AppError::NotFound("本服务器未启用联邦".into()))
+}
+
+/// 接收者是远端用户的影子账号时，把私聊消息转发到对端服务器（后台执行，失败会重试几次）
+pub(crate) fn relay_if_remote(state: &AppState, message: &Message) {
+    let Some(federation) = state.federation.clone() else {
+        return;
+    };
+    if message.message_type != "private" {
+        return;
+    }
+    let Ok(Some(remote)) = state.db_pool.get_remote_user(&message.receiver_id) else {
+        return;
+    };
+    let peer = match state.db_pool.get_federation_peer(&remote.host) {
+        Ok(Some(peer)) => peer,
+        _ => {
+            println!("消息 {} 的接收者所在服务器 {} 不在联邦白名单中，未转发", message.id, remote.host);
+            return;
+        }
+    };
+    let Ok(sender) = state.db_pool.get_user_by_id(&message.sender_id) else {
+        return;
+    };
+
+    let payload = FederatedMessage {
+        message_id: message.id.clone(),
+        sender: sender.username,
+        recipient: remote.remote_username,
+        content: message.content.clone(),
+        created_at: message.created_at,
+        sent_at: 0,
+    };
+    tokio::spawn(async move {
+        let mut delays = RELAY_RETRY_DELAYS.iter();
+        loop {
+            let payload = FederatedMessage { sent_at: unix_now(), ..payload.clone() };
+            let error = match federation.relay(&peer, &payload).await {
+                Ok(()) => return,
+                Err(e) => e,
+            };
+            match delays.next() {
+                Some(delay) => tokio::time::sleep(*delay).await,
+                None => {
+                    println!("转发消息 {} 到 {} 失败，放弃: {}", payload.message_id, peer.host, error);
+                    return;
+                }
+            }
+        }
+    });
+}
+
+// 本服联邦身份响应体
+#[derive(Serialize)]
+pub struct ServerKeyResponse {
+    pub success: bool,
+    pub message: String,
+    pub server_name: String,
+    pub public_key: String,
+}
+
+// 查询本服的联邦名称和公钥，供对端管理员登记
+pub async fn server_key_handler(
+    State(state): State<AppState>,
+) -> Result<Json<ServerKeyResponse>, AppError> {
+    let federation = enabled(&state)?;
+    Ok(Json(ServerKeyResponse {
+        success: true,
+        message: "获取联邦公钥成功".into(),
+        server_name: federation.server_name().to_string(),
+        public_key: federation.public_key(),
+    }))
+}
+
+// 解析远端地址请求体
+#[derive(Deserialize)]
+pub struct ResolveAddressRequest {
+    pub address: String, // user@other-host
+}
+
+// 解析远端地址响应体
+#[derive(Serialize)]
+pub struct ResolveAddressResponse {
+    pub success: bool,
+    pub message: String,
+    pub user_id: String,
+}
+
+// 把 user@other-host 解析为本地可用的用户ID，之后可像普通用户一样发私聊消息。
+// 远端用户还没有影子账号时返回地址本身，影子账号在第一条发给该地址的私聊消息时才创建
+pub async fn resolve_address_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<ResolveAddressRequest>,
+) -> Result<Json<ResolveAddressResponse>, AppError> {
+    super::user::session_user(&state, &headers)?;
+    let federation = enabled(&state)?;
+    let (username, host) = federation::parse_address(&req.address)
+        .ok_or_else(|| AppError::InvalidInput("地址格式应为 用户名@服务器".into()))?;
+
+    let user_id = if host == federation.server_name() {
+        local_user(&state, username)?
+    } else {
+        require_peer(&state, host)?;
+        state.db_pool.remote_user_id(username, host)
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .unwrap_or_else(|| format!("{}@{}", username, host))
+    };
+
+    Ok(Json(ResolveAddressResponse {
+        success: true,
+        message: "解析成功".into(),
+        user_id,
+    }))
+}
+
+/// 私聊接收者写成 user@other-host 时换成本地用户ID，远端用户第一次收到消息时创建影子账号；
+/// 未启用联邦或接收者不是地址时返回 None
+pub(crate) fn resolve_recipient(state: &AppState, receiver: &str) -> Result<Option<String>, AppError> {
+    let Some(federation) = state.federation.as_ref() else {
+        return Ok(None);
+    };
+    let Some((username, host)) = federation::parse_address(receiver) else {
+        return Ok(None);
+    };
+    if host == federation.server_name() {
+        return local_user(state, username).map(Some);
+    }
+    require_peer(state, host)?;
+    state.db_pool.resolve_remote_user(username, host, unix_now())
+        .map(Some)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+fn local_user(state: &AppState, username: &str) -> Result<String, AppError> {
+    state.db_pool.local_user_id_by_username(username)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("用户不存在".into()))
+}
+
+fn require_peer(state: &AppState, host: &str) -> Result<(), AppError> {
+    match state.db_pool.get_federation_peer(host).map_err(|e| AppError::Database(e.to_string()))? {
+        Some(_) => Ok(()),
+        None => Err(AppError::Forbidden(format!("服务器 {} 不在联邦白名单中", host))),
+    }
+}
+
+// 接收对端服务器转发来的私聊消息
+pub async fn receive_message_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<AdminResponse>, AppError> {
+    enabled(&state)?;
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    let (Some(origin), Some(signature)) = (header(ORIGIN_HEADER), header(SIGNATURE_HEADER)) else {
+        return Err(AppError::InvalidCredentials("缺少联邦签名".into()));
+    };
+    let peer = state.db_pool.get_federation_peer(origin)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::Forbidden(format!("服务器 {} 不在联邦白名单中", origin)))?;
+    if !federation::verify_signature(&peer.public_key, &body, signature) {
+        return Err(AppError::InvalidCredentials("联邦签名无效".into()));
+    }
+
+    let payload: FederatedMessage = serde_json::from_slice(&body)
+        .map_err(|e| AppError::InvalidInput(format!("消息格式错误: {}", e)))?;
+    let now = unix_now();
+    if (now - payload.sent_at).abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(AppError::InvalidCredentials("请求已过期".into()));
+    }
+
+    let receiver_id = state.db_pool.local_user_id_by_username(&payload.recipient)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("接收者不存在".into()))?;
+    let sender_id = state.db_pool.resolve_remote_user(&payload.sender, &peer.host, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    // 远端发送者与本地用户一样受接收者私信设置的限制
+    super::privacy::require_dm_allowed(&state, &sender_id, &receiver_id)?;
+    let stored = state.db_pool.store_federated_message(
+        &peer.host,
+        &payload.message_id,
+        &sender_id,
+        &receiver_id,
+        &payload.content,
+        payload.created_at,
+    )
+    .map_err(|e| AppError::Database(e.to_string()))?;
+    // 与本地私聊一样推送给接收者（离线时进入发件箱）；对端重试导致的重复投递直接确认
+    if let Some(message) = stored {
+        super::message::deliver(&state, &message);
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "消息已接收".into(),
+    }))
+}
+
+// 登记对端服务器请求体
+#[derive(Deserialize)]
+pub struct AddPeerRequest {
+    pub host: String,
+    pub base_url: String,
+    pub public_key: String,
+}
+
+// 对端服务器列表响应体
+#[derive(Serialize)]
+pub struct PeersResponse {
+    pub success: bool,
+    pub message: String,
+    pub peers: Vec<FederationPeer>,
+}
+
+// 列出联邦白名单
+pub async fn list_peers_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<PeersResponse>, AppError> {
+    let peers = state.db_pool.list_federation_peers()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(Json(PeersResponse {
+        success: true,
+        message: "获取联邦白名单成功".into(),
+        peers,
+    }))
+}
+
+// 添加或更新对端服务器
+pub async fn add_peer_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<AddPeerRequest>,
+) -> Result<Json<AdminResponse>, AppError> {
+    if req.host.is_empty() || req.host.contains('@') {
+        return Err(AppError::InvalidInput("服务器名无效".into()));
+    }
+    if !req.base_url.starts_with("https://") {
+        return Err(AppError::InvalidInput("对端地址必须使用 https://".into()));
+    }
+    let key_valid = BASE64.decode(&req.public_key).is_ok_and(|bytes| bytes.len() == 32);
+    if !key_valid {
+        return Err(AppError::InvalidInput("公钥应为 base64 编码的 32 字节 Ed25519 公钥".into()));
+    }
+    state.db_pool.add_federation_peer(&req.host, &req.base_url, &req.public_key, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "对端服务器已加入白名单".into(),
+    }))
+}
+
+// 把对端服务器移出白名单
+pub async fn remove_peer_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(host): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let removed = state.db_pool.remove_federation_peer(&host)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !removed {
+        return Err(AppError::NotFound("对端服务器不存在".into()));
+    }
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "对端服务器已移出白名单".into(),
+    }))
+}
+
+/// 注册联邦相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/federation/key", get(server_key_handler))
+        .route("/federation/resolve", post(resolve_address_handler))
+        .route("/federation/messages", post(receive_message_handler))
+        .route("/admin/federation/peers", get(list_peers_handler).post(add_peer_handler))
+        .route("/admin/federation/peers/{host}", delete(remove_peer_handler))
+}
@@ -13,6 +13,7 @@ use crate::error::AppError;
 
 // 共享应用状态
 use super::AppState;
+use super::policy::{authorize, Check};
 
 // 好友功能相关结构体
 
@@ -34,6 +35,32 @@ pub struct SearchUser {
     pub username: String,
 }
 
+// 一次通讯录匹配最多提交的用户名和邮箱哈希个数
+const MAX_LOOKUP_HANDLES: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct LookupUsersRequest {
+    #[serde(default)]
+    pub usernames: Vec<String>,
+    #[serde(default)]
+    pub email_hashes: Vec<String>, // 去掉首尾空白并转为小写后的邮箱的 SHA-256（十六进制）
+}
+
+#[derive(Serialize)]
+pub struct LookupUsersResponse {
+    pub success: bool,
+    pub message: String,
+    pub users: Vec<LookupUser>,
+}
+
+#[derive(Serialize)]
+pub struct LookupUser {
+    pub handle: String, // 请求中的用户名或邮箱哈希
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+}
+
 #[derive(Deserialize)]
 pub struct SendFriendRequestRequest {
     pub from_user_id: String,
@@ -111,18 +138,26 @@ pub struct RemoveFriendResponse {
     pub message: String,
 }
 
-// 搜索用户
+// 搜索用户：与通讯录匹配一样需要登录，按对方的 discovery_privacy 设置过滤，不返回本人
 pub async fn search_users_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<SearchUsersRequest>,
 ) -> Result<Json<SearchUsersResponse>, AppError> {
+    let viewer = super::user::session_user(&state, &headers)?;
     let users = state.db_pool.search_users(&req.query)
         .map_err(|e| AppError::Database(e.to_string()))?;
 
-    let search_users: Vec<SearchUser> = users.into_iter().map(|user| SearchUser {
-        id: user.id,
-        username: user.username,
-    }).collect();
+    let mut search_users = Vec::new();
+    for user in users {
+        if user.id == viewer || !super::privacy::discoverable(&state, &user.id, &viewer)? {
+            continue;
+        }
+        search_users.push(SearchUser {
+            id: user.id,
+            username: user.username,
+        });
+    }
 
     Ok(Json(SearchUsersResponse {
         success: true,
@@ -131,13 +166,49 @@ pub async fn search_users_handler(
     }))
 }
 
+// 通讯录匹配：返回请求中对应已注册用户的用户名和邮箱哈希，没有匹配到的不出现在结果中；
+// 按对方的 discovery_privacy 设置过滤，不返回本人
+pub async fn lookup_users_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<LookupUsersRequest>,
+) -> Result<Json<LookupUsersResponse>, AppError> {
+    let viewer = super::user::session_user(&state, &headers)?;
+    if req.usernames.len() + req.email_hashes.len() > MAX_LOOKUP_HANDLES {
+        return Err(AppError::InvalidInput(format!("一次最多匹配 {} 个联系人", MAX_LOOKUP_HANDLES)));
+    }
+    let found = state.db_pool.discover_users(&req.usernames, &req.email_hashes)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let mut users = Vec::new();
+    for user in found {
+        if user.user_id == viewer || !super::privacy::discoverable(&state, &user.user_id, &viewer)? {
+            continue;
+        }
+        let display_name = state.db_pool.display_name(&user.user_id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        users.push(LookupUser {
+            handle: user.handle,
+            user_id: user.user_id,
+            username: user.username,
+            display_name,
+        });
+    }
+
+    Ok(Json(LookupUsersResponse {
+        success: true,
+        message: "通讯录匹配完成".into(),
+        users,
+    }))
+}
+
 // 发送好友请求
 pub async fn send_friend_request_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<SendFriendRequestRequest>,
 ) -> Result<Json<SendFriendRequestResponse>, AppError> {
-
-    
+    authorize(&state, &headers, &[Check::Is(&req.from_user_id)])?;
     let result = state.db_pool.send_friend_request(&req.from_user_id, &req.to_username)
         .map_err(|e| {
             match e {
@@ -168,9 +239,7 @@ pub async fn send_friend_request_handler(
     })
     .to_string();
 
-    if let Some(tx) = state.get_clients().lock().unwrap().get(&result.to_user_id) {
-        let _ = tx.send(notify);
-    }
+    state.send_to_user(&result.to_user_id, notify);
 
     Ok(Json(SendFriendRequestResponse {
         success: true,
@@ -182,8 +251,10 @@ pub async fn send_friend_request_handler(
 // 获取收到的好友请求
 pub async fn get_friend_requests_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<GetFriendRequestsRequest>,
 ) -> Result<Json<GetFriendRequestsResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
     let requests = state.db_pool.get_received_friend_requests(&req.user_id)
         .map_err(|e| AppError::Database(e.to_string()))?;
 
@@ -224,8 +295,10 @@ pub async fn get_friend_requests_handler(
 // 响应好友请求
 pub async fn respond_to_friend_request_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<RespondToFriendRequestRequest>,
 ) -> Result<Json<RespondToFriendRequestResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
     // 调用存储层并获取结果（如果被接受，会返回创建的 Friendship）
     let friendship = state.db_pool.respond_to_friend_request(&req.request_id, &req.user_id, &req.response)
         .map_err(|e| {
@@ -273,19 +346,10 @@ pub async fn respond_to_friend_request_handler(
         .to_string();
 
         // 尝试向发送者和接收者发送通知（如果他们通过 websocket 标识并连接）
-        let clients = state.get_clients().lock().unwrap();
-        if let Some(tx) = clients.get(&friendship.friend_id) {
-            println!("Sending friend_added notify to {}: {}", friendship.friend_id, notify);
-            let _ = tx.send(notify.clone());
-        } else {
-            println!("No websocket client for {} when sending notify", friendship.friend_id);
-        }
-        if let Some(tx) = clients.get(&friendship.user_id) {
-            println!("Sending friend_added notify to {}: {}", friendship.user_id, reverse_notify);
-            let _ = tx.send(reverse_notify.clone());
-        } else {
-            println!("No websocket client for {} when sending notify", friendship.user_id);
-        }
+        println!("Sending friend_added notify to {}: {}", friendship.friend_id, notify);
+        state.send_to_user(&friendship.friend_id, notify.clone());
+        println!("Sending friend_added notify to {}: {}", friendship.user_id, reverse_notify);
+        state.send_to_user(&friendship.user_id, reverse_notify.clone());
 
         // 准备返回的好友信息（用于前端立即更新）——对调用者（接收者）返回对方信息
         friendship_info = Some(FriendInfo { id: friendship.user_id.clone(), username: from_username.clone() });
@@ -304,8 +368,10 @@ pub async fn respond_to_friend_request_handler(
 // 获取好友列表
 pub async fn get_friends_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<GetFriendsRequest>,
 ) -> Result<Json<GetFriendsResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
     let friends = state.db_pool.get_friends(&req.user_id)
         .map_err(|e| AppError::Database(e.to_string()))?;
 
@@ -324,8 +390,10 @@ pub async fn get_friends_handler(
 // 删除好友
 pub async fn remove_friend_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<RemoveFriendRequest>,
 ) -> Result<Json<RemoveFriendResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
     state.db_pool.remove_friend(&req.user_id, &req.friend_id)
         .map_err(|e| AppError::Database(e.to_string()))?;
 
@@ -340,6 +408,7 @@ pub fn register_routes() -> Router<AppState> {
     Router::new()
         // 好友功能路由
         .route("/search-users", post(search_users_handler))
+        .route("/users/lookup", post(lookup_users_handler))
         .route("/send-friend-request", post(send_friend_request_handler))
         .route("/friends/add", post(send_friend_request_handler))
         .route("/get-friend-requests", post(get_friend_requests_handler))
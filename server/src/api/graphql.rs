@@ -0,0 +1,391 @@
+//! GraphQL 查询接口：用户、会话列表和分页的消息历史
+//!
+//! 请求携带 `Authorization: Bearer <登录时返回的 token>` 以用户身份查询，
+//! 携带管理令牌时以管理员身份查询（需要通过 userId 参数指定查看哪个用户的数据）。
+//! 会话和消息限定在 `X-Workspace` 请求头指定的工作区内
+
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use axum::{
+    extract::{FromRequestParts, State},
+    http::request::Parts,
+    response::Json,
+    routing::post,
+    Extension, Router
+};
+use crate::error::AppError;
+use crate::storage::{workspaces::Workspace, Conversation, Message, User};
+
+// 共享应用状态
+use super::AppState;
+use super::workspace::WorkspaceScope;
+
+// 单页最多返回的条数
+const MAX_PAGE_SIZE: i64 = 100;
+// 查询嵌套深度和复杂度上限，防止一次请求拖垮数据库
+const MAX_QUERY_DEPTH: usize = 8;
+const MAX_QUERY_COMPLEXITY: usize = 500;
+
+pub type ChatSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// 发起查询的身份
+#[derive(Clone)]
+pub enum Viewer {
+    Anonymous,
+    User(String),
+    Admin,
+}
+
+impl FromRequestParts<AppState> for Viewer {
+    type Rejection = AppError;
+
+    // 没有携带令牌时为匿名访问，携带了无效令牌则直接拒绝
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let Some(token) = super::user::bearer_token(&parts.headers) else {
+            return Ok(Viewer::Anonymous);
+        };
+        if super::admin::token_matches(&state.settings.admin.token, Some(token)) {
+            return Ok(Viewer::Admin);
+        }
+        super::user::session_user(state, &parts.headers).map(Viewer::User)
+    }
+}
+
+// 带错误码的 GraphQL 错误，便于客户端区分
+fn graphql_error(code: &str, message: impl Into<String>) -> async_graphql::Error {
+    async_graphql::Error::new(message).extend_with(|_, e| e.set("code", code))
+}
+
+fn db_error(e: rusqlite::Error) -> async_graphql::Error {
+    graphql_error("INTERNAL", e.to_string())
+}
+
+fn unauthenticated() -> async_graphql::Error {
+    graphql_error("UNAUTHENTICATED", "需要登录")
+}
+
+fn forbidden() -> async_graphql::Error {
+    graphql_error("FORBIDDEN", "无权访问")
+}
+
+// 解析本次查询代表的用户：普通用户只能查自己，管理员必须指定 userId
+fn acting_user(ctx: &Context<'_>, user_id: Option<String>) -> async_graphql::Result<String> {
+    match (ctx.data::<Viewer>()?, user_id) {
+        (Viewer::Anonymous, _) => Err(unauthenticated()),
+        (Viewer::User(viewer), Some(user_id)) if *viewer != user_id => Err(forbidden()),
+        (Viewer::User(viewer), _) => Ok(viewer.clone()),
+        (Viewer::Admin, Some(user_id)) => Ok(user_id),
+        (Viewer::Admin, None) => Err(graphql_error("BAD_REQUEST", "管理员查询需要指定 userId")),
+    }
+}
+
+// 本次查询所在的工作区，用户必须是其成员
+fn workspace_of<'a>(ctx: &Context<'a>, user_id: &str) -> async_graphql::Result<&'a str> {
+    let state = ctx.data::<AppState>()?;
+    let workspace = ctx.data::<Workspace>()?;
+    if !state.db_pool.is_workspace_member(&workspace.id, user_id).map_err(db_error)? {
+        return Err(forbidden());
+    }
+    Ok(&workspace.id)
+}
+
+// 分页参数：默认 20 条，最多 MAX_PAGE_SIZE 条
+fn page_size(first: Option<i64>) -> i64 {
+    first.unwrap_or(20).clamp(1, MAX_PAGE_SIZE)
+}
+
+fn load_user(ctx: &Context<'_>, user_id: &str) -> async_graphql::Result<Option<UserNode>> {
+    let state = ctx.data::<AppState>()?;
+    match state.db_pool.get_user_by_id(user_id) {
+        Ok(user) => Ok(Some(UserNode(user))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(db_error(e)),
+    }
+}
+
+/// 用户
+pub struct UserNode(User);
+
+impl UserNode {
+    // 邮箱和设置等字段只对本人和管理员可见
+    fn check_owner(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        match ctx.data::<Viewer>()? {
+            Viewer::Admin => Ok(()),
+            Viewer::User(viewer) if *viewer == self.0.id => Ok(()),
+            Viewer::User(_) => Err(forbidden()),
+            Viewer::Anonymous => Err(unauthenticated()),
+        }
+    }
+}
+
+/// 用户设置项
+#[derive(SimpleObject)]
+pub struct SettingEntry {
+    key: String,
+    value: String,
+}
+
+#[Object(name = "User")]
+impl UserNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    async fn avatar_url(&self) -> &str {
+        &self.0.avatar_url
+    }
+
+    async fn created_at(&self) -> i64 {
+        self.0.created_at
+    }
+
+    /// 仅本人和管理员可见
+    async fn email(&self, ctx: &Context<'_>) -> async_graphql::Result<&str> {
+        self.check_owner(ctx)?;
+        Ok(&self.0.email)
+    }
+
+    /// 仅本人和管理员可见
+    async fn settings(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<SettingEntry>> {
+        self.check_owner(ctx)?;
+        let state = ctx.data::<AppState>()?;
+        let mut settings: Vec<SettingEntry> = state.db_pool.get_user_settings(&self.0.id)
+            .map_err(db_error)?
+            .into_iter()
+            .map(|(key, value)| SettingEntry { key, value })
+            .collect();
+        settings.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(settings)
+    }
+}
+
+/// 消息
+pub struct MessageNode(Message);
+
+#[Object(name = "Message")]
+impl MessageNode {
+    async fn id(&self) -> &str {
+        &self.0.id
+    }
+
+    async fn sender_id(&self) -> &str {
+        &self.0.sender_id
+    }
+
+    async fn receiver_id(&self) -> &str {
+        &self.0.receiver_id
+    }
+
+    async fn content(&self) -> &str {
+        &self.0.content
+    }
+
+    async fn message_type(&self) -> &str {
+        &self.0.message_type
+    }
+
+    async fn created_at(&self) -> i64 {
+        self.0.created_at
+    }
+
+    /// 服务器收到消息的时间
+    async fn sent_at(&self) -> i64 {
+        self.0.sent_at
+    }
+
+    /// 服务器记录的送达时间，未送达时为空
+    async fn delivered_at(&self) -> Option<i64> {
+        self.0.delivered_at
+    }
+
+    /// 服务器记录的已读时间，未读时为空
+    async fn read_at(&self) -> Option<i64> {
+        self.0.read_at
+    }
+
+    /// 会话内连续递增的序号
+    async fn seq(&self) -> Option<i64> {
+        self.0.seq
+    }
+
+    async fn status(&self) -> &str {
+        &self.0.status
+    }
+
+    async fn is_read(&self) -> bool {
+        self.0.is_read
+    }
+
+    async fn sender(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        load_user(ctx, &self.0.sender_id)
+    }
+}
+
+/// 一页消息，nextCursor 为空表示没有更早的消息
+#[derive(SimpleObject)]
+pub struct MessagePage {
+    messages: Vec<MessageNode>,
+    next_cursor: Option<String>,
+}
+
+// 读取 user_id 与 peer_id 之间（或群 peer_id 中）的一页消息：cursor 为上一页的 nextCursor，优先于 before；
+// 游标与 REST 的历史接口相同，带上消息ID，同一秒内的消息不会漏掉
+fn load_messages(
+    ctx: &Context<'_>,
+    user_id: &str,
+    peer_id: &str,
+    before: Option<i64>,
+    cursor: Option<String>,
+    first: Option<i64>,
+) -> async_graphql::Result<MessagePage> {
+    let state = ctx.data::<AppState>()?;
+    let workspace_id = workspace_of(ctx, user_id)?;
+    let limit = page_size(first);
+    let (before, before_id) = match cursor {
+        Some(cursor) => super::conversation::decode_cursor(&cursor)
+            .map_err(|e| graphql_error("BAD_REQUEST", e.parts().2))?,
+        None => (before.unwrap_or(i64::MAX), String::new()),
+    };
+    let is_group = state.db_pool.is_group_member(peer_id, user_id).map_err(db_error)?;
+    let messages = if is_group {
+        state.db_pool.get_group_history(workspace_id, peer_id, before, &before_id, limit)
+    } else {
+        state.db_pool.get_conversation_history(workspace_id, user_id, peer_id, before, &before_id, limit)
+    }
+    .map_err(db_error)?;
+
+    let next_cursor = (messages.len() as i64 == limit)
+        .then(|| messages.last().map(|m| super::conversation::encode_cursor(m.created_at, &m.id)))
+        .flatten();
+    Ok(MessagePage {
+        messages: messages.into_iter().map(MessageNode).collect(),
+        next_cursor,
+    })
+}
+
+/// 会话（私聊或群聊）
+pub struct ConversationNode {
+    user_id: String,
+    conversation: Conversation,
+}
+
+#[Object(name = "Conversation")]
+impl ConversationNode {
+    /// 私聊对象的用户ID或群ID
+    async fn peer_id(&self) -> &str {
+        &self.conversation.peer_id
+    }
+
+    /// "private" 或 "group"
+    async fn conversation_type(&self) -> &str {
+        &self.conversation.conversation_type
+    }
+
+    async fn last_message_at(&self) -> i64 {
+        self.conversation.last_message_at
+    }
+
+    async fn unread_count(&self) -> i64 {
+        self.conversation.unread_count
+    }
+
+    /// 私聊对象，群聊时为空
+    async fn peer(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        if self.conversation.conversation_type != "private" {
+            return Ok(None);
+        }
+        load_user(ctx, &self.conversation.peer_id)
+    }
+
+    /// 会话中的消息（按时间倒序），cursor 传上一页返回的 nextCursor
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        before: Option<i64>,
+        cursor: Option<String>,
+        first: Option<i64>,
+    ) -> async_graphql::Result<MessagePage> {
+        load_messages(ctx, &self.user_id, &self.conversation.peer_id, before, cursor, first)
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// 当前登录的用户
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<UserNode>> {
+        let user_id = acting_user(ctx, None)?;
+        load_user(ctx, &user_id)
+    }
+
+    /// 按ID查询用户（需要登录）
+    async fn user(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<UserNode>> {
+        if matches!(ctx.data::<Viewer>()?, Viewer::Anonymous) {
+            return Err(unauthenticated());
+        }
+        load_user(ctx, &id)
+    }
+
+    /// 会话列表（按最后一条消息时间倒序）
+    async fn conversations(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i64>,
+        user_id: Option<String>,
+    ) -> async_graphql::Result<Vec<ConversationNode>> {
+        let user_id = acting_user(ctx, user_id)?;
+        let state = ctx.data::<AppState>()?;
+        let workspace_id = workspace_of(ctx, &user_id)?;
+        let conversations = state.db_pool.get_conversations(workspace_id, &user_id, page_size(first)).map_err(db_error)?;
+        Ok(conversations
+            .into_iter()
+            .map(|conversation| ConversationNode { user_id: user_id.clone(), conversation })
+            .collect())
+    }
+
+    /// 与某个用户或群的消息历史，cursor 传上一页返回的 nextCursor
+    async fn messages(
+        &self,
+        ctx: &Context<'_>,
+        peer_id: String,
+        before: Option<i64>,
+        cursor: Option<String>,
+        first: Option<i64>,
+        user_id: Option<String>,
+    ) -> async_graphql::Result<MessagePage> {
+        let user_id = acting_user(ctx, user_id)?;
+        load_messages(ctx, &user_id, &peer_id, before, cursor, first)
+    }
+}
+
+/// 构建 GraphQL schema
+pub fn schema() -> ChatSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_QUERY_DEPTH)
+        .limit_complexity(MAX_QUERY_COMPLEXITY)
+        .finish()
+}
+
+// GraphQL 查询处理器
+pub async fn graphql_handler(
+    viewer: Viewer,
+    State(state): State<AppState>,
+    WorkspaceScope(workspace): WorkspaceScope,
+    Extension(schema): Extension<ChatSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request.data(viewer).data(state).data(workspace)).await)
+}
+
+/// 注册 GraphQL 路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/graphql", post(graphql_handler))
+        .layer(Extension(schema()))
+}
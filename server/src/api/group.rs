@@ -0,0 +1,462 @@
+//! 群主操作（转让群主、删除群聊、禁言成员、公开设置和审批加群申请）和群目录
+//!
+//! 删除分两步：群主先申请一次性确认令牌，再带着令牌删除，防止误操作。删除后群聊留下墓碑，
+//! 成员收到 group_deleted 系统消息后被移出；消息在保留策略的清理期限（purge_deleted_days）后与群一起清除。
+//!
+//! 公开的群出现在工作区的群目录中，工作区成员可以搜索并直接加入；开启加入审批的群先记下申请，
+//! 群主收到 group_join_requested 事件，批准或拒绝后申请人收到 group_join_resolved 事件
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::{delete, get, post, put},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::error::AppError;
+use crate::storage::groups::{GroupJoinRequest, GroupListing};
+use crate::storage::system_messages::SystemEvent;
+
+// 共享应用状态
+use super::AppState;
+use super::policy::{authorize, Check};
+use super::conversation::{decode_cursor, encode_cursor, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+use super::workspace::{require_member, WorkspaceScope};
+use crate::core::datetime::unix_now;
+
+// 删除确认令牌的有效期
+const DELETION_TOKEN_TTL_SECS: i64 = 300;
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+// 群目录搜索关键词的最大长度
+const MAX_DIRECTORY_QUERY_CHARS: usize = 64;
+// 单次禁言的最长时间
+const MAX_MUTE_SECS: i64 = 30 * SECS_PER_DAY;
+
+// 当前会话用户必须是群主，返回用户ID和群所属的工作区
+fn require_owner(state: &AppState, headers: &http::HeaderMap, group_id: &str) -> Result<(String, String), AppError> {
+    let user_id = authorize(state, headers, &[Check::GroupOwner(group_id)])?;
+    let workspace_id = state.db_pool.group_workspace(group_id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("群聊不存在".into()))?;
+    Ok((user_id, workspace_id))
+}
+
+// 转让群主的请求体
+#[derive(Deserialize)]
+pub struct TransferOwnerRequest {
+    pub user_id: String,
+}
+
+// 通用成功响应
+#[derive(Serialize)]
+pub struct GroupResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+// 把群主转让给其他群成员，原群主成为普通成员
+pub async fn transfer_owner_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<TransferOwnerRequest>,
+) -> Result<Json<GroupResponse>, AppError> {
+    let (owner, workspace_id) = require_owner(&state, &headers, &group_id)?;
+    if req.user_id == owner {
+        return Err(AppError::InvalidInput("你已经是群主".into()));
+    }
+    let transferred = state.db_pool.transfer_group_ownership(&group_id, &owner, &req.user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !transferred {
+        return Err(AppError::InvalidInput("新群主必须是群成员".into()));
+    }
+    super::message::post_system_message(&state, &workspace_id, &group_id, SystemEvent::OwnerChanged {
+        old_owner: owner,
+        new_owner: req.user_id,
+    })?;
+
+    Ok(Json(GroupResponse {
+        success: true,
+        message: "群主已转让".into(),
+    }))
+}
+
+// 删除确认令牌响应体
+#[derive(Serialize)]
+pub struct DeletionTokenResponse {
+    pub success: bool,
+    pub message: String,
+    pub confirmation_token: String,   // 只返回这一次，删除时原样提交
+    pub expires_at: i64,
+}
+
+// 申请删除群聊的确认令牌，重复申请时之前的令牌作废
+pub async fn deletion_token_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<DeletionTokenResponse>, AppError> {
+    let (owner, _) = require_owner(&state, &headers, &group_id)?;
+    let expires_at = unix_now() + DELETION_TOKEN_TTL_SECS;
+    let confirmation_token = state.db_pool.create_group_deletion_token(&group_id, &owner, expires_at)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(DeletionTokenResponse {
+        success: true,
+        message: "请在有效期内确认删除".into(),
+        confirmation_token,
+        expires_at,
+    }))
+}
+
+// 删除群聊的请求体
+#[derive(Deserialize)]
+pub struct DeleteGroupRequest {
+    pub confirmation_token: String,
+}
+
+// 删除群聊响应体
+#[derive(Serialize)]
+pub struct DeleteGroupResponse {
+    pub success: bool,
+    pub message: String,
+    pub purge_at: Option<i64>,   // 消息彻底清除的时间，保留策略不清除已删除数据时为空
+}
+
+// 带确认令牌删除群聊：先在群里留下系统消息通知成员，再留下墓碑并移除全部成员
+pub async fn delete_group_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<DeleteGroupRequest>,
+) -> Result<Json<DeleteGroupResponse>, AppError> {
+    let (owner, workspace_id) = require_owner(&state, &headers, &group_id)?;
+    let now = unix_now();
+    let confirmed = state.db_pool.consume_group_deletion_token(&group_id, &owner, &req.confirmation_token, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !confirmed {
+        return Err(AppError::Forbidden("确认令牌无效或已过期".into()));
+    }
+    super::message::post_system_message(&state, &workspace_id, &group_id, SystemEvent::GroupDeleted { deleted_by: owner })?;
+    state.db_pool.tombstone_group(&group_id, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let purge_days = state.settings.retention.purge_deleted_days as i64;
+    Ok(Json(DeleteGroupResponse {
+        success: true,
+        message: "群聊已删除".into(),
+        purge_at: (purge_days > 0).then_some(now + purge_days * SECS_PER_DAY),
+    }))
+}
+
+// 公开设置的请求体，join_approval 省略时为 false
+#[derive(Deserialize)]
+pub struct VisibilityRequest {
+    pub visibility: String,
+    #[serde(default)]
+    pub join_approval: bool,
+}
+
+// 设置群是否公开（public 或 private）以及加入是否需要群主批准
+pub async fn set_visibility_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<VisibilityRequest>,
+) -> Result<Json<GroupResponse>, AppError> {
+    require_owner(&state, &headers, &group_id)?;
+    let public = match req.visibility.as_str() {
+        "public" => true,
+        "private" => false,
+        _ => return Err(AppError::InvalidInput("群可见性只能是 public 或 private".into())),
+    };
+    state.db_pool.set_group_visibility(&group_id, public, req.join_approval)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(GroupResponse {
+        success: true,
+        message: "群设置已更新".into(),
+    }))
+}
+
+// 群目录的查询参数，query 为按群名搜索的关键词
+#[derive(Deserialize)]
+pub struct DirectoryQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+    pub query: Option<String>,
+}
+
+// 群目录响应
+#[derive(Serialize)]
+pub struct DirectoryResponse {
+    pub success: bool,
+    pub message: String,
+    pub groups: Vec<GroupListing>,
+    pub next_cursor: Option<String>,
+}
+
+// 工作区内的公开群，按创建时间倒序分页；只有工作区成员可以查看
+pub async fn discover_groups_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
+    Query(query): Query<DirectoryQuery>,
+) -> Result<Json<DirectoryResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    require_member(&state, workspace.id(), &user_id)?;
+    let search = query.query.as_deref().unwrap_or_default().trim();
+    if search.chars().count() > MAX_DIRECTORY_QUERY_CHARS {
+        return Err(AppError::InvalidInput(format!("群名搜索关键词不能超过 {} 个字符", MAX_DIRECTORY_QUERY_CHARS)));
+    }
+    let before = match &query.cursor {
+        Some(cursor) => decode_cursor(cursor)?,
+        None => (i64::MAX, String::new()),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let groups = state.db_pool.group_directory(workspace.id(), search, (before.0, &before.1), limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let next_cursor = (groups.len() as i64 == limit)
+        .then(|| groups.last().map(|g| encode_cursor(g.created_at, &g.id)))
+        .flatten();
+    Ok(Json(DirectoryResponse {
+        success: true,
+        message: "获取群目录成功".into(),
+        groups,
+        next_cursor,
+    }))
+}
+
+// 加入公开群的响应
+#[derive(Serialize)]
+pub struct JoinGroupResponse {
+    pub success: bool,
+    pub message: String,
+    pub joined: bool,    // 是否新加入
+    pub pending: bool,   // 是否在等待群主批准
+}
+
+// 加入公开群：不需要审批时直接加入，否则记下申请并通知群主；已是成员时什么也不做
+pub async fn join_group_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<JoinGroupResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let settings = state.db_pool.group_directory_settings(&group_id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("群聊不存在".into()))?;
+    if state.db_pool.is_group_member(&group_id, &user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Ok(Json(JoinGroupResponse {
+            success: true,
+            message: "已加入群聊".into(),
+            joined: false,
+            pending: false,
+        }));
+    }
+    // 私有群对非成员不可见
+    if !settings.public {
+        return Err(AppError::NotFound("群聊不存在".into()));
+    }
+    require_member(&state, &settings.workspace_id, &user_id)?;
+
+    if settings.join_approval {
+        let requested_at = unix_now();
+        let created = state.db_pool.create_join_request(&group_id, &user_id, requested_at)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let owner = state.db_pool.group_owner(&group_id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        if let (true, Some(owner)) = (created, owner) {
+            state.send_to_user(&owner, json!({
+                "type": "group_join_requested",
+                "group_id": group_id,
+                "user_id": user_id,
+                "requested_at": requested_at,
+            }).to_string());
+        }
+        return Ok(Json(JoinGroupResponse {
+            success: true,
+            message: "已提交加群申请，等待群主批准".into(),
+            joined: false,
+            pending: true,
+        }));
+    }
+
+    let joined = state.db_pool.add_group_member(&group_id, &user_id, "member")
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if joined {
+        super::message::post_system_message(&state, &settings.workspace_id, &group_id, SystemEvent::MemberJoined { user_id })?;
+    }
+    Ok(Json(JoinGroupResponse {
+        success: true,
+        message: "已加入群聊".into(),
+        joined,
+        pending: false,
+    }))
+}
+
+// 加群申请列表响应
+#[derive(Serialize)]
+pub struct JoinRequestsResponse {
+    pub success: bool,
+    pub message: String,
+    pub requests: Vec<GroupJoinRequest>,
+}
+
+// 群主查看等待批准的加群申请
+pub async fn list_join_requests_handler(
+    State(state): State<AppState>,
+    Path(group_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<JoinRequestsResponse>, AppError> {
+    require_owner(&state, &headers, &group_id)?;
+    let requests = state.db_pool.join_requests(&group_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(JoinRequestsResponse {
+        success: true,
+        message: "获取加群申请成功".into(),
+        requests,
+    }))
+}
+
+// 处理加群申请的请求体
+#[derive(Deserialize)]
+pub struct ResolveJoinRequest {
+    pub approve: bool,
+}
+
+// 群主批准或拒绝加群申请，申请人收到 group_join_resolved 事件
+pub async fn resolve_join_request_handler(
+    State(state): State<AppState>,
+    Path((group_id, user_id)): Path<(String, String)>,
+    headers: http::HeaderMap,
+    Json(req): Json<ResolveJoinRequest>,
+) -> Result<Json<GroupResponse>, AppError> {
+    let (_, workspace_id) = require_owner(&state, &headers, &group_id)?;
+    let found = state.db_pool.take_join_request(&group_id, &user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !found {
+        return Err(AppError::NotFound("加群申请不存在".into()));
+    }
+    let joined = if req.approve {
+        // 申请后离开了工作区的用户不能再加入
+        require_member(&state, &workspace_id, &user_id)?;
+        state.db_pool.add_group_member(&group_id, &user_id, "member")
+            .map_err(|e| AppError::Database(e.to_string()))?
+    } else {
+        false
+    };
+    // 先告诉申请人结果，再在群里发 member_joined 系统消息
+    state.send_to_user(&user_id, json!({
+        "type": "group_join_resolved",
+        "group_id": group_id,
+        "approved": req.approve,
+    }).to_string());
+    if joined {
+        super::message::post_system_message(&state, &workspace_id, &group_id, SystemEvent::MemberJoined { user_id })?;
+    }
+
+    Ok(Json(GroupResponse {
+        success: true,
+        message: if req.approve { "已批准加群申请" } else { "已拒绝加群申请" }.into(),
+    }))
+}
+
+/// 发送者在群里被禁言且未到期时返回 Muted，错误中带禁言结束时间
+pub(crate) fn require_not_muted(state: &AppState, group_id: &str, user_id: &str) -> Result<(), AppError> {
+    let members = state.db_pool.get_group_members(group_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let muted_until = members.iter()
+        .find(|m| m.user_id == user_id)
+        .and_then(|m| m.muted_until)
+        .filter(|&until| until > unix_now());
+    match muted_until {
+        Some(until) => Err(AppError::Muted(until)),
+        None => Ok(()),
+    }
+}
+
+// 禁言的请求体
+#[derive(Deserialize)]
+pub struct MuteRequest {
+    pub duration_secs: i64,
+}
+
+// 禁言响应体
+#[derive(Serialize)]
+pub struct MuteResponse {
+    pub success: bool,
+    pub message: String,
+    pub muted_until: Option<i64>,   // 禁言结束时间，解除禁言后为空
+}
+
+// 设置成员的禁言结束时间并通知被禁言的成员
+fn set_muted_until(state: &AppState, headers: &http::HeaderMap, group_id: &str, user_id: &str, muted_until: Option<i64>) -> Result<(), AppError> {
+    let (owner, _) = require_owner(state, headers, group_id)?;
+    if user_id == owner {
+        return Err(AppError::InvalidInput("不能禁言群主".into()));
+    }
+    let updated = state.db_pool.set_member_muted_until(group_id, user_id, muted_until)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !updated {
+        return Err(AppError::NotFound("该用户不是群成员".into()));
+    }
+    state.send_to_user(user_id, json!({
+        "type": "group_member_muted",
+        "group_id": group_id,
+        "muted_until": muted_until,
+    }).to_string());
+    Ok(())
+}
+
+// 群主禁言成员 duration_secs 秒，禁言期间该成员在群里发的消息被拒绝；重复禁言时以新的时长为准
+pub async fn mute_member_handler(
+    State(state): State<AppState>,
+    Path((group_id, user_id)): Path<(String, String)>,
+    headers: http::HeaderMap,
+    Json(req): Json<MuteRequest>,
+) -> Result<Json<MuteResponse>, AppError> {
+    if !(1..=MAX_MUTE_SECS).contains(&req.duration_secs) {
+        return Err(AppError::InvalidInput(format!("禁言时长应为 1 到 {} 秒", MAX_MUTE_SECS)));
+    }
+    let muted_until = unix_now() + req.duration_secs;
+    set_muted_until(&state, &headers, &group_id, &user_id, Some(muted_until))?;
+
+    Ok(Json(MuteResponse {
+        success: true,
+        message: "已禁言该成员".into(),
+        muted_until: Some(muted_until),
+    }))
+}
+
+// 群主提前解除成员的禁言
+pub async fn unmute_member_handler(
+    State(state): State<AppState>,
+    Path((group_id, user_id)): Path<(String, String)>,
+    headers: http::HeaderMap,
+) -> Result<Json<MuteResponse>, AppError> {
+    set_muted_until(&state, &headers, &group_id, &user_id, None)?;
+
+    Ok(Json(MuteResponse {
+        success: true,
+        message: "已解除禁言".into(),
+        muted_until: None,
+    }))
+}
+
+/// 注册群主操作和群目录路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/groups/{group_id}/owner", put(transfer_owner_handler))
+        .route("/groups/{group_id}/deletion-token", post(deletion_token_handler))
+        .route("/groups/{group_id}", delete(delete_group_handler))
+        .route("/groups/{group_id}/visibility", put(set_visibility_handler))
+        .route("/groups/discover", get(discover_groups_handler))
+        .route("/groups/{group_id}/join", post(join_group_handler))
+        .route("/groups/{group_id}/join-requests", get(list_join_requests_handler))
+        .route("/groups/{group_id}/join-requests/{user_id}", post(resolve_join_request_handler))
+        .route("/groups/{group_id}/members/{user_id}/mute", put(mute_member_handler).delete(unmute_member_handler))
+}
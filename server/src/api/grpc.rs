@@ -0,0 +1,199 @@
+//! gRPC 接口：注册、登录、发消息和实时事件订阅
+//!
+//! 与 REST 接口共用同一份 AppState 和业务逻辑，只是换了一种传输方式。
+//! 服务令牌（`[grpc] token`，放在 authorization 元数据中）只决定能否调用本服务；
+//! 发消息和订阅事件以哪个用户的身份进行，由 x-session-token 元数据中的会话令牌（登录返回的 token）确定
+
+use futures_util::stream::{self, BoxStream, StreamExt};
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tonic::transport::server::TcpIncoming;
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+use crate::error::AppError;
+use super::policy::{self, Check};
+use super::AppState;
+
+/// 由 proto/yueling.proto 生成的消息类型、服务端和客户端
+pub mod proto {
+    tonic::include_proto!("yueling.v1");
+}
+
+use proto::chat_server::{Chat, ChatServer};
+use proto::{
+    Event, LoginReply, LoginRequest, RegisterReply, RegisterRequest,
+    SendMessageReply, SendMessageRequest, StreamEventsRequest,
+};
+
+// 与 REST 接口的状态码保持一致
+impl From<AppError> for Status {
+    fn from(e: AppError) -> Self {
+        match e {
+            AppError::UserExists(msg) => Status::already_exists(msg),
+            AppError::InvalidCredentials(msg) => Status::unauthenticated(msg),
+            AppError::NotFound(msg) => Status::not_found(msg),
+            AppError::Forbidden(msg) => Status::permission_denied(msg),
+            AppError::InvalidInput(msg) | AppError::FriendOperation(msg) => Status::invalid_argument(msg),
+            AppError::RateLimited(msg) => Status::resource_exhausted(msg),
+            AppError::Conflict(msg) => Status::aborted(msg),
+            AppError::Maintenance(msg) => Status::unavailable(msg),
+            e @ AppError::Muted(_) => Status::permission_denied(e.parts().2),
+            e => Status::internal(e.to_string()),
+        }
+    }
+}
+
+struct ChatService {
+    state: AppState,
+}
+
+impl ChatService {
+    // 按 x-session-token 元数据中的会话令牌识别调用者；请求中的用户ID可以留空，填写时只能是调用者本人
+    fn caller<T>(&self, request: &Request<T>, claimed: &str) -> Result<String, Status> {
+        let mut headers = http::HeaderMap::new();
+        if let Some(token) = request.metadata().get("x-session-token").and_then(|value| value.to_str().ok())
+            && let Ok(value) = format!("Bearer {}", token).parse()
+        {
+            headers.insert(http::header::AUTHORIZATION, value);
+        }
+        let checks: Vec<Check> = (!claimed.is_empty()).then_some(Check::Is(claimed)).into_iter().collect();
+        Ok(policy::authorize(&self.state, &headers, &checks)?)
+    }
+}
+
+#[tonic::async_trait]
+impl Chat for ChatService {
+    async fn register(&self, request: Request<RegisterRequest>) -> Result<Response<RegisterReply>, Status> {
+        let req = request.into_inner();
+        // 与 REST 注册一样，开启注册防刷时先完成人机验证
+        let proof = super::challenge::ChallengeProof {
+            pow_challenge: req.pow_challenge,
+            pow_nonce: req.pow_nonce,
+            captcha_token: req.captcha_token,
+        };
+        super::challenge::verify(&self.state, &proof).await?;
+        let user = super::user::register_user(
+            &self.state,
+            &req.username,
+            &req.email,
+            &req.password,
+            &req.workspace,
+            req.invite_code.as_deref(),
+        ).await?;
+        Ok(Response::new(RegisterReply { user_id: user.id }))
+    }
+
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<LoginReply>, Status> {
+        let req = request.into_inner();
+        let device = super::devices::DeviceInfo {
+            device_id: req.device_id,
+            device_name: req.device_name,
+            platform: req.platform,
+            push_token: req.push_token,
+            device_code: req.device_code,
+        };
+        let login = super::user::login_user(&self.state, &req.username, &req.password, &device).await?;
+        Ok(Response::new(LoginReply {
+            user_id: login.user_id.unwrap_or_default(),
+            username: login.username.unwrap_or_default(),
+            token: login.token.unwrap_or_default(),
+            device_id: login.device_id.unwrap_or_default(),
+            device_verification_required: login.device_verification_required,
+        }))
+    }
+
+    async fn send_message(&self, request: Request<SendMessageRequest>) -> Result<Response<SendMessageReply>, Status> {
+        let sender_id = self.caller(&request, &request.get_ref().sender_id)?;
+        let req = request.into_inner();
+        let message_type = if req.message_type.is_empty() { "private" } else { req.message_type.as_str() };
+        let workspace = super::workspace::resolve_workspace(&self.state, &req.workspace)?;
+        let client_message_id = (!req.client_message_id.is_empty()).then_some(req.client_message_id.as_str());
+        let (message, created) = super::message::send_message_once(
+            &self.state,
+            &workspace.id,
+            &sender_id,
+            &req.receiver_id,
+            &req.content,
+            (!req.format.is_empty()).then_some(req.format.as_str()),
+            message_type,
+            client_message_id,
+        )?;
+
+        // 私聊保存时已经推送给接收方，群聊广播给在线成员（重发的消息已经推送过）
+        if created && message.message_type == "group" {
+            self.state.send_to_group(&message.receiver_id, super::message::message_event(&message));
+        }
+        if created {
+            super::message::echo_to_own_devices(&self.state, &message, client_message_id, None);
+        }
+
+        Ok(Response::new(SendMessageReply {
+            message_id: message.id,
+            created_at: message.created_at,
+            client_message_id: req.client_message_id,
+            sent_at: message.sent_at,
+            seq: message.seq.unwrap_or_default(),
+        }))
+    }
+
+    type StreamEventsStream = BoxStream<'static, Result<Event, Status>>;
+
+    async fn stream_events(&self, request: Request<StreamEventsRequest>) -> Result<Response<Self::StreamEventsStream>, Status> {
+        let user_id = self.caller(&request, &request.get_ref().user_id)?;
+
+        // 像一个 WebSocket 连接一样标识为该用户，调用方断开后清理
+        let client_id = format!("grpc-{}", Uuid::new_v4());
+        let (tx, mut rx) = super::outbound::channel(self.state.settings.server.ws_send_queue);
+        self.state.attach_client(&client_id, &user_id, tx.clone());
+
+        let (events_tx, events_rx) = mpsc::channel(100);
+        let state = self.state.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    // 处理过慢时发送队列按优先级丢弃事件，丢弃数见 /metrics
+                    received = rx.recv() => match received {
+                        Some(payload) => {
+                            if events_tx.send(Ok(Event { payload })).await.is_err() {
+                                break;
+                            }
+                        }
+                        None => break,
+                    },
+                    _ = events_tx.closed() => break,
+                }
+            }
+            state.detach_client(&client_id, &tx);
+        });
+
+        let events = stream::unfold(events_rx, |mut rx| async move { rx.recv().await.map(|event| (event, rx)) });
+        Ok(Response::new(events.boxed()))
+    }
+}
+
+// 校验调用方携带的令牌（未配置令牌时不校验）
+fn authorize(token: &str, request: Request<()>) -> Result<Request<()>, Status> {
+    if token.is_empty() {
+        return Ok(request);
+    }
+    let provided = request.metadata()
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if super::admin::token_matches(token, provided) {
+        Ok(request)
+    } else {
+        Err(Status::unauthenticated("缺少或错误的 gRPC 令牌"))
+    }
+}
+
+/// 在已绑定的端口上提供 gRPC 服务，直到出错退出
+pub async fn serve(state: AppState, listener: TcpListener) -> Result<(), tonic::transport::Error> {
+    let token = state.settings.grpc.token.clone();
+    let service = ChatServer::with_interceptor(ChatService { state }, move |request| authorize(&token, request));
+    tonic::transport::Server::builder()
+        .add_service(service)
+        .serve_with_incoming(TcpIncoming::from(listener))
+        .await
+}
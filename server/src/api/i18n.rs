@@ -0,0 +1,226 @@
+//! 接口提示语的多语言支持
+//!
+//! 处理器照常返回简体中文提示语；响应经过本中间件时按 `Accept-Language` 协商语言，
+//! 在 `locales/` 目录中找到提示语对应的 code 后替换 `message` 并附上 `code`，
+//! 客户端应以 `code` 判断结果，`message` 只用于展示
+
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::Response
+};
+use serde_json::Value;
+
+// 未协商出支持的语言时使用源语言
+pub const DEFAULT_LOCALE: &str = "zh-CN";
+
+// 超过该大小的响应（大批量的消息列表等）不做翻译，原样返回
+const MAX_LOCALIZED_BODY: u64 = 1024 * 1024;
+
+// 语言目录：键为 code，值为带 {} 占位符的提示语
+const CATALOGS: &[(&str, &str)] = &[
+    ("zh-CN", include_str!("../../locales/zh-CN.toml")),
+    ("en-US", include_str!("../../locales/en-US.toml")),
+];
+
+static CATALOG: LazyLock<Catalog> = LazyLock::new(|| Catalog::load(CATALOGS));
+
+// 把 [section] key = "..." 展开成 section.key
+fn flatten(prefix: &str, table: &toml::Table, out: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let code = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::Table(table) => flatten(&code, table, out),
+            toml::Value::String(text) => {
+                out.insert(code, text.clone());
+            }
+            _ => panic!("语言目录中 {} 的值必须是字符串", code),
+        }
+    }
+}
+
+// 按模板匹配提示语，返回依次填入占位符的参数
+fn match_template(template: &str, text: &str) -> Option<Vec<String>> {
+    let parts: Vec<&str> = template.split("{}").collect();
+    let (first, rest) = parts.split_first()?;
+    let mut remaining = text.strip_prefix(first)?;
+    let mut args = Vec::new();
+    for (i, part) in rest.iter().enumerate() {
+        let end = if i == rest.len() - 1 {
+            // 最后一段必须出现在结尾
+            if !remaining.ends_with(part) {
+                return None;
+            }
+            remaining.len() - part.len()
+        } else {
+            remaining.find(part)?
+        };
+        args.push(remaining[..end].to_string());
+        remaining = &remaining[end + part.len()..];
+    }
+    Some(args)
+}
+
+fn render(template: &str, args: &[String]) -> String {
+    let mut parts = template.split("{}");
+    let mut out = parts.next().unwrap_or_default().to_string();
+    for (part, arg) in parts.zip(args.iter().map(String::as_str).chain(std::iter::repeat(""))) {
+        out.push_str(arg);
+        out.push_str(part);
+    }
+    out
+}
+
+struct Catalog {
+    // 源语言提示语到 code 的反查表（不带占位符的）
+    exact: HashMap<String, String>,
+    // 带占位符的源语言模板，较长的排在前面以优先匹配更具体的模板
+    templates: Vec<(String, String)>,
+    locales: HashMap<&'static str, HashMap<String, String>>,
+}
+
+impl Catalog {
+    fn load(catalogs: &[(&'static str, &str)]) -> Self {
+        let mut locales = HashMap::new();
+        for (locale, text) in catalogs {
+            let table: toml::Table = toml::from_str(text)
+                .unwrap_or_else(|e| panic!("解析语言目录 {} 失败: {}", locale, e));
+            let mut entries = HashMap::new();
+            flatten("", &table, &mut entries);
+            locales.insert(*locale, entries);
+        }
+
+        let mut exact = HashMap::new();
+        let mut templates = Vec::new();
+        for (code, text) in &locales[DEFAULT_LOCALE] {
+            if text.contains("{}") {
+                templates.push((text.clone(), code.clone()));
+            } else {
+                exact.insert(text.clone(), code.clone());
+            }
+        }
+        templates.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.1.cmp(&b.1)));
+        Self { exact, templates, locales }
+    }
+
+    // 查找源语言提示语对应的 code 和占位符参数
+    fn lookup(&self, message: &str) -> Option<(&str, Vec<String>)> {
+        if let Some(code) = self.exact.get(message) {
+            return Some((code, Vec::new()));
+        }
+        self.templates
+            .iter()
+            .find_map(|(template, code)| match_template(template, message).map(|args| (code.as_str(), args)))
+    }
+
+    fn translate(&self, locale: &str, code: &str, args: &[String]) -> Option<String> {
+        self.locales.get(locale)?.get(code).map(|template| render(template, args))
+    }
+}
+
+/// 按 `Accept-Language` 选出支持的语言（按 q 值排序，主语言相同即可匹配，如 en 对应 en-US）
+pub fn negotiate(accept_language: Option<&str>) -> &'static str {
+    let Some(header) = accept_language else {
+        return DEFAULT_LOCALE;
+    };
+    let mut ranges: Vec<(&str, f32)> = header
+        .split(',')
+        .filter_map(|range| {
+            let mut fields = range.split(';');
+            let tag = fields.next()?.trim();
+            let q = fields
+                .find_map(|field| field.trim().strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && q > 0.0).then_some((tag, q))
+        })
+        .collect();
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    for (tag, _) in ranges {
+        if tag == "*" {
+            return DEFAULT_LOCALE;
+        }
+        let primary = tag.split('-').next().unwrap_or(tag);
+        let matched = CATALOGS
+            .iter()
+            .map(|(locale, _)| *locale)
+            .find(|locale| locale.eq_ignore_ascii_case(tag))
+            .or_else(|| CATALOGS.iter().map(|(locale, _)| *locale).find(|locale| {
+                locale.split('-').next().is_some_and(|p| p.eq_ignore_ascii_case(primary))
+            }));
+        if let Some(locale) = matched {
+            return locale;
+        }
+    }
+    DEFAULT_LOCALE
+}
+
+/// 把源语言提示语翻译为 locale，返回 (code, 提示语)；目录中没有该提示语时使用 fallback_code，提示语原样返回
+///
+/// 用于不经过 HTTP 中间件的提示语，如 WebSocket 错误事件
+pub fn localize_message(locale: &str, message: &str, fallback_code: &str) -> (String, String) {
+    match CATALOG.lookup(message) {
+        Some((code, args)) => (
+            code.to_string(),
+            CATALOG.translate(locale, code, &args).unwrap_or_else(|| message.to_string()),
+        ),
+        None => (fallback_code.to_string(), message.to_string()),
+    }
+}
+
+// 替换 JSON 响应中的 message 并附上 code，返回是否有改动
+fn localize_body(value: &mut Value, locale: &str) -> bool {
+    let Some(object) = value.as_object_mut() else {
+        return false;
+    };
+    let Some(Value::String(message)) = object.get("message") else {
+        return false;
+    };
+    let Some((code, args)) = CATALOG.lookup(message) else {
+        return false;
+    };
+    let translated = CATALOG.translate(locale, code, &args).unwrap_or_else(|| message.clone());
+    let code = code.to_string();
+    object.insert("message".into(), Value::String(translated));
+    object.insert("code".into(), Value::String(code));
+    true
+}
+
+/// 多语言中间件，作用于全部路由的 JSON 响应
+pub async fn localize(request: Request, next: Next) -> Response {
+    let locale = negotiate(request.headers().get(header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()));
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let small = response.body().size_hint().exact().is_some_and(|len| len <= MAX_LOCALIZED_BODY);
+    if !is_json || !small {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let Ok(bytes) = axum::body::to_bytes(body, MAX_LOCALIZED_BODY as usize).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+    let localized = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|mut value| localize_body(&mut value, locale).then_some(value));
+    let body = match localized {
+        Some(value) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Body::from(value.to_string())
+        }
+        None => Body::from(bytes),
+    };
+    parts.headers.insert(header::CONTENT_LANGUAGE, HeaderValue::from_static(locale));
+    Response::from_parts(parts, body)
+}
@@ -0,0 +1,210 @@
+//! 按来源 IP 的访问控制：在路由之前拒绝不在允许网段内（或在拒绝网段内）的请求
+//!
+//! 规则来自配置文件的 `[access]`，运行时可以通过管理接口替换或从配置文件重新加载
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router
+};
+use ipnet::IpNet;
+use serde::Serialize;
+use crate::config::settings::AccessSettings;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::AdminAuth;
+
+// 解析后的规则
+#[derive(Debug, Default)]
+struct AccessRules {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    admin_allow: Vec<IpNet>,
+}
+
+// 接受 CIDR 或单个地址
+fn parse_nets(entries: &[String]) -> Result<Vec<IpNet>, String> {
+    entries
+        .iter()
+        .map(|entry| {
+            let entry = entry.trim();
+            entry.parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("无效的网段: {}", entry))
+        })
+        .collect()
+}
+
+fn contains(nets: &[IpNet], ip: IpAddr) -> bool {
+    nets.iter().any(|net| net.contains(&ip))
+}
+
+impl AccessRules {
+    fn parse(settings: &AccessSettings) -> Result<Self, String> {
+        Ok(Self {
+            allow: parse_nets(&settings.allow)?,
+            deny: parse_nets(&settings.deny)?,
+            admin_allow: parse_nets(&settings.admin_allow)?,
+        })
+    }
+
+    fn is_empty(&self, admin: bool) -> bool {
+        self.allow.is_empty() && self.deny.is_empty() && (!admin || self.admin_allow.is_empty())
+    }
+
+    // 拒绝列表优先；配置了规则却拿不到来源 IP 时一律拒绝
+    fn permits(&self, ip: Option<IpAddr>, admin: bool) -> bool {
+        if self.is_empty(admin) {
+            return true;
+        }
+        let Some(ip) = ip.map(|ip| ip.to_canonical()) else {
+            return false;
+        };
+        if contains(&self.deny, ip) {
+            return false;
+        }
+        if !self.allow.is_empty() && !contains(&self.allow, ip) {
+            return false;
+        }
+        !admin || self.admin_allow.is_empty() || contains(&self.admin_allow, ip)
+    }
+}
+
+/// 可在运行时替换的 IP 访问控制规则
+#[derive(Clone)]
+pub struct IpFilter {
+    inner: Arc<RwLock<(AccessSettings, AccessRules)>>,
+}
+
+impl IpFilter {
+    /// 按配置构建，配置中有无效网段时返回错误
+    pub fn from_settings(settings: &AccessSettings) -> Result<Self, String> {
+        let rules = AccessRules::parse(settings)?;
+        Ok(Self { inner: Arc::new(RwLock::new((settings.clone(), rules))) })
+    }
+
+    /// 拒绝所有来源（配置无效时使用，避免在规则缺失的情况下放行）
+    pub fn deny_all() -> Self {
+        let settings = AccessSettings {
+            deny: vec!["0.0.0.0/0".into(), "::/0".into()],
+            ..Default::default()
+        };
+        Self::from_settings(&settings).expect("内置规则必然有效")
+    }
+
+    /// 当前生效的规则
+    pub fn current(&self) -> AccessSettings {
+        self.inner.read().unwrap().0.clone()
+    }
+
+    /// 整体替换规则，有无效网段时保持原规则不变
+    pub fn replace(&self, settings: AccessSettings) -> Result<(), String> {
+        let rules = AccessRules::parse(&settings)?;
+        *self.inner.write().unwrap() = (settings, rules);
+        Ok(())
+    }
+
+    /// 请求的来源 IP，按 trust_forwarded_for 决定是否采用 X-Forwarded-For
+    pub fn client_ip(&self, request: &Request) -> Option<IpAddr> {
+        client_ip(request, &self.inner.read().unwrap().0)
+    }
+
+    // admin 为 true 时还要求来源在 admin_allow 内
+    fn permits(&self, request: &Request, admin: bool) -> bool {
+        let inner = self.inner.read().unwrap();
+        inner.1.permits(client_ip(request, &inner.0), admin)
+    }
+}
+
+// 请求的来源 IP：信任代理时取 X-Forwarded-For 从右数第 forwarded_hops 个地址，否则取连接的对端地址。
+// 左边的地址由客户端自己填写，不可信；每层代理在右边追加它看到的对端地址。
+// 地址数不足 forwarded_hops 时取最左边的，此时它也是由可信代理追加的
+fn client_ip(request: &Request, settings: &AccessSettings) -> Option<IpAddr> {
+    if settings.trust_forwarded_for
+        && let Some(forwarded) = request.headers().get("x-forwarded-for").and_then(|v| v.to_str().ok())
+    {
+        let hops: Vec<&str> = forwarded.split(',').collect();
+        let index = hops.len().saturating_sub(settings.forwarded_hops.max(1));
+        return hops[index].trim().parse().ok();
+    }
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip())
+}
+
+/// 访问控制中间件，作用于全部路由。`/admin` 下的路由和携带管理令牌的请求（如 `POST /user/{id}/restore`、
+/// 管理员签发邀请码、代发批量消息、GraphQL 管理查询）都按 admin_allow 检查来源
+pub async fn enforce(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let admin = request.uri().path().starts_with("/admin")
+        || super::admin::token_matches(&state.settings.admin.token, super::user::bearer_token(request.headers()));
+    if state.ip_filter.permits(&request, admin) {
+        next.run(request).await
+    } else {
+        AppError::Forbidden("来源地址不允许访问".into()).into_response()
+    }
+}
+
+// 访问控制规则响应体
+#[derive(Serialize)]
+pub struct AccessResponse {
+    pub success: bool,
+    pub message: String,
+    pub access: AccessSettings,
+}
+
+// 查看当前生效的规则
+pub async fn get_access_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<AccessResponse>, AppError> {
+    Ok(Json(AccessResponse {
+        success: true,
+        message: "获取访问控制规则成功".into(),
+        access: state.ip_filter.current(),
+    }))
+}
+
+// 整体替换规则（只在内存中生效，重启后以配置文件为准）
+pub async fn replace_access_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(access): Json<AccessSettings>,
+) -> Result<Json<AccessResponse>, AppError> {
+    state.ip_filter.replace(access).map_err(AppError::InvalidInput)?;
+
+    Ok(Json(AccessResponse {
+        success: true,
+        message: "访问控制规则已更新".into(),
+        access: state.ip_filter.current(),
+    }))
+}
+
+// 从配置文件重新加载规则
+pub async fn reload_access_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<AccessResponse>, AppError> {
+    let settings = crate::config::loader::load().map_err(AppError::Internal)?;
+    state.ip_filter.replace(settings.access).map_err(AppError::InvalidInput)?;
+
+    Ok(Json(AccessResponse {
+        success: true,
+        message: "已从配置文件重新加载访问控制规则".into(),
+        access: state.ip_filter.current(),
+    }))
+}
+
+/// 注册访问控制管理路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/access", get(get_access_handler).put(replace_access_handler))
+        .route("/admin/access/reload", post(reload_access_handler))
+}
@@ -0,0 +1,155 @@
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    response::Json,
+    routing::{get, post},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::error::AppError;
+use crate::storage::jobs::{Job, JOB_EXPORT_USER, JOB_REKEY, JOB_STATUSES};
+use crate::storage::rekey::RekeyRun;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use crate::core::datetime::unix_now;
+
+// 任务列表默认返回条数
+const DEFAULT_JOB_LIMIT: i64 = 50;
+
+// 任务列表查询参数
+#[derive(Deserialize)]
+pub struct JobsQuery {
+    pub status: Option<String>, // pending、running、done 或 dead，不传则返回全部
+    pub limit: Option<i64>,
+}
+
+// 任务列表响应体
+#[derive(Serialize)]
+pub struct JobsResponse {
+    pub success: bool,
+    pub message: String,
+    pub jobs: Vec<Job>,
+}
+
+// 加入队列的任务响应体
+#[derive(Serialize)]
+pub struct EnqueuedJobResponse {
+    pub success: bool,
+    pub message: String,
+    pub job_id: String,
+}
+
+// 查看任务队列（例如 ?status=dead 查看死信）
+pub async fn list_jobs_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Query(query): Query<JobsQuery>,
+) -> Result<Json<JobsResponse>, AppError> {
+    if let Some(status) = &query.status
+        && !JOB_STATUSES.contains(&status.as_str())
+    {
+        return Err(AppError::InvalidInput(format!("未知的任务状态 {}，可选: {}", status, JOB_STATUSES.join(", "))));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_JOB_LIMIT).clamp(1, 500);
+    let jobs = state.db_pool.list_jobs(query.status.as_deref(), limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(JobsResponse {
+        success: true,
+        message: "获取任务列表成功".into(),
+        jobs,
+    }))
+}
+
+// 立即重试一个死信任务
+pub async fn retry_job_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(job_id): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let now = unix_now();
+    let retried = state.db_pool.retry_job(&job_id, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !retried {
+        return Err(AppError::NotFound("任务不存在或不处于死信状态".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "任务已重新加入队列".into(),
+    }))
+}
+
+// 在后台导出用户数据到 jobs.export_dir
+pub async fn export_user_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(user_id): UrlPath<String>,
+) -> Result<Json<EnqueuedJobResponse>, AppError> {
+    if !state.db_pool.user_exists_by_id(&user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("用户不存在".into()));
+    }
+    let job_id = crate::tasks::jobs::enqueue(&state, JOB_EXPORT_USER, json!({ "user_id": user_id }))
+        .ok_or_else(|| AppError::Internal("加入导出任务失败".into()))?;
+
+    Ok(Json(EnqueuedJobResponse {
+        success: true,
+        message: "导出任务已加入队列".into(),
+        job_id,
+    }))
+}
+
+// 重新加密进度响应体
+#[derive(Serialize)]
+pub struct RekeyProgressResponse {
+    pub success: bool,
+    pub message: String,
+    pub run: Option<RekeyRun>, // 从未执行过时为 null
+}
+
+// 在后台把明文、旧纪元密钥加密的消息和明文邮箱改用当前密钥加密
+pub async fn start_rekey_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<EnqueuedJobResponse>, AppError> {
+    if !state.db_pool.encryption_enabled() {
+        return Err(AppError::InvalidInput("未开启消息或邮箱加密，无需重新加密".into()));
+    }
+    if state.db_pool.has_unfinished_job(JOB_REKEY).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::Conflict("已有重新加密任务在执行".into()));
+    }
+    let job_id = crate::tasks::jobs::enqueue(&state, JOB_REKEY, json!({}))
+        .ok_or_else(|| AppError::Internal("加入重新加密任务失败".into()))?;
+
+    Ok(Json(EnqueuedJobResponse {
+        success: true,
+        message: "重新加密任务已加入队列".into(),
+        job_id,
+    }))
+}
+
+// 查看最近一次重新加密任务的进度
+pub async fn rekey_progress_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<RekeyProgressResponse>, AppError> {
+    let run = state.db_pool.latest_rekey_run()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(RekeyProgressResponse {
+        success: true,
+        message: "获取重新加密进度成功".into(),
+        run,
+    }))
+}
+
+/// 注册任务队列相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/jobs", get(list_jobs_handler))
+        .route("/admin/jobs/{job_id}/retry", post(retry_job_handler))
+        .route("/admin/users/{user_id}/export", post(export_user_handler))
+        .route("/admin/rekey", get(rekey_progress_handler).post(start_rekey_handler))
+}
@@ -0,0 +1,183 @@
+//! 维护模式：开启后拒绝新的登录和消息，并通知本实例上的 WebSocket 连接
+//!
+//! 配置中豁免的用户（管理员、运维账号）照常登录、发消息并保持连接；
+//! 状态只保存在本实例的内存中，多实例部署时需要分别开启，重启后自动关闭
+
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::{State, ws::CloseFrame},
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use crate::core::datetime::unix_now;
+
+/// 维护期间断开非豁免连接时的关闭码
+pub const CLOSE_MAINTENANCE: u16 = 4004;
+
+// 管理员没有给出提示语时使用
+const DEFAULT_NOTICE: &str = "服务器正在维护，请稍后再试";
+
+/// 当前的维护状态
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceStatus {
+    pub notice: String,
+    pub started_at: i64,
+    pub expected_end_at: Option<i64>,  // 没有给出预计时长时为空
+}
+
+/// 可在运行时开启和关闭的维护模式，克隆开销很小
+#[derive(Clone, Default)]
+pub struct Maintenance {
+    current: Arc<RwLock<Option<MaintenanceStatus>>>,
+}
+
+impl Maintenance {
+    /// 维护中时返回维护状态
+    pub fn status(&self) -> Option<MaintenanceStatus> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+fn is_exempt(state: &AppState, user_id: &str) -> bool {
+    state.settings.maintenance.exempt_user_ids.iter().any(|id| id == user_id)
+}
+
+/// 维护期间拒绝非豁免用户的登录和发消息
+pub(crate) fn check(state: &AppState, user_id: &str) -> Result<(), AppError> {
+    match state.maintenance.status() {
+        Some(status) if !is_exempt(state, user_id) => Err(AppError::Maintenance(status.notice)),
+        _ => Ok(()),
+    }
+}
+
+/// 维护期间非豁免用户 identify 时：返回要推送的 maintenance 事件，调用方随后以 close_frame() 关闭连接
+pub(crate) fn identify_blocked(state: &AppState, user_id: &str) -> Option<String> {
+    state.maintenance.status()
+        .filter(|_| !is_exempt(state, user_id))
+        .map(|status| event(Some(&status)))
+}
+
+/// 断开非豁免连接的关闭帧
+pub(crate) fn close_frame() -> CloseFrame {
+    CloseFrame { code: CLOSE_MAINTENANCE, reason: "maintenance".into() }
+}
+
+// type 为 maintenance 的事件；duration_secs 为预计的维护总时长，没有预计时长时和 expected_end_at 一样为 null
+fn event(status: Option<&MaintenanceStatus>) -> String {
+    match status {
+        Some(status) => json!({
+            "type": "maintenance",
+            "active": true,
+            "code": "server.maintenance",
+            "notice": status.notice,
+            "started_at": status.started_at,
+            "expected_end_at": status.expected_end_at,
+            "duration_secs": status.expected_end_at.map(|end| end - status.started_at),
+        }),
+        None => json!({ "type": "maintenance", "active": false }),
+    }.to_string()
+}
+
+// 开启维护的请求体
+#[derive(Deserialize)]
+pub struct MaintenanceRequest {
+    pub notice: Option<String>,       // 为空时使用默认提示语
+    pub duration_secs: Option<i64>,   // 预计时长，只用于告知客户端，到期后不会自动关闭
+}
+
+// 维护状态响应体
+#[derive(Serialize)]
+pub struct MaintenanceResponse {
+    pub success: bool,
+    pub message: String,
+    pub maintenance: Option<MaintenanceStatus>,
+    pub disconnected: usize,   // 本次断开的 WebSocket 连接数
+}
+
+// 查看维护状态
+pub async fn get_maintenance_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<MaintenanceResponse>, AppError> {
+    Ok(Json(MaintenanceResponse {
+        success: true,
+        message: "获取维护状态成功".into(),
+        maintenance: state.maintenance.status(),
+        disconnected: 0,
+    }))
+}
+
+// 开启维护，已在维护中时更新提示语和预计时长（开始时间不变）；
+// 向本实例的所有连接推送 maintenance 事件，并断开非豁免用户的连接
+pub async fn enable_maintenance_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<MaintenanceRequest>,
+) -> Result<Json<MaintenanceResponse>, AppError> {
+    if req.duration_secs.is_some_and(|secs| secs < 0) {
+        return Err(AppError::InvalidInput("预计时长不能为负数".into()));
+    }
+    let notice = req.notice
+        .map(|notice| notice.trim().to_string())
+        .filter(|notice| !notice.is_empty())
+        .unwrap_or_else(|| DEFAULT_NOTICE.into());
+
+    let status = {
+        let mut current = state.maintenance.current.write().unwrap();
+        let started_at = current.as_ref().map_or_else(unix_now, |status| status.started_at);
+        let status = MaintenanceStatus {
+            notice,
+            started_at,
+            expected_end_at: req.duration_secs.map(|secs| unix_now() + secs),
+        };
+        *current = Some(status.clone());
+        status
+    };
+    let disconnected = state.connections.notify_all(
+        &event(Some(&status)),
+        |user_id| user_id.is_some_and(|user_id| is_exempt(&state, user_id)),
+        close_frame,
+    );
+
+    Ok(Json(MaintenanceResponse {
+        success: true,
+        message: "维护模式已开启".into(),
+        maintenance: Some(status),
+        disconnected,
+    }))
+}
+
+// 关闭维护，通知仍然在线的连接
+pub async fn disable_maintenance_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<AdminResponse>, AppError> {
+    if state.maintenance.current.write().unwrap().take().is_some() {
+        state.connections.notify_all(&event(None), |_| true, close_frame);
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "维护模式已关闭".into(),
+    }))
+}
+
+/// 注册维护模式路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/admin/maintenance",
+            get(get_maintenance_handler)
+                .put(enable_maintenance_handler)
+                .delete(disable_maintenance_handler),
+        )
+}
@@ -8,21 +8,32 @@ use serde::{
     Deserialize, 
     Serialize
 };
+use serde_json::json;
 use crate::storage::{
-    Message
+    deletion::Tombstone,
+    system_messages::{SystemEvent, SYSTEM_MESSAGE_TYPE},
+    webhooks::EVENT_MESSAGE_SENT,
+    Message,
+    NewMessage
 };
 use crate::error::AppError;
+use crate::core::markdown::{self, FormattedText, UnsafeLink};
 
 // 共享应用状态
 use super::AppState;
+use super::policy::{self, authorize, Check};
+use super::workspace::{require_member, WorkspaceScope};
+use crate::core::datetime::unix_now;
 
-// 消息请求体
+// 消息请求体；发送者是会话用户，sender_id 只为兼容旧客户端保留，填写时必须是会话用户本人
 #[derive(Deserialize)]
 pub struct SendMessageRequest {
-    pub sender_id: String,
+    pub sender_id: Option<String>,
     pub receiver_id: String,
     pub content: String,
     pub message_type: String, // "private"或"group"
+    pub client_message_id: Option<String>, // 客户端生成的临时ID，原样出现在响应和 message_ack 事件中
+    pub format: Option<String>, // "plain"（默认）、"markdown" 或 "sticker"
 }
 
 // 消息响应体
@@ -31,9 +42,41 @@ pub struct SendMessageResponse {
     pub success: bool,
     pub message: String,
     pub message_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_message_id: Option<String>,
+    pub created_at: Option<i64>,
+    pub sent_at: Option<i64>,
+    pub seq: Option<i64>,
+}
+
+// 单次批量发送的最大消息数
+const MAX_BATCH_SIZE: usize = 500;
+
+// 批量发送消息请求体
+#[derive(Deserialize)]
+pub struct SendMessagesBatchRequest {
+    pub messages: Vec<BatchMessage>,
 }
 
-// 获取未读消息请求
+// 批量发送中的一条消息
+#[derive(Deserialize)]
+pub struct BatchMessage {
+    pub sender_id: String,
+    pub receiver_id: String,
+    pub content: String,
+    pub message_type: String,
+    pub format: Option<String>, // 同 SendMessageRequest.format
+}
+
+// 批量发送消息响应体（ID 顺序与请求顺序一致）
+#[derive(Serialize)]
+pub struct SendMessagesBatchResponse {
+    pub success: bool,
+    pub message: String,
+    pub message_ids: Vec<String>,
+}
+
+// 获取未读消息请求（user_id 必须是会话用户本人，下同）
 #[derive(Deserialize)]
 pub struct GetUnreadMessagesRequest {
     pub user_id: String,
@@ -87,41 +130,370 @@ pub struct SyncMessagesResponse {
     pub success: bool,
     pub message: String,
     pub messages: Vec<Message>,
+    pub tombstones: Vec<Tombstone>, // last_sync_time 之后被删除的消息
     pub last_sync_time: i64,
 }
 
+// 会话历史请求（before 和 cursor 都为空时从最新消息开始）；peer_id 为私聊对象或用户所在的群
+#[derive(Deserialize)]
+pub struct MessageHistoryRequest {
+    pub user_id: String,
+    pub peer_id: String,
+    pub before: Option<i64>,
+    pub cursor: Option<String>, // 上一页返回的 next_cursor，优先于 before
+    pub limit: i64,
+}
+
+// 会话历史响应
+#[derive(Serialize)]
+pub struct MessageHistoryResponse {
+    pub success: bool,
+    pub message: String,
+    pub messages: Vec<Message>,
+    pub next_cursor: Option<String>, // 为空表示没有更早的消息
+}
+
+// 删除/恢复消息请求（user_id 必须是会话用户本人，且是消息发送者）
+#[derive(Deserialize)]
+pub struct MessageDeletionRequest {
+    pub message_id: String,
+    pub user_id: String,
+}
+
+// 删除/恢复消息响应
+#[derive(Serialize)]
+pub struct MessageDeletionResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+// 通知会话双方消息被删除或恢复（若其已通过 WebSocket 标识并连接）
+fn notify_conversation(state: &AppState, message: &Message, notify: String) {
+    for user_id in [&message.sender_id, &message.receiver_id] {
+        state.send_to_user(user_id, notify.clone());
+    }
+}
+
+// 消息发送后的外部通知：订阅了 message.sent 的 webhook（按接收方会话过滤），以及离线接收者的推送
+pub(crate) fn after_message_sent(state: &AppState, message: &Message) {
+    super::webhook::emit(state, EVENT_MESSAGE_SENT, Some(&message.receiver_id), json!(message));
+    super::push::notify_offline(state, message);
+    super::federation::relay_if_remote(state, message);
+}
+
+// 消息格式：纯文本、markdown 子集和贴纸
+const FORMAT_PLAIN: &str = "plain";
+const FORMAT_MARKDOWN: &str = "markdown";
+const FORMAT_STICKER: &str = "sticker";
+
+// 客户端临时ID的最大长度
+const MAX_CLIENT_MESSAGE_ID_LEN: usize = 64;
+
+// 在工作区内保存消息并触发外部通知（REST、WebSocket 和 gRPC 共用）
+//
+// 发送者必须属于该工作区，私聊的接收者也必须属于该工作区
+pub(crate) fn send_message(
+    state: &AppState,
+    workspace_id: &str,
+    sender_id: &str,
+    receiver_id: &str,
+    content: &str,
+    message_type: &str,
+) -> Result<Message, AppError> {
+    send_message_once(state, workspace_id, sender_id, receiver_id, content, None, message_type, None)
+        .map(|(message, _)| message)
+}
+
+// 按 format 处理消息内容：plain（默认）原样保存；markdown 解析为去掉标记的纯文本和格式区间；
+// sticker 的内容是工作区自定义表情的名称
+fn format_content(state: &AppState, workspace_id: &str, content: &str, format: Option<&str>) -> Result<FormattedText, AppError> {
+    match format.unwrap_or(FORMAT_PLAIN) {
+        FORMAT_PLAIN => Ok(FormattedText { text: content.to_string(), entities: Vec::new() }),
+        FORMAT_MARKDOWN => markdown::parse(content)
+            .map_err(|UnsafeLink(url)| AppError::InvalidInput(format!("链接只支持 http、https 和 mailto: {}", url))),
+        FORMAT_STICKER => super::emoji::sticker_content(state, workspace_id, content),
+        other => Err(AppError::InvalidInput(format!("不支持的消息格式: {}", other))),
+    }
+}
+
+// 同 send_message，附带客户端临时ID时按 (发送者, 临时ID) 去重：
+// 客户端没收到确认而重发时返回第一次保存的消息，不再通知接收方，第二项为 false。
+// format 为 markdown 时服务器解析格式，保存纯文本和格式区间；为 sticker 时发送自定义表情贴纸
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn send_message_once(
+    state: &AppState,
+    workspace_id: &str,
+    sender_id: &str,
+    receiver_id: &str,
+    content: &str,
+    format: Option<&str>,
+    message_type: &str,
+    client_message_id: Option<&str>,
+) -> Result<(Message, bool), AppError> {
+    if client_message_id.is_some_and(|id| id.is_empty() || id.len() > MAX_CLIENT_MESSAGE_ID_LEN) {
+        return Err(AppError::InvalidInput(format!("客户端消息ID应为 1 到 {} 个字符", MAX_CLIENT_MESSAGE_ID_LEN)));
+    }
+    let (receiver_id, formatted) = check_outgoing(state, workspace_id, sender_id, receiver_id, content, format, message_type)?;
+    let (message, created) = state.db_pool
+        .send_message_once(workspace_id, sender_id, &receiver_id, &formatted.text, &formatted.entities, message_type, client_message_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if created {
+        deliver(state, &message);
+    }
+    Ok((message, created))
+}
+
+// 保存一条消息前的检查（单条发送和批量发送共用）：消息类型、端到端加密开关、维护模式、发送额度、
+// 发送者的工作区成员身份和邮箱验证、消息长度，私聊接收者的工作区和隐私设置，群聊的成员身份和禁言；
+// 通过后返回实际的接收者（远端地址换成影子账号ID）和按 format 处理后的内容
+fn check_outgoing(
+    state: &AppState,
+    workspace_id: &str,
+    sender_id: &str,
+    receiver_id: &str,
+    content: &str,
+    format: Option<&str>,
+    message_type: &str,
+) -> Result<(String, FormattedText), AppError> {
+    reject_system_type(message_type)?;
+    super::runtime::check_e2ee(state, content)?;
+    super::maintenance::check(state, sender_id)?;
+    super::rate_limit::check_message(state, sender_id)?;
+    require_member(state, workspace_id, sender_id)?;
+    super::account::require_verified_email(state, sender_id)?;
+    let remote = if message_type != "group" { super::federation::resolve_recipient(state, receiver_id)? } else { None };
+    let receiver_id = remote.unwrap_or_else(|| receiver_id.to_string());
+    super::quota::check_message_length(state, sender_id, &receiver_id, message_type, content)?;
+    if message_type != "group" {
+        require_member(state, workspace_id, &receiver_id)?;
+        super::privacy::require_dm_allowed(state, sender_id, &receiver_id)?;
+    } else if state.db_pool.is_group_deleted(&receiver_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("群聊不存在".into()));
+    } else {
+        policy::require(state, sender_id, &[Check::GroupMember(&receiver_id)])?;
+        super::group::require_not_muted(state, &receiver_id, sender_id)?;
+    }
+    let formatted = format_content(state, workspace_id, content, format)?;
+    Ok((receiver_id, formatted))
+}
+
+// 新保存的消息触发外部通知并推送给私聊的接收方；保存时已写入发件箱，接收方确认送达前由后台任务重发
+pub(crate) fn deliver(state: &AppState, message: &Message) {
+    after_message_sent(state, message);
+    if message.message_type == "private" && message.sender_id != message.receiver_id {
+        state.push_to_receiver(message);
+    }
+}
+
+// 服务器以 sender_id 的名义保存的私聊消息（如未接来电），内容和格式区间由服务器生成，不经过发送者的检查
+pub(crate) fn send_server_message(
+    state: &AppState,
+    workspace_id: &str,
+    sender_id: &str,
+    receiver_id: &str,
+    formatted: FormattedText,
+) -> Result<Message, AppError> {
+    let (message, _) = state.db_pool
+        .send_message_once(workspace_id, sender_id, receiver_id, &formatted.text, &formatted.entities, "private", None)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    deliver(state, &message);
+    Ok(message)
+}
+
+// 系统消息只能由服务器生成，客户端经任何接口发送时拒绝
+fn reject_system_type(message_type: &str) -> Result<(), AppError> {
+    if message_type == SYSTEM_MESSAGE_TYPE {
+        return Err(AppError::InvalidInput("系统消息只能由服务器生成".into()));
+    }
+    Ok(())
+}
+
+// 在群会话中保存系统消息并实时推送给所有群成员（type 为 system_message，event 为结构化内容）
+pub(crate) fn post_system_message(state: &AppState, workspace_id: &str, group_id: &str, event: SystemEvent) -> Result<Message, AppError> {
+    let message = state.db_pool.post_system_message(workspace_id, group_id, &event)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let members = state.db_pool.get_group_members(group_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let payload = system_message_event(&message, &event);
+    for member in members {
+        state.send_to_user(&member.user_id, payload.clone());
+    }
+    Ok(message)
+}
+
+// 给单个用户发一条系统消息（如新设备登录提醒），保存后推送给该用户在线的连接
+pub(crate) fn post_user_system_message(state: &AppState, user_id: &str, event: SystemEvent) -> Result<Message, AppError> {
+    let message = state.db_pool.post_user_system_message(user_id, &event)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    state.send_to_user(user_id, system_message_event(&message, &event));
+    Ok(message)
+}
+
+fn system_message_event(message: &Message, event: &SystemEvent) -> String {
+    with_server_fields(json!({
+        "type": "system_message",
+        "sender_id": message.sender_id,
+        "receiver_id": message.receiver_id,
+        "message_type": message.message_type,
+        "event": event,
+    }), message).to_string()
+}
+
+// 新消息的实时推送事件，私聊推送给接收方和群聊广播给成员时共用
+pub(crate) fn message_event(message: &Message) -> String {
+    with_server_fields(json!({
+        "type": "message",
+        "sender_id": message.sender_id,
+        "receiver_id": message.receiver_id,
+        "content": message.content,
+        "message_type": message.message_type,
+    }), message).to_string()
+}
+
+// 给实时推送的事件补上消息ID、会话内序号和服务器记录的三个时间，客户端按服务器时间排序，不依赖本机时钟；
+// 按 markdown 发送的消息再带上服务器解析出的格式区间
+pub(crate) fn with_server_fields(mut event: serde_json::Value, message: &Message) -> serde_json::Value {
+    if let Some(fields) = event.as_object_mut() {
+        fields.insert("message_id".into(), json!(message.id));
+        fields.insert("created_at".into(), json!(message.created_at));
+        fields.insert("sent_at".into(), json!(message.sent_at));
+        fields.insert("delivered_at".into(), json!(message.delivered_at));
+        fields.insert("read_at".into(), json!(message.read_at));
+        fields.insert("seq".into(), json!(message.seq));
+        if !message.entities.is_empty() {
+            fields.insert("entities".into(), json!(message.entities));
+        }
+    }
+    event
+}
+
+// 发送确认事件：客户端据此把临时ID对应的消息换成服务器分配的ID和时间
+pub(crate) fn ack_event(message: &Message, client_message_id: Option<&str>) -> String {
+    with_server_fields(json!({
+        "type": "message_ack",
+        "client_message_id": client_message_id,
+    }), message).to_string()
+}
+
+// 把新消息回显到发送者的其他设备（type 为 own_message），多设备之间的会话不用轮询即可保持一致；
+// except_client 为发出消息的 WebSocket 连接，HTTP 和 gRPC 发送时没有可排除的连接，发送设备按消息ID去重。
+// 发给自己的私聊已经推送给自己的所有连接，不再回显
+pub(crate) fn echo_to_own_devices(state: &AppState, message: &Message, client_message_id: Option<&str>, except_client: Option<&str>) {
+    if message.message_type != "group" && message.sender_id == message.receiver_id {
+        return;
+    }
+    let event = with_server_fields(json!({
+        "type": "own_message",
+        "client_message_id": client_message_id,
+        "sender_id": message.sender_id,
+        "receiver_id": message.receiver_id,
+        "content": message.content,
+        "message_type": message.message_type,
+    }), message).to_string();
+    match except_client {
+        Some(client_id) => state.send_to_user_except(&message.sender_id, client_id, event),
+        None => {
+            state.send_to_user(&message.sender_id, event);
+        }
+    }
+}
+
 // 发送消息处理器
 pub async fn send_message_handler(
     State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
     Json(req): Json<SendMessageRequest>,
 ) -> Result<Json<SendMessageResponse>, AppError> {
-
-    
-    let message = state.db_pool.send_message(
-        &req.sender_id,
-        &req.receiver_id,
-        &req.content,
-        &req.message_type,
-    )
-    .map_err(|e| AppError::Database(e.to_string()))?;
+    let sender_id = authorize(&state, &headers, &[])?;
+    if let Some(claimed) = &req.sender_id {
+        policy::require(&state, &sender_id, &[Check::Is(claimed)])?;
+    }
+    let client_message_id = req.client_message_id.as_deref();
+    let (message, created) = send_message_once(
+        &state, workspace.id(), &sender_id, &req.receiver_id, &req.content, req.format.as_deref(), &req.message_type, client_message_id,
+    )?;
+    // 发送者的其他连接（如同时打开的 WebSocket）也能收到确认
+    if client_message_id.is_some() {
+        state.send_to_user(&sender_id, ack_event(&message, client_message_id));
+    }
+    if created {
+        echo_to_own_devices(&state, &message, client_message_id, None);
+    }
 
     Ok(Json(SendMessageResponse {
         success: true,
         message: "消息发送成功".into(),
         message_id: Some(message.id),
+        client_message_id: req.client_message_id,
+        created_at: Some(message.created_at),
+        sent_at: Some(message.sent_at),
+        seq: message.seq,
+    }))
+}
+
+// 批量发送消息处理器（供机器人和导入工具使用）；发送者必须是会话用户本人，带管理令牌时可以是任意用户
+pub async fn send_messages_batch_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
+    Json(req): Json<SendMessagesBatchRequest>,
+) -> Result<Json<SendMessagesBatchResponse>, AppError> {
+    if req.messages.is_empty() {
+        return Err(AppError::InvalidInput("消息列表不能为空".into()));
+    }
+    if req.messages.len() > MAX_BATCH_SIZE {
+        return Err(AppError::InvalidInput(format!("单次最多发送 {} 条消息", MAX_BATCH_SIZE)));
+    }
+    let caller = if policy::is_server_admin(&state, &headers) {
+        None
+    } else {
+        Some(authorize(&state, &headers, &[])?)
+    };
+
+    // 每条消息都经过与单条发送相同的检查，任一条不通过时整批不保存
+    let mut checked = Vec::with_capacity(req.messages.len());
+    for message in req.messages {
+        if let Some(caller) = &caller {
+            policy::require(&state, caller, &[Check::Is(&message.sender_id)])?;
+        }
+        let (receiver_id, formatted) = check_outgoing(
+            &state, workspace.id(), &message.sender_id, &message.receiver_id, &message.content, message.format.as_deref(), &message.message_type,
+        )?;
+        checked.push(NewMessage {
+            sender_id: message.sender_id,
+            receiver_id,
+            content: formatted.text,
+            entities: formatted.entities,
+            message_type: message.message_type,
+        });
+    }
+
+    let messages = state.db_pool.send_messages_batch(workspace.id(), &checked)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for message in &messages {
+        deliver(&state, message);
+    }
+
+    Ok(Json(SendMessagesBatchResponse {
+        success: true,
+        message: format!("成功发送 {} 条消息", messages.len()),
+        message_ids: messages.into_iter().map(|m| m.id).collect(),
     }))
 }
 
 // 获取未读消息处理器
 pub async fn get_unread_messages_handler(
     State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
     Json(req): Json<GetUnreadMessagesRequest>,
 ) -> Result<Json<GetUnreadMessagesResponse>, AppError> {
-    let messages = state.db_pool.get_unread_messages(&req.user_id)
+    authorize(&state, &headers, &[Check::Is(&req.user_id), Check::WorkspaceMember(workspace.id())])?;
+    let messages = state.db_pool.get_unread_messages(workspace.id(), &req.user_id)
         .map_err(|e| AppError::Database(e.to_string()))?;
     
 
-
     Ok(Json(GetUnreadMessagesResponse {
         success: true,
         message: "获取未读消息成功".into(),
@@ -129,12 +501,35 @@ pub async fn get_unread_messages_handler(
     }))
 }
 
-// 标记消息为已读处理器
+// 调用者须是每条消息的接收者：私聊的接收方，或群消息所在群的成员；已删除或不存在的消息跳过
+pub(crate) fn require_recipient(state: &AppState, user_id: &str, message_ids: &[String]) -> Result<(), AppError> {
+    for message_id in message_ids {
+        let message = state.db_pool.active_message(message_id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        let Some(message) = message else { continue };
+        let received = if message.message_type == "group" {
+            policy::require(state, user_id, &[Check::GroupMember(&message.receiver_id)]).is_ok()
+        } else {
+            message.receiver_id == user_id
+        };
+        if !received {
+            return Err(AppError::Forbidden("只能标记发给自己的消息".into()));
+        }
+    }
+    Ok(())
+}
+
+// 标记消息为已读处理器：调用者须是消息的接收者，其中的群消息同时记为该成员的回执
 pub async fn mark_messages_as_read_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<MarkMessagesAsReadRequest>,
 ) -> Result<Json<MarkMessagesAsReadResponse>, AppError> {
-    state.db_pool.mark_messages_as_read(&req.message_ids)
+    let user_id = authorize(&state, &headers, &[])?;
+    require_recipient(&state, &user_id, &req.message_ids)?;
+    let now = unix_now();
+    state.db_pool.mark_messages_as_read(&req.message_ids, now)
+        .and_then(|()| state.db_pool.record_group_receipts(&user_id, &req.message_ids, true, now))
         .map_err(|e| AppError::Database(e.to_string()))?;
 
     Ok(Json(MarkMessagesAsReadResponse {
@@ -143,12 +538,17 @@ pub async fn mark_messages_as_read_handler(
     }))
 }
 
-// 标记消息为已送达处理器
+// 标记消息为已送达处理器，检查和回执同上
 pub async fn mark_messages_as_delivered_handler(
     State(state): State<AppState>,
+    headers: http::HeaderMap,
     Json(req): Json<MarkMessagesAsDeliveredRequest>,
 ) -> Result<Json<MarkMessagesAsDeliveredResponse>, AppError> {
-    state.db_pool.mark_messages_as_delivered(&req.message_ids)
+    let user_id = authorize(&state, &headers, &[])?;
+    require_recipient(&state, &user_id, &req.message_ids)?;
+    let now = unix_now();
+    state.db_pool.mark_messages_as_delivered(&req.message_ids, now)
+        .and_then(|()| state.db_pool.record_group_receipts(&user_id, &req.message_ids, false, now))
         .map_err(|e| AppError::Database(e.to_string()))?;
 
     Ok(Json(MarkMessagesAsDeliveredResponse {
@@ -160,15 +560,22 @@ pub async fn mark_messages_as_delivered_handler(
 // 同步消息处理器
 pub async fn sync_messages_handler(
     State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
     Json(req): Json<SyncMessagesRequest>,
 ) -> Result<Json<SyncMessagesResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id), Check::WorkspaceMember(workspace.id())])?;
     let messages = state.db_pool.sync_messages(
+        workspace.id(),
         &req.user_id,
         req.last_sync_time,
         req.limit
     )
     .map_err(|e| AppError::Database(e.to_string()))?;
 
+    let tombstones = state.db_pool.get_tombstones(&req.user_id, req.last_sync_time)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
     let last_sync_time = if messages.is_empty() {
         req.last_sync_time
     } else {
@@ -179,16 +586,110 @@ pub async fn sync_messages_handler(
         success: true,
         message: "消息同步成功".into(),
         messages,
+        tombstones,
         last_sync_time,
     }))
 }
 
+// 会话历史处理器
+pub async fn message_history_handler(
+    State(state): State<AppState>,
+    workspace: WorkspaceScope,
+    headers: http::HeaderMap,
+    Json(req): Json<MessageHistoryRequest>,
+) -> Result<Json<MessageHistoryResponse>, AppError> {
+    let checks = [Check::Is(&req.user_id), Check::WorkspaceMember(workspace.id()), Check::Participant(&req.peer_id)];
+    authorize(&state, &headers, &checks)?;
+    let (before, before_id) = match &req.cursor {
+        Some(cursor) => super::conversation::decode_cursor(cursor)?,
+        None => (req.before.unwrap_or(i64::MAX), String::new()),
+    };
+    let is_group = state.db_pool.is_group_member(&req.peer_id, &req.user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let messages = if is_group {
+        state.db_pool.get_group_history(workspace.id(), &req.peer_id, before, &before_id, req.limit)
+    } else {
+        state.db_pool.get_conversation_history(workspace.id(), &req.user_id, &req.peer_id, before, &before_id, req.limit)
+    }
+    .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let next_cursor = (messages.len() as i64 == req.limit)
+        .then(|| messages.last().map(|m| super::conversation::encode_cursor(m.created_at, &m.id)))
+        .flatten();
+    Ok(Json(MessageHistoryResponse {
+        success: true,
+        message: "获取历史消息成功".into(),
+        messages,
+        next_cursor,
+    }))
+}
+
+// 删除消息处理器（软删除，撤销窗口内可恢复）
+pub async fn delete_message_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<MessageDeletionRequest>,
+) -> Result<Json<MessageDeletionResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
+    let now = unix_now();
+    let message = state.db_pool.delete_message(&req.message_id, &req.user_id, now)
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("消息不存在或无权删除".into()),
+            _ => AppError::Database(e.to_string())
+        })?;
+
+    let notify = json!({
+        "type": "message_deleted",
+        "message_id": message.id,
+        "deleted_at": now
+    })
+    .to_string();
+    notify_conversation(&state, &message, notify);
+
+    Ok(Json(MessageDeletionResponse {
+        success: true,
+        message: "消息已删除".into(),
+    }))
+}
+
+// 恢复消息处理器
+pub async fn restore_message_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<MessageDeletionRequest>,
+) -> Result<Json<MessageDeletionResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
+    let now = unix_now();
+    let grace_secs = state.settings.retention.restore_grace_secs as i64;
+    let message = state.db_pool.restore_message(&req.message_id, &req.user_id, now, grace_secs)
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("消息不存在或已超过可恢复时间".into()),
+            _ => AppError::Database(e.to_string())
+        })?;
+
+    let notify = json!({
+        "type": "message_restored",
+        "message": message
+    })
+    .to_string();
+    notify_conversation(&state, &message, notify);
+
+    Ok(Json(MessageDeletionResponse {
+        success: true,
+        message: "消息已恢复".into(),
+    }))
+}
+
 /// 注册消息相关路由
 pub fn register_routes() -> Router<AppState> {
     Router::new()
         .route("/send-message", post(send_message_handler))
+        .route("/messages/batch", post(send_messages_batch_handler))
         .route("/messages/unread", post(get_unread_messages_handler))
         .route("/messages/read", post(mark_messages_as_read_handler))
         .route("/messages/delivered", post(mark_messages_as_delivered_handler))
         .route("/messages/sync", post(sync_messages_handler))
+        .route("/messages/history", post(message_history_handler))
+        .route("/messages/delete", post(delete_message_handler))
+        .route("/messages/restore", post(restore_message_handler))
 }
\ No newline at end of file
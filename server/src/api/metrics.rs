@@ -0,0 +1,51 @@
+//! 监控指标：GET /metrics 以 OpenMetrics 文本格式导出加解密和密码校验的耗时直方图（见 crypto::metrics）、
+//! WebSocket 发送队列的长度与丢弃数（见 outbound）、处理器 panic 次数（见 recovery）
+//! 以及慢请求和慢查询的次数（见 slow_requests、storage::slow_query），供 Prometheus 等抓取
+//!
+//! 指标按进程统计，多实例部署时分别抓取
+
+use axum::{
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router
+};
+use crate::crypto::metrics;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+
+/// OpenMetrics 文本格式的 Content-Type
+pub const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+// 导出指标；配置了抓取令牌时校验 Authorization 请求头
+pub async fn metrics_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Response, AppError> {
+    let settings = &state.settings.metrics;
+    if !settings.enabled {
+        return Err(AppError::NotFound("本服务器未开启监控指标".into()));
+    }
+    if !settings.token.is_empty() && !super::admin::token_matches(&settings.token, super::user::bearer_token(&headers)) {
+        return Err(AppError::InvalidCredentials("监控指标令牌无效".into()));
+    }
+
+    let mut body = String::new();
+    metrics::render(&mut body);
+    super::outbound::render(&mut body);
+    super::recovery::render(&mut body);
+    super::slow_requests::render(&mut body);
+    crate::storage::slow_query::render(&mut body);
+    crate::tasks::attachment_gc::render(&mut body);
+    body.push_str("# EOF\n");
+    Ok(([(header::CONTENT_TYPE, OPENMETRICS_CONTENT_TYPE)], body).into_response())
+}
+
+/// 注册监控指标路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+}
@@ -1,28 +1,117 @@
-use axum::{Router};
+use axum::{middleware, Router};
 
 // 导入子模块
 mod user;
+mod challenge;
+mod account;
+mod account_settings;
+mod oauth;
+mod devices;
+mod username;
+mod profile;
+mod privacy;
 mod friend;
 mod message;
+mod attachment;
+mod emoji;
+mod quota;
+mod conversation;
+mod conversation_export;
+mod group;
+mod policy;
+mod translate;
+mod receipts;
+mod relay;
+mod metrics;
+// 慢请求日志，测试中也用来检查查询参数的脱敏
+pub mod slow_requests;
 mod ws;
+// 每个连接的发送队列，测试中也用来模拟连接
+pub mod outbound;
+// 处理器 panic 时返回 500，测试中用来包装会 panic 的路由
+pub mod recovery;
+mod calls;
+mod connections;
+mod maintenance;
+mod runtime;
+mod server_info;
+mod admin;
+mod webhook;
+mod bot;
+mod rate_limit;
+mod push;
+mod federation;
+mod jobs;
+mod graphql;
+mod workspace;
+mod ip_filter;
+mod i18n;
+// 与 REST 并行的 gRPC 接口
+pub mod grpc;
 
 // 重新导出AppState，以便其他模块可以通过super::AppState导入
 pub use ws::AppState;
 
 /// 注册所有API路由
-pub fn register_routes(db_pool: crate::storage::DbPool) -> Router {
+pub fn register_routes(db_pool: crate::storage::DbPool, settings: crate::config::settings::Settings) -> Router {
     // 创建共享应用状态
-    let app_state = ws::AppState::new(db_pool);
-    
+    router(ws::AppState::new(db_pool, settings))
+}
+
+/// 使用已有的应用状态构建路由（后台任务需要与路由共享同一份在线状态）
+pub fn router(app_state: AppState) -> Router {
     // 主路由器配置
     Router::new()
         // WebSocket路由
         .merge(ws::register_ws_route())
         // 用户相关路由
         .merge(user::register_routes())
+        .merge(profile::register_routes())
+        .merge(server_info::register_routes())
+        .merge(challenge::register_routes())
+        .merge(account::register_routes())
+        .merge(account_settings::register_routes())
+        .merge(oauth::register_routes())
+        .merge(devices::register_routes())
         // 好友相关路由
         .merge(friend::register_routes())
         // 消息相关路由
         .merge(message::register_routes())
+        .merge(attachment::register_routes())
+        .merge(emoji::register_routes())
+        .merge(quota::register_routes())
+        .merge(rate_limit::register_routes())
+        .merge(conversation::register_routes())
+        .merge(conversation_export::register_routes())
+        .merge(group::register_routes())
+        .merge(translate::register_routes())
+        .merge(receipts::register_routes())
+        .merge(calls::register_routes())
+        .merge(relay::register_routes())
+        // 管理相关路由
+        .merge(admin::register_routes())
+        .merge(connections::register_routes())
+        .merge(maintenance::register_routes())
+        .merge(runtime::register_routes())
+        .merge(metrics::register_routes())
+        .merge(webhook::register_routes())
+        .merge(jobs::register_routes())
+        .merge(workspace::register_routes())
+        .merge(ip_filter::register_routes())
+        .merge(graphql::register_routes())
+        // 机器人相关路由
+        .merge(bot::register_routes())
+        // 推送相关路由
+        .merge(push::register_routes())
+        // 服务器间联邦路由
+        .merge(federation::register_routes())
+        // 处理器 panic 时返回带请求ID的 500（在翻译之前，提示语同样会被翻译）
+        .layer(middleware::from_fn(recovery::catch_panic))
+        // 记录处理时间超过阈值的请求（在 panic 兜底之外，日志中带上请求ID）
+        .layer(middleware::from_fn_with_state(app_state.clone(), slow_requests::log_slow_requests))
+        // 按 Accept-Language 翻译提示语
+        .layer(middleware::from_fn(i18n::localize))
+        // 来源 IP 访问控制，先于所有处理器执行
+        .layer(middleware::from_fn_with_state(app_state.clone(), ip_filter::enforce))
         .with_state(app_state)
 }
@@ -0,0 +1,372 @@
+//! 第三方登录（OAuth2 授权码 + PKCE）：GitHub、Google 和通用 OpenID Connect
+//!
+//! 客户端先 `GET /oauth/<提供方>/authorize` 取得授权地址并跳转，提供方回调 `/oauth/<提供方>/callback` 后
+//! 按关联记录登录；没有关联时自动注册（`allow_signup`），发起授权时携带会话令牌则关联到当前用户
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::{delete, get},
+    Router
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use crate::config::settings::OAuthProviderSettings;
+use crate::error::AppError;
+use crate::storage::identities::Identity;
+
+// 共享应用状态
+use super::AppState;
+use super::user::SuccessResponse;
+use crate::core::datetime::unix_now;
+
+pub const KIND_GITHUB: &str = "github";
+pub const KIND_GOOGLE: &str = "google";
+pub const KIND_OIDC: &str = "oidc";
+
+// 授权地址响应体
+#[derive(Serialize)]
+pub struct AuthorizeResponse {
+    pub success: bool,
+    pub message: String,
+    pub authorize_url: String,
+    pub state: String,
+}
+
+// 提供方回调参数
+#[derive(Deserialize)]
+pub struct CallbackQuery {
+    #[serde(default)]
+    pub code: Option<String>,
+    #[serde(default)]
+    pub state: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+// 第三方登录响应体（与 /login 一致，另外说明是否新建或关联了账号）
+#[derive(Serialize)]
+pub struct OAuthLoginResponse {
+    pub success: bool,
+    pub message: String,
+    pub user_id: String,
+    pub username: String,
+    pub token: String,
+    pub created: bool,
+    pub linked: bool,
+}
+
+// 已关联的第三方账号响应体
+#[derive(Serialize)]
+pub struct IdentitiesResponse {
+    pub success: bool,
+    pub message: String,
+    pub identities: Vec<Identity>,
+}
+
+// 补全默认地址后的提供方配置
+struct Provider<'a> {
+    name: &'a str,
+    kind: &'a str,
+    settings: &'a OAuthProviderSettings,
+    authorize_url: String,
+    token_url: String,
+    userinfo_url: String,
+    scopes: Vec<String>,
+}
+
+// 第三方账号信息
+struct ExternalUser {
+    id: String,
+    username: Option<String>,
+    email: Option<String>,
+}
+
+fn or_default(value: &str, default: &str) -> String {
+    if value.is_empty() { default.to_string() } else { value.to_string() }
+}
+
+fn provider<'a>(state: &'a AppState, name: &'a str) -> Result<Provider<'a>, AppError> {
+    let settings = state.settings.oauth.providers.get(name)
+        .ok_or_else(|| AppError::NotFound(format!("未配置第三方登录提供方 {}", name)))?;
+    let (authorize, token, userinfo, scopes): (&str, &str, &str, &[&str]) = match settings.kind.as_str() {
+        KIND_GITHUB => (
+            "https://github.com/login/oauth/authorize",
+            "https://github.com/login/oauth/access_token",
+            "https://api.github.com/user",
+            &["read:user", "user:email"],
+        ),
+        KIND_GOOGLE => (
+            "https://accounts.google.com/o/oauth2/v2/auth",
+            "https://oauth2.googleapis.com/token",
+            "https://openidconnect.googleapis.com/v1/userinfo",
+            &["openid", "email", "profile"],
+        ),
+        KIND_OIDC => ("", "", "", &["openid", "email", "profile"]),
+        other => return Err(AppError::Internal(format!("不支持的第三方登录类型 {}", other))),
+    };
+    let provider = Provider {
+        name,
+        kind: settings.kind.as_str(),
+        settings,
+        authorize_url: or_default(&settings.authorize_url, authorize),
+        token_url: or_default(&settings.token_url, token),
+        userinfo_url: or_default(&settings.userinfo_url, userinfo),
+        scopes: if settings.scopes.is_empty() { scopes.iter().map(|s| s.to_string()).collect() } else { settings.scopes.clone() },
+    };
+    if provider.authorize_url.is_empty() || provider.token_url.is_empty() || provider.userinfo_url.is_empty() {
+        return Err(AppError::Internal(format!("第三方登录提供方 {} 缺少授权、令牌或用户信息地址", name)));
+    }
+    Ok(provider)
+}
+
+// 解析用户信息：GitHub 使用数字 id 和 login，OpenID Connect 使用 sub 和 preferred_username
+fn parse_userinfo(kind: &str, value: &Value) -> Option<ExternalUser> {
+    let text = |key: &str| value[key].as_str().filter(|s| !s.is_empty()).map(str::to_string);
+    if kind == KIND_GITHUB {
+        let id = value["id"].as_i64().map(|id| id.to_string()).or_else(|| text("id"))?;
+        return Some(ExternalUser { id, username: text("login"), email: text("email") });
+    }
+    // 提供方明确表示邮箱未验证时不使用
+    let email = text("email").filter(|_| value["email_verified"].as_bool() != Some(false));
+    Some(ExternalUser {
+        id: text("sub")?,
+        username: text("preferred_username").or_else(|| text("nickname")).or_else(|| text("name")),
+        email,
+    })
+}
+
+fn upstream_error(e: reqwest::Error) -> AppError {
+    AppError::Internal(format!("请求第三方登录提供方失败: {}", e))
+}
+
+// 用授权码换取访问令牌，再读取第三方用户信息
+async fn fetch_external_user(provider: &Provider<'_>, code: &str, code_verifier: &str) -> Result<ExternalUser, AppError> {
+    let client = reqwest::Client::new();
+    let token: Value = client
+        .post(&provider.token_url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", provider.settings.redirect_uri.as_str()),
+            ("client_id", provider.settings.client_id.as_str()),
+            ("client_secret", provider.settings.client_secret.as_str()),
+            ("code_verifier", code_verifier),
+        ])
+        .send()
+        .await
+        .map_err(upstream_error)?
+        .json()
+        .await
+        .map_err(upstream_error)?;
+    let Some(access_token) = token["access_token"].as_str() else {
+        let reason = token["error_description"].as_str().or(token["error"].as_str()).unwrap_or("未返回访问令牌");
+        return Err(AppError::Forbidden(format!("第三方授权失败: {}", reason)));
+    };
+
+    let userinfo: Value = client
+        .get(&provider.userinfo_url)
+        .bearer_auth(access_token)
+        .header(reqwest::header::ACCEPT, "application/json")
+        // GitHub API 要求携带 User-Agent
+        .header(reqwest::header::USER_AGENT, "yueling")
+        .send()
+        .await
+        .map_err(upstream_error)?
+        .error_for_status()
+        .map_err(upstream_error)?
+        .json()
+        .await
+        .map_err(upstream_error)?;
+    parse_userinfo(provider.kind, &userinfo)
+        .ok_or_else(|| AppError::Internal("第三方用户信息无效".into()))
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+// 为第三方账号创建本地用户：优先使用第三方用户名，冲突或不合规时加随机后缀
+async fn sign_up(state: &AppState, external: &ExternalUser) -> Result<crate::storage::User, AppError> {
+    let suggested = external.username.clone()
+        .or_else(|| external.email.as_deref().and_then(|e| e.split('@').next()).map(str::to_string))
+        .unwrap_or_default();
+    let mut base: String = suggested.chars().filter(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.')).collect();
+    base = base.chars().take(state.settings.username.max_length.saturating_sub(5)).collect();
+    if base.is_empty() {
+        base = "user".into();
+    }
+    // 第三方账号不使用密码登录
+    let password = random_hex(32);
+    let email = external.email.as_deref().unwrap_or_default();
+
+    let mut last_error = None;
+    for attempt in 0..3 {
+        let candidate = match attempt {
+            0 => base.clone(),
+            1 => format!("{}_{}", base, random_hex(2)),
+            _ => format!("user_{}", random_hex(3)),
+        };
+        match super::user::register_user(state, &candidate, email, &password, "", None).await {
+            Ok(user) => return Ok(user),
+            Err(AppError::UserExists(msg)) if msg.contains("邮箱") => {
+                return Err(AppError::UserExists("该邮箱已注册，请登录后再关联第三方账号".into()));
+            }
+            Err(e @ (AppError::UserExists(_) | AppError::InvalidInput(_))) => last_error = Some(e),
+            Err(e) => return Err(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| AppError::Internal("创建账号失败".into())))
+}
+
+fn link_error(e: rusqlite::Error) -> AppError {
+    match e {
+        rusqlite::Error::SqliteFailure(_, Some(msg)) if msg.contains("关联") => AppError::UserExists(msg),
+        _ => AppError::Database(e.to_string()),
+    }
+}
+
+// 获取第三方授权地址；携带会话令牌时授权完成后关联到当前用户
+pub async fn authorize_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<AuthorizeResponse>, AppError> {
+    let provider = provider(&state, &name)?;
+    let link_user_id = match super::user::bearer_token(&headers) {
+        Some(_) => Some(super::user::session_user(&state, &headers)?),
+        None => None,
+    };
+    let (oauth_state, code_verifier) = state.db_pool
+        .create_oauth_state(provider.name, link_user_id.as_deref(), state.settings.oauth.state_ttl_secs, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let mut url = reqwest::Url::parse(&provider.authorize_url)
+        .map_err(|e| AppError::Internal(format!("无效的授权地址: {}", e)))?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &provider.settings.client_id)
+        .append_pair("redirect_uri", &provider.settings.redirect_uri)
+        .append_pair("scope", &provider.scopes.join(" "))
+        .append_pair("state", &oauth_state)
+        .append_pair("code_challenge", &URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes())))
+        .append_pair("code_challenge_method", "S256");
+
+    Ok(Json(AuthorizeResponse {
+        success: true,
+        message: "获取授权地址成功".into(),
+        authorize_url: url.into(),
+        state: oauth_state,
+    }))
+}
+
+// 提供方授权完成后的回调：登录、注册或关联账号
+pub async fn callback_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Json<OAuthLoginResponse>, AppError> {
+    let provider = provider(&state, &name)?;
+    if let Some(error) = query.error {
+        return Err(AppError::Forbidden(format!("第三方授权失败: {}", error)));
+    }
+    let (Some(code), Some(oauth_state)) = (query.code, query.state) else {
+        return Err(AppError::InvalidInput("授权请求无效或已过期".into()));
+    };
+    let pending = state.db_pool.consume_oauth_state(&oauth_state, provider.name, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::InvalidInput("授权请求无效或已过期".into()))?;
+
+    let external = fetch_external_user(&provider, &code, &pending.code_verifier).await?;
+    let existing = state.db_pool.find_identity_user(provider.name, &external.id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    let (user_id, created, linked, message) = match (pending.link_user_id, existing) {
+        // 关联到发起授权的用户
+        (Some(user_id), _) => {
+            state.db_pool.link_identity(&user_id, provider.name, &external.id, external.email.as_deref(), false, unix_now())
+                .map_err(link_error)?;
+            (user_id, false, true, "账号已关联")
+        }
+        (None, Some(user_id)) => (user_id, false, false, "登录成功"),
+        (None, None) => {
+            if !state.settings.oauth.allow_signup {
+                return Err(AppError::Forbidden("该第三方账号未关联本地用户".into()));
+            }
+            let user = sign_up(&state, &external).await?;
+            state.db_pool.link_identity(&user.id, provider.name, &external.id, external.email.as_deref(), true, unix_now())
+                .map_err(link_error)?;
+            (user.id, true, true, "注册成功")
+        }
+    };
+
+    let user = state.db_pool.get_user_by_id(&user_id).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("用户不存在".into()),
+        _ => AppError::Database(e.to_string()),
+    })?;
+    let token = super::user::create_session(&state, &user_id, None)?;
+    Ok(Json(OAuthLoginResponse {
+        success: true,
+        message: message.into(),
+        user_id,
+        username: user.username,
+        token,
+        created,
+        linked,
+    }))
+}
+
+// 当前用户关联的第三方账号
+pub async fn list_identities_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<IdentitiesResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let identities = state.db_pool.list_identities(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(IdentitiesResponse {
+        success: true,
+        message: "获取关联账号成功".into(),
+        identities,
+    }))
+}
+
+// 解除关联；通过第三方账号注册的用户没有可用的密码，不能解除注册时使用的关联
+pub async fn unlink_identity_handler(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let identities = state.db_pool.list_identities(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let identity = identities.iter().find(|identity| identity.provider == name)
+        .ok_or_else(|| AppError::NotFound("未关联该第三方账号".into()))?;
+    if identity.signup {
+        return Err(AppError::Forbidden("该账号通过此第三方账号注册，不能解除关联".into()));
+    }
+    state.db_pool.unlink_identity(&user_id, &name)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "已解除关联".into(),
+    }))
+}
+
+/// 注册第三方登录路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/oauth/{provider}/authorize", get(authorize_handler))
+        .route("/oauth/{provider}/callback", get(callback_handler))
+        .route("/account/identities", get(list_identities_handler))
+        .route("/account/identities/{provider}", delete(unlink_identity_handler))
+}
@@ -0,0 +1,203 @@
+//! 每个 WebSocket 连接的发送队列：容量有限，由连接的写任务逐条取出发给客户端
+//!
+//! 客户端读得太慢导致队列满时先丢弃低优先级的事件（输入状态、在线状态等，丢了客户端也能恢复），
+//! 仍然放不下才丢弃新的消息事件；未确认送达的私聊消息由发件箱重发。
+//! 排队中的事件数和丢弃数由 /metrics 导出
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Deserialize;
+use tokio::sync::Notify;
+
+// 可以丢弃的事件类型（事件 JSON 的 type 字段）
+const LOW_PRIORITY_EVENTS: &[&str] = &["typing", "presence", "profile_updated"];
+
+/// 事件的优先级，队列满时先丢弃 Low
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Priority {
+    Low,
+    Normal,
+}
+
+impl Priority {
+    // 按事件的 type 字段判断，不是 JSON 对象的负载（如群聊转发的原始内容）按 Normal 处理
+    fn of(payload: &str) -> Self {
+        #[derive(Deserialize)]
+        struct Event<'a> {
+            #[serde(rename = "type", borrow)]
+            kind: Option<&'a str>,
+        }
+        match serde_json::from_str::<Event>(payload) {
+            Ok(Event { kind: Some(kind) }) if LOW_PRIORITY_EVENTS.contains(&kind) => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+        }
+    }
+}
+
+// 所有连接的队列中排队的事件数
+static QUEUED: AtomicU64 = AtomicU64::new(0);
+// 按优先级统计的丢弃数
+static DROPPED: [AtomicU64; 2] = [AtomicU64::new(0), AtomicU64::new(0)];
+
+fn record_dropped(priority: Priority) {
+    DROPPED[priority as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+struct Shared {
+    queue: Mutex<VecDeque<(Priority, String)>>,
+    capacity: usize,
+    notify: Notify,
+    senders: AtomicUsize,
+    closed: AtomicBool,     // 写任务已经结束
+}
+
+/// 队列的写入端，克隆后共享同一个队列
+pub struct Sender {
+    shared: Arc<Shared>,
+}
+
+/// 队列的读取端，由连接的写任务持有，释放后队列关闭
+pub struct Receiver {
+    shared: Arc<Shared>,
+}
+
+/// 读取端已经释放（连接已断开）
+#[derive(Debug)]
+pub struct Closed;
+
+/// 队列中没有事件
+#[derive(Debug)]
+pub struct Empty;
+
+/// 创建最多容纳 capacity 个事件的队列
+pub fn channel(capacity: usize) -> (Sender, Receiver) {
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(VecDeque::new()),
+        capacity: capacity.max(1),
+        notify: Notify::new(),
+        senders: AtomicUsize::new(1),
+        closed: AtomicBool::new(false),
+    });
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+impl Sender {
+    /// 把事件放入队列；队列满时按优先级丢弃（仍返回 Ok），只有连接已断开时返回 Err
+    pub fn send(&self, payload: String) -> Result<(), Closed> {
+        if self.is_closed() {
+            return Err(Closed);
+        }
+        let priority = Priority::of(&payload);
+        let mut queue = self.shared.queue.lock().unwrap();
+        if queue.len() >= self.shared.capacity {
+            // 给消息腾出位置时丢弃最早的低优先级事件
+            let evicted = match priority {
+                Priority::Low => None,
+                Priority::Normal => queue.iter().position(|(p, _)| *p == Priority::Low),
+            };
+            let Some(index) = evicted else {
+                record_dropped(priority);
+                return Ok(());
+            };
+            queue.remove(index);
+            QUEUED.fetch_sub(1, Ordering::Relaxed);
+            record_dropped(Priority::Low);
+        }
+        queue.push_back((priority, payload));
+        QUEUED.fetch_add(1, Ordering::Relaxed);
+        drop(queue);
+        self.shared.notify.notify_one();
+        Ok(())
+    }
+
+    /// 读取端是否已经释放
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::Acquire)
+    }
+
+    /// 是否为同一个队列的写入端
+    pub fn same_channel(&self, other: &Sender) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// 排队中的事件数
+    pub fn len(&self) -> usize {
+        self.shared.queue.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        // 最后一个写入端释放时唤醒读取端，让它发完剩下的事件后结束
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+impl Receiver {
+    /// 取出下一个事件，队列为空时等待；队列为空且写入端都已释放时返回 None
+    pub async fn recv(&mut self) -> Option<String> {
+        loop {
+            if let Some((_, payload)) = self.shared.queue.lock().unwrap().pop_front() {
+                QUEUED.fetch_sub(1, Ordering::Relaxed);
+                return Some(payload);
+            }
+            if self.shared.senders.load(Ordering::Acquire) == 0 {
+                return None;
+            }
+            // 只有一个读取端，notify_one 在没有等待者时会保留一次唤醒，不会错过
+            self.shared.notify.notified().await;
+        }
+    }
+
+    /// 不等待地取出下一个事件，队列为空时返回 Err
+    pub fn try_recv(&mut self) -> Result<String, Empty> {
+        let (_, payload) = self.shared.queue.lock().unwrap().pop_front().ok_or(Empty)?;
+        QUEUED.fetch_sub(1, Ordering::Relaxed);
+        Ok(payload)
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+        let mut queue = self.shared.queue.lock().unwrap();
+        QUEUED.fetch_sub(queue.len() as u64, Ordering::Relaxed);
+        queue.clear();
+    }
+}
+
+/// 以 OpenMetrics 文本格式输出排队和丢弃的事件数，不含结尾的 `# EOF`
+pub fn render(out: &mut String) {
+    let _ = writeln!(out, "# TYPE yueling_ws_send_queue_events gauge");
+    let _ = writeln!(out, "# HELP yueling_ws_send_queue_events Events waiting in WebSocket send queues.");
+    let _ = writeln!(out, "yueling_ws_send_queue_events {}", QUEUED.load(Ordering::Relaxed));
+    let _ = writeln!(out, "# TYPE yueling_ws_send_queue_dropped counter");
+    let _ = writeln!(out, "# HELP yueling_ws_send_queue_dropped Events dropped because a WebSocket send queue was full.");
+    for priority in [Priority::Low, Priority::Normal] {
+        let dropped = DROPPED[priority as usize].load(Ordering::Relaxed);
+        let _ = writeln!(out, "yueling_ws_send_queue_dropped_total{{priority=\"{}\"}} {dropped}", priority.as_str());
+    }
+}
@@ -0,0 +1,91 @@
+//! 访问控制策略：处理器声明调用者需要满足的条件，由这里统一检查并给出一致的错误
+//!
+//! 调用者只由会话令牌（Authorization: Bearer）确定，请求中的 user_id、sender_id 不再代表身份，
+//! 只能是调用者本人（`Check::Is`）。调用者看不到的群按不存在处理，不暴露群是否存在。
+//! 服务器管理员使用配置中的管理令牌，管理接口见 `AdminAuth`
+
+use crate::crypto::conversation::conversation_id;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+
+/// 一项访问条件
+pub(crate) enum Check<'a> {
+    /// 属于该工作区
+    WorkspaceMember(&'a str),
+    /// 是会话的参与者：peer 是群时须是群成员，否则是与该用户的私聊
+    Participant(&'a str),
+    /// 是该群的成员
+    GroupMember(&'a str),
+    /// 是该群的群主
+    GroupOwner(&'a str),
+    /// 就是该用户：请求中指定的用户ID，或资源所有者的用户ID
+    Is(&'a str),
+}
+
+fn group_not_found() -> AppError {
+    AppError::NotFound("群聊不存在".into())
+}
+
+fn is_group_member(state: &AppState, group_id: &str, user_id: &str) -> Result<bool, AppError> {
+    state.db_pool.is_group_member(group_id, user_id).map_err(|e| AppError::Database(e.to_string()))
+}
+
+/// 按会话令牌识别调用者，满足全部条件时返回其用户ID
+pub(crate) fn authorize(state: &AppState, headers: &http::HeaderMap, checks: &[Check]) -> Result<String, AppError> {
+    let user_id = super::user::session_user(state, headers)?;
+    require(state, &user_id, checks)?;
+    Ok(user_id)
+}
+
+/// 按顺序检查已识别的用户，返回第一个不满足的条件对应的错误
+pub(crate) fn require(state: &AppState, user_id: &str, checks: &[Check]) -> Result<(), AppError> {
+    for check in checks {
+        match *check {
+            Check::WorkspaceMember(workspace_id) => super::workspace::require_member(state, workspace_id, user_id)?,
+            Check::Participant(peer_id) => {
+                conversation(state, user_id, peer_id)?;
+            }
+            Check::GroupMember(group_id) => {
+                if !is_group_member(state, group_id, user_id)? {
+                    return Err(group_not_found());
+                }
+            }
+            Check::GroupOwner(group_id) => {
+                let owner = state.db_pool.group_owner(group_id)
+                    .map_err(|e| AppError::Database(e.to_string()))?;
+                match owner {
+                    Some(owner) if owner == user_id => {}
+                    Some(_) if is_group_member(state, group_id, user_id)? =>
+                        return Err(AppError::Forbidden("只有群主可以执行此操作".into())),
+                    _ => return Err(group_not_found()),
+                }
+            }
+            Check::Is(expected) => {
+                if expected != user_id {
+                    return Err(AppError::Forbidden("不能以其他用户的身份操作".into()));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 用户与 peer_id 之间的会话类型和会话ID：peer_id 是群时用户须是群成员，否则按私聊处理
+pub(crate) fn conversation(state: &AppState, user_id: &str, peer_id: &str) -> Result<(&'static str, String), AppError> {
+    let members = state.db_pool.get_group_members(peer_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if members.is_empty() {
+        Ok(("private", conversation_id("private", user_id, peer_id)))
+    } else if members.iter().any(|m| m.user_id == user_id) {
+        Ok(("group", conversation_id("group", user_id, peer_id)))
+    } else {
+        Err(group_not_found())
+    }
+}
+
+/// 请求是否带着服务器管理令牌；配置中令牌为空时没有管理员
+pub(crate) fn is_server_admin(state: &AppState, headers: &http::HeaderMap) -> bool {
+    super::admin::token_matches(&state.settings.admin.token, super::user::bearer_token(headers))
+}
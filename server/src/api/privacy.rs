@@ -0,0 +1,43 @@
+//! 隐私设置的服务端检查：谁可以发私信、谁能看到在线状态、谁可以把自己加入群聊、谁可以在通讯录匹配中找到自己
+//!
+//! 设置项保存在用户设置中（`dm_privacy`、`last_seen_privacy`、`group_add_privacy`、`discovery_privacy`），
+//! 取值为 everyone、friends 或 nobody，未设置时为 everyone
+
+use crate::error::AppError;
+use crate::storage::user_settings::{
+    SETTING_DISCOVERY_PRIVACY, SETTING_DM_PRIVACY, SETTING_GROUP_ADD_PRIVACY, SETTING_LAST_SEEN_PRIVACY,
+};
+
+// 共享应用状态
+use super::AppState;
+
+fn allows(state: &AppState, owner: &str, key: &str, other: &str) -> Result<bool, AppError> {
+    state.db_pool.privacy_allows(owner, key, other)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+/// 接收者的隐私设置不允许发送者发私信或发起通话时返回 Forbidden
+pub(crate) fn require_dm_allowed(state: &AppState, sender_id: &str, receiver_id: &str) -> Result<(), AppError> {
+    if !allows(state, receiver_id, SETTING_DM_PRIVACY, sender_id)? {
+        return Err(AppError::Forbidden("对方的隐私设置不允许你发送私信".into()));
+    }
+    Ok(())
+}
+
+/// viewer 能否看到 user_id 的在线状态和最后在线时间，未登录的查看者只能看到设为 everyone 的用户
+pub(crate) fn last_seen_visible(state: &AppState, user_id: &str, viewer: Option<&str>) -> Result<bool, AppError> {
+    allows(state, user_id, SETTING_LAST_SEEN_PRIVACY, viewer.unwrap_or_default())
+}
+
+/// 用户的隐私设置不允许 adder 把其加入群聊时返回 Forbidden
+pub(crate) fn require_group_add_allowed(state: &AppState, adder_id: &str, user_id: &str) -> Result<(), AppError> {
+    if !allows(state, user_id, SETTING_GROUP_ADD_PRIVACY, adder_id)? {
+        return Err(AppError::Forbidden("对方的隐私设置不允许你把其加入群聊".into()));
+    }
+    Ok(())
+}
+
+/// viewer 能否在通讯录匹配中找到 user_id
+pub(crate) fn discoverable(state: &AppState, user_id: &str, viewer: &str) -> Result<bool, AppError> {
+    allows(state, user_id, SETTING_DISCOVERY_PRIVACY, viewer)
+}
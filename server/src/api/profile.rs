@@ -0,0 +1,145 @@
+//! 个人资料：显示名称（昵称）的修改、修改记录和改名冷却
+//!
+//! 显示名称或用户名变化后向好友、所在的群和本人的其他设备推送 profile_updated 事件，
+//! 客户端据此更新缓存的名称
+
+use axum::{
+    extract::{Path, State},
+    response::Json,
+    routing::{get, patch},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::error::AppError;
+use crate::storage::profiles::DisplayNameChange;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+// 修改记录每次最多返回的条数
+const HISTORY_LIMIT: i64 = 50;
+
+/// 向好友、所在的群和本人的连接推送 profile_updated 事件
+pub(crate) fn broadcast_profile_updated(state: &AppState, user_id: &str, username: &str, display_name: Option<&str>, updated_at: i64) -> Result<(), AppError> {
+    let event = json!({
+        "type": "profile_updated",
+        "user_id": user_id,
+        "username": username,
+        "display_name": display_name,
+        "updated_at": updated_at,
+    }).to_string();
+
+    let friends = state.db_pool.get_friends(user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for friend in friends {
+        state.send_to_user(&friend.id, event.clone());
+    }
+    let groups = state.db_pool.groups_of_user(user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    for (group_id, _) in groups {
+        state.send_to_group(&group_id, event.clone());
+    }
+    state.send_to_user(user_id, event);
+    Ok(())
+}
+
+// 修改个人资料的请求体，display_name 为 null 或空字符串时清除显示名称
+#[derive(Deserialize)]
+pub struct UpdateProfileRequest {
+    pub display_name: Option<String>,
+}
+
+// 个人资料响应体
+#[derive(Serialize)]
+pub struct ProfileResponse {
+    pub success: bool,
+    pub message: String,
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub next_change_at: Option<i64>,   // 冷却结束、可以再次修改的时间，不限制时为空
+}
+
+// 修改个人资料（需要本人的登录会话），两次修改显示名称之间至少间隔 display_name_cooldown_secs
+pub async fn update_profile_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<UpdateProfileRequest>,
+) -> Result<Json<ProfileResponse>, AppError> {
+    if super::user::session_user(&state, &headers)? != user_id {
+        return Err(AppError::Forbidden("只能修改自己的个人资料".into()));
+    }
+    let settings = &state.settings.username;
+    let display_name = req.display_name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty());
+    if let Some(name) = &display_name {
+        super::username::check_display_name(settings, name)?;
+    }
+    let user = state.db_pool.get_user_by_id(&user_id).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("用户不存在".into()),
+        _ => AppError::Database(e.to_string()),
+    })?;
+
+    let now = unix_now();
+    let cooldown = settings.display_name_cooldown_secs as i64;
+    let current = state.db_pool.display_name(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if current != display_name {
+        let last_change = state.db_pool.last_display_name_change(&user_id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        if let Some(remaining) = last_change.map(|at| at + cooldown - now).filter(|secs| *secs > 0) {
+            return Err(AppError::RateLimited(format!("修改显示名称过于频繁，请 {} 秒后再试", remaining)));
+        }
+        state.db_pool.set_display_name(&user_id, display_name.as_deref(), now)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        broadcast_profile_updated(&state, &user_id, &user.username, display_name.as_deref(), now)?;
+    }
+    let last_change = state.db_pool.last_display_name_change(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(ProfileResponse {
+        success: true,
+        message: "个人资料已更新".into(),
+        user_id,
+        username: user.username,
+        display_name,
+        next_change_at: last_change.filter(|_| cooldown > 0).map(|at| at + cooldown),
+    }))
+}
+
+// 显示名称修改记录响应体
+#[derive(Serialize)]
+pub struct DisplayNameHistoryResponse {
+    pub success: bool,
+    pub message: String,
+    pub history: Vec<DisplayNameChange>,
+}
+
+// 显示名称的修改记录，最近的在前，便于其他用户识别改名冒充
+pub async fn display_name_history_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<DisplayNameHistoryResponse>, AppError> {
+    if !state.db_pool.user_exists_by_id(&user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("用户不存在".into()));
+    }
+    let history = state.db_pool.display_name_history(&user_id, HISTORY_LIMIT)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(DisplayNameHistoryResponse {
+        success: true,
+        message: "获取显示名称修改记录成功".into(),
+        history,
+    }))
+}
+
+/// 注册个人资料路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/user/{user_id}/profile", patch(update_profile_handler))
+        .route("/user/{user_id}/display-names", get(display_name_history_handler))
+}
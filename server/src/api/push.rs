@@ -0,0 +1,130 @@
+use axum::{
+    extract::State,
+    response::Json,
+    routing::post,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::push::{PushNotification, PLATFORMS};
+use crate::storage::{jobs::JOB_PUSH, Message};
+use serde_json::json;
+
+// 共享应用状态
+use super::AppState;
+use super::policy::{authorize, Check};
+use crate::core::datetime::unix_now;
+
+// 注册推送令牌请求体
+#[derive(Deserialize)]
+pub struct RegisterPushTokenRequest {
+    pub user_id: String,
+    pub platform: String, // "fcm" 或 "apns"
+    pub token: String,
+}
+
+// 注销推送令牌请求体
+#[derive(Deserialize)]
+pub struct UnregisterPushTokenRequest {
+    pub token: String,
+}
+
+// 推送令牌响应体
+#[derive(Serialize)]
+pub struct PushTokenResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// 给不在线且不在免打扰时段的接收者发送推送（加入任务队列，不阻塞消息发送）
+pub(crate) fn notify_offline(state: &AppState, message: &Message) {
+    if !state.push.is_enabled() {
+        return;
+    }
+
+    // 群聊推送给除发送者外的全部成员；私聊的会话ID对接收者而言是发送者
+    let (recipients, conversation_id) = if message.message_type == "group" {
+        let members = state.db_pool.get_group_members(&message.receiver_id).unwrap_or_default();
+        let recipients = members
+            .into_iter()
+            .map(|m| m.user_id)
+            .filter(|id| id != &message.sender_id)
+            .collect();
+        (recipients, &message.receiver_id)
+    } else {
+        (vec![message.receiver_id.clone()], &message.sender_id)
+    };
+    // 处于免打扰时段的用户不推送，消息照常保存，上线后同步即可看到
+    let now = unix_now();
+    let offline: Vec<String> = recipients
+        .into_iter()
+        .filter(|id| !state.is_online(id))
+        .filter(|id| !matches!(state.db_pool.dnd_until(id, now), Ok(Some(_))))
+        .collect();
+    if offline.is_empty() {
+        return;
+    }
+
+    let sender_name = state.db_pool.get_user_by_id(&message.sender_id)
+        .map(|u| u.username)
+        .unwrap_or_default();
+    let notification = PushNotification::new_message(&sender_name, &message.content, &message.id, conversation_id);
+    // 经由任务队列发送，服务器重启也不会丢失
+    crate::tasks::jobs::enqueue(state, JOB_PUSH, json!({
+        "user_ids": offline,
+        "notification": notification,
+    }));
+}
+
+// 注册推送令牌处理器
+pub async fn register_push_token_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<RegisterPushTokenRequest>,
+) -> Result<Json<PushTokenResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&req.user_id)])?;
+    if !PLATFORMS.contains(&req.platform.as_str()) {
+        return Err(AppError::InvalidInput(format!("不支持的推送平台 {}，可选: {}", req.platform, PLATFORMS.join(", "))));
+    }
+    if req.token.is_empty() {
+        return Err(AppError::InvalidInput("推送令牌不能为空".into()));
+    }
+
+    let now = unix_now();
+    let registered = state.db_pool.register_push_token(&req.user_id, &req.platform, &req.token, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !registered {
+        return Err(AppError::Forbidden("推送令牌已被其他用户注册".into()));
+    }
+
+    Ok(Json(PushTokenResponse {
+        success: true,
+        message: "推送令牌已注册".into(),
+    }))
+}
+
+// 注销推送令牌处理器（退出登录时调用），只能注销会话用户自己的令牌
+pub async fn unregister_push_token_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<UnregisterPushTokenRequest>,
+) -> Result<Json<PushTokenResponse>, AppError> {
+    let user_id = authorize(&state, &headers, &[])?;
+    let removed = state.db_pool.remove_push_token(&user_id, &req.token)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !removed {
+        return Err(AppError::NotFound("推送令牌不存在".into()));
+    }
+
+    Ok(Json(PushTokenResponse {
+        success: true,
+        message: "推送令牌已注销".into(),
+    }))
+}
+
+/// 注册推送相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/push/register", post(register_push_token_handler))
+        .route("/push/unregister", post(unregister_push_token_handler))
+}
@@ -0,0 +1,152 @@
+//! 配额：单条消息长度、单个附件大小和每个用户的附件总量
+//!
+//! 默认值来自 [quotas] 和 [attachments] 配置，管理员可以为单个用户或群覆盖；群的覆盖只影响发往该群的消息长度
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    response::Json,
+    routing::{get, put},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::storage::quotas::{Quota, QuotaOverride, Usage};
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use crate::core::datetime::unix_now;
+
+// 配额覆盖请求体，省略的项使用默认值，0 表示不限制
+#[derive(Deserialize)]
+pub struct QuotaOverrideRequest {
+    pub max_message_chars: Option<i64>,
+    pub max_attachment_bytes: Option<i64>,
+    pub max_storage_bytes: Option<i64>,
+}
+
+// 配额覆盖列表响应体
+#[derive(Serialize)]
+pub struct QuotaOverridesResponse {
+    pub success: bool,
+    pub message: String,
+    pub defaults: Quota,
+    pub overrides: Vec<QuotaOverride>,
+}
+
+// 用量响应体
+#[derive(Serialize)]
+pub struct UsageResponse {
+    pub success: bool,
+    pub message: String,
+    pub usage: Usage,
+    pub quota: Quota,
+}
+
+// 用户（发往群聊时再考虑群）的生效配额
+pub(crate) fn quota_for(state: &AppState, user_id: &str, group_id: Option<&str>) -> Result<Quota, AppError> {
+    let defaults = Quota::defaults(&state.settings.quotas, &state.settings.attachments);
+    state.db_pool.quota_for(defaults, user_id, group_id)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+// 检查消息长度是否在发送者（群聊时为该群）的配额内
+pub(crate) fn check_message_length(state: &AppState, sender_id: &str, receiver_id: &str, message_type: &str, content: &str) -> Result<(), AppError> {
+    let group_id = (message_type == "group").then_some(receiver_id);
+    let limit = quota_for(state, sender_id, group_id)?.max_message_chars;
+    if limit > 0 && content.chars().count() as i64 > limit {
+        return Err(AppError::InvalidInput(format!("消息不能超过 {} 个字符", limit)));
+    }
+    Ok(())
+}
+
+// 当前用户的用量和生效配额
+pub async fn usage_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<UsageResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let usage = state.db_pool.storage_usage(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(UsageResponse {
+        success: true,
+        message: "获取用量成功".into(),
+        usage,
+        quota: quota_for(&state, &user_id, None)?,
+    }))
+}
+
+// 列出默认配额和全部覆盖
+pub async fn list_quota_overrides_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<QuotaOverridesResponse>, AppError> {
+    let overrides = state.db_pool.list_quota_overrides()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(QuotaOverridesResponse {
+        success: true,
+        message: "获取配额成功".into(),
+        defaults: Quota::defaults(&state.settings.quotas, &state.settings.attachments),
+        overrides,
+    }))
+}
+
+// 设置用户或群的配额覆盖（整体替换）
+pub async fn set_quota_override_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(subject_id): UrlPath<String>,
+    Json(req): Json<QuotaOverrideRequest>,
+) -> Result<Json<AdminResponse>, AppError> {
+    if [req.max_message_chars, req.max_attachment_bytes, req.max_storage_bytes].iter().flatten().any(|value| *value < 0) {
+        return Err(AppError::InvalidInput("配额不能为负数".into()));
+    }
+    let is_user = state.db_pool.user_exists_by_id(&subject_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let is_group = state.db_pool.group_workspace(&subject_id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .is_some();
+    if !is_user && !is_group {
+        return Err(AppError::NotFound("用户或群聊不存在".into()));
+    }
+    state.db_pool.set_quota_override(&QuotaOverride {
+        subject_id,
+        max_message_chars: req.max_message_chars,
+        max_attachment_bytes: req.max_attachment_bytes,
+        max_storage_bytes: req.max_storage_bytes,
+        updated_at: unix_now(),
+    }).map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "配额已更新".into(),
+    }))
+}
+
+// 删除配额覆盖，恢复默认配额
+pub async fn remove_quota_override_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(subject_id): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let removed = state.db_pool.remove_quota_override(&subject_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !removed {
+        return Err(AppError::NotFound("没有单独设置的配额".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "配额已恢复默认".into(),
+    }))
+}
+
+/// 注册配额相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/usage", get(usage_handler))
+        .route("/admin/quotas", get(list_quota_overrides_handler))
+        .route("/admin/quotas/{subject_id}", put(set_quota_override_handler).delete(remove_quota_override_handler))
+}
@@ -0,0 +1,213 @@
+//! 限流：固定窗口限流器，以及按档位（普通用户、机器人、管理员）限制每分钟请求数和发消息数
+//!
+//! 需要登录的 REST 接口在校验会话时计入请求数，机器人 API 按密钥计入；
+//! 各种途径发送的消息都在保存前计入发消息数，服务器代发的系统消息不计入
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::Serialize;
+use crate::config::settings::{RateLimitSettings, RateTierSettings};
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+
+// 计数表超过该大小时清理已过期的窗口
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// 档位的计数窗口
+const TIER_WINDOW: Duration = Duration::from_secs(60);
+
+/// 固定窗口限流器：每个键在一个窗口内最多通过 limit 次，limit 为 0 表示不限
+#[derive(Clone)]
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    counters: Arc<Mutex<HashMap<String, (Instant, u32)>>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            counters: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// 记录一次请求；超过限额时返回距窗口结束的秒数
+    pub fn check(&self, key: &str) -> Result<(), u64> {
+        if self.limit == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut counters = self.counters.lock().unwrap();
+        if counters.len() > PRUNE_THRESHOLD {
+            counters.retain(|_, (start, _)| now.duration_since(*start) < self.window);
+        }
+
+        let entry = counters.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(entry.0) >= self.window {
+            *entry = (now, 0);
+        }
+        if entry.1 >= self.limit {
+            let remaining = self.window.saturating_sub(now.duration_since(entry.0));
+            return Err(remaining.as_secs().max(1));
+        }
+        entry.1 += 1;
+        Ok(())
+    }
+
+    /// 当前窗口剩余的次数和距窗口结束的秒数（不计入一次请求）；不限时剩余次数为 None
+    pub fn usage(&self, key: &str) -> (Option<u32>, u64) {
+        if self.limit == 0 {
+            return (None, 0);
+        }
+        let now = Instant::now();
+        match self.counters.lock().unwrap().get(key) {
+            Some((start, count)) if now.duration_since(*start) < self.window => {
+                let reset = self.window.saturating_sub(now.duration_since(*start));
+                (Some(self.limit.saturating_sub(*count)), reset.as_secs().max(1))
+            }
+            _ => (Some(self.limit), 0),
+        }
+    }
+}
+
+/// 限流档位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tier {
+    User,
+    Bot,
+    Admin,
+}
+
+// 一个档位的额度和计数
+#[derive(Clone)]
+struct TierLimiters {
+    budget: RateTierSettings,
+    requests: RateLimiter,
+    messages: RateLimiter,
+}
+
+/// 各档位的限流器，克隆开销很小
+#[derive(Clone)]
+pub struct RateLimits {
+    tiers: Arc<HashMap<Tier, TierLimiters>>,
+    admin_user_ids: Arc<Vec<String>>,
+}
+
+impl RateLimits {
+    pub fn from_settings(settings: &RateLimitSettings) -> Self {
+        let tiers = [(Tier::User, &settings.user), (Tier::Bot, &settings.bot), (Tier::Admin, &settings.admin)]
+            .into_iter()
+            .map(|(tier, budget)| {
+                (tier, TierLimiters {
+                    budget: budget.clone(),
+                    requests: RateLimiter::new(budget.requests_per_minute, TIER_WINDOW),
+                    messages: RateLimiter::new(budget.messages_per_minute, TIER_WINDOW),
+                })
+            })
+            .collect();
+        Self {
+            tiers: Arc::new(tiers),
+            admin_user_ids: Arc::new(settings.admin_user_ids.clone()),
+        }
+    }
+
+    fn tier(&self, tier: Tier) -> &TierLimiters {
+        &self.tiers[&tier]
+    }
+
+    /// 计入一次请求；key 为用户ID，机器人为 API 密钥ID。超过额度时返回距窗口结束的秒数
+    pub fn check_request(&self, tier: Tier, key: &str) -> Result<(), u64> {
+        self.tier(tier).requests.check(key)
+    }
+
+    /// 计入一条消息；超过额度时返回距窗口结束的秒数
+    pub fn check_message(&self, tier: Tier, user_id: &str) -> Result<(), u64> {
+        self.tier(tier).messages.check(user_id)
+    }
+}
+
+/// 用户所属的档位：配置中的管理员、机器人账号或普通用户
+pub(crate) fn tier_of(state: &AppState, user_id: &str) -> Result<Tier, AppError> {
+    if state.rate_limits.admin_user_ids.iter().any(|id| id == user_id) {
+        return Ok(Tier::Admin);
+    }
+    let is_bot = state.db_pool.is_bot(user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    Ok(if is_bot { Tier::Bot } else { Tier::User })
+}
+
+/// 已登录用户的一次请求，超过档位的请求额度时拒绝
+pub(crate) fn check_request(state: &AppState, user_id: &str) -> Result<(), AppError> {
+    let tier = tier_of(state, user_id)?;
+    state.rate_limits.check_request(tier, user_id)
+        .map_err(|retry_after| AppError::RateLimited(format!("请求过于频繁，请在 {} 秒后重试", retry_after)))
+}
+
+/// 发送一条消息，超过发送者档位的消息额度时拒绝
+pub(crate) fn check_message(state: &AppState, sender_id: &str) -> Result<(), AppError> {
+    let tier = tier_of(state, sender_id)?;
+    state.rate_limits.check_message(tier, sender_id)
+        .map_err(|retry_after| AppError::RateLimited(format!("发送消息过于频繁，请在 {} 秒后重试", retry_after)))
+}
+
+// 一项额度的使用情况
+#[derive(Serialize)]
+pub struct Budget {
+    pub per_minute: u32,          // 0 表示不限
+    pub remaining: Option<u32>,   // 当前窗口剩余次数，不限时为空
+    pub reset_secs: u64,          // 距当前窗口结束的秒数，窗口未开始时为 0
+}
+
+impl Budget {
+    fn of(limiter: &RateLimiter, per_minute: u32, key: &str) -> Self {
+        let (remaining, reset_secs) = limiter.usage(key);
+        Self { per_minute, remaining, reset_secs }
+    }
+}
+
+// 当前用户限流额度响应体
+#[derive(Serialize)]
+pub struct LimitsResponse {
+    pub success: bool,
+    pub message: String,
+    pub tier: Tier,
+    pub requests: Budget,
+    pub messages: Budget,
+}
+
+// 当前用户的限流档位和剩余额度（本次请求已计入）
+pub async fn limits_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<LimitsResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let tier = tier_of(&state, &user_id)?;
+    let limiters = state.rate_limits.tier(tier);
+
+    Ok(Json(LimitsResponse {
+        success: true,
+        message: "获取限流额度成功".into(),
+        tier,
+        requests: Budget::of(&limiters.requests, limiters.budget.requests_per_minute, &user_id),
+        messages: Budget::of(&limiters.messages, limiters.budget.messages_per_minute, &user_id),
+    }))
+}
+
+/// 注册限流额度路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/account/limits", get(limits_handler))
+}
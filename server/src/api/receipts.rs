@@ -0,0 +1,73 @@
+//! 群消息的回执：发送者和群成员查看一条群消息送达了谁、谁已读
+//!
+//! 返回人数和每类的第一页成员，大群也只读取一页；私聊消息的回执见消息本身的 delivered_at 和 read_at
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::storage::receipts::MessageReceipts;
+
+// 共享应用状态
+use super::AppState;
+use super::policy::{self, Check};
+
+// 每类成员默认和最多返回的人数
+const DEFAULT_RECEIPT_PAGE: i64 = 20;
+const MAX_RECEIPT_PAGE: i64 = 100;
+
+// 回执的查询参数
+#[derive(Deserialize)]
+pub struct ReceiptsQuery {
+    pub limit: Option<i64>,
+}
+
+// 回执响应体
+#[derive(Serialize)]
+pub struct ReceiptsResponse {
+    pub success: bool,
+    pub message: String,
+    pub message_id: String,
+    #[serde(flatten)]
+    pub receipts: MessageReceipts,
+}
+
+// 查看群消息的送达和已读情况，需要是发送者或群成员
+pub async fn message_receipts_handler(
+    State(state): State<AppState>,
+    Path(message_id): Path<String>,
+    headers: http::HeaderMap,
+    Query(query): Query<ReceiptsQuery>,
+) -> Result<Json<ReceiptsResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let message = state.db_pool.active_message(&message_id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound("消息不存在".into()))?;
+    if message.message_type != "group" {
+        return Err(AppError::InvalidInput("只有群消息有成员回执".into()));
+    }
+    if message.sender_id != user_id {
+        policy::require(&state, &user_id, &[Check::GroupMember(&message.receiver_id)])
+            .map_err(|_| AppError::NotFound("消息不存在".into()))?;
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_RECEIPT_PAGE).clamp(1, MAX_RECEIPT_PAGE);
+    let receipts = state.db_pool.group_message_receipts(&message.id, &message.receiver_id, &message.sender_id, &user_id, limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(ReceiptsResponse {
+        success: true,
+        message: "获取消息回执成功".into(),
+        message_id: message.id,
+        receipts,
+    }))
+}
+
+/// 注册消息回执路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/messages/{message_id}/receipts", get(message_receipts_handler))
+}
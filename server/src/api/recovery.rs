@@ -0,0 +1,109 @@
+//! 处理器 panic 时的兜底：返回带请求ID的 500 错误响应，而不是直接断开连接
+//!
+//! 每个请求都有请求ID（沿用客户端的 X-Request-Id，否则生成新的），随响应头返回；
+//! panic 时把请求ID、请求行、panic 信息和调用栈打印到日志，并计入 /metrics 的 panic 次数
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json
+};
+use futures_util::FutureExt;
+use serde_json::json;
+
+/// 请求ID的请求头和响应头
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+// 客户端提供的请求ID超过这个长度时不采用
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+static PANICS: AtomicU64 = AtomicU64::new(0);
+static INSTALL_HOOK: Once = Once::new();
+
+thread_local! {
+    // panic 钩子在 panic 发生的线程上记录调用栈，catch_unwind 在同一线程上取出
+    static LAST_BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+// 在原有的 panic 钩子之前记录调用栈，原有钩子照常输出 panic 信息
+fn install_hook() {
+    INSTALL_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            LAST_BACKTRACE.with(|slot| *slot.borrow_mut() = Some(Backtrace::force_capture()));
+            previous(info);
+        }));
+    });
+}
+
+// 只接受可打印的 ASCII，避免日志注入
+fn request_id(request: &Request) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .filter(|id| id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_'))
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+// panic 负载通常是 &str 或 String
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("未知错误")
+}
+
+/// 捕获处理器 panic 的中间件，作用于全部路由
+pub async fn catch_panic(request: Request, next: Next) -> Response {
+    install_hook();
+    let id = request_id(&request);
+    let method = request.method().clone();
+    let uri = request.uri().path().to_string();
+
+    let mut response = match AssertUnwindSafe(next.run(request)).catch_unwind().await {
+        Ok(response) => response,
+        Err(payload) => {
+            PANICS.fetch_add(1, Ordering::Relaxed);
+            let backtrace = LAST_BACKTRACE.with(|slot| slot.borrow_mut().take());
+            println!(
+                "请求 {} {} {} 处理时 panic: {}\n{}",
+                id,
+                method,
+                uri,
+                panic_message(payload.as_ref()),
+                backtrace.map(|b| b.to_string()).unwrap_or_default()
+            );
+            let body = json!({
+                "success": false,
+                "code": "error.internal",
+                "message": "服务器内部错误，请稍后重试",
+                "request_id": id,
+            });
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(body)).into_response()
+        }
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+/// 以 OpenMetrics 文本格式输出处理器 panic 次数，不含结尾的 `# EOF`
+pub fn render(out: &mut String) {
+    let _ = writeln!(out, "# TYPE yueling_http_panics counter");
+    let _ = writeln!(out, "# HELP yueling_http_panics HTTP handler panics recovered as 500 responses.");
+    let _ = writeln!(out, "yueling_http_panics_total {}", PANICS.load(Ordering::Relaxed));
+}
@@ -0,0 +1,252 @@
+//! 二进制流中转：无法建立点对点连接的两个客户端经服务器转发大文件或屏幕共享的数据
+//!
+//! 发起方用 POST /relay 指定对方，得到中转ID；双方各自带会话令牌连接 GET /relay/{relay_id} 的 WebSocket，
+//! 都连上后一方发出的每一帧原样转给另一方。每个方向按配置限速，并且转发完一帧才读取下一帧，
+//! 接收方读得慢时发送方随之变慢。任一方断开时中转结束；中转状态保存在实例内存中，
+//! 多实例部署时双方需要连接到同一个实例
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade},
+        Path,
+        Query,
+        State
+    },
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router
+};
+use futures_util::{
+    stream::{SplitSink, SplitStream},
+    SinkExt,
+    StreamExt
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use uuid::Uuid;
+use crate::config::settings::RelaySettings;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+/// 单帧超过 max_chunk_bytes 时双方的关闭码（即标准的 1009 Message Too Big）
+pub const CLOSE_RELAY_CHUNK_TOO_LARGE: u16 = 1009;
+/// 对方没有在 join_timeout_secs 内连上时的关闭码
+pub const CLOSE_RELAY_TIMEOUT: u16 = 4005;
+/// 对方断开、中转结束时的关闭码
+pub const CLOSE_RELAY_PEER_LEFT: u16 = 4006;
+
+struct Relay {
+    owner: String,
+    peer: String,
+    created_at: Instant,
+    joined: Vec<String>,
+    // 先连上的一方在此等待对方的连接
+    waiting: Option<oneshot::Sender<WebSocket>>,
+}
+
+impl Relay {
+    fn involves(&self, user_id: &str) -> bool {
+        self.owner == user_id || self.peer == user_id
+    }
+}
+
+/// 本实例上的中转，克隆开销很小
+#[derive(Clone, Default)]
+pub struct RelayRegistry {
+    relays: Arc<Mutex<HashMap<String, Relay>>>,
+}
+
+// 创建中转请求体
+#[derive(Deserialize)]
+pub struct CreateRelayRequest {
+    pub peer_id: String,
+}
+
+// 创建中转响应体，客户端按 max_chunk_bytes 切分数据
+#[derive(Serialize)]
+pub struct CreateRelayResponse {
+    pub success: bool,
+    pub message: String,
+    pub relay_id: String,
+    pub expires_in: u64,                // 双方需要在该时长（秒）内连上
+    pub max_chunk_bytes: usize,
+    pub bytes_per_sec: u64,
+}
+
+// 浏览器的 WebSocket 不能设置请求头，会话令牌也可以放在 token 查询参数中
+#[derive(Deserialize)]
+pub struct JoinRelayQuery {
+    pub token: Option<String>,
+}
+
+fn close_frame(code: u16, reason: &'static str) -> CloseFrame {
+    CloseFrame { code, reason: reason.into() }
+}
+
+// 创建与 peer_id 之间的中转，需要登录且对方的隐私设置允许私聊
+pub async fn create_relay_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    Json(req): Json<CreateRelayRequest>,
+) -> Result<Json<CreateRelayResponse>, AppError> {
+    let settings = &state.settings.relay;
+    if !settings.enabled {
+        return Err(AppError::NotFound("本服务器未启用中转".into()));
+    }
+    let user_id = super::user::session_user(&state, &headers)?;
+    if req.peer_id == user_id {
+        return Err(AppError::InvalidInput("不能和自己建立中转".into()));
+    }
+    if !state.db_pool.user_exists_by_id(&req.peer_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("用户不存在".into()));
+    }
+    super::privacy::require_dm_allowed(&state, &user_id, &req.peer_id)?;
+
+    let timeout = Duration::from_secs(settings.join_timeout_secs);
+    let relay_id = Uuid::new_v4().to_string();
+    {
+        let mut relays = state.relays.relays.lock().unwrap();
+        // 顺便清理超时仍未配对的中转
+        relays.retain(|_, relay| relay.joined.len() == 2 || relay.created_at.elapsed() < timeout);
+        if relays.values().filter(|relay| relay.involves(&user_id)).count() >= settings.max_per_user {
+            return Err(AppError::RateLimited("同时进行的中转过多，请先结束其他中转".into()));
+        }
+        relays.insert(relay_id.clone(), Relay {
+            owner: user_id,
+            peer: req.peer_id,
+            created_at: Instant::now(),
+            joined: Vec::new(),
+            waiting: None,
+        });
+    }
+
+    Ok(Json(CreateRelayResponse {
+        success: true,
+        message: "中转已创建".into(),
+        relay_id,
+        expires_in: settings.join_timeout_secs,
+        max_chunk_bytes: settings.max_chunk_bytes,
+        bytes_per_sec: settings.bytes_per_sec,
+    }))
+}
+
+// 以中转的一方连接，每一方只能连接一次
+pub async fn join_relay_handler(
+    State(state): State<AppState>,
+    Path(relay_id): Path<String>,
+    Query(query): Query<JoinRelayQuery>,
+    headers: http::HeaderMap,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let token = super::user::bearer_token(&headers).or(query.token.as_deref())
+        .ok_or_else(|| AppError::InvalidCredentials("缺少会话令牌".into()))?;
+    let user_id = state.db_pool.authenticate_session(token, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::InvalidCredentials("会话令牌无效或已过期".into()))?;
+    let timeout = Duration::from_secs(state.settings.relay.join_timeout_secs);
+    {
+        let mut relays = state.relays.relays.lock().unwrap();
+        let relay = relays.get_mut(&relay_id)
+            .filter(|relay| relay.involves(&user_id) && relay.created_at.elapsed() < timeout)
+            .ok_or_else(|| AppError::NotFound("中转不存在或已经结束".into()))?;
+        if relay.joined.contains(&user_id) {
+            return Err(AppError::Conflict("你已经连接到该中转".into()));
+        }
+        relay.joined.push(user_id);
+    }
+
+    Ok(upgrade.on_upgrade(move |socket| run_relay(state, relay_id, socket)).into_response())
+}
+
+// 先连上的一方等待对方，后连上的一方把连接交给等待的一方，由其负责双向转发
+async fn run_relay(state: AppState, relay_id: String, socket: WebSocket) {
+    let timeout = Duration::from_secs(state.settings.relay.join_timeout_secs);
+    let waiting = {
+        let mut relays = state.relays.relays.lock().unwrap();
+        relays.get_mut(&relay_id).map(|relay| match relay.waiting.take() {
+            Some(waiting) => Err(waiting),
+            None => {
+                let (tx, rx) = oneshot::channel();
+                relay.waiting = Some(tx);
+                Ok((rx, timeout.saturating_sub(relay.created_at.elapsed())))
+            }
+        })
+    };
+    match waiting {
+        Some(Err(waiting)) => {
+            // 等待的一方已经超时离开
+            if let Err(mut socket) = waiting.send(socket) {
+                let _ = socket.send(Message::Close(Some(close_frame(CLOSE_RELAY_TIMEOUT, "relay_timeout")))).await;
+            }
+        }
+        Some(Ok((rx, remaining))) => {
+            match tokio::time::timeout(remaining, rx).await {
+                Ok(Ok(other)) => pump(&state.settings.relay, socket, other).await,
+                _ => {
+                    let mut socket = socket;
+                    let _ = socket.send(Message::Close(Some(close_frame(CLOSE_RELAY_TIMEOUT, "relay_timeout")))).await;
+                }
+            }
+            state.relays.relays.lock().unwrap().remove(&relay_id);
+        }
+        // 升级期间中转已被清理
+        None => {
+            let mut socket = socket;
+            let _ = socket.send(Message::Close(Some(close_frame(CLOSE_RELAY_TIMEOUT, "relay_timeout")))).await;
+        }
+    }
+}
+
+// 双向转发，任一方向结束时用同一个关闭帧关闭双方
+async fn pump(settings: &RelaySettings, a: WebSocket, b: WebSocket) {
+    let (mut a_tx, mut a_rx) = a.split();
+    let (mut b_tx, mut b_rx) = b.split();
+    let close = tokio::select! {
+        close = forward(settings, &mut a_rx, &mut b_tx) => close,
+        close = forward(settings, &mut b_rx, &mut a_tx) => close,
+    };
+    let _ = a_tx.send(Message::Close(Some(close.clone()))).await;
+    let _ = b_tx.send(Message::Close(Some(close))).await;
+}
+
+// 把 from 收到的帧转给 to，直到一方断开或帧过大，返回关闭双方用的关闭帧
+async fn forward(settings: &RelaySettings, from: &mut SplitStream<WebSocket>, to: &mut SplitSink<WebSocket, Message>) -> CloseFrame {
+    let bytes_per_sec = settings.bytes_per_sec.max(1) as f64;
+    // 限速：每帧按大小占用一段发送时间，前一帧的时间用完后才转发下一帧
+    let mut next_send_at = Instant::now();
+    while let Some(Ok(frame)) = from.next().await {
+        let len = match &frame {
+            Message::Binary(data) => data.len(),
+            Message::Text(text) => text.len(),
+            Message::Close(_) => break,
+            // ping/pong 由底层自动应答
+            _ => continue,
+        };
+        if len > settings.max_chunk_bytes {
+            return close_frame(CLOSE_RELAY_CHUNK_TOO_LARGE, "chunk_too_large");
+        }
+        let now = Instant::now();
+        if next_send_at > now {
+            tokio::time::sleep(next_send_at - now).await;
+        }
+        next_send_at = next_send_at.max(now) + Duration::from_secs_f64(len as f64 / bytes_per_sec);
+        if to.send(frame).await.is_err() {
+            break;
+        }
+    }
+    close_frame(CLOSE_RELAY_PEER_LEFT, "peer_left")
+}
+
+/// 注册中转路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/relay", post(create_relay_handler))
+        .route("/relay/{relay_id}", get(join_relay_handler))
+}
@@ -0,0 +1,217 @@
+//! 运行时设置：管理员不重启服务器即可修改日志级别、开关部分功能，并查看当前生效的配置
+//!
+//! 修改保存在数据库中，重启后仍然生效并优先于配置文件；多实例部署时其他实例在重启后才读取到新的值
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, RwLock};
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use crate::config::log_level::{self, Level, LEVELS};
+use crate::config::settings::Settings;
+use crate::error::AppError;
+
+// 共享应用状态
+use super::AppState;
+use super::admin::AdminAuth;
+use crate::core::datetime::unix_now;
+
+/// 关闭后拒绝新用户注册（含第三方登录时自动注册），已有用户不受影响
+pub const FLAG_REGISTRATION: &str = "registration";
+/// 关闭后拒绝客户端加密（`yle2e1:` 前缀）的消息，已保存的消息照常读取
+pub const FLAG_E2EE: &str = "e2ee";
+/// 关闭后不提供消息翻译，即使配置了翻译后端
+pub const FLAG_TRANSLATION: &str = "translation";
+
+/// 可以在运行时开关的功能，未修改过时都是开启的
+pub const FLAGS: &[&str] = &[FLAG_REGISTRATION, FLAG_E2EE, FLAG_TRANSLATION];
+
+/// 客户端加密的消息内容的前缀
+pub const E2EE_PREFIX: &str = "yle2e1:";
+
+// 数据库中保存日志级别和功能开关的键
+const KEY_LOG_LEVEL: &str = "log_level";
+const FLAG_KEY_PREFIX: &str = "flag.";
+
+// 配置中这些项的取值不返回给管理员：名称为 token、以 password / secret 结尾，或是密钥、带密码的地址
+const SECRET_KEYS: &[&str] = &["token", "master_key", "api_key", "redis_url"];
+const SECRET_SUFFIXES: &[&str] = &["password", "secret"];
+
+/// 运行时的日志级别和功能开关，克隆开销很小
+#[derive(Clone)]
+pub struct Runtime {
+    log_level: Arc<RwLock<Level>>,
+    flags: Arc<RwLock<BTreeMap<String, bool>>>,
+}
+
+impl Runtime {
+    /// 读取数据库中保存的修改，没有修改过的日志级别使用配置文件中的值，并设为当前的全局日志级别
+    pub(crate) fn load(db_pool: &crate::storage::DbPool, settings: &Settings) -> Self {
+        let saved = db_pool.runtime_settings().unwrap_or_else(|e| {
+            println!("读取运行时设置失败，使用配置文件中的值: {}", e);
+            HashMap::new()
+        });
+        let configured = Level::parse(&settings.server.log_level).unwrap_or_else(|| {
+            println!("日志级别 {} 无效，使用 info", settings.server.log_level);
+            Level::Info
+        });
+        let level = saved.get(KEY_LOG_LEVEL).and_then(|name| Level::parse(name)).unwrap_or(configured);
+        log_level::set(level);
+
+        let flags = FLAGS
+            .iter()
+            .map(|flag| {
+                let enabled = saved.get(&format!("{}{}", FLAG_KEY_PREFIX, flag)).is_none_or(|value| value == "on");
+                (flag.to_string(), enabled)
+            })
+            .collect();
+        Self {
+            log_level: Arc::new(RwLock::new(level)),
+            flags: Arc::new(RwLock::new(flags)),
+        }
+    }
+
+    /// 功能是否开启
+    pub fn enabled(&self, flag: &str) -> bool {
+        self.flags.read().unwrap().get(flag).copied().unwrap_or(true)
+    }
+
+    /// 当前的日志级别
+    pub fn log_level(&self) -> Level {
+        *self.log_level.read().unwrap()
+    }
+
+    /// 全部功能开关的当前状态
+    pub fn flags(&self) -> BTreeMap<String, bool> {
+        self.flags.read().unwrap().clone()
+    }
+}
+
+/// 新用户注册被管理员暂停时拒绝
+pub(crate) fn check_registration(state: &AppState) -> Result<(), AppError> {
+    if state.runtime.enabled(FLAG_REGISTRATION) {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("注册已暂停".into()))
+    }
+}
+
+/// 客户端加密被管理员关闭时拒绝加密的消息
+pub(crate) fn check_e2ee(state: &AppState, content: &str) -> Result<(), AppError> {
+    if content.starts_with(E2EE_PREFIX) && !state.runtime.enabled(FLAG_E2EE) {
+        Err(AppError::Forbidden("本服务器已关闭端到端加密消息".into()))
+    } else {
+        Ok(())
+    }
+}
+
+/// 当前生效的配置：日志级别取运行时的值，密钥、令牌、密码等非空的取值替换为 "***"
+pub fn effective_config(settings: &Settings, log_level: Level) -> Value {
+    let mut config = serde_json::to_value(settings).unwrap_or(Value::Null);
+    config["server"]["log_level"] = log_level.as_str().into();
+    redact(&mut config);
+    config
+}
+
+fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.contains(&key) || SECRET_SUFFIXES.iter().any(|suffix| key.ends_with(suffix))
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match value {
+                    Value::String(s) if is_secret(key) && !s.is_empty() => *s = "***".into(),
+                    _ => redact(value),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+// 修改运行时设置的请求体，未给出的项保持不变
+#[derive(Deserialize)]
+pub struct RuntimeRequest {
+    pub log_level: Option<String>,
+    pub flags: Option<BTreeMap<String, bool>>,
+}
+
+// 运行时设置响应体
+#[derive(Serialize)]
+pub struct RuntimeResponse {
+    pub success: bool,
+    pub message: String,
+    pub log_level: String,
+    pub flags: BTreeMap<String, bool>,
+    pub config: Value,
+}
+
+fn response(state: &AppState, message: &str) -> RuntimeResponse {
+    let level = state.runtime.log_level();
+    RuntimeResponse {
+        success: true,
+        message: message.into(),
+        log_level: level.as_str().into(),
+        flags: state.runtime.flags(),
+        config: effective_config(&state.settings, level),
+    }
+}
+
+// 查看日志级别、功能开关和当前生效的配置
+pub async fn get_runtime_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<RuntimeResponse>, AppError> {
+    Ok(Json(response(&state, "获取运行时设置成功")))
+}
+
+// 修改日志级别和功能开关：先校验全部取值，保存到数据库后立即生效
+pub async fn update_runtime_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<RuntimeRequest>,
+) -> Result<Json<RuntimeResponse>, AppError> {
+    let level = match req.log_level.as_deref() {
+        Some(name) => Some(Level::parse(name).ok_or_else(|| {
+            AppError::InvalidInput(format!("日志级别必须是 {} 之一", LEVELS.join("、")))
+        })?),
+        None => None,
+    };
+    let flags = req.flags.unwrap_or_default();
+    if let Some(name) = flags.keys().find(|name| !FLAGS.contains(&name.as_str())) {
+        return Err(AppError::InvalidInput(format!("未知的功能开关 {}", name)));
+    }
+
+    let mut saved = HashMap::new();
+    if let Some(level) = level {
+        saved.insert(KEY_LOG_LEVEL.to_string(), level.as_str().to_string());
+    }
+    for (name, enabled) in &flags {
+        saved.insert(format!("{}{}", FLAG_KEY_PREFIX, name), if *enabled { "on" } else { "off" }.to_string());
+    }
+    state.db_pool.set_runtime_settings(&saved, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    if let Some(level) = level {
+        *state.runtime.log_level.write().unwrap() = level;
+        log_level::set(level);
+    }
+    state.runtime.flags.write().unwrap().extend(flags);
+
+    Ok(Json(response(&state, "运行时设置已更新")))
+}
+
+/// 注册运行时设置路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/runtime", get(get_runtime_handler).put(update_runtime_handler))
+}
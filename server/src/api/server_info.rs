@@ -0,0 +1,104 @@
+//! 服务器信息：版本、协议版本和本实例开启的功能，客户端据此调整界面（如隐藏未开启的第三方登录按钮）
+//!
+//! 不需要登录，只返回客户端可以公开看到的配置
+
+use axum::{
+    extract::State,
+    response::Json,
+    routing::get,
+    Router
+};
+use serde::Serialize;
+use crate::error::AppError;
+use crate::storage::emoji::CustomEmoji;
+
+// 共享应用状态
+use super::AppState;
+
+/// 客户端协议版本，REST 或 WebSocket 的帧格式有不兼容的变化时加一
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// 客户端加密（`yle2e1:` 前缀）和服务器落库加密使用的加密套件
+pub const CIPHER_SUITES: &[&str] = &["aes-256-gcm-hmac-sha256"];
+
+// 本实例开启的功能
+#[derive(Serialize)]
+pub struct Features {
+    pub e2ee: bool,                     // 服务器原样保存和转发客户端加密的消息内容
+    pub encryption_at_rest: bool,       // 服务器按会话派生的密钥加密保存消息内容
+    pub attachments_max_bytes: u64,     // 单个附件的大小上限
+    pub federation: bool,
+    pub federation_server_name: Option<String>, // 远端用户地址 user@server_name 中的部分，未开启联邦时为空
+    pub grpc: bool,
+    pub oauth_providers: Vec<String>,   // 可用的第三方登录提供方，即 /oauth/{provider}/authorize 中的名称
+    pub device_verification: bool,      // 新设备登录需要邮箱验证码
+    pub translation: bool,              // 可以用 POST /messages/{id}/translate 翻译消息
+    pub relay: bool,                    // 可以用 POST /relay 经服务器中转二进制流
+}
+
+// 注册相关配置
+#[derive(Serialize)]
+pub struct Registration {
+    pub open: bool,                     // 默认工作区是否允许不带邀请码注册（注册未被暂停，工作区设置和 invite_only 配置都未关闭）
+    pub challenge: String,              // "off"、"pow" 或 "hcaptcha"
+    pub email_required: bool,
+    pub email_verification: bool,
+}
+
+// 服务器信息响应体
+#[derive(Serialize)]
+pub struct ServerInfoResponse {
+    pub success: bool,
+    pub message: String,
+    pub version: String,
+    pub protocol_version: u32,
+    pub features: Features,
+    pub cipher_suites: Vec<String>,
+    pub registration: Registration,
+    pub maintenance: bool,              // 是否处于维护模式
+    pub custom_emoji: Vec<CustomEmoji>, // 默认工作区的自定义表情，其他工作区用 GET /emoji 查询
+}
+
+// 服务器信息处理器
+pub async fn server_info_handler(State(state): State<AppState>) -> Result<Json<ServerInfoResponse>, AppError> {
+    let settings = &state.settings;
+    let default_workspace = super::workspace::resolve_workspace(&state, "")?;
+    let federation = settings.federation.enabled;
+
+    Ok(Json(ServerInfoResponse {
+        success: true,
+        message: "获取服务器信息成功".into(),
+        version: env!("CARGO_PKG_VERSION").into(),
+        protocol_version: PROTOCOL_VERSION,
+        features: Features {
+            e2ee: state.runtime.enabled(super::runtime::FLAG_E2EE),
+            encryption_at_rest: settings.security.encrypt_messages,
+            attachments_max_bytes: settings.attachments.max_bytes,
+            federation,
+            federation_server_name: federation.then(|| settings.federation.server_name.clone()),
+            grpc: settings.grpc.port != 0,
+            oauth_providers: settings.oauth.providers.keys().cloned().collect(),
+            device_verification: settings.devices.verify_new_devices && state.mailer.is_some(),
+            translation: state.translator.is_some() && state.runtime.enabled(super::runtime::FLAG_TRANSLATION),
+            relay: settings.relay.enabled,
+        },
+        cipher_suites: CIPHER_SUITES.iter().map(|suite| suite.to_string()).collect(),
+        registration: Registration {
+            open: state.runtime.enabled(super::runtime::FLAG_REGISTRATION)
+                && !default_workspace.invite_only
+                && !super::workspace::closed_by_config(&state, &default_workspace),
+            challenge: settings.registration.challenge.clone(),
+            email_required: settings.registration.email_required,
+            email_verification: settings.registration.email_verification,
+        },
+        maintenance: state.maintenance.status().is_some(),
+        custom_emoji: state.db_pool.custom_emoji(&default_workspace.id)
+            .map_err(|e| AppError::Database(e.to_string()))?,
+    }))
+}
+
+/// 注册服务器信息路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/server-info", get(server_info_handler))
+}
@@ -0,0 +1,97 @@
+//! 慢请求日志：处理时间超过 server.slow_request_ms 的 HTTP 请求打印到日志（warn 级别），并按路由计入 /metrics；
+//! 日志级别为 debug 时记录每个请求
+//!
+//! 耗时从收到请求到处理器返回响应头为止，不含流式响应体（附件下载等）的传输时间；
+//! 查询参数中的令牌、密码等值被隐去
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response
+};
+
+// 共享应用状态
+use super::AppState;
+use super::recovery::REQUEST_ID_HEADER;
+use crate::config::log_level::{self, Level};
+
+// 名称中含有这些词的查询参数隐去取值
+const SENSITIVE_PARAMS: &[&str] = &["token", "password", "secret", "key", "code", "signature"];
+
+// 按路由模板（如 /messages/{id}）统计，不会因路径参数产生无限多的序列
+static SLOW_REQUESTS: LazyLock<Mutex<BTreeMap<String, u64>>> = LazyLock::new(Default::default);
+
+/// 隐去查询字符串中敏感参数的取值
+pub fn scrub_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if SENSITIVE_PARAMS.iter().any(|word| name.to_lowercase().contains(word)) => {
+                format!("{}=***", name)
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// 记录慢请求的中间件，作用于全部路由
+pub async fn log_slow_requests(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let threshold = state.settings.server.slow_request_ms;
+    let debug = log_level::enabled(Level::Debug);
+    if threshold == 0 && !debug {
+        return next.run(request).await;
+    }
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".into());
+    let path = match request.uri().query() {
+        Some(query) => format!("{}?{}", request.uri().path(), scrub_query(query)),
+        None => request.uri().path().to_string(),
+    };
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+    let elapsed = started.elapsed();
+    let slow = threshold != 0 && elapsed.as_millis() >= threshold as u128;
+    if slow {
+        *SLOW_REQUESTS.lock().unwrap().entry(route).or_default() += 1;
+    }
+    if !(debug || slow && log_level::enabled(Level::Warn)) {
+        return response;
+    }
+
+    let request_id = response
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-");
+    println!(
+        "{} {} 毫秒: {} {} -> {}（请求ID {}）",
+        if slow { "慢请求" } else { "请求" },
+        elapsed.as_millis(),
+        method,
+        path,
+        response.status().as_u16(),
+        request_id
+    );
+    response
+}
+
+/// 以 OpenMetrics 文本格式输出各路由的慢请求次数，不含结尾的 `# EOF`
+pub fn render(out: &mut String) {
+    let _ = writeln!(out, "# TYPE yueling_slow_requests counter");
+    let _ = writeln!(out, "# HELP yueling_slow_requests HTTP requests slower than server.slow_request_ms.");
+    for (route, count) in SLOW_REQUESTS.lock().unwrap().iter() {
+        let route = route.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = writeln!(out, "yueling_slow_requests_total{{route=\"{}\"}} {}", route, count);
+    }
+}
@@ -0,0 +1,102 @@
+//! 消息翻译：会话参与者把一条消息翻译为指定语言，方便多语言的群聊互相阅读
+//!
+//! 译文按 (消息, 语言) 缓存，同一条消息第二次翻译为同一语言时不再调用翻译后端
+
+use axum::{
+    extract::{Path, Query, State},
+    response::Json,
+    routing::post,
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::storage::system_messages::SYSTEM_MESSAGE_TYPE;
+use crate::translate::is_valid_lang;
+
+// 共享应用状态
+use super::AppState;
+use crate::core::datetime::unix_now;
+
+// 翻译的查询参数，lang 为目标语言（如 en、zh-CN）
+#[derive(Deserialize)]
+pub struct TranslateQuery {
+    pub lang: String,
+}
+
+// 翻译响应体
+#[derive(Serialize)]
+pub struct TranslateResponse {
+    pub success: bool,
+    pub message: String,
+    pub message_id: String,
+    pub lang: String,
+    pub translated: String,
+    pub cached: bool,   // 是否来自缓存
+}
+
+// 用户能否看到这条消息：发送者、私聊的接收者或群成员
+fn can_read(state: &AppState, message: &crate::storage::Message, user_id: &str) -> Result<bool, AppError> {
+    if message.sender_id == user_id {
+        return Ok(true);
+    }
+    if message.message_type == "private" {
+        return Ok(message.receiver_id == user_id);
+    }
+    state.db_pool.is_group_member(&message.receiver_id, user_id)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+// 把消息翻译为 lang，优先返回缓存的译文
+pub async fn translate_message_handler(
+    State(state): State<AppState>,
+    Path(message_id): Path<String>,
+    headers: http::HeaderMap,
+    Query(query): Query<TranslateQuery>,
+) -> Result<Json<TranslateResponse>, AppError> {
+    let user_id = super::user::session_user(&state, &headers)?;
+    let translator = state.translator.clone()
+        .filter(|_| state.runtime.enabled(super::runtime::FLAG_TRANSLATION))
+        .ok_or_else(|| AppError::NotFound("本服务器未启用消息翻译".into()))?;
+    if !is_valid_lang(&query.lang) {
+        return Err(AppError::InvalidInput(format!("语言代码无效: {}", query.lang)));
+    }
+    let message = state.db_pool.active_message(&message_id)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .filter(|message| can_read(&state, message, &user_id).unwrap_or(false))
+        .ok_or_else(|| AppError::NotFound("消息不存在".into()))?;
+    if message.message_type == SYSTEM_MESSAGE_TYPE {
+        return Err(AppError::InvalidInput("系统消息不能翻译".into()));
+    }
+    let max_chars = state.settings.translation.max_chars;
+    if message.content.chars().count() > max_chars {
+        return Err(AppError::InvalidInput(format!("超过 {} 个字符的消息不能翻译", max_chars)));
+    }
+
+    let cached = state.db_pool.cached_translation(&message, &query.lang)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let (translated, cached) = match cached {
+        Some(translated) => (translated, true),
+        None => {
+            let translated = translator.translate(&message.content, &query.lang).await
+                .map_err(|e| AppError::Internal(format!("翻译失败: {}", e)))?;
+            state.db_pool.store_translation(&message, &query.lang, &translated, unix_now())
+                .map_err(|e| AppError::Database(e.to_string()))?;
+            (translated, false)
+        }
+    };
+
+    Ok(Json(TranslateResponse {
+        success: true,
+        message: "翻译成功".into(),
+        message_id,
+        lang: query.lang,
+        translated,
+        cached,
+    }))
+}
+
+/// 注册消息翻译路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/messages/{message_id}/translate", post(translate_message_handler))
+}
@@ -13,31 +13,47 @@ use axum::{
         get
     }, 
     Router, 
-    routing::put
+    routing::{put, delete}
 };
 use serde::{
     Deserialize, 
     Serialize
 };
+use serde_json::json;
 use crate::error::AppError;
-use bcrypt::{
-    verify
-};
+use crate::storage::webhooks::EVENT_USER_REGISTERED;
+use crate::storage::user_settings;
+use crate::storage::system_messages::SystemEvent;
+use crate::storage::User;
+use std::collections::HashMap;
 use std::fs;
 use std::path::Path as FilePath;
 use uuid::Uuid;
 use http::{
-    header::CONTENT_TYPE
+    header::CONTENT_TYPE,
+    StatusCode
 };
 use mime_guess::from_path;
 // 共享应用状态
 use super::AppState;
+use super::devices::DeviceLogin;
+use super::admin::AdminAuth;
+use super::policy::{self, authorize, Check};
+use crate::core::datetime::unix_now;
 
 // 注册请求体（前端提交数据）
 #[derive(Deserialize)]
 pub struct RegisterRequest {
     pub username: String,
     pub password: String, // 明文密码（后端哈希存储）
+    #[serde(default)]
+    pub workspace: Option<String>, // 工作区 slug，默认工作区可省略
+    #[serde(default)]
+    pub invite_code: Option<String>, // 仅限受邀注册的工作区需要
+    #[serde(default)]
+    pub email: String, // 可选，开启 email_required 时必填
+    #[serde(flatten)]
+    pub proof: super::challenge::ChallengeProof, // 开启注册防刷时需要的凭据
 }
 
 // 注册响应体（返回给前端）
@@ -53,6 +69,8 @@ pub struct RegisterResponse {
 pub struct LoginRequest {
     pub username: String,
     pub password: String, // 明文密码（后端验证）
+    #[serde(flatten)]
+    pub device: super::devices::DeviceInfo, // 登录设备信息，旧客户端可省略
 }
 
 // 登录响应体（返回给前端）
@@ -62,6 +80,9 @@ pub struct LoginResponse {
     pub message: String,
     pub user_id: Option<String>, // 成功时返回用户ID
     pub username: Option<String>, // 成功时返回用户名
+    pub token: Option<String>, // 会话令牌，访问需要登录的接口（如 /graphql）时携带
+    pub device_id: Option<String>, // 登录设备 ID，下次登录时上报
+    pub device_verification_required: bool, // 新设备需要验证时为 true，带上 device_id 和邮件中的验证码重新登录
 }
 
 // 用户存在检查
@@ -107,20 +128,151 @@ pub struct UserInfoResponse {
     pub user: Option<serde_json::Value>,
 }
 
+// 在线状态响应体
+#[derive(Serialize)]
+pub struct PresenceResponse {
+    pub success: bool,
+    pub message: String,
+    pub online: bool,
+    pub last_seen_at: Option<i64>,
+    pub dnd: bool,               // 当前是否处于免打扰时段
+    pub dnd_until: Option<i64>,  // 免打扰时段结束的时间戳
+}
+
+// 用户设置响应体
+#[derive(Serialize)]
+pub struct UserSettingsResponse {
+    pub success: bool,
+    pub message: String,
+    pub settings: HashMap<String, String>,
+}
+
+// 在指定工作区（slug，空字符串为默认工作区）注册用户，
+// 并通知订阅了 user.registered 的 webhook（REST 和 gRPC 共用）
+pub(crate) async fn register_user(
+    state: &AppState,
+    username: &str,
+    email: &str,
+    password: &str,
+    workspace: &str,
+    invite_code: Option<&str>,
+) -> Result<User, AppError> {
+    super::runtime::check_registration(state)?;
+    super::username::check_username(&state.settings.username, username)?;
+    if email.is_empty() {
+        if state.settings.registration.email_required {
+            return Err(AppError::InvalidInput("请填写邮箱".into()));
+        }
+    } else {
+        super::account::check_email(email)?;
+    }
+    let workspace = super::workspace::resolve_workspace(state, workspace)?;
+    if workspace.invite_only && invite_code.is_none() {
+        return Err(AppError::Forbidden("该工作区只允许持邀请码注册".into()));
+    }
+    if super::workspace::closed_by_config(state, &workspace) && invite_code.is_none() {
+        return Err(AppError::Forbidden("注册需要邀请码".into()));
+    }
+
+    // 在阻塞线程池中计算密码哈希，再调用存储层注册用户
+    let password_hash = state.auth_crypto.hash(password).await
+        .map_err(|_| AppError::Internal("密码哈希失败".into()))?;
+    let user = state.db_pool.register_user_in_workspace(username, email, &password_hash, &workspace.id, invite_code, unix_now())
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(_, Some(msg)) if msg.contains("用户名已存在") || msg.contains("邮箱已被使用") =>
+                AppError::UserExists(msg),
+            rusqlite::Error::SqliteFailure(_, Some(msg)) if msg.contains("邀请码") =>
+                AppError::Forbidden(msg),
+            _ => AppError::Database(e.to_string()),
+        })?;
+
+    super::webhook::emit(state, EVENT_USER_REGISTERED, None, json!({
+        "user_id": user.id,
+        "username": user.username,
+        "created_at": user.created_at,
+    }));
+    if !email.is_empty() {
+        super::account::request_email_verification(state, &user.id);
+    }
+    Ok(user)
+}
+
+// 校验用户名和密码，返回 (用户ID, 用户名)（REST 和 gRPC 共用）
+pub(crate) async fn authenticate(state: &AppState, username: &str, password: &str) -> Result<(String, String), AppError> {
+    // 调用存储层获取用户（锁在校验密码前释放）
+    let user = state.db_pool.user_credentials(username)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::InvalidCredentials("用户名或密码错误".into()))?;
+
+    // 在阻塞线程池中验证密码
+    let (id, username, password_hash) = user;
+    let verified = state.auth_crypto.verify(password, &password_hash).await
+        .map_err(|_| AppError::Internal("密码验证失败".into()))?;
+    if !verified {
+        return Err(AppError::InvalidCredentials("用户名或密码错误".into()));
+    }
+    Ok((id, username))
+}
+
+// 为登录成功的用户签发会话令牌（REST 和 gRPC 共用）
+pub(crate) fn create_session(state: &AppState, user_id: &str, device_id: Option<&str>) -> Result<String, AppError> {
+    super::maintenance::check(state, user_id)?;
+    state.db_pool.create_session(user_id, device_id, state.settings.security.session_ttl_secs, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+// 读取 Authorization: Bearer 请求头
+pub(crate) fn bearer_token(headers: &http::HeaderMap) -> Option<&str> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+}
+
+// 按 Authorization: Bearer 会话令牌识别当前用户，并计入该用户的请求额度
+pub(crate) fn session_user(state: &AppState, headers: &http::HeaderMap) -> Result<String, AppError> {
+    let token = bearer_token(headers)
+        .ok_or_else(|| AppError::InvalidCredentials("缺少会话令牌".into()))?;
+    let user_id = state.db_pool.authenticate_session(token, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::InvalidCredentials("会话令牌无效或已过期".into()))?;
+    super::rate_limit::check_request(state, &user_id)?;
+    Ok(user_id)
+}
+
+// 注销处理器：吊销请求携带的会话令牌
+pub async fn logout_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let token = bearer_token(&headers)
+        .ok_or_else(|| AppError::InvalidCredentials("缺少会话令牌".into()))?;
+    if !state.db_pool.revoke_session(token).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::InvalidCredentials("会话令牌无效或已过期".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "已退出登录".into(),
+    }))
+}
+
 // 注册处理器（核心API逻辑）
 pub async fn register_handler(
     State(state): State<AppState>, // 注入共享状态
     Json(req): Json<RegisterRequest>, // 解析JSON请求体
 ) -> Result<Json<RegisterResponse>, AppError> {
+    // 开启注册防刷时先完成人机验证
+    super::challenge::verify(&state, &req.proof).await?;
 
-    
-    // 调用存储层注册用户（使用原始密码）
-    let user = state.db_pool.register_user(&req.username, "", &req.password)
-        .map_err(|e| match e {
-            rusqlite::Error::SqliteFailure(_, Some(msg)) if msg.contains("用户名已存在") =>
-                AppError::UserExists(msg),
-            _ => AppError::Database(e.to_string()),
-        })?;
+    let user = register_user(
+        &state,
+        &req.username,
+        &req.email,
+        &req.password,
+        req.workspace.as_deref().unwrap_or_default(),
+        req.invite_code.as_deref(),
+    ).await?;
 
     // 返回成功响应
     Ok(Json(RegisterResponse {
@@ -131,44 +283,54 @@ pub async fn register_handler(
 }
 
 // 登录处理器（核心API逻辑）
+//
+// 新设备需要验证时返回 202 和 device_id，不签发会话令牌
 pub async fn login_handler(
     State(state): State<AppState>, // 注入共享状态
     Json(req): Json<LoginRequest>, // 解析JSON请求体
-) -> Result<Json<LoginResponse>, AppError> {
-    // 使用私有算法和公有算法加密密码（与注册时相同）
-
-    
-    // 调用存储层获取用户
-    let conn = state.db_pool.0.lock().unwrap();
-    let user = conn.query_row(
-        "SELECT id, username, password_hash FROM users WHERE username = ?",
-        [&req.username],
-        |row| {
-            Ok((
-                row.get::<_, String>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, String>(2)?,
-            ))
-        },
-    ).map_err(|e| match e {
-        rusqlite::Error::QueryReturnedNoRows =>
-            AppError::InvalidCredentials("用户名或密码错误".into()),
-        _ => AppError::Database(e.to_string()),
-    })?;
+) -> Result<(StatusCode, Json<LoginResponse>), AppError> {
+    let response = login_user(&state, &req.username, &req.password, &req.device).await?;
+    let status = if response.device_verification_required { StatusCode::ACCEPTED } else { StatusCode::OK };
+    Ok((status, Json(response)))
+}
 
-    // 验证密码（使用解密后的原始密码）
-    let (id, username, password_hash) = user;
-    if !verify(&req.password, &password_hash).map_err(|_| AppError::Internal("密码验证失败".into()))? {
-        return Err(AppError::InvalidCredentials("用户名或密码错误".into()));
-    }
+// 用户名密码登录（REST 和 gRPC 共用）：校验密码、维护模式和登录设备，新设备需要验证时不签发会话
+pub(crate) async fn login_user(
+    state: &AppState,
+    username: &str,
+    password: &str,
+    device: &super::devices::DeviceInfo,
+) -> Result<LoginResponse, AppError> {
+    let (id, username) = authenticate(state, username, password).await?;
+    // 先于新设备验证检查，维护期间不发送验证码
+    super::maintenance::check(state, &id)?;
+    let device_id = match super::devices::check_device(state, &id, device).await? {
+        DeviceLogin::Untracked => None,
+        DeviceLogin::Ready(device_id) => Some(device_id),
+        DeviceLogin::Pending(device_id) => {
+            return Ok(LoginResponse {
+                success: false,
+                message: "新设备需要验证，验证码已发送到邮箱".into(),
+                user_id: Some(id),
+                username: Some(username),
+                token: None,
+                device_id: Some(device_id),
+                device_verification_required: true,
+            });
+        }
+    };
+    let token = create_session(state, &id, device_id.as_deref())?;
 
     // 返回成功响应
-    Ok(Json(LoginResponse {
+    Ok(LoginResponse {
         success: true,
         message: "登录成功".into(),
         user_id: Some(id),
         username: Some(username),
-    }))
+        token: Some(token),
+        device_id,
+        device_verification_required: false,
+    })
 }
 
 // 检查用户是否存在的处理器
@@ -194,10 +356,13 @@ pub async fn get_user_info_handler(
     // 获取用户信息
     let user = state.db_pool.get_user_by_id(&user_id).map_err(|e| AppError::Database(e.to_string()))?;
     
+    let display_name = state.db_pool.display_name(&user_id).map_err(|e| AppError::Database(e.to_string()))?;
+    
     // 转换为JSON值，不包含敏感信息
     let user_json = serde_json::json!({
         "id": user.id,
         "username": user.username,
+        "display_name": display_name,
         "avatar_url": user.avatar_url,
         "created_at": user.created_at,
     });
@@ -213,8 +378,12 @@ pub async fn get_user_info_handler(
 pub async fn upload_avatar_handler(
     State(state): State<AppState>,
     Path(user_id): Path<String>,
+    headers: http::HeaderMap,
     mut multipart: Multipart,
 ) -> Result<Json<AvatarUploadResponse>, AppError> {
+    // 只能修改自己的头像
+    authorize(&state, &headers, &[Check::Is(&user_id)])?;
+
     // 创建上传目录
     let upload_dir = FilePath::new("./uploads/avatars");
     if !upload_dir.exists() {
@@ -279,15 +448,46 @@ pub async fn get_avatar_handler(
     ))
 }
 
-// 更新用户信息处理器
+// 更新用户信息处理器（只能修改自己的用户名和邮箱）
 pub async fn update_user_info_handler(
     State(state): State<AppState>,
     Path(user_id): Path<String>,
+    headers: http::HeaderMap,
     Json(req): Json<UpdateUserRequest>,
 ) -> Result<Json<SuccessResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&user_id)])?;
+    super::username::check_username(&state.settings.username, &req.username)?;
+    super::account::check_email(&req.email)?;
+    let previous = state.db_pool.get_user_by_id(&user_id).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("用户不存在".into()),
+        _ => AppError::Database(e.to_string()),
+    })?;
     // 更新用户信息
     state.db_pool.update_user_info(&user_id, &req.username, &req.email)
-        .map_err(|e| AppError::Database(e.to_string()))?;
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(_, Some(msg)) if msg.contains("用户名已存在") || msg.contains("邮箱已被使用") =>
+                AppError::UserExists(msg),
+            _ => AppError::Database(e.to_string()),
+        })?;
+    // 修改邮箱后需要重新验证
+    if previous.email != req.email {
+        super::account::request_email_verification(&state, &user_id);
+    }
+    // 改名后在所在的每个群里留下系统消息
+    if previous.username != req.username {
+        let groups = state.db_pool.groups_of_user(&user_id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        for (group_id, workspace_id) in groups {
+            super::message::post_system_message(&state, &workspace_id, &group_id, SystemEvent::NameChanged {
+                user_id: user_id.clone(),
+                old_name: previous.username.clone(),
+                new_name: req.username.clone(),
+            })?;
+        }
+        let display_name = state.db_pool.display_name(&user_id)
+            .map_err(|e| AppError::Database(e.to_string()))?;
+        super::profile::broadcast_profile_updated(&state, &user_id, &req.username, display_name.as_deref(), unix_now())?;
+    }
     
     Ok(Json(SuccessResponse {
         success: true,
@@ -295,6 +495,117 @@ pub async fn update_user_info_handler(
     }))
 }
 
+// 删除用户处理器（软删除，撤销窗口内可恢复，超过清理期限后彻底清除）；用户本人或管理员可以删除
+pub async fn delete_user_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<SuccessResponse>, AppError> {
+    if !policy::is_server_admin(&state, &headers) {
+        authorize(&state, &headers, &[Check::Is(&user_id)])?;
+    }
+    let now = unix_now();
+    let deleted = state.db_pool.delete_user(&user_id, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !deleted {
+        return Err(AppError::NotFound("用户不存在".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "用户已删除".into(),
+    }))
+}
+
+// 恢复用户处理器（已删除的用户不能登录，由管理员恢复）
+pub async fn restore_user_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+) -> Result<Json<SuccessResponse>, AppError> {
+    let now = unix_now();
+    let grace_secs = state.settings.retention.restore_grace_secs as i64;
+    let restored = state.db_pool.restore_user(&user_id, now, grace_secs)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !restored {
+        return Err(AppError::NotFound("用户不存在或已超过可恢复时间".into()));
+    }
+
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "用户已恢复".into(),
+    }))
+}
+
+// 获取用户设置处理器（只能查看自己的设置；未修改过的项不返回，客户端使用默认值）
+pub async fn get_user_settings_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<UserSettingsResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&user_id)])?;
+    let settings = state.db_pool.get_user_settings(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(UserSettingsResponse {
+        success: true,
+        message: "获取用户设置成功".into(),
+        settings,
+    }))
+}
+
+// 在线状态处理器：是否在线、最后在线时间，以及是否处于免打扰时段（其他人据此决定是否打扰）
+pub async fn get_presence_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<PresenceResponse>, AppError> {
+    let last_seen_at = state.db_pool.last_seen_at(&user_id).map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("用户不存在".into()),
+        _ => AppError::Database(e.to_string()),
+    })?;
+    let now = unix_now();
+    let dnd_until = state.db_pool.dnd_until(&user_id, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    // 隐私设置不允许查看者看到时，按离线且没有最后在线时间返回
+    let viewer = session_user(&state, &headers).ok();
+    let visible = super::privacy::last_seen_visible(&state, &user_id, viewer.as_deref())?;
+
+    Ok(Json(PresenceResponse {
+        success: true,
+        message: "获取在线状态成功".into(),
+        online: visible && state.is_online(&user_id),
+        last_seen_at: last_seen_at.filter(|_| visible),
+        dnd: dnd_until.is_some(),
+        dnd_until,
+    }))
+}
+
+// 更新用户设置处理器（只能修改自己的设置），请求体为 {"设置项": "取值"}，只更新提交的项
+pub async fn update_user_settings_handler(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    headers: http::HeaderMap,
+    Json(req): Json<HashMap<String, String>>,
+) -> Result<Json<UserSettingsResponse>, AppError> {
+    authorize(&state, &headers, &[Check::Is(&user_id)])?;
+    for (key, value) in &req {
+        user_settings::validate_setting(key, value).map_err(AppError::InvalidInput)?;
+    }
+
+    let now = unix_now();
+    state.db_pool.set_user_settings(&user_id, &req, now)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let settings = state.db_pool.get_user_settings(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(UserSettingsResponse {
+        success: true,
+        message: "用户设置已更新".into(),
+        settings,
+    }))
+}
+
 // 健康检查响应体
 #[derive(Serialize)]
 pub struct HealthResponse {
@@ -312,15 +623,44 @@ pub async fn health_check_handler() -> Result<Json<HealthResponse>, AppError> {
     }))
 }
 
+// 服务器时间响应体
+#[derive(Serialize)]
+pub struct ServerTimeResponse {
+    pub success: bool,
+    pub message: String,
+    pub server_time: i64,    // 秒级时间戳，与消息中的时间一致
+    pub server_time_ms: i64, // 毫秒级时间戳，用于估算本机时钟偏差
+}
+
+// 服务器时间处理器：客户端记下请求发出和收到响应的本机时间 t0、t1，
+// 时钟偏差约为 server_time_ms - (t0 + t1) / 2
+pub async fn server_time_handler() -> Result<Json<ServerTimeResponse>, AppError> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap();
+    Ok(Json(ServerTimeResponse {
+        success: true,
+        message: "获取服务器时间成功".into(),
+        server_time: now.as_secs() as i64,
+        server_time_ms: now.as_millis() as i64,
+    }))
+}
+
 /// 注册用户相关路由
 pub fn register_routes() -> Router<AppState> {
     Router::new()
         .route("/register", post(register_handler))
         .route("/login", post(login_handler))
+        .route("/logout", post(logout_handler))
         .route("/health", get(health_check_handler))
+        .route("/time", get(server_time_handler))
         .route("/user/exists", post(user_exists_handler))
         .route("/user/{user_id}", get(get_user_info_handler))
         .route("/user/{user_id}", put(update_user_info_handler))
+        .route("/user/{user_id}", delete(delete_user_handler))
+        .route("/user/{user_id}/restore", post(restore_user_handler))
         .route("/user/{user_id}/avatar", post(upload_avatar_handler))
+        .route("/user/{user_id}/settings", get(get_user_settings_handler).put(update_user_settings_handler))
+        .route("/user/{user_id}/presence", get(get_presence_handler))
         .route("/uploads/avatars/{filename}", get(get_avatar_handler))
 }
\ No newline at end of file
@@ -0,0 +1,74 @@
+//! 用户名规则：长度、允许的字符范围、混用文字检测、保留名和敏感词，注册和改名时校验
+
+use unicode_security::MixedScript;
+use crate::config::settings::UsernameSettings;
+use crate::error::AppError;
+use crate::storage::usernames::normalize_username;
+
+// 解析 "0030-0039" 或 "005F" 形式的码位范围
+fn parse_range(range: &str) -> Option<(u32, u32)> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    let start = u32::from_str_radix(start.trim().trim_start_matches("U+"), 16).ok()?;
+    let end = u32::from_str_radix(end.trim().trim_start_matches("U+"), 16).ok()?;
+    (start <= end).then_some((start, end))
+}
+
+fn char_allowed(c: char, ranges: &[(u32, u32)]) -> bool {
+    if ranges.is_empty() {
+        return c.is_alphanumeric() || matches!(c, '_' | '-' | '.');
+    }
+    ranges.iter().any(|(start, end)| (*start..=*end).contains(&(c as u32)))
+}
+
+/// 按配置校验用户名，不合规时返回 InvalidInput
+pub(crate) fn check_username(settings: &UsernameSettings, username: &str) -> Result<(), AppError> {
+    // user@host 形式保留给联邦中的远端用户
+    if username.contains('@') {
+        return Err(AppError::InvalidInput("用户名不能包含 @".into()));
+    }
+    let length = username.chars().count();
+    if length < settings.min_length || length > settings.max_length {
+        return Err(AppError::InvalidInput(format!(
+            "用户名长度必须在 {} 到 {} 个字符之间", settings.min_length, settings.max_length
+        )));
+    }
+
+    let ranges = settings.allowed_ranges
+        .iter()
+        .map(|range| parse_range(range).ok_or_else(|| AppError::Internal(format!("无效的用户名字符范围: {}", range))))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(c) = username.chars().find(|c| !char_allowed(*c, &ranges)) {
+        return Err(AppError::InvalidInput(format!("用户名包含不允许的字符“{}”", c)));
+    }
+    if !settings.allow_mixed_scripts && !username.is_single_script() {
+        return Err(AppError::InvalidInput("用户名不能混用多种文字".into()));
+    }
+
+    // 保留名和敏感词都按归一化后的形式比较，防止用大小写或形近字绕过
+    let normalized = normalize_username(username);
+    if settings.reserved.iter().any(|name| normalize_username(name) == normalized) {
+        return Err(AppError::InvalidInput("该用户名已被保留".into()));
+    }
+    if settings.blocked_words.iter().any(|word| !word.is_empty() && normalized.contains(&normalize_username(word))) {
+        return Err(AppError::InvalidInput("用户名包含不允许的词语".into()));
+    }
+    Ok(())
+}
+
+/// 校验显示名称（昵称）：长度上限、控制字符，以及与用户名相同的保留名和敏感词规则
+pub(crate) fn check_display_name(settings: &UsernameSettings, display_name: &str) -> Result<(), AppError> {
+    if display_name.chars().count() > settings.display_name_max_length {
+        return Err(AppError::InvalidInput(format!("显示名称不能超过 {} 个字符", settings.display_name_max_length)));
+    }
+    if display_name.chars().any(char::is_control) {
+        return Err(AppError::InvalidInput("显示名称不能包含控制字符".into()));
+    }
+    let normalized = normalize_username(display_name);
+    if settings.reserved.iter().any(|name| normalize_username(name) == normalized) {
+        return Err(AppError::InvalidInput("该显示名称已被保留".into()));
+    }
+    if settings.blocked_words.iter().any(|word| !word.is_empty() && normalized.contains(&normalize_username(word))) {
+        return Err(AppError::InvalidInput("显示名称包含不允许的词语".into()));
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    response::Json,
+    routing::{delete, get},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::storage::webhooks::{Webhook, WebhookDelivery, WEBHOOK_EVENTS};
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use crate::core::datetime::unix_now;
+
+// 投递记录默认返回条数
+const DEFAULT_DELIVERY_LIMIT: i64 = 50;
+
+/// 记录一个 webhook 事件，由后台任务异步投递
+///
+/// 写入失败只打印日志，不影响触发事件的业务请求
+pub fn emit(state: &AppState, event: &str, channel_id: Option<&str>, data: serde_json::Value) {
+    if let Err(e) = state.db_pool.enqueue_webhook_event(event, channel_id, &data, unix_now()) {
+        println!("记录 webhook 事件 {} 失败: {}", event, e);
+    }
+}
+
+// 注册 webhook 请求体
+#[derive(Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub event: String,
+    pub channel_id: Option<String>, // 仅对 message.sent 有意义：只推送该会话（用户或群ID）的消息
+}
+
+// 注册 webhook 响应体（签名密钥只返回这一次）
+#[derive(Serialize)]
+pub struct CreateWebhookResponse {
+    pub success: bool,
+    pub message: String,
+    pub webhook: Webhook,
+    pub secret: String,
+}
+
+// webhook 列表响应体
+#[derive(Serialize)]
+pub struct WebhooksResponse {
+    pub success: bool,
+    pub message: String,
+    pub webhooks: Vec<Webhook>,
+}
+
+// 投递记录查询参数
+#[derive(Deserialize)]
+pub struct DeliveriesQuery {
+    pub limit: Option<i64>,
+}
+
+// 投递记录响应体
+#[derive(Serialize)]
+pub struct DeliveriesResponse {
+    pub success: bool,
+    pub message: String,
+    pub deliveries: Vec<WebhookDelivery>,
+}
+
+// 注册 webhook
+pub async fn create_webhook_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<CreateWebhookRequest>,
+) -> Result<Json<CreateWebhookResponse>, AppError> {
+    if !WEBHOOK_EVENTS.contains(&req.event.as_str()) {
+        return Err(AppError::InvalidInput(format!("不支持的事件 {}，可选: {}", req.event, WEBHOOK_EVENTS.join(", "))));
+    }
+    if !(req.url.starts_with("https://") || req.url.starts_with("http://")) {
+        return Err(AppError::InvalidInput("url 必须以 http:// 或 https:// 开头".into()));
+    }
+
+    let webhook = state.db_pool.create_webhook(&req.url, &req.event, req.channel_id.as_deref(), unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    let secret = webhook.secret.clone();
+
+    Ok(Json(CreateWebhookResponse {
+        success: true,
+        message: "webhook 已注册".into(),
+        webhook,
+        secret,
+    }))
+}
+
+// 列出 webhook
+pub async fn list_webhooks_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<WebhooksResponse>, AppError> {
+    let webhooks = state.db_pool.list_webhooks()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(WebhooksResponse {
+        success: true,
+        message: "获取 webhook 成功".into(),
+        webhooks,
+    }))
+}
+
+// 删除 webhook
+pub async fn delete_webhook_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(webhook_id): UrlPath<String>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let removed = state.db_pool.delete_webhook(&webhook_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !removed {
+        return Err(AppError::NotFound("webhook 不存在".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "webhook 已删除".into(),
+    }))
+}
+
+// 查看 webhook 最近的投递记录
+pub async fn list_deliveries_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(webhook_id): UrlPath<String>,
+    Query(query): Query<DeliveriesQuery>,
+) -> Result<Json<DeliveriesResponse>, AppError> {
+    let exists = state.db_pool.webhook_exists(&webhook_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    if !exists {
+        return Err(AppError::NotFound("webhook 不存在".into()));
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_DELIVERY_LIMIT).clamp(1, 500);
+    let deliveries = state.db_pool.list_webhook_deliveries(&webhook_id, limit)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(DeliveriesResponse {
+        success: true,
+        message: "获取投递记录成功".into(),
+        deliveries,
+    }))
+}
+
+/// 注册 webhook 管理路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/webhooks", get(list_webhooks_handler).post(create_webhook_handler))
+        .route("/admin/webhooks/{webhook_id}", delete(delete_webhook_handler))
+        .route("/admin/webhooks/{webhook_id}/deliveries", get(list_deliveries_handler))
+}
@@ -0,0 +1,307 @@
+use axum::{
+    extract::{FromRequestParts, Path as UrlPath, State},
+    http::request::Parts,
+    response::Json,
+    routing::{get, post, put},
+    Router
+};
+use serde::{Deserialize, Serialize};
+use crate::error::AppError;
+use crate::storage::workspaces::{Workspace, DEFAULT_WORKSPACE, ROLE_MEMBER, WORKSPACE_ROLES};
+
+// 共享应用状态
+use super::AppState;
+use super::admin::{AdminAuth, AdminResponse};
+use super::policy::{self, Check};
+use crate::core::datetime::unix_now;
+
+// 选择工作区的请求头，值为工作区的 slug；不传时使用默认工作区
+pub const WORKSPACE_HEADER: &str = "x-workspace";
+
+// 邀请码默认有效期（7 天）
+const DEFAULT_INVITE_TTL_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// 请求所在的工作区
+///
+/// 由 `X-Workspace: <slug>` 请求头指定，不传时为默认工作区
+pub struct WorkspaceScope(pub Workspace);
+
+impl FromRequestParts<AppState> for WorkspaceScope {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let slug = parts
+            .headers
+            .get(WORKSPACE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or(DEFAULT_WORKSPACE);
+        resolve_workspace(state, slug).map(WorkspaceScope)
+    }
+}
+
+impl WorkspaceScope {
+    pub fn id(&self) -> &str {
+        &self.0.id
+    }
+}
+
+// 按 slug 查找工作区（空字符串视为默认工作区，供 WebSocket 和 gRPC 使用）
+pub(crate) fn resolve_workspace(state: &AppState, slug: &str) -> Result<Workspace, AppError> {
+    let slug = if slug.is_empty() { DEFAULT_WORKSPACE } else { slug };
+    state.db_pool.get_workspace_by_slug(slug)
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::NotFound(format!("工作区 {} 不存在", slug)))
+}
+
+// 配置关闭了开放注册时，默认工作区也只允许持邀请码注册
+pub(crate) fn closed_by_config(state: &AppState, workspace: &Workspace) -> bool {
+    state.settings.registration.invite_only && workspace.id == DEFAULT_WORKSPACE
+}
+
+// 校验用户属于该工作区
+pub(crate) fn require_member(state: &AppState, workspace_id: &str, user_id: &str) -> Result<(), AppError> {
+    if state.db_pool.is_workspace_member(workspace_id, user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        Ok(())
+    } else {
+        Err(AppError::Forbidden("用户不属于该工作区".into()))
+    }
+}
+
+// 创建工作区请求体
+#[derive(Deserialize)]
+pub struct CreateWorkspaceRequest {
+    pub slug: String,
+    pub name: String,
+    #[serde(default)]
+    pub invite_only: bool,
+}
+
+// 修改工作区请求体
+#[derive(Deserialize)]
+pub struct UpdateWorkspaceRequest {
+    pub invite_only: bool,
+}
+
+// 添加成员请求体
+#[derive(Deserialize)]
+pub struct AddMemberRequest {
+    #[serde(default)]
+    pub role: Option<String>, // "owner" 或 "member"，默认 member
+}
+
+// 签发邀请码请求体
+#[derive(Deserialize, Default)]
+pub struct CreateInviteRequest {
+    pub expires_in_secs: Option<i64>,
+}
+
+// 单个工作区响应体
+#[derive(Serialize)]
+pub struct WorkspaceResponse {
+    pub success: bool,
+    pub message: String,
+    pub workspace: Workspace,
+}
+
+// 工作区列表响应体
+#[derive(Serialize)]
+pub struct WorkspacesResponse {
+    pub success: bool,
+    pub message: String,
+    pub workspaces: Vec<Workspace>,
+}
+
+// 邀请码响应体（invite_code 只返回这一次）
+#[derive(Serialize)]
+pub struct InviteResponse {
+    pub success: bool,
+    pub message: String,
+    pub invite_code: String,
+    pub expires_at: i64,
+}
+
+// 创建工作区
+pub async fn create_workspace_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    Json(req): Json<CreateWorkspaceRequest>,
+) -> Result<Json<WorkspaceResponse>, AppError> {
+    let slug = req.slug.trim();
+    if slug.is_empty() || !slug.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+        return Err(AppError::InvalidInput("slug 只能包含小写字母、数字和 -".into()));
+    }
+    if req.name.trim().is_empty() {
+        return Err(AppError::InvalidInput("工作区名称不能为空".into()));
+    }
+    let workspace = state.db_pool.create_workspace(slug, req.name.trim(), req.invite_only, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?
+        .ok_or_else(|| AppError::InvalidInput(format!("工作区 {} 已存在", slug)))?;
+
+    Ok(Json(WorkspaceResponse {
+        success: true,
+        message: "工作区已创建".into(),
+        workspace,
+    }))
+}
+
+// 列出全部工作区
+pub async fn list_workspaces_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+) -> Result<Json<WorkspacesResponse>, AppError> {
+    let workspaces = state.db_pool.list_workspaces()
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(WorkspacesResponse {
+        success: true,
+        message: "获取工作区列表成功".into(),
+        workspaces,
+    }))
+}
+
+// 开启或关闭仅限受邀注册
+pub async fn update_workspace_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(slug): UrlPath<String>,
+    Json(req): Json<UpdateWorkspaceRequest>,
+) -> Result<Json<WorkspaceResponse>, AppError> {
+    let mut workspace = resolve_workspace(&state, &slug)?;
+    state.db_pool.set_workspace_invite_only(&workspace.id, req.invite_only)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    workspace.invite_only = req.invite_only;
+
+    Ok(Json(WorkspaceResponse {
+        success: true,
+        message: "工作区已更新".into(),
+        workspace,
+    }))
+}
+
+// 把已有用户加入工作区
+pub async fn add_member_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath((slug, user_id)): UrlPath<(String, String)>,
+    Json(req): Json<AddMemberRequest>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let workspace = resolve_workspace(&state, &slug)?;
+    if workspace.id == DEFAULT_WORKSPACE {
+        return Err(AppError::InvalidInput("所有用户都属于默认工作区".into()));
+    }
+    let role = req.role.as_deref().unwrap_or(ROLE_MEMBER);
+    if !WORKSPACE_ROLES.contains(&role) {
+        return Err(AppError::InvalidInput(format!("未知的角色 {}，可选: {}", role, WORKSPACE_ROLES.join(", "))));
+    }
+    if !state.db_pool.user_exists_by_id(&user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("用户不存在".into()));
+    }
+    state.db_pool.add_workspace_member(&workspace.id, &user_id, role, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "已加入工作区".into(),
+    }))
+}
+
+// 把用户移出工作区
+pub async fn remove_member_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath((slug, user_id)): UrlPath<(String, String)>,
+) -> Result<Json<AdminResponse>, AppError> {
+    let workspace = resolve_workspace(&state, &slug)?;
+    if !state.db_pool.remove_workspace_member(&workspace.id, &user_id).map_err(|e| AppError::Database(e.to_string()))? {
+        return Err(AppError::NotFound("该用户不是工作区成员".into()));
+    }
+
+    Ok(Json(AdminResponse {
+        success: true,
+        message: "已移出工作区".into(),
+    }))
+}
+
+// 签发注册邀请码
+pub async fn create_invite_handler(
+    _admin: AdminAuth,
+    State(state): State<AppState>,
+    UrlPath(slug): UrlPath<String>,
+    req: Option<Json<CreateInviteRequest>>,
+) -> Result<Json<InviteResponse>, AppError> {
+    let workspace = resolve_workspace(&state, &slug)?;
+    let ttl = req.and_then(|Json(r)| r.expires_in_secs).unwrap_or(DEFAULT_INVITE_TTL_SECS);
+    if ttl <= 0 {
+        return Err(AppError::InvalidInput("expires_in_secs 必须大于 0".into()));
+    }
+    let invite = state.db_pool.create_workspace_invite(&workspace.id, ttl, unix_now())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(InviteResponse {
+        success: true,
+        message: "邀请码已生成".into(),
+        invite_code: invite.code,
+        expires_at: invite.expires_at,
+    }))
+}
+
+// 签发默认工作区的注册邀请码：携带管理令牌时不限数量，
+// 否则按会话令牌识别用户，最多签发 invites_per_user 个
+pub async fn create_default_invite_handler(
+    State(state): State<AppState>,
+    headers: http::HeaderMap,
+    req: Option<Json<CreateInviteRequest>>,
+) -> Result<Json<InviteResponse>, AppError> {
+    let ttl = req.and_then(|Json(r)| r.expires_in_secs).unwrap_or(DEFAULT_INVITE_TTL_SECS);
+    if ttl <= 0 {
+        return Err(AppError::InvalidInput("expires_in_secs 必须大于 0".into()));
+    }
+    let invite = if policy::is_server_admin(&state, &headers) {
+        state.db_pool.create_workspace_invite(DEFAULT_WORKSPACE, ttl, unix_now())
+            .map_err(|e| AppError::Database(e.to_string()))?
+    } else {
+        let user_id = super::user::session_user(&state, &headers)?;
+        let max_invites = state.settings.registration.invites_per_user;
+        if max_invites <= 0 {
+            return Err(AppError::Forbidden("没有签发邀请码的权限".into()));
+        }
+        state.db_pool.create_user_invite(&user_id, max_invites, ttl, unix_now())
+            .map_err(|e| AppError::Database(e.to_string()))?
+            .ok_or_else(|| AppError::RateLimited(format!("最多只能签发 {} 个邀请码", max_invites)))?
+    };
+
+    Ok(Json(InviteResponse {
+        success: true,
+        message: "邀请码已生成".into(),
+        invite_code: invite.code,
+        expires_at: invite.expires_at,
+    }))
+}
+
+// 用户所属的工作区
+pub async fn user_workspaces_handler(
+    State(state): State<AppState>,
+    UrlPath(user_id): UrlPath<String>,
+    headers: http::HeaderMap,
+) -> Result<Json<WorkspacesResponse>, AppError> {
+    policy::authorize(&state, &headers, &[Check::Is(&user_id)])?;
+    let workspaces = state.db_pool.list_user_workspaces(&user_id)
+        .map_err(|e| AppError::Database(e.to_string()))?;
+
+    Ok(Json(WorkspacesResponse {
+        success: true,
+        message: "获取工作区列表成功".into(),
+        workspaces,
+    }))
+}
+
+/// 注册工作区相关路由
+pub fn register_routes() -> Router<AppState> {
+    Router::new()
+        .route("/admin/workspaces", get(list_workspaces_handler).post(create_workspace_handler))
+        .route("/admin/workspaces/{slug}", put(update_workspace_handler))
+        .route("/admin/workspaces/{slug}/members/{user_id}", put(add_member_handler).delete(remove_member_handler))
+        .route("/admin/workspaces/{slug}/invites", post(create_invite_handler))
+        .route("/invites", post(create_default_invite_handler))
+        .route("/user/{user_id}/workspaces", get(user_workspaces_handler))
+}
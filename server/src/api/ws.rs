@@ -1,16 +1,21 @@
 use axum::{
     extract::{
+        FromRequest,
+        Request,
         State,
         ws::{
             WebSocketUpgrade, 
             Message, 
-            WebSocket
+            WebSocket,
+            CloseFrame
         }
     },
+    response::{IntoResponse, Response},
     routing::get,
     Router
 };
 use serde_json::{
+    json,
     Value
 };
 use std::collections::HashMap;
@@ -22,56 +27,336 @@ use futures_util::{
     SinkExt, 
     StreamExt
 };
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+use crate::bus::BusEvent;
+use crate::error::AppError;
+use super::policy::{self, Check};
 use uuid::Uuid;
+use crate::core::datetime::unix_now;
+
+/// 同一用户的各个 WebSocket 连接：客户端ID → 该连接的发送队列
+type Connections = HashMap<String, super::outbound::Sender>;
+
+/// 上报了设备ID的连接：(用户ID, 设备ID) → (客户端ID, 关闭该连接的通道)
+type DeviceConnections = HashMap<(String, String), (String, mpsc::Sender<CloseFrame>)>;
+
+/// 同一设备的新连接顶替旧连接时，旧连接的关闭码
+pub const CLOSE_REPLACED_BY_NEW_LOGIN: u16 = 4001;
+/// 同一设备已经在线、拒绝新连接时的关闭码
+pub const CLOSE_DUPLICATE_LOGIN: u16 = 4002;
+
+/// 同一设备重复登录时的处理结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceLoginOutcome {
+    Attached,           // 该设备没有其他连接
+    ReplacedOld,        // 关闭了该设备原有的连接
+    DeniedNew,          // 该设备已在线，拒绝新连接
+}
 
 /// 共享应用状态
 #[derive(Clone)]
 pub struct AppState {
     pub db_pool: crate::storage::DbPool,
-    /// 用户ID到WebSocket广播通道的映射
-    clients: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>,
+    /// 服务器配置（只读）
+    pub settings: Arc<crate::config::settings::Settings>,
+    /// 用户ID到其各个WebSocket连接的映射，同一用户可以在多台设备上同时在线
+    clients: Arc<Mutex<HashMap<String, Connections>>>,
     /// 客户端ID到用户ID的映射，用于断开连接时清理资源
     client_user_map: Arc<Mutex<HashMap<String, String>>>,
+    /// 同一设备只保留一个连接
+    device_connections: Arc<Mutex<DeviceConnections>>,
     /// 全局广播通道，用于向所有客户端发送消息
     broadcaster: broadcast::Sender<String>,
     pub group_chat_broadcast_channel_map: Arc<Mutex<HashMap<String, broadcast::Sender<String>>>>,
+    /// 按档位限制请求数和发消息数
+    pub rate_limits: super::rate_limit::RateLimits,
+    /// 离线推送分发器
+    pub push: crate::push::PushDispatcher,
+    /// 服务器间联邦（未启用时为 None）
+    pub federation: Option<crate::federation::Federation>,
+    /// 多实例部署时的消息总线和其他实例上的在线状态
+    pub cluster: crate::bus::Cluster,
+    /// 来源 IP 访问控制
+    pub ip_filter: super::ip_filter::IpFilter,
+    /// 本实例上各个 WebSocket 连接的元数据，供管理员查看
+    pub(crate) connections: super::connections::ConnectionRegistry,
+    /// 维护模式，管理员在运行时开启和关闭
+    pub(crate) maintenance: super::maintenance::Maintenance,
+    /// 本实例上进行中的一对一通话
+    pub(crate) calls: super::calls::CallRegistry,
+    /// 本实例上的二进制流中转
+    pub(crate) relays: super::relay::RelayRegistry,
+    /// 邮件发送方（未配置 SMTP 时为 None）
+    pub mailer: Option<Arc<dyn crate::email::EmailProvider>>,
+    /// 消息翻译后端（未配置时为 None）
+    pub translator: Option<Arc<dyn crate::translate::Translator>>,
+    /// 附件病毒扫描后端（未配置时为 None）
+    pub scanner: Option<Arc<dyn crate::scan::Scanner>>,
+    /// 应用层加密（未配置主密钥时为 None）
+    pub crypto: Option<crate::crypto::CryptoService>,
+    /// 注册和登录时的密码哈希，在阻塞线程池中执行
+    pub(crate) auth_crypto: crate::crypto::password::AuthCrypto,
+    /// 常驻后台任务的监督器
+    pub tasks: crate::tasks::supervisor::Supervisor,
+    /// 管理员在运行时修改的日志级别和功能开关
+    pub(crate) runtime: super::runtime::Runtime,
 }
 
 impl AppState {
     /// 创建新的应用状态
-    pub fn new(db_pool: crate::storage::DbPool) -> Self {
+    pub fn new(db_pool: crate::storage::DbPool, settings: crate::config::settings::Settings) -> Self {
         let (broadcaster, _) = broadcast::channel(100);
+        let rate_limits = super::rate_limit::RateLimits::from_settings(&settings.rate_limits);
+        let push = crate::push::PushDispatcher::from_settings(db_pool.clone(), &settings.push);
+        let federation = crate::federation::Federation::from_settings(&settings.federation)
+            .unwrap_or_else(|e| {
+                println!("联邦配置无效，已禁用: {}", e);
+                None
+            });
+        let cluster = crate::bus::Cluster::from_settings(&settings.cluster)
+            .unwrap_or_else(|e| {
+                println!("集群配置无效，以单实例模式运行: {}", e);
+                crate::bus::Cluster::new(None, std::time::Duration::from_secs(settings.cluster.heartbeat_secs))
+            });
+        let ip_filter = super::ip_filter::IpFilter::from_settings(&settings.access)
+            .unwrap_or_else(|e| {
+                println!("访问控制配置无效，拒绝所有请求: {}", e);
+                super::ip_filter::IpFilter::deny_all()
+            });
+        let mailer = crate::email::from_settings(&settings.email)
+            .unwrap_or_else(|e| {
+                println!("邮件配置无效，不发送邮件: {}", e);
+                None
+            });
+        let translator = crate::translate::from_settings(&settings.translation)
+            .unwrap_or_else(|e| {
+                println!("翻译配置无效，不提供翻译: {}", e);
+                None
+            });
+        // 启动时已经校验过（见 main），这里不再退回到不扫描
+        let scanner = crate::scan::from_settings(&settings.scan)
+            .unwrap_or_else(|e| panic!("病毒扫描配置无效: {}", e));
+        let crypto = crate::crypto::CryptoService::from_settings(&settings.security);
+        let auth_crypto = crate::crypto::password::AuthCrypto::new(settings.security.password_hash_concurrency);
+        let tasks = crate::tasks::supervisor::Supervisor::new(
+            std::time::Duration::from_millis(settings.server.task_restart_backoff_ms),
+        );
+        let db_pool = db_pool.clone().with_encryption(&settings.security).unwrap_or_else(|e| {
+            println!("落库加密配置无效，数据不加密: {}", e);
+            db_pool
+        });
+        let runtime = super::runtime::Runtime::load(&db_pool, &settings);
         Self {
             db_pool,
+            settings: Arc::new(settings),
             clients: Arc::new(Mutex::new(HashMap::new())),
             client_user_map: Arc::new(Mutex::new(HashMap::new())),
+            device_connections: Arc::new(Mutex::new(HashMap::new())),
             broadcaster,
-            group_chat_broadcast_channel_map: Arc::new(Mutex::new(HashMap::new()))
+            group_chat_broadcast_channel_map: Arc::new(Mutex::new(HashMap::new())),
+            rate_limits,
+            push,
+            federation,
+            cluster,
+            ip_filter,
+            connections: Default::default(),
+            maintenance: Default::default(),
+            calls: Default::default(),
+            relays: Default::default(),
+            mailer,
+            translator,
+            scanner,
+            crypto,
+            auth_crypto,
+            tasks,
+            runtime,
         }
     }
     
-    /// 获取客户端映射（用于消息推送）
-    pub fn get_clients(&self) -> &Arc<Mutex<HashMap<String, broadcast::Sender<String>>>> {
-        &self.clients
+    /// 用户当前是否有活跃的 WebSocket 连接（本实例或集群中的其他实例）
+    ///
+    /// 断开时不会从 clients 中移除用户，因此以发送队列是否已关闭为准
+    pub fn is_online(&self, user_id: &str) -> bool {
+        self.is_local_online(user_id) || self.cluster.is_remote_online(user_id)
+    }
+
+    fn is_local_online(&self, user_id: &str) -> bool {
+        self.clients
+            .lock()
+            .unwrap()
+            .get(user_id)
+            .is_some_and(|connections| connections.values().any(|tx| !tx.is_closed()))
+    }
+
+    /// 本实例上在线的全部用户
+    pub fn local_online_users(&self) -> Vec<String> {
+        self.clients
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, connections)| connections.values().any(|tx| !tx.is_closed()))
+            .map(|(user_id, _)| user_id.clone())
+            .collect()
+    }
+
+    /// 把连接标识为某个用户，与该用户在其他设备上的连接并存
+    pub fn attach_client(&self, client_id: &str, user_id: &str, tx: super::outbound::Sender) {
+        let previous = self.client_user_map.lock().unwrap().insert(client_id.to_string(), user_id.to_string());
+        let mut clients = self.clients.lock().unwrap();
+        // 同一连接改用其他用户标识时，从原用户的连接中移除
+        if let Some(previous) = previous.filter(|previous| previous != user_id)
+            && let Some(connections) = clients.get_mut(&previous)
+        {
+            connections.remove(client_id);
+        }
+        let connections = clients.entry(user_id.to_string()).or_default();
+        // 顺便清理已经断开的连接
+        connections.retain(|_, tx| !tx.is_closed());
+        connections.insert(client_id.to_string(), tx);
+        drop(clients);
+        self.connections.identify(client_id, user_id, None);
+        println!("WebSocket客户端 {} 标识为用户 {}", client_id, user_id);
+        touch_last_seen(self, user_id);
+        self.cluster.publish(BusEvent::Online { user_id: user_id.to_string() });
+    }
+
+    /// 把连接标识为用户在某台设备上的连接，按 [devices] duplicate_login 处理该设备已有的连接：
+    /// kick_old 关闭旧连接，deny_new 关闭新连接（不会标识为该用户）
+    pub fn attach_device(
+        &self,
+        client_id: &str,
+        user_id: &str,
+        device_id: &str,
+        tx: super::outbound::Sender,
+        close: mpsc::Sender<CloseFrame>,
+    ) -> DeviceLoginOutcome {
+        let key = (user_id.to_string(), device_id.to_string());
+        let mut devices = self.device_connections.lock().unwrap();
+        let existing = devices.get(&key)
+            .filter(|(existing_id, existing_close)| existing_id != client_id && !existing_close.is_closed())
+            .cloned();
+        let outcome = match existing {
+            None => DeviceLoginOutcome::Attached,
+            Some(_) if self.settings.devices.duplicate_login == "deny_new" => {
+                drop(devices);
+                let _ = close.try_send(close_frame(CLOSE_DUPLICATE_LOGIN, "duplicate_login"));
+                return DeviceLoginOutcome::DeniedNew;
+            }
+            Some((_, existing_close)) => {
+                let _ = existing_close.try_send(close_frame(CLOSE_REPLACED_BY_NEW_LOGIN, "replaced_by_new_login"));
+                DeviceLoginOutcome::ReplacedOld
+            }
+        };
+        devices.insert(key, (client_id.to_string(), close));
+        drop(devices);
+        self.attach_client(client_id, user_id, tx);
+        self.connections.identify(client_id, user_id, Some(device_id));
+        outcome
+    }
+
+    /// 连接断开时清理映射，返回该连接对应的用户
+    pub(crate) fn detach_client(&self, client_id: &str, tx: &super::outbound::Sender) -> Option<String> {
+        self.device_connections.lock().unwrap().retain(|_, (id, _)| id != client_id);
+        let user_id = self.client_user_map.lock().unwrap().remove(client_id)?;
+        touch_last_seen(self, &user_id);
+        let mut clients = self.clients.lock().unwrap();
+        let connections = clients.entry(user_id.clone()).or_default();
+        if connections.get(client_id).is_some_and(|current| current.same_channel(tx)) {
+            connections.remove(client_id);
+        }
+        // 用户在其他设备上还有连接时不算下线
+        let online = connections.values().any(|current| !current.same_channel(tx) && !current.is_closed());
+        if connections.is_empty() {
+            clients.remove(&user_id);
+        }
+        drop(clients);
+        if !online {
+            self.cluster.publish(BusEvent::Offline { user_id: user_id.clone() });
+        }
+        Some(user_id)
+    }
+
+    /// 向用户推送一条 WebSocket 消息，集群中其他实例上的连接也会收到；返回本实例是否有该用户的连接
+    pub fn send_to_user(&self, user_id: &str, payload: String) -> bool {
+        let delivered = self.deliver_local(user_id, payload.clone());
+        self.cluster.publish(BusEvent::User { user_id: user_id.to_string(), payload });
+        delivered
+    }
+
+    /// 把私聊消息实时推送给接收者（type 为 message），发件箱重发时推送同样的事件；返回本实例是否有接收者的连接
+    pub fn push_to_receiver(&self, message: &crate::storage::Message) -> bool {
+        self.send_to_user(&message.receiver_id, super::message::message_event(message))
+    }
+
+    /// 向用户除 except_client 之外的连接推送，用于把一台设备上的操作同步到其他设备
+    pub fn send_to_user_except(&self, user_id: &str, except_client: &str, payload: String) {
+        self.deliver_local_except(user_id, Some(except_client), payload.clone());
+        self.cluster.publish(BusEvent::User { user_id: user_id.to_string(), payload });
+    }
+
+    /// 只投递给本实例上的连接
+    pub fn deliver_local(&self, user_id: &str, payload: String) -> bool {
+        self.deliver_local_except(user_id, None, payload)
+    }
+
+    fn deliver_local_except(&self, user_id: &str, except_client: Option<&str>, payload: String) -> bool {
+        let clients = self.clients.lock().unwrap();
+        let Some(connections) = clients.get(user_id) else {
+            return false;
+        };
+        let mut delivered = false;
+        for (client_id, tx) in connections {
+            if Some(client_id.as_str()) != except_client {
+                delivered |= tx.send(payload.clone()).is_ok();
+            }
+        }
+        delivered
+    }
+
+    /// 向群聊广播通道推送消息，集群中其他实例上的成员也会收到
+    pub fn send_to_group(&self, group_id: &str, payload: String) {
+        self.deliver_local_group(group_id, payload.clone());
+        self.cluster.publish(BusEvent::Group { group_id: group_id.to_string(), payload });
+    }
+
+    /// 只投递给本实例上订阅了该群的连接
+    pub fn deliver_local_group(&self, group_id: &str, payload: String) {
+        if let Some(tx) = self.group_chat_broadcast_channel_map.lock().unwrap().get(group_id) {
+            let _ = tx.send(payload);
+        }
     }
 }
 
 /// WebSocket连接升级处理器
 async fn ws_handler(
-    upgrade: WebSocketUpgrade,
     State(state): State<AppState>,
-) -> impl axum::response::IntoResponse {
-    upgrade.on_upgrade(|socket| handle_websocket(socket, state))
+    request: Request,
+) -> Response {
+    // 错误事件的提示语按升级请求的 Accept-Language 翻译
+    let locale = super::i18n::negotiate(request.headers().get(http::header::ACCEPT_LANGUAGE).and_then(|v| v.to_str().ok()));
+    let ip = state.ip_filter.client_ip(&request).map(|ip| ip.to_string());
+    // identify 帧不带会话令牌时使用升级请求的 Authorization 请求头
+    let mut auth = http::HeaderMap::new();
+    if let Some(value) = request.headers().get(http::header::AUTHORIZATION) {
+        auth.insert(http::header::AUTHORIZATION, value.clone());
+    }
+    match WebSocketUpgrade::from_request(request, &state).await {
+        Ok(upgrade) => upgrade.on_upgrade(move |socket| handle_websocket(socket, state, locale, ip, auth)),
+        Err(rejection) => rejection.into_response(),
+    }
 }
 
 /// 处理WebSocket连接
-async fn handle_websocket(socket: WebSocket, state: AppState) {
+async fn handle_websocket(socket: WebSocket, state: AppState, locale: &'static str, ip: Option<String>, auth: http::HeaderMap) {
     let (mut sender, mut receiver) = socket.split();
     let client_id = Uuid::new_v4().to_string();
     
-    // 创建客户端专用广播通道
-    let (self_tx, mut self_rx) = broadcast::channel(100);
+    // 创建客户端专用发送队列，由下面的发送任务逐条写入套接字
+    let (self_tx, mut self_rx) = super::outbound::channel(state.settings.server.ws_send_queue);
+    // 服务器主动关闭本连接的通道（同一设备重复登录、管理员强制关闭时使用）
+    let (close_tx, mut close_rx) = mpsc::channel::<CloseFrame>(1);
+    // 登记连接元数据，收发帧时更新计数
+    let connection = state.connections.register(&client_id, ip, self_tx.clone(), close_tx.clone());
     
     println!("新WebSocket客户端连接: {}", client_id);
     
@@ -79,196 +364,228 @@ async fn handle_websocket(socket: WebSocket, state: AppState) {
     let _ = state.broadcaster.send(format!("Client {} joined", client_id));
 //---------------------------------------------------------------------------------------------------------------------------------------------------------------------
     // 初始化或获取用户加入的所有群聊的订阅广播通道
-    let head = if let Some(Ok(Message::Text(text))) = receiver.next().await{
-        let head: Value=serde_json::from_str(&text).unwrap(); //注意unwrap后续修复 
-        println!("调试打印: {{来自ws的消息: {head}}}");
-        if let Some(list_of_group_chats)=head["list_of_group_chats"].as_array() {
-            println!("调试打印: {{群聊功能初始化: 此用户存在群}}");
-            for group_id_value in list_of_group_chats { //为每个群聊创建一个广播通道
-                if let Value::String(group_id)=group_id_value {
-                    let mut group_chat_broadcast_channel_map= state.group_chat_broadcast_channel_map.lock().unwrap(); //注意unwrap后续修复
-                    // 当前群广播通道已经创建过了直接克隆订阅端通道,并开启群消息接收任务
-                    if group_chat_broadcast_channel_map.contains_key(group_id) {
-                        let mut rx=group_chat_broadcast_channel_map[group_id].subscribe();
-                        let self_tx=self_tx.clone();
-                        tokio::spawn(async move {
-                            while let Ok(msg) = rx.recv().await {
-                                if self_tx.send(msg).is_err() {
-                                    break;
-                                }
-                            }
-                        });
-                        continue;
-                    }
-                    // 当前群没有创建过群聊广播通道则创建,并开启消息接收任务
-                    let (tx, mut rx) = broadcast::channel::<String>(100);
-                    group_chat_broadcast_channel_map.insert(group_id.clone(),tx);
-                    let self_tx=self_tx.clone();
-                    tokio::spawn(async move {
-                        while let Ok(msg) = rx.recv().await {
-                            if self_tx.send(msg).is_err() {
-                                break;
-                            }   
-                        }
-                    });
-                }
-            }
-        }else{
-            println!("调试打印: {{群聊功能初始化: 此用户没有群}}");
-        }
-        head
-    }else {
-        Value::Null
+    // 第一帧无效时照常建立连接，并告知客户端原因
+    let first = receiver.next().await;
+    if let Some(Ok(_)) = first {
+        connection.received();
+    }
+    let first = match first {
+        Some(Ok(Message::Text(text))) => parse_frame(&text).map(Some),
+        Some(Ok(Message::Binary(_))) => Err(AppError::InvalidInput("只支持文本帧".into())),
+        _ => Ok(None),
     };
-//-----------------------------------------------------------------------------------------------------------------------------------------------------------------
+    let first = first.unwrap_or_else(|e| {
+        let _ = self_tx.send(error_event(locale, e, None));
+        None
+    });
+    let head = first.unwrap_or(Value::Null);
+    if head != Value::Null {
+        println!("调试打印: {{来自ws的消息: {head}}}");
+    }
     let state_clone = state.clone();
     let client_id_clone = client_id.clone();
     // 身份初始化
-    if let Some(msg_type) = head.get("type").and_then(|x| x.as_str()) {
-        match msg_type {
-            "identify" => {
-                // 处理客户端身份标识
-                if let Some(user_id) = head.get("user_id").and_then(|x| x.as_str()) {
-                    // 将客户端通道映射到用户ID，方便推送定向通知
-                    let mut clients_map = state_clone.clients.lock().unwrap();
-                    clients_map.insert(user_id.to_string(), self_tx.clone());
-                    // 记录客户端ID到用户ID的映射，便于断开时清理
-                    state_clone.client_user_map.lock().unwrap().insert(client_id_clone.clone(), user_id.to_string());
-                    println!("WebSocket客户端 {} 标识为用户 {}", client_id_clone, user_id);
-                }
-            },
-            _=>()
-        }
+    if head.get("type").and_then(|x| x.as_str()) == Some("identify") {
+        // 将客户端通道映射到用户ID，方便推送定向通知
+        identify(&state_clone, &client_id_clone, &head, &auth, locale, &self_tx, &close_tx);
+    }
+    // 首帧中的 list_of_group_chats 为要订阅的群聊，须在身份初始化之后处理
+    if let Some(group_ids) = head["list_of_group_chats"].as_array()
+        && !group_ids.is_empty()
+    {
+        subscribe_groups(&state, &client_id, &head, &auth, locale, &self_tx, group_ids);
     }
-//----------------------------------------------------------------------------------------------------------------------------------------------------------------------
-// 身份初始化和群聊初始化先后顺序好像搞反了但不影响运行
+//-----------------------------------------------------------------------------------------------------------------------------------------------------------------
 
+    // 断开时用于确认清理的是本连接的通道
+    let cleanup_tx = self_tx.clone();
     // 处理接收消息的任务
+    let recv_connection = connection.clone();
     let recv_task = tokio::spawn(async move {
-        while let Some(Ok(Message::Text(text))) = receiver.next().await {
+        // 格式错误的帧回复 type 为 error 的事件，附上出错请求的 client_message_id
+        let reply_error = |e: AppError, request: Option<&Value>| {
+            let _ = self_tx.send(error_event(locale, e, request));
+        };
+        while let Some(Ok(frame)) = receiver.next().await {
+            recv_connection.received();
+            let text = match frame {
+                Message::Text(text) => text,
+                Message::Binary(_) => {
+                    reply_error(AppError::InvalidInput("只支持文本帧".into()), None);
+                    continue;
+                }
+                Message::Close(_) => break,
+                // ping/pong 由底层自动应答
+                _ => continue,
+            };
             // 尝试解析为JSON以处理特殊类型消息
-            if let Ok(v) = serde_json::from_str::<Value>(&text) {
-                println!("调试打印: {{来自ws的消息: {v}}}");
-                if let Some(msg_type) = v.get("type").and_then(|x| x.as_str()) {
-                    match msg_type {
-                        // 身份标识消息
-                        "identify" => {
-                            // 处理客户端身份标识
-                            if let Some(user_id) = v.get("user_id").and_then(|x| x.as_str()) {
-                                // 将客户端通道映射到用户ID，方便推送定向通知
-                                let mut clients_map = state_clone.clients.lock().unwrap();
-                                // 替换旧的连接，确保用户只有一个活跃连接
-                                clients_map.insert(user_id.to_string(), self_tx.clone());
-                                // 记录客户端ID到用户ID的映射，便于断开时清理
-                                let mut client_user_map = state_clone.client_user_map.lock().unwrap();
-                                client_user_map.insert(client_id_clone.clone(), user_id.to_string());
-                                println!("WebSocket客户端 {} 标识为用户 {}", client_id_clone, user_id);
-                            }
-                        },
-                        // 普通消息分支
-                        "message"=>{
-                            // 提取消息内容
-                            if let Some(sender_id) = v.get("sender_id").and_then(|x| x.as_str()) {
-                                if let Some(receiver_id) = v.get("receiver_id").and_then(|x| x.as_str()) {
-                                    if let Some(content) = v.get("content").and_then(|x| x.as_str()) {
-                                        // 保存消息到数据库
-                                        match state_clone.db_pool.send_message(
-                                            sender_id,
-                                            receiver_id,
-                                            content,
-                                            "private"
-                                        ) {
-                                            Ok(message) => {
-                                                println!("消息已保存到数据库: {:?}", message);
-                                                // 尝试发送消息给目标用户
-                                                let clients_map = state_clone.clients.lock().unwrap();
-                                                if let Some(sender) = clients_map.get(receiver_id) {
-                                                    let _ = sender.send(text.to_string());
-                                                }
-                                            },
-                                            Err(e) => {
-                                                println!("保存消息失败: {:?}", e);
-                                            }
-                                        }
-                                    }
+            let v = match parse_frame(&text) {
+                Ok(v) => v,
+                Err(e) => {
+                    reply_error(e, None);
+                    continue;
+                }
+            };
+            println!("调试打印: {{来自ws的消息: {v}}}");
+            let Some(msg_type) = v.get("type").and_then(|x| x.as_str()) else {
+                reply_error(AppError::InvalidInput("帧缺少 type 字段".into()), Some(&v));
+                continue;
+            };
+            match msg_type {
+                // 身份标识消息
+                "identify" => {
+                    // 将客户端通道映射到用户ID，与该用户其他设备上的连接并存
+                    identify(&state_clone, &client_id_clone, &v, &auth, locale, &self_tx, &close_tx);
+                },
+                // 普通消息分支
+                "message"=>{
+                    // 提取消息内容
+                    if let Some(receiver_id) = v.get("receiver_id").and_then(|x| x.as_str())
+                        && let Some(content) = v.get("content").and_then(|x| x.as_str())
+                    {
+                        // 可选的 workspace 字段为工作区 slug，省略时为默认工作区
+                        let workspace = v.get("workspace").and_then(|x| x.as_str()).unwrap_or_default();
+                        // 可选的 client_message_id 为客户端临时ID，保存后在 message_ack 中回传；
+                        // 可选的 format 为 plain、markdown 或 sticker
+                        let client_message_id = v.get("client_message_id").and_then(|x| x.as_str());
+                        // 保存消息到数据库，发送者是本连接标识的用户
+                        let saved = frame_sender(&state_clone, &client_id_clone, &v).and_then(|sender_id| {
+                            let workspace = super::workspace::resolve_workspace(&state_clone, workspace)?;
+                            super::message::send_message_once(
+                                &state_clone,
+                                &workspace.id,
+                                &sender_id,
+                                receiver_id,
+                                content,
+                                v.get("format").and_then(|x| x.as_str()),
+                                "private",
+                                client_message_id,
+                            )
+                        });
+                        match saved {
+                            Ok((message, created)) => {
+                                println!("消息已保存到数据库: {:?}", message);
+                                // 保存时已经推送给接收方，这里只回显到自己的其他设备（重发的消息已经回显过）
+                                if created {
+                                    super::message::echo_to_own_devices(&state_clone, &message, client_message_id, Some(&client_id_clone));
                                 }
-                            }
-                        },
-                        // 语音通话相关消息
-                        "voice_call_offer" => {
-                            // 提取消息内容
-                            if let Some(receiver_id) = v.get("receiver_id").and_then(|x| x.as_str()) {
-                                if let Some(sender_id) = v.get("sender_id").and_then(|x| x.as_str()) {
-                                    println!("收到语音通话邀请: 从用户 {} 到用户 {}", sender_id, receiver_id);
-                                    // 尝试发送消息给目标用户
-                                    let clients_map = state_clone.clients.lock().unwrap();
-                                    if let Some(sender) = clients_map.get(receiver_id) {
-                                        println!("转发语音通话邀请给用户 {}", receiver_id);
-                                        let _ = sender.send(text.to_string());
-                                    } else {
-                                        println!("目标用户 {} 不在线", receiver_id);
-                                    }
+                                if client_message_id.is_some() {
+                                    let _ = self_tx.send(super::message::ack_event(&message, client_message_id));
                                 }
+                            },
+                            Err(e) => {
+                                println!("保存消息失败: {:?}", e);
+                                reply_error(e, Some(&v));
                             }
-                        },
-                        "voice_call_answer" => {
-                            // 提取消息内容
-                            if let Some(receiver_id) = v.get("remote_user_id").and_then(|x| x.as_str()) {
-                                println!("收到语音通话应答，转发给用户 {}", receiver_id);
-                                // 尝试发送消息给目标用户
-                                let clients_map = state_clone.clients.lock().unwrap();
-                                if let Some(sender) = clients_map.get(receiver_id) {
-                                    let _ = sender.send(text.to_string());
-                                } else {
-                                    println!("目标用户 {} 不在线", receiver_id);
-                                }
+                        }
+                    } else {
+                        reply_error(missing_field(&v, &["receiver_id", "content"]), Some(&v));
+                    }
+                },
+                // 送达确认：接收方收到 message 推送后回复，确认后发件箱不再重发；
+                // 连接须已标识为消息的接收者，其中的群消息同时记为该成员的回执
+                "delivered" => {
+                    match v.get("message_ids").and_then(|x| serde_json::from_value::<Vec<String>>(x.clone()).ok()) {
+                        Some(message_ids) => {
+                            let user_id = state_clone.client_user_map.lock().unwrap().get(&client_id_clone).cloned();
+                            let marked = user_id
+                                .ok_or_else(|| AppError::InvalidCredentials("请先发送 identify 帧标识用户".into()))
+                                .and_then(|user_id| {
+                                    super::message::require_recipient(&state_clone, &user_id, &message_ids)?;
+                                    let now = unix_now();
+                                    state_clone.db_pool.mark_messages_as_delivered(&message_ids, now)
+                                        .and_then(|()| state_clone.db_pool.record_group_receipts(&user_id, &message_ids, false, now))
+                                        .map_err(|e| AppError::Database(e.to_string()))
+                                });
+                            if let Err(e) = marked {
+                                println!("标记消息送达失败: {:?}", e);
+                                reply_error(e, Some(&v));
                             }
                         },
-                        "ice_candidate" => {
-                            // 提取消息内容
-                            if let Some(receiver_id) = v.get("remote_user_id").and_then(|x| x.as_str()) {
-                                println!("收到ICE候选，转发给用户 {}", receiver_id);
-                                // 尝试发送消息给目标用户
-                                let clients_map = state_clone.clients.lock().unwrap();
-                                if let Some(sender) = clients_map.get(receiver_id) {
-                                    let _ = sender.send(text.to_string());
-                                } else {
-                                    println!("目标用户 {} 不在线", receiver_id);
-                                }
+                        None => reply_error(missing_field(&v, &["message_ids"]), Some(&v)),
+                    }
+                },
+                // 一对一通话信令（带 call_id），由服务器记录通话状态，见 calls 模块；
+                // 不带 call_id 的 ice_candidate 是旧版 voice_call_* 信令，按 remote_user_id 直接转发
+                "call_offer" | "call_answer" | "call_end" | "ice_candidate" if msg_type != "ice_candidate" || v.get("call_id").is_some() => {
+                    let user_id = state_clone.client_user_map.lock().unwrap().get(&client_id_clone).cloned();
+                    let handled = match &user_id {
+                        Some(user_id) => super::calls::handle_frame(&state_clone, &client_id_clone, user_id, msg_type, &v),
+                        None => Err(AppError::InvalidCredentials("请先发送 identify 帧标识用户".into())),
+                    };
+                    if let Err(e) = handled {
+                        reply_error(e, Some(&v));
+                    }
+                },
+                // 旧版语音通话信令，只转发、不记录状态
+                "voice_call_offer" => {
+                    // 提取消息内容
+                    if let Some(receiver_id) = v.get("receiver_id").and_then(|x| x.as_str()) {
+                        // 邀请方是本连接标识的用户，转发时填入 sender_id；接收者的隐私设置不允许时不转发
+                        let allowed = frame_sender(&state_clone, &client_id_clone, &v).and_then(|sender_id| {
+                            println!("收到语音通话邀请: 从用户 {} 到用户 {}", sender_id, receiver_id);
+                            super::privacy::require_dm_allowed(&state_clone, &sender_id, receiver_id)?;
+                            Ok(sender_id)
+                        });
+                        match allowed {
+                            Err(e) => reply_error(e, Some(&v)),
+                            Ok(sender_id) if state_clone.is_online(receiver_id) => {
+                                println!("转发语音通话邀请给用户 {}", receiver_id);
+                                let mut offer = v.clone();
+                                offer["sender_id"] = json!(sender_id);
+                                state_clone.send_to_user(receiver_id, offer.to_string());
                             }
-                        },
-                        "voice_call_end" => {
-                            // 提取消息内容
-                            if let Some(receiver_id) = v.get("remote_user_id").and_then(|x| x.as_str()) {
-                                println!("收到语音通话结束，转发给用户 {}", receiver_id);
-                                // 尝试发送消息给目标用户
-                                let clients_map = state_clone.clients.lock().unwrap();
-                                if let Some(sender) = clients_map.get(receiver_id) {
-                                    let _ = sender.send(text.to_string());
-                                } else {
-                                    println!("目标用户 {} 不在线", receiver_id);
-                                }
+                            Ok(_) => println!("目标用户 {} 不在线", receiver_id),
+                        }
+                    } else {
+                        reply_error(missing_field(&v, &["receiver_id"]), Some(&v));
+                    }
+                },
+                // 应答、ICE 候选和挂断：发送者同样是本连接标识的用户，转发时由服务器填入 sender_id
+                "voice_call_answer" | "ice_candidate" | "voice_call_end" => {
+                    if let Some(receiver_id) = v.get("remote_user_id").and_then(|x| x.as_str()) {
+                        let allowed = frame_sender(&state_clone, &client_id_clone, &v).and_then(|sender_id| {
+                            println!("收到 {} 帧: 从用户 {} 到用户 {}", msg_type, sender_id, receiver_id);
+                            super::privacy::require_dm_allowed(&state_clone, &sender_id, receiver_id)?;
+                            Ok(sender_id)
+                        });
+                        match allowed {
+                            Err(e) => reply_error(e, Some(&v)),
+                            Ok(sender_id) if state_clone.is_online(receiver_id) => {
+                                let mut signal = v.clone();
+                                signal["sender_id"] = json!(sender_id);
+                                state_clone.send_to_user(receiver_id, signal.to_string());
                             }
-                        },
-                        // 好友消息分支
-                        "friend_Message"=>{
+                            Ok(_) => println!("目标用户 {} 不在线", receiver_id),
+                        }
+                    } else {
+                        reply_error(missing_field(&v, &["remote_user_id"]), Some(&v));
+                    }
+                },
+                // 好友消息分支
+                "friend_Message"=>{
 
-                        },
-                        // 群聊消息分支
-                        "group_chat"  => {
-                            if let Some(group_id)=v.get("group_id").and_then(|x| x.as_str()) {
-                                let group_chat_broadcast_channel_map=state_clone.group_chat_broadcast_channel_map.lock().unwrap();
-                                let content=if let Some(msg)= v.get("content").and_then(|x| x.as_str()) {
-                                    msg
-                                }else{
-                                    continue;
-                                };
-                                group_chat_broadcast_channel_map[group_id].send(content.to_string()).unwrap();
-                            }
-                        },
-                        _ => {}
+                },
+                // 群聊消息分支
+                "group_chat"  => {
+                    if let Some(group_id) = v.get("group_id").and_then(|x| x.as_str())
+                        && let Some(content) = v.get("content").and_then(|x| x.as_str())
+                    {
+                        // 发送者是本连接标识的用户，须是群成员且未被禁言
+                        let allowed = frame_sender(&state_clone, &client_id_clone, &v).and_then(|user_id| {
+                            super::maintenance::check(&state_clone, &user_id)?;
+                            policy::require(&state_clone, &user_id, &[Check::GroupMember(group_id)])?;
+                            super::group::require_not_muted(&state_clone, group_id, &user_id)
+                        });
+                        match allowed {
+                            Ok(()) => state_clone.send_to_group(group_id, content.to_string()),
+                            Err(e) => reply_error(e, Some(&v)),
+                        }
+                    } else {
+                        reply_error(missing_field(&v, &["group_id", "content"]), Some(&v));
                     }
-                }
+                },
+                other => reply_error(AppError::InvalidInput(format!("不支持的帧类型: {}", other)), Some(&v)),
             }
             println!("从客户端 {} 收到消息: {}", client_id_clone, text);
         }
@@ -276,9 +593,23 @@ async fn handle_websocket(socket: WebSocket, state: AppState) {
     
     // 处理发送消息的任务
     let send_task = tokio::spawn(async move {
-        while let Ok(msg) = self_rx.recv().await {
-            if sender.send(Message::Text(msg.into())).await.is_err() {
-                break;
+        loop {
+            // 先发完已排队的事件再关闭，关闭前推送的通知（如 maintenance 事件）不会丢失
+            tokio::select! {
+                biased;
+                msg = self_rx.recv() => {
+                    let Some(msg) = msg else {
+                        break;
+                    };
+                    if sender.send(Message::Text(msg.into())).await.is_err() {
+                        break;
+                    }
+                    connection.sent();
+                },
+                Some(frame) = close_rx.recv() => {
+                    let _ = sender.send(Message::Close(Some(frame))).await;
+                    break;
+                },
             }
         }
     });
@@ -290,26 +621,174 @@ async fn handle_websocket(socket: WebSocket, state: AppState) {
     }
     
     // 清理用户ID映射（如果存在）
-    {
-        let mut client_user_map = state.client_user_map.lock().unwrap();
-        if let Some(user_id) = client_user_map.remove(&client_id) {
-            // 注意：不要立即移除用户在线状态，因为客户端可能正在重新连接
-            // 让前端在重新连接时通过identify消息重新注册
-            println!("客户端 {} 断开连接，用户 {} 可能正在重新连接", client_id, user_id);
+    if let Some(user_id) = state.detach_client(&client_id, &cleanup_tx) {
+        // 注意：不要立即移除用户在线状态，因为客户端可能正在重新连接
+        // 让前端在重新连接时通过identify消息重新注册
+        println!("客户端 {} 断开连接，用户 {} 可能正在重新连接", client_id, user_id);
+        // 通话信令依赖连接，用户的所有连接都断开时结束其通话
+        if !state.is_online(&user_id) {
+            super::calls::end_calls_of(&state, &user_id);
         }
     }
 
-    // 清理客户端映射
-    {
-        let mut clients = state.clients.lock().unwrap();
-        // 注意：这里不应该移除客户端ID，因为clients_map的键是用户ID，不是客户端ID
-    }
-
+    state.connections.remove(&client_id);
     println!("WebSocket客户端断开连接: {}", client_id);
     // 广播客户端断开连接消息
     let _ = state.broadcaster.send(format!("Client {} left", client_id));
 }
 
+// 处理 identify 消息：用户由会话令牌确定，令牌在帧的 token 字段中（浏览器的 WebSocket 不能设置请求头），
+// 或在升级请求的 Authorization 请求头中；帧中的 user_id 只为兼容旧客户端保留，填写时必须是会话用户本人。
+// 带有该用户的设备ID（登录时返回）时按设备处理重复登录，否则只标识用户
+// 帧中的 token 字段优先于升级请求的 Authorization 请求头
+fn frame_headers(auth: &http::HeaderMap, v: &Value) -> http::HeaderMap {
+    let mut headers = auth.clone();
+    if let Some(token) = v.get("token").and_then(|x| x.as_str())
+        && let Ok(value) = format!("Bearer {}", token).parse()
+    {
+        headers.insert(http::header::AUTHORIZATION, value);
+    }
+    headers
+}
+
+// 订阅首帧中的群聊广播：订阅者是本连接标识的用户，未标识时按帧或升级请求中的会话令牌确定；
+// 只订阅该用户所在的群，其余的群逐个回复错误事件
+fn subscribe_groups(
+    state: &AppState,
+    client_id: &str,
+    head: &Value,
+    auth: &http::HeaderMap,
+    locale: &'static str,
+    tx: &super::outbound::Sender,
+    group_ids: &[Value],
+) {
+    let identified = state.client_user_map.lock().unwrap().get(client_id).cloned();
+    // identify 失败时已经回复过错误
+    if identified.is_none() && head.get("type").and_then(|x| x.as_str()) == Some("identify") {
+        return;
+    }
+    let user_id = match identified.map_or_else(|| policy::authorize(state, &frame_headers(auth, head), &[]), Ok) {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            let _ = tx.send(error_event(locale, e, Some(head)));
+            return;
+        }
+    };
+    for group_id in group_ids.iter().filter_map(|x| x.as_str()) {
+        if let Err(e) = policy::require(state, &user_id, &[Check::GroupMember(group_id)]) {
+            let _ = tx.send(error_event(locale, e, Some(head)));
+            continue;
+        }
+        // 群广播通道不存在时创建，并开启把群消息转到本连接的任务
+        let mut rx = state.group_chat_broadcast_channel_map.lock().unwrap()
+            .entry(group_id.to_string())
+            .or_insert_with(|| broadcast::channel::<String>(100).0)
+            .subscribe();
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            while let Ok(msg) = rx.recv().await {
+                if tx.send(msg).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+fn identify(
+    state: &AppState,
+    client_id: &str,
+    v: &Value,
+    auth: &http::HeaderMap,
+    locale: &'static str,
+    tx: &super::outbound::Sender,
+    close: &mpsc::Sender<CloseFrame>,
+) {
+    let headers = frame_headers(auth, v);
+    let claimed = v.get("user_id").and_then(|x| x.as_str());
+    let checks: Vec<Check> = claimed.map(Check::Is).into_iter().collect();
+    let user_id = match policy::authorize(state, &headers, &checks) {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            let _ = tx.send(error_event(locale, e, Some(v)));
+            return;
+        }
+    };
+    let user_id = user_id.as_str();
+    // 维护期间只有豁免用户可以保持连接
+    if let Some(event) = super::maintenance::identify_blocked(state, user_id) {
+        let _ = tx.send(event);
+        let _ = close.try_send(super::maintenance::close_frame());
+        return;
+    }
+    let device = v.get("device_id")
+        .and_then(|x| x.as_str())
+        .and_then(|device_id| state.db_pool.find_device(user_id, device_id).unwrap_or_else(|e| {
+            println!("查询设备 {} 失败: {}", device_id, e);
+            None
+        }));
+    match device {
+        Some(device) => {
+            let outcome = state.attach_device(client_id, user_id, &device.id, tx.clone(), close.clone());
+            if outcome != DeviceLoginOutcome::Attached {
+                println!("设备 {} 重复连接: {:?}", device.id, outcome);
+                super::devices::notify_duplicate_login(state, user_id, &device, outcome);
+            }
+        }
+        None => state.attach_client(client_id, user_id, tx.clone()),
+    }
+}
+
+// 帧的发送者：本连接 identify 时标识的用户。帧中的 sender_id 只为兼容旧客户端保留，填写时必须是该用户本人
+fn frame_sender(state: &AppState, client_id: &str, v: &Value) -> Result<String, AppError> {
+    let user_id = state.client_user_map.lock().unwrap().get(client_id).cloned()
+        .ok_or_else(|| AppError::InvalidCredentials("请先发送 identify 帧标识用户".into()))?;
+    let claimed: Vec<Check> = v.get("sender_id").and_then(|x| x.as_str()).map(Check::Is).into_iter().collect();
+    policy::require(state, &user_id, &claimed)?;
+    Ok(user_id)
+}
+
+// 解析客户端发来的 JSON 帧
+fn parse_frame(text: &str) -> Result<Value, AppError> {
+    serde_json::from_str(text).map_err(|_| AppError::InvalidInput("帧不是有效的 JSON".into()))
+}
+
+// 缺少必填字段（或字段不是字符串）的错误，报告第一个缺少的字段
+fn missing_field(v: &Value, fields: &[&str]) -> AppError {
+    let field = fields.iter()
+        .find(|field| !v.get(**field).is_some_and(|x| x.is_string() || x.is_array()))
+        .unwrap_or(&fields[0]);
+    AppError::InvalidInput(format!("帧缺少字段: {}", field))
+}
+
+// type 为 error 的事件：code 和按连接语言翻译的提示语，以及出错请求的 client_message_id（没有时为 null）
+fn error_event(locale: &str, error: AppError, request: Option<&Value>) -> String {
+    let details = error.details();
+    let (_, code, message) = error.parts();
+    let (code, message) = super::i18n::localize_message(locale, &message, code);
+    let mut event = json!({
+        "type": "error",
+        "code": code,
+        "message": message,
+        "client_message_id": request.and_then(|v| v.get("client_message_id")).cloned().unwrap_or(Value::Null),
+    });
+    if let Some((key, value)) = details {
+        event[key] = json!(value);
+    }
+    event.to_string()
+}
+
+fn close_frame(code: u16, reason: &'static str) -> CloseFrame {
+    CloseFrame { code, reason: reason.into() }
+}
+
+// 更新用户最后在线时间（用于判断是否发送离线邮件摘要）
+fn touch_last_seen(state: &AppState, user_id: &str) {
+    if let Err(e) = state.db_pool.touch_last_seen(user_id, unix_now()) {
+        println!("更新用户 {} 最后在线时间失败: {}", user_id, e);
+    }
+}
+
 /// 注册WebSocket路由
 pub fn register_ws_route() -> Router<AppState> {
     Router::new().route("/ws", get(ws_handler))
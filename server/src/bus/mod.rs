@@ -0,0 +1,160 @@
+//! 集群消息总线：多个服务器实例部署在负载均衡后面时，通过 pub/sub 共享 WebSocket 推送和在线状态
+//!
+//! 每个实例只持有自己的 WebSocket 连接，发给用户或群的推送除本地投递外还会发布到总线，
+//! 其他实例收到后投递给各自的连接；在线状态通过上线、下线和定期心跳事件同步
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::BoxStream;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::config::settings::ClusterSettings;
+
+pub mod redis;
+
+/// 总线上传递的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BusEvent {
+    /// 推送给某个用户的 WebSocket 消息
+    User { user_id: String, payload: String },
+    /// 推送给某个群聊广播通道的消息
+    Group { group_id: String, payload: String },
+    /// 用户在来源实例上线
+    Online { user_id: String },
+    /// 用户在来源实例的连接全部断开
+    Offline { user_id: String },
+    /// 来源实例当前在线的全部用户，定期发送以便其他实例清理过期状态
+    Heartbeat { user_ids: Vec<String> },
+}
+
+/// 带来源实例ID的事件，实例据此忽略自己发布的事件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Envelope {
+    pub origin: String,
+    pub event: BusEvent,
+}
+
+/// pub/sub 传输（Redis 或测试替身）
+pub trait MessageBus: Send + Sync {
+    /// 发布一条事件
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, Result<(), String>>;
+    /// 订阅全部事件（包括自己发布的）
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, Envelope>, String>>;
+}
+
+/// 本实例在集群中的状态
+#[derive(Clone)]
+pub struct Cluster {
+    instance_id: String,
+    bus: Option<Arc<dyn MessageBus>>,
+    // 发布按顺序经由一个队列完成，保证同一实例发出的事件顺序不变
+    outbox: Option<mpsc::UnboundedSender<Envelope>>,
+    outbox_rx: Arc<Mutex<Option<mpsc::UnboundedReceiver<Envelope>>>>,
+    // 用户ID -> (实例ID -> 最后一次确认在线的时间)
+    remote_presence: Arc<Mutex<HashMap<String, HashMap<String, Instant>>>>,
+    presence_ttl: Duration,
+}
+
+impl Cluster {
+    /// 创建集群状态，bus 为 None 时为单实例模式
+    pub fn new(bus: Option<Arc<dyn MessageBus>>, heartbeat: Duration) -> Self {
+        let (outbox, outbox_rx) = match bus {
+            Some(_) => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (Some(tx), Some(rx))
+            }
+            None => (None, None),
+        };
+        Self {
+            instance_id: uuid::Uuid::new_v4().to_string(),
+            bus,
+            outbox,
+            outbox_rx: Arc::new(Mutex::new(outbox_rx)),
+            remote_presence: Arc::new(Mutex::new(HashMap::new())),
+            // 错过两次心跳才认为对端实例上的用户已离线
+            presence_ttl: heartbeat * 3,
+        }
+    }
+
+    /// 按配置创建，未配置 redis_url 时为单实例模式
+    pub fn from_settings(settings: &ClusterSettings) -> Result<Self, String> {
+        let heartbeat = Duration::from_secs(settings.heartbeat_secs.max(1));
+        if settings.redis_url.is_empty() {
+            return Ok(Self::new(None, heartbeat));
+        }
+        let bus = redis::RedisBus::new(&settings.redis_url, &settings.channel)?;
+        Ok(Self::new(Some(Arc::new(bus)), heartbeat))
+    }
+
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    pub fn bus(&self) -> Option<&Arc<dyn MessageBus>> {
+        self.bus.as_ref()
+    }
+
+    /// 取出发布队列的接收端（只能取一次，由后台发布任务持有）
+    pub fn take_outbox(&self) -> Option<mpsc::UnboundedReceiver<Envelope>> {
+        self.outbox_rx.lock().unwrap().take()
+    }
+
+    /// 把事件放入发布队列，单实例模式下什么都不做
+    pub fn publish(&self, event: BusEvent) {
+        if let Some(outbox) = &self.outbox {
+            let _ = outbox.send(Envelope {
+                origin: self.instance_id.clone(),
+                event,
+            });
+        }
+    }
+
+    /// 用户是否在其他实例上在线
+    pub fn is_remote_online(&self, user_id: &str) -> bool {
+        let presence = self.remote_presence.lock().unwrap();
+        presence
+            .get(user_id)
+            .is_some_and(|instances| instances.values().any(|seen| seen.elapsed() < self.presence_ttl))
+    }
+
+    /// 处理其他实例发布的事件：在线状态事件在这里消化，需要投递给本地连接的事件原样返回
+    pub fn handle_remote(&self, envelope: Envelope) -> Option<BusEvent> {
+        if envelope.origin == self.instance_id {
+            return None;
+        }
+        let mut presence = self.remote_presence.lock().unwrap();
+        match envelope.event {
+            BusEvent::Online { user_id } => {
+                presence.entry(user_id).or_default().insert(envelope.origin, Instant::now());
+                None
+            }
+            BusEvent::Offline { user_id } => {
+                if let Some(instances) = presence.get_mut(&user_id) {
+                    instances.remove(&envelope.origin);
+                    if instances.is_empty() {
+                        presence.remove(&user_id);
+                    }
+                }
+                None
+            }
+            BusEvent::Heartbeat { user_ids } => {
+                let now = Instant::now();
+                for user_id in user_ids {
+                    presence.entry(user_id).or_default().insert(envelope.origin.clone(), now);
+                }
+                // 顺带清理过期的记录
+                let ttl = self.presence_ttl;
+                presence.retain(|_, instances| {
+                    instances.retain(|_, seen| seen.elapsed() < ttl);
+                    !instances.is_empty()
+                });
+                None
+            }
+            event => Some(event),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! 基于 Redis PUBLISH/SUBSCRIBE 的消息总线
+
+use futures_util::future::BoxFuture;
+use futures_util::stream::{BoxStream, StreamExt};
+use redis::AsyncCommands;
+use tokio::sync::OnceCell;
+
+use super::{Envelope, MessageBus};
+
+pub struct RedisBus {
+    client: redis::Client,
+    channel: String,
+    // 发布用的多路复用连接，第一次发布时建立
+    connection: OnceCell<redis::aio::MultiplexedConnection>,
+}
+
+impl RedisBus {
+    pub fn new(url: &str, channel: &str) -> Result<Self, String> {
+        let client = redis::Client::open(url).map_err(|e| format!("Redis 地址无效: {}", e))?;
+        Ok(Self {
+            client,
+            channel: channel.to_string(),
+            connection: OnceCell::new(),
+        })
+    }
+}
+
+impl MessageBus for RedisBus {
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let payload = serde_json::to_string(envelope).map_err(|e| e.to_string())?;
+            let connection = self.connection
+                .get_or_try_init(|| self.client.get_multiplexed_async_connection())
+                .await
+                .map_err(|e| format!("连接 Redis 失败: {}", e))?;
+            // 多路复用连接可以廉价克隆，断线后会自动重连
+            let mut connection = connection.clone();
+            connection.publish::<_, _, ()>(&self.channel, payload)
+                .await
+                .map_err(|e| format!("Redis 发布失败: {}", e))
+        })
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, Envelope>, String>> {
+        Box::pin(async move {
+            let mut pubsub = self.client.get_async_pubsub()
+                .await
+                .map_err(|e| format!("连接 Redis 失败: {}", e))?;
+            pubsub.subscribe(&self.channel)
+                .await
+                .map_err(|e| format!("Redis 订阅失败: {}", e))?;
+            let stream = pubsub.into_on_message().filter_map(|msg| async move {
+                let payload: String = msg.get_payload().ok()?;
+                serde_json::from_str(&payload).ok()
+            });
+            Ok(stream.boxed())
+        })
+    }
+}
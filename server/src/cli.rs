@@ -0,0 +1,293 @@
+use clap::{Parser, Subcommand};
+use rand::RngCore;
+use rusqlite::Connection;
+use std::path::PathBuf;
+
+use server::{
+    backup,
+    cipher,
+    datetime::unix_now,
+    doctor::{self, Status},
+    escrow,
+    import,
+    loader,
+    migrations,
+    seed::SeedOptions,
+    settings::Settings,
+    user_settings,
+    workspaces::DEFAULT_WORKSPACE,
+    DbPool
+};
+
+/// 月灵聊天服务器
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// 服务器管理子命令（不带子命令时等同于 serve）
+#[derive(Subcommand)]
+pub enum Command {
+    /// 启动 HTTP 和 WebSocket 服务
+    Serve,
+    /// 执行数据库迁移后退出
+    Migrate,
+    /// 检查配置、密钥、数据库、端口和目录权限，不修改任何数据
+    Doctor,
+    /// 生成新的管理令牌（写入配置文件 [admin] token 后生效）
+    CreateAdmin,
+    /// 用新的主密钥重新加密数据库（需先停止服务器）
+    RotateKey {
+        /// 新主密钥，建议通过环境变量提供
+        #[arg(long, env = "YUELING_NEW_MASTER_KEY", hide_env_values = true)]
+        new_master_key: String,
+    },
+    /// 用口令包装主密钥导出为密钥包，迁移服务器时在新主机上用 import-keyring 恢复
+    ExportKeyring {
+        /// 输出文件，省略时输出到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// 包装口令（至少 12 个字符），建议通过环境变量提供
+        #[arg(long, env = "YUELING_KEYRING_PASSPHRASE", hide_env_values = true)]
+        passphrase: String,
+    },
+    /// 用口令解开密钥包，恢复主密钥（写入文件后通过 YUELING_MASTER_KEY 提供给服务器）
+    ImportKeyring {
+        /// export-keyring 导出的密钥包
+        file: PathBuf,
+        /// 主密钥写入的文件（仅所有者可读），省略时输出到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// 包装口令，建议通过环境变量提供
+        #[arg(long, env = "YUELING_KEYRING_PASSPHRASE", hide_env_values = true)]
+        passphrase: String,
+    },
+    /// 立即备份数据库到 [backup] dir
+    Backup,
+    /// 从备份文件恢复数据库（需先停止服务器）
+    Restore {
+        /// 备份文件路径
+        file: PathBuf,
+    },
+    /// 导入其他系统或本服务器导出的聊天记录，保留原时间，内容按当前配置重新加密
+    ///
+    /// JSON 可以是 export-user 或会话导出的文件，也可以是与 POST /messages/batch 的 messages 字段相同的数组；
+    /// CSV 需要表头，默认读取 sender、receiver、content、created_at、message_type 列
+    Import {
+        /// 导出文件路径
+        file: PathBuf,
+        /// 导入到哪个工作区（slug）
+        #[arg(long, default_value = DEFAULT_WORKSPACE)]
+        workspace: String,
+        /// 文件格式：json 或 csv，省略时按扩展名判断
+        #[arg(long)]
+        format: Option<String>,
+        /// CSV 的列映射，如 "sender=From,receiver=To,content=Text,created_at=Date"
+        #[arg(long, default_value = "")]
+        columns: String,
+        /// CSV 中不带时区的时间按该时区换算，如 +08:00
+        #[arg(long, default_value = "+00:00", allow_hyphen_values = true)]
+        utc_offset: String,
+    },
+    /// 导出单个用户的资料、好友和消息为 JSON
+    ExportUser {
+        /// 用户ID
+        user_id: String,
+        /// 输出文件，省略时输出到标准输出
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// 生成演示数据（用户、好友、群聊和消息），用于性能测试和界面开发
+    Seed {
+        /// 用户数
+        #[arg(long, default_value_t = 50)]
+        users: usize,
+        /// 群聊数
+        #[arg(long, default_value_t = 5)]
+        groups: usize,
+        /// 每个群的成员数
+        #[arg(long, default_value_t = 10)]
+        group_size: usize,
+        /// 每个用户主动添加的好友数
+        #[arg(long, default_value_t = 5)]
+        friends_per_user: usize,
+        /// 消息总数
+        #[arg(long, default_value_t = 10_000)]
+        messages: usize,
+        /// 消息时间分布在最近多少天内
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+        /// 用户名前缀
+        #[arg(long, default_value = "demo")]
+        prefix: String,
+        /// 所有演示用户的密码
+        #[arg(long, default_value = "password")]
+        password: String,
+        /// 随机种子
+        #[arg(long, default_value_t = 2025)]
+        seed: u64,
+    },
+}
+
+// 打开数据库，开启了消息或邮箱加密时附带对应密钥
+fn open_db(settings: &Settings, db_key: Option<&str>) -> Result<DbPool, Box<dyn std::error::Error>> {
+    Ok(DbPool::with_settings(&settings.database, db_key)?.with_encryption(&settings.security)?)
+}
+
+// 写入只有所有者可读写的文件
+fn write_secret(path: &std::path::Path, secret: &str) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(path)?, secret.as_bytes())
+}
+
+/// 逐项输出自检结果，有未通过的检查时返回错误
+///
+/// 在加载配置和校验密钥之前执行，这样配置或密钥有问题时也能给出完整的报告
+pub fn doctor() -> Result<(), Box<dyn std::error::Error>> {
+    let settings = loader::load();
+    let checks = doctor::diagnose(settings.as_ref().map_err(String::as_str));
+    for check in &checks {
+        let mark = match check.status {
+            Status::Ok => "通过",
+            Status::Warn => "注意",
+            Status::Fail => "失败",
+        };
+        println!("[{}] {}: {}", mark, check.name, check.detail);
+    }
+    let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+    if failed > 0 {
+        return Err(format!("{} 项检查未通过", failed).into());
+    }
+    println!("全部检查通过");
+    Ok(())
+}
+
+/// 执行除 serve 以外的管理子命令
+pub fn run(command: Command, settings: &Settings, db_key: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Serve | Command::Doctor => unreachable!("serve 和 doctor 由 main 处理"),
+        Command::Migrate => {
+            let db = open_db(settings, db_key)?;
+            let conn = db.0.lock().unwrap();
+            println!("数据库架构版本: {}", migrations::current_version(&conn)?);
+        }
+        Command::CreateAdmin => {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            println!("新的管理令牌（请写入配置文件 [admin] token 并重启服务器）：");
+            println!("{}", hex::encode(bytes));
+        }
+        Command::RotateKey { new_master_key } => {
+            let Some(old_key) = db_key else {
+                return Err("数据库未加密（database.encrypt = false），无需轮换密钥".into());
+            };
+            if new_master_key.is_empty() {
+                return Err("新主密钥不能为空".into());
+            }
+            let conn = Connection::open(&settings.database.path)?;
+            cipher::apply_key(&conn, old_key)?;
+            cipher::rekey(&conn, &cipher::database_key(&new_master_key))?;
+            println!("数据库已用新主密钥重新加密，请更新 YUELING_MASTER_KEY；旧备份仍需旧主密钥恢复");
+        }
+        Command::ExportKeyring { output, passphrase } => {
+            if settings.security.master_key.is_empty() {
+                return Err("未配置主密钥（security.master_key 或 YUELING_MASTER_KEY），没有可导出的密钥".into());
+            }
+            let now = unix_now();
+            let bundle = escrow::export(&settings.security.master_key, &passphrase, now)?;
+            let json = serde_json::to_string_pretty(&bundle)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    println!("已导出密钥包到 {}（主密钥指纹 {}），请与口令分开保管", path.display(), bundle.fingerprint);
+                }
+                None => println!("{}", json),
+            }
+        }
+        Command::ImportKeyring { file, output, passphrase } => {
+            let bundle: escrow::KeyringBundle = serde_json::from_slice(&std::fs::read(&file)?)?;
+            let master_key = escrow::import(&bundle, &passphrase)?;
+            // 数据库已加密时确认恢复的主密钥能打开本机的数据库
+            if settings.database.encrypt {
+                DbPool::open_read_only(&settings.database, Some(&cipher::database_key(&master_key)))
+                    .map_err(|e| format!("恢复的主密钥无法打开数据库 {}: {}", settings.database.path, e))?;
+            }
+            match output {
+                Some(path) => {
+                    write_secret(&path, &master_key)?;
+                    println!("已恢复主密钥（指纹 {}）到 {}，请通过 YUELING_MASTER_KEY 提供给服务器", bundle.fingerprint, path.display());
+                }
+                None => println!("{}", master_key),
+            }
+            if !settings.security.master_key.is_empty() && settings.security.master_key != master_key {
+                eprintln!("注意：恢复的主密钥与当前配置的主密钥不同");
+            }
+        }
+        Command::Backup => {
+            let db = open_db(settings, db_key)?;
+            let dir = std::path::Path::new(&settings.backup.dir);
+            let path = db.backup_to_dir(dir, db_key)?;
+            println!("已备份到 {}", path.display());
+            for removed in backup::prune_backups(dir, settings.backup.keep)? {
+                println!("已删除过期备份: {}", removed.display());
+            }
+        }
+        Command::Restore { file } => {
+            backup::restore_from(&settings.database.path, &file, db_key)?;
+            println!("已从 {} 恢复数据库到 {}", file.display(), settings.database.path);
+        }
+        Command::Import { file, workspace, format, columns, utc_offset } => {
+            let text = std::fs::read_to_string(&file)?;
+            let format = format.unwrap_or_else(|| {
+                let csv = file.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv"));
+                if csv { "csv" } else { "json" }.to_string()
+            });
+            let records = match format.as_str() {
+                "json" => import::parse_json(&text)?,
+                "csv" => {
+                    let offset = user_settings::parse_utc_offset(&utc_offset)
+                        .ok_or_else(|| format!("时区 {} 无效，应写成 +HH:MM 或 -HH:MM", utc_offset))?;
+                    import::parse_csv(&text, &import::CsvColumns::parse(&columns)?, offset)?
+                }
+                other => return Err(format!("不支持的格式 {}，可用 json 或 csv", other).into()),
+            };
+            let db = open_db(settings, db_key)?;
+            let workspace = db.get_workspace_by_slug(&workspace)?
+                .ok_or_else(|| format!("工作区 {} 不存在", workspace))?;
+            let now = unix_now();
+            let report = db.import_messages(&workspace.id, records, now)?;
+            println!("已导入 {} 条消息，跳过 {} 条已存在的消息", report.imported, report.skipped);
+        }
+        Command::ExportUser { user_id, output } => {
+            let db = open_db(settings, db_key)?;
+            let export = db.export_user(&user_id).map_err(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => format!("用户 {} 不存在", user_id),
+                other => other.to_string(),
+            })?;
+            let json = serde_json::to_string_pretty(&export)?;
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, json)?;
+                    println!("已导出 {} 条消息到 {}", export.messages.len(), path.display());
+                }
+                None => println!("{}", json),
+            }
+        }
+        Command::Seed { users, groups, group_size, friends_per_user, messages, days, prefix, password, seed } => {
+            let options = SeedOptions { users, groups, group_size, friends_per_user, messages, days, prefix, password, seed };
+            let db = open_db(settings, db_key)?;
+            let now = unix_now();
+            let report = db.seed_demo_data(&options, now)?;
+            println!(
+                "已生成 {} 个用户、{} 对好友、{} 个群聊、{} 条消息（密码均为 {}）",
+                report.users, report.friendships, report.groups, report.messages, options.password
+            );
+        }
+    }
+    Ok(())
+}
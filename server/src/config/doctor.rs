@@ -0,0 +1,208 @@
+use rusqlite::{Connection, OpenFlags};
+use std::net::TcpListener;
+use std::path::Path;
+
+use super::settings::Settings;
+use crate::crypto::{conversation::ConversationKeys, field_crypto, hardware_accelerated};
+use crate::storage::{cipher, migrations};
+
+// 检查结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    // 能启动，但建议处理
+    Warn,
+    // 服务器会拒绝启动或运行时出错
+    Fail,
+}
+
+// 单项检查：名称、结果和说明（失败时说明如何处理）
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, status: Status, detail: impl Into<String>) -> Self {
+        Self { name: name.into(), status, detail: detail.into() }
+    }
+}
+
+// yueling doctor：不修改任何数据地检查配置、密钥、数据库、端口和目录权限
+// 配置加载失败时其余检查依赖的配置不可信，只返回这一项
+pub fn diagnose(settings: Result<&Settings, &str>) -> Vec<Check> {
+    let settings = match settings {
+        Ok(settings) => settings,
+        Err(e) => return vec![Check::new("配置", Status::Fail, format!("{}，请修正配置文件或 YUELING_CONFIG 指向的路径", e))],
+    };
+
+    let mut checks = vec![check_config(settings)];
+    checks.extend(check_keys(settings));
+    checks.push(match cipher::resolve_key(settings) {
+        Ok(key) => check_database(settings, key.as_deref()),
+        Err(e) => Check::new("数据库", Status::Fail, e),
+    });
+    checks.extend(check_ports(settings));
+    checks.extend(check_dirs(settings));
+    checks
+}
+
+// 文件能解析后再检查会导致启动失败的组合
+fn check_config(settings: &Settings) -> Check {
+    if !settings.server.tcp && settings.server.unix_socket.is_empty() {
+        return Check::new("配置", Status::Fail, "server.tcp 为 false 时必须配置 server.unix_socket");
+    }
+    if settings.grpc.port != 0 && settings.server.tcp && settings.grpc.port == settings.server.port {
+        return Check::new("配置", Status::Fail, format!("grpc.port 与 server.port 相同（{}），请改用其他端口", settings.server.port));
+    }
+    Check::new("配置", Status::Ok, "配置文件有效")
+}
+
+// 主密钥是否齐全，并用它做一次加解密往返
+fn check_keys(settings: &Settings) -> Vec<Check> {
+    let mut checks = Vec::new();
+    match ConversationKeys::from_settings(&settings.security) {
+        Err(e) => checks.push(Check::new("消息加密", Status::Fail, e)),
+        Ok(None) => checks.push(Check::new("消息加密", Status::Ok, "未开启（security.encrypt_messages = false）")),
+        Ok(Some(keys)) => {
+            let sealed = keys.seal_bytes("doctor", 0, "doctor", "月灵".as_bytes());
+            checks.push(match keys.open_bytes("doctor", "doctor", &sealed) {
+                Ok(text) if text == "月灵" => Check::new("消息加密", Status::Ok, "加解密往返正常"),
+                _ => Check::new("消息加密", Status::Fail, "加密后无法解密，请检查主密钥是否完整"),
+            });
+        }
+    }
+    match field_crypto(&settings.security) {
+        Err(e) => checks.push(Check::new("邮箱加密", Status::Fail, e)),
+        Ok(None) => checks.push(Check::new("邮箱加密", Status::Ok, "未开启（security.encrypt_emails = false）")),
+        Ok(Some(crypto)) => {
+            let sealed = crypto.encrypt(b"doctor@example.com", b"doctor");
+            checks.push(match crypto.decrypt(&sealed, b"doctor") {
+                Ok(plain) if plain == b"doctor@example.com" => Check::new("邮箱加密", Status::Ok, "加解密往返正常"),
+                _ => Check::new("邮箱加密", Status::Fail, "加密后无法解密，请检查主密钥是否完整"),
+            });
+        }
+    }
+    let encrypting = settings.security.encrypt_messages || settings.security.encrypt_emails;
+    if encrypting && !hardware_accelerated() {
+        checks.push(Check::new("AES 硬件加速", Status::Warn, "当前 CPU 不支持，消息加解密将使用较慢的软件实现"));
+    }
+    checks
+}
+
+// 以只读方式打开数据库，检查密钥、完整性和架构版本；文件不存在时不创建
+fn check_database(settings: &Settings, key: Option<&str>) -> Check {
+    let path = &settings.database.path;
+    if !Path::new(path).exists() {
+        return Check::new("数据库", Status::Warn, format!("{} 不存在，首次启动时创建", path));
+    }
+    let conn = match Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) {
+        Ok(conn) => conn,
+        Err(e) => return Check::new("数据库", Status::Fail, format!("打开 {} 失败: {}，请检查文件权限", path, e)),
+    };
+    let readable = match key {
+        Some(key) => cipher::apply_key(&conn, key).map_err(|e| format!("{}，请检查主密钥", e)),
+        None => conn
+            .query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+            .map(|_| ())
+            .map_err(|e| format!("{}，文件可能已加密（请检查 database.encrypt 与主密钥）或已损坏", e)),
+    };
+    if let Err(e) = readable {
+        return Check::new("数据库", Status::Fail, format!("读取 {} 失败: {}", path, e));
+    }
+    let problems: Vec<String> = match conn.prepare("PRAGMA quick_check").and_then(|mut stmt| {
+        stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<String>>>()
+    }) {
+        Ok(rows) => rows.into_iter().filter(|r| r != "ok").collect(),
+        Err(e) => return Check::new("数据库", Status::Fail, format!("完整性检查失败: {}", e)),
+    };
+    if !problems.is_empty() {
+        return Check::new(
+            "数据库",
+            Status::Fail,
+            format!("完整性检查发现问题：{}；请从备份恢复（yueling restore）", problems.join("; ")),
+        );
+    }
+    let version = match migrations::current_version(&conn) {
+        Ok(version) => version,
+        Err(e) => return Check::new("数据库", Status::Fail, format!("读取架构版本失败: {}", e)),
+    };
+    let latest = migrations::latest_version();
+    if version > latest {
+        Check::new("数据库", Status::Fail, format!("架构版本 {} 高于当前程序支持的 {}，请使用更新版本的服务器", version, latest))
+    } else if version < latest {
+        Check::new("数据库", Status::Warn, format!("架构版本 {}，有 {} 个迁移待执行（启动时自动执行，或运行 yueling migrate）", version, latest - version))
+    } else {
+        Check::new("数据库", Status::Ok, format!("架构版本 {}", version))
+    }
+}
+
+// 尝试绑定服务器要监听的端口；服务器正在运行时这里会报告端口被占用
+fn check_ports(settings: &Settings) -> Vec<Check> {
+    let mut ports = Vec::new();
+    if settings.server.tcp {
+        ports.push(("HTTP 端口", "server.port", settings.server.port));
+    }
+    if settings.grpc.port != 0 {
+        ports.push(("gRPC 端口", "grpc.port", settings.grpc.port));
+    }
+    ports
+        .into_iter()
+        .map(|(name, key, port)| {
+            let addr = format!("{}:{}", settings.server.host, port);
+            match TcpListener::bind(&addr) {
+                Ok(_) => Check::new(name, Status::Ok, format!("{} 可用", addr)),
+                Err(e) => Check::new(
+                    name,
+                    Status::Fail,
+                    format!("无法监听 {}: {}；请停止占用端口的进程或修改 {}", addr, e, key),
+                ),
+            }
+        })
+        .collect()
+}
+
+// 附件和备份目录需要可写；目录不存在时检查能否在最近的上级目录中创建
+fn check_dirs(settings: &Settings) -> Vec<Check> {
+    [
+        ("附件目录", "attachments.dir", settings.attachments.dir.as_str()),
+        ("备份目录", "backup.dir", settings.backup.dir.as_str()),
+    ]
+    .into_iter()
+    .map(|(name, key, dir)| {
+        let path = Path::new(dir);
+        if path.is_dir() {
+            return match probe_writable(path) {
+                Ok(()) => Check::new(name, Status::Ok, format!("{} 可写", dir)),
+                Err(e) => Check::new(name, Status::Fail, format!("{} 不可写: {}；请修改目录权限或 {}", dir, e, key)),
+            };
+        }
+        if path.exists() {
+            return Check::new(name, Status::Fail, format!("{} 不是目录，请修改 {}", dir, key));
+        }
+        let parent = path
+            .ancestors()
+            .skip(1)
+            .find(|p| p.as_os_str().is_empty() || p.is_dir())
+            .map(|p| if p.as_os_str().is_empty() { Path::new(".") } else { p })
+            .unwrap_or(Path::new("."));
+        match probe_writable(parent) {
+            Ok(()) => Check::new(name, Status::Warn, format!("{} 不存在，首次使用时创建", dir)),
+            Err(e) => Check::new(
+                name,
+                Status::Fail,
+                format!("{} 不存在且无法在 {} 中创建: {}；请手动创建目录或修改 {}", dir, parent.display(), e, key),
+            ),
+        }
+    })
+    .collect()
+}
+
+// 写入并删除一个临时文件，比检查权限位更可靠（ACL、只读挂载等）
+fn probe_writable(dir: &Path) -> std::io::Result<()> {
+    let probe = dir.join(format!(".yueling-doctor-{}", std::process::id()));
+    std::fs::OpenOptions::new().write(true).create_new(true).open(&probe)?;
+    std::fs::remove_file(&probe)
+}
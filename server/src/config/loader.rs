@@ -0,0 +1,33 @@
+use std::fs;
+use std::path::Path;
+
+use super::settings::Settings;
+
+// 默认配置文件路径，可通过环境变量 YUELING_CONFIG 覆盖
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+// 主密钥环境变量，优先于配置文件中的 security.master_key
+pub const MASTER_KEY_ENV: &str = "YUELING_MASTER_KEY";
+
+// 加载配置：文件不存在时使用默认值，再应用环境变量覆盖
+pub fn load() -> Result<Settings, String> {
+    let path = std::env::var("YUELING_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+    let mut settings = load_from(&path)?;
+    if let Ok(master_key) = std::env::var(MASTER_KEY_ENV) {
+        settings.security.master_key = master_key;
+    }
+    Ok(settings)
+}
+
+// 从指定路径加载配置
+pub fn load_from(path: impl AsRef<Path>) -> Result<Settings, String> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(Settings::default());
+    }
+
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("读取配置文件 {} 失败: {}", path.display(), e))?;
+    toml::from_str(&text)
+        .map_err(|e| format!("解析配置文件 {} 失败: {}", path.display(), e))
+}
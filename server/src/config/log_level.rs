@@ -0,0 +1,50 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// 日志级别，从严重到详细；低于当前级别的日志不输出
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+// 可选的级别名称，与 Level 的顺序一致
+pub const LEVELS: &[&str] = &["error", "warn", "info", "debug"];
+
+static CURRENT: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        LEVELS[self as usize]
+    }
+}
+
+// 当前的日志级别（进程内全局，管理员可在运行时修改）
+pub fn current() -> Level {
+    match CURRENT.load(Ordering::Relaxed) {
+        0 => Level::Error,
+        1 => Level::Warn,
+        2 => Level::Info,
+        _ => Level::Debug,
+    }
+}
+
+pub fn set(level: Level) {
+    CURRENT.store(level as u8, Ordering::Relaxed);
+}
+
+// 该级别的日志是否输出
+pub fn enabled(level: Level) -> bool {
+    level <= current()
+}
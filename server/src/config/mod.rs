@@ -1,3 +1,5 @@
+pub mod doctor;
 pub mod loader;
+pub mod log_level;
 pub mod settings;
 
@@ -0,0 +1,691 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+// 服务器全局配置（对应 config.toml）
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub admin: AdminSettings,
+    pub backup: BackupSettings,
+    pub retention: RetentionSettings,
+    pub security: SecuritySettings,
+    pub webhooks: WebhookSettings,
+    pub rate_limits: RateLimitSettings,
+    pub push: PushSettings,
+    pub email: EmailSettings,
+    pub digest: DigestSettings,
+    pub federation: FederationSettings,
+    pub cluster: ClusterSettings,
+    pub jobs: JobSettings,
+    pub grpc: GrpcSettings,
+    pub access: AccessSettings,
+    pub registration: RegistrationSettings,
+    pub username: UsernameSettings,
+    pub oauth: OAuthSettings,
+    pub devices: DeviceSettings,
+    pub attachments: AttachmentSettings,
+    pub scan: ScanSettings,
+    pub quotas: QuotaSettings,
+    pub outbox: OutboxSettings,
+    pub maintenance: MaintenanceSettings,
+    pub translation: TranslationSettings,
+    pub calls: CallSettings,
+    pub relay: RelaySettings,
+    pub metrics: MetricsSettings,
+}
+
+// HTTP/WebSocket 监听配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    pub tcp: bool,                // 是否监听 TCP，只用 Unix 套接字时可以关闭
+    pub unix_socket: String,      // Unix 套接字路径，留空则不监听（仅 Unix 系统）
+    pub ws_send_queue: usize,     // 每个 WebSocket 连接最多排队的待发送事件数，满了按优先级丢弃
+    pub task_restart_backoff_ms: u64,  // 后台任务 panic 后第一次重启前的等待时间，之后每次翻倍，最多一分钟
+    pub shutdown_timeout_secs: u64,    // 关闭时等待后台任务停止和每个关闭钩子的最长时间
+    pub slow_request_ms: u64,     // 处理时间超过该值的 HTTP 请求打印到日志，0 表示不记录
+    pub log_level: String,        // 日志级别：error、warn、info 或 debug，管理员可在运行时修改
+    pub public_url: String,       // 客户端访问服务器的地址，如 https://chat.example.com，导出文件中的链接以此开头
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".into(),
+            port: 2025,
+            tcp: true,
+            unix_socket: String::new(),
+            ws_send_queue: 256,
+            task_restart_backoff_ms: 1000,
+            shutdown_timeout_secs: 10,
+            slow_request_ms: 1000,
+            log_level: "info".into(),
+            public_url: String::new(),
+        }
+    }
+}
+
+// SQLite 数据库配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DatabaseSettings {
+    pub path: String,
+    pub journal_mode: String,     // 日志模式，默认 WAL，读写可并发
+    pub synchronous: String,      // 同步级别，WAL 下 NORMAL 已足够安全
+    pub busy_timeout_ms: u64,     // 遇到锁时的等待时间，避免直接返回 database is locked
+    pub foreign_keys: bool,       // 是否启用外键约束
+    pub encrypt: bool,            // 是否用 SQLCipher 加密数据库文件（需启用 sqlcipher 特性）
+    pub cache_capacity: u64,      // 每类热点缓存（用户、群成员）的最大条目数
+    pub cache_ttl_secs: u64,      // 缓存条目的存活时间
+    pub on_integrity_failure: String, // 启动自检失败时的处理：refuse 拒绝启动，read_only 以只读模式启动
+    pub slow_query_ms: u64,       // 执行时间超过该值的语句连同参数（隐去敏感值）打印到日志，0 表示不记录
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        Self {
+            path: "server.db".into(),
+            journal_mode: "WAL".into(),
+            synchronous: "NORMAL".into(),
+            busy_timeout_ms: 5000,
+            foreign_keys: true,
+            encrypt: false,
+            cache_capacity: 10_000,
+            cache_ttl_secs: 300,
+            on_integrity_failure: "refuse".into(),
+            slow_query_ms: 200,
+        }
+    }
+}
+
+// 管理接口配置
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AdminSettings {
+    pub token: String,            // 管理令牌，为空时禁用所有 /admin 接口
+}
+
+// 监控指标配置
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MetricsSettings {
+    pub enabled: bool,            // 是否提供 GET /metrics
+    pub token: String,            // 非空时抓取请求需要 Authorization: Bearer <token>
+}
+
+// 数据库备份配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackupSettings {
+    pub dir: String,              // 备份文件目录
+    pub interval_secs: u64,       // 自动备份间隔，0 表示关闭
+    pub keep: usize,              // 保留最近的备份数量
+}
+
+impl Default for BackupSettings {
+    fn default() -> Self {
+        Self {
+            dir: "backups".into(),
+            interval_secs: 0,
+            keep: 7,
+        }
+    }
+}
+
+// 数据保留策略配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetentionSettings {
+    pub run_interval_secs: u64,   // 维护任务执行间隔，0 表示关闭
+    pub message_days: u64,        // 消息默认保留天数，0 表示永久保留（群聊可单独覆盖）
+    pub vacuum_interval_secs: u64, // VACUUM 间隔，0 表示不执行
+    pub restore_grace_secs: u64,  // 软删除后允许撤销的时间窗口
+    pub purge_deleted_days: u64,  // 软删除数据在多少天后彻底清除，0 表示不清除
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        Self {
+            run_interval_secs: 3600,
+            message_days: 0,
+            vacuum_interval_secs: 0,
+            restore_grace_secs: 300,
+            purge_deleted_days: 30,
+        }
+    }
+}
+
+// 出站 webhook 投递配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WebhookSettings {
+    pub dispatch_interval_secs: u64, // 投递任务轮询间隔，0 表示关闭
+    pub max_attempts: i64,        // 最多尝试次数，超过后标记为失败
+    pub timeout_secs: u64,        // 单次请求超时
+    pub batch_size: i64,          // 每轮最多投递的条数
+}
+
+impl Default for WebhookSettings {
+    fn default() -> Self {
+        Self {
+            dispatch_interval_secs: 5,
+            max_attempts: 5,
+            timeout_secs: 10,
+            batch_size: 50,
+        }
+    }
+}
+
+// 限流档位配置：普通用户、机器人和管理员分别限制每分钟的请求数和发消息数
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitSettings {
+    pub user: RateTierSettings,
+    pub bot: RateTierSettings,    // 机器人的请求数按 API 密钥计算，发消息数按机器人计算
+    pub admin: RateTierSettings,
+    pub admin_user_ids: Vec<String>, // 按管理员档位限流的用户
+}
+
+impl Default for RateLimitSettings {
+    fn default() -> Self {
+        Self {
+            user: RateTierSettings { requests_per_minute: 300, messages_per_minute: 60 },
+            bot: RateTierSettings { requests_per_minute: 600, messages_per_minute: 300 },
+            admin: RateTierSettings { requests_per_minute: 0, messages_per_minute: 0 },
+            admin_user_ids: Vec::new(),
+        }
+    }
+}
+
+// 一个限流档位，0 表示不限
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateTierSettings {
+    pub requests_per_minute: u32, // 需要登录的 REST 接口和机器人 API 的请求数
+    pub messages_per_minute: u32, // 经 REST、WebSocket、gRPC 或机器人 API 发送的消息数
+}
+
+// 离线推送配置（对应字段为空时不启用该平台）
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PushSettings {
+    pub fcm_service_account_file: String, // Firebase 服务账号 JSON 文件
+    pub apns_key_file: String,    // APNs 令牌认证密钥（.p8 文件）
+    pub apns_key_id: String,
+    pub apns_team_id: String,
+    pub apns_topic: String,       // 应用的 Bundle ID
+    pub apns_sandbox: bool,       // 是否使用 APNs 沙盒环境（开发版应用）
+}
+
+// 邮件发送配置（smtp_host 为空时不发送邮件）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct EmailSettings {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub smtp_tls: String,         // starttls、tls（隐式 TLS，通常 465 端口）或 none（仅限本机测试）
+    pub from: String,             // 发件人，如 "月灵 <noreply@example.com>"
+}
+
+impl Default for EmailSettings {
+    fn default() -> Self {
+        Self {
+            smtp_host: String::new(),
+            smtp_port: 587,
+            smtp_username: String::new(),
+            smtp_password: String::new(),
+            smtp_tls: "starttls".into(),
+            from: String::new(),
+        }
+    }
+}
+
+// 未读消息邮件摘要配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DigestSettings {
+    pub interval_secs: u64,       // 摘要任务执行间隔，0 表示关闭
+    pub offline_secs: u64,        // 用户离线超过该时长才发送摘要
+    pub max_messages: usize,      // 每封摘要最多列出的消息条数
+}
+
+impl Default for DigestSettings {
+    fn default() -> Self {
+        Self {
+            interval_secs: 3600,
+            offline_secs: 86400,
+            max_messages: 20,
+        }
+    }
+}
+
+// 消息翻译配置（backend 为空时不提供翻译）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TranslationSettings {
+    pub backend: String,          // libretranslate（兼容 LibreTranslate 的 HTTP 接口）或 command（本机程序，如本地模型）
+    pub url: String,              // libretranslate 接口地址，如 http://127.0.0.1:5000
+    pub api_key: String,          // libretranslate 的 API 密钥，不需要时留空
+    pub command: Vec<String>,     // command 的程序和参数，参数中的 {lang} 替换为目标语言；原文从标准输入读入，译文写到标准输出
+    pub timeout_secs: u64,        // 单次翻译的超时时间
+    pub max_chars: usize,         // 超过该长度的消息不翻译
+}
+
+impl Default for TranslationSettings {
+    fn default() -> Self {
+        Self {
+            backend: String::new(),
+            url: String::new(),
+            api_key: String::new(),
+            command: Vec::new(),
+            timeout_secs: 10,
+            max_chars: 5000,
+        }
+    }
+}
+
+// 一对一通话配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CallSettings {
+    pub ring_timeout_secs: u64,   // 响铃超过该时长仍未接听时由服务器结束通话，记为未接来电
+    pub stun_urls: Vec<String>,   // 下发给客户端的 STUN 服务器，如 "stun:stun.example.com:3478"
+    pub turn_urls: Vec<String>,   // 下发给客户端的 TURN 服务器，如 "turn:turn.example.com:3478?transport=udp"
+    pub turn_secret: String,      // 与 TURN 服务器共享的密钥（coturn 的 static-auth-secret），为空则不下发 TURN
+    pub turn_ttl_secs: u64,       // TURN 临时凭据的有效期
+}
+
+impl Default for CallSettings {
+    fn default() -> Self {
+        Self {
+            ring_timeout_secs: 45,
+            stun_urls: Vec::new(),
+            turn_urls: Vec::new(),
+            turn_secret: String::new(),
+            turn_ttl_secs: 86400,
+        }
+    }
+}
+
+// 两个客户端之间的二进制流中转配置（无法建立点对点连接时传大文件、共享屏幕）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RelaySettings {
+    pub enabled: bool,
+    pub bytes_per_sec: u64,         // 每个中转每个方向的带宽上限
+    pub max_chunk_bytes: usize,     // 单帧的大小上限，超过时断开中转
+    pub join_timeout_secs: u64,     // 创建后双方都要在该时长内连上，否则作废
+    pub max_per_user: usize,        // 每个用户同时参与（含等待对方连接）的中转数上限
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bytes_per_sec: 1024 * 1024,
+            max_chunk_bytes: 64 * 1024,
+            join_timeout_secs: 60,
+            max_per_user: 2,
+        }
+    }
+}
+
+// 聊天附件配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AttachmentSettings {
+    pub dir: String,              // 附件保存目录
+    pub max_bytes: u64,           // 单个附件的大小上限
+    pub gc_interval_secs: u64,    // 回收附件的间隔，0 表示不自动回收
+    pub gc_grace_secs: u64,       // 目录中没有附件记录的文件至少存在这么久才删除，避免删掉正在上传的文件
+    pub gc_verify_checksums: bool, // 回收时是否校验其余附件文件的 SHA-256
+    pub strip_image_metadata: bool, // 是否去掉上传图片中的 EXIF（含 GPS 位置）等元数据
+    pub signed_url_ttl_secs: u64, // 签名下载链接的有效期，0 表示不签发
+    pub export_inline_max_bytes: u64, // 导出 HTML 时内嵌为缩略图的图片大小上限，0 表示不内嵌
+}
+
+impl Default for AttachmentSettings {
+    fn default() -> Self {
+        Self {
+            dir: "uploads/attachments".into(),
+            max_bytes: 100 * 1024 * 1024,
+            gc_interval_secs: 86_400,
+            gc_grace_secs: 3600,
+            gc_verify_checksums: true,
+            strip_image_metadata: true,
+            signed_url_ttl_secs: 3600,
+            export_inline_max_bytes: 512 * 1024,
+        }
+    }
+}
+
+// 附件病毒扫描配置（backend 为空时不扫描）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ScanSettings {
+    pub backend: String,          // clamav（通过 clamd 的 INSTREAM 命令扫描）
+    pub clamav_address: String,   // clamd 的地址：host:port，或 unix: 加套接字路径
+    pub timeout_secs: u64,        // 单个文件的扫描超时
+    pub action: String,           // 检出病毒时：reject 拒绝上传，quarantine 保存但隔离，等待管理员审核
+}
+
+impl Default for ScanSettings {
+    fn default() -> Self {
+        Self {
+            backend: String::new(),
+            clamav_address: "127.0.0.1:3310".into(),
+            timeout_secs: 60,
+            action: "reject".into(),
+        }
+    }
+}
+
+// 默认配额（管理员可以通过 /admin/quotas 为单个用户或群覆盖），0 表示不限制；
+// 单个附件的大小上限沿用 [attachments] max_bytes
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QuotaSettings {
+    pub max_message_chars: u64,   // 单条消息的最大字符数
+    pub max_storage_bytes: u64,   // 每个用户附件的总字节数
+}
+
+impl Default for QuotaSettings {
+    fn default() -> Self {
+        Self {
+            max_message_chars: 10_000,
+            max_storage_bytes: 0,
+        }
+    }
+}
+
+// 实时推送发件箱：私聊消息的推送在接收者确认送达前保留，在线时按间隔重发
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OutboxSettings {
+    pub redeliver_interval_secs: u64, // 重发任务轮询间隔，0 表示关闭
+    pub redeliver_after_secs: i64,    // 推送后多久未确认则重发
+    pub max_attempts: i64,            // 最多重发次数，超过后放弃
+    pub ttl_secs: i64,                // 记录最长保留时间，更早的消息由客户端重连后同步
+    pub batch_size: i64,              // 每轮最多处理的条数
+}
+
+impl Default for OutboxSettings {
+    fn default() -> Self {
+        Self {
+            redeliver_interval_secs: 10,
+            redeliver_after_secs: 30,
+            max_attempts: 5,
+            ttl_secs: 3600,
+            batch_size: 100,
+        }
+    }
+}
+
+// 服务器间联邦配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FederationSettings {
+    pub enabled: bool,
+    pub server_name: String,      // 本服的对外主机名，即远端用户地址 user@server_name 中的部分
+    pub signing_key_file: String, // Ed25519 签名密钥文件，不存在时自动生成
+    pub client_cert_file: String, // 双向 TLS 的客户端证书和私钥（PEM），为空时不出示客户端证书
+    pub client_key_file: String,
+    pub ca_file: String,          // 额外信任的 CA 证书（PEM），用于对端使用私有 CA 的情况
+    pub timeout_secs: u64,        // 转发请求超时
+}
+
+impl Default for FederationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server_name: String::new(),
+            signing_key_file: "federation.key".into(),
+            client_cert_file: String::new(),
+            client_key_file: String::new(),
+            ca_file: String::new(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+// 多实例部署配置（redis_url 为空时为单实例模式）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ClusterSettings {
+    pub redis_url: String,        // 如 redis://127.0.0.1:6379
+    pub channel: String,          // 实例之间通信使用的 Redis 频道
+    pub heartbeat_secs: u64,      // 在线状态心跳间隔
+}
+
+impl Default for ClusterSettings {
+    fn default() -> Self {
+        Self {
+            redis_url: String::new(),
+            channel: "yueling:events".into(),
+            heartbeat_secs: 15,
+        }
+    }
+}
+
+// 后台任务队列配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct JobSettings {
+    pub workers: usize,           // 并发执行任务的 worker 数量，0 表示不执行队列中的任务
+    pub poll_interval_ms: u64,    // 队列为空时的轮询间隔
+    pub max_attempts: i64,        // 最多尝试次数，超过后进入死信状态
+    pub export_dir: String,       // 用户数据导出文件目录
+    pub rekey_batch_size: i64,    // 重新加密任务每批处理的消息数
+    pub rekey_batch_delay_ms: u64, // 重新加密任务两批之间的间隔，避免长时间占用数据库
+}
+
+impl Default for JobSettings {
+    fn default() -> Self {
+        Self {
+            workers: 2,
+            poll_interval_ms: 1000,
+            max_attempts: 5,
+            export_dir: "exports".into(),
+            rekey_batch_size: 500,
+            rekey_batch_delay_ms: 200,
+        }
+    }
+}
+
+// gRPC 接口配置（与 HTTP 监听同一个 host）
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GrpcSettings {
+    pub port: u16,                // 监听端口，0 表示不启用
+    pub token: String,            // 调用方需携带 authorization: Bearer <token>，留空则不校验
+}
+
+// 按来源 IP 的访问控制（CIDR，单个地址也可以，如 10.0.0.0/8、::1）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AccessSettings {
+    pub allow: Vec<String>,        // 非空时只允许这些网段访问
+    pub deny: Vec<String>,         // 优先于 allow
+    pub admin_allow: Vec<String>,  // 非空时 /admin 接口只允许这些网段访问
+    pub trust_forwarded_for: bool, // 部署在反向代理之后时，以 X-Forwarded-For 中由代理追加的地址为来源 IP
+    pub forwarded_hops: usize,     // 前面可信代理的层数，取 X-Forwarded-For 从右数第几个地址（1 为最右边）
+}
+
+impl Default for AccessSettings {
+    fn default() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: Vec::new(),
+            admin_allow: Vec::new(),
+            trust_forwarded_for: false,
+            forwarded_hops: 1,
+        }
+    }
+}
+
+// 维护模式（通过 /admin/maintenance 开启和关闭）
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MaintenanceSettings {
+    pub exempt_user_ids: Vec<String>, // 维护期间仍可登录、发消息并保持 WebSocket 连接的用户（管理员、运维账号）
+}
+
+// 注册防刷配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RegistrationSettings {
+    pub challenge: String,           // "off"、"pow"（服务器出题的工作量证明）或 "hcaptcha"
+    pub pow_difficulty: u32,         // 工作量证明要求哈希前导零的位数，每加 1 位计算量翻倍
+    pub pow_ttl_secs: i64,           // 题目有效期
+    pub hcaptcha_sitekey: String,    // 前端渲染 hCaptcha 组件所需
+    pub hcaptcha_secret: String,
+    pub hcaptcha_verify_url: String,
+    pub email_required: bool,          // 注册时必须填写邮箱
+    pub email_verification: bool,      // 注册或修改邮箱后发送验证邮件（需要配置 [email]）
+    pub email_token_ttl_secs: i64,     // 验证令牌有效期
+    pub email_verify_link: String,     // 邮件中的验证链接，{token} 替换为令牌；为空时只在邮件中给出令牌
+    pub require_verified_email: bool,  // 验证邮箱之前不能发消息
+    pub invite_only: bool,             // 关闭开放注册：注册到默认工作区需要邀请码（POST /invites 签发）
+    pub invites_per_user: i64,         // 每个用户可签发的邀请码数（已使用和未过期的计入），0 表示只有管理员可以签发
+}
+
+impl Default for RegistrationSettings {
+    fn default() -> Self {
+        Self {
+            challenge: "off".into(),
+            pow_difficulty: 20,
+            pow_ttl_secs: 300,
+            hcaptcha_sitekey: String::new(),
+            hcaptcha_secret: String::new(),
+            hcaptcha_verify_url: "https://api.hcaptcha.com/siteverify".into(),
+            email_required: false,
+            email_verification: false,
+            email_token_ttl_secs: 86400,
+            email_verify_link: String::new(),
+            require_verified_email: false,
+            invite_only: false,
+            invites_per_user: 0,
+        }
+    }
+}
+
+// 用户名规则，注册和改名时校验；保留名和敏感词按归一化后的形式比较
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct UsernameSettings {
+    pub min_length: usize,             // 按字符计数
+    pub max_length: usize,
+    pub allowed_ranges: Vec<String>,   // 允许的码位范围，如 "0030-0039"、"4E00-9FFF"、"005F"；为空时允许任意字母、数字和 _ - .
+    pub allow_mixed_scripts: bool,     // 是否允许混用多种文字（如拉丁字母夹杂西里尔字母）
+    pub reserved: Vec<String>,         // 不能注册的完整用户名
+    pub blocked_words: Vec<String>,    // 不能出现在用户名中的词语
+    pub display_name_max_length: usize,   // 显示名称（昵称）的最大长度，按字符计数
+    pub display_name_cooldown_secs: u64,  // 两次修改显示名称之间的最短间隔，0 表示不限制
+}
+
+impl Default for UsernameSettings {
+    fn default() -> Self {
+        Self {
+            min_length: 3,
+            max_length: 32,
+            allowed_ranges: Vec::new(),
+            allow_mixed_scripts: false,
+            reserved: ["admin", "administrator", "root", "system", "support", "moderator", "official", "yueling"]
+                .map(String::from)
+                .to_vec(),
+            blocked_words: Vec::new(),
+            display_name_max_length: 32,
+            display_name_cooldown_secs: 86400,
+        }
+    }
+}
+
+// 第三方登录配置，providers 的键即接口路径中的提供方名称（如 /oauth/github/authorize）
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OAuthSettings {
+    pub state_ttl_secs: i64,           // 授权请求（state）的有效期
+    pub allow_signup: bool,            // 未关联的第三方账号登录时是否自动创建本地账号
+    pub providers: BTreeMap<String, OAuthProviderSettings>,
+}
+
+impl Default for OAuthSettings {
+    fn default() -> Self {
+        Self {
+            state_ttl_secs: 600,
+            allow_signup: true,
+            providers: BTreeMap::new(),
+        }
+    }
+}
+
+// 登录设备管理
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+    pub verify_new_devices: bool,      // 新设备登录时是否需要邮箱验证码（需要配置邮件发送，用户邮箱为占位邮箱时跳过）
+    pub code_ttl_secs: i64,            // 设备验证码的有效期
+    pub max_code_attempts: i64,        // 验证码最多可以输错的次数
+    pub notify_new_device: bool,       // 新设备登录后是否发送系统消息提醒
+    pub duplicate_login: String,       // 同一设备再次建立 WebSocket 连接时：kick_old 断开旧连接，deny_new 拒绝新连接
+}
+
+impl Default for DeviceSettings {
+    fn default() -> Self {
+        Self {
+            verify_new_devices: false,
+            code_ttl_secs: 600,
+            max_code_attempts: 5,
+            notify_new_device: true,
+            duplicate_login: "kick_old".into(),
+        }
+    }
+}
+
+// 单个第三方登录提供方
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OAuthProviderSettings {
+    pub kind: String,                  // github、google 或 oidc（通用 OpenID Connect）
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_uri: String,          // 在提供方登记的回调地址，指向 /oauth/<名称>/callback
+    pub authorize_url: String,         // 以下三个地址 github 和 google 可省略，oidc 必须填写
+    pub token_url: String,
+    pub userinfo_url: String,
+    pub scopes: Vec<String>,           // 为空时使用该类型的默认权限
+}
+
+// 安全相关配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SecuritySettings {
+    pub master_key: String,       // 主密钥（派生数据库和应用层加密密钥），建议通过环境变量 YUELING_MASTER_KEY 提供而不是写在文件里
+    pub session_ttl_secs: i64,    // 登录会话令牌有效期
+    pub encrypt_messages: bool,   // 是否用按会话派生的密钥加密保存消息内容（需要主密钥）
+    pub message_key_rotation_secs: i64, // 会话密钥前进到下一个纪元的间隔，0 表示不轮换
+    pub encrypt_emails: bool,     // 是否确定性加密保存用户邮箱（仍可等值查询，需要主密钥）
+    pub message_cache_bytes: u64, // 已解密消息内容的缓存上限（字节），0 表示不缓存
+    pub password_hash_concurrency: usize, // 同时执行的密码哈希和校验数，0 表示与 CPU 核数相同
+}
+
+impl Default for SecuritySettings {
+    fn default() -> Self {
+        Self {
+            master_key: String::new(),
+            session_ttl_secs: 30 * 24 * 60 * 60,
+            encrypt_messages: false,
+            message_key_rotation_secs: 7 * 24 * 60 * 60,
+            encrypt_emails: false,
+            message_cache_bytes: 32 * 1024 * 1024,
+            password_hash_concurrency: 0,
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! 消息的富文本格式：服务器解析的 markdown 子集
+//!
+//! 支持 `**粗体**`、`*斜体*`（或 `_斜体_`）、`` `代码` `` 和 `[文字](链接)`，格式之间不嵌套，
+//! 反斜杠转义标记字符。解析结果是去掉标记的纯文本和格式区间，客户端按区间渲染，
+//! 始终把文本当作纯文本显示，其中的 HTML 和脚本不会被解释；链接只允许 http、https 和 mailto
+
+use serde::{Deserialize, Serialize};
+
+// 可以用反斜杠转义的标记字符
+const ESCAPABLE: &[char] = &['\\', '*', '_', '`', '[', ']', '(', ')'];
+
+// 链接地址的最大长度
+pub const MAX_URL_LEN: usize = 2048;
+
+// 允许的链接协议
+const ALLOWED_SCHEMES: &[&str] = &["http://", "https://", "mailto:"];
+
+/// 格式类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Bold,
+    Italic,
+    Code,
+    Link,
+    Sticker, // 整条消息是一个贴纸，见 api::emoji
+    Call,    // 整条消息是一条未接来电记录，见 api::calls
+}
+
+/// 纯文本中的一段格式，offset 和 length 按 Unicode 字符计
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TextEntity {
+    #[serde(rename = "type")]
+    pub kind: EntityKind,
+    pub offset: usize,
+    pub length: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>, // 仅链接有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>, // 仅贴纸有：自定义表情的名称
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment_id: Option<String>, // 仅贴纸有：发送时表情的图片，表情之后被删除或替换也能显示
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub call_id: Option<String>, // 仅通话记录有：主叫方生成的通话ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media: Option<String>, // 仅通话记录有：audio 或 video
+}
+
+impl TextEntity {
+    pub fn new(kind: EntityKind, offset: usize, length: usize) -> Self {
+        Self { kind, offset, length, url: None, emoji: None, attachment_id: None, call_id: None, media: None }
+    }
+}
+
+/// 解析后的消息：去掉标记的纯文本和其中的格式区间（按 offset 升序，互不重叠）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormattedText {
+    pub text: String,
+    pub entities: Vec<TextEntity>,
+}
+
+/// 链接地址不合法：协议不在允许范围内、含空白或控制字符，或超过长度上限
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsafeLink(pub String);
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    text: String,
+    len: usize,                 // text 中的字符数
+    entities: Vec<TextEntity>,
+    exhausted: Vec<&'static str>, // 之后已经找不到的结束标记，不再重复查找
+}
+
+/// 解析 markdown 子集；找不到配对的标记按原样保留为文本
+pub fn parse(input: &str) -> Result<FormattedText, UnsafeLink> {
+    let mut parser = Parser {
+        chars: input.chars().collect(),
+        pos: 0,
+        text: String::with_capacity(input.len()),
+        len: 0,
+        entities: Vec::new(),
+        exhausted: Vec::new(),
+    };
+    while parser.pos < parser.chars.len() {
+        parser.step()?;
+    }
+    Ok(FormattedText { text: parser.text, entities: parser.entities })
+}
+
+impl Parser {
+    fn at(&self, pos: usize) -> Option<char> {
+        self.chars.get(pos).copied()
+    }
+
+    fn starts_with(&self, pos: usize, marker: &str) -> bool {
+        marker.chars().enumerate().all(|(i, c)| self.at(pos + i) == Some(c))
+    }
+
+    fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.len += 1;
+    }
+
+    // 处理当前位置的一个字符或一段格式
+    fn step(&mut self) -> Result<(), UnsafeLink> {
+        let c = self.chars[self.pos];
+        let formatted = match c {
+            '\\' if self.at(self.pos + 1).is_some_and(|next| ESCAPABLE.contains(&next)) => {
+                self.push(self.chars[self.pos + 1]);
+                self.pos += 2;
+                return Ok(());
+            }
+            '`' => self.span("`", EntityKind::Code),
+            '*' if self.at(self.pos + 1) == Some('*') => self.span("**", EntityKind::Bold),
+            '*' => self.span("*", EntityKind::Italic),
+            // 词中间的下划线（如 snake_case）不是标记
+            '_' if !self.pos.checked_sub(1).and_then(|p| self.at(p)).is_some_and(char::is_alphanumeric) => {
+                self.span("_", EntityKind::Italic)
+            }
+            '[' => self.link()?,
+            _ => false,
+        };
+        if !formatted {
+            // 没有配对的标记原样保留；** 整体保留，避免拆成两个斜体标记
+            let marker_len = if c == '*' && self.at(self.pos + 1) == Some('*') { 2 } else { 1 };
+            for _ in 0..marker_len {
+                self.push(self.chars[self.pos]);
+                self.pos += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // 从 from 起查找未转义的 marker，遇到 stop 中的字符即放弃
+    fn find(&self, from: usize, marker: &str, stop: &[char]) -> Option<usize> {
+        let mut pos = from;
+        while pos < self.chars.len() {
+            if self.starts_with(pos, marker) {
+                return Some(pos);
+            }
+            if stop.contains(&self.chars[pos]) {
+                return None;
+            }
+            pos += if self.chars[pos] == '\\' && marker != "`" { 2 } else { 1 };
+        }
+        None
+    }
+
+    // 把 [from, to) 的原文（处理转义）追加到文本；代码中的反斜杠原样保留
+    fn push_range(&mut self, from: usize, to: usize, unescape: bool) {
+        let mut pos = from;
+        while pos < to {
+            if unescape && self.chars[pos] == '\\' && pos + 1 < to && ESCAPABLE.contains(&self.chars[pos + 1]) {
+                pos += 1;
+            }
+            self.push(self.chars[pos]);
+            pos += 1;
+        }
+    }
+
+    // 以 marker 包围的一段格式，内容不能为空，也不能以空白开头或结尾
+    fn span(&mut self, marker: &'static str, kind: EntityKind) -> bool {
+        if self.exhausted.contains(&marker) {
+            return false;
+        }
+        let start = self.pos + marker.chars().count();
+        let Some(end) = self.find(start, marker, &[]) else {
+            self.exhausted.push(marker);
+            return false;
+        };
+        let content = &self.chars[start..end];
+        if content.first().is_none_or(|c| c.is_whitespace()) || content.last().is_none_or(|c| c.is_whitespace()) {
+            return false;
+        }
+        let after = end + marker.chars().count();
+        if marker == "_" && self.at(after).is_some_and(char::is_alphanumeric) {
+            return false;
+        }
+        let offset = self.len;
+        self.push_range(start, end, kind != EntityKind::Code);
+        self.entities.push(TextEntity::new(kind, offset, self.len - offset));
+        self.pos = after;
+        true
+    }
+
+    // [文字](链接)：文字不能为空且不含 [ 或换行，链接地址中不能有空白
+    fn link(&mut self) -> Result<bool, UnsafeLink> {
+        let start = self.pos + 1;
+        let Some(close) = self.find(start, "]", &['[', '\n']) else {
+            return Ok(false);
+        };
+        if close == start || self.at(close + 1) != Some('(') {
+            return Ok(false);
+        }
+        let Some(end) = self.find(close + 2, ")", &[' ', '\n']) else {
+            return Ok(false);
+        };
+        let url: String = self.chars[close + 2..end].iter().collect();
+        check_url(&url)?;
+        let offset = self.len;
+        self.push_range(start, close, true);
+        self.entities.push(TextEntity { url: Some(url), ..TextEntity::new(EntityKind::Link, offset, self.len - offset) });
+        self.pos = end + 1;
+        Ok(true)
+    }
+}
+
+/// 检查链接地址是否可以渲染：协议在允许范围内、不超长、不含空白和会破坏 HTML 属性的字符
+pub fn check_url(url: &str) -> Result<(), UnsafeLink> {
+    let lower = url.to_ascii_lowercase();
+    let allowed = ALLOWED_SCHEMES.iter().any(|scheme| lower.starts_with(scheme) && lower.len() > scheme.len())
+        && url.len() <= MAX_URL_LEN
+        && !url.chars().any(|c| c.is_whitespace() || c.is_control() || c == '"' || c == '<' || c == '>');
+    if allowed { Ok(()) } else { Err(UnsafeLink(url.to_string())) }
+}
@@ -0,0 +1,246 @@
+//! 图片元数据清理：去掉 JPEG、PNG 和 WebP 中的 EXIF（含 GPS 位置）、XMP、IPTC 和文本注释
+//!
+//! 只重写文件的容器结构，像素数据原样复制，不解码也不损失画质；JPEG 的拍摄方向单独保留，
+//! 去掉 EXIF 后照片不会被转歪。其他格式（如 GIF、HEIC）不处理
+
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+// EXIF 中的拍摄方向标签
+const ORIENTATION_TAG: u16 = 0x0112;
+
+/// 支持清理的图片格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg,
+    Png,
+    WebP,
+}
+
+impl ImageFormat {
+    /// 按文件开头的魔数识别格式，至少需要 12 个字节
+    pub fn sniff(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(Self::Jpeg)
+        } else if header.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if header.len() >= 12 && &header[..4] == b"RIFF" && &header[8..12] == b"WEBP" {
+            Some(Self::WebP)
+        } else {
+            None
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// 把 input 中的图片去掉元数据后写入 output；文件结构无效时返回 InvalidData
+pub fn strip_metadata<R: Read, W: Write + Seek>(format: ImageFormat, input: &mut R, output: &mut W) -> io::Result<()> {
+    match format {
+        ImageFormat::Jpeg => strip_jpeg(input, output),
+        ImageFormat::Png => strip_png(input, output),
+        ImageFormat::WebP => strip_webp(input, output),
+    }
+}
+
+fn read_u8<R: Read>(input: &mut R) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    input.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+// 复制恰好 len 个字节，输入提前结束时报错
+fn copy_exact<R: Read, W: Write>(input: &mut R, output: &mut W, len: u64) -> io::Result<()> {
+    if io::copy(&mut input.take(len), output)? < len {
+        return Err(invalid("文件被截断"));
+    }
+    Ok(())
+}
+
+// 跳过恰好 len 个字节
+fn skip_exact<R: Read>(input: &mut R, len: u64) -> io::Result<()> {
+    copy_exact(input, &mut io::sink(), len)
+}
+
+// JPEG：逐段复制，丢掉 EXIF/XMP（APP1）、除 ICC 色彩配置外的 APP2、APP3 到 APP13、APP15 和注释段；
+// 图像数据（SOS 之后）原样复制
+fn strip_jpeg<R: Read, W: Write>(input: &mut R, output: &mut W) -> io::Result<()> {
+    let mut soi = [0u8; 2];
+    input.read_exact(&mut soi)?;
+    if soi != [0xFF, 0xD8] {
+        return Err(invalid("不是 JPEG 文件"));
+    }
+    output.write_all(&soi)?;
+
+    loop {
+        if read_u8(input)? != 0xFF {
+            return Err(invalid("JPEG 段标记无效"));
+        }
+        let mut marker = read_u8(input)?;
+        // 标记前可以有任意个填充的 0xFF
+        while marker == 0xFF {
+            marker = read_u8(input)?;
+        }
+        // 没有长度的独立标记
+        if marker == 0x01 || (0xD0..=0xD7).contains(&marker) {
+            output.write_all(&[0xFF, marker])?;
+            continue;
+        }
+        if marker == 0xD9 {
+            output.write_all(&[0xFF, marker])?;
+            return Ok(());
+        }
+
+        let mut len = [0u8; 2];
+        input.read_exact(&mut len)?;
+        let len = u16::from_be_bytes(len);
+        if len < 2 {
+            return Err(invalid("JPEG 段长度无效"));
+        }
+        let mut payload = vec![0u8; len as usize - 2];
+        input.read_exact(&mut payload)?;
+
+        let keep = match marker {
+            0xE0 => payload.starts_with(b"JFIF\0"),
+            0xE1 => {
+                let orientation = payload.strip_prefix(b"Exif\0\0").and_then(exif_orientation);
+                if let Some(orientation) = orientation.filter(|&o| o != 1) {
+                    output.write_all(&orientation_segment(orientation))?;
+                }
+                false
+            }
+            0xE2 => payload.starts_with(b"ICC_PROFILE\0"),
+            0xE3..=0xED | 0xEF | 0xFE => false,
+            _ => true,
+        };
+        if keep {
+            output.write_all(&[0xFF, marker])?;
+            output.write_all(&len.to_be_bytes())?;
+            output.write_all(&payload)?;
+        }
+        // 扫描开始，其后是熵编码的图像数据
+        if marker == 0xDA {
+            io::copy(input, output)?;
+            return Ok(());
+        }
+    }
+}
+
+// 从 EXIF 的 TIFF 结构中读取 IFD0 的拍摄方向
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..4)? {
+        b"MM\0*" => true,
+        b"II*\0" => false,
+        _ => return None,
+    };
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes: [u8; 2] = tiff.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes: [u8; 4] = tiff.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) })
+    };
+    let ifd = u32_at(4)? as usize;
+    let count = u16_at(ifd)? as usize;
+    (0..count)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| u16_at(entry) == Some(ORIENTATION_TAG))
+        .and_then(|entry| u16_at(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+// 只含拍摄方向的 APP1 段
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut segment = vec![0xFF, 0xE1, 0, 32];
+    segment.extend_from_slice(b"Exif\0\0MM\0*");
+    segment.extend_from_slice(&8u32.to_be_bytes());             // IFD0 的偏移
+    segment.extend_from_slice(&1u16.to_be_bytes());             // 一个条目
+    segment.extend_from_slice(&ORIENTATION_TAG.to_be_bytes());
+    segment.extend_from_slice(&3u16.to_be_bytes());             // SHORT
+    segment.extend_from_slice(&1u32.to_be_bytes());
+    segment.extend_from_slice(&orientation.to_be_bytes());
+    segment.extend_from_slice(&[0, 0]);
+    segment.extend_from_slice(&0u32.to_be_bytes());             // 没有下一个 IFD
+    segment
+}
+
+// PNG：逐块复制，丢掉 eXIf、文本块（tEXt、zTXt、iTXt）和修改时间 tIME
+fn strip_png<R: Read, W: Write>(input: &mut R, output: &mut W) -> io::Result<()> {
+    let mut signature = [0u8; 8];
+    input.read_exact(&mut signature)?;
+    if &signature != b"\x89PNG\r\n\x1a\n" {
+        return Err(invalid("不是 PNG 文件"));
+    }
+    output.write_all(&signature)?;
+
+    loop {
+        let mut header = [0u8; 8];
+        input.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header[..4].try_into().unwrap());
+        if len > i32::MAX as u32 {
+            return Err(invalid("PNG 块长度无效"));
+        }
+        let kind = &header[4..];
+        // 数据加 CRC
+        let body = len as u64 + 4;
+        if matches!(kind, b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME") {
+            skip_exact(input, body)?;
+            continue;
+        }
+        output.write_all(&header)?;
+        copy_exact(input, output, body)?;
+        if kind == b"IEND" {
+            return Ok(());
+        }
+    }
+}
+
+// WebP：逐块复制，丢掉 EXIF 和 XMP 块并清除 VP8X 中对应的标志位，最后改写 RIFF 长度
+fn strip_webp<R: Read, W: Write + Seek>(input: &mut R, output: &mut W) -> io::Result<()> {
+    let mut header = [0u8; 12];
+    input.read_exact(&mut header)?;
+    if &header[..4] != b"RIFF" || &header[8..] != b"WEBP" {
+        return Err(invalid("不是 WebP 文件"));
+    }
+    let riff_len = u32::from_le_bytes(header[4..8].try_into().unwrap()) as u64;
+    let start = output.stream_position()?;
+    output.write_all(&header)?;
+
+    // RIFF 长度包含 "WEBP" 这 4 个字节
+    let mut remaining = riff_len.checked_sub(4).ok_or_else(|| invalid("WebP 长度无效"))?;
+    let mut written = 4u64;
+    while remaining > 0 {
+        let mut chunk = [0u8; 8];
+        input.read_exact(&mut chunk)?;
+        let len = u32::from_le_bytes(chunk[4..].try_into().unwrap()) as u64;
+        // 奇数长度的块后补一个字节
+        let body = len + (len & 1);
+        remaining = remaining.checked_sub(8 + body).ok_or_else(|| invalid("WebP 块长度无效"))?;
+        match &chunk[..4] {
+            b"EXIF" | b"XMP " => skip_exact(input, body)?,
+            b"VP8X" => {
+                let mut data = vec![0u8; body as usize];
+                input.read_exact(&mut data)?;
+                if let Some(flags) = data.first_mut() {
+                    *flags &= !0x0C;
+                }
+                output.write_all(&chunk)?;
+                output.write_all(&data)?;
+                written += 8 + body;
+            }
+            _ => {
+                output.write_all(&chunk)?;
+                copy_exact(input, output, body)?;
+                written += 8 + body;
+            }
+        }
+    }
+
+    let end = output.stream_position()?;
+    output.seek(SeekFrom::Start(start + 4))?;
+    output.write_all(&(written as u32).to_le_bytes())?;
+    output.seek(SeekFrom::Start(end))?;
+    Ok(())
+}
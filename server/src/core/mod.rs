@@ -1,2 +1,5 @@
 pub mod auth;
+pub mod datetime;
+pub mod markdown;
+pub mod media;
 pub mod models;
@@ -0,0 +1,36 @@
+//! 密文容器的完整性标签：在整个容器（头部、nonce、密文）末尾追加 HMAC-SHA256，
+//! 解密前先校验，截断、拼接或改动头部都会明确报告为完整性错误，而不是含糊的解密失败
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use super::CryptoError;
+
+pub const TAG_LEN: usize = 32;
+
+// 标签覆盖容器长度、容器本身和附加数据，附加数据不同的密文不能互换
+fn mac(mac_key: &[u8], container: &[u8], aad: &[u8]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(mac_key).expect("HMAC 接受任意长度的密钥");
+    mac.update(&(container.len() as u64).to_be_bytes());
+    mac.update(container);
+    mac.update(aad);
+    mac
+}
+
+/// 在容器末尾追加完整性标签
+pub fn append_tag(mac_key: &[u8], aad: &[u8], container: &mut Vec<u8>) {
+    let tag = mac(mac_key, container, aad).finalize().into_bytes();
+    container.extend_from_slice(&tag);
+}
+
+/// 校验并去掉末尾的完整性标签，返回容器本体
+pub fn verify_tag<'a>(mac_key: &[u8], aad: &[u8], data: &'a [u8]) -> Result<&'a [u8], CryptoError> {
+    if data.len() < TAG_LEN {
+        return Err(CryptoError::Malformed);
+    }
+    let (container, tag) = data.split_at(data.len() - TAG_LEN);
+    mac(mac_key, container, aad)
+        .verify_slice(tag)
+        .map_err(|_| CryptoError::Integrity)?;
+    Ok(container)
+}
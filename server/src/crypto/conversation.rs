@@ -0,0 +1,194 @@
+//! 会话消息密钥：每个会话由主密钥经 HKDF 派生独立的链密钥，并按纪元（epoch）单向棘轮前进
+//!
+//! 第 n 个纪元的链密钥为 chain_n = HMAC(chain_{n-1}, "chain")，消息密钥为 HMAC(chain_n, "message")，
+//! 单个消息密钥泄露不会暴露其他纪元或其他会话的消息。密文头部记录纪元，解密时据此还原密钥；
+//! 容器末尾的完整性标签用同一纪元的 HMAC(chain_n, "mac") 计算，改动纪元头部会被直接识别
+//!
+//! 消息内容以 BLOB 保存二进制容器（seal_bytes），不经过 base64；格式区间、翻译等文本列保存加前缀的 base64（seal）
+
+use aes_gcm::{
+    aead::{AeadInPlace, KeyInit},
+    Aes256Gcm, Key, Nonce
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use moka::sync::Cache;
+use rand::RngCore;
+use sha2::Sha256;
+
+use super::{container, CryptoError};
+use crate::config::settings::SecuritySettings;
+
+// 加密后的消息内容前缀，没有该前缀的内容视为加密功能开启前保存的明文
+pub const SEALED_PREFIX: &str = "ylm1:";
+
+// HKDF 的 salt，与其他用途的密钥派生区分
+const HKDF_SALT: &[u8] = b"yueling-conversation-v1";
+
+const NONCE_LEN: usize = 12;
+const EPOCH_LEN: usize = 4;
+// AES-GCM 认证标签的长度
+const GCM_TAG_LEN: usize = 16;
+
+// 密文头部允许的最大纪元：还原密钥需要从头前进 epoch 步，伪造的超大纪元不能拖慢解密
+const MAX_EPOCH: u32 = 1 << 20;
+
+// 缓存的消息密钥数量（会话 × 纪元）
+const KEY_CACHE_CAPACITY: u64 = 10_000;
+
+/// 会话标识：私聊为排序后的双方ID，群聊（含群内的系统消息）为群ID，发给单个用户的系统消息为该用户ID
+pub fn conversation_id(message_type: &str, sender_id: &str, receiver_id: &str) -> String {
+    if message_type == "group" || message_type == "system" {
+        return format!("group:{}", receiver_id);
+    }
+    let (a, b) = if sender_id <= receiver_id { (sender_id, receiver_id) } else { (receiver_id, sender_id) };
+    format!("private:{}:{}", a, b)
+}
+
+fn hmac(key: &[u8], label: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC 接受任意长度的密钥");
+    mac.update(label);
+    mac.finalize().into_bytes().into()
+}
+
+/// 按会话和纪元派生消息密钥并加解密消息内容
+pub struct ConversationKeys {
+    hkdf: Hkdf<Sha256>,
+    rotation_secs: i64,
+    keys: Cache<(String, u32), EpochKeys>,
+}
+
+// 某个会话某个纪元的加密密钥和完整性标签密钥
+#[derive(Clone)]
+struct EpochKeys {
+    cipher: Key<Aes256Gcm>,
+    mac: [u8; 32],
+}
+
+impl ConversationKeys {
+    pub fn new(master_key: &str, rotation_secs: i64) -> Self {
+        Self {
+            hkdf: Hkdf::<Sha256>::new(Some(HKDF_SALT), master_key.as_bytes()),
+            rotation_secs,
+            keys: Cache::new(KEY_CACHE_CAPACITY),
+        }
+    }
+
+    /// 按配置创建：未开启消息加密时返回 None，开启但缺少主密钥时报错
+    pub fn from_settings(settings: &SecuritySettings) -> Result<Option<Self>, String> {
+        if !settings.encrypt_messages {
+            return Ok(None);
+        }
+        if settings.master_key.is_empty() {
+            return Err("security.encrypt_messages 已开启，但未配置主密钥（security.master_key 或 YUELING_MASTER_KEY）".into());
+        }
+        Ok(Some(Self::new(&settings.master_key, settings.message_key_rotation_secs)))
+    }
+
+    /// 纪元开始后多久前进到下一个纪元，0 表示不轮换
+    pub fn rotation_secs(&self) -> i64 {
+        self.rotation_secs
+    }
+
+    // 从会话的初始链密钥前进 epoch 步，得到该纪元的消息密钥
+    fn epoch_keys(&self, conversation_id: &str, epoch: u32) -> EpochKeys {
+        self.keys.get_with((conversation_id.to_string(), epoch), || {
+            let mut chain = [0u8; 32];
+            self.hkdf
+                .expand(conversation_id.as_bytes(), &mut chain)
+                .expect("32 字节在 HKDF-SHA256 的输出长度范围内");
+            for _ in 0..epoch {
+                chain = hmac(&chain, b"chain");
+            }
+            EpochKeys {
+                cipher: *Key::<Aes256Gcm>::from_slice(&hmac(&chain, b"message")),
+                mac: hmac(&chain, b"mac"),
+            }
+        })
+    }
+
+    /// 用会话在 epoch 纪元的密钥加密消息内容，aad 通常为消息ID
+    pub fn seal(&self, conversation_id: &str, epoch: u32, aad: &str, plaintext: &str) -> String {
+        encode_sealed(&self.seal_bytes(conversation_id, epoch, aad, plaintext.as_bytes()))
+    }
+
+    /// 与 seal 相同，但返回不带前缀、不做 base64 编码的二进制容器，消息内容以 BLOB 保存时使用
+    pub fn seal_bytes(&self, conversation_id: &str, epoch: u32, aad: &str, plaintext: &[u8]) -> Vec<u8> {
+        super::metrics::timed(super::metrics::Operation::Encrypt, plaintext.len(), || self.seal_untimed(conversation_id, epoch, aad, plaintext))
+    }
+
+    // 直接在输出缓冲区中原地加密，明文只复制一次
+    fn seal_untimed(&self, conversation_id: &str, epoch: u32, aad: &str, plaintext: &[u8]) -> Vec<u8> {
+        let keys = self.epoch_keys(conversation_id, epoch);
+        let cipher = Aes256Gcm::new(&keys.cipher);
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let mut out = Vec::with_capacity(EPOCH_LEN + NONCE_LEN + plaintext.len() + GCM_TAG_LEN + container::TAG_LEN);
+        out.extend_from_slice(&epoch.to_be_bytes());
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(plaintext);
+        let tag = cipher
+            .encrypt_in_place_detached(Nonce::from_slice(&nonce), aad.as_bytes(), &mut out[EPOCH_LEN + NONCE_LEN..])
+            .expect("AES-GCM 加密不会因输入长度以外的原因失败");
+        out.extend_from_slice(&tag);
+        container::append_tag(&keys.mac, aad.as_bytes(), &mut out);
+        out
+    }
+
+    /// 校验完整性标签后解密 seal 的输出；没有加密前缀的内容原样返回
+    pub fn open(&self, conversation_id: &str, aad: &str, stored: &str) -> Result<String, CryptoError> {
+        let Some(encoded) = stored.strip_prefix(SEALED_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let data = BASE64.decode(encoded).map_err(|_| CryptoError::Malformed)?;
+        self.open_bytes(conversation_id, aad, &data)
+    }
+
+    /// 校验完整性标签后解密 seal_bytes 的输出
+    pub fn open_bytes(&self, conversation_id: &str, aad: &str, data: &[u8]) -> Result<String, CryptoError> {
+        super::metrics::timed(super::metrics::Operation::Decrypt, data.len(), || self.open_untimed(conversation_id, aad, data))
+    }
+
+    // 密文只复制一次，原地解密后直接作为明文字符串的缓冲区
+    fn open_untimed(&self, conversation_id: &str, aad: &str, data: &[u8]) -> Result<String, CryptoError> {
+        if data.len() < EPOCH_LEN + NONCE_LEN + container::TAG_LEN {
+            return Err(CryptoError::Malformed);
+        }
+        let epoch = u32::from_be_bytes(data[..EPOCH_LEN].try_into().expect("长度已检查"));
+        if epoch > MAX_EPOCH {
+            return Err(CryptoError::Malformed);
+        }
+        let keys = self.epoch_keys(conversation_id, epoch);
+        let data = container::verify_tag(&keys.mac, aad.as_bytes(), data)?;
+        let (nonce, ciphertext) = data[EPOCH_LEN..].split_at(NONCE_LEN);
+        let mut buffer = ciphertext.to_vec();
+        Aes256Gcm::new(&keys.cipher)
+            .decrypt_in_place(Nonce::from_slice(nonce), aad.as_bytes(), &mut buffer)
+            .map_err(|_| CryptoError::Decrypt)?;
+        String::from_utf8(buffer).map_err(|_| CryptoError::Malformed)
+    }
+}
+
+/// 把 seal_bytes 的输出编码为 seal 的文本格式（加前缀的 base64）
+pub fn encode_sealed(data: &[u8]) -> String {
+    format!("{}{}", SEALED_PREFIX, BASE64.encode(data))
+}
+
+/// encode_sealed 的逆操作，不是 seal 输出格式的文本返回 None
+pub fn decode_sealed(stored: &str) -> Option<Vec<u8>> {
+    BASE64.decode(stored.strip_prefix(SEALED_PREFIX)?).ok()
+}
+
+/// 读取二进制容器头部记录的纪元
+pub fn sealed_bytes_epoch(data: &[u8]) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(..EPOCH_LEN)?.try_into().ok()?))
+}
+
+/// 读取密文头部记录的纪元，明文返回 None
+///
+/// 只解码开头 8 个字符（6 字节），读取时用它查解密缓存，不必解码整条密文
+pub fn sealed_epoch(stored: &str) -> Option<u32> {
+    let data = BASE64.decode(stored.strip_prefix(SEALED_PREFIX)?.get(..8)?).ok()?;
+    Some(u32::from_be_bytes(data.get(..EPOCH_LEN)?.try_into().ok()?))
+}
@@ -0,0 +1,155 @@
+//! 密钥托管：用运维提供的口令包装主密钥导出为密钥包，迁移到新主机时导入恢复
+//!
+//! 所有落库加密的密钥（数据库、账号设置、会话消息、邮箱）都由主密钥派生，会话纪元记录在数据库中，
+//! 因此密钥包只需保存主密钥。包装密钥由口令经 Argon2id 派生，主密钥用 AES-256-GCM 加密，
+//! 头部（格式、指纹、KDF 参数、salt）作为附加数据参与认证，改动任何一项都无法解开
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+// 密钥包格式标识
+pub const KEYRING_FORMAT: &str = "yueling-keyring-v1";
+
+// 口令最短长度
+pub const MIN_PASSPHRASE_LEN: usize = 12;
+
+// 派生主密钥指纹时使用的域分隔前缀
+const FINGERPRINT_CONTEXT: &[u8] = b"yueling-key-fingerprint-v1:";
+
+// Argon2id 参数（OWASP 推荐的最低配置：19 MiB 内存、2 轮）
+const KDF_MEMORY_KIB: u32 = 19 * 1024;
+const KDF_ITERATIONS: u32 = 2;
+const KDF_PARALLELISM: u32 = 1;
+
+// 导入时接受的 KDF 参数上限，防止伪造的密钥包耗尽内存
+const MAX_KDF_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_KDF_ITERATIONS: u32 = 64;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum EscrowError {
+    #[error("口令至少需要 {0} 个字符")]
+    WeakPassphrase(usize),
+    #[error("不支持的密钥包格式: {0}")]
+    Unsupported(String),
+    #[error("密钥包格式无效")]
+    Malformed,
+    #[error("口令错误或密钥包已损坏")]
+    WrongPassphrase,
+}
+
+/// 包装密钥的派生参数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub algorithm: String, // 目前只有 argon2id
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    pub salt: String, // base64
+}
+
+/// 导出的密钥包（JSON）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyringBundle {
+    pub format: String,
+    pub fingerprint: String, // 主密钥指纹，导入前即可核对是否为同一把密钥
+    pub created_at: i64,
+    pub kdf: KdfParams,
+    pub nonce: String,      // base64
+    pub ciphertext: String, // base64
+}
+
+impl KeyringBundle {
+    // 参与认证的头部
+    fn header(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}",
+            self.format, self.fingerprint, self.created_at, self.kdf.algorithm,
+            self.kdf.memory_kib, self.kdf.iterations, self.kdf.parallelism, self.kdf.salt
+        )
+    }
+}
+
+/// 主密钥指纹（SHA-256 前 8 字节的十六进制），可以公开展示，用于核对两台主机的主密钥是否一致
+pub fn fingerprint(master_key: &str) -> String {
+    hex::encode(&super::derive(FINGERPRINT_CONTEXT, master_key)[..8])
+}
+
+fn wrapping_key(passphrase: &str, kdf: &KdfParams) -> Result<Aes256Gcm, EscrowError> {
+    if kdf.algorithm != "argon2id" {
+        return Err(EscrowError::Unsupported(kdf.algorithm.clone()));
+    }
+    if kdf.memory_kib > MAX_KDF_MEMORY_KIB || kdf.iterations > MAX_KDF_ITERATIONS {
+        return Err(EscrowError::Malformed);
+    }
+    let salt = BASE64.decode(&kdf.salt).map_err(|_| EscrowError::Malformed)?;
+    let params = Params::new(kdf.memory_kib, kdf.iterations, kdf.parallelism, Some(32))
+        .map_err(|_| EscrowError::Malformed)?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+        .map_err(|_| EscrowError::Malformed)?;
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+/// 用口令包装主密钥
+pub fn export(master_key: &str, passphrase: &str, now: i64) -> Result<KeyringBundle, EscrowError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(EscrowError::WeakPassphrase(MIN_PASSPHRASE_LEN));
+    }
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let mut bundle = KeyringBundle {
+        format: KEYRING_FORMAT.into(),
+        fingerprint: fingerprint(master_key),
+        created_at: now,
+        kdf: KdfParams {
+            algorithm: "argon2id".into(),
+            memory_kib: KDF_MEMORY_KIB,
+            iterations: KDF_ITERATIONS,
+            parallelism: KDF_PARALLELISM,
+            salt: BASE64.encode(salt),
+        },
+        nonce: BASE64.encode(nonce),
+        ciphertext: String::new(),
+    };
+    let header = bundle.header();
+    let ciphertext = wrapping_key(passphrase, &bundle.kdf)?
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: master_key.as_bytes(), aad: header.as_bytes() })
+        .expect("AES-GCM 加密不会因输入长度以外的原因失败");
+    bundle.ciphertext = BASE64.encode(ciphertext);
+    Ok(bundle)
+}
+
+/// 用口令解开密钥包，返回主密钥
+pub fn import(bundle: &KeyringBundle, passphrase: &str) -> Result<String, EscrowError> {
+    if bundle.format != KEYRING_FORMAT {
+        return Err(EscrowError::Unsupported(bundle.format.clone()));
+    }
+    let nonce = BASE64.decode(&bundle.nonce).map_err(|_| EscrowError::Malformed)?;
+    let ciphertext = BASE64.decode(&bundle.ciphertext).map_err(|_| EscrowError::Malformed)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(EscrowError::Malformed);
+    }
+    let header = bundle.header();
+    let master_key = wrapping_key(passphrase, &bundle.kdf)?
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: header.as_bytes() })
+        .map_err(|_| EscrowError::WrongPassphrase)?;
+    let master_key = String::from_utf8(master_key).map_err(|_| EscrowError::Malformed)?;
+    // 指纹已参与认证，这里再核对一次，防止导出方本身写错
+    if fingerprint(&master_key) != bundle.fingerprint {
+        return Err(EscrowError::Malformed);
+    }
+    Ok(master_key)
+}
@@ -0,0 +1,106 @@
+//! 加解密耗时的直方图：按操作和数据大小分档记录，由 /metrics 以 OpenMetrics 文本格式导出，
+//! 运维据此判断加密是否成了瓶颈
+//!
+//! 计数都是原子变量，记录一次只做几次原子加法，不加锁
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+/// 指标名（OpenMetrics 要求单位作为名称的后缀）
+pub const METRIC_NAME: &str = "yueling_crypto_operation_seconds";
+
+// 耗时分档的上限（秒）
+const LATENCY_BUCKETS: &[f64] = &[0.00001, 0.00005, 0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0];
+
+// 数据大小分档的上限（字节），最后一档不设上限
+const SIZE_BUCKETS: &[u64] = &[1024, 16 * 1024, 256 * 1024, 4 * 1024 * 1024];
+
+/// 记录耗时的操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Encrypt,        // 应用层加密和消息内容加密
+    Decrypt,
+    BcryptHash,     // 注册时计算密码哈希
+    BcryptVerify,   // 登录时校验密码
+}
+
+const OPERATIONS: [Operation; 4] = [Operation::Encrypt, Operation::Decrypt, Operation::BcryptHash, Operation::BcryptVerify];
+
+impl Operation {
+    fn as_str(self) -> &'static str {
+        match self {
+            Operation::Encrypt => "encrypt",
+            Operation::Decrypt => "decrypt",
+            Operation::BcryptHash => "bcrypt_hash",
+            Operation::BcryptVerify => "bcrypt_verify",
+        }
+    }
+}
+
+struct Histogram {
+    buckets: Vec<AtomicU64>,    // 各耗时档的计数（不累计），最后一个为超过最大档的次数，总次数为各档之和
+    sum_nanos: AtomicU64,
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Self {
+            buckets: (0..=LATENCY_BUCKETS.len()).map(|_| AtomicU64::new(0)).collect(),
+            sum_nanos: AtomicU64::new(0),
+        }
+    }
+}
+
+// 按操作、数据大小档排列的直方图
+static HISTOGRAMS: LazyLock<Vec<Histogram>> =
+    LazyLock::new(|| (0..OPERATIONS.len() * (SIZE_BUCKETS.len() + 1)).map(|_| Histogram::new()).collect());
+
+fn size_bucket(bytes: usize) -> usize {
+    SIZE_BUCKETS.iter().position(|&limit| bytes as u64 <= limit).unwrap_or(SIZE_BUCKETS.len())
+}
+
+fn size_label(index: usize) -> String {
+    SIZE_BUCKETS.get(index).map(|limit| limit.to_string()).unwrap_or_else(|| "+Inf".into())
+}
+
+/// 记录一次操作的耗时，bytes 为明文或密文的大小
+pub fn observe(operation: Operation, bytes: usize, elapsed: Duration) {
+    let op = OPERATIONS.iter().position(|&o| o == operation).expect("所有操作都在 OPERATIONS 中");
+    let histogram = &HISTOGRAMS[op * (SIZE_BUCKETS.len() + 1) + size_bucket(bytes)];
+    let secs = elapsed.as_secs_f64();
+    let bucket = LATENCY_BUCKETS.iter().position(|&le| secs <= le).unwrap_or(LATENCY_BUCKETS.len());
+    histogram.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    histogram.sum_nanos.fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// 计时执行 f 并记录
+pub fn timed<T>(operation: Operation, bytes: usize, f: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = f();
+    observe(operation, bytes, started.elapsed());
+    result
+}
+
+/// 以 OpenMetrics 文本格式输出所有直方图，不含结尾的 `# EOF`
+pub fn render(out: &mut String) {
+    let _ = writeln!(out, "# TYPE {METRIC_NAME} histogram");
+    let _ = writeln!(out, "# UNIT {METRIC_NAME} seconds");
+    let _ = writeln!(out, "# HELP {METRIC_NAME} Latency of encryption, decryption, password hashing and verification by payload size in bytes.");
+    for (op, operation) in OPERATIONS.iter().enumerate() {
+        for size in 0..=SIZE_BUCKETS.len() {
+            let histogram = &HISTOGRAMS[op * (SIZE_BUCKETS.len() + 1) + size];
+            let labels = format!("operation=\"{}\",size=\"{}\"", operation.as_str(), size_label(size));
+            let mut cumulative = 0;
+            for (i, bucket) in histogram.buckets.iter().enumerate() {
+                cumulative += bucket.load(Ordering::Relaxed);
+                let le = LATENCY_BUCKETS.get(i).map(|le| le.to_string()).unwrap_or_else(|| "+Inf".into());
+                let _ = writeln!(out, "{METRIC_NAME}_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+            }
+            let sum = histogram.sum_nanos.load(Ordering::Relaxed) as f64 / 1e9;
+            let _ = writeln!(out, "{METRIC_NAME}_count{{{labels}}} {cumulative}");
+            let _ = writeln!(out, "{METRIC_NAME}_sum{{{labels}}} {sum}");
+        }
+    }
+}
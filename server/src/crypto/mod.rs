@@ -0,0 +1,176 @@
+//! 应用层加密：用由主密钥派生的 AES-256-GCM 密钥加密落库的敏感数据（如账号设置），消息内容按会话派生密钥（见 conversation）
+//!
+//! 密文格式为 12 字节随机 nonce、密文和认证标签，末尾再附上覆盖整个容器的 HMAC（见 container）；
+//! 加密时绑定的附加数据（通常是用户ID）解密时必须一致，防止把一个用户的密文挪到另一个用户名下
+//!
+//! 需要等值查询的字段（如邮箱）使用确定性模式：nonce 由明文的 HMAC 合成，相同明文得到相同密文，
+//! 数据库可以直接对密文建唯一索引和按密文查询，代价是会暴露哪些记录的取值相同
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Key, Nonce
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::config::settings::SecuritySettings;
+
+pub mod container;
+pub mod conversation;
+pub mod escrow;
+pub mod metrics;
+pub mod password;
+
+// 派生数据加密密钥时使用的域分隔前缀，与数据库密钥的前缀不同
+const DATA_KEY_CONTEXT: &[u8] = b"yueling-data-v1:";
+// 派生容器完整性标签密钥时使用的域分隔前缀
+const DATA_MAC_CONTEXT: &[u8] = b"yueling-data-mac-v1:";
+// 派生确定性模式合成 nonce 的密钥时使用的域分隔前缀
+const FIELD_NONCE_CONTEXT: &[u8] = b"yueling-field-nonce-v1:";
+
+// 确定性加密的字段值前缀，没有该前缀的值视为明文（开启前保存的数据或占位值）
+pub const SEALED_FIELD_PREFIX: &str = "yld1:";
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum CryptoError {
+    #[error("密文格式无效")]
+    Malformed,
+    #[error("解密失败（密钥错误或数据被篡改）")]
+    Decrypt,
+    #[error("密文完整性校验失败（被截断、篡改或密钥不匹配）")]
+    Integrity,
+}
+
+/// 当前 CPU 是否支持 AES 硬件指令（x86 的 AES-NI 加 PCLMULQDQ，ARM 的 AES 扩展）
+///
+/// aes-gcm 在运行时检测并自动选用硬件实现，这里只用于启动时提示；
+/// 不支持时回退到常数时间的软件实现，吞吐量低一个数量级左右
+pub fn hardware_accelerated() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+fn derive(context: &[u8], master_key: &str) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(context);
+    hasher.update(master_key.as_bytes());
+    hasher.finalize().into()
+}
+
+/// 加解密服务，克隆开销很小
+#[derive(Clone)]
+pub struct CryptoService {
+    cipher: Aes256Gcm,
+    mac_key: [u8; 32],
+    field_nonce_key: [u8; 32],
+}
+
+impl CryptoService {
+    /// 由主密钥派生数据加密密钥和完整性标签密钥
+    pub fn new(master_key: &str) -> Self {
+        let key = derive(DATA_KEY_CONTEXT, master_key);
+        Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+            mac_key: derive(DATA_MAC_CONTEXT, master_key),
+            field_nonce_key: derive(FIELD_NONCE_CONTEXT, master_key),
+        }
+    }
+
+    /// 按配置创建，未配置主密钥时返回 None
+    pub fn from_settings(settings: &SecuritySettings) -> Option<Self> {
+        (!settings.master_key.is_empty()).then(|| Self::new(&settings.master_key))
+    }
+
+    /// 加密明文，aad 为解密时必须一致的附加数据
+    pub fn encrypt(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        metrics::timed(metrics::Operation::Encrypt, plaintext.len(), || self.encrypt_untimed(plaintext, aad))
+    }
+
+    fn encrypt_untimed(&self, plaintext: &[u8], aad: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+            .expect("AES-GCM 加密不会因输入长度以外的原因失败");
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len() + container::TAG_LEN);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+        container::append_tag(&self.mac_key, aad, &mut out);
+        out
+    }
+
+    /// 解密 encrypt 的输出：先校验完整性标签，再解密
+    pub fn decrypt(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        metrics::timed(metrics::Operation::Decrypt, data.len(), || self.decrypt_untimed(data, aad))
+    }
+
+    fn decrypt_untimed(&self, data: &[u8], aad: &[u8]) -> Result<Vec<u8>, CryptoError> {
+        let data = container::verify_tag(&self.mac_key, aad, data)?;
+        if data.len() < NONCE_LEN {
+            return Err(CryptoError::Malformed);
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| CryptoError::Decrypt)
+    }
+
+    /// 确定性加密字段值：同一字段的相同明文总是得到相同密文，field 为字段名（如 "email"），
+    /// 不同字段的相同取值密文不同
+    pub fn seal_field(&self, field: &str, value: &str) -> String {
+        metrics::timed(metrics::Operation::Encrypt, value.len(), || self.seal_field_untimed(field, value))
+    }
+
+    fn seal_field_untimed(&self, field: &str, value: &str) -> String {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.field_nonce_key).expect("HMAC 接受任意长度的密钥");
+        mac.update(field.as_bytes());
+        mac.update(&[0]);
+        mac.update(value.as_bytes());
+        let nonce = mac.finalize().into_bytes();
+        let nonce = &nonce[..NONCE_LEN];
+        let ciphertext = self.cipher
+            .encrypt(Nonce::from_slice(nonce), Payload { msg: value.as_bytes(), aad: field.as_bytes() })
+            .expect("AES-GCM 加密不会因输入长度以外的原因失败");
+        let mut out = Vec::with_capacity(NONCE_LEN + ciphertext.len() + container::TAG_LEN);
+        out.extend_from_slice(nonce);
+        out.extend_from_slice(&ciphertext);
+        container::append_tag(&self.mac_key, field.as_bytes(), &mut out);
+        format!("{}{}", SEALED_FIELD_PREFIX, BASE64.encode(out))
+    }
+
+    /// 解密 seal_field 的输出；没有加密前缀的值原样返回
+    pub fn open_field(&self, field: &str, stored: &str) -> Result<String, CryptoError> {
+        let Some(encoded) = stored.strip_prefix(SEALED_FIELD_PREFIX) else {
+            return Ok(stored.to_string());
+        };
+        let data = BASE64.decode(encoded).map_err(|_| CryptoError::Malformed)?;
+        let plaintext = self.decrypt(&data, field.as_bytes())?;
+        String::from_utf8(plaintext).map_err(|_| CryptoError::Malformed)
+    }
+}
+
+/// 按配置创建字段加密服务：未开启邮箱加密时返回 None，开启但缺少主密钥时报错
+pub fn field_crypto(settings: &SecuritySettings) -> Result<Option<CryptoService>, String> {
+    if !settings.encrypt_emails {
+        return Ok(None);
+    }
+    if settings.master_key.is_empty() {
+        return Err("security.encrypt_emails 已开启，但未配置主密钥（security.master_key 或 YUELING_MASTER_KEY）".into());
+    }
+    Ok(Some(CryptoService::new(&settings.master_key)))
+}
@@ -0,0 +1,47 @@
+//! 注册和登录时的密码哈希与校验：bcrypt 每次要几百毫秒的 CPU 时间，放在 tokio 的阻塞线程池中执行，
+//! 不占用处理其他请求的工作线程；同时执行的数量有上限，登录高峰时多余的请求排队等待
+//!
+//! 耗时记在 crypto::metrics 中（不含排队时间）
+
+use std::sync::Arc;
+
+use bcrypt::{BcryptError, DEFAULT_COST};
+use tokio::sync::Semaphore;
+
+use super::metrics::{self, Operation};
+
+/// 密码哈希服务，克隆开销很小
+#[derive(Clone)]
+pub struct AuthCrypto {
+    permits: Arc<Semaphore>,
+}
+
+impl AuthCrypto {
+    /// concurrency 为同时执行的哈希和校验数，0 表示与 CPU 核数相同
+    pub fn new(concurrency: usize) -> Self {
+        let concurrency = match concurrency {
+            0 => std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+            n => n,
+        };
+        Self { permits: Arc::new(Semaphore::new(concurrency)) }
+    }
+
+    // 取得名额后在阻塞线程池中执行 f
+    async fn run<T: Send + 'static>(&self, f: impl FnOnce() -> T + Send + 'static) -> T {
+        let _permit = self.permits.acquire().await.expect("信号量不会被关闭");
+        tokio::task::spawn_blocking(f).await.expect("密码哈希任务不会 panic")
+    }
+
+    /// 计算新密码的 bcrypt 哈希
+    pub async fn hash(&self, password: &str) -> Result<String, BcryptError> {
+        let password = password.to_string();
+        self.run(move || metrics::timed(Operation::BcryptHash, password.len(), || bcrypt::hash(&password, DEFAULT_COST))).await
+    }
+
+    /// 校验密码与保存的 bcrypt 哈希是否一致
+    pub async fn verify(&self, password: &str, password_hash: &str) -> Result<bool, BcryptError> {
+        let password = password.to_string();
+        let password_hash = password_hash.to_string();
+        self.run(move || metrics::timed(Operation::BcryptVerify, password.len(), || bcrypt::verify(&password, &password_hash))).await
+    }
+}
@@ -0,0 +1,29 @@
+//! 邮件发送：目前用于未读消息摘要，通过 EmailProvider 抽象以便替换发送方式
+
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+
+use crate::config::settings::EmailSettings;
+
+pub mod smtp;
+
+/// 一封纯文本邮件
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+/// 邮件发送方（SMTP 或测试替身）
+pub trait EmailProvider: Send + Sync {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>>;
+}
+
+/// 按配置创建邮件发送方，未配置 smtp_host 时返回 None
+pub fn from_settings(settings: &EmailSettings) -> Result<Option<Arc<dyn EmailProvider>>, String> {
+    if settings.smtp_host.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(smtp::SmtpProvider::from_settings(settings)?)))
+}
@@ -0,0 +1,62 @@
+//! 通过 SMTP 服务器发送邮件
+
+use futures_util::future::BoxFuture;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use super::{Email, EmailProvider};
+use crate::config::settings::EmailSettings;
+
+pub struct SmtpProvider {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpProvider {
+    pub fn from_settings(settings: &EmailSettings) -> Result<Self, String> {
+        let from: Mailbox = settings.from.parse()
+            .map_err(|e| format!("发件人地址 {:?} 无效: {}", settings.from, e))?;
+
+        let host = settings.smtp_host.as_str();
+        let builder = match settings.smtp_tls.as_str() {
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(host),
+            "none" => Ok(AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)),
+            other => return Err(format!("未知的 smtp_tls 取值 {}，可选: starttls、tls、none", other)),
+        }
+        .map_err(|e| format!("SMTP 配置无效: {}", e))?;
+
+        let mut builder = builder.port(settings.smtp_port);
+        if !settings.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                settings.smtp_username.clone(),
+                settings.smtp_password.clone(),
+            ));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+impl EmailProvider for SmtpProvider {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let to: Mailbox = email.to.parse()
+                .map_err(|e| format!("收件人地址 {} 无效: {}", email.to, e))?;
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(email.subject.as_str())
+                .header(ContentType::TEXT_PLAIN)
+                .body(email.body.clone())
+                .map_err(|e| format!("构造邮件失败: {}", e))?;
+            self.transport.send(message).await
+                .map(|_| ())
+                .map_err(|e| format!("SMTP 发送失败: {}", e))
+        })
+    }
+}
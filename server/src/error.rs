@@ -22,21 +22,60 @@ pub enum AppError {
     FriendOperation(String),
     #[error("资源未找到: {0}")]
     NotFound(String),
+    #[error("无权访问: {0}")]
+    Forbidden(String),
+    #[error("请求参数无效: {0}")]
+    InvalidInput(String),
+    #[error("请求过于频繁: {0}")]
+    RateLimited(String),
+    #[error("数据已被修改: {0}")]
+    Conflict(String),
+    #[error("服务器维护中: {0}")]
+    Maintenance(String),
+    /// 被群主禁言，值为禁言结束时间
+    #[error("已被禁言至 {0}")]
+    Muted(i64),
+}
+
+impl AppError {
+    /// HTTP 状态码、错误类别 code 和提示语；提示语在语言目录中有对应条目时 code 会被替换为更具体的 code
+    pub fn parts(self) -> (StatusCode, &'static str, String) {
+        match self {
+            AppError::UserExists(e) => (StatusCode::CONFLICT, "error.user_exists", e),
+            AppError::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, "error.database", e),
+            AppError::Bcrypt(e) => (StatusCode::INTERNAL_SERVER_ERROR, "error.internal", e.to_string()),
+            AppError::InvalidCredentials(e) => (StatusCode::UNAUTHORIZED, "error.invalid_credentials", e),
+            AppError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, "error.internal", e),
+            AppError::FriendOperation(e) => (StatusCode::BAD_REQUEST, "error.friend_operation", e),
+            AppError::NotFound(e) => (StatusCode::NOT_FOUND, "error.not_found", e),
+            AppError::Forbidden(e) => (StatusCode::FORBIDDEN, "error.forbidden", e),
+            AppError::InvalidInput(e) => (StatusCode::BAD_REQUEST, "error.invalid_input", e),
+            AppError::RateLimited(e) => (StatusCode::TOO_MANY_REQUESTS, "error.rate_limited", e),
+            AppError::Conflict(e) => (StatusCode::CONFLICT, "error.conflict", e),
+            // 提示语可由管理员自定义，code 固定，客户端据此识别维护状态
+            AppError::Maintenance(e) => (StatusCode::SERVICE_UNAVAILABLE, "server.maintenance", e),
+            AppError::Muted(until) => (StatusCode::FORBIDDEN, "error.forbidden", format!("你已被禁言，{} 之前不能在群里发言", until)),
+        }
+    }
+
+    /// 随错误一起返回给客户端的结构化字段，目前只有禁言结束时间 muted_until
+    pub fn details(&self) -> Option<(&'static str, i64)> {
+        match self {
+            AppError::Muted(until) => Some(("muted_until", *until)),
+            _ => None,
+        }
+    }
 }
 
 // 实现axum的错误转换
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, msg) = match self {
-            AppError::UserExists(e) => (StatusCode::CONFLICT, e),
-            AppError::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
-            AppError::Bcrypt(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
-            AppError::InvalidCredentials(e) => (StatusCode::UNAUTHORIZED, e),
-            AppError::Internal(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
-            AppError::FriendOperation(e) => (StatusCode::BAD_REQUEST, e),
-            AppError::NotFound(e) => (StatusCode::NOT_FOUND, e),
-        };
-        let body = Json(json!({ "success": false, "message": msg }));
-        (status, body).into_response()
+        let details = self.details();
+        let (status, code, msg) = self.parts();
+        let mut body = json!({ "success": false, "code": code, "message": msg });
+        if let Some((key, value)) = details {
+            body[key] = json!(value);
+        }
+        (status, Json(body)).into_response()
     }
 }
\ No newline at end of file
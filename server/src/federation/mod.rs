@@ -0,0 +1,192 @@
+//! 服务器间联邦：发给 user@other-host 的私聊消息通过 HTTPS 转发到对端月灵服务器
+//!
+//! 请求体用本服的 Ed25519 密钥签名，对端用白名单中登记的公钥校验；
+//! 传输层可配置客户端证书做双向 TLS（入站方向由反向代理校验客户端证书）
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::settings::FederationSettings;
+use crate::storage::federation::FederationPeer;
+
+// 对端用于标识来源服务器和携带签名的请求头
+pub const ORIGIN_HEADER: &str = "x-yueling-origin";
+pub const SIGNATURE_HEADER: &str = "x-yueling-signature";
+
+// 入站载荷的 sent_at 与本机时间允许的最大偏差，超出视为重放
+pub const MAX_CLOCK_SKEW_SECS: i64 = 300;
+
+/// 把 user@host 形式的地址拆成 (用户名, 主机)
+pub fn parse_address(address: &str) -> Option<(&str, &str)> {
+    let (username, host) = address.rsplit_once('@')?;
+    if username.is_empty() || host.is_empty() || username.contains('@') {
+        return None;
+    }
+    Some((username, host))
+}
+
+/// 对端消息在本服保存时使用的ID：由来源服务器和对端的消息ID派生（UUID v8），
+/// 不会与本服生成的 v4/v7 ID 或其他来源的消息重复，同一来源重试时得到相同的ID
+pub fn local_message_id(origin: &str, message_id: &str) -> String {
+    let digest = Sha256::new().chain_update(origin).chain_update([0]).chain_update(message_id).finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    uuid::Builder::from_custom_bytes(bytes).into_uuid().to_string()
+}
+
+/// 服务器间转发的一条私聊消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FederatedMessage {
+    pub message_id: String,
+    pub sender: String,    // 发送者在来源服务器上的用户名
+    pub recipient: String, // 接收者在目标服务器上的用户名
+    pub content: String,
+    pub created_at: i64,
+    pub sent_at: i64,      // 本次发送时间，用于拒绝过期的重放请求
+}
+
+/// 本服的 Ed25519 签名密钥
+pub struct ServerKey(SigningKey);
+
+impl ServerKey {
+    /// 从文件加载（十六进制的 32 字节种子），文件不存在时生成并保存
+    pub fn load_or_create(path: &Path) -> Result<Self, String> {
+        if path.exists() {
+            let text = std::fs::read_to_string(path)
+                .map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+            let seed: [u8; 32] = hex::decode(text.trim())
+                .ok()
+                .and_then(|bytes| bytes.try_into().ok())
+                .ok_or_else(|| format!("{} 不是有效的 Ed25519 密钥", path.display()))?;
+            return Ok(Self(SigningKey::from_bytes(&seed)));
+        }
+
+        // 私钥文件只有所有者可读写；create_new 保证不会覆盖同时生成的另一份密钥
+        let seed: [u8; 32] = rand::random();
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        options.open(path)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, hex::encode(seed).as_bytes()))
+            .map_err(|e| format!("写入 {} 失败: {}", path.display(), e))?;
+        println!("已生成联邦签名密钥 {}", path.display());
+        Ok(Self(SigningKey::from_bytes(&seed)))
+    }
+
+    /// 由种子创建（用于测试）
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        Self(SigningKey::from_bytes(&seed))
+    }
+
+    /// 公钥（base64），交给对端管理员登记
+    pub fn public_key(&self) -> String {
+        BASE64.encode(self.0.verifying_key().as_bytes())
+    }
+
+    /// 对请求体签名（base64）
+    pub fn sign(&self, body: &[u8]) -> String {
+        BASE64.encode(self.0.sign(body).to_bytes())
+    }
+}
+
+/// 用对端登记的公钥校验签名
+pub fn verify_signature(public_key: &str, body: &[u8], signature: &str) -> bool {
+    let Some(key) = BASE64.decode(public_key).ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+    else {
+        return false;
+    };
+    let Some(signature) = BASE64.decode(signature).ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .map(|bytes| Signature::from_bytes(&bytes))
+    else {
+        return false;
+    };
+    key.verify(body, &signature).is_ok()
+}
+
+/// 联邦客户端：签名并转发消息到对端
+#[derive(Clone)]
+pub struct Federation {
+    server_name: String,
+    key: Arc<ServerKey>,
+    client: reqwest::Client,
+}
+
+impl Federation {
+    pub fn new(server_name: String, key: ServerKey, client: reqwest::Client) -> Self {
+        Self {
+            server_name,
+            key: Arc::new(key),
+            client,
+        }
+    }
+
+    /// 按配置创建，未启用时返回 None
+    pub fn from_settings(settings: &FederationSettings) -> Result<Option<Self>, String> {
+        if !settings.enabled {
+            return Ok(None);
+        }
+        if settings.server_name.is_empty() {
+            return Err("启用联邦时必须配置 server_name".into());
+        }
+        let key = ServerKey::load_or_create(Path::new(&settings.signing_key_file))?;
+
+        let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(settings.timeout_secs));
+        if !settings.client_cert_file.is_empty() {
+            let mut pem = std::fs::read(&settings.client_cert_file)
+                .map_err(|e| format!("读取 {} 失败: {}", settings.client_cert_file, e))?;
+            pem.extend(std::fs::read(&settings.client_key_file)
+                .map_err(|e| format!("读取 {} 失败: {}", settings.client_key_file, e))?);
+            let identity = reqwest::Identity::from_pem(&pem)
+                .map_err(|e| format!("客户端证书无效: {}", e))?;
+            builder = builder.identity(identity);
+        }
+        if !settings.ca_file.is_empty() {
+            let pem = std::fs::read(&settings.ca_file)
+                .map_err(|e| format!("读取 {} 失败: {}", settings.ca_file, e))?;
+            let ca = reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| format!("CA 证书无效: {}", e))?;
+            builder = builder.add_root_certificate(ca);
+        }
+        let client = builder.build().map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+
+        Ok(Some(Self::new(settings.server_name.clone(), key, client)))
+    }
+
+    pub fn server_name(&self) -> &str {
+        &self.server_name
+    }
+
+    pub fn public_key(&self) -> String {
+        self.key.public_key()
+    }
+
+    /// 把消息转发给对端服务器
+    pub async fn relay(&self, peer: &FederationPeer, message: &FederatedMessage) -> Result<(), String> {
+        let body = serde_json::to_vec(message).map_err(|e| e.to_string())?;
+        let url = format!("{}/federation/messages", peer.base_url.trim_end_matches('/'));
+        let response = self.client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(ORIGIN_HEADER, &self.server_name)
+            .header(SIGNATURE_HEADER, self.key.sign(&body))
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(format!("对端返回 {}", response.status()))
+        }
+    }
+}
@@ -3,10 +3,23 @@ mod api;
 mod storage;
 mod core;
 mod config;
+mod tasks;
+mod push;
+mod email;
+mod translate;
+mod scan;
+mod federation;
+mod bus;
+mod crypto;
 
 // 导出核心功能模块
 pub use api::{
-    register_routes
+    grpc,
+    outbound,
+    recovery,
+    slow_requests,
+    register_routes,
+    router
 };
 
 pub use api::{
@@ -14,7 +27,35 @@ pub use api::{
 };
 
 pub use storage::{
-    DbPool
+    DbPool,
+    User,
+    Message,
+    Conversation,
+    NewMessage,
+    Friendship,
+    FriendRequest,
+    Group,
+    GroupMember,
+    backup,
+    bots,
+    cipher,
+    digest,
+    discovery,
+    export,
+    import,
+    integrity,
+    jobs,
+    migrations,
+    outbox,
+    queries,
+    quotas,
+    seed,
+    slow_query,
+    system_messages,
+    user_settings,
+    usernames,
+    webhooks,
+    workspaces
 };
 
 pub use error::{
@@ -22,11 +63,58 @@ pub use error::{
 };
 pub use core::{
     auth,
+    datetime,
+    markdown,
     models
 };
 pub use config::{
+    doctor,
     loader,
+    log_level,
     settings
 };
+pub use bus::{
+    BusEvent,
+    Cluster,
+    Envelope,
+    MessageBus
+};
+pub use federation::{
+    local_message_id,
+    FederatedMessage,
+    Federation,
+    ServerKey
+};
+pub use push::{
+    PushDispatcher,
+    PushError,
+    PushNotification,
+    PushProvider
+};
+pub use email::{
+    Email,
+    EmailProvider
+};
+pub use translate::Translator;
+pub use scan::{from_settings as attachment_scanner, Scanner, Verdict};
+pub use crypto::{
+    conversation::{self, ConversationKeys},
+    escrow,
+    field_crypto,
+    hardware_accelerated,
+    CryptoError,
+    CryptoService,
+    SEALED_FIELD_PREFIX
+};
+pub use tasks::{
+    attachment_gc,
+    spawn_background_tasks,
+    supervisor::{Supervisor, TaskState, TaskStatus},
+    cluster::spawn as spawn_cluster,
+    jobs::work_once as run_next_job,
+    outbox::redeliver_due as redeliver_outbox,
+    digest::{send_digests, PresenceCheck},
+    webhooks as webhook_dispatcher
+};
 
 
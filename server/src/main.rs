@@ -1,40 +1,186 @@
 use server::{
-    register_routes,
-    DbPool
+    grpc,
+    router,
+    spawn_background_tasks,
+    AppState,
+    cipher,
+    field_crypto,
+    attachment_scanner,
+    hardware_accelerated,
+    ConversationKeys,
+    integrity::{self, IntegrityStatus},
+    settings::Settings,
+    DbPool,
+    loader
 };
 
+use clap::Parser;
+use std::net::SocketAddr;
+use std::time::Duration;
 use tokio::net::TcpListener;
 use tower_http::cors::{CorsLayer, Any};
 use axum::http::Method;
 
-/// 主函数：启动聊天服务器
-/// 
-/// 1. 初始化数据库连接池
+mod cli;
+
+use cli::{Cli, Command};
+
+/// 主函数：解析命令行并执行对应子命令
+///
+/// 不带子命令时启动服务器，其余子命令（migrate、backup、restore 等）执行完即退出
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let cli = Cli::parse();
+    if matches!(cli.command, Some(Command::Doctor)) {
+        return cli::doctor();
+    }
+    // 加载配置（config.toml 不存在时使用默认值）
+    let settings = loader::load()?;
+    // 数据库加密密钥（未开启加密时为 None）
+    let db_key = cipher::resolve_key(&settings)?;
+    // 开启消息或邮箱加密但缺少主密钥时直接退出，避免数据以明文落盘
+    ConversationKeys::from_settings(&settings.security)?;
+    field_crypto(&settings.security)?;
+    // 附件扫描配置无效时同样直接退出，避免在不扫描的情况下接受上传
+    attachment_scanner(&settings.scan)?;
+
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(settings, db_key).await,
+        command => cli::run(command, &settings, db_key.as_deref()),
+    }
+}
+
+/// 启动聊天服务器
+///
+/// 1. 自检并初始化数据库连接池
 /// 2. 构建API路由和WebSocket服务
 /// 3. 配置CORS
 /// 4. 启动HTTP和WebSocket服务器
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 初始化数据库连接池
-    let db_pool = DbPool::new("server.db")?;
+async fn serve(mut settings: Settings, db_key: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    // 启动自检，再初始化数据库连接池
+    let db_pool = match integrity::verify(&settings.database, db_key.as_deref())? {
+        IntegrityStatus::Healthy => DbPool::with_settings(&settings.database, db_key.as_deref())?,
+        IntegrityStatus::Degraded(problems) => {
+            eprintln!("数据库自检发现问题，以只读恢复模式启动（请尽快备份并修复）：");
+            for problem in &problems {
+                eprintln!("  {}", problem);
+            }
+            // 只读模式下保留策略和 VACUUM 都无法执行，备份仍然可用
+            settings.retention.run_interval_secs = 0;
+            settings.retention.vacuum_interval_secs = 0;
+            DbPool::open_read_only(&settings.database, db_key.as_deref())?
+        }
+    };
+
+    // 开启了应用层加密但 CPU 不支持 AES 硬件指令时提示，加解密会明显变慢
+    let encrypting = settings.security.encrypt_messages || settings.security.encrypt_emails;
+    if encrypting && !hardware_accelerated() {
+        eprintln!("当前 CPU 不支持 AES 硬件加速，消息加解密将使用较慢的软件实现");
+    }
 
     // 配置跨域资源共享（CORS）策略
     let cors = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE, Method::OPTIONS])
         .allow_headers(Any);
 
     // 构建API路由
-    let app = register_routes(db_pool.clone()).layer(cors);
-
-    // 启动服务器
-    let port = 2025;
-    let addr = format!("0.0.0.0:{}", port);
-    let listener = TcpListener::bind(&addr).await?;
-    println!("服务器正在监听 http://{} (HTTP) 和 ws://{} (WebSocket)", addr, addr);
-    
-    // 启动HTTP和WebSocket服务
-    axum::serve(listener, app).await?;
-    
+    let state = AppState::new(db_pool, settings.clone());
+    let app = router(state.clone()).layer(cors);
+
+    // 启动后台任务（自动备份、邮件摘要等）
+    spawn_background_tasks(&state);
+
+    // 按配置启动 gRPC 服务（与 HTTP 共用同一份应用状态）
+    if settings.grpc.port != 0 {
+        let grpc_addr = format!("{}:{}", settings.server.host, settings.grpc.port);
+        let grpc_listener = TcpListener::bind(&grpc_addr).await?;
+        println!("gRPC 服务正在监听 {}", grpc_addr);
+        let grpc_state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = grpc::serve(grpc_state, grpc_listener).await {
+                eprintln!("gRPC 服务异常退出: {}", e);
+            }
+        });
+    }
+
+    if !settings.server.tcp && settings.server.unix_socket.is_empty() {
+        return Err("server.tcp 为 false 时必须配置 server.unix_socket".into());
+    }
+
+    // 按配置同时监听 Unix 套接字
+    let unix_server = if settings.server.unix_socket.is_empty() {
+        None
+    } else {
+        Some(serve_unix(&settings.server.unix_socket, app.clone())?)
+    };
+
+    if !settings.server.tcp {
+        // 只监听 Unix 套接字
+        if let Some(unix_server) = unix_server {
+            tokio::select! {
+                result = unix_server => result??,
+                _ = shutdown_signal() => {}
+            }
+        }
+    } else {
+        // 启动服务器
+        let addr = format!("{}:{}", settings.server.host, settings.server.port);
+        let listener = TcpListener::bind(&addr).await?;
+        println!("服务器正在监听 http://{} (HTTP) 和 ws://{} (WebSocket)", addr, addr);
+
+        // 启动HTTP和WebSocket服务（携带对端地址，供 IP 访问控制使用）
+        let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>());
+        tokio::select! {
+            result = server => result?,
+            _ = shutdown_signal() => {}
+        }
+    }
+
+    // 不等待 WebSocket 长连接自行断开，停止后台任务并执行关闭钩子后退出
+    println!("正在关闭服务器");
+    state.tasks.shutdown(Duration::from_secs(settings.server.shutdown_timeout_secs)).await;
     Ok(())
 }
+
+/// 等待 Ctrl+C 或 SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// 在 Unix 套接字上提供 HTTP 和 WebSocket 服务
+///
+/// 启动前删除上次运行遗留的套接字文件；经由套接字的请求没有来源 IP，
+/// 配置了 `[access]` 规则时需要开启 `trust_forwarded_for` 并由反向代理传递 X-Forwarded-For
+#[cfg(unix)]
+fn serve_unix(path: &str, app: axum::Router) -> Result<tokio::task::JoinHandle<std::io::Result<()>>, Box<dyn std::error::Error>> {
+    let path = std::path::Path::new(path);
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = tokio::net::UnixListener::bind(path)?;
+    println!("服务器正在监听 Unix 套接字 {}", path.display());
+    Ok(tokio::spawn(async move { axum::serve(listener, app).await }))
+}
+
+#[cfg(not(unix))]
+fn serve_unix(_path: &str, _app: axum::Router) -> Result<tokio::task::JoinHandle<std::io::Result<()>>, Box<dyn std::error::Error>> {
+    Err("当前系统不支持 Unix 套接字，请清空 server.unix_socket".into())
+}
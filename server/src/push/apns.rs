@@ -0,0 +1,122 @@
+//! Apple Push Notification service，使用 .p8 密钥的令牌认证（ES256 JWT）
+
+use futures_util::future::BoxFuture;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::Serialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::{PushError, PushNotification, PushProvider, PLATFORM_APNS};
+use crate::config::settings::PushSettings;
+
+const PRODUCTION_HOST: &str = "https://api.push.apple.com";
+const SANDBOX_HOST: &str = "https://api.sandbox.push.apple.com";
+// APNs 要求认证令牌在 20~60 分钟之间刷新
+const TOKEN_LIFETIME: Duration = Duration::from_secs(50 * 60);
+
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    iat: u64,
+}
+
+pub struct ApnsProvider {
+    client: reqwest::Client,
+    host: &'static str,
+    key_id: String,
+    team_id: String,
+    topic: String,
+    key: EncodingKey,
+    auth_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl ApnsProvider {
+    pub fn from_settings(settings: &PushSettings) -> Result<Self, String> {
+        if settings.apns_key_id.is_empty() || settings.apns_team_id.is_empty() || settings.apns_topic.is_empty() {
+            return Err("需要同时配置 apns_key_id、apns_team_id 和 apns_topic".into());
+        }
+        let pem = std::fs::read(&settings.apns_key_file)
+            .map_err(|e| format!("读取 {} 失败: {}", settings.apns_key_file, e))?;
+        let key = EncodingKey::from_ec_pem(&pem).map_err(|e| format!("APNs 密钥无效: {}", e))?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            host: if settings.apns_sandbox { SANDBOX_HOST } else { PRODUCTION_HOST },
+            key_id: settings.apns_key_id.clone(),
+            team_id: settings.apns_team_id.clone(),
+            topic: settings.apns_topic.clone(),
+            key,
+            auth_token: Mutex::new(None),
+        })
+    }
+
+    // 获取（必要时重新签发）认证令牌
+    async fn auth_token(&self) -> Result<String, PushError> {
+        let mut cached = self.auth_token.lock().await;
+        if let Some((token, issued_at)) = cached.as_ref()
+            && issued_at.elapsed() < TOKEN_LIFETIME
+        {
+            return Ok(token.clone());
+        }
+
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some(self.key_id.clone());
+        let claims = Claims {
+            iss: &self.team_id,
+            iat: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        };
+        let token = jsonwebtoken::encode(&header, &claims, &self.key)
+            .map_err(|e| PushError::Failed(format!("签名 JWT 失败: {}", e)))?;
+
+        *cached = Some((token.clone(), Instant::now()));
+        Ok(token)
+    }
+}
+
+impl PushProvider for ApnsProvider {
+    fn platform(&self) -> &'static str {
+        PLATFORM_APNS
+    }
+
+    fn send<'a>(&'a self, token: &'a str, notification: &'a PushNotification) -> BoxFuture<'a, Result<(), PushError>> {
+        Box::pin(async move {
+            let auth_token = self.auth_token().await?;
+            let body = json!({
+                "aps": {
+                    "alert": { "title": notification.title, "body": notification.body },
+                    "sound": "default"
+                },
+                "message_id": notification.message_id,
+                "conversation_id": notification.conversation_id
+            });
+            let response = self.client
+                .post(format!("{}/3/device/{}", self.host, token))
+                .bearer_auth(auth_token)
+                .header("apns-topic", &self.topic)
+                .header("apns-push-type", "alert")
+                .header("apns-priority", "10")
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| PushError::Failed(e.to_string()))?;
+
+            match response.status() {
+                status if status.is_success() => Ok(()),
+                // 410 Unregistered：应用已卸载
+                reqwest::StatusCode::GONE => Err(PushError::InvalidToken),
+                status => {
+                    let reason = response.text().await.unwrap_or_default();
+                    if reason.contains("BadDeviceToken") {
+                        Err(PushError::InvalidToken)
+                    } else {
+                        Err(PushError::Failed(format!("HTTP {} {}", status, reason)))
+                    }
+                }
+            }
+        })
+    }
+}
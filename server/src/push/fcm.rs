@@ -0,0 +1,146 @@
+//! Firebase Cloud Messaging HTTP v1 接口，使用服务账号换取 OAuth2 访问令牌
+
+use futures_util::future::BoxFuture;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::{PushError, PushNotification, PushProvider, PLATFORM_FCM};
+use crate::config::settings::PushSettings;
+
+// 访问令牌权限范围
+const FCM_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+// 在过期前提前刷新访问令牌
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+// 服务账号 JSON 文件中用到的字段
+#[derive(Deserialize)]
+struct ServiceAccount {
+    project_id: String,
+    client_email: String,
+    private_key: String,
+    token_uri: String,
+}
+
+// 换取访问令牌用的 JWT 声明
+#[derive(Serialize)]
+struct Claims<'a> {
+    iss: &'a str,
+    scope: &'a str,
+    aud: &'a str,
+    iat: u64,
+    exp: u64,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+pub struct FcmProvider {
+    client: reqwest::Client,
+    project_id: String,
+    client_email: String,
+    token_uri: String,
+    key: EncodingKey,
+    access_token: Mutex<Option<(String, Instant)>>,
+}
+
+impl FcmProvider {
+    pub fn from_settings(settings: &PushSettings) -> Result<Self, String> {
+        let text = std::fs::read_to_string(&settings.fcm_service_account_file)
+            .map_err(|e| format!("读取 {} 失败: {}", settings.fcm_service_account_file, e))?;
+        let account: ServiceAccount = serde_json::from_str(&text)
+            .map_err(|e| format!("解析服务账号文件失败: {}", e))?;
+        let key = EncodingKey::from_rsa_pem(account.private_key.as_bytes())
+            .map_err(|e| format!("服务账号私钥无效: {}", e))?;
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            project_id: account.project_id,
+            client_email: account.client_email,
+            token_uri: account.token_uri,
+            key,
+            access_token: Mutex::new(None),
+        })
+    }
+
+    // 获取（必要时刷新）OAuth2 访问令牌
+    async fn access_token(&self) -> Result<String, PushError> {
+        let mut cached = self.access_token.lock().await;
+        if let Some((token, expires_at)) = cached.as_ref()
+            && Instant::now() + TOKEN_REFRESH_MARGIN < *expires_at
+        {
+            return Ok(token.clone());
+        }
+
+        let iat = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let claims = Claims {
+            iss: &self.client_email,
+            scope: FCM_SCOPE,
+            aud: &self.token_uri,
+            iat,
+            exp: iat + 3600,
+        };
+        let assertion = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .map_err(|e| PushError::Failed(format!("签名 JWT 失败: {}", e)))?;
+
+        // JWT 只含 base64url 字符和点号，无需再做 URL 编码
+        let response = self.client
+            .post(&self.token_uri)
+            .header(reqwest::header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(format!("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Ajwt-bearer&assertion={}", assertion))
+            .send()
+            .await
+            .map_err(|e| PushError::Failed(e.to_string()))?;
+        if !response.status().is_success() {
+            return Err(PushError::Failed(format!("获取 FCM 访问令牌失败: HTTP {}", response.status())));
+        }
+        let token: TokenResponse = response.json().await.map_err(|e| PushError::Failed(e.to_string()))?;
+
+        *cached = Some((token.access_token.clone(), Instant::now() + Duration::from_secs(token.expires_in)));
+        Ok(token.access_token)
+    }
+}
+
+impl PushProvider for FcmProvider {
+    fn platform(&self) -> &'static str {
+        PLATFORM_FCM
+    }
+
+    fn send<'a>(&'a self, token: &'a str, notification: &'a PushNotification) -> BoxFuture<'a, Result<(), PushError>> {
+        Box::pin(async move {
+            let access_token = self.access_token().await?;
+            let body = json!({
+                "message": {
+                    "token": token,
+                    "notification": { "title": notification.title, "body": notification.body },
+                    "data": {
+                        "message_id": notification.message_id,
+                        "conversation_id": notification.conversation_id
+                    }
+                }
+            });
+            let response = self.client
+                .post(format!("https://fcm.googleapis.com/v1/projects/{}/messages:send", self.project_id))
+                .bearer_auth(access_token)
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| PushError::Failed(e.to_string()))?;
+
+            match response.status() {
+                status if status.is_success() => Ok(()),
+                // UNREGISTERED：令牌已失效
+                reqwest::StatusCode::NOT_FOUND => Err(PushError::InvalidToken),
+                status => Err(PushError::Failed(format!("HTTP {}", status))),
+            }
+        })
+    }
+}
@@ -0,0 +1,136 @@
+//! 离线推送：用户没有活跃的 WebSocket 连接时，通过 FCM / APNs 推送新消息提醒
+
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::config::settings::PushSettings;
+use crate::storage::DbPool;
+
+pub mod apns;
+pub mod fcm;
+
+// 推送平台标识（与 push_tokens.platform 一致）
+pub const PLATFORM_FCM: &str = "fcm";
+pub const PLATFORM_APNS: &str = "apns";
+
+pub const PLATFORMS: &[&str] = &[PLATFORM_FCM, PLATFORM_APNS];
+
+// 推送正文的最大字符数
+const MAX_BODY_CHARS: usize = 100;
+
+/// 一条推送通知
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushNotification {
+    pub title: String,
+    pub body: String,
+    pub message_id: String,
+    pub conversation_id: String,
+}
+
+impl PushNotification {
+    /// 由消息内容构造通知，正文过长时截断
+    pub fn new_message(sender_name: &str, content: &str, message_id: &str, conversation_id: &str) -> Self {
+        let mut body: String = content.chars().take(MAX_BODY_CHARS).collect();
+        if content.chars().count() > MAX_BODY_CHARS {
+            body.push('…');
+        }
+        Self {
+            title: sender_name.to_string(),
+            body,
+            message_id: message_id.to_string(),
+            conversation_id: conversation_id.to_string(),
+        }
+    }
+}
+
+/// 推送失败原因
+#[derive(Debug)]
+pub enum PushError {
+    /// 令牌已失效（应用被卸载等），应删除
+    InvalidToken,
+    /// 其他错误，保留令牌
+    Failed(String),
+}
+
+/// 推送服务提供方（FCM、APNs 或测试替身）
+pub trait PushProvider: Send + Sync {
+    /// 对应的平台标识
+    fn platform(&self) -> &'static str;
+    /// 向单个设备令牌发送通知
+    fn send<'a>(&'a self, token: &'a str, notification: &'a PushNotification) -> BoxFuture<'a, Result<(), PushError>>;
+}
+
+/// 推送分发器：查询用户的设备令牌并交给对应平台发送
+#[derive(Clone)]
+pub struct PushDispatcher {
+    db_pool: DbPool,
+    providers: Arc<HashMap<&'static str, Arc<dyn PushProvider>>>,
+}
+
+impl PushDispatcher {
+    pub fn new(db_pool: DbPool, providers: Vec<Arc<dyn PushProvider>>) -> Self {
+        let providers = providers.into_iter().map(|p| (p.platform(), p)).collect();
+        Self {
+            db_pool,
+            providers: Arc::new(providers),
+        }
+    }
+
+    /// 按配置创建已启用的平台，配置错误的平台会被跳过并打印原因
+    pub fn from_settings(db_pool: DbPool, settings: &PushSettings) -> Self {
+        let mut providers: Vec<Arc<dyn PushProvider>> = Vec::new();
+        if !settings.fcm_service_account_file.is_empty() {
+            match fcm::FcmProvider::from_settings(settings) {
+                Ok(provider) => providers.push(Arc::new(provider)),
+                Err(e) => println!("FCM 推送配置无效，已禁用: {}", e),
+            }
+        }
+        if !settings.apns_key_file.is_empty() {
+            match apns::ApnsProvider::from_settings(settings) {
+                Ok(provider) => providers.push(Arc::new(provider)),
+                Err(e) => println!("APNs 推送配置无效，已禁用: {}", e),
+            }
+        }
+        Self::new(db_pool, providers)
+    }
+
+    /// 是否至少启用了一个平台
+    pub fn is_enabled(&self) -> bool {
+        !self.providers.is_empty()
+    }
+
+    /// 向这些用户的所有设备发送通知，返回成功条数；失效的令牌会被删除
+    pub async fn notify(&self, user_ids: Vec<String>, notification: PushNotification) -> usize {
+        let db_pool = self.db_pool.clone();
+        let tokens = match tokio::task::spawn_blocking(move || db_pool.push_tokens_for(&user_ids)).await {
+            Ok(Ok(tokens)) => tokens,
+            Ok(Err(e)) => {
+                println!("查询推送令牌失败: {}", e);
+                return 0;
+            }
+            Err(e) => {
+                println!("查询推送令牌任务异常: {}", e);
+                return 0;
+            }
+        };
+
+        let mut sent = 0;
+        for token in tokens {
+            let Some(provider) = self.providers.get(token.platform.as_str()) else {
+                continue;
+            };
+            match provider.send(&token.token, &notification).await {
+                Ok(()) => sent += 1,
+                Err(PushError::InvalidToken) => {
+                    println!("推送令牌已失效，删除: 用户 {} ({})", token.user_id, token.platform);
+                    let db_pool = self.db_pool.clone();
+                    let _ = tokio::task::spawn_blocking(move || db_pool.remove_push_token(&token.user_id, &token.token)).await;
+                }
+                Err(PushError::Failed(e)) => println!("向用户 {} 推送失败 ({}): {}", token.user_id, token.platform, e),
+            }
+        }
+        sent
+    }
+}
@@ -0,0 +1,93 @@
+//! ClamAV 扫描：通过 clamd 的 INSTREAM 命令把文件内容发给守护进程，不要求 clamd 能读取附件目录
+//!
+//! 内容超过 clamd 的 StreamMaxLength 时 clamd 返回错误，按扫描失败处理
+
+use futures_util::future::BoxFuture;
+use std::path::Path;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::{Scanner, Verdict};
+use crate::config::settings::ScanSettings;
+
+// 每个 INSTREAM 数据块的大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// Unix 套接字地址的前缀（仅 Unix 系统）
+#[cfg(unix)]
+const UNIX_PREFIX: &str = "unix:";
+
+pub struct ClamAv {
+    address: String,
+    timeout: Duration,
+}
+
+impl ClamAv {
+    pub fn from_settings(settings: &ScanSettings) -> Result<Self, String> {
+        if settings.clamav_address.is_empty() {
+            return Err("clamav 后端需要配置 clamav_address".into());
+        }
+        Ok(Self {
+            address: settings.clamav_address.clone(),
+            timeout: Duration::from_secs(settings.timeout_secs),
+        })
+    }
+
+    async fn run(&self, path: &Path) -> Result<Verdict, String> {
+        #[cfg(unix)]
+        if let Some(socket) = self.address.strip_prefix(UNIX_PREFIX) {
+            let stream = tokio::net::UnixStream::connect(socket).await
+                .map_err(|e| format!("连接 clamd {} 失败: {}", self.address, e))?;
+            return instream(stream, path).await;
+        }
+        let stream = tokio::net::TcpStream::connect(&self.address).await
+            .map_err(|e| format!("连接 clamd {} 失败: {}", self.address, e))?;
+        instream(stream, path).await
+    }
+}
+
+// 发送 zINSTREAM 命令和以 4 字节大端长度开头的数据块，长度为 0 的块表示结束，然后读取 clamd 的结论
+async fn instream<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, path: &Path) -> Result<Verdict, String> {
+    let mut file = tokio::fs::File::open(path).await
+        .map_err(|e| format!("打开 {} 失败: {}", path.display(), e))?;
+    let send_error = |e: std::io::Error| format!("向 clamd 发送数据失败: {}", e);
+    stream.write_all(b"zINSTREAM\0").await.map_err(send_error)?;
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await
+            .map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+        stream.write_all(&(n as u32).to_be_bytes()).await.map_err(send_error)?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&buf[..n]).await.map_err(send_error)?;
+    }
+    stream.flush().await.map_err(send_error)?;
+
+    let mut reply = Vec::new();
+    stream.read_to_end(&mut reply).await
+        .map_err(|e| format!("读取 clamd 的结果失败: {}", e))?;
+    parse_reply(&String::from_utf8_lossy(&reply))
+}
+
+// 解析 clamd 的回复：stream: OK、stream: <病毒名> FOUND，其余（如 ... ERROR）视为扫描失败
+fn parse_reply(reply: &str) -> Result<Verdict, String> {
+    let reply = reply.trim_end_matches(['\0', '\r', '\n']);
+    let result = reply.strip_prefix("stream:").map(str::trim).unwrap_or(reply);
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(name) = result.strip_suffix(" FOUND") {
+        Ok(Verdict::Infected(name.to_string()))
+    } else {
+        Err(format!("clamd 返回错误: {}", reply))
+    }
+}
+
+impl Scanner for ClamAv {
+    fn scan<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Verdict, String>> {
+        Box::pin(async move {
+            tokio::time::timeout(self.timeout, self.run(path)).await
+                .map_err(|_| format!("clamd 超过 {} 秒未完成扫描", self.timeout.as_secs()))?
+        })
+    }
+}
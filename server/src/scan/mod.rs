@@ -0,0 +1,39 @@
+//! 附件病毒扫描：通过 Scanner 抽象接入扫描程序，上传的文件在登记为附件之前扫描
+
+use futures_util::future::BoxFuture;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::config::settings::ScanSettings;
+
+pub mod clamav;
+
+/// 检出病毒时的处理方式
+pub const ACTION_REJECT: &str = "reject";
+pub const ACTION_QUARANTINE: &str = "quarantine";
+
+/// 一个文件的扫描结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// 检出的病毒或恶意软件名称
+    Infected(String),
+}
+
+/// 扫描后端（ClamAV 或测试替身）
+pub trait Scanner: Send + Sync {
+    /// 扫描 path 处已写完的文件
+    fn scan<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Verdict, String>>;
+}
+
+/// 按配置创建扫描后端，未配置 backend 时返回 None
+pub fn from_settings(settings: &ScanSettings) -> Result<Option<Arc<dyn Scanner>>, String> {
+    if ![ACTION_REJECT, ACTION_QUARANTINE].contains(&settings.action.as_str()) {
+        return Err(format!("未知的处理方式 {}，可选: {}、{}", settings.action, ACTION_REJECT, ACTION_QUARANTINE));
+    }
+    match settings.backend.as_str() {
+        "" => Ok(None),
+        "clamav" => Ok(Some(Arc::new(clamav::ClamAv::from_settings(settings)?))),
+        other => Err(format!("未知的扫描后端 {}，可选: clamav", other)),
+    }
+}
@@ -0,0 +1,245 @@
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{id_array, DbPool};
+use crate::core::markdown::TextEntity;
+
+// 聊天附件的元数据
+#[derive(Debug, Clone, Serialize)]
+pub struct Attachment {
+    pub id: String,
+    pub uploader_id: String,
+    pub filename: String,     // 上传时的原始文件名（已去掉路径）
+    pub content_type: String,
+    pub size: i64,            // 字节数
+    pub sha256: String,       // 文件内容的 SHA-256（十六进制）
+    pub created_at: i64,
+    #[serde(skip)]
+    pub blob: String,         // 文件名：内容的带密钥哈希，内容相同的附件共用一个文件；同时用作下载的 ETag
+    pub scan_status: String,  // 病毒扫描状态，见 SCAN_* 常量
+    pub scan_detail: String,  // 隔离原因：检出的病毒名或扫描失败的原因
+}
+
+// 消息引用的一个附件，用于会话导出
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageAttachment {
+    pub message_id: String,
+    #[serde(flatten)]
+    pub attachment: Attachment,
+}
+
+const ATTACHMENT_COLUMNS: &str = "id, uploader_id, filename, content_type, size, sha256, created_at, blob, scan_status, scan_detail";
+
+// 附件的病毒扫描状态：未配置扫描时上传的为未扫描；隔离的附件不能下载，管理员审核后放行或删除
+pub const SCAN_UNSCANNED: &str = "unscanned";
+pub const SCAN_CLEAN: &str = "clean";
+pub const SCAN_QUARANTINED: &str = "quarantined";
+pub const SCAN_RELEASED: &str = "released";
+
+// 附件ID为带连字符的 UUID
+const ATTACHMENT_ID_LEN: usize = 36;
+
+// 可以回收的附件：曾被消息引用、引用它的消息都已删除（软删除的消息可以恢复，不算删除），且不是自定义表情的图片
+const ORPHANED: &str = "
+    EXISTS (SELECT 1 FROM message_attachments ma WHERE ma.attachment_id = a.id)
+    AND NOT EXISTS (SELECT 1 FROM message_attachments ma JOIN messages m ON m.id = ma.message_id WHERE ma.attachment_id = a.id)
+    AND NOT EXISTS (SELECT 1 FROM custom_emoji e WHERE e.attachment_id = a.id)";
+
+impl Attachment {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            uploader_id: row.get(1)?,
+            filename: row.get(2)?,
+            content_type: row.get(3)?,
+            size: row.get(4)?,
+            sha256: row.get(5)?,
+            created_at: row.get(6)?,
+            blob: row.get(7)?,
+            scan_status: row.get(8)?,
+            scan_detail: row.get(9)?,
+        })
+    }
+}
+
+// 从消息内容和贴纸中识别附件ID，登记其中确实存在的附件（在保存消息的事务中调用）；
+// 客户端加密的内容中识别不到附件，这些附件不会被回收
+pub(super) fn link_message_attachments(conn: &Connection, message_id: &str, content: &str, entities: &[TextEntity]) -> Result<()> {
+    let candidates = content
+        .split(|c: char| !(c.is_ascii_hexdigit() || c == '-'))
+        .filter(|token| token.len() == ATTACHMENT_ID_LEN && Uuid::parse_str(token).is_ok())
+        .map(str::to_lowercase)
+        .chain(entities.iter().filter_map(|entity| entity.attachment_id.clone()));
+    let mut stmt = conn.prepare_cached(
+        "INSERT OR IGNORE INTO message_attachments (message_id, attachment_id) SELECT ?1, id FROM attachments WHERE id = ?2",
+    )?;
+    for attachment_id in candidates {
+        stmt.execute(params![message_id, attachment_id])?;
+    }
+    Ok(())
+}
+
+impl DbPool {
+    // 计算附件文件名（内容的 HMAC）用的密钥，由迁移随机生成
+    pub fn attachment_blob_key(&self) -> Result<Vec<u8>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row("SELECT value FROM server_secrets WHERE name = 'attachment_blob_key'", [], |row| row.get(0))
+    }
+
+    // 签名附件下载链接用的密钥，由迁移随机生成
+    pub fn attachment_url_key(&self) -> Result<Vec<u8>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row("SELECT value FROM server_secrets WHERE name = 'attachment_url_key'", [], |row| row.get(0))
+    }
+
+    // 在存储配额内登记已写入临时文件的附件：上传者已有附件加上这个超过 max_storage_bytes（0 为不限制）时
+    // 不登记，返回 false。统计和插入在同一把锁内完成，并发上传不会一起越过配额；
+    // place_blob 把临时文件放到共用的文件名下，也在锁内执行，不会与回收同一文件的操作交错
+    pub fn insert_attachment(
+        &self,
+        attachment: &Attachment,
+        max_storage_bytes: i64,
+        place_blob: impl FnOnce() -> std::io::Result<()>,
+    ) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        if max_storage_bytes > 0 {
+            let used: i64 = conn.query_row(
+                "SELECT COALESCE(SUM(size), 0) FROM attachments WHERE uploader_id = ?",
+                [&attachment.uploader_id],
+                |row| row.get(0),
+            )?;
+            if used + attachment.size > max_storage_bytes {
+                return Ok(false);
+            }
+        }
+        place_blob().map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        conn.execute(
+            "INSERT INTO attachments (id, uploader_id, filename, content_type, size, sha256, created_at, blob, scan_status, scan_detail)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                attachment.id,
+                attachment.uploader_id,
+                attachment.filename,
+                attachment.content_type,
+                attachment.size,
+                attachment.sha256,
+                attachment.created_at,
+                attachment.blob,
+                attachment.scan_status,
+                attachment.scan_detail,
+            ],
+        )?;
+        Ok(true)
+    }
+
+    pub fn get_attachment(&self, id: &str) -> Result<Option<Attachment>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM attachments WHERE id = ?", ATTACHMENT_COLUMNS),
+            [id],
+            Attachment::from_row,
+        ).optional()
+    }
+
+    // 用户能否读取附件：上传者本人；引用该附件的未删除消息的私聊双方或群成员；
+    // 用户所在工作区（默认工作区包含所有用户）的自定义表情图片
+    pub fn can_read_attachment(&self, attachment_id: &str, user_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM attachments WHERE id = ?1 AND uploader_id = ?2)
+                 OR EXISTS (
+                     SELECT 1 FROM message_attachments ma JOIN messages m ON m.id = ma.message_id
+                     WHERE ma.attachment_id = ?1 AND m.deleted_at IS NULL AND (
+                         (m.message_type != 'group' AND ?2 IN (m.sender_id, m.receiver_id))
+                         OR (m.message_type = 'group' AND EXISTS (
+                             SELECT 1 FROM group_members gm WHERE gm.group_id = m.receiver_id AND gm.user_id = ?2
+                         ))
+                     )
+                 )
+                 OR EXISTS (
+                     SELECT 1 FROM custom_emoji e WHERE e.attachment_id = ?1 AND (
+                         e.workspace_id = ?3
+                         OR e.workspace_id IN (SELECT workspace_id FROM workspace_members WHERE user_id = ?2)
+                     )
+                 )",
+            params![attachment_id, user_id, super::workspaces::DEFAULT_WORKSPACE],
+            |row| row.get(0),
+        )
+    }
+
+    // 这些消息引用的附件，按消息ID和上传时间排序
+    pub fn message_attachments(&self, message_ids: &[String]) -> Result<Vec<MessageAttachment>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, ma.message_id FROM message_attachments ma JOIN attachments ON id = ma.attachment_id
+             WHERE ma.message_id IN rarray(?1) ORDER BY ma.message_id, created_at",
+            ATTACHMENT_COLUMNS,
+        ))?;
+        stmt.query_map([id_array(message_ids)], |row| {
+            Ok(MessageAttachment { message_id: row.get(10)?, attachment: Attachment::from_row(row)? })
+        })?.collect()
+    }
+
+    // 全部附件，按上传时间排序
+    pub fn list_attachments(&self) -> Result<Vec<Attachment>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM attachments ORDER BY created_at", ATTACHMENT_COLUMNS))?;
+        stmt.query_map([], Attachment::from_row)?.collect()
+    }
+
+    // 引用它的消息都已删除、可以回收的附件
+    pub fn orphaned_attachments(&self) -> Result<Vec<Attachment>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM attachments a WHERE {}", ATTACHMENT_COLUMNS, ORPHANED))?;
+        stmt.query_map([], Attachment::from_row)?.collect()
+    }
+
+    // 删除可以回收的附件记录及其引用记录，期间又被新消息引用时不删除，返回 None；
+    // 没有其他附件共用该文件时在锁内调用 remove_blob 删除文件，返回其结果（释放的字节数）
+    pub fn delete_orphaned_attachment(&self, id: &str, remove_blob: impl FnOnce(&str) -> u64) -> Result<Option<u64>> {
+        self.delete_attachment_where(id, ORPHANED, remove_blob)
+    }
+
+    // 等待审核的隔离附件，按上传时间排序
+    pub fn quarantined_attachments(&self) -> Result<Vec<Attachment>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM attachments WHERE scan_status = ? ORDER BY created_at", ATTACHMENT_COLUMNS,
+        ))?;
+        stmt.query_map([SCAN_QUARANTINED], Attachment::from_row)?.collect()
+    }
+
+    // 放行隔离的附件，附件不存在或不在隔离中时返回 false
+    pub fn release_attachment(&self, id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE attachments SET scan_status = ?2 WHERE id = ?1 AND scan_status = ?3",
+            params![id, SCAN_RELEASED, SCAN_QUARANTINED],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // 删除隔离的附件及使用它的自定义表情，返回值同 delete_orphaned_attachment
+    pub fn delete_quarantined_attachment(&self, id: &str, remove_blob: impl FnOnce(&str) -> u64) -> Result<Option<u64>> {
+        self.delete_attachment_where(id, &format!("a.scan_status = '{}'", SCAN_QUARANTINED), remove_blob)
+    }
+
+    fn delete_attachment_where(&self, id: &str, condition: &str, remove_blob: impl FnOnce(&str) -> u64) -> Result<Option<u64>> {
+        let mut conn = self.0.lock().unwrap();
+        let tx = conn.transaction()?;
+        let blob: Option<String> = tx.query_row(
+            &format!("DELETE FROM attachments AS a WHERE a.id = ?1 AND {} RETURNING blob", condition),
+            [id],
+            |row| row.get(0),
+        ).optional()?;
+        let Some(blob) = blob else {
+            return Ok(None);
+        };
+        tx.execute("DELETE FROM message_attachments WHERE attachment_id = ?", [id])?;
+        tx.execute("DELETE FROM custom_emoji WHERE attachment_id = ?", [id])?;
+        let shared: bool = tx.query_row("SELECT EXISTS (SELECT 1 FROM attachments WHERE blob = ?)", [&blob], |row| row.get(0))?;
+        tx.commit()?;
+        Ok(Some(if shared { 0 } else { remove_blob(&blob) }))
+    }
+}
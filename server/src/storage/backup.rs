@@ -0,0 +1,102 @@
+use rusqlite::backup::Backup;
+use rusqlite::{Connection, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{cipher, DbPool};
+
+// 备份文件名前缀和后缀：server-<unix毫秒>.db（旧版本按 unix 秒命名）
+const BACKUP_PREFIX: &str = "server-";
+const BACKUP_SUFFIX: &str = ".db";
+
+// 小于该值的时间戳是旧版本写入的 unix 秒
+const SECONDS_CUTOFF: u64 = 1_000_000_000_000;
+
+impl DbPool {
+    // 使用 SQLite 在线备份 API 生成一致性快照，返回备份文件路径
+    // 数据库加密时备份文件使用同一密钥加密
+    pub fn backup_to_dir(&self, dir: &Path, key: Option<&str>) -> std::io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+
+        // 持锁后再选文件名，同一毫秒内的多次备份顺延到下一个未被占用的毫秒
+        let conn = self.0.lock().unwrap();
+        let mut created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let mut path = backup_path(dir, created_at);
+        while path.exists() {
+            created_at += 1;
+            path = backup_path(dir, created_at);
+        }
+
+        copy_database(&conn, &path, key).map_err(std::io::Error::other)?;
+        Ok(path)
+    }
+}
+
+fn backup_path(dir: &Path, created_at_ms: u64) -> PathBuf {
+    dir.join(format!("{}{}{}", BACKUP_PREFIX, created_at_ms, BACKUP_SUFFIX))
+}
+
+// 从备份文件名解析创建时间（毫秒），按秒命名的旧备份换算为毫秒
+fn backup_time_ms(path: &Path) -> Option<u64> {
+    let timestamp: u64 = path.file_name()?
+        .to_str()?
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_SUFFIX)?
+        .parse()
+        .ok()?;
+    Some(if timestamp < SECONDS_CUTOFF { timestamp * 1000 } else { timestamp })
+}
+
+// 列出目录中的备份文件（按创建时间从旧到新）
+pub fn list_backups(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups: Vec<(u64, PathBuf)> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter_map(|path| backup_time_ms(&path).map(|ms| (ms, path)))
+        .collect();
+    // 新旧两种命名的时间戳位数不同，按解析出的时间而不是文件名排序
+    backups.sort();
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+// 只保留最近 keep 份备份，返回被删除的文件
+pub fn prune_backups(dir: &Path, keep: usize) -> std::io::Result<Vec<PathBuf>> {
+    let backups = list_backups(dir)?;
+    let excess = backups.len().saturating_sub(keep);
+    let removed: Vec<PathBuf> = backups.into_iter().take(excess).collect();
+    for path in &removed {
+        fs::remove_file(path)?;
+    }
+    Ok(removed)
+}
+
+// 打开数据库文件，加密时先设置密钥
+fn open_with_key(path: &Path, key: Option<&str>) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    if let Some(key) = key {
+        cipher::apply_key(&conn, key)?;
+    }
+    Ok(conn)
+}
+
+// 把 src 的完整内容在线复制到 dst_path
+fn copy_database(src: &Connection, dst_path: &Path, key: Option<&str>) -> Result<()> {
+    let mut dst = open_with_key(dst_path, key)?;
+    let backup = Backup::new(src, &mut dst)?;
+    backup.run_to_completion(100, std::time::Duration::from_millis(10), None)
+}
+
+// 从备份文件恢复到目标数据库（服务器需处于停止状态）
+pub fn restore_from(db_path: &str, backup_path: &Path, key: Option<&str>) -> Result<()> {
+    if !backup_path.exists() {
+        return Err(rusqlite::Error::InvalidPath(backup_path.to_path_buf()));
+    }
+    let src = open_with_key(backup_path, key)?;
+    copy_database(&src, Path::new(db_path), key)
+}
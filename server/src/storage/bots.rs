@@ -0,0 +1,187 @@
+use bcrypt::{hash, DEFAULT_COST};
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::DbPool;
+
+// 只能发送消息
+pub const SCOPE_SEND: &str = "send";
+// 只能读取消息
+pub const SCOPE_READ: &str = "read";
+
+pub const BOT_SCOPES: &[&str] = &[SCOPE_SEND, SCOPE_READ];
+
+// API 密钥前缀，便于在日志和代码仓库中识别泄露的密钥
+const API_KEY_PREFIX: &str = "ylb_";
+
+// 机器人账号
+#[derive(Debug, Serialize)]
+pub struct Bot {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub keys: Vec<BotApiKey>,
+}
+
+// API 密钥元数据（不含密钥本身）
+#[derive(Debug, Serialize)]
+pub struct BotApiKey {
+    pub id: String,
+    pub scope: String,
+    pub created_at: i64,
+    pub last_used_at: Option<i64>,
+    pub revoked_at: Option<i64>,
+}
+
+// 校验通过的密钥
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey {
+    pub key_id: String,
+    pub bot_id: String,
+    pub scope: String,
+}
+
+// 新生成的密钥：明文只在创建时返回一次
+#[derive(Debug)]
+pub struct IssuedKey {
+    pub key_id: String,
+    pub api_key: String,
+}
+
+fn hash_api_key(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+impl DbPool {
+    // 创建机器人账号并签发第一个密钥，返回 (机器人ID, 密钥)
+    pub fn create_bot(&self, name: &str, scope: &str, now: i64) -> Result<(String, IssuedKey)> {
+        // 机器人不能通过密码登录：使用随机且不保存的密码
+        let password_hash = hash(random_hex(32), DEFAULT_COST)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let bot_id = Uuid::new_v4().to_string();
+
+        let key = self.with_tx(|conn| {
+            conn.execute(
+                "INSERT INTO users (id, username, email, password_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![bot_id, name, format!("{}@bot.local", bot_id), password_hash, now],
+            )?;
+            conn.execute(
+                "INSERT INTO bots (id, name, created_at) VALUES (?1, ?2, ?3)",
+                params![bot_id, name, now],
+            )?;
+            issue_key(conn, &bot_id, scope, now)
+        })?;
+        Ok((bot_id, key))
+    }
+
+    // 为已有机器人签发新密钥，机器人不存在时返回 None
+    pub fn issue_bot_key(&self, bot_id: &str, scope: &str, now: i64) -> Result<Option<IssuedKey>> {
+        self.with_tx(|conn| {
+            let exists = conn
+                .query_row("SELECT 1 FROM bots WHERE id = ?", [bot_id], |_| Ok(()))
+                .optional()?
+                .is_some();
+            if !exists {
+                return Ok(None);
+            }
+            issue_key(conn, bot_id, scope, now).map(Some)
+        })
+    }
+
+    // 吊销密钥，返回是否存在未吊销的该密钥
+    pub fn revoke_bot_key(&self, key_id: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE bot_api_keys SET revoked_at = ?2 WHERE id = ?1 AND revoked_at IS NULL",
+            params![key_id, now],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // 用户是否为机器人账号
+    pub fn is_bot(&self, user_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.query_row("SELECT 1 FROM bots WHERE id = ?", [user_id], |_| Ok(())).optional()?.is_some())
+    }
+
+    // 列出所有机器人及其密钥
+    pub fn list_bots(&self) -> Result<Vec<Bot>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT id, name, created_at FROM bots ORDER BY created_at")?;
+        let mut bots: Vec<Bot> = stmt
+            .query_map([], |row| {
+                Ok(Bot {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                    keys: Vec::new(),
+                })
+            })?
+            .collect::<Result<_>>()?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, scope, created_at, last_used_at, revoked_at FROM bot_api_keys WHERE bot_id = ? ORDER BY created_at"
+        )?;
+        for bot in &mut bots {
+            bot.keys = stmt
+                .query_map([&bot.id], |row| {
+                    Ok(BotApiKey {
+                        id: row.get(0)?,
+                        scope: row.get(1)?,
+                        created_at: row.get(2)?,
+                        last_used_at: row.get(3)?,
+                        revoked_at: row.get(4)?,
+                    })
+                })?
+                .collect::<Result<_>>()?;
+        }
+        Ok(bots)
+    }
+
+    // 校验 API 密钥并记录使用时间；密钥无效、已吊销或机器人已删除时返回 None
+    pub fn authenticate_bot_key(&self, api_key: &str, now: i64) -> Result<Option<AuthenticatedKey>> {
+        let conn = self.0.lock().unwrap();
+        let key = conn
+            .query_row(
+                "SELECT k.id, k.bot_id, k.scope FROM bot_api_keys k
+                 JOIN users u ON u.id = k.bot_id
+                 WHERE k.key_hash = ?1 AND k.revoked_at IS NULL AND u.deleted_at IS NULL",
+                [hash_api_key(api_key)],
+                |row| {
+                    Ok(AuthenticatedKey {
+                        key_id: row.get(0)?,
+                        bot_id: row.get(1)?,
+                        scope: row.get(2)?,
+                    })
+                },
+            )
+            .optional()?;
+        if let Some(key) = &key {
+            conn.execute(
+                "UPDATE bot_api_keys SET last_used_at = ?2 WHERE id = ?1",
+                params![key.key_id, now],
+            )?;
+        }
+        Ok(key)
+    }
+}
+
+// 生成并保存一个新密钥
+fn issue_key(conn: &rusqlite::Transaction, bot_id: &str, scope: &str, now: i64) -> Result<IssuedKey> {
+    let api_key = format!("{}{}", API_KEY_PREFIX, random_hex(32));
+    let key_id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO bot_api_keys (id, bot_id, key_hash, scope, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![key_id, bot_id, hash_api_key(&api_key), scope, now],
+    )?;
+    Ok(IssuedKey { key_id, api_key })
+}
@@ -0,0 +1,39 @@
+use moka::sync::Cache;
+use std::time::Duration;
+
+use super::{GroupMember, User};
+use crate::config::settings::DatabaseSettings;
+
+// 热点查询的进程内缓存
+// 读写都在持有数据库连接锁时进行，保证写入后的失效不会被并发读回填旧值
+#[derive(Clone)]
+pub struct StorageCache {
+    pub(crate) users: Cache<String, User>,
+    pub(crate) group_members: Cache<String, Vec<GroupMember>>,
+}
+
+impl StorageCache {
+    pub fn new(settings: &DatabaseSettings) -> Self {
+        let ttl = Duration::from_secs(settings.cache_ttl_secs);
+        Self {
+            users: Cache::builder()
+                .max_capacity(settings.cache_capacity)
+                .time_to_live(ttl)
+                .build(),
+            group_members: Cache::builder()
+                .max_capacity(settings.cache_capacity)
+                .time_to_live(ttl)
+                .build(),
+        }
+    }
+
+    // 用户资料变更后调用
+    pub fn invalidate_user(&self, user_id: &str) {
+        self.users.invalidate(user_id);
+    }
+
+    // 群成员变更后调用
+    pub fn invalidate_group(&self, group_id: &str) {
+        self.group_members.invalidate(group_id);
+    }
+}
@@ -0,0 +1,30 @@
+use rand::RngCore;
+use rusqlite::{params, Result};
+
+use super::DbPool;
+
+impl DbPool {
+    // 签发一道注册用的工作量证明题目，顺便清理已过期的题目
+    pub fn issue_registration_challenge(&self, ttl_secs: i64, now: i64) -> Result<(String, i64)> {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let challenge = hex::encode(bytes);
+        let expires_at = now + ttl_secs;
+        let conn = self.0.lock().unwrap();
+        conn.execute("DELETE FROM registration_challenges WHERE expires_at <= ?", [now])?;
+        conn.execute(
+            "INSERT INTO registration_challenges (challenge, expires_at) VALUES (?1, ?2)",
+            params![challenge, expires_at],
+        )?;
+        Ok((challenge, expires_at))
+    }
+
+    // 核销题目：存在且未过期时删除并返回 true，每道题只能用一次
+    pub fn consume_registration_challenge(&self, challenge: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM registration_challenges WHERE challenge = ?1 AND expires_at > ?2",
+            params![challenge, now],
+        )? > 0)
+    }
+}
@@ -0,0 +1,49 @@
+use rusqlite::{Connection, Result};
+use sha2::{Digest, Sha256};
+
+use crate::config::settings::Settings;
+
+// 派生数据库密钥时使用的域分隔前缀，避免与主密钥的其他用途冲突
+const DATABASE_KEY_CONTEXT: &[u8] = b"yueling-sqlcipher-v1:";
+
+// 由主密钥派生 SQLCipher 原始密钥（32 字节，十六进制）
+pub fn database_key(master_key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(DATABASE_KEY_CONTEXT);
+    hasher.update(master_key.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+// 根据配置决定是否加密数据库，返回派生后的密钥
+pub fn resolve_key(settings: &Settings) -> std::result::Result<Option<String>, String> {
+    if !settings.database.encrypt {
+        return Ok(None);
+    }
+    if !cfg!(feature = "sqlcipher") {
+        // 普通 SQLite 会忽略 PRAGMA key，继续运行会让数据以明文落盘
+        return Err("database.encrypt 已开启，但服务器编译时未启用 sqlcipher 特性".into());
+    }
+    if settings.security.master_key.is_empty() {
+        return Err("database.encrypt 已开启，但未配置主密钥（security.master_key 或 YUELING_MASTER_KEY）".into());
+    }
+    Ok(Some(database_key(&settings.security.master_key)))
+}
+
+// 在连接上设置密钥并验证能否读取数据库（必须在任何其他语句之前执行）
+pub fn apply_key(conn: &Connection, key: &str) -> Result<()> {
+    conn.execute_batch(&format!("PRAGMA key = \"x'{}'\";", key))?;
+    conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(err, _) if err.code == rusqlite::ErrorCode::NotADatabase => {
+                rusqlite::Error::SqliteFailure(err, Some("数据库密钥错误或文件未加密".to_string()))
+            }
+            other => other,
+        })?;
+    Ok(())
+}
+
+// 用新密钥重新加密整个数据库（连接须已用旧密钥解开）
+// 旧备份仍使用旧密钥加密，轮换后需要保留旧主密钥才能恢复
+pub fn rekey(conn: &Connection, new_key: &str) -> Result<()> {
+    conn.execute_batch(&format!("PRAGMA rekey = \"x'{}'\";", new_key))
+}
@@ -0,0 +1,82 @@
+use rusqlite::{params, Result};
+use serde::Serialize;
+
+use super::{queries, DbPool, Message};
+
+// 消息墓碑：客户端据此删除本地副本
+#[derive(Debug, Serialize)]
+pub struct Tombstone {
+    pub message_id: String,
+    pub deleted_at: i64,
+}
+
+impl DbPool {
+    // 软删除消息（仅发送者可删除），返回被删除的消息用于通知会话双方
+    pub fn delete_message(&self, message_id: &str, requester_id: &str, now: i64) -> Result<Message> {
+        self.with_tx(|conn| {
+            let message = conn.query_row(
+                queries::ACTIVE_MESSAGE_OF_SENDER,
+                params![message_id, requester_id],
+                |row| self.message_from_row(row),
+            )?;
+            conn.execute(
+                "UPDATE messages SET deleted_at = ?1 WHERE id = ?2",
+                params![now, message_id],
+            )?;
+            Ok(message)
+        })
+    }
+
+    // 在撤销窗口内恢复已删除的消息
+    pub fn restore_message(&self, message_id: &str, requester_id: &str, now: i64, grace_secs: i64) -> Result<Message> {
+        self.with_tx(|conn| {
+            let message = conn.query_row(
+                queries::DELETED_MESSAGE_OF_SENDER,
+                params![message_id, requester_id, now - grace_secs],
+                |row| self.message_from_row(row),
+            )?;
+            conn.execute(
+                "UPDATE messages SET deleted_at = NULL WHERE id = ?",
+                [message_id],
+            )?;
+            Ok(message)
+        })
+    }
+
+    // 软删除用户，返回是否存在未删除的该用户
+    pub fn delete_user(&self, user_id: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE users SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+            params![now, user_id],
+        )?;
+        self.1.invalidate_user(user_id);
+        Ok(updated > 0)
+    }
+
+    // 在撤销窗口内恢复已删除的用户
+    pub fn restore_user(&self, user_id: &str, now: i64, grace_secs: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE users SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL AND deleted_at >= ?2",
+            params![user_id, now - grace_secs],
+        )?;
+        self.1.invalidate_user(user_id);
+        Ok(updated > 0)
+    }
+
+    // 获取 since 之后产生的与用户相关的消息墓碑
+    pub fn get_tombstones(&self, user_id: &str, since: i64) -> Result<Vec<Tombstone>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(queries::SYNC_TOMBSTONES)?;
+        let tombstones = stmt.query_map(params![user_id, since], |row| {
+            Ok(Tombstone {
+                message_id: row.get(0)?,
+                deleted_at: row.get(1)?,
+            })
+        })?
+        .filter_map(Result::ok)
+        .collect();
+        Ok(tombstones)
+    }
+}
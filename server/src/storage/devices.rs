@@ -0,0 +1,175 @@
+use rand::Rng;
+use rusqlite::{params, OptionalExtension, Result, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::DbPool;
+
+// 系统通知的发送者（第一次发送系统通知时创建，没有密码，不能登录）
+pub const SYSTEM_USER_ID: &str = "system";
+
+// 用户登录过的一台设备
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub platform: String,
+    pub push_token: Option<String>,
+    pub trusted: bool,
+    pub first_seen_at: i64,
+    pub last_seen_at: i64,
+}
+
+const DEVICE_COLUMNS: &str = "id, user_id, name, platform, push_token, trusted, first_seen_at, last_seen_at";
+
+fn device_from_row(row: &Row) -> Result<Device> {
+    Ok(Device {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        name: row.get(2)?,
+        platform: row.get(3)?,
+        push_token: row.get(4)?,
+        trusted: row.get(5)?,
+        first_seen_at: row.get(6)?,
+        last_seen_at: row.get(7)?,
+    })
+}
+
+fn hash_code(device_id: &str, code: &str) -> String {
+    hex::encode(Sha256::digest(format!("{}:{}", device_id, code).as_bytes()))
+}
+
+impl DbPool {
+    // 确保系统通知账号存在
+    pub fn ensure_system_user(&self, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO users (id, username, username_normalized, email, password_hash, created_at)
+             VALUES (?1, ?1, ?1, ?2, '', ?3)",
+            params![SYSTEM_USER_ID, format!("{}@local", SYSTEM_USER_ID), now],
+        )?;
+        Ok(())
+    }
+
+    // 查找用户未移除的设备
+    pub fn find_device(&self, user_id: &str, device_id: &str) -> Result<Option<Device>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM devices WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL", DEVICE_COLUMNS),
+            params![device_id, user_id],
+            device_from_row,
+        ).optional()
+    }
+
+    // 登记新设备
+    pub fn create_device(&self, user_id: &str, name: &str, platform: &str, push_token: Option<&str>, trusted: bool, now: i64) -> Result<Device> {
+        let device = Device {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            name: name.to_string(),
+            platform: platform.to_string(),
+            push_token: push_token.map(str::to_string),
+            trusted,
+            first_seen_at: now,
+            last_seen_at: now,
+        };
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO devices (id, user_id, name, platform, push_token, trusted, first_seen_at, last_seen_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)",
+            params![device.id, user_id, name, platform, push_token, trusted, now],
+        )?;
+        Ok(device)
+    }
+
+    // 设备再次登录：更新最后登录时间、推送令牌，trusted 为 true 时标记为可信
+    pub fn touch_device(&self, device_id: &str, push_token: Option<&str>, trusted: bool, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "UPDATE devices SET last_seen_at = ?2, push_token = COALESCE(?3, push_token), trusted = trusted OR ?4 WHERE id = ?1",
+            params![device_id, now, push_token, trusted],
+        )?;
+        Ok(())
+    }
+
+    // 用户未移除的全部设备，最近登录的在前
+    pub fn list_devices(&self, user_id: &str) -> Result<Vec<Device>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM devices WHERE user_id = ? AND revoked_at IS NULL ORDER BY last_seen_at DESC, first_seen_at DESC",
+            DEVICE_COLUMNS
+        ))?;
+        stmt.query_map([user_id], device_from_row)?.collect()
+    }
+
+    // 除指定设备外，用户是否还有其他可信设备（用于判断是否需要发送新设备提醒）
+    pub fn has_other_trusted_devices(&self, user_id: &str, device_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM devices WHERE user_id = ?1 AND id != ?2 AND trusted = 1 AND revoked_at IS NULL)",
+            params![user_id, device_id],
+            |row| row.get(0),
+        )
+    }
+
+    // 重命名设备，返回设备是否存在
+    pub fn rename_device(&self, user_id: &str, device_id: &str, name: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute(
+            "UPDATE devices SET name = ?3 WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL",
+            params![device_id, user_id, name],
+        )? > 0)
+    }
+
+    // 移除设备：注销该设备上的会话并删除其推送令牌，返回设备是否存在
+    pub fn revoke_device(&self, user_id: &str, device_id: &str, now: i64) -> Result<bool> {
+        self.with_tx(|conn| {
+            let push_token: Option<Option<String>> = conn.query_row(
+                "UPDATE devices SET revoked_at = ?3 WHERE id = ?1 AND user_id = ?2 AND revoked_at IS NULL RETURNING push_token",
+                params![device_id, user_id, now],
+                |row| row.get(0),
+            ).optional()?;
+            let Some(push_token) = push_token else {
+                return Ok(false);
+            };
+            conn.execute("DELETE FROM sessions WHERE device_id = ?", [device_id])?;
+            conn.execute("DELETE FROM device_codes WHERE device_id = ?", [device_id])?;
+            if let Some(token) = push_token {
+                conn.execute("DELETE FROM push_tokens WHERE token = ?1 AND user_id = ?2", params![token, user_id])?;
+            }
+            Ok(true)
+        })
+    }
+
+    // 为待验证的设备签发 6 位验证码（替换之前的验证码）
+    pub fn issue_device_code(&self, device_id: &str, ttl_secs: i64, now: i64) -> Result<String> {
+        let code = format!("{:06}", rand::thread_rng().gen_range(0..1_000_000));
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO device_codes (device_id, code_hash, attempts, expires_at) VALUES (?1, ?2, 0, ?3)",
+            params![device_id, hash_code(device_id, &code), now + ttl_secs],
+        )?;
+        Ok(code)
+    }
+
+    // 校验设备验证码：正确时删除验证码并返回 true；错误次数达到 max_attempts 后验证码作废
+    pub fn check_device_code(&self, device_id: &str, code: &str, max_attempts: i64, now: i64) -> Result<bool> {
+        self.with_tx(|conn| {
+            let stored: Option<(String, i64)> = conn.query_row(
+                "SELECT code_hash, attempts FROM device_codes WHERE device_id = ?1 AND expires_at > ?2",
+                params![device_id, now],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).optional()?;
+            let Some((code_hash, attempts)) = stored else {
+                return Ok(false);
+            };
+            if attempts < max_attempts && code_hash == hash_code(device_id, code.trim()) {
+                conn.execute("DELETE FROM device_codes WHERE device_id = ?", [device_id])?;
+                return Ok(true);
+            }
+            conn.execute("UPDATE device_codes SET attempts = attempts + 1 WHERE device_id = ?", [device_id])?;
+            Ok(false)
+        })
+    }
+}
@@ -0,0 +1,62 @@
+use rusqlite::{params, Result};
+
+use super::{DbPool, Message};
+
+// 应收到未读消息摘要的用户
+#[derive(Debug, Clone)]
+pub struct DigestRecipient {
+    pub user_id: String,
+    pub username: String,
+    pub email: String,
+    pub last_digest_at: i64, // 只摘要这之后的新消息，避免重复发送
+}
+
+impl DbPool {
+    // 查询离线时间早于 cutoff、有新的未读私聊消息且未关闭摘要的用户
+    // 注册时生成的 <id>@local 占位邮箱和机器人账号不会收到邮件
+    pub fn digest_recipients(&self, cutoff: i64) -> Result<Vec<DigestRecipient>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT u.id, u.username, u.email, COALESCE(u.last_digest_at, 0)
+             FROM users u
+             WHERE u.deleted_at IS NULL
+               AND u.email NOT LIKE '%@local'
+               AND u.id NOT IN (SELECT id FROM bots)
+               AND COALESCE(u.last_seen_at, u.created_at) <= ?1
+               AND NOT EXISTS (
+                   SELECT 1 FROM user_settings s
+                   WHERE s.user_id = u.id AND s.key = ?2 AND s.value = 'off')
+               AND EXISTS (
+                   SELECT 1 FROM messages m
+                   WHERE m.receiver_id = u.id AND m.is_read = 0 AND m.message_type = 'private'
+                     AND m.deleted_at IS NULL AND m.created_at > COALESCE(u.last_digest_at, 0))",
+        )?;
+        stmt.query_map(params![cutoff, super::user_settings::SETTING_EMAIL_DIGEST], |row| {
+            Ok(DigestRecipient {
+                user_id: row.get(0)?,
+                username: row.get(1)?,
+                email: self.2.open_email(row.get(2)?, 2)?,
+                last_digest_at: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 上次摘要之后收到的未读私聊消息（所有工作区，按时间正序）
+    pub fn unread_messages_since(&self, user_id: &str, since: i64) -> Result<Vec<Message>> {
+        let mut messages = Vec::new();
+        for workspace in self.list_user_workspaces(user_id)? {
+            messages.extend(self.get_unread_messages(&workspace.id, user_id)?);
+        }
+        messages.retain(|m| m.created_at > since);
+        messages.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+        Ok(messages)
+    }
+
+    // 记录摘要已发送
+    pub fn mark_digest_sent(&self, user_id: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute("UPDATE users SET last_digest_at = ? WHERE id = ?", params![now, user_id])?;
+        Ok(())
+    }
+}
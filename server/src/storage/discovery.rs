@@ -0,0 +1,70 @@
+//! 通讯录匹配：按用户名或邮箱哈希查找已注册的本地用户
+//!
+//! 邮箱哈希是去掉首尾空白并转为小写后的邮箱的 SHA-256（十六进制小写）。服务器不保存这个哈希——
+//! 未加盐的邮箱哈希与明文邮箱等价，保存它会让邮箱加密失去意义——而是在查找时逐个计算
+
+use rusqlite::{params, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+
+use super::email_verification::is_placeholder_email;
+use super::usernames::normalize_username;
+use super::DbPool;
+
+/// 通讯录中的一项匹配到的用户，handle 为请求中的原始写法
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredUser {
+    pub handle: String,
+    pub user_id: String,
+    pub username: String,
+}
+
+/// 客户端上传的邮箱哈希
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+impl DbPool {
+    // 按用户名（归一化后比较）和邮箱哈希查找本地用户，不包括机器人、远程用户和已注销的用户；
+    // 匹配结果按请求中的顺序排列，同一个用户被多个写法匹配到时各自返回
+    pub fn discover_users(&self, usernames: &[String], email_hashes: &[String]) -> Result<Vec<DiscoveredUser>> {
+        let conn = self.0.lock().unwrap();
+        let mut found = Vec::new();
+        for handle in usernames {
+            let user: Option<(String, String)> = conn.query_row(
+                "SELECT id, username FROM users WHERE username_normalized = ?1 AND deleted_at IS NULL",
+                params![normalize_username(handle)],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).optional()?;
+            if let Some((user_id, username)) = user {
+                found.push(DiscoveredUser { handle: handle.clone(), user_id, username });
+            }
+        }
+
+        if email_hashes.is_empty() {
+            return Ok(found);
+        }
+        let wanted: HashSet<String> = email_hashes.iter().map(|hash| hash.to_ascii_lowercase()).collect();
+        let mut by_hash: HashMap<String, (String, String)> = HashMap::new();
+        let mut stmt = conn.prepare(
+            "SELECT id, username, email FROM users WHERE username_normalized IS NOT NULL AND deleted_at IS NULL",
+        )?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let email = self.2.open_email(row.get(2)?, 2)?;
+            if is_placeholder_email(&email) {
+                continue;
+            }
+            let hash = email_hash(&email);
+            if wanted.contains(&hash) {
+                by_hash.insert(hash, (row.get(0)?, row.get(1)?));
+            }
+        }
+        for handle in email_hashes {
+            if let Some((user_id, username)) = by_hash.get(&handle.to_ascii_lowercase()) {
+                found.push(DiscoveredUser { handle: handle.clone(), user_id: user_id.clone(), username: username.clone() });
+            }
+        }
+        Ok(found)
+    }
+}
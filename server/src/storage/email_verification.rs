@@ -0,0 +1,79 @@
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+
+use super::DbPool;
+
+// 邮箱验证令牌前缀
+const EMAIL_TOKEN_PREFIX: &str = "yle_";
+
+// 未填写邮箱的用户使用 <用户ID>@local 占位（邮箱列唯一且非空）
+pub fn is_placeholder_email(email: &str) -> bool {
+    email.ends_with("@local")
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+impl DbPool {
+    // 为用户当前的邮箱签发验证令牌（替换之前未使用的令牌），返回 (邮箱, 令牌)
+    //
+    // 用户不存在、邮箱为占位邮箱或已经验证过时返回 None
+    pub fn issue_email_verification(&self, user_id: &str, ttl_secs: i64, now: i64) -> Result<Option<(String, String)>> {
+        let conn = self.0.lock().unwrap();
+        let email: Option<String> = conn.query_row(
+            "SELECT email FROM users WHERE id = ? AND deleted_at IS NULL AND email_verified_at IS NULL",
+            [user_id],
+            |row| row.get(0),
+        ).optional()?;
+        // 验证记录中保存与 users 表相同的（可能已加密的）邮箱，核销时直接比较
+        let Some(stored) = email.filter(|email| !is_placeholder_email(email)) else {
+            return Ok(None);
+        };
+        let email = self.2.open_email(stored.clone(), 0)?;
+
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = format!("{}{}", EMAIL_TOKEN_PREFIX, hex::encode(bytes));
+        conn.execute(
+            "INSERT OR REPLACE INTO email_verifications (user_id, token_hash, email, expires_at) VALUES (?1, ?2, ?3, ?4)",
+            params![user_id, hash_token(&token), stored, now + ttl_secs],
+        )?;
+        Ok(Some((email, token)))
+    }
+
+    // 核销验证令牌并标记邮箱已验证，返回用户ID
+    //
+    // 令牌无效、已过期，或签发后用户又修改了邮箱时返回 None
+    pub fn verify_email(&self, token: &str, now: i64) -> Result<Option<String>> {
+        self.with_tx(|conn| {
+            let pending: Option<(String, String)> = conn.query_row(
+                "DELETE FROM email_verifications WHERE token_hash = ?1 AND expires_at > ?2 RETURNING user_id, email",
+                params![hash_token(token), now],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).optional()?;
+            let Some((user_id, email)) = pending else {
+                return Ok(None);
+            };
+            let updated = conn.execute(
+                "UPDATE users SET email_verified_at = ?3 WHERE id = ?1 AND email = ?2 AND deleted_at IS NULL",
+                params![user_id, email, now],
+            )?;
+            Ok((updated > 0).then_some(user_id))
+        })
+    }
+
+    // 发消息前需要先验证邮箱的用户：本地注册且未验证（机器人和联邦影子账号不受限制）
+    pub fn needs_email_verification(&self, user_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.query_row(
+            "SELECT u.email_verified_at IS NULL
+                    AND NOT EXISTS (SELECT 1 FROM bots b WHERE b.id = u.id)
+                    AND NOT EXISTS (SELECT 1 FROM remote_users r WHERE r.user_id = u.id)
+             FROM users u WHERE u.id = ?",
+            [user_id],
+            |row| row.get(0),
+        ).optional()?.unwrap_or(false))
+    }
+}
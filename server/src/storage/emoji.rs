@@ -0,0 +1,78 @@
+use rusqlite::{params, OptionalExtension, Result, Row};
+use serde::Serialize;
+
+use super::DbPool;
+
+// 工作区的自定义表情，也用作贴纸
+#[derive(Debug, Clone, Serialize)]
+pub struct CustomEmoji {
+    pub name: String,          // 消息中以 :name: 引用
+    pub attachment_id: String, // 图片附件，按 GET /attachments/{id} 下载
+    pub content_type: String,
+    pub created_at: i64,
+}
+
+const EMOJI_COLUMNS: &str = "e.name, e.attachment_id, a.content_type, e.created_at";
+
+impl CustomEmoji {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            name: row.get(0)?,
+            attachment_id: row.get(1)?,
+            content_type: row.get(2)?,
+            created_at: row.get(3)?,
+        })
+    }
+}
+
+impl DbPool {
+    // 登记自定义表情，同名的表情改用新图片；返回是否为新增
+    pub fn put_custom_emoji(&self, workspace_id: &str, name: &str, attachment_id: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let existed = conn.query_row(
+            "SELECT 1 FROM custom_emoji WHERE workspace_id = ?1 AND name = ?2",
+            params![workspace_id, name],
+            |_| Ok(()),
+        ).optional()?.is_some();
+        conn.execute(
+            "INSERT INTO custom_emoji (workspace_id, name, attachment_id, created_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(workspace_id, name) DO UPDATE SET attachment_id = excluded.attachment_id, created_at = excluded.created_at",
+            params![workspace_id, name, attachment_id, now],
+        )?;
+        Ok(!existed)
+    }
+
+    // 删除自定义表情，不存在时返回 false；图片附件保留，已发送的贴纸仍能下载
+    pub fn delete_custom_emoji(&self, workspace_id: &str, name: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let deleted = conn.execute(
+            "DELETE FROM custom_emoji WHERE workspace_id = ?1 AND name = ?2",
+            params![workspace_id, name],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    // 工作区的全部自定义表情，按名称排序
+    pub fn custom_emoji(&self, workspace_id: &str) -> Result<Vec<CustomEmoji>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM custom_emoji e JOIN attachments a ON a.id = e.attachment_id
+             WHERE e.workspace_id = ? ORDER BY e.name",
+            EMOJI_COLUMNS,
+        ))?;
+        stmt.query_map([workspace_id], CustomEmoji::from_row)?.collect()
+    }
+
+    pub fn custom_emoji_by_name(&self, workspace_id: &str, name: &str) -> Result<Option<CustomEmoji>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM custom_emoji e JOIN attachments a ON a.id = e.attachment_id
+                 WHERE e.workspace_id = ?1 AND e.name = ?2",
+                EMOJI_COLUMNS,
+            ),
+            params![workspace_id, name],
+            CustomEmoji::from_row,
+        ).optional()
+    }
+}
@@ -0,0 +1,228 @@
+use moka::sync::Cache;
+use rusqlite::{params, types::{ToSqlOutput, Type, Value, ValueRef}, Connection, OptionalExtension, Result, Row};
+use std::sync::Arc;
+
+use super::{DbPool, Message};
+use super::email_verification::is_placeholder_email;
+use crate::config::settings::SecuritySettings;
+use crate::core::markdown::TextEntity;
+use crate::crypto::{self, conversation::{conversation_id, encode_sealed, sealed_bytes_epoch, sealed_epoch, ConversationKeys}, CryptoError, CryptoService};
+
+// 确定性加密邮箱时使用的字段名
+const EMAIL_FIELD: &str = "email";
+
+// 落库前加密用的密钥，未开启对应功能时为 None
+#[derive(Clone, Default)]
+pub struct StorageKeys {
+    pub(crate) messages: Option<Arc<ConversationKeys>>, // 消息内容，按会话派生
+    pub(crate) fields: Option<CryptoService>,           // 需要等值查询的字段（邮箱），确定性加密
+    pub(crate) plaintexts: Option<Cache<(String, u32), String>>, // 已解密的消息内容，键为 (消息ID, 纪元)
+}
+
+// 重新加密单条消息的结果，消息内容的新密文为二进制容器，格式区间为文本
+pub(crate) enum Reseal<T = String> {
+    Current,           // 已是会话当前纪元的密文
+    Rewritten(T),      // 新的密文
+    Failed,            // 旧密文无法解密（密钥不匹配或数据损坏），保持原样
+}
+
+fn decrypt_failed(column: usize, e: CryptoError) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(e))
+}
+
+impl StorageKeys {
+    // 写入前加密邮箱；占位邮箱不是个人信息，保持明文以便按后缀识别
+    pub(crate) fn seal_email(&self, email: &str) -> String {
+        match &self.fields {
+            Some(crypto) if !email.is_empty() && !is_placeholder_email(email) => crypto.seal_field(EMAIL_FIELD, email),
+            _ => email.to_string(),
+        }
+    }
+
+    // 读取邮箱列（column 为其在行中的位置，用于报告错误）
+    pub(crate) fn open_email(&self, stored: String, column: usize) -> Result<String> {
+        match &self.fields {
+            Some(crypto) => crypto.open_field(EMAIL_FIELD, &stored).map_err(|e| decrypt_failed(column, e)),
+            None => Ok(stored),
+        }
+    }
+}
+
+// 会话当前的密钥纪元：第一次加密时从 0 开始，纪元开始超过 rotation_secs 后前进一步，
+// 返回 (纪元, 是否刚刚前进)
+fn current_epoch(conn: &Connection, conversation_id: &str, rotation_secs: i64, now: i64) -> Result<(u32, bool)> {
+    let current: Option<(u32, i64)> = conn.query_row(
+        "SELECT epoch, started_at FROM conversation_keys WHERE conversation_id = ?",
+        [conversation_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+    match current {
+        None => {
+            conn.execute(
+                "INSERT INTO conversation_keys (conversation_id, epoch, started_at) VALUES (?1, 0, ?2)",
+                params![conversation_id, now],
+            )?;
+            Ok((0, false))
+        }
+        Some((epoch, started_at)) if rotation_secs > 0 && now - started_at >= rotation_secs => {
+            conn.execute(
+                "UPDATE conversation_keys SET epoch = ?2, started_at = ?3 WHERE conversation_id = ?1",
+                params![conversation_id, epoch + 1, now],
+            )?;
+            Ok((epoch + 1, true))
+        }
+        Some((epoch, _)) => Ok((epoch, false)),
+    }
+}
+
+// 格式区间加密时的附加数据，与消息内容区分开
+pub(crate) fn entities_aad(message_id: &str) -> String {
+    format!("{}/entities", message_id)
+}
+
+fn parse_entities(json: &str) -> Vec<TextEntity> {
+    serde_json::from_str(json).unwrap_or_default()
+}
+
+impl DbPool {
+    // 按 [security] 配置开启消息加密和邮箱加密；开启了但缺少主密钥时报错
+    pub fn with_encryption(mut self, settings: &SecuritySettings) -> std::result::Result<Self, String> {
+        if let Some(keys) = ConversationKeys::from_settings(settings)? {
+            self = self.with_message_keys(keys, settings.message_cache_bytes);
+        }
+        self.2.fields = crypto::field_crypto(settings)?;
+        Ok(self)
+    }
+
+    // 是否开启了消息或邮箱加密
+    pub fn encryption_enabled(&self) -> bool {
+        self.2.messages.is_some() || self.2.fields.is_some()
+    }
+
+    // 开启消息加密：之后写入的消息内容按会话密钥加密，读取时自动解密；
+    // 解密结果按内容字节数计入缓存，cache_bytes 为 0 时不缓存
+    pub fn with_message_keys(mut self, keys: ConversationKeys, cache_bytes: u64) -> Self {
+        self.2.messages = Some(Arc::new(keys));
+        self.2.plaintexts = (cache_bytes > 0).then(|| {
+            Cache::builder()
+                .max_capacity(cache_bytes)
+                .weigher(|(id, _): &(String, u32), content: &String| (id.len() + content.len()).try_into().unwrap_or(u32::MAX))
+                .build()
+        });
+        self
+    }
+
+    // 写入前加密消息内容（未开启加密时原样返回），conn 须是持有的连接或事务；
+    // conversation 由 crypto::conversation::conversation_id 得出
+    pub(crate) fn seal_content(&self, conn: &Connection, conversation: &str, message_id: &str, content: &str, now: i64) -> Result<String> {
+        let Some(keys) = &self.2.messages else {
+            return Ok(content.to_string());
+        };
+        let (epoch, rotated) = current_epoch(conn, conversation, keys.rotation_secs(), now)?;
+        // 有会话换了新纪元时清空解密缓存，旧纪元的明文不在内存里多留
+        if rotated && let Some(plaintexts) = &self.2.plaintexts {
+            plaintexts.invalidate_all();
+        }
+        Ok(keys.seal(conversation, epoch, message_id, content))
+    }
+
+    // 写入前加密消息内容：开启加密时为二进制容器（以 BLOB 保存），否则直接绑定明文，不复制
+    pub(crate) fn seal_message<'a>(&self, conn: &Connection, conversation: &str, message_id: &str, content: &'a str, now: i64) -> Result<ToSqlOutput<'a>> {
+        let Some(keys) = &self.2.messages else {
+            return Ok(ToSqlOutput::Borrowed(ValueRef::Text(content.as_bytes())));
+        };
+        let (epoch, rotated) = current_epoch(conn, conversation, keys.rotation_secs(), now)?;
+        if rotated && let Some(plaintexts) = &self.2.plaintexts {
+            plaintexts.invalidate_all();
+        }
+        Ok(ToSqlOutput::Owned(Value::Blob(keys.seal_bytes(conversation, epoch, message_id, content.as_bytes()))))
+    }
+
+    // 把已保存的消息内容（明文、旧版的 base64 密文或旧纪元的二进制密文）改用会话当前纪元的密钥加密
+    pub(crate) fn reseal_message(&self, conn: &Connection, conversation: &str, message_id: &str, stored: ValueRef, now: i64) -> Result<Reseal<Vec<u8>>> {
+        let Some(keys) = &self.2.messages else {
+            return Ok(Reseal::Current);
+        };
+        let (epoch, _) = current_epoch(conn, conversation, keys.rotation_secs(), now)?;
+        let plaintext = match stored {
+            ValueRef::Blob(sealed) if sealed_bytes_epoch(sealed) == Some(epoch) => return Ok(Reseal::Current),
+            ValueRef::Blob(sealed) => keys.open_bytes(conversation, message_id, sealed),
+            ValueRef::Text(text) => keys.open(conversation, message_id, &String::from_utf8_lossy(text)),
+            _ => return Ok(Reseal::Failed),
+        };
+        let Ok(plaintext) = plaintext else {
+            return Ok(Reseal::Failed);
+        };
+        Ok(Reseal::Rewritten(keys.seal_bytes(conversation, epoch, message_id, plaintext.as_bytes())))
+    }
+
+    // 把已保存的格式区间（明文或旧纪元的密文）改用会话当前纪元的密钥加密，未开启消息加密时不做处理
+    pub(crate) fn reseal_content(&self, conn: &Connection, conversation: &str, message_id: &str, stored: &str, now: i64) -> Result<Reseal> {
+        let Some(keys) = &self.2.messages else {
+            return Ok(Reseal::Current);
+        };
+        let (epoch, _) = current_epoch(conn, conversation, keys.rotation_secs(), now)?;
+        if sealed_epoch(stored) == Some(epoch) {
+            return Ok(Reseal::Current);
+        }
+        let Ok(plaintext) = keys.open(conversation, message_id, stored) else {
+            return Ok(Reseal::Failed);
+        };
+        Ok(Reseal::Rewritten(keys.seal(conversation, epoch, message_id, &plaintext)))
+    }
+
+    // 按 queries::message_columns 顺序构造消息并解密内容，先查解密缓存
+    //
+    // 消息内容写入后不会修改，(消息ID, 纪元) 相同的密文解密结果总是相同；
+    // 明文消息（开启加密前保存的）没有纪元，不进缓存。密文直接从 SQLite 的 BLOB 借用，解密前不复制
+    pub(crate) fn message_from_row(&self, row: &Row) -> Result<Message> {
+        let mut message = Message::from_row(row)?;
+        let entities: Option<String> = row.get(12)?;
+        let Some(keys) = &self.2.messages else {
+            message.entities = entities.map(|json| parse_entities(&json)).unwrap_or_default();
+            // 关闭加密后读到的密文按旧版的文本格式原样返回
+            if let ValueRef::Blob(sealed) = row.get_ref(3)? {
+                message.content = encode_sealed(sealed);
+            }
+            return Ok(message);
+        };
+        // 格式区间解不开时按纯文本显示，不影响读取消息
+        if let Some(stored) = entities {
+            let conversation = conversation_id(&message.message_type, &message.sender_id, &message.receiver_id);
+            message.entities = keys.open(&conversation, &entities_aad(&message.id), &stored)
+                .map(|json| parse_entities(&json))
+                .unwrap_or_default();
+        }
+        let stored = row.get_ref(3)?;
+        let epoch = match stored {
+            ValueRef::Blob(sealed) => sealed_bytes_epoch(sealed),
+            _ => sealed_epoch(&message.content),
+        };
+        let cache_key = self.2.plaintexts.as_ref()
+            .and_then(|cache| Some((cache, (message.id.clone(), epoch?))));
+        if let Some(content) = cache_key.as_ref().and_then(|(cache, key)| cache.get(key)) {
+            message.content = content;
+            return Ok(message);
+        }
+        let conversation = conversation_id(&message.message_type, &message.sender_id, &message.receiver_id);
+        let opened = match stored {
+            ValueRef::Blob(sealed) => keys.open_bytes(&conversation, &message.id, sealed),
+            _ => keys.open(&conversation, &message.id, &message.content),
+        };
+        message.content = opened.map_err(|e| decrypt_failed(3, e))?;
+        if let Some((cache, key)) = cache_key {
+            cache.insert(key, message.content.clone());
+        }
+        Ok(message)
+    }
+
+    // 会话当前的密钥纪元，未加密过消息时返回 None
+    pub fn conversation_epoch(&self, message_type: &str, sender_id: &str, receiver_id: &str) -> Result<Option<u32>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT epoch FROM conversation_keys WHERE conversation_id = ?",
+            [conversation_id(message_type, sender_id, receiver_id)],
+            |row| row.get(0),
+        ).optional()
+    }
+}
@@ -0,0 +1,108 @@
+use rusqlite::Result;
+use serde::Serialize;
+
+use super::attachments::MessageAttachment;
+use super::{queries, DbPool, Message};
+use crate::core::datetime::unix_now;
+
+// 导出的用户资料（不含密码哈希）
+#[derive(Debug, Serialize)]
+pub struct ExportedUser {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub created_at: i64,
+    pub avatar_url: String,
+}
+
+// 单个用户的数据导出
+#[derive(Debug, Serialize)]
+pub struct UserExport {
+    pub user: ExportedUser,
+    pub friends: Vec<ExportedUser>,
+    pub messages: Vec<Message>,
+    pub exported_at: i64,
+}
+
+// 一个会话的导出：未删除的消息按序号升序，附件为这些消息引用的附件
+#[derive(Debug, Serialize)]
+pub struct ConversationExport {
+    pub peer_id: String,
+    pub conversation_type: String, // "private"或"group"
+    pub messages: Vec<Message>,
+    pub attachments: Vec<MessageAttachment>,
+    pub exported_at: i64,
+}
+
+// 导出会话时每次读取的消息数
+const EXPORT_BATCH: i64 = 500;
+
+impl From<super::User> for ExportedUser {
+    fn from(user: super::User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            created_at: user.created_at,
+            avatar_url: user.avatar_url,
+        }
+    }
+}
+
+impl DbPool {
+    // 导出用户资料、好友列表和全部未删除的消息
+    pub fn export_user(&self, user_id: &str) -> Result<UserExport> {
+        let user = self.get_user_by_id(user_id)?;
+        let friends = self.get_friends(user_id)?;
+
+        let messages = {
+            let conn = self.0.lock().unwrap();
+            let mut stmt = conn.prepare(queries::USER_MESSAGES)?;
+            stmt.query_map([user_id], |row| self.message_from_row(row))?
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let exported_at = unix_now();
+
+        Ok(UserExport {
+            user: user.into(),
+            friends: friends.into_iter().map(ExportedUser::from).collect(),
+            messages,
+            exported_at,
+        })
+    }
+
+    // 导出会话中全部未删除的消息及其引用的附件；每批单独加锁，不会长时间阻塞其他请求
+    pub fn export_conversation(
+        &self,
+        workspace_id: &str,
+        peer_id: &str,
+        conversation_type: &str,
+        conversation_id: &str,
+    ) -> Result<ConversationExport> {
+        let mut messages = Vec::new();
+        let mut from_seq = 1;
+        loop {
+            let range = self.messages_from_seq(workspace_id, conversation_id, from_seq, EXPORT_BATCH)?;
+            let returned = range.messages.len() + range.deleted_seqs.len();
+            let max_seq = range.messages.iter().filter_map(|m| m.seq).chain(range.deleted_seqs.iter().copied()).max();
+            messages.extend(range.messages);
+            match max_seq {
+                Some(seq) if returned as i64 == EXPORT_BATCH => from_seq = seq + 1,
+                _ => break,
+            }
+        }
+        let ids: Vec<String> = messages.iter().map(|m| m.id.clone()).collect();
+        let attachments = self.message_attachments(&ids)?;
+
+        let exported_at = unix_now();
+
+        Ok(ConversationExport {
+            peer_id: peer_id.to_string(),
+            conversation_type: conversation_type.to_string(),
+            messages,
+            attachments,
+            exported_at,
+        })
+    }
+}
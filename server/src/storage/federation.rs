@@ -0,0 +1,200 @@
+use bcrypt::{hash, DEFAULT_COST};
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{DbPool, Message};
+use crate::crypto::conversation::conversation_id;
+use crate::core::datetime::unix_now;
+
+// 白名单中的对端服务器
+#[derive(Debug, Clone, Serialize)]
+pub struct FederationPeer {
+    pub host: String,
+    pub base_url: String,   // 如 https://chat.example.org
+    pub public_key: String, // Ed25519 公钥（base64）
+    pub created_at: i64,
+}
+
+// 远端用户在本地的影子账号
+#[derive(Debug, Clone)]
+pub struct RemoteUser {
+    pub user_id: String,
+    pub host: String,
+    pub remote_username: String,
+}
+
+impl DbPool {
+    // 添加或更新对端服务器
+    pub fn add_federation_peer(&self, host: &str, base_url: &str, public_key: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO federation_peers (host, base_url, public_key, created_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(host) DO UPDATE SET base_url = excluded.base_url, public_key = excluded.public_key",
+            params![host, base_url, public_key, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn list_federation_peers(&self) -> Result<Vec<FederationPeer>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT host, base_url, public_key, created_at FROM federation_peers ORDER BY host")?;
+        stmt.query_map([], |row| {
+            Ok(FederationPeer {
+                host: row.get(0)?,
+                base_url: row.get(1)?,
+                public_key: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    pub fn get_federation_peer(&self, host: &str) -> Result<Option<FederationPeer>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT host, base_url, public_key, created_at FROM federation_peers WHERE host = ?",
+            [host],
+            |row| {
+                Ok(FederationPeer {
+                    host: row.get(0)?,
+                    base_url: row.get(1)?,
+                    public_key: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    // 移出白名单，返回是否存在（已有的影子账号和消息保留）
+    pub fn remove_federation_peer(&self, host: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let removed = conn.execute("DELETE FROM federation_peers WHERE host = ?", [host])?;
+        Ok(removed > 0)
+    }
+
+    // 查询用户是否为远端用户的影子账号
+    pub fn get_remote_user(&self, user_id: &str) -> Result<Option<RemoteUser>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT user_id, host, remote_username FROM remote_users WHERE user_id = ?",
+            [user_id],
+            |row| {
+                Ok(RemoteUser {
+                    user_id: row.get(0)?,
+                    host: row.get(1)?,
+                    remote_username: row.get(2)?,
+                })
+            },
+        )
+        .optional()
+    }
+
+    // 按用户名查找本地用户（不含影子账号和已删除用户）
+    pub fn local_user_id_by_username(&self, username: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT id FROM users
+             WHERE username = ? AND deleted_at IS NULL AND id NOT IN (SELECT user_id FROM remote_users)",
+            [username],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    // 查询远端用户的影子账号ID
+    pub fn remote_user_id(&self, remote_username: &str, host: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        find_remote_user(&conn, remote_username, host)
+    }
+
+    // 获取远端用户的影子账号ID，不存在时创建；查询和插入在同一事务中，同一地址只会有一个影子账号
+    pub fn resolve_remote_user(&self, remote_username: &str, host: &str, now: i64) -> Result<String> {
+        if let Some(user_id) = self.remote_user_id(remote_username, host)? {
+            return Ok(user_id);
+        }
+
+        // 影子账号不能登录：使用随机且不保存的密码（哈希较慢，在事务外计算）
+        let mut password = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut password);
+        let password_hash = hash(hex::encode(password), DEFAULT_COST)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let user_id = Uuid::new_v4().to_string();
+
+        self.with_tx(|conn| {
+            if let Some(existing) = find_remote_user(conn, remote_username, host)? {
+                return Ok(existing);
+            }
+            conn.execute(
+                "INSERT INTO users (id, username, email, password_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![user_id, format!("{}@{}", remote_username, host), format!("{}@local", user_id), password_hash, now],
+            )?;
+            conn.execute(
+                "INSERT INTO remote_users (user_id, host, remote_username) VALUES (?1, ?2, ?3)",
+                params![user_id, host, remote_username],
+            )?;
+            Ok(user_id.clone())
+        })
+    }
+
+    // 保存 origin 服务器转发来的私聊消息，消息ID按来源重新生成（见 federation::local_message_id），
+    // 对端无法借用本地消息的ID；重复投递时返回 None
+    pub fn store_federated_message(
+        &self,
+        origin: &str,
+        remote_message_id: &str,
+        sender_id: &str,
+        receiver_id: &str,
+        content: &str,
+        created_at: i64,
+    ) -> Result<Option<Message>> {
+        let message_id = &crate::federation::local_message_id(origin, remote_message_id);
+        let conn = self.0.lock().unwrap();
+        // 密钥纪元按本机时间前进，不受对端时钟影响
+        let now = unix_now();
+        let tx = conn.unchecked_transaction()?;
+        let conversation = conversation_id("private", sender_id, receiver_id);
+        let stored = self.seal_message(&tx, &conversation, message_id, content, now)?;
+        let inserted = tx.execute(
+            "INSERT OR IGNORE INTO messages (id, sender_id, receiver_id, content, message_type, created_at, status, is_read, sent_at, conversation_id)
+             VALUES (?1, ?2, ?3, ?4, 'private', ?5, 'sent', 0, ?6, ?7)",
+            params![message_id, sender_id, receiver_id, stored, created_at, now, conversation],
+        )?;
+        if inserted == 0 {
+            return Ok(None);
+        }
+        // 确认不是重复投递后才分配序号，避免留下缺口
+        let seq = super::sequence::next_seq(&tx, super::workspaces::DEFAULT_WORKSPACE, &conversation)?;
+        tx.execute("UPDATE messages SET seq = ?2 WHERE id = ?1", params![message_id, seq])?;
+        // 与本地私聊一样进入发件箱，接收者确认送达前由后台任务重发
+        super::outbox::enqueue(&tx, message_id, receiver_id, now)?;
+        tx.commit()?;
+        Ok(Some(Message {
+            id: message_id.to_string(),
+            sender_id: sender_id.to_string(),
+            receiver_id: receiver_id.to_string(),
+            content: content.to_string(),
+            message_type: "private".into(),
+            created_at,
+            status: "sent".into(),
+            is_read: false,
+            workspace_id: super::workspaces::DEFAULT_WORKSPACE.into(),
+            sent_at: now,
+            delivered_at: None,
+            read_at: None,
+            seq: Some(seq),
+            entities: Vec::new(),
+        }))
+    }
+}
+
+fn find_remote_user(conn: &Connection, remote_username: &str, host: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT user_id FROM remote_users WHERE host = ? AND remote_username = ?",
+        [host, remote_username],
+        |row| row.get(0),
+    )
+    .optional()
+}
@@ -0,0 +1,227 @@
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{contains_pattern, queries, DbPool};
+
+// 群目录中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupListing {
+    pub id: String,
+    pub name: String,
+    pub created_at: i64,
+    pub join_approval: bool,   // 加入是否需要群主批准
+    pub member_count: i64,
+}
+
+// 群的公开设置，只有未删除的群才有
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupDirectorySettings {
+    pub workspace_id: String,
+    pub public: bool,
+    pub join_approval: bool,
+}
+
+// 等待批准的加群申请
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupJoinRequest {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub requested_at: i64,
+}
+
+// 删除群聊确认令牌的前缀
+const DELETION_TOKEN_PREFIX: &str = "ylg_";
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+impl DbPool {
+    // 群名称，群不存在或已删除时为 None
+    pub fn group_name(&self, group_id: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT name FROM groups WHERE id = ?1 AND deleted_at IS NULL",
+            params![group_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    // 群主的用户ID，群不存在或已删除时为 None
+    pub fn group_owner(&self, group_id: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT creator_id FROM groups WHERE id = ?1 AND deleted_at IS NULL",
+            [group_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    // 群是否已被删除（不存在的群返回 false）
+    pub fn is_group_deleted(&self, group_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM groups WHERE id = ?1 AND deleted_at IS NOT NULL)",
+            [group_id],
+            |row| row.get(0),
+        )
+    }
+
+    // 把群主转让给群成员 new_owner：原群主降为普通成员；new_owner 不是群成员时返回 false
+    pub fn transfer_group_ownership(&self, group_id: &str, old_owner: &str, new_owner: &str) -> Result<bool> {
+        let transferred = self.with_tx(|conn| {
+            let promoted = conn.execute(
+                "UPDATE group_members SET role = 'owner' WHERE group_id = ?1 AND user_id = ?2",
+                params![group_id, new_owner],
+            )?;
+            if promoted == 0 {
+                return Ok(false);
+            }
+            conn.execute(
+                "UPDATE group_members SET role = 'member' WHERE group_id = ?1 AND user_id = ?2",
+                params![group_id, old_owner],
+            )?;
+            conn.execute("UPDATE groups SET creator_id = ?1 WHERE id = ?2", params![new_owner, group_id])?;
+            // 转让后之前签发的删除确认令牌作废
+            conn.execute("DELETE FROM group_deletion_tokens WHERE group_id = ?", [group_id])?;
+            Ok(true)
+        })?;
+        self.1.invalidate_group(group_id);
+        Ok(transferred)
+    }
+
+    // 为群主签发删除群聊的确认令牌（覆盖之前签发的），返回明文令牌，只返回这一次
+    pub fn create_group_deletion_token(&self, group_id: &str, user_id: &str, expires_at: i64) -> Result<String> {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = format!("{}{}", DELETION_TOKEN_PREFIX, hex::encode(bytes));
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO group_deletion_tokens (group_id, token_hash, user_id, expires_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(group_id) DO UPDATE SET token_hash = excluded.token_hash, user_id = excluded.user_id, expires_at = excluded.expires_at",
+            params![group_id, hash_token(&token), user_id, expires_at],
+        )?;
+        Ok(token)
+    }
+
+    // 校验并作废删除确认令牌，令牌必须由 user_id 签发且未过期
+    pub fn consume_group_deletion_token(&self, group_id: &str, user_id: &str, token: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM group_deletion_tokens WHERE group_id = ?1 AND token_hash = ?2 AND user_id = ?3 AND expires_at > ?4",
+            params![group_id, hash_token(token), user_id, now],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // 把群标记为已删除并移除全部成员；消息保留到保留策略的清理期限后与群一起清除
+    pub fn tombstone_group(&self, group_id: &str, now: i64) -> Result<bool> {
+        let deleted = self.with_tx(|conn| {
+            let deleted = conn.execute(
+                "UPDATE groups SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL",
+                params![now, group_id],
+            )?;
+            conn.execute("DELETE FROM group_members WHERE group_id = ?", [group_id])?;
+            conn.execute("DELETE FROM group_join_requests WHERE group_id = ?", [group_id])?;
+            Ok(deleted > 0)
+        })?;
+        self.1.invalidate_group(group_id);
+        Ok(deleted)
+    }
+
+    // 设置群是否出现在群目录中、加入是否需要批准；关闭审批时不删除已有申请，由群主逐个处理
+    pub fn set_group_visibility(&self, group_id: &str, public: bool, join_approval: bool) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE groups SET visibility = ?1, join_approval = ?2 WHERE id = ?3 AND deleted_at IS NULL",
+            params![if public { "public" } else { "private" }, join_approval, group_id],
+        )?;
+        Ok(updated > 0)
+    }
+
+    // 群的公开设置，群不存在或已删除时为 None
+    pub fn group_directory_settings(&self, group_id: &str) -> Result<Option<GroupDirectorySettings>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT workspace_id, visibility, join_approval FROM groups WHERE id = ?1 AND deleted_at IS NULL",
+            [group_id],
+            |row| Ok(GroupDirectorySettings {
+                workspace_id: row.get(0)?,
+                public: row.get::<_, String>(1)? == "public",
+                join_approval: row.get(2)?,
+            }),
+        ).optional()
+    }
+
+    // 群目录的一页：工作区内的公开群按创建时间倒序，只返回 before 之前的；search 不为空时按群名包含该词筛选（不区分大小写）
+    pub fn group_directory(&self, workspace_id: &str, search: &str, before: (i64, &str), limit: i64) -> Result<Vec<GroupListing>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(queries::GROUP_DIRECTORY)?;
+        stmt.query_map(params![workspace_id, contains_pattern(search), before.0, before.1, limit], |row| {
+            Ok(GroupListing {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                join_approval: row.get(3)?,
+                member_count: row.get(4)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 记录加群申请，已申请过时保留原来的申请时间并返回 false
+    pub fn create_join_request(&self, group_id: &str, user_id: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO group_join_requests (group_id, user_id, requested_at) VALUES (?1, ?2, ?3)",
+            params![group_id, user_id, now],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    // 群的待批准申请，按申请时间排序，已注销的用户不列出
+    pub fn join_requests(&self, group_id: &str) -> Result<Vec<GroupJoinRequest>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT r.user_id, u.username, u.display_name, r.requested_at
+             FROM group_join_requests r JOIN users u ON u.id = r.user_id
+             WHERE r.group_id = ?1 AND u.deleted_at IS NULL
+             ORDER BY r.requested_at, r.user_id"
+        )?;
+        stmt.query_map([group_id], |row| {
+            Ok(GroupJoinRequest {
+                user_id: row.get(0)?,
+                username: row.get(1)?,
+                display_name: row.get(2)?,
+                requested_at: row.get(3)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 移除一条加群申请（批准或拒绝时），申请不存在时返回 false
+    pub fn take_join_request(&self, group_id: &str, user_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM group_join_requests WHERE group_id = ?1 AND user_id = ?2",
+            params![group_id, user_id],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // 设置群成员的禁言结束时间（None 为解除禁言），不是群成员时返回 false
+    pub fn set_member_muted_until(&self, group_id: &str, user_id: &str, muted_until: Option<i64>) -> Result<bool> {
+        let updated = {
+            let conn = self.0.lock().unwrap();
+            conn.execute(
+                "UPDATE group_members SET muted_until = ?1 WHERE group_id = ?2 AND user_id = ?3",
+                params![muted_until, group_id, user_id],
+            )?
+        };
+        self.1.invalidate_group(group_id);
+        Ok(updated > 0)
+    }
+}
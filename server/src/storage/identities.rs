@@ -0,0 +1,135 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension, Result};
+use serde::Serialize;
+
+use super::DbPool;
+
+// 关联到本地用户的第三方账号
+#[derive(Debug, Clone, Serialize)]
+pub struct Identity {
+    pub provider: String,
+    pub external_id: String,
+    pub email: Option<String>,
+    pub signup: bool,
+    pub created_at: i64,
+}
+
+// 进行中的授权请求
+#[derive(Debug, Clone)]
+pub struct OAuthState {
+    pub code_verifier: String,
+    pub link_user_id: Option<String>,
+}
+
+fn random_token(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn conflict(message: &str) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(0), Some(message.to_string()))
+}
+
+impl DbPool {
+    // 发起授权：保存 state 和 PKCE code_verifier，返回 (state, code_verifier)，顺便清理过期的请求
+    pub fn create_oauth_state(&self, provider: &str, link_user_id: Option<&str>, ttl_secs: i64, now: i64) -> Result<(String, String)> {
+        let state = random_token(16);
+        let code_verifier = random_token(32);
+        let conn = self.0.lock().unwrap();
+        conn.execute("DELETE FROM oauth_states WHERE expires_at <= ?", [now])?;
+        conn.execute(
+            "INSERT INTO oauth_states (state, provider, code_verifier, link_user_id, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![state, provider, code_verifier, link_user_id, now + ttl_secs],
+        )?;
+        Ok((state, code_verifier))
+    }
+
+    // 核销 state：必须属于同一个提供方且未过期，每个 state 只能使用一次
+    pub fn consume_oauth_state(&self, state: &str, provider: &str, now: i64) -> Result<Option<OAuthState>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "DELETE FROM oauth_states WHERE state = ?1 AND provider = ?2 AND expires_at > ?3
+             RETURNING code_verifier, link_user_id",
+            params![state, provider, now],
+            |row| Ok(OAuthState { code_verifier: row.get(0)?, link_user_id: row.get(1)? }),
+        ).optional()
+    }
+
+    // 查找第三方账号关联的本地用户（已删除的用户除外）
+    pub fn find_identity_user(&self, provider: &str, external_id: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT i.user_id FROM identities i JOIN users u ON u.id = i.user_id
+             WHERE i.provider = ?1 AND i.external_id = ?2 AND u.deleted_at IS NULL",
+            params![provider, external_id],
+            |row| row.get(0),
+        ).optional()
+    }
+
+    // 把第三方账号关联到本地用户；第三方账号已关联其他用户，或该用户已关联同一提供方的其他账号时返回错误
+    pub fn link_identity(
+        &self,
+        user_id: &str,
+        provider: &str,
+        external_id: &str,
+        email: Option<&str>,
+        signup: bool,
+        now: i64,
+    ) -> Result<()> {
+        self.with_tx(|conn| {
+            let owner: Option<String> = conn.query_row(
+                "SELECT user_id FROM identities WHERE provider = ?1 AND external_id = ?2",
+                params![provider, external_id],
+                |row| row.get(0),
+            ).optional()?;
+            match owner {
+                Some(owner) if owner == user_id => return Ok(()),
+                Some(_) => return Err(conflict("该第三方账号已关联其他用户")),
+                None => {}
+            }
+            let linked: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM identities WHERE user_id = ?1 AND provider = ?2)",
+                params![user_id, provider],
+                |row| row.get(0),
+            )?;
+            if linked {
+                return Err(conflict("已关联该提供方的其他账号"));
+            }
+            conn.execute(
+                "INSERT INTO identities (provider, external_id, user_id, email, signup, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![provider, external_id, user_id, email, signup, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    // 用户关联的全部第三方账号
+    pub fn list_identities(&self, user_id: &str) -> Result<Vec<Identity>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT provider, external_id, email, signup, created_at FROM identities WHERE user_id = ? ORDER BY created_at, provider",
+        )?;
+        stmt.query_map([user_id], |row| {
+            Ok(Identity {
+                provider: row.get(0)?,
+                external_id: row.get(1)?,
+                email: row.get(2)?,
+                signup: row.get(3)?,
+                created_at: row.get(4)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 解除关联，返回是否删除了记录
+    pub fn unlink_identity(&self, user_id: &str, provider: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM identities WHERE user_id = ?1 AND provider = ?2",
+            params![user_id, provider],
+        )? > 0)
+    }
+}
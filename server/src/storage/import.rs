@@ -0,0 +1,257 @@
+//! 从其他系统迁移聊天记录：解析本服务器导出的 JSON 或按列映射的 CSV，保留原时间写入消息表
+//!
+//! JSON 可以是 export-user 或会话导出的文件（取其中的 messages），也可以是与 POST /messages/batch 相同的消息数组；
+//! 带原消息ID的消息重复导入时跳过。发送者和接收者可以写用户ID或用户名，接收者也可以是群ID。
+//! 内容按本服务器当前的落库加密配置重新加密
+
+use std::collections::HashMap;
+
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use super::{sequence, DbPool};
+use crate::core::datetime;
+use crate::crypto::conversation::conversation_id;
+
+// 单个导入事务包含的消息数
+const IMPORT_CHUNK_SIZE: usize = 500;
+
+// 可以导入的消息类型
+const MESSAGE_TYPES: &[&str] = &["private", "group", "system"];
+
+/// 待导入的一条消息；message_type 省略时按接收者是否为群判断，created_at 省略时为导入时间
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportRecord {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub sender_id: String,
+    pub receiver_id: String,
+    pub content: String,
+    #[serde(default)]
+    pub message_type: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+}
+
+// JSON 文件：消息数组，或带 messages 字段的导出文件
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonArchive {
+    Messages(Vec<ImportRecord>),
+    Export { messages: Vec<ImportRecord> },
+}
+
+/// 解析 JSON 文件
+pub fn parse_json(text: &str) -> std::result::Result<Vec<ImportRecord>, String> {
+    match serde_json::from_str(text).map_err(|e| format!("JSON 格式无效: {}", e))? {
+        JsonArchive::Messages(messages) | JsonArchive::Export { messages } => Ok(messages),
+    }
+}
+
+/// CSV 各字段所在的列名，sender、receiver 和 content 列必须存在
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvColumns {
+    pub sender: String,
+    pub receiver: String,
+    pub content: String,
+    pub created_at: String,
+    pub message_type: String,
+}
+
+impl Default for CsvColumns {
+    fn default() -> Self {
+        Self {
+            sender: "sender".into(),
+            receiver: "receiver".into(),
+            content: "content".into(),
+            created_at: "created_at".into(),
+            message_type: "message_type".into(),
+        }
+    }
+}
+
+impl CsvColumns {
+    /// 解析 "sender=From,content=Text" 形式的映射，未写出的字段使用默认列名
+    pub fn parse(spec: &str) -> std::result::Result<Self, String> {
+        let mut columns = Self::default();
+        for pair in spec.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (field, column) = pair.split_once('=').ok_or_else(|| format!("列映射 {} 应写成 字段=列名", pair))?;
+            let slot = match field.trim() {
+                "sender" => &mut columns.sender,
+                "receiver" => &mut columns.receiver,
+                "content" => &mut columns.content,
+                "created_at" => &mut columns.created_at,
+                "message_type" => &mut columns.message_type,
+                other => return Err(format!("未知的字段 {}，可用 sender、receiver、content、created_at、message_type", other)),
+            };
+            *slot = column.trim().to_string();
+        }
+        Ok(columns)
+    }
+}
+
+// 按 RFC 4180 切分 CSV：逗号分隔，字段可以用双引号包围，其中可以有逗号和换行，双引号写两次；跳过空行
+fn split_csv(text: &str) -> std::result::Result<Vec<Vec<String>>, String> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if quoted => quoted = false,
+            '"' if field.is_empty() => quoted = true,
+            _ if quoted => field.push(c),
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                rows.push(std::mem::take(&mut row));
+            }
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("CSV 中有未闭合的引号".into());
+    }
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push(row);
+    }
+    rows.retain(|row| !(row.len() == 1 && row[0].is_empty()));
+    Ok(rows)
+}
+
+/// 解析带表头的 CSV；时间列可以是 Unix 秒数或日期时间，不带时区的按 utc_offset（秒）换算
+pub fn parse_csv(text: &str, columns: &CsvColumns, utc_offset: i64) -> std::result::Result<Vec<ImportRecord>, String> {
+    let mut rows = split_csv(text)?.into_iter();
+    let header = rows.next().ok_or("CSV 文件为空")?;
+    let find = |name: &str| header.iter().position(|column| column.trim() == name);
+    let require = |name: &str| find(name).ok_or_else(|| format!("CSV 表头中没有 {} 列", name));
+    let (sender, receiver, content) = (require(&columns.sender)?, require(&columns.receiver)?, require(&columns.content)?);
+    let (created_at, message_type) = (find(&columns.created_at), find(&columns.message_type));
+
+    rows.enumerate().map(|(i, row)| {
+        // 表头是第 1 行
+        let line = i + 2;
+        let cell = |index: usize| row.get(index).map(|value| value.trim()).unwrap_or_default();
+        let created_at = match created_at.map(cell).filter(|value| !value.is_empty()) {
+            Some(value) => Some(datetime::parse_timestamp(value, utc_offset)
+                .ok_or_else(|| format!("第 {} 行的时间 {} 无法识别", line, value))?),
+            None => None,
+        };
+        Ok(ImportRecord {
+            id: None,
+            sender_id: cell(sender).to_string(),
+            receiver_id: cell(receiver).to_string(),
+            content: row.get(content).cloned().unwrap_or_default(),
+            message_type: message_type.map(cell).filter(|value| !value.is_empty()).map(str::to_string),
+            created_at,
+        })
+    }).collect()
+}
+
+/// 一次导入的结果
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize, // 原消息ID已存在、跳过的消息数
+}
+
+// 用户ID或用户名对应的用户ID
+fn resolve_user(conn: &Connection, value: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT id FROM users WHERE (id = ?1 OR username = ?1) AND deleted_at IS NULL ORDER BY id = ?1 DESC LIMIT 1",
+        [value],
+        |row| row.get(0),
+    ).optional()
+}
+
+impl DbPool {
+    /// 把消息导入到工作区：先检查全部发送者和接收者，有无法识别的就不写入任何消息；
+    /// 再按时间先后分配会话序号，每 IMPORT_CHUNK_SIZE 条一个事务
+    pub fn import_messages(
+        &self,
+        workspace_id: &str,
+        mut records: Vec<ImportRecord>,
+        now: i64,
+    ) -> std::result::Result<ImportReport, String> {
+        {
+            let conn = self.0.lock().unwrap();
+            let mut users: HashMap<String, Option<String>> = HashMap::new();
+            let mut lookup = |value: &str| -> Result<Option<String>> {
+                if let Some(id) = users.get(value) {
+                    return Ok(id.clone());
+                }
+                let id = resolve_user(&conn, value)?;
+                users.insert(value.to_string(), id.clone());
+                Ok(id)
+            };
+            for (i, record) in records.iter_mut().enumerate() {
+                let number = i + 1;
+                record.sender_id = lookup(&record.sender_id).map_err(|e| e.to_string())?
+                    .ok_or_else(|| format!("第 {} 条消息的发送者 {} 不存在", number, record.sender_id))?;
+                let group_workspace: Option<String> = conn.query_row(
+                    "SELECT workspace_id FROM groups WHERE id = ?1 AND deleted_at IS NULL",
+                    [&record.receiver_id],
+                    |row| row.get(0),
+                ).optional().map_err(|e| e.to_string())?;
+                let message_type = record.message_type.clone()
+                    .unwrap_or_else(|| if group_workspace.is_some() { "group" } else { "private" }.to_string());
+                if !MESSAGE_TYPES.contains(&message_type.as_str()) {
+                    return Err(format!("第 {} 条消息的类型 {} 无效", number, message_type));
+                }
+                if message_type == "private" {
+                    record.receiver_id = lookup(&record.receiver_id).map_err(|e| e.to_string())?
+                        .ok_or_else(|| format!("第 {} 条消息的接收者 {} 不存在", number, record.receiver_id))?;
+                } else if group_workspace.as_deref() != Some(workspace_id) {
+                    return Err(format!("第 {} 条消息的群 {} 不在该工作区中", number, record.receiver_id));
+                }
+                record.message_type = Some(message_type);
+            }
+        }
+        // 稳定排序，同一时间的消息保持文件中的顺序
+        records.sort_by_key(|record| record.created_at.unwrap_or(now));
+
+        let mut report = ImportReport::default();
+        for chunk in records.chunks(IMPORT_CHUNK_SIZE) {
+            let (imported, skipped) = self.with_tx(|conn| {
+                let mut stmt = conn.prepare(
+                    "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at, status, is_read, workspace_id, sent_at, conversation_id, seq)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'sent', 0, ?7, ?6, ?8, ?9)",
+                )?;
+                let (mut imported, mut skipped) = (0, 0);
+                for record in chunk {
+                    let original = record.id.as_deref().and_then(|id| Uuid::parse_str(id).ok()).map(|id| id.to_string());
+                    if let Some(id) = &original {
+                        let exists: bool = conn.query_row("SELECT EXISTS(SELECT 1 FROM messages WHERE id = ?)", [id], |row| row.get(0))?;
+                        if exists {
+                            skipped += 1;
+                            continue;
+                        }
+                    }
+                    let message_id = original.unwrap_or_else(|| Uuid::now_v7().to_string());
+                    let message_type = record.message_type.as_deref().unwrap_or("private");
+                    let created_at = record.created_at.unwrap_or(now);
+                    let conversation = conversation_id(message_type, &record.sender_id, &record.receiver_id);
+                    let stored = self.seal_message(conn, &conversation, &message_id, &record.content, created_at)?;
+                    let seq = sequence::next_seq(conn, workspace_id, &conversation)?;
+                    stmt.execute(params![
+                        message_id, record.sender_id, record.receiver_id, stored, message_type, created_at,
+                        workspace_id, conversation, seq,
+                    ])?;
+                    imported += 1;
+                }
+                Ok((imported, skipped))
+            }).map_err(|e| e.to_string())?;
+            report.imported += imported;
+            report.skipped += skipped;
+        }
+        Ok(report)
+    }
+}
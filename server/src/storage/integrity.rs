@@ -0,0 +1,81 @@
+use rusqlite::{Connection, ErrorCode};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::{cache::StorageCache, cipher, migrations, DbPool};
+use crate::config::settings::DatabaseSettings;
+
+// 启动自检结果
+#[derive(Debug)]
+pub enum IntegrityStatus {
+    // 数据库完好（可能经过自动修复）
+    Healthy,
+    // 存在问题但配置允许以只读恢复模式启动
+    Degraded(Vec<String>),
+}
+
+// 启动前检查数据库：密钥能否解开、PRAGMA integrity_check、架构版本是否被当前程序支持
+// 仅索引损坏时自动 REINDEX 修复；其余问题按 database.on_integrity_failure 拒绝启动或进入只读模式
+pub fn verify(settings: &DatabaseSettings, key: Option<&str>) -> Result<IntegrityStatus, String> {
+    let conn = Connection::open(&settings.path)
+        .map_err(|e| format!("打开数据库 {} 失败: {}", settings.path, e))?;
+    if let Some(key) = key {
+        cipher::apply_key(&conn, key).map_err(|e| format!("数据库密钥校验失败: {}", e))?;
+    } else if let Err(e) = conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0)) {
+        // 未提供密钥却读不出文件头：文件已加密或已损坏，两种情况都无法继续
+        return Err(match e.sqlite_error_code() {
+            Some(ErrorCode::NotADatabase) => format!(
+                "数据库 {} 无法读取：文件可能已加密（请检查 database.encrypt 与主密钥）或已损坏",
+                settings.path
+            ),
+            _ => format!("读取数据库 {} 失败: {}", settings.path, e),
+        });
+    }
+
+    let mut problems = integrity_problems(&conn)?;
+    if !problems.is_empty() && problems.iter().all(|p| p.contains("index")) {
+        println!("检测到索引损坏，尝试 REINDEX 修复: {:?}", problems);
+        conn.execute_batch("REINDEX").map_err(|e| format!("REINDEX 失败: {}", e))?;
+        problems = integrity_problems(&conn)?;
+    }
+
+    let version = migrations::current_version(&conn).map_err(|e| e.to_string())?;
+    if version > migrations::latest_version() {
+        problems.push(format!(
+            "数据库架构版本 {} 高于当前程序支持的 {}，请使用更新版本的服务器",
+            version,
+            migrations::latest_version()
+        ));
+    }
+
+    if problems.is_empty() {
+        return Ok(IntegrityStatus::Healthy);
+    }
+    if settings.on_integrity_failure == "read_only" {
+        return Ok(IntegrityStatus::Degraded(problems));
+    }
+    Err(format!("数据库自检失败，拒绝启动：\n{}", problems.join("\n")))
+}
+
+// 执行 PRAGMA integrity_check，返回发现的问题（完好时为空）
+fn integrity_problems(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn.prepare("PRAGMA integrity_check").map_err(|e| e.to_string())?;
+    let rows: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .and_then(|rows| rows.collect())
+        .map_err(|e| format!("完整性检查失败: {}", e))?;
+    Ok(rows.into_iter().filter(|r| r != "ok").collect())
+}
+
+impl DbPool {
+    // 以只读恢复模式打开数据库：不建表、不迁移，所有写操作都会失败
+    pub fn open_read_only(settings: &DatabaseSettings, key: Option<&str>) -> rusqlite::Result<Self> {
+        let conn = Connection::open(&settings.path)?;
+        if let Some(key) = key {
+            cipher::apply_key(&conn, key)?;
+        }
+        conn.pragma_update(None, "query_only", true)?;
+        conn.busy_timeout(Duration::from_millis(settings.busy_timeout_ms))?;
+        Ok(Self(Arc::new(Mutex::new(conn)), StorageCache::new(settings), super::encryption::StorageKeys::default()))
+    }
+}
@@ -0,0 +1,149 @@
+use rusqlite::{params, OptionalExtension, Result, Row};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::DbPool;
+
+// 任务类型
+pub const JOB_PUSH: &str = "push";               // 离线推送
+pub const JOB_RETENTION: &str = "retention";     // 执行数据保留策略
+pub const JOB_EXPORT_USER: &str = "export_user"; // 导出单个用户的数据到文件
+pub const JOB_VERIFY_EMAIL: &str = "verify_email"; // 签发邮箱验证令牌并发送验证邮件
+pub const JOB_REKEY: &str = "rekey";             // 把旧密钥、旧格式或明文保存的数据改用当前密钥加密
+
+// 任务状态：dead 为超过最大尝试次数的死信，可由管理员手动重试
+pub const JOB_STATUSES: &[&str] = &["pending", "running", "done", "dead"];
+
+// 队列中的一个任务
+#[derive(Debug, Clone, Serialize)]
+pub struct Job {
+    pub id: String,
+    pub kind: String,
+    pub payload: String, // JSON
+    pub status: String,
+    pub attempts: i64,
+    pub max_attempts: i64,
+    pub last_error: Option<String>,
+    pub run_at: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+const JOB_COLUMNS: &str = "id, kind, payload, status, attempts, max_attempts, last_error, run_at, created_at, updated_at";
+
+impl Job {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            kind: row.get(1)?,
+            payload: row.get(2)?,
+            status: row.get(3)?,
+            attempts: row.get(4)?,
+            max_attempts: row.get(5)?,
+            last_error: row.get(6)?,
+            run_at: row.get(7)?,
+            created_at: row.get(8)?,
+            updated_at: row.get(9)?,
+        })
+    }
+}
+
+impl DbPool {
+    // 加入任务队列，返回任务ID
+    pub fn enqueue_job(&self, kind: &str, payload: &serde_json::Value, max_attempts: i64, now: i64) -> Result<String> {
+        let conn = self.0.lock().unwrap();
+        let id = Uuid::now_v7().to_string();
+        conn.execute(
+            "INSERT INTO jobs (id, kind, payload, status, max_attempts, run_at, created_at, updated_at)
+             VALUES (?1, ?2, ?3, 'pending', ?4, ?5, ?5, ?5)",
+            params![id, kind, payload.to_string(), max_attempts, now],
+        )?;
+        Ok(id)
+    }
+
+    // 是否已有尚未完成的同类任务（用于周期性任务避免堆积）
+    pub fn has_unfinished_job(&self, kind: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM jobs WHERE kind = ? AND status IN ('pending', 'running'))",
+            [kind],
+            |row| row.get(0),
+        )
+    }
+
+    // 取出一个到期的任务并标记为执行中，同时计入一次尝试
+    pub fn claim_job(&self, now: i64) -> Result<Option<Job>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!(
+                "UPDATE jobs SET status = 'running', attempts = attempts + 1, updated_at = ?1
+                 WHERE id = (SELECT id FROM jobs WHERE status = 'pending' AND run_at <= ?1 ORDER BY run_at, id LIMIT 1)
+                 RETURNING {}",
+                JOB_COLUMNS
+            ),
+            [now],
+            Job::from_row,
+        )
+        .optional()
+    }
+
+    pub fn complete_job(&self, id: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET status = 'done', last_error = NULL, updated_at = ?2 WHERE id = ?1",
+            params![id, now],
+        )?;
+        Ok(())
+    }
+
+    // 记录失败：next_run_at 为 None 时进入死信状态，否则等待重试
+    pub fn fail_job(&self, id: &str, error: &str, next_run_at: Option<i64>, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        match next_run_at {
+            Some(run_at) => conn.execute(
+                "UPDATE jobs SET status = 'pending', last_error = ?2, run_at = ?3, updated_at = ?4 WHERE id = ?1",
+                params![id, error, run_at, now],
+            )?,
+            None => conn.execute(
+                "UPDATE jobs SET status = 'dead', last_error = ?2, updated_at = ?3 WHERE id = ?1",
+                params![id, error, now],
+            )?,
+        };
+        Ok(())
+    }
+
+    // 服务器重启后，把上次异常退出时仍在执行中的任务放回队列
+    pub fn requeue_running_jobs(&self, now: i64) -> Result<usize> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "UPDATE jobs SET status = 'pending', run_at = ?1, updated_at = ?1 WHERE status = 'running'",
+            [now],
+        )
+    }
+
+    // 按状态列出任务（最近更新的在前），status 为 None 时列出全部
+    pub fn list_jobs(&self, status: Option<&str>, limit: i64) -> Result<Vec<Job>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM jobs WHERE ?1 IS NULL OR status = ?1 ORDER BY updated_at DESC, id DESC LIMIT ?2",
+            JOB_COLUMNS
+        ))?;
+        stmt.query_map(params![status, limit], Job::from_row)?.collect()
+    }
+
+    // 立即重试一个死信任务（重新计算尝试次数），任务不存在或不是死信时返回 false
+    pub fn retry_job(&self, id: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let updated = conn.execute(
+            "UPDATE jobs SET status = 'pending', attempts = 0, run_at = ?2, updated_at = ?2 WHERE id = ?1 AND status = 'dead'",
+            params![id, now],
+        )?;
+        Ok(updated > 0)
+    }
+
+    pub fn get_job(&self, id: &str) -> Result<Option<Job>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(&format!("SELECT {} FROM jobs WHERE id = ?", JOB_COLUMNS), [id], Job::from_row)
+            .optional()
+    }
+}
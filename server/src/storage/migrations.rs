@@ -0,0 +1,803 @@
+use rusqlite::{params, Connection, Result, Transaction};
+use uuid::{NoContext, Timestamp, Uuid};
+
+use crate::crypto::conversation::{decode_sealed, SEALED_PREFIX};
+
+// 单个迁移步骤：版本号递增，SQL 与可选的数据迁移函数在同一事务中执行
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+    pub apply: Option<fn(&Transaction) -> Result<()>>,
+}
+
+// 所有迁移（按版本号升序追加，已发布的迁移不要修改）
+// 基础表结构由 DbPool::from_connection 以 CREATE IF NOT EXISTS 创建，视为版本 0
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "messages_query_indexes",
+        sql: "
+            -- 未读消息查询：receiver_id = ? AND is_read = 0 ORDER BY created_at
+            CREATE INDEX IF NOT EXISTS idx_messages_receiver_unread ON messages (receiver_id, is_read, created_at);
+            -- 双人会话历史：sender_id = ? AND receiver_id = ? ORDER BY created_at
+            CREATE INDEX IF NOT EXISTS idx_messages_conversation ON messages (sender_id, receiver_id, created_at);
+            -- 群聊历史：群消息的 receiver_id 即群ID
+            CREATE INDEX IF NOT EXISTS idx_messages_group_created ON messages (receiver_id, created_at) WHERE message_type = 'group';
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 2,
+        name: "retention_overrides",
+        sql: "
+            -- 单个会话（群聊）的消息保留天数覆盖，0 表示永久保留
+            CREATE TABLE IF NOT EXISTS retention_overrides (
+                conversation_id TEXT PRIMARY KEY,
+                message_days INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 3,
+        name: "message_ids_v7",
+        sql: "
+            -- 旧消息ID（UUIDv4）到新ID（UUIDv7）的映射，便于客户端用旧ID查询
+            CREATE TABLE IF NOT EXISTS message_id_map (
+                old_id TEXT PRIMARY KEY,
+                new_id TEXT NOT NULL UNIQUE
+            );
+        ",
+        apply: Some(migrate_message_ids_to_v7),
+    },
+    Migration {
+        version: 4,
+        name: "soft_delete",
+        sql: "
+            -- 软删除时间戳，NULL 表示未删除
+            ALTER TABLE users ADD COLUMN deleted_at INTEGER;
+            ALTER TABLE messages ADD COLUMN deleted_at INTEGER;
+            -- 墓碑同步和清理只关心已删除的行
+            CREATE INDEX IF NOT EXISTS idx_messages_deleted ON messages (deleted_at) WHERE deleted_at IS NOT NULL;
+            CREATE INDEX IF NOT EXISTS idx_users_deleted ON users (deleted_at) WHERE deleted_at IS NOT NULL;
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 5,
+        name: "webhooks",
+        sql: "
+            -- 管理员注册的出站 webhook，channel_id 非空时只接收该会话的事件
+            CREATE TABLE IF NOT EXISTS webhooks (
+                id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                event TEXT NOT NULL,
+                channel_id TEXT,
+                secret TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            -- 投递记录，同时作为待发送队列：status 为 pending 且到达 next_attempt_at 时发送
+            CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                id TEXT PRIMARY KEY,
+                webhook_id TEXT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+                event TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                next_attempt_at INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                delivered_at INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_webhooks_event ON webhooks (event);
+            CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+            CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook ON webhook_deliveries (webhook_id, created_at);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 6,
+        name: "bots",
+        sql: "
+            -- 机器人账号：同时在 users 表中有一行，消息的 sender_id 指向它
+            CREATE TABLE IF NOT EXISTS bots (
+                id TEXT PRIMARY KEY REFERENCES users(id),
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            -- 机器人 API 密钥，只保存 SHA-256 摘要；scope 为 send 或 read
+            CREATE TABLE IF NOT EXISTS bot_api_keys (
+                id TEXT PRIMARY KEY,
+                bot_id TEXT NOT NULL REFERENCES bots(id),
+                key_hash TEXT NOT NULL UNIQUE,
+                scope TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                last_used_at INTEGER,
+                revoked_at INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_bot_api_keys_bot ON bot_api_keys (bot_id);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 7,
+        name: "push_tokens",
+        sql: "
+            -- 移动端推送令牌，platform 为 fcm 或 apns；同一令牌只属于最后注册它的用户
+            CREATE TABLE IF NOT EXISTS push_tokens (
+                token TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                platform TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_push_tokens_user ON push_tokens (user_id);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 8,
+        name: "user_settings",
+        sql: "
+            -- 用户偏好设置（键值对），如 email_digest = off 表示不接收邮件摘要
+            CREATE TABLE IF NOT EXISTS user_settings (
+                user_id TEXT NOT NULL REFERENCES users(id),
+                key TEXT NOT NULL,
+                value TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (user_id, key)
+            );
+            -- 最后一次在线时间（WebSocket 身份确认或断开时更新）和最后一次发送摘要的时间
+            ALTER TABLE users ADD COLUMN last_seen_at INTEGER;
+            ALTER TABLE users ADD COLUMN last_digest_at INTEGER;
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 9,
+        name: "federation",
+        sql: "
+            -- 允许互通的对端服务器（管理员维护的白名单），public_key 为 Ed25519 公钥的 base64
+            CREATE TABLE IF NOT EXISTS federation_peers (
+                host TEXT PRIMARY KEY,
+                base_url TEXT NOT NULL,
+                public_key TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            -- 远端用户在本地的影子账号，users.username 为 user@host
+            CREATE TABLE IF NOT EXISTS remote_users (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                host TEXT NOT NULL,
+                remote_username TEXT NOT NULL,
+                UNIQUE (host, remote_username)
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 10,
+        name: "jobs",
+        sql: "
+            -- 持久化任务队列：status 为 pending、running、done 或 dead（超过重试次数的死信）
+            CREATE TABLE IF NOT EXISTS jobs (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                payload TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                max_attempts INTEGER NOT NULL,
+                last_error TEXT,
+                run_at INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_jobs_due ON jobs (run_at) WHERE status = 'pending';
+            CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs (status, updated_at);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 11,
+        name: "sessions",
+        sql: "
+            -- 用户登录会话，只保存令牌的 SHA-256
+            CREATE TABLE IF NOT EXISTS sessions (
+                token_hash TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id);
+            CREATE INDEX IF NOT EXISTS idx_sessions_expires ON sessions (expires_at);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 12,
+        name: "group_members_user",
+        sql: "
+            -- 会话列表从用户所在的群出发查群消息
+            CREATE INDEX IF NOT EXISTS idx_group_members_user ON group_members (user_id);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 13,
+        name: "workspaces",
+        sql: "
+            -- 工作区（租户）；所有用户都隐式属于 id 为 default 的默认工作区
+            CREATE TABLE IF NOT EXISTS workspaces (
+                id TEXT PRIMARY KEY,
+                slug TEXT NOT NULL UNIQUE,
+                name TEXT NOT NULL,
+                invite_only INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL
+            );
+            INSERT OR IGNORE INTO workspaces (id, slug, name, invite_only, created_at)
+                VALUES ('default', 'default', '默认工作区', 0, CAST(strftime('%s', 'now') AS INTEGER));
+            -- 默认工作区以外的成员关系
+            CREATE TABLE IF NOT EXISTS workspace_members (
+                workspace_id TEXT NOT NULL REFERENCES workspaces(id),
+                user_id TEXT NOT NULL REFERENCES users(id),
+                role TEXT NOT NULL,
+                joined_at INTEGER NOT NULL,
+                PRIMARY KEY (workspace_id, user_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_workspace_members_user ON workspace_members (user_id);
+            -- 邀请码只保存 SHA-256，使用后记录使用者
+            CREATE TABLE IF NOT EXISTS workspace_invites (
+                code_hash TEXT PRIMARY KEY,
+                workspace_id TEXT NOT NULL REFERENCES workspaces(id),
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL,
+                used_by TEXT,
+                used_at INTEGER
+            );
+            -- 已有的消息和群聊归入默认工作区
+            ALTER TABLE messages ADD COLUMN workspace_id TEXT NOT NULL DEFAULT 'default';
+            ALTER TABLE groups ADD COLUMN workspace_id TEXT NOT NULL DEFAULT 'default';
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 14,
+        name: "registration_challenges",
+        sql: "
+            -- 注册时的工作量证明题目，核销或过期后删除
+            CREATE TABLE IF NOT EXISTS registration_challenges (
+                challenge TEXT PRIMARY KEY,
+                expires_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 15,
+        name: "normalized_usernames",
+        sql: "
+            -- 归一化后的用户名（大小写折叠并映射易混淆字符），只对本地注册的用户填写
+            ALTER TABLE users ADD COLUMN username_normalized TEXT;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_users_username_normalized
+                ON users (username_normalized) WHERE username_normalized IS NOT NULL;
+        ",
+        apply: Some(super::usernames::backfill_normalized_usernames),
+    },
+    Migration {
+        version: 16,
+        name: "email_verification",
+        sql: "
+            -- 邮箱验证时间，NULL 表示未验证；修改邮箱后清空
+            ALTER TABLE users ADD COLUMN email_verified_at INTEGER;
+            -- 每个用户只保留最近一次签发的验证令牌（只保存 SHA-256），同时记录签发时的邮箱
+            CREATE TABLE IF NOT EXISTS email_verifications (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                token_hash TEXT NOT NULL UNIQUE,
+                email TEXT NOT NULL,
+                expires_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 17,
+        name: "oauth_identities",
+        sql: "
+            -- 第三方账号与本地用户的关联；signup 表示本地账号是通过该第三方账号创建的
+            CREATE TABLE IF NOT EXISTS identities (
+                provider TEXT NOT NULL,
+                external_id TEXT NOT NULL,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                email TEXT,
+                signup INTEGER NOT NULL DEFAULT 0,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (provider, external_id),
+                UNIQUE (user_id, provider)
+            );
+            -- 进行中的授权请求（PKCE code_verifier 只保存在服务器），link_user_id 非空时为关联已有账号
+            CREATE TABLE IF NOT EXISTS oauth_states (
+                state TEXT PRIMARY KEY,
+                provider TEXT NOT NULL,
+                code_verifier TEXT NOT NULL,
+                link_user_id TEXT,
+                expires_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 18,
+        name: "devices",
+        sql: "
+            -- 用户登录过的设备；trusted 为 0 时尚未通过新设备验证，revoked_at 非空表示已移除
+            CREATE TABLE IF NOT EXISTS devices (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id),
+                name TEXT NOT NULL,
+                platform TEXT NOT NULL,
+                push_token TEXT,
+                trusted INTEGER NOT NULL DEFAULT 0,
+                first_seen_at INTEGER NOT NULL,
+                last_seen_at INTEGER NOT NULL,
+                revoked_at INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_devices_user ON devices (user_id);
+            -- 新设备登录验证码（只保存 SHA-256），每台设备同时只有一个
+            CREATE TABLE IF NOT EXISTS device_codes (
+                device_id TEXT PRIMARY KEY REFERENCES devices(id),
+                code_hash TEXT NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                expires_at INTEGER NOT NULL
+            );
+            ALTER TABLE sessions ADD COLUMN device_id TEXT;
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 19,
+        name: "account_settings",
+        sql: "
+            -- 客户端自定义的账号设置（JSON，用 CryptoService 加密后保存），version 每次写入加一
+            CREATE TABLE IF NOT EXISTS account_settings (
+                user_id TEXT PRIMARY KEY REFERENCES users(id),
+                ciphertext BLOB NOT NULL,
+                version INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 20,
+        name: "conversation_keys",
+        sql: "
+            -- 开启消息加密后每个会话当前的密钥纪元（只保存纪元编号，密钥由主密钥派生）
+            CREATE TABLE IF NOT EXISTS conversation_keys (
+                conversation_id TEXT PRIMARY KEY,
+                epoch INTEGER NOT NULL,
+                started_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 21,
+        name: "rekey_runs",
+        sql: "
+            -- 重新加密任务的进度，cursor 为已处理到的消息ID，任务重试时从这里继续
+            CREATE TABLE IF NOT EXISTS rekey_runs (
+                job_id TEXT PRIMARY KEY,
+                cursor TEXT NOT NULL DEFAULT '',
+                total INTEGER NOT NULL,
+                processed INTEGER NOT NULL DEFAULT 0,
+                rewritten INTEGER NOT NULL DEFAULT 0,
+                failed INTEGER NOT NULL DEFAULT 0,
+                emails_rewritten INTEGER NOT NULL DEFAULT 0,
+                started_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                finished_at INTEGER
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 22,
+        name: "attachments",
+        sql: "
+            -- 聊天附件的元数据，文件本身以附件ID为文件名保存在 [attachments] dir 中
+            CREATE TABLE IF NOT EXISTS attachments (
+                id TEXT PRIMARY KEY,
+                uploader_id TEXT NOT NULL,
+                filename TEXT NOT NULL,
+                content_type TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                sha256 TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 23,
+        name: "client_message_ids",
+        sql: "
+            -- 客户端发送时附带的临时ID，同一发送者重发同一ID时返回已保存的消息
+            ALTER TABLE messages ADD COLUMN client_message_id TEXT;
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_client_id
+                ON messages(sender_id, client_message_id) WHERE client_message_id IS NOT NULL;
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 24,
+        name: "message_timestamps",
+        sql: "
+            -- 服务器记录的发送、送达和已读时间；之前保存的消息没有 sent_at，查询时以 created_at 代替，
+            -- 送达和已读时间无从得知，保持为空
+            ALTER TABLE messages ADD COLUMN sent_at INTEGER;
+            ALTER TABLE messages ADD COLUMN delivered_at INTEGER;
+            ALTER TABLE messages ADD COLUMN read_at INTEGER;
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 25,
+        name: "conversation_seqs",
+        sql: "
+            -- 每条消息在所属会话（私聊双方或群聊，按工作区区分）内的连续序号，客户端据此发现缺口
+            ALTER TABLE messages ADD COLUMN conversation_id TEXT;
+            ALTER TABLE messages ADD COLUMN seq INTEGER;
+            CREATE TABLE IF NOT EXISTS conversation_seqs (
+                workspace_id TEXT NOT NULL,
+                conversation_id TEXT NOT NULL,
+                last_seq INTEGER NOT NULL,
+                PRIMARY KEY (workspace_id, conversation_id)
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS idx_messages_conversation_seq
+                ON messages(workspace_id, conversation_id, seq) WHERE seq IS NOT NULL;
+        ",
+        apply: Some(super::sequence::backfill_seqs),
+    },
+    Migration {
+        version: 26,
+        name: "system_messages",
+        sql: "
+            -- 服务器生成的系统消息（message_type = 'system'）与群消息同属群会话，群聊历史的部分索引一并收录
+            DROP INDEX IF EXISTS idx_messages_group_created;
+            CREATE INDEX IF NOT EXISTS idx_messages_group_created ON messages (receiver_id, created_at)
+                WHERE message_type IN ('group', 'system');
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 27,
+        name: "quota_overrides",
+        sql: "
+            -- 管理员为单个用户或群设置的配额，为空的项使用 [quotas] 中的默认值
+            CREATE TABLE IF NOT EXISTS quota_overrides (
+                subject_id TEXT PRIMARY KEY,
+                max_message_chars INTEGER,
+                max_attachment_bytes INTEGER,
+                max_storage_bytes INTEGER,
+                updated_at INTEGER NOT NULL
+            );
+            -- 统计用户的附件用量
+            CREATE INDEX IF NOT EXISTS idx_attachments_uploader ON attachments (uploader_id);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 28,
+        name: "ws_outbox",
+        sql: "
+            -- 实时推送发件箱：与私聊消息在同一事务中写入，接收者确认送达后删除
+            CREATE TABLE IF NOT EXISTS ws_outbox (
+                message_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                queued_at INTEGER NOT NULL,
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_attempt_at INTEGER,
+                PRIMARY KEY (message_id, user_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_ws_outbox_queued ON ws_outbox (queued_at);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 29,
+        name: "invite_creators",
+        sql: "
+            -- 用户签发的邀请码记录签发者，用于计算邀请名额；管理员签发的为空
+            ALTER TABLE workspace_invites ADD COLUMN created_by TEXT;
+            CREATE INDEX IF NOT EXISTS idx_workspace_invites_creator ON workspace_invites (created_by);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 30,
+        name: "display_names",
+        sql: "
+            -- 显示名称（昵称），为空时客户端显示用户名
+            ALTER TABLE users ADD COLUMN display_name TEXT;
+            -- 显示名称的修改记录，也用于计算改名冷却时间
+            CREATE TABLE IF NOT EXISTS display_name_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                user_id TEXT NOT NULL,
+                old_name TEXT,
+                new_name TEXT,
+                changed_at INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_display_name_history_user ON display_name_history (user_id, changed_at);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 31,
+        name: "group_member_pages",
+        sql: "
+            -- 群成员分页按用户ID顺序读取，覆盖角色和加入时间，不必回表
+            CREATE INDEX IF NOT EXISTS idx_group_members_page ON group_members (group_id, user_id, role, joined_at);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 32,
+        name: "group_deletion",
+        sql: "
+            -- 群主删除群聊后保留墓碑，消息按保留策略的清理期限清除
+            ALTER TABLE groups ADD COLUMN deleted_at INTEGER;
+            -- 删除群聊前签发的一次性确认令牌，只保存哈希
+            CREATE TABLE IF NOT EXISTS group_deletion_tokens (
+                group_id TEXT PRIMARY KEY,
+                token_hash TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                expires_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 33,
+        name: "group_directory",
+        sql: "
+            -- 公开的群出现在群目录中，任何工作区成员都可以搜索和加入；join_approval 为 1 时加入需要群主批准
+            ALTER TABLE groups ADD COLUMN visibility TEXT NOT NULL DEFAULT 'private';
+            ALTER TABLE groups ADD COLUMN join_approval INTEGER NOT NULL DEFAULT 0;
+            CREATE INDEX IF NOT EXISTS idx_groups_directory ON groups (workspace_id, visibility, created_at, id);
+            -- 等待群主批准的加群申请
+            CREATE TABLE IF NOT EXISTS group_join_requests (
+                group_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                requested_at INTEGER NOT NULL,
+                PRIMARY KEY (group_id, user_id)
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 34,
+        name: "group_member_mutes",
+        sql: "
+            -- 被群主禁言的成员在此时间之前不能在群里发言
+            ALTER TABLE group_members ADD COLUMN muted_until INTEGER;
+            -- 成员列表显示禁言状态，分页索引随之覆盖禁言时间
+            DROP INDEX IF EXISTS idx_group_members_page;
+            CREATE INDEX IF NOT EXISTS idx_group_members_page ON group_members (group_id, user_id, role, joined_at, muted_until);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 35,
+        name: "message_translations",
+        sql: "
+            -- 消息译文缓存，开启消息加密时与原文一样加密保存
+            CREATE TABLE IF NOT EXISTS message_translations (
+                message_id TEXT NOT NULL,
+                lang TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (message_id, lang)
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 36,
+        name: "message_entities",
+        sql: "
+            -- 按 markdown 子集发送的消息：content 为去掉标记的纯文本，entities 为格式区间（JSON，开启消息加密时加密保存）
+            ALTER TABLE messages ADD COLUMN entities TEXT;
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 37,
+        name: "custom_emoji",
+        sql: "
+            -- 工作区的自定义表情，图片保存为附件；贴纸消息按名称引用
+            CREATE TABLE IF NOT EXISTS custom_emoji (
+                workspace_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                attachment_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (workspace_id, name)
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 38,
+        name: "group_receipts",
+        sql: "
+            -- 群消息按成员记录的送达和已读时间；私聊消息仍记录在 messages 的 delivered_at 和 read_at 上
+            CREATE TABLE IF NOT EXISTS message_receipts (
+                message_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                delivered_at INTEGER NOT NULL,
+                read_at INTEGER,
+                PRIMARY KEY (message_id, user_id)
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 39,
+        name: "message_content_blobs",
+        sql: "
+            -- 加密的消息内容改为以 BLOB 保存二进制密文容器，读写时不再经过 base64；
+            -- content 列的 TEXT 亲和性不会转换 BLOB，无需重建表，由 apply 转换已有的 base64 密文
+        ",
+        apply: Some(migrate_sealed_contents_to_blobs),
+    },
+    Migration {
+        version: 40,
+        name: "runtime_settings",
+        sql: "
+            -- 管理员在运行时修改的日志级别和功能开关，重启后仍然生效，优先于配置文件
+            CREATE TABLE IF NOT EXISTS runtime_settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 41,
+        name: "message_attachments",
+        sql: "
+            -- 消息引用的附件，发送时从内容和贴纸中识别；消息被删除后保留，供附件回收判断引用它的消息是否都已删除
+            CREATE TABLE IF NOT EXISTS message_attachments (
+                message_id TEXT NOT NULL,
+                attachment_id TEXT NOT NULL,
+                PRIMARY KEY (message_id, attachment_id)
+            );
+            CREATE INDEX IF NOT EXISTS idx_message_attachments_attachment ON message_attachments (attachment_id);
+        ",
+        apply: None,
+    },
+    Migration {
+        version: 42,
+        name: "attachment_blobs",
+        sql: "
+            -- 服务器自己生成、不随配置变化的密钥
+            CREATE TABLE IF NOT EXISTS server_secrets (
+                name TEXT PRIMARY KEY,
+                value BLOB NOT NULL
+            );
+            INSERT OR IGNORE INTO server_secrets (name, value) VALUES ('attachment_blob_key', randomblob(32));
+        ",
+        apply: Some(add_attachment_blobs),
+    },
+    Migration {
+        version: 43,
+        name: "attachment_scan_status",
+        sql: "",
+        apply: Some(add_attachment_scan_status),
+    },
+    Migration {
+        version: 44,
+        name: "attachment_url_key",
+        sql: "
+            -- 签名附件下载链接的密钥
+            INSERT OR IGNORE INTO server_secrets (name, value) VALUES ('attachment_url_key', randomblob(32));
+        ",
+        apply: None,
+    },
+];
+
+// 把已有消息的 UUIDv4 主键替换为按 created_at 生成的 UUIDv7，使ID与时间顺序一致
+fn migrate_message_ids_to_v7(tx: &Transaction) -> Result<()> {
+    let old_messages: Vec<(String, i64)> = {
+        let mut stmt = tx.prepare("SELECT id, created_at FROM messages")?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_>>()?
+    };
+
+    for (old_id, created_at) in old_messages {
+        // 已经是 v7 的ID无需迁移
+        if Uuid::parse_str(&old_id).is_ok_and(|id| id.get_version_num() == 7) {
+            continue;
+        }
+        let timestamp = Timestamp::from_unix(NoContext, created_at.max(0) as u64, 0);
+        let new_id = Uuid::new_v7(timestamp).to_string();
+        tx.execute("UPDATE messages SET id = ?1 WHERE id = ?2", params![new_id, old_id])?;
+        tx.execute(
+            "INSERT INTO message_id_map (old_id, new_id) VALUES (?1, ?2)",
+            params![old_id, new_id],
+        )?;
+    }
+    Ok(())
+}
+
+// 把以 base64 文本保存的消息密文解码为 BLOB；容器字节不变，解密不需要密钥参与转换
+fn migrate_sealed_contents_to_blobs(tx: &Transaction) -> Result<()> {
+    let sealed: Vec<(String, String)> = {
+        let mut stmt = tx.prepare("SELECT id, content FROM messages WHERE typeof(content) = 'text' AND content LIKE ? || '%'")?;
+        stmt.query_map([SEALED_PREFIX], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_>>()?
+    };
+    for (id, content) in sealed {
+        // 无法解码的（已损坏的）密文保持原样，读取时照常报告解密失败
+        if let Some(data) = decode_sealed(&content) {
+            tx.execute("UPDATE messages SET content = ?2 WHERE id = ?1", params![id, data])?;
+        }
+    }
+    Ok(())
+}
+
+// 表中没有该列时添加，使迁移可以在回退了 user_version 的数据库上重新执行
+fn add_column(tx: &Transaction, table: &str, column: &str, definition: &str) -> Result<()> {
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        tx.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+    Ok(())
+}
+
+// 附件文件按内容的带密钥哈希命名，内容相同的附件共用一个文件；已有附件的文件仍以附件ID命名
+fn add_attachment_blobs(tx: &Transaction) -> Result<()> {
+    add_column(tx, "attachments", "blob", "TEXT NOT NULL DEFAULT ''")?;
+    tx.execute_batch("
+        UPDATE attachments SET blob = id WHERE blob = '';
+        CREATE INDEX IF NOT EXISTS idx_attachments_blob ON attachments (blob);
+    ")
+}
+
+// 附件的病毒扫描状态，已有附件记为未扫描
+fn add_attachment_scan_status(tx: &Transaction) -> Result<()> {
+    add_column(tx, "attachments", "scan_status", "TEXT NOT NULL DEFAULT 'unscanned'")?;
+    add_column(tx, "attachments", "scan_detail", "TEXT NOT NULL DEFAULT ''")?;
+    tx.execute_batch("CREATE INDEX IF NOT EXISTS idx_attachments_scan_status ON attachments (scan_status)")
+}
+
+// 当前二进制支持的最新架构版本
+pub fn latest_version() -> i64 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+// 读取数据库当前架构版本
+pub fn current_version(conn: &Connection) -> Result<i64> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+// 依次执行尚未应用的迁移，每个迁移单独一个事务
+pub fn run(conn: &mut Connection) -> Result<()> {
+    let current = current_version(conn)?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)?;
+        if let Some(apply) = migration.apply {
+            apply(&tx)?;
+        }
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+        println!("数据库迁移已应用: {} ({})", migration.version, migration.name);
+    }
+    Ok(())
+}
@@ -1,11 +1,60 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, types::{Value, ValueRef}, vtab::array::Array, Connection, OptionalExtension, Result, Row, Transaction};
 use bcrypt::{hash, DEFAULT_COST};
 use uuid::Uuid;
 use serde::{Serialize, Deserialize};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::config::settings::DatabaseSettings;
+use crate::core::markdown::TextEntity;
+use crate::crypto::conversation::conversation_id;
+use cache::StorageCache;
+use encryption::StorageKeys;
+use crate::core::datetime::unix_now;
+
+pub mod attachments;
+pub mod backup;
+pub mod bots;
+pub mod cache;
+pub mod challenges;
+pub mod cipher;
+pub mod deletion;
+pub mod devices;
+pub mod discovery;
+pub mod digest;
+pub mod email_verification;
+pub mod emoji;
+pub mod encryption;
+pub mod export;
+pub mod federation;
+pub mod groups;
+pub mod identities;
+pub mod import;
+pub mod integrity;
+pub mod jobs;
+pub mod migrations;
+pub mod outbox;
+pub mod profiles;
+pub mod push_tokens;
+pub mod queries;
+pub mod quotas;
+pub mod receipts;
+pub mod rekey;
+pub mod retention;
+pub mod runtime;
+pub mod seed;
+pub mod sequence;
+pub mod sessions;
+pub mod slow_query;
+pub mod system_messages;
+pub mod translations;
+pub mod user_settings;
+pub mod usernames;
+pub mod workspaces;
+pub mod webhooks;
 
 // 用户模型（对应数据库表）
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,          // UUID主键
     pub username: String,    // 用户名（唯一）
@@ -18,7 +67,7 @@ pub struct User {
 // 消息模型
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Message {
-    pub id: String,          // UUID主键
+    pub id: String,          // UUIDv7主键（按时间有序）
     pub sender_id: String,   // 发送者ID
     pub receiver_id: String, // 接收者ID（用户或群聊）
     pub content: String,     // 消息内容
@@ -26,6 +75,59 @@ pub struct Message {
     pub created_at: i64,     // 创建时间戳
     pub status: String,      // 消息状态："sent", "delivered", "read"
     pub is_read: bool,       // 是否已读
+    pub workspace_id: String, // 所属工作区
+    pub sent_at: i64,        // 服务器收到消息的时间（联邦消息的 created_at 为对端时间）
+    pub delivered_at: Option<i64>, // 服务器记录的送达时间
+    pub read_at: Option<i64>, // 服务器记录的已读时间
+    pub seq: Option<i64>,    // 会话内连续递增的序号（工作区内每个私聊或群聊从 1 开始）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub entities: Vec<TextEntity>, // 按 markdown 发送时 content 中的格式区间，纯文本消息为空
+}
+
+// 会话摘要（私聊对象或群聊）
+#[derive(Debug, Clone, Serialize)]
+pub struct Conversation {
+    pub peer_id: String,           // 私聊对象的用户ID或群ID
+    pub conversation_type: String, // "private"或"group"
+    pub last_message_at: i64,      // 最后一条消息的时间戳
+    pub unread_count: i64,         // 未读私聊消息数（群聊暂不统计）
+}
+
+impl Message {
+    // 从按 queries::message_columns 顺序查询出的行构造消息
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Message {
+            id: row.get(0)?,
+            sender_id: row.get(1)?,
+            receiver_id: row.get(2)?,
+            // 加密的内容是 BLOB，由 DbPool::message_from_row 解密
+            content: match row.get_ref(3)? {
+                ValueRef::Blob(_) => String::new(),
+                _ => row.get(3)?,
+            },
+            message_type: row.get(4)?,
+            created_at: row.get(5)?,
+            status: row.get(6)?,
+            is_read: row.get(7)?,
+            workspace_id: row.get(8)?,
+            sent_at: row.get(9)?,
+            delivered_at: row.get(10)?,
+            read_at: row.get(11)?,
+            seq: row.get(13)?,
+            // 格式区间可能是密文，由 DbPool::message_from_row 解析
+            entities: Vec::new(),
+        })
+    }
+}
+
+// 待插入的消息（批量发送使用），内容和格式区间已按 format 处理
+#[derive(Debug)]
+pub struct NewMessage {
+    pub sender_id: String,
+    pub receiver_id: String,
+    pub content: String,
+    pub entities: Vec<TextEntity>,
+    pub message_type: String,
 }
 
 // 好友关系模型
@@ -45,16 +147,30 @@ pub struct Group {
     pub name: String,        // 群聊名称
     pub creator_id: String,  // 创建者ID
     pub created_at: i64,     // 创建时间戳
+    pub workspace_id: String, // 所属工作区
 }
 
 // 群聊成员模型
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GroupMember {
     pub id: String,          // UUID主键
     pub group_id: String,    // 群聊ID
     pub user_id: String,     // 用户ID
     pub joined_at: i64,      // 加入时间戳
     pub role: String,        // 角色："owner"或"member"
+    #[serde(default)]
+    pub muted_until: Option<i64>, // 禁言结束时间，未被禁言时为空
+}
+
+// 成员列表中的一项，附带用户名和显示名称
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupMemberEntry {
+    pub user_id: String,
+    pub role: String,
+    pub joined_at: i64,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub muted_until: Option<i64>,
 }
 
 // 好友请求响应
@@ -67,15 +183,76 @@ pub struct FriendRequest {
     pub created_at: i64,
 }
 
-// 数据库连接池（线程安全）
+// 把搜索词转成 LIKE 的“包含”模式（转义字符为反斜杠），空搜索词返回空字符串表示不筛选
+fn contains_pattern(search: &str) -> String {
+    if search.is_empty() {
+        return String::new();
+    }
+    let escaped = search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+// 把一组ID转成 rarray 绑定的数组参数，一条语句处理整批ID
+fn id_array(ids: &[String]) -> Array {
+    Rc::new(ids.iter().cloned().map(Value::from).collect())
+}
+
+// 连接上缓存的预编译语句数，容纳所有热点查询
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+
+// 数据库连接池（线程安全），附带热点查询缓存和落库加密密钥
 #[derive(Clone)]
-pub struct DbPool(pub Arc<Mutex<Connection>>);
+pub struct DbPool(pub Arc<Mutex<Connection>>, pub(crate) StorageCache, pub(crate) StorageKeys);
 
 impl DbPool {
-    // 初始化数据库连接并创建所有表
+    // 初始化数据库连接并创建所有表（使用默认调优参数，不加密）
     pub fn new(db_path: &str) -> Result<Self> {
-        let conn = Connection::open(db_path)?;
-        
+        Self::with_settings(&DatabaseSettings {
+            path: db_path.to_string(),
+            ..DatabaseSettings::default()
+        }, None)
+    }
+
+    // 按配置打开数据库：先设置密钥（若加密）和 PRAGMA 再建表
+    pub fn with_settings(settings: &DatabaseSettings, key: Option<&str>) -> Result<Self> {
+        let conn = Connection::open(&settings.path)?;
+        if let Some(key) = key {
+            cipher::apply_key(&conn, key)?;
+        }
+        Self::apply_pragmas(&conn, settings)?;
+        slow_query::set_threshold(Duration::from_millis(settings.slow_query_ms));
+        Self::from_connection(conn, StorageCache::new(settings))
+    }
+
+    // 创建内存数据库（用于测试，进程结束即丢弃）
+    pub fn in_memory() -> Result<Self> {
+        let conn = Connection::open_in_memory()?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        Self::from_connection(conn, StorageCache::new(&DatabaseSettings::default()))
+    }
+
+    // 设置连接级 PRAGMA（WAL、busy_timeout、外键、同步级别）
+    fn apply_pragmas(conn: &Connection, settings: &DatabaseSettings) -> Result<()> {
+        // PRAGMA 不支持参数绑定，只接受白名单内的取值
+        let journal_mode = settings.journal_mode.to_uppercase();
+        if !["DELETE", "TRUNCATE", "PERSIST", "MEMORY", "WAL", "OFF"].contains(&journal_mode.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!("journal_mode={}", settings.journal_mode)));
+        }
+        let synchronous = settings.synchronous.to_uppercase();
+        if !["OFF", "NORMAL", "FULL", "EXTRA"].contains(&synchronous.as_str()) {
+            return Err(rusqlite::Error::InvalidParameterName(format!("synchronous={}", settings.synchronous)));
+        }
+
+        // journal_mode 会返回实际生效的模式，需要用 query_row 读取
+        let _: String = conn.query_row(&format!("PRAGMA journal_mode = {}", journal_mode), [], |row| row.get(0))?;
+        conn.execute_batch(&format!("PRAGMA synchronous = {};", synchronous))?;
+        conn.pragma_update(None, "foreign_keys", settings.foreign_keys)?;
+        conn.busy_timeout(Duration::from_millis(settings.busy_timeout_ms))?;
+        Ok(())
+    }
+
+    // 在已打开的连接上建表并包装为连接池
+    fn from_connection(mut conn: Connection, cache: StorageCache) -> Result<Self> {
         // 创建表（若不存在）
         conn.execute(
             "CREATE TABLE IF NOT EXISTS users (
@@ -96,7 +273,7 @@ impl DbPool {
                 id TEXT PRIMARY KEY,
                 sender_id TEXT NOT NULL,
                 receiver_id TEXT NOT NULL,
-                content TEXT NOT NULL,
+                content TEXT NOT NULL, -- 开启消息加密后为 BLOB（二进制密文容器），TEXT 亲和性的列原样保存 BLOB
                 message_type TEXT NOT NULL,
                 created_at INTEGER NOT NULL,
                 status TEXT NOT NULL DEFAULT 'sent',
@@ -176,86 +353,103 @@ impl DbPool {
             [],
         )?;
         
-        Ok(Self(Arc::new(Mutex::new(conn))))
+        // 应用增量迁移（索引等）
+        migrations::run(&mut conn)?;
+        // 批量标记已读等语句以 rarray(?) 绑定ID数组
+        rusqlite::vtab::array::load_module(&conn)?;
+        conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+        slow_query::install(&conn);
+        
+        Ok(Self(Arc::new(Mutex::new(conn)), cache, StorageKeys::default()))
+    }
+
+    // 在单个事务中执行多步操作：闭包返回 Err 时自动回滚
+    pub fn with_tx<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T>,
+    {
+        let mut conn = self.0.lock().unwrap();
+        let tx = conn.transaction()?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
     }
 
-    // 注册新用户（核心逻辑）
+    // 注册新用户（核心逻辑），用户隐式属于默认工作区
     pub fn register_user(
         &self,
         username: &str,
-        _email: &str, // 保留参数但忽略，保持向后兼容
+        email: &str, // 为空时使用占位邮箱
         password: &str,
     ) -> Result<User> {
-        let conn = self.0.lock().unwrap();
-        
-        // 检查用户名是否已存在
-        let exists: bool = conn.query_row(
-            "SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)",
-            [username],
-            |row| row.get(0),
-        )?;
-        
-        if exists {
-            return Err(rusqlite::Error::SqliteFailure(
-                rusqlite::ffi::Error::new(0),
-                Some("用户名已存在".to_string())
-            ));
-        }
-
-        // 密码哈希（bcrypt）
+        // 密码哈希（bcrypt）；接口层在阻塞线程池中计算后调用 register_user_in_workspace
         let password_hash = hash(password, DEFAULT_COST).map_err(|e| {
             rusqlite::Error::ToSqlConversionFailure(Box::new(e))
         })?;
-
-        // 插入数据库
-        let user_id = Uuid::new_v4().to_string();
-        let created_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        // 生成唯一占位邮箱（避免使用空字符串导致 UNIQUE 约束冲突）
-        let email_placeholder = format!("{}@local", user_id);
-
-        conn.execute(
-            "INSERT INTO users (id, username, email, password_hash, created_at) 
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![user_id, username, &email_placeholder, &password_hash, created_at],
-        )?;
-
-        // 返回新用户（不含敏感信息）
-        Ok(User {
-            id: user_id,
-            username: username.to_string(),
-            email: email_placeholder,
-            password_hash,
-            created_at,
-            avatar_url: String::new(),
-        })
+        self.with_tx(|conn| insert_user(conn, &self.2, username, email, &password_hash))
     }
     
     // 发送消息
     pub fn send_message(
         &self,
+        workspace_id: &str,
         sender_id: &str,
         receiver_id: &str,
         content: &str,
         message_type: &str,
     ) -> Result<Message> {
+        self.send_message_once(workspace_id, sender_id, receiver_id, content, &[], message_type, None)
+            .map(|(message, _)| message)
+    }
+
+    // 发送消息，附带客户端临时ID时按 (发送者, 临时ID) 去重：已保存过则返回原消息，第二项为 false；
+    // entities 为 content 中的格式区间（core::markdown 解析得出），纯文本消息为空
+    #[allow(clippy::too_many_arguments)]
+    pub fn send_message_once(
+        &self,
+        workspace_id: &str,
+        sender_id: &str,
+        receiver_id: &str,
+        content: &str,
+        entities: &[TextEntity],
+        message_type: &str,
+        client_message_id: Option<&str>,
+    ) -> Result<(Message, bool)> {
         let conn = self.0.lock().unwrap();
+        if let Some(client_message_id) = client_message_id {
+            let existing = conn.query_row(queries::MESSAGE_BY_CLIENT_ID, params![sender_id, client_message_id], |row| self.message_from_row(row))
+                .optional()?;
+            if let Some(message) = existing {
+                return Ok((message, false));
+            }
+        }
+
+        // UUIDv7 按时间有序，便于游标分页和索引局部性
+        let message_id = Uuid::now_v7().to_string();
+        let created_at = unix_now();
         
-        let message_id = Uuid::new_v4().to_string();
-        let created_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
-        
-        conn.execute(
-            "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at, status, is_read) 
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![message_id, sender_id, receiver_id, content, message_type, created_at, "sent", false],
+        // 分配序号和插入在同一事务中，插入失败时序号一起回滚
+        let tx = conn.unchecked_transaction()?;
+        let conversation = conversation_id(message_type, sender_id, receiver_id);
+        let stored = self.seal_message(&tx, &conversation, &message_id, content, created_at)?;
+        let stored_entities = match entities {
+            [] => None,
+            entities => {
+                let json = serde_json::to_string(entities).expect("格式区间可以序列化");
+                Some(self.seal_content(&tx, &conversation, &encryption::entities_aad(&message_id), &json, created_at)?)
+            }
+        };
+        let seq = sequence::next_seq(&tx, workspace_id, &conversation)?;
+        tx.prepare_cached(queries::INSERT_MESSAGE)?.execute(
+            params![message_id, sender_id, receiver_id, stored, message_type, created_at, workspace_id, client_message_id, conversation, seq, stored_entities],
         )?;
+        if message_type == "private" && sender_id != receiver_id {
+            outbox::enqueue(&tx, &message_id, receiver_id, created_at)?;
+        }
+        attachments::link_message_attachments(&tx, &message_id, content, entities)?;
+        tx.commit()?;
         
-        Ok(Message {
+        Ok((Message {
             id: message_id,
             sender_id: sender_id.to_string(),
             receiver_id: receiver_id.to_string(),
@@ -264,96 +458,164 @@ impl DbPool {
             created_at,
             status: "sent".to_string(),
             is_read: false,
+            workspace_id: workspace_id.to_string(),
+            sent_at: created_at,
+            delivered_at: None,
+            read_at: None,
+            seq: Some(seq),
+            entities: entities.to_vec(),
+        }, true))
+    }
+    
+    // 批量发送消息：单个事务 + 单条预编译语句，任一条失败则全部回滚
+    pub fn send_messages_batch(&self, workspace_id: &str, messages: &[NewMessage]) -> Result<Vec<Message>> {
+        self.with_tx(|conn| {
+            let created_at = unix_now();
+            let mut stmt = conn.prepare_cached(queries::INSERT_MESSAGE)?;
+
+            let mut inserted = Vec::with_capacity(messages.len());
+            for new in messages {
+                let message_id = Uuid::now_v7().to_string();
+                let conversation = conversation_id(&new.message_type, &new.sender_id, &new.receiver_id);
+                let stored = self.seal_message(conn, &conversation, &message_id, &new.content, created_at)?;
+                let stored_entities = match new.entities.as_slice() {
+                    [] => None,
+                    entities => {
+                        let json = serde_json::to_string(entities).expect("格式区间可以序列化");
+                        Some(self.seal_content(conn, &conversation, &encryption::entities_aad(&message_id), &json, created_at)?)
+                    }
+                };
+                let seq = sequence::next_seq(conn, workspace_id, &conversation)?;
+                stmt.execute(params![
+                    message_id, new.sender_id, new.receiver_id, stored, new.message_type, created_at, workspace_id,
+                    None::<&str>, conversation, seq, stored_entities,
+                ])?;
+                // 与单条发送一样写入发件箱并关联引用的附件
+                if new.message_type == "private" && new.sender_id != new.receiver_id {
+                    outbox::enqueue(conn, &message_id, &new.receiver_id, created_at)?;
+                }
+                attachments::link_message_attachments(conn, &message_id, &new.content, &new.entities)?;
+                inserted.push(Message {
+                    id: message_id,
+                    sender_id: new.sender_id.clone(),
+                    receiver_id: new.receiver_id.clone(),
+                    content: new.content.clone(),
+                    message_type: new.message_type.clone(),
+                    created_at,
+                    status: "sent".to_string(),
+                    is_read: false,
+                    workspace_id: workspace_id.to_string(),
+                    sent_at: created_at,
+                    delivered_at: None,
+                    read_at: None,
+                    seq: Some(seq),
+                    entities: new.entities.clone(),
+                });
+            }
+            Ok(inserted)
         })
     }
     
-    // 获取用户的未读消息
-    pub fn get_unread_messages(&self, user_id: &str) -> Result<Vec<Message>> {
+    // 获取用户在工作区内的未读消息
+    pub fn get_unread_messages(&self, workspace_id: &str, user_id: &str) -> Result<Vec<Message>> {
         let conn = self.0.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, sender_id, receiver_id, content, message_type, created_at, status, is_read 
-             FROM messages 
-             WHERE receiver_id = ? AND is_read = 0 AND message_type = 'private'"
-        )?;
+        let mut stmt = conn.prepare_cached(queries::UNREAD_MESSAGES)?;
         
-        let messages = stmt.query_map([user_id], |row| {
-            Ok(Message {
-                id: row.get(0)?,
-                sender_id: row.get(1)?,
-                receiver_id: row.get(2)?,
-                content: row.get(3)?,
-                message_type: row.get(4)?,
-                created_at: row.get(5)?,
-                status: row.get(6)?,
-                is_read: row.get(7)?,
-            })
-        })?
-        .filter_map(Result::ok)
-        .collect();
+        let messages = stmt.query_map(params![user_id, workspace_id], |row| self.message_from_row(row))?
+            .filter_map(Result::ok)
+            .collect();
         
         Ok(messages)
     }
     
-    // 将消息标记为已读
-    pub fn mark_messages_as_read(&self, message_ids: &[String]) -> Result<()> {
+    // 将消息标记为已读，第一次标记时记录已读时间（没有送达时间的一并补上）
+    pub fn mark_messages_as_read(&self, message_ids: &[String], now: i64) -> Result<()> {
+        let ids = id_array(message_ids);
+        self.with_tx(|conn| {
+            conn.prepare_cached(queries::MARK_MESSAGES_READ)?.execute(params![ids, now])?;
+            outbox::acknowledge(conn, &ids)
+        })
+    }
+    
+    // 将消息标记为已送达，第一次标记时记录送达时间；已读的消息不会退回送达状态
+    pub fn mark_messages_as_delivered(&self, message_ids: &[String], now: i64) -> Result<()> {
+        let ids = id_array(message_ids);
+        self.with_tx(|conn| {
+            conn.prepare_cached(queries::MARK_MESSAGES_DELIVERED)?.execute(params![ids, now])?;
+            outbox::acknowledge(conn, &ids)
+        })
+    }
+    
+    // 同步消息（支持断点续传和批量获取）
+    pub fn sync_messages(&self, workspace_id: &str, user_id: &str, last_sync_time: i64, limit: i64) -> Result<Vec<Message>> {
         let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare_cached(queries::SYNC_MESSAGES)?;
         
-        for message_id in message_ids {
-            conn.execute(
-                "UPDATE messages SET is_read = 1, status = 'read' WHERE id = ?",
-                [message_id],
-            )?;
-        }
+        let messages = stmt.query_map(params![user_id, last_sync_time, limit, workspace_id], |row| self.message_from_row(row))?
+            .filter_map(Result::ok)
+            .collect();
         
-        Ok(())
+        Ok(messages)
     }
     
-    // 将消息标记为已送达
-    pub fn mark_messages_as_delivered(&self, message_ids: &[String]) -> Result<()> {
+    // 获取双人会话历史（before 之前的消息，按时间倒序）；
+    // before_id 为上一页最后一条消息的ID，时间戳与 before 相同但ID更小的消息仍会返回，为空时只按时间戳
+    pub fn get_conversation_history(&self, workspace_id: &str, user_id: &str, peer_id: &str, before: i64, before_id: &str, limit: i64) -> Result<Vec<Message>> {
         let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(queries::CONVERSATION_HISTORY)?;
         
-        for message_id in message_ids {
-            conn.execute(
-                "UPDATE messages SET status = 'delivered' WHERE id = ?",
-                [message_id],
-            )?;
-        }
+        let messages = stmt.query_map(params![user_id, peer_id, before, limit, workspace_id, before_id], |row| self.message_from_row(row))?
+            .filter_map(Result::ok)
+            .collect();
         
-        Ok(())
+        Ok(messages)
     }
     
-    // 同步消息（支持断点续传和批量获取）
-    pub fn sync_messages(&self, user_id: &str, last_sync_time: i64, limit: i64) -> Result<Vec<Message>> {
+    // 获取群聊历史（before 之前的消息，按时间倒序），before_id 同 get_conversation_history
+    pub fn get_group_history(&self, workspace_id: &str, group_id: &str, before: i64, before_id: &str, limit: i64) -> Result<Vec<Message>> {
         let conn = self.0.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, sender_id, receiver_id, content, message_type, created_at, status, is_read 
-             FROM messages 
-             WHERE (receiver_id = ? OR sender_id = ?) AND created_at > ? 
-             ORDER BY created_at ASC 
-             LIMIT ?"
-        )?;
+        let mut stmt = conn.prepare(queries::GROUP_HISTORY)?;
         
-        let messages = stmt.query_map(
-            params![user_id, user_id, last_sync_time, limit],
-            |row| {
-                Ok(Message {
-                    id: row.get(0)?,
-                    sender_id: row.get(1)?,
-                    receiver_id: row.get(2)?,
-                    content: row.get(3)?,
-                    message_type: row.get(4)?,
-                    created_at: row.get(5)?,
-                    status: row.get(6)?,
-                    is_read: row.get(7)?,
-                })
-            }
-        )?
-        .filter_map(Result::ok)
-        .collect();
+        let messages = stmt.query_map(params![group_id, before, limit, workspace_id, before_id], |row| self.message_from_row(row))?
+            .filter_map(Result::ok)
+            .collect();
         
         Ok(messages)
     }
     
+    // 获取用户在工作区内的会话列表（按最后一条消息时间倒序）
+    pub fn get_conversations(&self, workspace_id: &str, user_id: &str, limit: i64) -> Result<Vec<Conversation>> {
+        self.get_conversations_after(workspace_id, user_id, (i64::MAX, ""), limit)
+    }
+
+    // 会话列表中排在 after（上一页最后一个会话的 (最后消息时间, 会话对象ID)）之后的一页
+    pub fn get_conversations_after(&self, workspace_id: &str, user_id: &str, after: (i64, &str), limit: i64) -> Result<Vec<Conversation>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(queries::CONVERSATIONS)?;
+
+        let conversations = stmt.query_map(params![user_id, limit, workspace_id, after.0, after.1], |row| {
+            Ok(Conversation {
+                peer_id: row.get(0)?,
+                conversation_type: row.get(1)?,
+                last_message_at: row.get(2)?,
+                unread_count: row.get(3)?,
+            })
+        })?
+        .collect::<Result<_>>()?;
+
+        Ok(conversations)
+    }
+
+    // 两个用户是否互为好友
+    pub fn are_friends(&self, user_id: &str, friend_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM friendships WHERE user_id = ?1 AND friend_id = ?2 AND status = 'accepted')",
+            params![user_id, friend_id],
+            |row| row.get(0),
+        )
+    }
+
     // 获取用户好友列表
     pub fn get_friends(&self, user_id: &str) -> Result<Vec<User>> {
         let conn = self.0.lock().unwrap();
@@ -361,14 +623,14 @@ impl DbPool {
             "SELECT u.id, u.username, u.email, u.password_hash, u.created_at, u.avatar_url 
              FROM users u 
              JOIN friendships f ON u.id = f.friend_id 
-             WHERE f.user_id = ? AND f.status = 'accepted'"
+             WHERE f.user_id = ? AND f.status = 'accepted' AND u.deleted_at IS NULL"
         )?;
         
         let friends = stmt.query_map([user_id], |row| {
             Ok(User {
                 id: row.get(0)?,
                 username: row.get(1)?,
-                email: row.get(2)?,
+                email: self.2.open_email(row.get(2)?, 2)?,
                 password_hash: row.get(3)?,
                 created_at: row.get(4)?,
                 avatar_url: row.get(5)?,
@@ -388,7 +650,7 @@ impl DbPool {
         let mut stmt = conn.prepare(
             "SELECT id, username, email, password_hash, created_at, avatar_url 
              FROM users 
-             WHERE username LIKE ? OR id LIKE ? 
+             WHERE (username LIKE ? OR id LIKE ?) AND deleted_at IS NULL 
              LIMIT 10"
         )?;
         
@@ -399,7 +661,7 @@ impl DbPool {
                 Ok(User {
                     id: row.get(0)?,
                     username: row.get(1)?,
-                    email: row.get(2)?,
+                    email: self.2.open_email(row.get(2)?, 2)?,
                     password_hash: row.get(3)?,
                     created_at: row.get(4)?,
                     avatar_url: row.get(5)?,
@@ -412,11 +674,22 @@ impl DbPool {
         Ok(users)
     }
 
+    // 登录校验用的 (用户ID, 用户名, 密码哈希)，用户不存在或不能用密码登录时返回 None
+    pub fn user_credentials(&self, username: &str) -> Result<Option<(String, String, String)>> {
+        let conn = self.0.lock().unwrap();
+        conn.prepare_cached(queries::USER_CREDENTIALS)?
+            .query_row([username], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .optional()
+    }
+
     // 检查用户是否存在（根据用户ID）
     pub fn user_exists_by_id(&self, user_id: &str) -> Result<bool> {
         let conn = self.0.lock().unwrap();
+        if self.1.users.contains_key(user_id) {
+            return Ok(true);
+        }
         let exists: bool = conn.query_row(
-            "SELECT EXISTS(SELECT 1 FROM users WHERE id = ?)",
+            "SELECT EXISTS(SELECT 1 FROM users WHERE id = ? AND deleted_at IS NULL)",
             [user_id],
             |row| row.get(0),
         )?;
@@ -425,62 +698,59 @@ impl DbPool {
 
     // 发送好友请求
     pub fn send_friend_request(&self, from_user_id: &str, to_username: &str) -> Result<FriendRequest> {
-        let conn = self.0.lock().unwrap();
-        
-        // 检查目标用户是否存在
-        let to_user_id: String = conn.query_row(
-            "SELECT id FROM users WHERE username = ?",
-            [to_username],
-            |row| row.get(0),
-        )?;
+        self.with_tx(|conn| {
+            // 检查目标用户是否存在
+            let to_user_id: String = conn.query_row(
+                "SELECT id FROM users WHERE username = ? AND deleted_at IS NULL",
+                [to_username],
+                |row| row.get(0),
+            )?;
         
-        // 检查是否已经是好友
-        let is_friend: bool = conn.query_row(
-            "SELECT EXISTS(SELECT 1 FROM friendships WHERE user_id = ? AND friend_id = ? AND status = 'accepted')",
-            params![from_user_id, to_user_id],
-            |row| row.get(0),
-        )?;
+            // 检查是否已经是好友
+            let is_friend: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM friendships WHERE user_id = ? AND friend_id = ? AND status = 'accepted')",
+                params![from_user_id, to_user_id],
+                |row| row.get(0),
+            )?;
         
-        if is_friend {
-            return Err(rusqlite::Error::SqliteFailure(
-                rusqlite::ffi::Error::new(1),
-                Some("Already friends".to_string())
-            ));
-        }
+            if is_friend {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(1),
+                    Some("Already friends".to_string())
+                ));
+            }
         
-        // 检查是否已经发送过请求
-        let has_pending_request: bool = conn.query_row(
-            "SELECT EXISTS(SELECT 1 FROM friend_requests WHERE from_user_id = ? AND to_user_id = ? AND status = 'pending')",
-            params![from_user_id, to_user_id],
-            |row| row.get(0),
-        )?;
+            // 检查是否已经发送过请求
+            let has_pending_request: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM friend_requests WHERE from_user_id = ? AND to_user_id = ? AND status = 'pending')",
+                params![from_user_id, to_user_id],
+                |row| row.get(0),
+            )?;
         
-        if has_pending_request {
-            return Err(rusqlite::Error::SqliteFailure(
-                rusqlite::ffi::Error::new(2),
-                Some("Friend request already sent".to_string())
-            ));
-        }
+            if has_pending_request {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(2),
+                    Some("Friend request already sent".to_string())
+                ));
+            }
         
-        // 创建好友请求
-        let request_id = Uuid::new_v4().to_string();
-        let created_at = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+            // 创建好友请求
+            let request_id = Uuid::new_v4().to_string();
+            let created_at = unix_now();
         
-        conn.execute(
-            "INSERT INTO friend_requests (id, from_user_id, to_user_id, status, created_at) 
-             VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![request_id, from_user_id, to_user_id, "pending", created_at],
-        )?;
+            conn.execute(
+                "INSERT INTO friend_requests (id, from_user_id, to_user_id, status, created_at) 
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![request_id, from_user_id, to_user_id, "pending", created_at],
+            )?;
         
-        Ok(FriendRequest {
-            id: request_id,
-            from_user_id: from_user_id.to_string(),
-            to_user_id,
-            status: "pending".to_string(),
-            created_at,
+            Ok(FriendRequest {
+                id: request_id,
+                from_user_id: from_user_id.to_string(),
+                to_user_id,
+                status: "pending".to_string(),
+                created_at,
+            })
         })
     }
 
@@ -510,87 +780,183 @@ impl DbPool {
 
     // 响应好友请求
     pub fn respond_to_friend_request(&self, request_id: &str, responder_id: &str, response: &str) -> Result<Friendship> {
-        let conn = self.0.lock().unwrap();
+        self.with_tx(|conn| {
+            // 验证请求存在且由该接收方(responder)处理
+            // 查询出原始发送者(from_user_id)和当前状态
+            let (from_user_id, current_status): (String, String) = conn.query_row(
+                "SELECT from_user_id, status FROM friend_requests WHERE id = ? AND to_user_id = ?",
+                params![request_id, responder_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
 
-        // 验证请求存在且由该接收方(responder)处理
-        // 查询出原始发送者(from_user_id)和当前状态
-        let (from_user_id, current_status): (String, String) = conn.query_row(
-            "SELECT from_user_id, status FROM friend_requests WHERE id = ? AND to_user_id = ?",
-            params![request_id, responder_id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )?;
+            if current_status != "pending" {
+                return Err(rusqlite::Error::SqliteFailure(
+                    rusqlite::ffi::Error::new(3),
+                    Some("Friend request already processed".to_string())
+                ));
+            }
 
-        if current_status != "pending" {
-            return Err(rusqlite::Error::SqliteFailure(
-                rusqlite::ffi::Error::new(3),
-                Some("Friend request already processed".to_string())
-            ));
-        }
+            // 更新好友请求状态
+            conn.execute(
+                "UPDATE friend_requests SET status = ? WHERE id = ?",
+                params![response, request_id],
+            )?;
 
-        // 更新好友请求状态
-        conn.execute(
-            "UPDATE friend_requests SET status = ? WHERE id = ?",
-            params![response, request_id],
-        )?;
+            if response == "accepted" {
+                // 创建双向好友关系（from_user_id <-> responder_id）
+                let friendship_id = Uuid::new_v4().to_string();
+                let created_at = unix_now();
+
+                // 正向关系（发送者的好友是接收者）
+                conn.execute(
+                    "INSERT INTO friendships (id, user_id, friend_id, status, created_at) 
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![friendship_id, from_user_id, responder_id, "accepted", created_at],
+                )?;
+
+                // 反向关系（接收者的好友是发送者）
+                let reverse_friendship_id = Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO friendships (id, user_id, friend_id, status, created_at) 
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![reverse_friendship_id, responder_id, from_user_id, "accepted", created_at],
+                )?;
+
+                Ok(Friendship {
+                    id: friendship_id,
+                    user_id: from_user_id.to_string(),
+                    friend_id: responder_id.to_string(),
+                    status: "accepted".to_string(),
+                    created_at,
+                })
+            } else {
+                // 拒绝请求，只更新状态，不创建好友关系
+                Ok(Friendship {
+                    id: request_id.to_string(),
+                    user_id: from_user_id.to_string(),
+                    friend_id: responder_id.to_string(),
+                    status: "rejected".to_string(),
+                    created_at: 0,
+                })
+            }
+        })
+    }
 
-        if response == "accepted" {
-            // 创建双向好友关系（from_user_id <-> responder_id）
-            let friendship_id = Uuid::new_v4().to_string();
-            let created_at = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_secs() as i64;
+    // 删除好友
+    pub fn remove_friend(&self, user_id: &str, friend_id: &str) -> Result<()> {
+        self.with_tx(|conn| {
+            // 删除双向好友关系
+            conn.execute(
+                "DELETE FROM friendships WHERE user_id = ? AND friend_id = ?",
+                params![user_id, friend_id],
+            )?;
+        
+            conn.execute(
+                "DELETE FROM friendships WHERE user_id = ? AND friend_id = ?",
+                params![friend_id, user_id],
+            )?;
+        
+            Ok(())
+        })
+    }
+
+    // 创建群聊并把创建者加入为群主（同一事务，避免出现无群主的群）
+    pub fn create_group(&self, workspace_id: &str, name: &str, creator_id: &str) -> Result<Group> {
+        self.with_tx(|conn| {
+            let group_id = Uuid::new_v4().to_string();
+            let created_at = unix_now();
 
-            // 正向关系（发送者的好友是接收者）
             conn.execute(
-                "INSERT INTO friendships (id, user_id, friend_id, status, created_at) 
-                 VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![friendship_id, from_user_id, responder_id, "accepted", created_at],
+                "INSERT INTO groups (id, group_id, name, creator_id, created_at, workspace_id) 
+                 VALUES (?1, ?1, ?2, ?3, ?4, ?5)",
+                params![group_id, name, creator_id, created_at, workspace_id],
             )?;
 
-            // 反向关系（接收者的好友是发送者）
-            let reverse_friendship_id = Uuid::new_v4().to_string();
             conn.execute(
-                "INSERT INTO friendships (id, user_id, friend_id, status, created_at) 
+                "INSERT INTO group_members (id, group_id, user_id, joined_at, role) 
                  VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![reverse_friendship_id, responder_id, from_user_id, "accepted", created_at],
+                params![Uuid::new_v4().to_string(), group_id, creator_id, created_at, "owner"],
             )?;
+            self.1.invalidate_group(&group_id);
 
-            Ok(Friendship {
-                id: friendship_id,
-                user_id: from_user_id.to_string(),
-                friend_id: responder_id.to_string(),
-                status: "accepted".to_string(),
+            Ok(Group {
+                id: group_id,
+                name: name.to_string(),
+                creator_id: creator_id.to_string(),
                 created_at,
+                workspace_id: workspace_id.to_string(),
             })
-        } else {
-            // 拒绝请求，只更新状态，不创建好友关系
-            Ok(Friendship {
-                id: request_id.to_string(),
-                user_id: from_user_id.to_string(),
-                friend_id: responder_id.to_string(),
-                status: "rejected".to_string(),
-                created_at: 0,
-            })
-        }
+        })
     }
 
-    // 删除好友
-    pub fn remove_friend(&self, user_id: &str, friend_id: &str) -> Result<()> {
+    // 把用户加入群聊（已是成员时不做改动），返回是否新加入
+    pub fn add_group_member(&self, group_id: &str, user_id: &str, role: &str) -> Result<bool> {
         let conn = self.0.lock().unwrap();
-        
-        // 删除双向好友关系
-        conn.execute(
-            "DELETE FROM friendships WHERE user_id = ? AND friend_id = ?",
-            params![user_id, friend_id],
+        let joined_at = unix_now();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO group_members (id, group_id, user_id, joined_at, role) 
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![Uuid::new_v4().to_string(), group_id, user_id, joined_at, role],
         )?;
-        
-        conn.execute(
-            "DELETE FROM friendships WHERE user_id = ? AND friend_id = ?",
-            params![friend_id, user_id],
+        self.1.invalidate_group(group_id);
+        Ok(inserted > 0)
+    }
+
+    // 群聊所属的工作区，群不存在或已删除时为 None
+    pub fn group_workspace(&self, group_id: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row("SELECT workspace_id FROM groups WHERE id = ?1 AND deleted_at IS NULL", params![group_id], |row| row.get(0))
+            .optional()
+    }
+
+    // 获取群成员列表（优先读缓存）
+    pub fn get_group_members(&self, group_id: &str) -> Result<Vec<GroupMember>> {
+        let conn = self.0.lock().unwrap();
+        if let Some(members) = self.1.group_members.get(group_id) {
+            return Ok(members);
+        }
+        let mut stmt = conn.prepare(
+            "SELECT id, group_id, user_id, joined_at, role, muted_until FROM group_members WHERE group_id = ?"
         )?;
-        
-        Ok(())
+
+        let members: Vec<GroupMember> = stmt.query_map([group_id], |row| {
+            Ok(GroupMember {
+                id: row.get(0)?,
+                group_id: row.get(1)?,
+                user_id: row.get(2)?,
+                joined_at: row.get(3)?,
+                role: row.get(4)?,
+                muted_until: row.get(5)?,
+            })
+        })?
+        .filter_map(Result::ok)
+        .collect();
+
+        self.1.group_members.insert(group_id.to_string(), members.clone());
+        Ok(members)
+    }
+
+    // 群成员的一页，按用户ID排序，只返回 after 之后的成员；search 不为空时按用户名或显示名称包含该词筛选（不区分大小写）
+    pub fn group_members_page(&self, group_id: &str, after: &str, search: &str, limit: i64) -> Result<Vec<GroupMemberEntry>> {
+        let pattern = contains_pattern(search);
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(queries::GROUP_MEMBERS_PAGE)?;
+        stmt.query_map(params![group_id, after, pattern, limit], |row| {
+            Ok(GroupMemberEntry {
+                user_id: row.get(0)?,
+                role: row.get(1)?,
+                joined_at: row.get(2)?,
+                username: row.get(3)?,
+                display_name: row.get(4)?,
+                muted_until: row.get(5)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 检查用户是否为群成员（用于消息发送和路由判断）
+    pub fn is_group_member(&self, group_id: &str, user_id: &str) -> Result<bool> {
+        Ok(self.get_group_members(group_id)?.iter().any(|m| m.user_id == user_id))
     }
 
     // 更新用户头像URL
@@ -600,35 +966,119 @@ impl DbPool {
             "UPDATE users SET avatar_url = ? WHERE id = ?",
             params![avatar_url, user_id],
         )?;
+        self.1.invalidate_user(user_id);
         Ok(())
     }
 
     // 更新用户信息
     pub fn update_user_info(&self, user_id: &str, username: &str, email: &str) -> Result<()> {
-        let conn = self.0.lock().unwrap();
-        conn.execute(
-            "UPDATE users SET username = ?, email = ? WHERE id = ?",
-            params![username, email, user_id],
-        )?;
+        let normalized = usernames::normalize_username(username);
+        self.with_tx(|conn| {
+            ensure_username_available(conn, username, &normalized, Some(user_id))?;
+            ensure_email_available(conn, &self.2, email, Some(user_id))?;
+            let email = self.2.seal_email(email);
+            // 修改邮箱后需要重新验证
+            conn.execute(
+                "UPDATE users SET username = ?1, email = ?2, username_normalized = ?3,
+                     email_verified_at = CASE WHEN email = ?2 THEN email_verified_at END
+                 WHERE id = ?4",
+                params![username, email, normalized, user_id],
+            )?;
+            Ok(())
+        })?;
+        self.1.invalidate_user(user_id);
         Ok(())
     }
 
-    // 根据ID获取用户
+    // 根据ID获取用户（优先读缓存）
     pub fn get_user_by_id(&self, user_id: &str) -> Result<User> {
         let conn = self.0.lock().unwrap();
-        conn.query_row(
-            "SELECT id, username, email, password_hash, created_at, avatar_url FROM users WHERE id = ?",
+        if let Some(user) = self.1.users.get(user_id) {
+            return Ok(user);
+        }
+        let user = conn.query_row(
+            "SELECT id, username, email, password_hash, created_at, avatar_url FROM users WHERE id = ? AND deleted_at IS NULL",
             [user_id],
             |row| {
                 Ok(User {
                     id: row.get(0)?,
                     username: row.get(1)?,
-                    email: row.get(2)?,
+                    email: self.2.open_email(row.get(2)?, 2)?,
                     password_hash: row.get(3)?,
                     created_at: row.get(4)?,
                     avatar_url: row.get(5)?,
                 })
             },
-        )
+        )?;
+        self.1.users.insert(user_id.to_string(), user.clone());
+        Ok(user)
     }
-}
\ No newline at end of file
+}
+
+// 用户名（原样或归一化后）被其他用户占用时返回“用户名已存在”
+fn ensure_username_available(conn: &Connection, username: &str, normalized: &str, except_user: Option<&str>) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE (username = ?1 OR username_normalized = ?2) AND id IS NOT ?3)",
+        params![username, normalized, except_user],
+        |row| row.get(0),
+    )?;
+
+    if exists {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(0),
+            Some("用户名已存在".to_string())
+        ));
+    }
+    Ok(())
+}
+
+// 邮箱被其他用户占用时返回“邮箱已被使用”
+//
+// 开启邮箱加密后同时比较密文和明文，开启前保存的明文邮箱也算占用
+fn ensure_email_available(conn: &Connection, keys: &StorageKeys, email: &str, except_user: Option<&str>) -> Result<()> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE email IN (?1, ?2) AND id IS NOT ?3)",
+        params![keys.seal_email(email), email, except_user],
+        |row| row.get(0),
+    )?;
+
+    if exists {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(0),
+            Some("邮箱已被使用".to_string())
+        ));
+    }
+    Ok(())
+}
+
+// 在事务中创建用户（register_user 和按工作区注册共用）
+fn insert_user(conn: &Connection, keys: &StorageKeys, username: &str, email: &str, password_hash: &str) -> Result<User> {
+    // 检查用户名是否已存在（归一化后相同也算）
+    let normalized = usernames::normalize_username(username);
+    ensure_username_available(conn, username, &normalized, None)?;
+    if !email.is_empty() {
+        ensure_email_available(conn, keys, email, None)?;
+    }
+
+    // 插入数据库
+    let user_id = Uuid::new_v4().to_string();
+    let created_at = unix_now();
+    // 未填写邮箱时生成唯一占位邮箱（避免使用空字符串导致 UNIQUE 约束冲突）
+    let email = if email.is_empty() { format!("{}@local", user_id) } else { email.to_string() };
+
+    conn.execute(
+        "INSERT INTO users (id, username, email, password_hash, created_at, username_normalized) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![user_id, username, keys.seal_email(&email), password_hash, created_at, normalized],
+    )?;
+
+    // 返回新用户（不含敏感信息）
+    Ok(User {
+        id: user_id,
+        username: username.to_string(),
+        email,
+        password_hash: password_hash.to_string(),
+        created_at,
+        avatar_url: String::new(),
+    })
+}
@@ -0,0 +1,72 @@
+use rusqlite::{params, vtab::array::Array, Connection, Result};
+
+use super::{queries, DbPool, Message};
+
+// 发件箱中等待接收者确认的实时推送
+#[derive(Debug)]
+pub struct OutboxEntry {
+    pub user_id: String,           // 接收推送的用户
+    pub attempts: i64,             // 已重发的次数（不含保存后的第一次推送）
+    pub message: Message,
+}
+
+// 登记一条待确认的实时推送，需要与插入消息在同一事务中调用：
+// 消息提交后、推送发出前服务器崩溃时，重启后仍能从发件箱重发
+pub(crate) fn enqueue(conn: &Connection, message_id: &str, user_id: &str, now: i64) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR IGNORE INTO ws_outbox (message_id, user_id, queued_at, attempts) VALUES (?1, ?2, ?3, 0)",
+    )?.execute(params![message_id, user_id, now])?;
+    Ok(())
+}
+
+// 消息已送达（或已读），删除对应的待确认推送；message_ids 为 rarray 绑定的消息ID数组
+pub(crate) fn acknowledge(conn: &Connection, message_ids: &Array) -> Result<()> {
+    conn.prepare_cached("DELETE FROM ws_outbox WHERE message_id IN rarray(?)")?.execute([message_ids])?;
+    Ok(())
+}
+
+impl DbPool {
+    // 上次推送早于 before 的待确认推送，已删除的消息不再重发
+    pub fn due_outbox(&self, before: i64, limit: i64) -> Result<Vec<OutboxEntry>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(queries::OUTBOX_DUE)?;
+        let entries = stmt.query_map(params![before, limit], |row| {
+            Ok(OutboxEntry {
+                message: self.message_from_row(row)?,
+                user_id: row.get(queries::MESSAGE_COLUMN_COUNT)?,
+                attempts: row.get(queries::MESSAGE_COLUMN_COUNT + 1)?,
+            })
+        })?.collect::<Result<Vec<_>>>()?;
+        Ok(entries)
+    }
+
+    // 记下一次重发；接收者不在线时 counted 为 false，只推迟下次检查，不计入次数
+    pub fn record_outbox_attempt(&self, message_id: &str, user_id: &str, counted: bool, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "UPDATE ws_outbox SET last_attempt_at = ?3, attempts = attempts + ?4 WHERE message_id = ?1 AND user_id = ?2",
+            params![message_id, user_id, now, counted as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_outbox_entry(&self, message_id: &str, user_id: &str) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute("DELETE FROM ws_outbox WHERE message_id = ?1 AND user_id = ?2", params![message_id, user_id])?;
+        Ok(())
+    }
+
+    // 删除登记早于 queued_before 的记录（客户端重连后通过同步接口补齐），返回删除的条数
+    pub fn prune_outbox(&self, queued_before: i64) -> Result<usize> {
+        let conn = self.0.lock().unwrap();
+        conn.execute("DELETE FROM ws_outbox WHERE queued_at < ?", [queued_before])
+    }
+
+    // 用户尚未确认的推送对应的消息ID
+    pub fn pending_outbox(&self, user_id: &str) -> Result<Vec<String>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT message_id FROM ws_outbox WHERE user_id = ? ORDER BY queued_at, message_id")?;
+        let ids = stmt.query_map([user_id], |row| row.get(0))?.collect::<Result<Vec<_>>>()?;
+        Ok(ids)
+    }
+}
@@ -0,0 +1,70 @@
+use rusqlite::{params, Result};
+use serde::Serialize;
+
+use super::DbPool;
+
+// 一次显示名称修改，名称为空表示清除了显示名称
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DisplayNameChange {
+    pub old_name: Option<String>,
+    pub new_name: Option<String>,
+    pub changed_at: i64,
+}
+
+impl DbPool {
+    // 用户的显示名称，用户不存在或已删除时返回 QueryReturnedNoRows
+    pub fn display_name(&self, user_id: &str) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT display_name FROM users WHERE id = ? AND deleted_at IS NULL",
+            [user_id],
+            |row| row.get(0),
+        )
+    }
+
+    // 最近一次修改显示名称的时间，从未修改过时返回 None
+    pub fn last_display_name_change(&self, user_id: &str) -> Result<Option<i64>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT MAX(changed_at) FROM display_name_history WHERE user_id = ?",
+            [user_id],
+            |row| row.get(0),
+        )
+    }
+
+    // 修改显示名称并在同一事务中记录修改历史，返回修改前的名称；名称没有变化时不记录
+    pub fn set_display_name(&self, user_id: &str, display_name: Option<&str>, now: i64) -> Result<Option<String>> {
+        self.with_tx(|conn| {
+            let old_name: Option<String> = conn.query_row(
+                "SELECT display_name FROM users WHERE id = ? AND deleted_at IS NULL",
+                [user_id],
+                |row| row.get(0),
+            )?;
+            if old_name.as_deref() != display_name {
+                conn.execute("UPDATE users SET display_name = ?1 WHERE id = ?2", params![display_name, user_id])?;
+                conn.execute(
+                    "INSERT INTO display_name_history (user_id, old_name, new_name, changed_at) VALUES (?1, ?2, ?3, ?4)",
+                    params![user_id, old_name, display_name, now],
+                )?;
+            }
+            Ok(old_name)
+        })
+    }
+
+    // 显示名称的修改历史，最近的在前
+    pub fn display_name_history(&self, user_id: &str, limit: i64) -> Result<Vec<DisplayNameChange>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT old_name, new_name, changed_at FROM display_name_history
+             WHERE user_id = ?1 ORDER BY changed_at DESC, id DESC LIMIT ?2",
+        )?;
+        stmt.query_map(params![user_id, limit], |row| {
+            Ok(DisplayNameChange {
+                old_name: row.get(0)?,
+                new_name: row.get(1)?,
+                changed_at: row.get(2)?,
+            })
+        })?
+        .collect()
+    }
+}
@@ -0,0 +1,53 @@
+use rusqlite::{params, params_from_iter, Result};
+
+use super::DbPool;
+
+// 用户的一个推送令牌
+#[derive(Debug, Clone)]
+pub struct PushToken {
+    pub user_id: String,
+    pub platform: String,
+    pub token: String,
+}
+
+impl DbPool {
+    // 注册推送令牌，返回是否成功；令牌已属于其他用户时不转移（设备换了登录账号时先由原用户注销）
+    pub fn register_push_token(&self, user_id: &str, platform: &str, token: &str, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let changed = conn.execute(
+            "INSERT INTO push_tokens (token, user_id, platform, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT(token) DO UPDATE SET platform = excluded.platform, updated_at = excluded.updated_at
+             WHERE push_tokens.user_id = excluded.user_id",
+            params![token, user_id, platform, now],
+        )?;
+        Ok(changed > 0)
+    }
+
+    // 注销用户自己的推送令牌，返回是否存在
+    pub fn remove_push_token(&self, user_id: &str, token: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let removed = conn.execute("DELETE FROM push_tokens WHERE token = ? AND user_id = ?", [token, user_id])?;
+        Ok(removed > 0)
+    }
+
+    // 获取一批用户的全部推送令牌
+    pub fn push_tokens_for(&self, user_ids: &[String]) -> Result<Vec<PushToken>> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let conn = self.0.lock().unwrap();
+        let placeholders = vec!["?"; user_ids.len()].join(", ");
+        let mut stmt = conn.prepare(&format!(
+            "SELECT user_id, platform, token FROM push_tokens WHERE user_id IN ({})",
+            placeholders
+        ))?;
+        stmt.query_map(params_from_iter(user_ids), |row| {
+            Ok(PushToken {
+                user_id: row.get(0)?,
+                platform: row.get(1)?,
+                token: row.get(2)?,
+            })
+        })?
+        .collect()
+    }
+}
@@ -0,0 +1,171 @@
+// 热点查询语句，集中定义以便复用和做查询计划回归测试
+
+// 消息表查询列（与 Message 结构体字段顺序一致）；迁移前保存的消息没有 sent_at，以 created_at 代替
+macro_rules! message_columns {
+    () => {
+        "id, sender_id, receiver_id, content, message_type, created_at, status, is_read, workspace_id, COALESCE(sent_at, created_at), delivered_at, read_at, entities, seq"
+    };
+}
+
+// message_columns 的列数，其后追加的列从这个下标开始
+pub const MESSAGE_COLUMN_COUNT: usize = 14;
+
+// 会话内 seq >= ?3 的消息（含已删除的，最后一列为 deleted_at），走 idx_messages_conversation_seq
+pub const MESSAGES_FROM_SEQ: &str = concat!(
+    "SELECT ", message_columns!(), ", deleted_at FROM messages
+     WHERE workspace_id = ?1 AND conversation_id = ?2 AND seq >= ?3
+     ORDER BY seq ASC
+     LIMIT ?4"
+);
+
+// 按发送者和客户端临时ID查找消息，走 idx_messages_client_id
+pub const MESSAGE_BY_CLIENT_ID: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages WHERE sender_id = ?1 AND client_message_id = ?2"
+);
+
+// 插入一条消息（发送消息的热点路径，语句缓存在连接上）
+pub const INSERT_MESSAGE: &str =
+    "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at, status, is_read, workspace_id, client_message_id, sent_at, conversation_id, seq, entities)
+     VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'sent', 0, ?7, ?8, ?6, ?9, ?10, ?11)";
+
+// 按用户名取登录校验所需的 (用户ID, 用户名, 密码哈希)；没有密码的账号（如系统通知账号）不能登录
+pub const USER_CREDENTIALS: &str =
+    "SELECT id, username, password_hash FROM users WHERE username = ? AND deleted_at IS NULL AND password_hash != ''";
+
+// 把一批消息（?1 为 rarray 绑定的消息ID数组）标为已读，第一次标记时记录已读时间（没有送达时间的一并补上）
+pub const MARK_MESSAGES_READ: &str =
+    "UPDATE messages SET is_read = 1, status = 'read',
+         delivered_at = COALESCE(delivered_at, ?2), read_at = COALESCE(read_at, ?2)
+     WHERE id IN rarray(?1)";
+
+// 把一批消息标为已送达，第一次标记时记录送达时间；已读的消息不会退回送达状态
+pub const MARK_MESSAGES_DELIVERED: &str =
+    "UPDATE messages SET status = CASE status WHEN 'read' THEN 'read' ELSE 'delivered' END,
+         delivered_at = COALESCE(delivered_at, ?2)
+     WHERE id IN rarray(?1)";
+
+// 用户在工作区内的未读私聊消息，走 idx_messages_receiver_unread
+pub const UNREAD_MESSAGES: &str = concat!(
+    "SELECT ", message_columns!(), "
+     FROM messages
+     WHERE receiver_id = ?1 AND is_read = 0 AND message_type = 'private' AND workspace_id = ?2 AND deleted_at IS NULL
+     ORDER BY created_at ASC"
+);
+
+// 工作区内的增量同步：拆成收/发两路以分别命中索引，自己发给自己的消息只保留一份
+pub const SYNC_MESSAGES: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages
+     WHERE receiver_id = ?1 AND created_at > ?2 AND workspace_id = ?4 AND deleted_at IS NULL
+     UNION ALL
+     SELECT ", message_columns!(), " FROM messages
+     WHERE sender_id = ?1 AND created_at > ?2 AND receiver_id != ?1 AND workspace_id = ?4 AND deleted_at IS NULL
+     ORDER BY created_at ASC
+     LIMIT ?3"
+);
+
+// 双人会话历史（早于游标 (?3 时间戳, ?6 消息ID)，倒序分页），走 idx_messages_conversation；
+// 时间戳相同的消息按ID区分，?6 为空字符串时只按时间戳
+pub const CONVERSATION_HISTORY: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages
+     WHERE sender_id = ?1 AND receiver_id = ?2 AND created_at <= ?3 AND (created_at < ?3 OR id < ?6) AND workspace_id = ?5 AND deleted_at IS NULL
+     UNION ALL
+     SELECT ", message_columns!(), " FROM messages
+     WHERE sender_id = ?2 AND receiver_id = ?1 AND created_at <= ?3 AND (created_at < ?3 OR id < ?6) AND ?1 != ?2 AND workspace_id = ?5 AND deleted_at IS NULL
+     ORDER BY created_at DESC, id DESC
+     LIMIT ?4"
+);
+
+// 用户在工作区内的会话列表：私聊对象和所在群聊，按最后一条消息时间倒序；私聊计入未读数；
+// 分页时只返回排在游标 (?4 时间戳, ?5 会话对象ID) 之后的会话
+pub const CONVERSATIONS: &str = "
+    SELECT peer_id, conversation_type, MAX(created_at) AS last_message_at, SUM(unread) AS unread_count
+    FROM (
+        SELECT receiver_id AS peer_id, 'private' AS conversation_type, created_at, 0 AS unread FROM messages
+        WHERE sender_id = ?1 AND message_type = 'private' AND workspace_id = ?3 AND deleted_at IS NULL
+        UNION ALL
+        SELECT sender_id, 'private', created_at, is_read = 0 FROM messages
+        WHERE receiver_id = ?1 AND sender_id != ?1 AND message_type = 'private' AND workspace_id = ?3 AND deleted_at IS NULL
+        UNION ALL
+        SELECT m.receiver_id, 'group', m.created_at, 0 FROM group_members g
+        JOIN messages m ON m.receiver_id = g.group_id AND m.message_type IN ('group', 'system') AND m.workspace_id = ?3 AND m.deleted_at IS NULL
+        WHERE g.user_id = ?1
+    )
+    GROUP BY peer_id, conversation_type
+    HAVING last_message_at < ?4 OR (last_message_at = ?4 AND peer_id < ?5)
+    ORDER BY last_message_at DESC, peer_id DESC
+    LIMIT ?2";
+
+// 群聊历史（早于游标 (?2 时间戳, ?5 消息ID)，倒序分页，含系统消息），走部分索引 idx_messages_group_created
+pub const GROUP_HISTORY: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages
+     WHERE receiver_id = ?1 AND message_type IN ('group', 'system') AND created_at <= ?2 AND (created_at < ?2 OR id < ?5) AND workspace_id = ?4 AND deleted_at IS NULL
+     ORDER BY created_at DESC, id DESC
+     LIMIT ?3"
+);
+
+// 上次推送早于 ?1 的待确认实时推送及其消息（末尾两列为接收者和已重发次数），已删除的消息不再重发
+pub const OUTBOX_DUE: &str = concat!(
+    "SELECT ", message_columns!(), ", ws_outbox.user_id, ws_outbox.attempts
+     FROM ws_outbox JOIN messages ON messages.id = ws_outbox.message_id
+     WHERE COALESCE(ws_outbox.last_attempt_at, ws_outbox.queued_at) < ?1 AND messages.deleted_at IS NULL
+     ORDER BY ws_outbox.queued_at
+     LIMIT ?2"
+);
+
+// 按ID查找未删除的消息
+pub const ACTIVE_MESSAGE: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages WHERE id = ?1 AND deleted_at IS NULL"
+);
+
+// 发送者名下未删除的消息（软删除前校验归属）
+pub const ACTIVE_MESSAGE_OF_SENDER: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages
+     WHERE id = ?1 AND sender_id = ?2 AND deleted_at IS NULL"
+);
+
+// 发送者名下在撤销窗口内删除的消息（deleted_at >= ?3）
+pub const DELETED_MESSAGE_OF_SENDER: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages
+     WHERE id = ?1 AND sender_id = ?2 AND deleted_at IS NOT NULL AND deleted_at >= ?3"
+);
+
+// 用户收发的全部消息（导出用），自己发给自己的消息只保留一份；同一秒内按 UUIDv7 排序
+pub const USER_MESSAGES: &str = concat!(
+    "SELECT ", message_columns!(), " FROM messages
+     WHERE receiver_id = ?1 AND deleted_at IS NULL
+     UNION ALL
+     SELECT ", message_columns!(), " FROM messages
+     WHERE sender_id = ?1 AND receiver_id != ?1 AND deleted_at IS NULL
+     ORDER BY created_at ASC, id ASC"
+);
+
+// 增量同步的墓碑：last_sync_time 之后被删除的、与用户相关的消息
+pub const SYNC_TOMBSTONES: &str = "
+    SELECT id, deleted_at FROM messages
+    WHERE receiver_id = ?1 AND deleted_at > ?2
+    UNION ALL
+    SELECT id, deleted_at FROM messages
+    WHERE sender_id = ?1 AND receiver_id != ?1 AND deleted_at > ?2
+    ORDER BY deleted_at ASC";
+
+// 群成员的一页，按用户ID排序、从游标 ?2 之后开始；?3 不为空时按用户名或显示名称包含 ?3 筛选（LIKE 模式，已转义）。
+// 走覆盖索引 idx_group_members_page，用户表按主键取名称
+pub const GROUP_MEMBERS_PAGE: &str = "
+    SELECT m.user_id, m.role, m.joined_at, u.username, u.display_name, m.muted_until
+    FROM group_members m JOIN users u ON u.id = m.user_id
+    WHERE m.group_id = ?1 AND m.user_id > ?2 AND u.deleted_at IS NULL
+      AND (?3 = '' OR u.username LIKE ?3 ESCAPE '\\' OR u.display_name LIKE ?3 ESCAPE '\\')
+    ORDER BY m.user_id
+    LIMIT ?4";
+
+// 群目录的一页：工作区内公开且未删除的群，按创建时间倒序、从游标 (?3, ?4) 之后开始；?2 不为空时按群名包含 ?2 筛选（LIKE 模式，已转义）。
+// 走索引 idx_groups_directory
+pub const GROUP_DIRECTORY: &str = "
+    SELECT g.id, g.name, g.created_at, g.join_approval,
+           (SELECT COUNT(*) FROM group_members m WHERE m.group_id = g.id) AS member_count
+    FROM groups g
+    WHERE g.workspace_id = ?1 AND g.visibility = 'public' AND g.deleted_at IS NULL
+      AND (g.created_at < ?3 OR (g.created_at = ?3 AND g.id < ?4))
+      AND (?2 = '' OR g.name LIKE ?2 ESCAPE '\\')
+    ORDER BY g.created_at DESC, g.id DESC
+    LIMIT ?5";
@@ -0,0 +1,120 @@
+use rusqlite::{params, OptionalExtension, Result, Row};
+use serde::Serialize;
+
+use super::DbPool;
+use crate::config::settings::{AttachmentSettings, QuotaSettings};
+
+// 生效的配额，0 表示不限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Quota {
+    pub max_message_chars: i64,    // 单条消息的最大字符数
+    pub max_attachment_bytes: i64, // 单个附件的最大字节数
+    pub max_storage_bytes: i64,    // 附件总字节数
+}
+
+impl Quota {
+    // 配置文件中的默认配额
+    pub fn defaults(quotas: &QuotaSettings, attachments: &AttachmentSettings) -> Self {
+        Self {
+            max_message_chars: quotas.max_message_chars as i64,
+            max_attachment_bytes: attachments.max_bytes as i64,
+            max_storage_bytes: quotas.max_storage_bytes as i64,
+        }
+    }
+}
+
+// 管理员为单个用户或群设置的配额覆盖，为空的项沿用默认值
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct QuotaOverride {
+    pub subject_id: String,        // 用户ID或群ID
+    pub max_message_chars: Option<i64>,
+    pub max_attachment_bytes: Option<i64>,
+    pub max_storage_bytes: Option<i64>,
+    pub updated_at: i64,
+}
+
+impl QuotaOverride {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            subject_id: row.get(0)?,
+            max_message_chars: row.get(1)?,
+            max_attachment_bytes: row.get(2)?,
+            max_storage_bytes: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    }
+}
+
+const OVERRIDE_COLUMNS: &str = "subject_id, max_message_chars, max_attachment_bytes, max_storage_bytes, updated_at";
+
+// 用户的用量
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub message_count: i64,        // 发出的未删除消息数
+    pub attachment_count: i64,
+    pub attachment_bytes: i64,     // 计入存储配额
+}
+
+impl DbPool {
+    // 用户的生效配额：用户的覆盖优先于默认值；发往群聊时群的覆盖再优先决定消息长度
+    pub fn quota_for(&self, defaults: Quota, user_id: &str, group_id: Option<&str>) -> Result<Quota> {
+        let mut quota = defaults;
+        if let Some(user) = self.quota_override(user_id)? {
+            quota.max_message_chars = user.max_message_chars.unwrap_or(quota.max_message_chars);
+            quota.max_attachment_bytes = user.max_attachment_bytes.unwrap_or(quota.max_attachment_bytes);
+            quota.max_storage_bytes = user.max_storage_bytes.unwrap_or(quota.max_storage_bytes);
+        }
+        if let Some(group) = group_id.map(|id| self.quota_override(id)).transpose()?.flatten() {
+            quota.max_message_chars = group.max_message_chars.unwrap_or(quota.max_message_chars);
+        }
+        Ok(quota)
+    }
+
+    pub fn quota_override(&self, subject_id: &str) -> Result<Option<QuotaOverride>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM quota_overrides WHERE subject_id = ?", OVERRIDE_COLUMNS),
+            [subject_id],
+            QuotaOverride::from_row,
+        ).optional()
+    }
+
+    pub fn list_quota_overrides(&self) -> Result<Vec<QuotaOverride>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM quota_overrides ORDER BY subject_id", OVERRIDE_COLUMNS))?;
+        let overrides = stmt.query_map([], QuotaOverride::from_row)?.collect::<Result<Vec<_>>>()?;
+        Ok(overrides)
+    }
+
+    // 设置（整体替换）用户或群的配额覆盖
+    pub fn set_quota_override(&self, quota: &QuotaOverride) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO quota_overrides (subject_id, max_message_chars, max_attachment_bytes, max_storage_bytes, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![quota.subject_id, quota.max_message_chars, quota.max_attachment_bytes, quota.max_storage_bytes, quota.updated_at],
+        )?;
+        Ok(())
+    }
+
+    // 删除配额覆盖，返回是否存在
+    pub fn remove_quota_override(&self, subject_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute("DELETE FROM quota_overrides WHERE subject_id = ?", [subject_id])? > 0)
+    }
+
+    pub fn storage_usage(&self, user_id: &str) -> Result<Usage> {
+        let conn = self.0.lock().unwrap();
+        let (attachment_count, attachment_bytes) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM attachments WHERE uploader_id = ?",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let message_count = conn.query_row(
+            "SELECT COUNT(*) FROM messages WHERE sender_id = ? AND deleted_at IS NULL",
+            [user_id],
+            |row| row.get(0),
+        )?;
+        Ok(Usage { message_count, attachment_count, attachment_bytes })
+    }
+}
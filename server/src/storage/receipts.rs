@@ -0,0 +1,102 @@
+//! 群消息的送达和已读回执：每个成员各一行，私聊消息仍记录在消息本身的 delivered_at 和 read_at 上
+//!
+//! 成员的 `read_receipt_privacy` 设置不允许查看者看到其已读时，该成员只算作已送达
+
+use rusqlite::{params, Result};
+use serde::Serialize;
+
+use super::user_settings::SETTING_READ_RECEIPT_PRIVACY;
+use super::DbPool;
+
+/// 群消息的回执汇总：人数和每类的第一页成员
+#[derive(Debug, Clone, Serialize)]
+pub struct MessageReceipts {
+    pub member_count: i64,    // 发送者以外的当前群成员数
+    pub delivered_count: i64, // 已送达（含已读）的成员数
+    pub read_count: i64,      // 查看者能看到的已读成员数
+    pub read_by: Vec<ReceiptUser>,      // 按已读时间排序
+    pub delivered_to: Vec<ReceiptUser>, // 已送达但未读（或不公开已读）的成员，按送达时间排序
+}
+
+/// 回执中的一个成员，at 为已读或送达时间
+#[derive(Debug, Clone, Serialize)]
+pub struct ReceiptUser {
+    pub user_id: String,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub at: i64,
+}
+
+// 当前群成员的回执，已读时间按成员的隐私设置对查看者（?2）可见时才保留；
+// 与 DbPool::privacy_allows 相同：本人总是可见，未设置时所有人可见
+const VISIBLE_RECEIPTS: &str = "
+    WITH visible AS (
+        SELECT r.user_id, u.username, u.display_name, r.delivered_at,
+               CASE WHEN r.user_id = ?2 OR COALESCE(s.value, 'everyone') = 'everyone'
+                         OR (s.value = 'friends' AND EXISTS (
+                             SELECT 1 FROM friendships f
+                             WHERE f.user_id = r.user_id AND f.friend_id = ?2 AND f.status = 'accepted'))
+                    THEN r.read_at END AS read_at
+        FROM message_receipts r
+        JOIN messages m ON m.id = r.message_id
+        JOIN group_members g ON g.group_id = m.receiver_id AND g.user_id = r.user_id
+        JOIN users u ON u.id = r.user_id
+        LEFT JOIN user_settings s ON s.user_id = r.user_id AND s.key = ?3
+        WHERE r.message_id = ?1
+    )";
+
+impl DbPool {
+    // 记录群成员对群消息的送达（read 为 true 时同时记录已读），第一次记录的时间不再改变；
+    // 不是群消息、已删除、发送者本人或不在群里的成员直接忽略
+    pub fn record_group_receipts(&self, user_id: &str, message_ids: &[String], read: bool, now: i64) -> Result<()> {
+        self.with_tx(|conn| {
+            for message_id in message_ids {
+                conn.execute(
+                    "INSERT INTO message_receipts (message_id, user_id, delivered_at, read_at)
+                     SELECT m.id, ?2, ?3, CASE WHEN ?4 THEN ?3 END
+                     FROM messages m
+                     JOIN group_members g ON g.group_id = m.receiver_id AND g.user_id = ?2
+                     WHERE m.id = ?1 AND m.message_type = 'group' AND m.sender_id != ?2 AND m.deleted_at IS NULL
+                     ON CONFLICT(message_id, user_id) DO UPDATE SET read_at = COALESCE(read_at, excluded.read_at)",
+                    params![message_id, user_id, now, read],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    // 群消息对 viewer 的回执汇总，每类最多返回 limit 个成员
+    pub fn group_message_receipts(&self, message_id: &str, group_id: &str, sender_id: &str, viewer: &str, limit: i64) -> Result<MessageReceipts> {
+        let conn = self.0.lock().unwrap();
+        let member_count: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM group_members WHERE group_id = ?1 AND user_id != ?2",
+            params![group_id, sender_id],
+            |row| row.get(0),
+        )?;
+        let (delivered_count, read_count): (i64, i64) = conn.query_row(
+            &format!("{VISIBLE_RECEIPTS} SELECT COUNT(*), COUNT(read_at) FROM visible"),
+            params![message_id, viewer, SETTING_READ_RECEIPT_PRIVACY],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        // 一类成员的第一页，column 为排序用的时间
+        let page = |column: &str, condition: &str| -> Result<Vec<ReceiptUser>> {
+            let mut stmt = conn.prepare(&format!(
+                "{VISIBLE_RECEIPTS} SELECT user_id, username, display_name, {column} FROM visible
+                 WHERE {condition} ORDER BY {column}, user_id LIMIT ?4"
+            ))?;
+            stmt.query_map(params![message_id, viewer, SETTING_READ_RECEIPT_PRIVACY, limit], |row| Ok(ReceiptUser {
+                user_id: row.get(0)?,
+                username: row.get(1)?,
+                display_name: row.get(2)?,
+                at: row.get(3)?,
+            }))?.collect()
+        };
+        Ok(MessageReceipts {
+            member_count,
+            delivered_count,
+            read_count,
+            read_by: page("read_at", "read_at IS NOT NULL")?,
+            delivered_to: page("delivered_at", "read_at IS NULL")?,
+        })
+    }
+}
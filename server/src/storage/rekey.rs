@@ -0,0 +1,131 @@
+use rusqlite::{params, types::Value, OptionalExtension, Result, Row};
+use serde::Serialize;
+
+use super::DbPool;
+use super::encryption::{entities_aad, Reseal};
+use crate::crypto::{conversation::conversation_id, SEALED_FIELD_PREFIX};
+
+// 一次重新加密任务的进度
+#[derive(Debug, Clone, Serialize)]
+pub struct RekeyRun {
+    pub job_id: String,
+    pub total: i64,            // 开始时的消息总数
+    pub processed: i64,        // 已检查的消息数
+    pub rewritten: i64,        // 重新加密的消息数
+    pub failed: i64,           // 无法解密、保持原样的消息数
+    pub emails_rewritten: i64, // 加密的明文邮箱数
+    pub started_at: i64,
+    pub updated_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+const REKEY_COLUMNS: &str = "job_id, total, processed, rewritten, failed, emails_rewritten, started_at, updated_at, finished_at";
+
+impl RekeyRun {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            job_id: row.get(0)?,
+            total: row.get(1)?,
+            processed: row.get(2)?,
+            rewritten: row.get(3)?,
+            failed: row.get(4)?,
+            emails_rewritten: row.get(5)?,
+            started_at: row.get(6)?,
+            updated_at: row.get(7)?,
+            finished_at: row.get(8)?,
+        })
+    }
+}
+
+impl DbPool {
+    // 登记重新加密任务；任务重试时保留已有进度
+    pub fn begin_rekey_run(&self, job_id: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT OR IGNORE INTO rekey_runs (job_id, total, started_at, updated_at)
+             SELECT ?1, COUNT(*), ?2, ?2 FROM messages",
+            params![job_id, now],
+        )?;
+        Ok(())
+    }
+
+    // 从上次的位置起按消息ID顺序处理一批消息，返回本批检查的消息数（0 表示已处理完）
+    pub fn rekey_messages_batch(&self, job_id: &str, batch_size: i64, now: i64) -> Result<usize> {
+        self.with_tx(|conn| {
+            let cursor: String = conn.query_row("SELECT cursor FROM rekey_runs WHERE job_id = ?", [job_id], |row| row.get(0))?;
+            let batch: Vec<(String, String, String, String, Value, Option<String>)> = {
+                let mut stmt = conn.prepare(
+                    "SELECT id, sender_id, receiver_id, message_type, content, entities FROM messages
+                     WHERE id > ?1 ORDER BY id LIMIT ?2",
+                )?;
+                stmt.query_map(params![cursor, batch_size], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?))
+                })?
+                .collect::<Result<_>>()?
+            };
+            let Some((last_id, ..)) = batch.last() else {
+                return Ok(0);
+            };
+
+            let (mut rewritten, mut failed) = (0, 0);
+            for (id, sender_id, receiver_id, message_type, content, entities) in &batch {
+                let conversation = conversation_id(message_type, sender_id, receiver_id);
+                // 格式区间随内容一起换用当前纪元的密钥，解不开的保持原样（读取时按纯文本显示）
+                if let Some(entities) = entities
+                    && let Reseal::Rewritten(sealed) = self.reseal_content(conn, &conversation, &entities_aad(id), entities, now)?
+                {
+                    conn.execute("UPDATE messages SET entities = ?2 WHERE id = ?1", params![id, sealed])?;
+                }
+                match self.reseal_message(conn, &conversation, id, content.into(), now)? {
+                    Reseal::Current => {}
+                    Reseal::Rewritten(sealed) => {
+                        conn.execute("UPDATE messages SET content = ?2 WHERE id = ?1", params![id, sealed])?;
+                        rewritten += 1;
+                    }
+                    Reseal::Failed => failed += 1,
+                }
+            }
+            conn.execute(
+                "UPDATE rekey_runs SET cursor = ?2, processed = processed + ?3, rewritten = rewritten + ?4,
+                        failed = failed + ?5, updated_at = ?6
+                 WHERE job_id = ?1",
+                params![job_id, last_id, batch.len() as i64, rewritten, failed, now],
+            )?;
+            Ok(batch.len())
+        })
+    }
+
+    // 加密开启邮箱加密前保存的明文邮箱，并结束任务
+    pub fn finish_rekey_run(&self, job_id: &str, now: i64) -> Result<()> {
+        self.with_tx(|conn| {
+            let plain: Vec<(String, String)> = {
+                let mut stmt = conn.prepare("SELECT id, email FROM users WHERE email NOT LIKE ? || '%'")?;
+                stmt.query_map([SEALED_FIELD_PREFIX], |row| Ok((row.get(0)?, row.get(1)?)))?
+                    .collect::<Result<_>>()?
+            };
+            let mut emails_rewritten = 0;
+            for (user_id, email) in plain {
+                let sealed = self.2.seal_email(&email);
+                if sealed != email {
+                    conn.execute("UPDATE users SET email = ?2 WHERE id = ?1", params![user_id, sealed])?;
+                    emails_rewritten += 1;
+                }
+            }
+            conn.execute(
+                "UPDATE rekey_runs SET emails_rewritten = ?2, updated_at = ?3, finished_at = ?3 WHERE job_id = ?1",
+                params![job_id, emails_rewritten, now],
+            )?;
+            Ok(())
+        })
+    }
+
+    // 最近一次重新加密任务的进度
+    pub fn latest_rekey_run(&self) -> Result<Option<RekeyRun>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM rekey_runs ORDER BY started_at DESC, job_id DESC LIMIT 1", REKEY_COLUMNS),
+            [],
+            RekeyRun::from_row,
+        ).optional()
+    }
+}
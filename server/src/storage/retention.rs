@@ -0,0 +1,208 @@
+use rusqlite::{params, Connection, Result};
+use serde::Serialize;
+
+use super::DbPool;
+use crate::config::settings::RetentionSettings;
+use crate::core::datetime::unix_now;
+
+// 已完成任务的保留天数
+const DONE_JOB_KEEP_DAYS: i64 = 7;
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+// 单个会话的保留期覆盖
+#[derive(Debug, Serialize)]
+pub struct RetentionOverride {
+    pub conversation_id: String,
+    pub message_days: i64,
+    pub updated_at: i64,
+}
+
+// 一次保留策略执行的结果
+#[derive(Debug, Default, Serialize)]
+pub struct RetentionReport {
+    pub messages_deleted: usize,
+    pub users_purged: usize,
+    pub groups_purged: usize,
+}
+
+// 查询一列ID，参数为清理期限
+fn select_ids(conn: &Connection, sql: &str, cutoff: i64) -> Result<Vec<String>> {
+    conn.prepare(sql)?.query_map([cutoff], |row| row.get(0))?.collect()
+}
+
+impl DbPool {
+    // 按保留策略删除过期消息：先处理默认规则（跳过有覆盖的会话），再逐个处理覆盖
+    pub fn apply_retention(&self, settings: &RetentionSettings, now: i64) -> Result<RetentionReport> {
+        // 被清除的群、成员有变化的群和被清除的用户，提交后从缓存中移除
+        let mut stale_groups = Vec::new();
+        let mut stale_users = Vec::new();
+        let report = self.with_tx(|conn| {
+            let mut report = RetentionReport::default();
+
+            if settings.message_days > 0 {
+                let cutoff = now - settings.message_days as i64 * SECS_PER_DAY;
+                report.messages_deleted += conn.execute(
+                    "DELETE FROM messages
+                     WHERE created_at < ?1
+                       AND receiver_id NOT IN (SELECT conversation_id FROM retention_overrides)",
+                    [cutoff],
+                )?;
+            }
+
+            report.messages_deleted += conn.execute(
+                "DELETE FROM messages
+                 WHERE EXISTS (
+                     SELECT 1 FROM retention_overrides o
+                     WHERE o.conversation_id = messages.receiver_id
+                       AND o.message_days > 0
+                       AND messages.created_at < ?1 - o.message_days * ?2
+                 )",
+                params![now, SECS_PER_DAY],
+            )?;
+
+            // 已完成的队列任务只保留一段时间供排查
+            conn.execute(
+                "DELETE FROM jobs WHERE status = 'done' AND updated_at < ?",
+                [now - DONE_JOB_KEEP_DAYS * SECS_PER_DAY],
+            )?;
+
+            conn.execute("DELETE FROM sessions WHERE expires_at < ?", [now])?;
+
+            // 彻底清除超过清理期限的软删除数据
+            if settings.purge_deleted_days > 0 {
+                let cutoff = now - settings.purge_deleted_days as i64 * SECS_PER_DAY;
+                report.messages_deleted += conn.execute(
+                    "DELETE FROM messages WHERE deleted_at IS NOT NULL AND deleted_at < ?",
+                    [cutoff],
+                )?;
+
+                // 已删除群聊的消息、保留期覆盖和群本身
+                const PURGED_GROUPS: &str = "SELECT id FROM groups WHERE deleted_at IS NOT NULL AND deleted_at < ?1";
+                stale_groups.extend(select_ids(conn, PURGED_GROUPS, cutoff)?);
+                report.messages_deleted += conn.execute(
+                    &format!("DELETE FROM messages WHERE receiver_id IN ({})", PURGED_GROUPS),
+                    [cutoff],
+                )?;
+                for (table, column) in [("retention_overrides", "conversation_id"), ("group_deletion_tokens", "group_id"), ("group_join_requests", "group_id")] {
+                    conn.execute(
+                        &format!("DELETE FROM {} WHERE {} IN ({})", table, column, PURGED_GROUPS),
+                        [cutoff],
+                    )?;
+                }
+                report.groups_purged += conn.execute(
+                    &format!("DELETE FROM groups WHERE id IN ({})", PURGED_GROUPS),
+                    [cutoff],
+                )?;
+
+                // 先清除被删除用户的关联数据，再删除用户本身
+                // 仍是群主的用户被群聊外键引用，暂不清除
+                const PURGED_USERS: &str = "SELECT id FROM users
+                    WHERE deleted_at IS NOT NULL AND deleted_at < ?1
+                      AND id NOT IN (SELECT creator_id FROM groups)";
+                stale_users.extend(select_ids(conn, PURGED_USERS, cutoff)?);
+                stale_groups.extend(select_ids(
+                    conn,
+                    &format!("SELECT DISTINCT group_id FROM group_members WHERE user_id IN ({})", PURGED_USERS),
+                    cutoff,
+                )?);
+                report.messages_deleted += conn.execute(
+                    &format!("DELETE FROM messages WHERE sender_id IN ({0}) OR receiver_id IN ({0})", PURGED_USERS),
+                    [cutoff],
+                )?;
+                conn.execute(
+                    &format!("DELETE FROM friendships WHERE user_id IN ({0}) OR friend_id IN ({0})", PURGED_USERS),
+                    [cutoff],
+                )?;
+                conn.execute(
+                    &format!("DELETE FROM friend_requests WHERE from_user_id IN ({0}) OR to_user_id IN ({0})", PURGED_USERS),
+                    [cutoff],
+                )?;
+                conn.execute(
+                    &format!("DELETE FROM group_members WHERE user_id IN ({})", PURGED_USERS),
+                    [cutoff],
+                )?;
+                conn.execute(
+                    &format!("DELETE FROM device_codes WHERE device_id IN (SELECT id FROM devices WHERE user_id IN ({}))", PURGED_USERS),
+                    [cutoff],
+                )?;
+                for table in ["push_tokens", "user_settings", "remote_users", "sessions", "workspace_members", "email_verifications", "identities", "devices", "account_settings", "display_name_history", "group_join_requests", "message_receipts"] {
+                    conn.execute(
+                        &format!("DELETE FROM {} WHERE user_id IN ({})", table, PURGED_USERS),
+                        [cutoff],
+                    )?;
+                }
+                report.users_purged += conn.execute(
+                    &format!("DELETE FROM users WHERE id IN ({})", PURGED_USERS),
+                    [cutoff],
+                )?;
+            }
+
+            // 原文已被删除的译文缓存和群消息回执
+            if report.messages_deleted > 0 {
+                conn.execute("DELETE FROM message_translations WHERE message_id NOT IN (SELECT id FROM messages)", [])?;
+                conn.execute("DELETE FROM message_receipts WHERE message_id NOT IN (SELECT id FROM messages)", [])?;
+            }
+
+            Ok(report)
+        })?;
+        for group_id in &stale_groups {
+            self.1.invalidate_group(group_id);
+        }
+        for user_id in &stale_users {
+            self.1.invalidate_user(user_id);
+        }
+        Ok(report)
+    }
+
+    // 设置会话的保留期覆盖（message_days 为 0 表示永久保留）
+    pub fn set_retention_override(&self, conversation_id: &str, message_days: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        let updated_at = unix_now();
+        conn.execute(
+            "INSERT INTO retention_overrides (conversation_id, message_days, updated_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT(conversation_id) DO UPDATE SET message_days = excluded.message_days, updated_at = excluded.updated_at",
+            params![conversation_id, message_days, updated_at],
+        )?;
+        Ok(())
+    }
+
+    // 删除会话的保留期覆盖，恢复使用默认规则
+    pub fn remove_retention_override(&self, conversation_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let removed = conn.execute(
+            "DELETE FROM retention_overrides WHERE conversation_id = ?",
+            [conversation_id],
+        )?;
+        Ok(removed > 0)
+    }
+
+    // 列出所有保留期覆盖
+    pub fn list_retention_overrides(&self) -> Result<Vec<RetentionOverride>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT conversation_id, message_days, updated_at FROM retention_overrides ORDER BY conversation_id"
+        )?;
+        let overrides = stmt.query_map([], |row| {
+            Ok(RetentionOverride {
+                conversation_id: row.get(0)?,
+                message_days: row.get(1)?,
+                updated_at: row.get(2)?,
+            })
+        })?
+        .filter_map(Result::ok)
+        .collect();
+        Ok(overrides)
+    }
+
+    // 整理数据库文件，回收删除数据占用的空间
+    pub fn vacuum(&self) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute_batch("VACUUM")
+    }
+
+    // 把 WAL 中的内容写回主文件并清空 WAL，关闭服务器前执行
+    pub fn checkpoint(&self) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")
+    }
+}
@@ -0,0 +1,28 @@
+use rusqlite::{params, Result};
+use std::collections::HashMap;
+
+use super::DbPool;
+
+impl DbPool {
+    // 获取管理员在运行时保存的全部设置（日志级别、功能开关等）
+    pub fn runtime_settings(&self) -> Result<HashMap<String, String>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT key, value FROM runtime_settings")?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    // 在一个事务中保存多项运行时设置
+    pub fn set_runtime_settings(&self, settings: &HashMap<String, String>, now: i64) -> Result<()> {
+        self.with_tx(|conn| {
+            for (key, value) in settings {
+                conn.execute(
+                    "INSERT INTO runtime_settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+                     ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+                    params![key, value, now],
+                )?;
+            }
+            Ok(())
+        })
+    }
+}
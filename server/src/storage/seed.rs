@@ -0,0 +1,174 @@
+use bcrypt::{hash, DEFAULT_COST};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::{params, Result};
+use uuid::{NoContext, Timestamp, Uuid};
+
+use super::DbPool;
+
+const SECS_PER_DAY: i64 = 24 * 60 * 60;
+
+// 演示消息内容
+const SAMPLE_CONTENTS: &[&str] = &[
+    "早上好！",
+    "今天的会议改到下午三点了",
+    "收到，马上处理",
+    "晚上一起吃饭吗？",
+    "这个问题我再看一下",
+    "哈哈哈哈",
+    "周末有什么安排？",
+    "文件已经发到群里了",
+    "好的，没问题",
+    "明天见 👋",
+];
+
+// 演示数据生成参数
+#[derive(Debug, Clone)]
+pub struct SeedOptions {
+    pub users: usize,
+    pub groups: usize,
+    pub group_size: usize,
+    pub friends_per_user: usize,
+    pub messages: usize,
+    pub days: i64,          // 消息时间分布在最近多少天内
+    pub prefix: String,     // 用户名前缀，生成 <prefix>_0001 这样的用户名
+    pub password: String,   // 所有演示用户共用的密码
+    pub seed: u64,          // 随机种子，相同参数生成相同的数据
+}
+
+impl Default for SeedOptions {
+    fn default() -> Self {
+        Self {
+            users: 50,
+            groups: 5,
+            group_size: 10,
+            friends_per_user: 5,
+            messages: 10_000,
+            days: 30,
+            prefix: "demo".into(),
+            password: "password".into(),
+            seed: 2025,
+        }
+    }
+}
+
+// 实际生成的数据量
+#[derive(Debug, Default)]
+pub struct SeedReport {
+    pub users: usize,
+    pub friendships: usize,
+    pub groups: usize,
+    pub messages: usize,
+}
+
+impl DbPool {
+    // 生成演示数据（单个事务，用户名冲突时整体回滚）
+    // 消息与正常发送走同一张表，开启 database.encrypt 时同样加密落盘
+    pub fn seed_demo_data(&self, options: &SeedOptions, now: i64) -> Result<SeedReport> {
+        // bcrypt 很慢，所有演示用户共用一个哈希
+        let password_hash = hash(&options.password, DEFAULT_COST)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let mut rng = StdRng::seed_from_u64(options.seed);
+
+        self.with_tx(|conn| {
+            let mut report = SeedReport::default();
+
+            let mut user_ids = Vec::with_capacity(options.users);
+            for i in 0..options.users {
+                let user_id = Uuid::new_v4().to_string();
+                conn.execute(
+                    "INSERT INTO users (id, username, email, password_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        user_id,
+                        format!("{}_{:04}", options.prefix, i + 1),
+                        format!("{}@local", user_id),
+                        password_hash,
+                        now - options.days * SECS_PER_DAY
+                    ],
+                )?;
+                user_ids.push(user_id);
+            }
+            report.users = user_ids.len();
+
+            // 好友关系：每个用户与环上之后的若干用户互为好友
+            let mut pairs = Vec::new();
+            let friends_per_user = options.friends_per_user.min(user_ids.len().saturating_sub(1));
+            for (i, user_id) in user_ids.iter().enumerate() {
+                for offset in 1..=friends_per_user {
+                    let friend_id = &user_ids[(i + offset) % user_ids.len()];
+                    // 环较小时 (a, b) 可能已经作为 (b, a) 加过
+                    let inserted = conn.execute(
+                        "INSERT OR IGNORE INTO friendships (id, user_id, friend_id, status, created_at) VALUES (?1, ?2, ?3, 'accepted', ?4)",
+                        params![Uuid::new_v4().to_string(), user_id, friend_id, now],
+                    )?;
+                    if inserted > 0 {
+                        conn.execute(
+                            "INSERT OR IGNORE INTO friendships (id, user_id, friend_id, status, created_at) VALUES (?1, ?2, ?3, 'accepted', ?4)",
+                            params![Uuid::new_v4().to_string(), friend_id, user_id, now],
+                        )?;
+                        pairs.push((user_id.clone(), friend_id.clone()));
+                    }
+                }
+            }
+            report.friendships = pairs.len();
+
+            // 群聊：随机挑选成员，第一个成员为群主
+            let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+            if !user_ids.is_empty() {
+                for i in 0..options.groups {
+                    let group_id = Uuid::new_v4().to_string();
+                    let size = options.group_size.clamp(1, user_ids.len());
+                    let members: Vec<String> = rand::seq::index::sample(&mut rng, user_ids.len(), size)
+                        .into_iter()
+                        .map(|idx| user_ids[idx].clone())
+                        .collect();
+                    conn.execute(
+                        "INSERT INTO groups (id, group_id, name, creator_id, created_at) VALUES (?1, ?1, ?2, ?3, ?4)",
+                        params![group_id, format!("{} 群 {}", options.prefix, i + 1), members[0], now],
+                    )?;
+                    for (idx, member) in members.iter().enumerate() {
+                        conn.execute(
+                            "INSERT INTO group_members (id, group_id, user_id, joined_at, role) VALUES (?1, ?2, ?3, ?4, ?5)",
+                            params![Uuid::new_v4().to_string(), group_id, member, now, if idx == 0 { "owner" } else { "member" }],
+                        )?;
+                    }
+                    self.1.invalidate_group(&group_id);
+                    groups.push((group_id, members));
+                }
+            }
+            report.groups = groups.len();
+
+            // 消息：有群聊时约三成发到群里，其余在好友之间私聊
+            if pairs.is_empty() && groups.is_empty() {
+                return Ok(report);
+            }
+            let mut stmt = conn.prepare(
+                "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at, status, is_read)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"
+            )?;
+            for _ in 0..options.messages {
+                let created_at = now - rng.gen_range(0..options.days.max(1) * SECS_PER_DAY);
+                let id = Uuid::new_v7(Timestamp::from_unix(NoContext, created_at as u64, rng.gen_range(0..1_000_000_000))).to_string();
+                let content = SAMPLE_CONTENTS[rng.gen_range(0..SAMPLE_CONTENTS.len())];
+                // 一天之前的消息视为已读
+                let is_read = now - created_at > SECS_PER_DAY;
+                let status = if is_read { "read" } else { "sent" };
+
+                if !groups.is_empty() && (pairs.is_empty() || rng.gen_bool(0.3)) {
+                    let (group_id, members) = &groups[rng.gen_range(0..groups.len())];
+                    let sender = &members[rng.gen_range(0..members.len())];
+                    stmt.execute(params![id, sender, group_id, content, "group", created_at, status, is_read])?;
+                } else {
+                    let (a, b) = &pairs[rng.gen_range(0..pairs.len())];
+                    let (sender, receiver) = if rng.gen_bool(0.5) { (a, b) } else { (b, a) };
+                    stmt.execute(params![id, sender, receiver, content, "private", created_at, status, is_read])?;
+                }
+                report.messages += 1;
+            }
+            // 消息时间是随机的，全部插入后再按时间顺序编号
+            super::sequence::assign_missing_seqs(conn)?;
+
+            Ok(report)
+        })
+    }
+}
@@ -0,0 +1,79 @@
+use rusqlite::{params, Connection, Result, Transaction};
+
+use super::{queries, DbPool, Message};
+
+// 会话内按 seq 读取的一段消息
+#[derive(Debug)]
+pub struct SeqRange {
+    pub messages: Vec<Message>,   // 未删除的消息，按 seq 升序
+    pub deleted_seqs: Vec<i64>,   // 范围内已删除消息的 seq，客户端据此确认这些位置不是缺口
+    pub last_seq: i64,            // 会话当前最大的 seq，没有消息时为 0
+}
+
+// 会话内下一个序号：每个工作区内的每个会话（私聊双方或群聊）从 1 开始连续递增，
+// 需要与插入消息在同一事务中调用，插入失败时序号一起回滚，不会留下缺口
+pub(crate) fn next_seq(conn: &Connection, workspace_id: &str, conversation_id: &str) -> Result<i64> {
+    conn.query_row(
+        "INSERT INTO conversation_seqs (workspace_id, conversation_id, last_seq) VALUES (?1, ?2, 1)
+         ON CONFLICT(workspace_id, conversation_id) DO UPDATE SET last_seq = last_seq + 1
+         RETURNING last_seq",
+        params![workspace_id, conversation_id],
+        |row| row.get(0),
+    )
+}
+
+// 给还没有序号的消息（迁移前保存的、演示数据生成的）按 (created_at, id) 顺序接着会话已有的序号编号
+pub(crate) fn assign_missing_seqs(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "UPDATE messages SET conversation_id = CASE
+                 WHEN message_type IN ('group', 'system') THEN 'group:' || receiver_id
+                 ELSE 'private:' || MIN(sender_id, receiver_id) || ':' || MAX(sender_id, receiver_id)
+             END
+         WHERE conversation_id IS NULL;
+
+         UPDATE messages SET seq = numbered.seq
+         FROM (
+             SELECT m.id, COALESCE(s.last_seq, 0)
+                 + ROW_NUMBER() OVER (PARTITION BY m.workspace_id, m.conversation_id ORDER BY m.created_at, m.id) AS seq
+             FROM messages m
+             LEFT JOIN conversation_seqs s ON s.workspace_id = m.workspace_id AND s.conversation_id = m.conversation_id
+             WHERE m.seq IS NULL
+         ) AS numbered
+         WHERE messages.id = numbered.id;
+
+         INSERT INTO conversation_seqs (workspace_id, conversation_id, last_seq)
+         SELECT workspace_id, conversation_id, MAX(seq) FROM messages WHERE seq IS NOT NULL GROUP BY workspace_id, conversation_id
+         ON CONFLICT(workspace_id, conversation_id) DO UPDATE SET last_seq = MAX(last_seq, excluded.last_seq);",
+    )
+}
+
+// 迁移 25 的数据迁移
+pub(crate) fn backfill_seqs(tx: &Transaction) -> Result<()> {
+    assign_missing_seqs(tx)
+}
+
+impl DbPool {
+    // 读取会话中 seq >= from_seq 的至多 limit 条消息（含已删除的占位），用于客户端发现缺口后补齐
+    pub fn messages_from_seq(&self, workspace_id: &str, conversation_id: &str, from_seq: i64, limit: i64) -> Result<SeqRange> {
+        let conn = self.0.lock().unwrap();
+        let last_seq = conn.query_row(
+            "SELECT COALESCE(MAX(last_seq), 0) FROM conversation_seqs WHERE workspace_id = ?1 AND conversation_id = ?2",
+            params![workspace_id, conversation_id],
+            |row| row.get(0),
+        )?;
+
+        let mut range = SeqRange { messages: Vec::new(), deleted_seqs: Vec::new(), last_seq };
+        let mut stmt = conn.prepare(queries::MESSAGES_FROM_SEQ)?;
+        let mut rows = stmt.query(params![workspace_id, conversation_id, from_seq, limit])?;
+        while let Some(row) = rows.next()? {
+            let deleted_at: Option<i64> = row.get(queries::MESSAGE_COLUMN_COUNT)?;
+            if deleted_at.is_some() {
+                // seq 是最后一列
+                range.deleted_seqs.push(row.get(queries::MESSAGE_COLUMN_COUNT - 1)?);
+            } else {
+                range.messages.push(self.message_from_row(row)?);
+            }
+        }
+        Ok(range)
+    }
+}
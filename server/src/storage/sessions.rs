@@ -0,0 +1,49 @@
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+
+use super::DbPool;
+
+// 会话令牌前缀，便于在日志和代码仓库中识别泄露的令牌
+const SESSION_TOKEN_PREFIX: &str = "yls_";
+
+// 数据库中只保存令牌的哈希
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+impl DbPool {
+    // 登录成功后签发会话令牌，明文只返回这一次
+    //
+    // 从已登记的设备登录时记录设备ID，移除设备时一并注销其会话
+    pub fn create_session(&self, user_id: &str, device_id: Option<&str>, ttl_secs: i64, now: i64) -> Result<String> {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token = format!("{}{}", SESSION_TOKEN_PREFIX, hex::encode(bytes));
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO sessions (token_hash, user_id, created_at, expires_at, device_id) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![hash_token(&token), user_id, now, now + ttl_secs, device_id],
+        )?;
+        Ok(token)
+    }
+
+    // 校验会话令牌，返回用户ID；令牌无效、已过期或用户已删除时返回 None
+    pub fn authenticate_session(&self, token: &str, now: i64) -> Result<Option<String>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT s.user_id FROM sessions s
+             JOIN users u ON u.id = s.user_id
+             WHERE s.token_hash = ?1 AND s.expires_at > ?2 AND u.deleted_at IS NULL",
+            params![hash_token(token), now],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    // 注销会话
+    pub fn revoke_session(&self, token: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute("DELETE FROM sessions WHERE token_hash = ?", [hash_token(token)])? > 0)
+    }
+}
@@ -0,0 +1,106 @@
+//! 慢查询日志：执行时间超过 database.slow_query_ms 的语句连同参数打印到日志（warn 级别），并计入 /metrics
+//!
+//! 参数取自 SQLite 展开后的语句。涉及密码、令牌、密钥、邮箱、消息内容等列的语句隐去全部字符串参数，
+//! 二进制参数（加密后的消息等）总是隐去，其余语句的字符串参数过长时截断
+
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use rusqlite::trace::{TraceEvent, TraceEventCodes};
+use rusqlite::Connection;
+
+use crate::config::log_level::{self, Level};
+
+// 语句中出现这些词时隐去全部字符串参数
+const SENSITIVE: &[&str] = &["password", "token", "secret", "key", "email", "content", "totp", "code"];
+// 非敏感语句中单个字符串参数保留的最大字符数
+const MAX_LITERAL_CHARS: usize = 64;
+
+// 阈值（微秒），0 表示不记录；SQLite 的回调只接受函数指针，阈值只能放在全局
+static THRESHOLD_MICROS: AtomicU64 = AtomicU64::new(0);
+static SLOW_QUERIES: AtomicU64 = AtomicU64::new(0);
+
+/// 设置慢查询阈值，为 0 时不记录
+pub fn set_threshold(threshold: Duration) {
+    THRESHOLD_MICROS.store(threshold.as_micros() as u64, Ordering::Relaxed);
+}
+
+// 在连接上注册语句耗时回调
+pub(crate) fn install(conn: &Connection) {
+    conn.trace_v2(TraceEventCodes::SQLITE_TRACE_PROFILE, Some(on_trace));
+}
+
+fn on_trace(event: TraceEvent<'_>) {
+    let TraceEvent::Profile(stmt, elapsed) = event else {
+        return;
+    };
+    let threshold = THRESHOLD_MICROS.load(Ordering::Relaxed);
+    if threshold == 0 || (elapsed.as_micros() as u64) < threshold {
+        return;
+    }
+    SLOW_QUERIES.fetch_add(1, Ordering::Relaxed);
+    if !log_level::enabled(Level::Warn) {
+        return;
+    }
+    let sql = stmt.sql();
+    let logged = match stmt.expanded_sql() {
+        Some(expanded) => scrub(&sql, &expanded),
+        None => sql.to_string(),
+    };
+    println!("慢查询 {} 毫秒: {}", elapsed.as_millis(), logged.split_whitespace().collect::<Vec<_>>().join(" "));
+}
+
+/// 隐去展开后语句中的敏感参数；sql 为带占位符的原始语句，用来判断是否涉及敏感列
+pub fn scrub(sql: &str, expanded: &str) -> String {
+    let lowered = sql.to_lowercase();
+    let sensitive = SENSITIVE.iter().any(|word| lowered.contains(word));
+    let mut out = String::with_capacity(expanded.len());
+    let mut chars = expanded.chars().peekable();
+    while let Some(c) = chars.next() {
+        let blob = (c == 'x' || c == 'X') && chars.peek() == Some(&'\'') && !ends_with_identifier(&out);
+        if c != '\'' && !blob {
+            out.push(c);
+            continue;
+        }
+        if blob {
+            chars.next();
+        }
+        // 读出整个字面量，'' 为转义的单引号
+        let mut literal = String::new();
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                    literal.push('\'');
+                    continue;
+                }
+                break;
+            }
+            literal.push(c);
+        }
+        if blob {
+            let _ = write!(out, "x'***'({} 字节)", literal.len() / 2);
+        } else if sensitive {
+            out.push_str("'***'");
+        } else if literal.chars().count() > MAX_LITERAL_CHARS {
+            let head: String = literal.chars().take(MAX_LITERAL_CHARS).collect();
+            let _ = write!(out, "'{}…'", head.replace('\'', "''"));
+        } else {
+            let _ = write!(out, "'{}'", literal.replace('\'', "''"));
+        }
+    }
+    out
+}
+
+// 前面紧跟字母、数字或下划线时 x 属于标识符，只有独立的 x'..' 才是 BLOB 字面量
+fn ends_with_identifier(out: &str) -> bool {
+    out.chars().last().is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// 以 OpenMetrics 文本格式输出慢查询次数，不含结尾的 `# EOF`
+pub fn render(out: &mut String) {
+    let _ = writeln!(out, "# TYPE yueling_slow_queries counter");
+    let _ = writeln!(out, "# HELP yueling_slow_queries Database statements slower than database.slow_query_ms.");
+    let _ = writeln!(out, "yueling_slow_queries_total {}", SLOW_QUERIES.load(Ordering::Relaxed));
+}
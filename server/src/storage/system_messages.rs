@@ -0,0 +1,73 @@
+use rusqlite::{params, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{DbPool, Message};
+use super::devices::SYSTEM_USER_ID;
+use super::workspaces::DEFAULT_WORKSPACE;
+use crate::core::datetime::unix_now;
+
+// 系统消息的 message_type；接收者为群ID时与群消息同属一个会话、共用序号，接收者为用户ID时是发给该用户的通知
+pub const SYSTEM_MESSAGE_TYPE: &str = "system";
+
+// 系统消息的结构化内容，以 JSON 保存在 content 中，event 字段区分类型，客户端按类型渲染
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SystemEvent {
+    // 用户加入群聊
+    MemberJoined { user_id: String },
+    // 群成员修改了用户名
+    NameChanged { user_id: String, old_name: String, new_name: String },
+    // 消息被置顶
+    MessagePinned { message_id: String, pinned_by: String },
+    // 发起了通话
+    CallStarted { call_id: String, started_by: String },
+    // 群主把群转让给了其他成员
+    OwnerChanged { old_owner: String, new_owner: String },
+    // 群主删除了群聊
+    GroupDeleted { deleted_by: String },
+    // 账号在新设备上登录（发给用户本人的安全提醒）
+    NewDeviceLogin { device_id: String, device_name: String, platform: String },
+    // 同一设备重复连接：replaced_old 为 true 时断开了该设备原有的连接，否则拒绝了新连接
+    DuplicateLogin { device_id: String, device_name: String, platform: String, replaced_old: bool },
+}
+
+impl SystemEvent {
+    // 触发事件的用户，作为系统消息的 sender_id
+    pub fn actor(&self) -> &str {
+        match self {
+            SystemEvent::MemberJoined { user_id } | SystemEvent::NameChanged { user_id, .. } => user_id,
+            SystemEvent::MessagePinned { pinned_by, .. } => pinned_by,
+            SystemEvent::CallStarted { started_by, .. } => started_by,
+            SystemEvent::OwnerChanged { old_owner, .. } => old_owner,
+            SystemEvent::GroupDeleted { deleted_by } => deleted_by,
+            SystemEvent::NewDeviceLogin { .. } | SystemEvent::DuplicateLogin { .. } => SYSTEM_USER_ID,
+        }
+    }
+}
+
+impl DbPool {
+    // 在群会话中保存一条系统消息，与普通消息一样分配序号、出现在群聊历史中
+    pub fn post_system_message(&self, workspace_id: &str, group_id: &str, event: &SystemEvent) -> Result<Message> {
+        let content = serde_json::to_string(event).expect("系统事件可以序列化");
+        self.send_message(workspace_id, event.actor(), group_id, &content, SYSTEM_MESSAGE_TYPE)
+    }
+
+    // 以系统账号给单个用户保存一条系统消息（如安全提醒），出现在该用户的增量同步中
+    pub fn post_user_system_message(&self, user_id: &str, event: &SystemEvent) -> Result<Message> {
+        self.ensure_system_user(unix_now())?;
+        let content = serde_json::to_string(event).expect("系统事件可以序列化");
+        self.send_message(DEFAULT_WORKSPACE, SYSTEM_USER_ID, user_id, &content, SYSTEM_MESSAGE_TYPE)
+    }
+
+    // 用户所在的群及其所属工作区（用户名变更时通知这些群）
+    pub fn groups_of_user(&self, user_id: &str) -> Result<Vec<(String, String)>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT g.id, g.workspace_id FROM group_members m JOIN groups g ON g.id = m.group_id
+             WHERE m.user_id = ?1 ORDER BY g.id",
+        )?;
+        let groups = stmt.query_map(params![user_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>>>()?;
+        Ok(groups)
+    }
+}
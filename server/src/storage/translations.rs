@@ -0,0 +1,51 @@
+use rusqlite::{params, OptionalExtension, Result};
+
+use super::{queries, DbPool, Message};
+use crate::crypto::conversation::conversation_id;
+
+// 译文加密时的附加数据，与原文的消息ID区分开
+fn translation_aad(message_id: &str, lang: &str) -> String {
+    format!("{}/{}", message_id, lang)
+}
+
+impl DbPool {
+    // 按ID查找未删除的消息
+    pub fn active_message(&self, message_id: &str) -> Result<Option<Message>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(queries::ACTIVE_MESSAGE, [message_id], |row| self.message_from_row(row))
+            .optional()
+    }
+
+    // 缓存的译文；解不开的密文（如更换主密钥后）视为没有缓存，重新翻译后覆盖
+    pub fn cached_translation(&self, message: &Message, lang: &str) -> Result<Option<String>> {
+        let stored: Option<String> = {
+            let conn = self.0.lock().unwrap();
+            conn.query_row(
+                "SELECT content FROM message_translations WHERE message_id = ?1 AND lang = ?2",
+                params![message.id, lang],
+                |row| row.get(0),
+            ).optional()?
+        };
+        let Some(stored) = stored else {
+            return Ok(None);
+        };
+        let Some(keys) = &self.2.messages else {
+            return Ok(Some(stored));
+        };
+        let conversation = conversation_id(&message.message_type, &message.sender_id, &message.receiver_id);
+        Ok(keys.open(&conversation, &translation_aad(&message.id, lang), &stored).ok())
+    }
+
+    // 保存译文，已有同一语言的译文时覆盖
+    pub fn store_translation(&self, message: &Message, lang: &str, content: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        let conversation = conversation_id(&message.message_type, &message.sender_id, &message.receiver_id);
+        let sealed = self.seal_content(&conn, &conversation, &translation_aad(&message.id, lang), content, now)?;
+        conn.execute(
+            "INSERT INTO message_translations (message_id, lang, content, created_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(message_id, lang) DO UPDATE SET content = excluded.content, created_at = excluded.created_at",
+            params![message.id, lang, sealed, now],
+        )?;
+        Ok(())
+    }
+}
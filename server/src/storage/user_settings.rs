@@ -0,0 +1,208 @@
+use rusqlite::{params, OptionalExtension, Result};
+use std::collections::HashMap;
+
+use super::DbPool;
+
+// 是否接收未读消息邮件摘要："on"（默认）或 "off"
+pub const SETTING_EMAIL_DIGEST: &str = "email_digest";
+// 免打扰时段："off"（默认）或本地时间 "HH:MM-HH:MM"，结束时间早于开始时间表示跨过午夜
+pub const SETTING_DND_SCHEDULE: &str = "dnd_schedule";
+// 用户所在时区相对 UTC 的偏移："+HH:MM" 或 "-HH:MM"，默认 "+00:00"
+pub const SETTING_UTC_OFFSET: &str = "utc_offset";
+// 谁可以给我发私聊消息和发起通话："everyone"（默认）、"friends" 或 "nobody"
+pub const SETTING_DM_PRIVACY: &str = "dm_privacy";
+// 谁可以看到我的在线状态和最后在线时间，取值同上
+pub const SETTING_LAST_SEEN_PRIVACY: &str = "last_seen_privacy";
+// 谁可以把我加入群聊，取值同上
+pub const SETTING_GROUP_ADD_PRIVACY: &str = "group_add_privacy";
+// 谁可以在通讯录匹配中按用户名或邮箱找到我，取值同上
+pub const SETTING_DISCOVERY_PRIVACY: &str = "discovery_privacy";
+// 谁可以在群消息回执中看到我已读，取值同上；看不到时只显示为已送达
+pub const SETTING_READ_RECEIPT_PRIVACY: &str = "read_receipt_privacy";
+
+// 隐私设置的取值
+const PRIVACY_LEVELS: &[&str] = &["everyone", "friends", "nobody"];
+
+// 设置项允许的取值：固定的几个值之一，或满足格式检查的字符串（附格式说明）
+pub enum AllowedValues {
+    OneOf(&'static [&'static str]),
+    Format(fn(&str) -> bool, &'static str),
+}
+
+// 允许用户修改的设置项及其可选值
+pub const USER_SETTINGS: &[(&str, AllowedValues)] = &[
+    (SETTING_EMAIL_DIGEST, AllowedValues::OneOf(&["on", "off"])),
+    (SETTING_DND_SCHEDULE, AllowedValues::Format(|value| value == "off" || DndSchedule::parse(value, 0).is_some(), "off 或 HH:MM-HH:MM")),
+    (SETTING_UTC_OFFSET, AllowedValues::Format(|value| parse_utc_offset(value).is_some(), "+HH:MM 或 -HH:MM")),
+    (SETTING_DM_PRIVACY, AllowedValues::OneOf(PRIVACY_LEVELS)),
+    (SETTING_LAST_SEEN_PRIVACY, AllowedValues::OneOf(PRIVACY_LEVELS)),
+    (SETTING_GROUP_ADD_PRIVACY, AllowedValues::OneOf(PRIVACY_LEVELS)),
+    (SETTING_DISCOVERY_PRIVACY, AllowedValues::OneOf(PRIVACY_LEVELS)),
+    (SETTING_READ_RECEIPT_PRIVACY, AllowedValues::OneOf(PRIVACY_LEVELS)),
+];
+
+// 校验设置项和取值，返回错误说明
+pub fn validate_setting(key: &str, value: &str) -> std::result::Result<(), String> {
+    let Some((_, allowed)) = USER_SETTINGS.iter().find(|(k, _)| *k == key) else {
+        return Err(format!("未知的设置项 {}", key));
+    };
+    match allowed {
+        AllowedValues::OneOf(values) if !values.contains(&value) => {
+            Err(format!("设置项 {} 的取值必须是 {} 之一", key, values.join("、")))
+        }
+        AllowedValues::Format(check, format) if !check(value) => {
+            Err(format!("设置项 {} 的格式应为 {}", key, format))
+        }
+        _ => Ok(()),
+    }
+}
+
+// "HH:MM" 转为当天的第几分钟
+fn parse_minute_of_day(value: &str) -> Option<i64> {
+    let (hours, minutes) = value.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    ((0..24).contains(&hours) && (0..60).contains(&minutes)).then_some(hours * 60 + minutes)
+}
+
+// "+HH:MM" / "-HH:MM" 转为相对 UTC 的秒数，范围 -12:00 到 +14:00
+pub fn parse_utc_offset(value: &str) -> Option<i64> {
+    let sign = match value.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let minutes = parse_minute_of_day(&value[1..])?;
+    let offset = sign * minutes * 60;
+    (-12 * 3600..=14 * 3600).contains(&offset).then_some(offset)
+}
+
+// 用户的免打扰时段（按用户时区换算），开始和结束为当天的第几分钟
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DndSchedule {
+    start: i64,
+    end: i64,
+    utc_offset: i64, // 秒
+}
+
+impl DndSchedule {
+    // 解析 "HH:MM-HH:MM"，开始和结束相同时视为无效
+    fn parse(value: &str, utc_offset: i64) -> Option<Self> {
+        let (start, end) = value.split_once('-')?;
+        let (start, end) = (parse_minute_of_day(start)?, parse_minute_of_day(end)?);
+        (start != end).then_some(Self { start, end, utc_offset })
+    }
+
+    // 从用户设置中读取，未开启免打扰时为 None
+    pub fn from_settings(settings: &HashMap<String, String>) -> Option<Self> {
+        let utc_offset = settings.get(SETTING_UTC_OFFSET).and_then(|value| parse_utc_offset(value)).unwrap_or(0);
+        Self::parse(settings.get(SETTING_DND_SCHEDULE)?, utc_offset)
+    }
+
+    // now 时刻处于免打扰时段时返回时段结束的时间戳
+    pub fn active_until(&self, now: i64) -> Option<i64> {
+        let second_of_day = (now + self.utc_offset).rem_euclid(86_400);
+        let minute = second_of_day / 60;
+        let active = if self.start < self.end {
+            self.start <= minute && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        };
+        active.then(|| now + (self.end * 60 - second_of_day).rem_euclid(86_400))
+    }
+}
+
+impl DbPool {
+    // 获取用户已保存的全部设置（未保存的项使用默认值，不在结果中）
+    pub fn get_user_settings(&self, user_id: &str) -> Result<HashMap<String, String>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT key, value FROM user_settings WHERE user_id = ?")?;
+        stmt.query_map([user_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect()
+    }
+
+    // 在一个事务中保存多项设置
+    pub fn set_user_settings(&self, user_id: &str, settings: &HashMap<String, String>, now: i64) -> Result<()> {
+        self.with_tx(|conn| {
+            for (key, value) in settings {
+                conn.execute(
+                    "INSERT INTO user_settings (user_id, key, value, updated_at) VALUES (?1, ?2, ?3, ?4)
+                     ON CONFLICT(user_id, key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+                    params![user_id, key, value, now],
+                )?;
+            }
+            Ok(())
+        })
+    }
+
+    // 获取用户加密后的账号设置及其版本号，从未保存过时返回 None
+    pub fn get_account_settings(&self, user_id: &str) -> Result<Option<(Vec<u8>, i64)>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT ciphertext, version FROM account_settings WHERE user_id = ?",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()
+    }
+
+    // 保存加密后的账号设置：只有当前版本等于 expected_version（从未保存过为 0）时才写入，
+    // 写入后版本号加一；版本不一致时返回 false，由调用方提示客户端重新读取
+    pub fn put_account_settings(&self, user_id: &str, ciphertext: &[u8], expected_version: i64, now: i64) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        let changed = if expected_version == 0 {
+            conn.execute(
+                "INSERT INTO account_settings (user_id, ciphertext, version, updated_at) VALUES (?1, ?2, 1, ?3)
+                 ON CONFLICT(user_id) DO NOTHING",
+                params![user_id, ciphertext, now],
+            )?
+        } else {
+            conn.execute(
+                "UPDATE account_settings SET ciphertext = ?2, version = version + 1, updated_at = ?4
+                 WHERE user_id = ?1 AND version = ?3",
+                params![user_id, ciphertext, expected_version, now],
+            )?
+        };
+        Ok(changed > 0)
+    }
+
+    // 按 owner 的隐私设置项 key 判断 other 是否被允许；本人总是允许，未设置时允许所有人
+    pub fn privacy_allows(&self, owner: &str, key: &str, other: &str) -> Result<bool> {
+        if owner == other {
+            return Ok(true);
+        }
+        let level: Option<String> = {
+            let conn = self.0.lock().unwrap();
+            conn.query_row(
+                "SELECT value FROM user_settings WHERE user_id = ?1 AND key = ?2",
+                params![owner, key],
+                |row| row.get(0),
+            ).optional()?
+        };
+        match level.as_deref() {
+            Some("nobody") => Ok(false),
+            Some("friends") => self.are_friends(owner, other),
+            _ => Ok(true),
+        }
+    }
+
+    // 用户当前免打扰时段的结束时间，不在免打扰时段内时为 None
+    pub fn dnd_until(&self, user_id: &str, now: i64) -> Result<Option<i64>> {
+        let settings = self.get_user_settings(user_id)?;
+        Ok(DndSchedule::from_settings(&settings).and_then(|schedule| schedule.active_until(now)))
+    }
+
+    // 用户最后一次在线的时间，从未连接过时为 None
+    pub fn last_seen_at(&self, user_id: &str) -> Result<Option<i64>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row("SELECT last_seen_at FROM users WHERE id = ?", [user_id], |row| row.get(0))
+    }
+
+    // 记录用户最后一次在线的时间
+    pub fn touch_last_seen(&self, user_id: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute("UPDATE users SET last_seen_at = ? WHERE id = ?", params![now, user_id])?;
+        Ok(())
+    }
+}
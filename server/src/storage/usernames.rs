@@ -0,0 +1,35 @@
+//! 用户名归一化：唯一索引建在归一化后的用户名上，`Alice`、`ａｌｉｃｅ`、`аlice`（西里尔字母 а）视为同一个名字
+
+use rusqlite::{params, Result, Transaction};
+use unicode_normalization::UnicodeNormalization;
+use unicode_security::skeleton;
+
+/// 归一化用户名：NFKC 兼容分解、大小写折叠，再按 UTS #39 映射易混淆字符
+pub fn normalize_username(username: &str) -> String {
+    let folded: String = username.nfkc().flat_map(char::to_lowercase).collect();
+    // 骨架会把部分字符映射为大写形式（如 0 -> O），再折叠一次
+    skeleton(&folded).flat_map(char::to_lowercase).collect()
+}
+
+// 迁移时为已有用户回填归一化用户名；与更早的用户冲突时留空，不影响其登录
+pub(super) fn backfill_normalized_usernames(tx: &Transaction) -> Result<()> {
+    let users: Vec<(String, String)> = {
+        let mut stmt = tx.prepare(
+            "SELECT u.id, u.username FROM users u
+             WHERE NOT EXISTS (SELECT 1 FROM bots b WHERE b.id = u.id)
+               AND NOT EXISTS (SELECT 1 FROM remote_users r WHERE r.user_id = u.id)
+             ORDER BY u.created_at, u.id",
+        )?;
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_>>()?
+    };
+
+    for (id, username) in users {
+        tx.execute(
+            "UPDATE users SET username_normalized = ?1
+             WHERE id = ?2 AND NOT EXISTS (SELECT 1 FROM users WHERE username_normalized = ?1)",
+            params![normalize_username(&username), id],
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use rand::RngCore;
+use rusqlite::{params, OptionalExtension, Result, Row};
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::DbPool;
+
+// 新用户注册
+pub const EVENT_USER_REGISTERED: &str = "user.registered";
+// 新消息（可按会话过滤）
+pub const EVENT_MESSAGE_SENT: &str = "message.sent";
+
+// 可订阅的事件
+pub const WEBHOOK_EVENTS: &[&str] = &[EVENT_USER_REGISTERED, EVENT_MESSAGE_SENT];
+
+// 已注册的 webhook（签名密钥只在创建时返回一次）
+#[derive(Debug, Serialize)]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    pub event: String,
+    pub channel_id: Option<String>,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    pub created_at: i64,
+}
+
+// 投递记录
+#[derive(Debug, Serialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: String,
+    pub payload: String,
+    pub status: String,           // "pending", "delivered" 或 "failed"
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    pub next_attempt_at: i64,
+    pub created_at: i64,
+    pub delivered_at: Option<i64>,
+}
+
+// 待投递的记录（连同目标地址和签名密钥）
+#[derive(Debug, Clone)]
+pub struct PendingDelivery {
+    pub id: String,
+    pub url: String,
+    pub secret: String,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i64,
+    pub created_at: i64,
+}
+
+impl Webhook {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            url: row.get(1)?,
+            event: row.get(2)?,
+            channel_id: row.get(3)?,
+            secret: row.get(4)?,
+            created_at: row.get(5)?,
+        })
+    }
+}
+
+impl DbPool {
+    // 注册 webhook，随机生成签名密钥
+    pub fn create_webhook(&self, url: &str, event: &str, channel_id: Option<&str>, now: i64) -> Result<Webhook> {
+        let mut secret = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut secret);
+        let webhook = Webhook {
+            id: Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            event: event.to_string(),
+            channel_id: channel_id.map(str::to_string),
+            secret: hex::encode(secret),
+            created_at: now,
+        };
+
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO webhooks (id, url, event, channel_id, secret, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![webhook.id, webhook.url, webhook.event, webhook.channel_id, webhook.secret, webhook.created_at],
+        )?;
+        Ok(webhook)
+    }
+
+    // 列出所有 webhook
+    pub fn list_webhooks(&self) -> Result<Vec<Webhook>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, url, event, channel_id, secret, created_at FROM webhooks ORDER BY created_at"
+        )?;
+        stmt.query_map([], Webhook::from_row)?.collect()
+    }
+
+    // 删除 webhook 及其投递记录
+    pub fn delete_webhook(&self, webhook_id: &str) -> Result<bool> {
+        self.with_tx(|conn| {
+            conn.execute("DELETE FROM webhook_deliveries WHERE webhook_id = ?", [webhook_id])?;
+            let removed = conn.execute("DELETE FROM webhooks WHERE id = ?", [webhook_id])?;
+            Ok(removed > 0)
+        })
+    }
+
+    // 为订阅了该事件的 webhook 各生成一条待投递记录，返回生成的条数
+    // channel_id 为空的 webhook 接收全部会话的事件
+    pub fn enqueue_webhook_event(&self, event: &str, channel_id: Option<&str>, data: &serde_json::Value, now: i64) -> Result<usize> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO webhook_deliveries (id, webhook_id, event, payload, next_attempt_at, created_at)
+             SELECT lower(hex(randomblob(16))), id, event, ?3, ?4, ?4 FROM webhooks
+             WHERE event = ?1 AND (channel_id IS NULL OR channel_id = ?2)",
+            params![event, channel_id, data.to_string(), now],
+        )
+    }
+
+    // 取出到期的待投递记录
+    pub fn due_webhook_deliveries(&self, now: i64, limit: i64) -> Result<Vec<PendingDelivery>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT d.id, w.url, w.secret, d.event, d.payload, d.attempts, d.created_at
+             FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+             WHERE d.status = 'pending' AND d.next_attempt_at <= ?1
+             ORDER BY d.next_attempt_at
+             LIMIT ?2"
+        )?;
+        stmt.query_map(params![now, limit], |row| {
+            Ok(PendingDelivery {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                secret: row.get(2)?,
+                event: row.get(3)?,
+                payload: row.get(4)?,
+                attempts: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 记录投递成功
+    pub fn mark_webhook_delivered(&self, delivery_id: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "UPDATE webhook_deliveries SET status = 'delivered', attempts = attempts + 1, last_error = NULL, delivered_at = ?2 WHERE id = ?1",
+            params![delivery_id, now],
+        )?;
+        Ok(())
+    }
+
+    // 记录投递失败：next_attempt_at 为空表示不再重试
+    pub fn mark_webhook_failed(&self, delivery_id: &str, error: &str, next_attempt_at: Option<i64>) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        match next_attempt_at {
+            Some(next) => conn.execute(
+                "UPDATE webhook_deliveries SET attempts = attempts + 1, last_error = ?2, next_attempt_at = ?3 WHERE id = ?1",
+                params![delivery_id, error, next],
+            )?,
+            None => conn.execute(
+                "UPDATE webhook_deliveries SET status = 'failed', attempts = attempts + 1, last_error = ?2 WHERE id = ?1",
+                params![delivery_id, error],
+            )?,
+        };
+        Ok(())
+    }
+
+    // 最近的投递记录（倒序）
+    pub fn list_webhook_deliveries(&self, webhook_id: &str, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, webhook_id, event, payload, status, attempts, last_error, next_attempt_at, created_at, delivered_at
+             FROM webhook_deliveries WHERE webhook_id = ?1
+             ORDER BY created_at DESC, rowid DESC
+             LIMIT ?2"
+        )?;
+        stmt.query_map(params![webhook_id, limit], |row| {
+            Ok(WebhookDelivery {
+                id: row.get(0)?,
+                webhook_id: row.get(1)?,
+                event: row.get(2)?,
+                payload: row.get(3)?,
+                status: row.get(4)?,
+                attempts: row.get(5)?,
+                last_error: row.get(6)?,
+                next_attempt_at: row.get(7)?,
+                created_at: row.get(8)?,
+                delivered_at: row.get(9)?,
+            })
+        })?
+        .collect()
+    }
+
+    // 查询 webhook 是否存在
+    pub fn webhook_exists(&self, webhook_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row("SELECT 1 FROM webhooks WHERE id = ?", [webhook_id], |_| Ok(()))
+            .optional()
+            .map(|found| found.is_some())
+    }
+}
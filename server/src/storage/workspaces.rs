@@ -0,0 +1,212 @@
+use rand::RngCore;
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use super::{DbPool, User};
+
+// 默认工作区：所有用户隐式属于它，未指定工作区的请求都落在这里
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+// 工作区成员角色
+pub const ROLE_OWNER: &str = "owner";
+pub const ROLE_MEMBER: &str = "member";
+pub const WORKSPACE_ROLES: &[&str] = &[ROLE_OWNER, ROLE_MEMBER];
+
+// 邀请码前缀，便于在日志和代码仓库中识别泄露的邀请码
+const INVITE_CODE_PREFIX: &str = "ylw_";
+
+// 工作区
+#[derive(Debug, Clone, Serialize)]
+pub struct Workspace {
+    pub id: String,
+    pub slug: String,       // 请求头 X-Workspace 中使用的标识
+    pub name: String,
+    pub invite_only: bool,  // 只允许持邀请码注册
+    pub created_at: i64,
+}
+
+impl Workspace {
+    fn from_row(row: &Row) -> Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            slug: row.get(1)?,
+            name: row.get(2)?,
+            invite_only: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    }
+}
+
+// 新生成的邀请码：明文只在创建时返回一次
+#[derive(Debug)]
+pub struct IssuedInvite {
+    pub code: String,
+    pub expires_at: i64,
+}
+
+const WORKSPACE_COLUMNS: &str = "id, slug, name, invite_only, created_at";
+
+fn hash_invite_code(code: &str) -> String {
+    hex::encode(Sha256::digest(code.as_bytes()))
+}
+
+// 与 register_user 相同的约定：带消息的 SqliteFailure 由接口层映射为业务错误
+fn invalid_invite() -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(0), Some("邀请码无效、已使用或已过期".to_string()))
+}
+
+fn insert_invite(conn: &Connection, workspace_id: &str, created_by: Option<&str>, ttl_secs: i64, now: i64) -> Result<IssuedInvite> {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let code = format!("{}{}", INVITE_CODE_PREFIX, hex::encode(bytes));
+    let expires_at = now + ttl_secs;
+    conn.execute(
+        "INSERT INTO workspace_invites (code_hash, workspace_id, created_at, expires_at, created_by) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![hash_invite_code(&code), workspace_id, now, expires_at, created_by],
+    )?;
+    Ok(IssuedInvite { code, expires_at })
+}
+
+impl DbPool {
+    // 创建工作区，slug 重复时返回 None
+    pub fn create_workspace(&self, slug: &str, name: &str, invite_only: bool, now: i64) -> Result<Option<Workspace>> {
+        let conn = self.0.lock().unwrap();
+        let id = Uuid::new_v4().to_string();
+        let inserted = conn.execute(
+            "INSERT OR IGNORE INTO workspaces (id, slug, name, invite_only, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, slug, name, invite_only, now],
+        )?;
+        Ok((inserted > 0).then(|| Workspace {
+            id,
+            slug: slug.to_string(),
+            name: name.to_string(),
+            invite_only,
+            created_at: now,
+        }))
+    }
+
+    pub fn list_workspaces(&self) -> Result<Vec<Workspace>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM workspaces ORDER BY id = '{}' DESC, created_at, slug", WORKSPACE_COLUMNS, DEFAULT_WORKSPACE))?;
+        stmt.query_map([], Workspace::from_row)?.collect()
+    }
+
+    pub fn get_workspace_by_slug(&self, slug: &str) -> Result<Option<Workspace>> {
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            &format!("SELECT {} FROM workspaces WHERE slug = ?", WORKSPACE_COLUMNS),
+            [slug],
+            Workspace::from_row,
+        )
+        .optional()
+    }
+
+    // 设置是否只允许受邀注册，工作区不存在时返回 false
+    pub fn set_workspace_invite_only(&self, workspace_id: &str, invite_only: bool) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute(
+            "UPDATE workspaces SET invite_only = ?2 WHERE id = ?1",
+            params![workspace_id, invite_only],
+        )? > 0)
+    }
+
+    // 用户所属的全部工作区（默认工作区排在最前）
+    pub fn list_user_workspaces(&self, user_id: &str) -> Result<Vec<Workspace>> {
+        let conn = self.0.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT w.id, w.slug, w.name, w.invite_only, w.created_at FROM workspaces w
+             WHERE w.id = ?2
+                OR EXISTS (SELECT 1 FROM workspace_members m WHERE m.workspace_id = w.id AND m.user_id = ?1)
+             ORDER BY w.id = ?2 DESC, w.created_at, w.slug",
+        )?;
+        stmt.query_map(params![user_id, DEFAULT_WORKSPACE], Workspace::from_row)?.collect()
+    }
+
+    pub fn is_workspace_member(&self, workspace_id: &str, user_id: &str) -> Result<bool> {
+        if workspace_id == DEFAULT_WORKSPACE {
+            return Ok(true);
+        }
+        let conn = self.0.lock().unwrap();
+        conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM workspace_members WHERE workspace_id = ?1 AND user_id = ?2)",
+            params![workspace_id, user_id],
+            |row| row.get(0),
+        )
+    }
+
+    // 把用户加入工作区（已是成员时更新角色）
+    pub fn add_workspace_member(&self, workspace_id: &str, user_id: &str, role: &str, now: i64) -> Result<()> {
+        let conn = self.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO workspace_members (workspace_id, user_id, role, joined_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (workspace_id, user_id) DO UPDATE SET role = excluded.role",
+            params![workspace_id, user_id, role, now],
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_workspace_member(&self, workspace_id: &str, user_id: &str) -> Result<bool> {
+        let conn = self.0.lock().unwrap();
+        Ok(conn.execute(
+            "DELETE FROM workspace_members WHERE workspace_id = ?1 AND user_id = ?2",
+            params![workspace_id, user_id],
+        )? > 0)
+    }
+
+    // 签发一个邀请码
+    pub fn create_workspace_invite(&self, workspace_id: &str, ttl_secs: i64, now: i64) -> Result<IssuedInvite> {
+        let conn = self.0.lock().unwrap();
+        insert_invite(&conn, workspace_id, None, ttl_secs, now)
+    }
+
+    // 用户签发默认工作区的邀请码：已使用和尚未过期的邀请码计入名额，名额用完时返回 None
+    pub fn create_user_invite(&self, user_id: &str, max_invites: i64, ttl_secs: i64, now: i64) -> Result<Option<IssuedInvite>> {
+        let conn = self.0.lock().unwrap();
+        let issued: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM workspace_invites
+             WHERE created_by = ?1 AND (used_by IS NOT NULL OR expires_at > ?2)",
+            params![user_id, now],
+            |row| row.get(0),
+        )?;
+        if issued >= max_invites {
+            return Ok(None);
+        }
+        insert_invite(&conn, DEFAULT_WORKSPACE, Some(user_id), ttl_secs, now).map(Some)
+    }
+
+    // 在指定工作区注册用户：同一事务内创建用户、核销邀请码（如有）并加入工作区
+    //
+    // 邀请码无效时整个注册回滚；password_hash 为 bcrypt 哈希（见 crypto::password）
+    pub fn register_user_in_workspace(
+        &self,
+        username: &str,
+        email: &str,
+        password_hash: &str,
+        workspace_id: &str,
+        invite_code: Option<&str>,
+        now: i64,
+    ) -> Result<User> {
+        self.with_tx(|conn| {
+            let user = super::insert_user(conn, &self.2, username, email, password_hash)?;
+            if let Some(code) = invite_code {
+                let redeemed = conn.execute(
+                    "UPDATE workspace_invites SET used_by = ?3, used_at = ?4
+                     WHERE code_hash = ?1 AND workspace_id = ?2 AND used_by IS NULL AND expires_at > ?4",
+                    params![hash_invite_code(code), workspace_id, user.id, now],
+                )?;
+                if redeemed == 0 {
+                    return Err(invalid_invite());
+                }
+            }
+            if workspace_id != DEFAULT_WORKSPACE {
+                conn.execute(
+                    "INSERT INTO workspace_members (workspace_id, user_id, role, joined_at) VALUES (?1, ?2, ?3, ?4)",
+                    params![workspace_id, user.id, ROLE_MEMBER, now],
+                )?;
+            }
+            Ok(user)
+        })
+    }
+}
@@ -0,0 +1,170 @@
+//! 附件回收：删除引用它的消息都已删除的附件，以及目录中没有附件记录的残留文件，并校验其余附件文件的完整性
+//!
+//! 内容相同的附件共用一个文件，最后一个引用它的附件记录删除时才删除文件；
+//! 删除附件记录后上传者的存储用量随之减少；校验不通过的文件只报告不删除，由管理员从备份恢复
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+use super::supervisor::Supervisor;
+use crate::config::settings::AttachmentSettings;
+use crate::storage::DbPool;
+
+// 校验时每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+static DELETED: AtomicU64 = AtomicU64::new(0);
+static RECLAIMED_BYTES: AtomicU64 = AtomicU64::new(0);
+// 最近一轮校验发现的损坏或缺失的附件数
+static CORRUPTED: AtomicU64 = AtomicU64::new(0);
+
+/// 一轮回收的结果
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    pub deleted: usize,          // 删除的附件数
+    pub stray_files: usize,      // 删除的残留文件数
+    pub reclaimed_bytes: u64,    // 释放的磁盘空间
+    pub verified: usize,         // 校验过的附件数（未开启校验时为 0）
+    pub corrupted: Vec<String>,  // 文件缺失或 SHA-256 不一致的附件ID
+}
+
+// 在阻塞线程池中执行数据库操作
+async fn blocking<T, F>(f: F) -> rusqlite::Result<T>
+where
+    F: FnOnce() -> rusqlite::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
+}
+
+// 删除文件，返回释放的字节数；文件已经不存在时为 0
+pub(crate) fn remove_file(path: &Path) -> u64 {
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    match std::fs::remove_file(path) {
+        Ok(()) => size,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => 0,
+        Err(e) => {
+            println!("删除附件文件 {} 失败: {}", path.display(), e);
+            0
+        }
+    }
+}
+
+// 计算文件的 SHA-256（十六进制），文件无法读取时为 None
+async fn file_sha256(path: &Path) -> Option<String> {
+    let mut file = tokio::fs::File::open(path).await.ok()?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        let n = file.read(&mut buf).await.ok()?;
+        if n == 0 {
+            return Some(hex::encode(hasher.finalize()));
+        }
+        hasher.update(&buf[..n]);
+    }
+}
+
+/// 执行一轮回收
+pub async fn collect(db_pool: &DbPool, settings: &AttachmentSettings) -> rusqlite::Result<GcReport> {
+    let dir = Path::new(&settings.dir);
+    let mut report = GcReport::default();
+
+    let db = db_pool.clone();
+    for attachment in blocking(move || db.orphaned_attachments()).await? {
+        let db = db_pool.clone();
+        let blob_dir = dir.to_path_buf();
+        // 期间又被新消息引用的附件保留；文件在数据库锁内删除，不会删掉同时上传的相同内容
+        let freed = blocking(move || {
+            db.delete_orphaned_attachment(&attachment.id, |blob| remove_file(&blob_dir.join(blob)))
+        }).await?;
+        if let Some(freed) = freed {
+            report.reclaimed_bytes += freed;
+            report.deleted += 1;
+        }
+    }
+
+    // 按文件归并其余附件，共用的文件只校验一次
+    let db = db_pool.clone();
+    let mut blobs: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+    for attachment in blocking(move || db.list_attachments()).await? {
+        blobs.entry(attachment.blob).or_insert_with(|| (attachment.sha256, Vec::new())).1.push(attachment.id);
+    }
+    if settings.gc_verify_checksums {
+        for (blob, (sha256, ids)) in &blobs {
+            if file_sha256(&dir.join(blob)).await.as_deref() != Some(sha256.as_str()) {
+                println!("附件 {} 的文件缺失或已损坏", ids.join("、"));
+                report.corrupted.extend(ids.iter().cloned());
+            }
+            report.verified += ids.len();
+        }
+        CORRUPTED.store(report.corrupted.len() as u64, Ordering::Relaxed);
+    }
+
+    // 没有附件记录、且存在超过宽限期的文件是上传失败或记录已删除时遗留的
+    let grace = Duration::from_secs(settings.gc_grace_secs);
+    if let Ok(mut entries) = tokio::fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let Ok(metadata) = entry.metadata().await else { continue };
+            let name = entry.file_name();
+            if !metadata.is_file() || name.to_str().is_some_and(|name| blobs.contains_key(name)) {
+                continue;
+            }
+            let age = metadata.modified().ok().and_then(|modified| SystemTime::now().duration_since(modified).ok());
+            if age.is_some_and(|age| age >= grace) {
+                report.reclaimed_bytes += remove_file(&entry.path());
+                report.stray_files += 1;
+            }
+        }
+    }
+
+    DELETED.fetch_add(report.deleted as u64, Ordering::Relaxed);
+    RECLAIMED_BYTES.fetch_add(report.reclaimed_bytes, Ordering::Relaxed);
+    Ok(report)
+}
+
+// 启动附件回收任务（gc_interval_secs 为 0 时不启动）
+pub fn spawn(tasks: &Supervisor, db_pool: DbPool, settings: AttachmentSettings) {
+    if settings.gc_interval_secs == 0 {
+        return;
+    }
+    tasks.spawn("attachment-gc", move || {
+        let db_pool = db_pool.clone();
+        let settings = settings.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(settings.gc_interval_secs));
+            loop {
+                interval.tick().await;
+                match collect(&db_pool, &settings).await {
+                    Ok(report) if report.deleted + report.stray_files > 0 => println!(
+                        "附件回收完成: 删除 {} 个附件和 {} 个残留文件，释放 {} 字节",
+                        report.deleted, report.stray_files, report.reclaimed_bytes
+                    ),
+                    Ok(_) => {}
+                    Err(e) => println!("附件回收失败: {}", e),
+                }
+            }
+        }
+    });
+}
+
+/// 以 OpenMetrics 文本格式输出附件回收的统计，不含结尾的 `# EOF`
+pub fn render(out: &mut String) {
+    let _ = writeln!(out, "# TYPE yueling_attachment_gc_deleted counter");
+    let _ = writeln!(out, "# HELP yueling_attachment_gc_deleted Attachments deleted because every referencing message was deleted.");
+    let _ = writeln!(out, "yueling_attachment_gc_deleted_total {}", DELETED.load(Ordering::Relaxed));
+    let _ = writeln!(out, "# TYPE yueling_attachment_gc_reclaimed_bytes counter");
+    let _ = writeln!(out, "# HELP yueling_attachment_gc_reclaimed_bytes Disk space freed by attachment garbage collection.");
+    let _ = writeln!(out, "yueling_attachment_gc_reclaimed_bytes_total {}", RECLAIMED_BYTES.load(Ordering::Relaxed));
+    let _ = writeln!(out, "# TYPE yueling_attachments_corrupted gauge");
+    let _ = writeln!(out, "# HELP yueling_attachments_corrupted Attachments whose file was missing or failed the checksum in the last pass.");
+    let _ = writeln!(out, "yueling_attachments_corrupted {}", CORRUPTED.load(Ordering::Relaxed));
+}
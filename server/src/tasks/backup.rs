@@ -0,0 +1,47 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::supervisor::Supervisor;
+use crate::config::settings::BackupSettings;
+use crate::storage::{backup, DbPool};
+
+// 执行一次备份并按保留数量清理旧备份（阻塞操作放到专用线程）
+pub async fn run_once(db_pool: &DbPool, settings: &BackupSettings, key: Option<String>) -> std::io::Result<PathBuf> {
+    let db_pool = db_pool.clone();
+    let dir = settings.dir.clone();
+    let keep = settings.keep;
+
+    tokio::task::spawn_blocking(move || {
+        let dir = Path::new(&dir);
+        let path = db_pool.backup_to_dir(dir, key.as_deref())?;
+        for removed in backup::prune_backups(dir, keep)? {
+            println!("已删除过期备份: {}", removed.display());
+        }
+        Ok(path)
+    })
+    .await
+    .map_err(std::io::Error::other)?
+}
+
+// 启动自动备份任务（interval_secs 为 0 时不启动）
+pub fn spawn(tasks: &Supervisor, db_pool: DbPool, settings: BackupSettings, key: Option<String>) {
+    if settings.interval_secs == 0 {
+        return;
+    }
+
+    tasks.spawn("backup", move || {
+        let (db_pool, settings, key) = (db_pool.clone(), settings.clone(), key.clone());
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_secs));
+            // 第一次 tick 立即返回，跳过它以免启动时就备份
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                match run_once(&db_pool, &settings, key.clone()).await {
+                    Ok(path) => println!("自动备份完成: {}", path.display()),
+                    Err(e) => println!("自动备份失败: {}", e),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,75 @@
+use futures_util::StreamExt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::api::AppState;
+use crate::bus::BusEvent;
+
+// 订阅断开后重新连接前的等待时间
+const RESUBSCRIBE_DELAY: Duration = Duration::from_secs(3);
+
+/// 启动集群消息总线的发布、订阅和在线状态心跳任务（单实例模式下什么都不做）
+pub fn spawn(state: AppState) {
+    let Some(bus) = state.cluster.bus().cloned() else {
+        return;
+    };
+
+    // 按顺序发布本实例产生的事件；发布队列只能取出一次，重启后沿用同一个接收端
+    if let Some(outbox) = state.cluster.take_outbox() {
+        let outbox = Arc::new(tokio::sync::Mutex::new(outbox));
+        let bus = bus.clone();
+        state.tasks.spawn("cluster-publish", move || {
+            let (outbox, bus) = (outbox.clone(), bus.clone());
+            async move {
+                let mut outbox = outbox.lock().await;
+                while let Some(envelope) = outbox.recv().await {
+                    if let Err(e) = bus.publish(&envelope).await {
+                        println!("发布集群事件失败: {}", e);
+                    }
+                }
+            }
+        });
+    }
+
+    // 把其他实例发布的推送投递给本实例上的连接
+    let subscriber_state = state.clone();
+    state.tasks.spawn("cluster-subscribe", move || {
+        let (bus, state) = (bus.clone(), subscriber_state.clone());
+        async move {
+            loop {
+                match bus.subscribe().await {
+                    Ok(mut events) => {
+                        while let Some(envelope) = events.next().await {
+                            match state.cluster.handle_remote(envelope) {
+                                Some(BusEvent::User { user_id, payload }) => {
+                                    state.deliver_local(&user_id, payload);
+                                }
+                                Some(BusEvent::Group { group_id, payload }) => {
+                                    state.deliver_local_group(&group_id, payload);
+                                }
+                                _ => {}
+                            }
+                        }
+                        println!("集群事件订阅已断开，正在重连");
+                    }
+                    Err(e) => println!("订阅集群事件失败: {}", e),
+                }
+                tokio::time::sleep(RESUBSCRIBE_DELAY).await;
+            }
+        }
+    });
+
+    // 定期广播本实例上的在线用户
+    let heartbeat = Duration::from_secs(state.settings.cluster.heartbeat_secs.max(1));
+    let heartbeat_state = state.clone();
+    state.tasks.spawn("cluster-heartbeat", move || {
+        let state = heartbeat_state.clone();
+        async move {
+            let mut interval = tokio::time::interval(heartbeat);
+            loop {
+                interval.tick().await;
+                state.cluster.publish(BusEvent::Heartbeat { user_ids: state.local_online_users() });
+            }
+        }
+    });
+}
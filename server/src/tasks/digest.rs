@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::supervisor::Supervisor;
+use crate::config::settings::DigestSettings;
+use crate::email::{Email, EmailProvider};
+use crate::storage::digest::DigestRecipient;
+use crate::storage::{DbPool, Message};
+use crate::core::datetime::unix_now;
+
+// 摘要中单条消息内容的最大字符数
+const MAX_PREVIEW_CHARS: usize = 80;
+
+/// 判断用户当前是否在线
+pub type PresenceCheck = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+// 生成一封摘要邮件
+fn compose(db_pool: &DbPool, recipient: &DigestRecipient, messages: &[Message], max_messages: usize) -> Email {
+    let mut body = format!("{}，你好：\n\n你离线期间收到了 {} 条未读消息：\n\n", recipient.username, messages.len());
+    for message in messages.iter().take(max_messages) {
+        let sender = db_pool.get_user_by_id(&message.sender_id)
+            .map(|u| u.username)
+            .unwrap_or_else(|_| message.sender_id.clone());
+        let mut preview: String = message.content.chars().take(MAX_PREVIEW_CHARS).collect();
+        if message.content.chars().count() > MAX_PREVIEW_CHARS {
+            preview.push('…');
+        }
+        body.push_str(&format!("  {}: {}\n", sender, preview));
+    }
+    if messages.len() > max_messages {
+        body.push_str(&format!("  ……还有 {} 条\n", messages.len() - max_messages));
+    }
+    body.push_str("\n登录月灵查看完整消息。如不想再收到此类邮件，可以在设置中关闭邮件摘要。\n");
+
+    Email {
+        to: recipient.email.clone(),
+        subject: format!("你有 {} 条未读消息", messages.len()),
+        body,
+    }
+}
+
+/// 执行一轮摘要发送，返回成功发送的邮件数
+///
+/// 发送失败的用户不记录发送时间，下一轮会重试
+pub async fn send_digests(
+    db_pool: &DbPool,
+    mailer: &dyn EmailProvider,
+    settings: &DigestSettings,
+    is_online: &PresenceCheck,
+    now: i64,
+) -> Result<usize, String> {
+    let cutoff = now - settings.offline_secs as i64;
+    let pool = db_pool.clone();
+    let recipients = tokio::task::spawn_blocking(move || pool.digest_recipients(cutoff))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let mut sent = 0;
+    for recipient in recipients {
+        // 连接一直保持着的用户 last_seen_at 也可能很旧，以实时在线状态为准
+        if is_online(&recipient.user_id) {
+            continue;
+        }
+        let pool = db_pool.clone();
+        let max_messages = settings.max_messages;
+        let (recipient, email) = tokio::task::spawn_blocking(move || {
+            let messages = pool.unread_messages_since(&recipient.user_id, recipient.last_digest_at)?;
+            let email = (!messages.is_empty()).then(|| compose(&pool, &recipient, &messages, max_messages));
+            Ok::<_, rusqlite::Error>((recipient, email))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+        let Some(email) = email else {
+            continue;
+        };
+
+        match mailer.send(&email).await {
+            Ok(()) => {
+                let pool = db_pool.clone();
+                let user_id = recipient.user_id.clone();
+                tokio::task::spawn_blocking(move || pool.mark_digest_sent(&user_id, now))
+                    .await
+                    .map_err(|e| e.to_string())?
+                    .map_err(|e| e.to_string())?;
+                sent += 1;
+            }
+            Err(e) => println!("向用户 {} 发送邮件摘要失败: {}", recipient.user_id, e),
+        }
+    }
+    Ok(sent)
+}
+
+// 启动邮件摘要任务
+pub fn spawn(tasks: &Supervisor, db_pool: DbPool, mailer: Arc<dyn EmailProvider>, settings: DigestSettings, is_online: PresenceCheck) {
+    if settings.interval_secs == 0 {
+        return;
+    }
+
+    tasks.spawn("digest", move || {
+        let (db_pool, mailer, settings, is_online) = (db_pool.clone(), mailer.clone(), settings.clone(), is_online.clone());
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(settings.interval_secs));
+            loop {
+                interval.tick().await;
+                let now = unix_now();
+                match send_digests(&db_pool, mailer.as_ref(), &settings, &is_online, now).await {
+                    Ok(0) => {}
+                    Ok(sent) => println!("已发送 {} 封未读消息摘要邮件", sent),
+                    Err(e) => println!("发送邮件摘要失败: {}", e),
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,184 @@
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::api::AppState;
+use crate::email::Email;
+use crate::push::PushNotification;
+use crate::storage::jobs::{Job, JOB_EXPORT_USER, JOB_PUSH, JOB_REKEY, JOB_RETENTION, JOB_VERIFY_EMAIL};
+use crate::core::datetime::unix_now;
+
+// 在阻塞线程池中执行数据库操作
+async fn blocking<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce() -> rusqlite::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+// 邮箱验证邮件：配置了验证链接时给出链接，否则只给出令牌
+fn verification_email(link: &str, to: String, token: &str) -> Email {
+    let action = if link.is_empty() {
+        format!("请在月灵中输入以下验证码完成验证：\n\n{}", token)
+    } else {
+        format!("请打开以下链接完成验证：\n\n{}", link.replace("{token}", token))
+    };
+    Email {
+        to,
+        subject: "验证你的月灵邮箱".into(),
+        body: format!("你好！\n\n{}\n\n如果这不是你本人的操作，请忽略这封邮件。\n", action),
+    }
+}
+
+// 执行单个任务，返回错误描述
+async fn run_job(state: &AppState, job: &Job) -> Result<(), String> {
+    let payload: Value = serde_json::from_str(&job.payload).map_err(|e| format!("任务参数无效: {}", e))?;
+    match job.kind.as_str() {
+        JOB_PUSH => {
+            let user_ids: Vec<String> = serde_json::from_value(payload["user_ids"].clone())
+                .map_err(|e| format!("任务参数无效: {}", e))?;
+            let notification: PushNotification = serde_json::from_value(payload["notification"].clone())
+                .map_err(|e| format!("任务参数无效: {}", e))?;
+            state.push.notify(user_ids, notification).await;
+            Ok(())
+        }
+        JOB_RETENTION => {
+            let db_pool = state.db_pool.clone();
+            let settings = state.settings.retention.clone();
+            let report = blocking(move || db_pool.apply_retention(&settings, unix_now())).await?;
+            if report.messages_deleted > 0 || report.users_purged > 0 || report.groups_purged > 0 {
+                println!(
+                    "保留策略已删除 {} 条过期消息、清除 {} 个用户和 {} 个已删除的群聊",
+                    report.messages_deleted, report.users_purged, report.groups_purged
+                );
+            }
+            Ok(())
+        }
+        JOB_EXPORT_USER => {
+            let user_id = payload["user_id"].as_str().ok_or("任务参数缺少 user_id")?.to_string();
+            let db_pool = state.db_pool.clone();
+            let export = {
+                let user_id = user_id.clone();
+                blocking(move || db_pool.export_user(&user_id)).await?
+            };
+            let dir = Path::new(&state.settings.jobs.export_dir);
+            tokio::fs::create_dir_all(dir).await.map_err(|e| e.to_string())?;
+            let path = dir.join(format!("{}-{}.json", user_id, export.exported_at));
+            let json = serde_json::to_vec_pretty(&export).map_err(|e| e.to_string())?;
+            tokio::fs::write(&path, json).await.map_err(|e| e.to_string())?;
+            println!("已导出用户 {} 的数据到 {}", user_id, path.display());
+            Ok(())
+        }
+        JOB_VERIFY_EMAIL => {
+            let user_id = payload["user_id"].as_str().ok_or("任务参数缺少 user_id")?.to_string();
+            let mailer = state.mailer.clone().ok_or("未配置邮件发送")?;
+            let settings = &state.settings.registration;
+            let db_pool = state.db_pool.clone();
+            let ttl_secs = settings.email_token_ttl_secs;
+            // 每次尝试都签发新令牌，只有最后一封邮件中的令牌有效
+            let Some((email, token)) = blocking(move || db_pool.issue_email_verification(&user_id, ttl_secs, unix_now())).await? else {
+                // 已验证或没有填写邮箱，无需发送
+                return Ok(());
+            };
+            mailer.send(&verification_email(&settings.email_verify_link, email, &token)).await
+        }
+        JOB_REKEY => {
+            if !state.db_pool.encryption_enabled() {
+                return Err("未开启消息或邮箱加密，无需重新加密".into());
+            }
+            let settings = &state.settings.jobs;
+            let batch_size = settings.rekey_batch_size.max(1);
+            let delay = Duration::from_millis(settings.rekey_batch_delay_ms);
+            let db_pool = state.db_pool.clone();
+            let job_id = job.id.clone();
+            blocking(move || db_pool.begin_rekey_run(&job_id, unix_now())).await?;
+            // 分批处理，每批一个事务，两批之间让出数据库给正常请求
+            loop {
+                let db_pool = state.db_pool.clone();
+                let job_id = job.id.clone();
+                let processed = blocking(move || db_pool.rekey_messages_batch(&job_id, batch_size, unix_now())).await?;
+                if processed == 0 {
+                    break;
+                }
+                tokio::time::sleep(delay).await;
+            }
+            let db_pool = state.db_pool.clone();
+            let job_id = job.id.clone();
+            blocking(move || db_pool.finish_rekey_run(&job_id, unix_now())).await
+        }
+        other => Err(format!("未知的任务类型 {}", other)),
+    }
+}
+
+/// 取出并执行一个到期任务，返回是否执行了任务
+///
+/// 失败的任务按指数退避重新排队，超过最大尝试次数后进入死信状态
+pub async fn work_once(state: &AppState) -> Result<bool, String> {
+    let db_pool = state.db_pool.clone();
+    let Some(job) = blocking(move || db_pool.claim_job(unix_now())).await? else {
+        return Ok(false);
+    };
+
+    let result = run_job(state, &job).await;
+    let db_pool = state.db_pool.clone();
+    blocking(move || {
+        let now = unix_now();
+        match result {
+            Ok(()) => db_pool.complete_job(&job.id, now),
+            Err(e) => {
+                println!("任务 {} ({}) 第 {} 次执行失败: {}", job.id, job.kind, job.attempts, e);
+                let next = (job.attempts < job.max_attempts)
+                    .then(|| now + super::webhooks::backoff_secs(job.attempts));
+                db_pool.fail_job(&job.id, &e, next, now)
+            }
+        }
+    })
+    .await?;
+    Ok(true)
+}
+
+/// 把任务加入队列；写入失败只打印日志
+pub fn enqueue(state: &AppState, kind: &str, payload: Value) -> Option<String> {
+    match state.db_pool.enqueue_job(kind, &payload, state.settings.jobs.max_attempts, unix_now()) {
+        Ok(id) => Some(id),
+        Err(e) => {
+            println!("加入 {} 任务失败: {}", kind, e);
+            None
+        }
+    }
+}
+
+// 启动任务队列的 worker（workers 为 0 时不启动）
+pub fn spawn(state: AppState) {
+    let settings = state.settings.jobs.clone();
+    if settings.workers == 0 {
+        return;
+    }
+    match state.db_pool.requeue_running_jobs(unix_now()) {
+        Ok(0) => {}
+        Ok(n) => println!("已将 {} 个中断的任务放回队列", n),
+        Err(e) => println!("恢复中断的任务失败: {}", e),
+    }
+
+    let poll_interval = Duration::from_millis(settings.poll_interval_ms.max(10));
+    for worker in 0..settings.workers {
+        let worker_state = state.clone();
+        state.tasks.spawn(format!("jobs-{}", worker), move || {
+            let state = worker_state.clone();
+            async move {
+                loop {
+                    match work_once(&state).await {
+                        Ok(true) => continue,
+                        Ok(false) => {}
+                        Err(e) => println!("任务队列出错: {}", e),
+                    }
+                    tokio::time::sleep(poll_interval).await;
+                }
+            }
+        });
+    }
+}
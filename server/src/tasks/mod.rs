@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use crate::api::AppState;
+use crate::storage::cipher;
+
+// 后台定时任务
+pub mod attachment_gc;
+pub mod backup;
+pub mod cluster;
+pub mod digest;
+pub mod jobs;
+pub mod outbox;
+pub mod retention;
+pub mod supervisor;
+pub mod webhooks;
+
+/// 由监督器启动所有按配置启用的后台任务，并注册关闭钩子
+pub fn spawn_background_tasks(state: &AppState) {
+    let db_pool = &state.db_pool;
+    let settings = &state.settings;
+    let tasks = &state.tasks;
+    // 启动时已校验过加密配置，这里不会失败
+    let key = cipher::resolve_key(settings).unwrap_or_default();
+    backup::spawn(tasks, db_pool.clone(), settings.backup.clone(), key);
+    attachment_gc::spawn(tasks, db_pool.clone(), settings.attachments.clone());
+    retention::spawn(tasks, db_pool.clone(), settings.retention.clone(), settings.jobs.max_attempts);
+    webhooks::spawn(tasks, db_pool.clone(), settings.webhooks.clone());
+    cluster::spawn(state.clone());
+    jobs::spawn(state.clone());
+    outbox::spawn(state.clone());
+
+    if let Some(mailer) = state.mailer.clone() {
+        let presence = state.clone();
+        digest::spawn(
+            tasks,
+            db_pool.clone(),
+            mailer,
+            settings.digest.clone(),
+            Arc::new(move |user_id: &str| presence.is_online(user_id)),
+        );
+    }
+
+    // 所有任务停止后把 WAL 写回主文件，下次启动和备份都不用再回放
+    let db_pool = db_pool.clone();
+    tasks.on_shutdown("checkpoint", async move {
+        match tokio::task::spawn_blocking(move || db_pool.checkpoint()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => println!("关闭前写回 WAL 失败: {}", e),
+            Err(e) => println!("关闭前写回 WAL 异常: {}", e),
+        }
+    });
+}
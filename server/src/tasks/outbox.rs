@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use crate::api::AppState;
+use crate::core::datetime::unix_now;
+
+// 在阻塞线程池中执行数据库操作
+async fn blocking<T, F>(f: F) -> rusqlite::Result<T>
+where
+    F: FnOnce() -> rusqlite::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
+}
+
+/// 重发到期未确认的实时推送，返回本轮重发的条数
+///
+/// 只重发给在线的接收者；重发次数用完或超过保留时间的记录直接删除，客户端重连后通过同步接口补齐
+pub async fn redeliver_due(state: &AppState) -> rusqlite::Result<usize> {
+    let settings = state.settings.outbox.clone();
+    let now = unix_now();
+    let db_pool = state.db_pool.clone();
+    let due = blocking(move || {
+        db_pool.prune_outbox(now - settings.ttl_secs)?;
+        db_pool.due_outbox(now - settings.redeliver_after_secs, settings.batch_size)
+    }).await?;
+
+    let mut redelivered = 0;
+    for entry in due {
+        let online = state.is_online(&entry.user_id);
+        if online {
+            state.push_to_receiver(&entry.message);
+            redelivered += 1;
+        }
+        let db_pool = state.db_pool.clone();
+        let max_attempts = state.settings.outbox.max_attempts;
+        blocking(move || {
+            if online && entry.attempts + 1 >= max_attempts {
+                db_pool.remove_outbox_entry(&entry.message.id, &entry.user_id)
+            } else {
+                db_pool.record_outbox_attempt(&entry.message.id, &entry.user_id, online, now)
+            }
+        }).await?;
+    }
+    Ok(redelivered)
+}
+
+// 启动发件箱重发任务（redeliver_interval_secs 为 0 时不启动）；服务器重启后也会重发崩溃前未确认的推送
+pub fn spawn(state: AppState) {
+    let interval_secs = state.settings.outbox.redeliver_interval_secs;
+    if interval_secs == 0 {
+        return;
+    }
+    let outbox_state = state.clone();
+    state.tasks.spawn("outbox", move || {
+        let state = outbox_state.clone();
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = redeliver_due(&state).await {
+                    println!("重发实时推送失败: {}", e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use super::supervisor::Supervisor;
+use crate::config::settings::RetentionSettings;
+use crate::storage::{jobs::JOB_RETENTION, DbPool};
+use crate::core::datetime::unix_now;
+
+// 启动数据保留维护任务：定期把保留策略任务加入任务队列，并按需执行 VACUUM
+pub fn spawn(tasks: &Supervisor, db_pool: DbPool, settings: RetentionSettings, max_attempts: i64) {
+    if settings.run_interval_secs > 0 {
+        let db_pool = db_pool.clone();
+        let interval_secs = settings.run_interval_secs;
+        tasks.spawn("retention", move || {
+            let db_pool = db_pool.clone();
+            async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                loop {
+                    interval.tick().await;
+                    let db_pool = db_pool.clone();
+                    let result = tokio::task::spawn_blocking(move || {
+                        let now = unix_now();
+                        // 上一轮还没执行完时不再重复排队
+                        if db_pool.has_unfinished_job(JOB_RETENTION)? {
+                            return Ok(());
+                        }
+                        db_pool.enqueue_job(JOB_RETENTION, &serde_json::json!({}), max_attempts, now).map(|_| ())
+                    })
+                    .await;
+                    match result {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => println!("加入保留策略任务失败: {}", e),
+                        Err(e) => println!("保留策略任务异常: {}", e),
+                    }
+                }
+            }
+        });
+    }
+
+    if settings.vacuum_interval_secs > 0 {
+        let interval_secs = settings.vacuum_interval_secs;
+        tasks.spawn("vacuum", move || {
+            let db_pool = db_pool.clone();
+            async move {
+                let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+                // 第一次 tick 立即返回，跳过它以免启动时就 VACUUM
+                interval.tick().await;
+                loop {
+                    interval.tick().await;
+                    let db_pool = db_pool.clone();
+                    match tokio::task::spawn_blocking(move || db_pool.vacuum()).await {
+                        Ok(Ok(())) => println!("数据库 VACUUM 完成"),
+                        Ok(Err(e)) => println!("数据库 VACUUM 失败: {}", e),
+                        Err(e) => println!("VACUUM 任务异常: {}", e),
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,208 @@
+//! 后台任务监督器：统一启动、命名和停止所有常驻的后台任务
+//!
+//! 任务 panic 后按指数退避重启（稳定运行一段时间后退避重置），正常返回则视为结束不再重启。
+//! 停止时先通知所有任务退出，再按注册顺序执行关闭钩子；各任务的状态由 /admin/tasks 查看
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use crate::core::datetime::unix_now;
+
+// 退避上限
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// 运行超过这个时长后才崩溃的任务，下次重启从初始退避开始
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+type Hook = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// 任务当前所处的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    // panic 后等待重启
+    Restarting,
+    // 任务自行返回，不再重启
+    Finished,
+    // 服务器关闭时被停止
+    Stopped,
+}
+
+/// 单个后台任务的状态
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskStatus {
+    pub name: String,
+    pub state: TaskState,
+    pub started_at: i64,              // 最近一次启动（或重启）的时间
+    pub restarts: u32,                // panic 后重启的次数
+    pub last_panic: Option<String>,
+    pub last_panic_at: Option<i64>,
+}
+
+struct Inner {
+    tasks: Mutex<BTreeMap<String, TaskStatus>>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+    hooks: Mutex<Vec<(String, Hook)>>,
+    shutdown: watch::Sender<bool>,
+    initial_backoff: Duration,
+}
+
+/// 后台任务监督器，克隆后共享同一组任务
+#[derive(Clone)]
+pub struct Supervisor {
+    inner: Arc<Inner>,
+}
+
+impl Supervisor {
+    /// initial_backoff 为第一次 panic 后的重启等待时间，之后每次翻倍，最多一分钟
+    pub fn new(initial_backoff: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                tasks: Mutex::new(BTreeMap::new()),
+                handles: Mutex::new(Vec::new()),
+                hooks: Mutex::new(Vec::new()),
+                shutdown: watch::Sender::new(false),
+                initial_backoff,
+            }),
+        }
+    }
+
+    /// 以给定名称启动任务；task 每次（重新）启动时调用一次，返回新的任务实例
+    ///
+    /// 同名任务会覆盖状态记录，调用方需保证名称唯一（多个工作者加上序号）
+    pub fn spawn<F, Fut>(&self, name: impl Into<String>, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let name = name.into();
+        let inner = self.inner.clone();
+        let handle = tokio::spawn(async move {
+            let mut shutdown = inner.shutdown.subscribe();
+            let mut backoff = inner.initial_backoff;
+            let mut restarts = 0;
+            loop {
+                if *shutdown.borrow() {
+                    inner.set_state(&name, TaskState::Stopped);
+                    return;
+                }
+                inner.record_start(&name, restarts);
+                let started = Instant::now();
+                let mut run = tokio::spawn(task());
+                let result = tokio::select! {
+                    result = &mut run => result,
+                    _ = stopped(&mut shutdown) => {
+                        run.abort();
+                        let _ = run.await;
+                        inner.set_state(&name, TaskState::Stopped);
+                        return;
+                    }
+                };
+                let panic = match result {
+                    Ok(()) => {
+                        inner.set_state(&name, TaskState::Finished);
+                        return;
+                    }
+                    Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                    Err(_) => {
+                        inner.set_state(&name, TaskState::Stopped);
+                        return;
+                    }
+                };
+                if started.elapsed() >= STABLE_AFTER {
+                    backoff = inner.initial_backoff;
+                }
+                println!("后台任务 {} 崩溃，{} 毫秒后重启: {}", name, backoff.as_millis(), panic);
+                inner.record_panic(&name, panic);
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = stopped(&mut shutdown) => {
+                        inner.set_state(&name, TaskState::Stopped);
+                        return;
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                restarts += 1;
+            }
+        });
+        self.inner.handles.lock().unwrap().push(handle);
+    }
+
+    /// 注册关闭钩子，在所有任务停止后按注册顺序执行
+    pub fn on_shutdown(&self, name: impl Into<String>, hook: impl Future<Output = ()> + Send + 'static) {
+        self.inner.hooks.lock().unwrap().push((name.into(), Box::pin(hook)));
+    }
+
+    /// 按名称排序的所有任务状态
+    pub fn statuses(&self) -> Vec<TaskStatus> {
+        self.inner.tasks.lock().unwrap().values().cloned().collect()
+    }
+
+    /// 停止所有任务并执行关闭钩子；等待任务退出和每个钩子各自最多 timeout
+    pub async fn shutdown(&self, timeout: Duration) {
+        self.inner.shutdown.send_replace(true);
+        let handles = std::mem::take(&mut *self.inner.handles.lock().unwrap());
+        if tokio::time::timeout(timeout, futures_util::future::join_all(handles)).await.is_err() {
+            println!("部分后台任务未能在 {} 秒内停止", timeout.as_secs());
+        }
+        let hooks = std::mem::take(&mut *self.inner.hooks.lock().unwrap());
+        for (name, hook) in hooks {
+            if tokio::time::timeout(timeout, hook).await.is_err() {
+                println!("关闭钩子 {} 超时", name);
+            }
+        }
+    }
+}
+
+impl Inner {
+    fn record_start(&self, name: &str, restarts: u32) {
+        let mut tasks = self.tasks.lock().unwrap();
+        let status = tasks.entry(name.to_string()).or_insert_with(|| TaskStatus {
+            name: name.to_string(),
+            state: TaskState::Running,
+            started_at: 0,
+            restarts: 0,
+            last_panic: None,
+            last_panic_at: None,
+        });
+        status.state = TaskState::Running;
+        status.started_at = unix_now();
+        status.restarts = restarts;
+    }
+
+    fn record_panic(&self, name: &str, panic: String) {
+        if let Some(status) = self.tasks.lock().unwrap().get_mut(name) {
+            status.state = TaskState::Restarting;
+            status.last_panic = Some(panic);
+            status.last_panic_at = Some(unix_now());
+        }
+    }
+
+    fn set_state(&self, name: &str, state: TaskState) {
+        if let Some(status) = self.tasks.lock().unwrap().get_mut(name) {
+            status.state = state;
+        }
+    }
+}
+
+// 等待关闭通知；返回前释放 watch 的读锁，避免跨 await 持有
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stop| *stop).await;
+}
+
+// panic 负载通常是 &str 或 String
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| "未知错误".into()),
+    }
+}
@@ -0,0 +1,116 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+
+use super::supervisor::Supervisor;
+use crate::config::settings::WebhookSettings;
+use crate::storage::{webhooks::PendingDelivery, DbPool};
+use crate::core::datetime::unix_now;
+
+// 重试退避上限（秒）
+const MAX_BACKOFF_SECS: i64 = 3600;
+
+// 计算请求体签名：hex(HMAC-SHA256(secret, body))，放在 X-Yueling-Signature 请求头中
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC 接受任意长度的密钥");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+// 第 attempts 次失败后的重试等待时间：10 秒起指数退避（任务队列也使用同样的策略）
+pub(crate) fn backoff_secs(attempts: i64) -> i64 {
+    (10i64 << attempts.clamp(0, 16)).min(MAX_BACKOFF_SECS)
+}
+
+// 投递单条记录，返回错误描述
+async fn deliver(client: &reqwest::Client, delivery: &PendingDelivery) -> Result<(), String> {
+    let body = format!(
+        r#"{{"id":{},"event":{},"created_at":{},"data":{}}}"#,
+        serde_json::Value::from(delivery.id.as_str()),
+        serde_json::Value::from(delivery.event.as_str()),
+        delivery.created_at,
+        delivery.payload
+    );
+    let response = client
+        .post(&delivery.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Yueling-Event", &delivery.event)
+        .header("X-Yueling-Delivery", &delivery.id)
+        .header("X-Yueling-Signature", sign(&delivery.secret, &body))
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status()))
+    }
+}
+
+// 投递所有到期的记录，返回本轮成功的条数
+pub async fn dispatch_due(db_pool: &DbPool, client: &reqwest::Client, settings: &WebhookSettings) -> rusqlite::Result<usize> {
+    let due = {
+        let db_pool = db_pool.clone();
+        let limit = settings.batch_size;
+        tokio::task::spawn_blocking(move || db_pool.due_webhook_deliveries(unix_now(), limit))
+            .await
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))??
+    };
+
+    let mut delivered = 0;
+    for delivery in due {
+        let result = deliver(client, &delivery).await;
+        let db_pool = db_pool.clone();
+        let max_attempts = settings.max_attempts;
+        let ok = result.is_ok();
+        tokio::task::spawn_blocking(move || {
+            let now = unix_now();
+            match result {
+                Ok(()) => db_pool.mark_webhook_delivered(&delivery.id, now),
+                Err(e) => {
+                    let attempts = delivery.attempts + 1;
+                    let next = (attempts < max_attempts).then(|| now + backoff_secs(attempts));
+                    db_pool.mark_webhook_failed(&delivery.id, &e, next)
+                }
+            }
+        })
+        .await
+        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))??;
+        if ok {
+            delivered += 1;
+        }
+    }
+    Ok(delivered)
+}
+
+// 启动 webhook 投递任务（dispatch_interval_secs 为 0 时不启动）
+pub fn spawn(tasks: &Supervisor, db_pool: DbPool, settings: WebhookSettings) {
+    if settings.dispatch_interval_secs == 0 {
+        return;
+    }
+    let client = match reqwest::Client::builder()
+        .timeout(Duration::from_secs(settings.timeout_secs))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            println!("创建 webhook HTTP 客户端失败: {}", e);
+            return;
+        }
+    };
+
+    tasks.spawn("webhooks", move || {
+        let (db_pool, client, settings) = (db_pool.clone(), client.clone(), settings.clone());
+        async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(settings.dispatch_interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = dispatch_due(&db_pool, &client, &settings).await {
+                    println!("webhook 投递失败: {}", e);
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,65 @@
+//! 运行本机程序翻译（如本地模型的命令行）：原文写入标准输入，从标准输出读取译文
+
+use futures_util::future::BoxFuture;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use super::Translator;
+use crate::config::settings::TranslationSettings;
+
+// 参数中替换为目标语言的占位符
+const LANG_PLACEHOLDER: &str = "{lang}";
+
+pub struct CommandTranslator {
+    program: String,
+    args: Vec<String>,
+    timeout: Duration,
+}
+
+impl CommandTranslator {
+    pub fn from_settings(settings: &TranslationSettings) -> Result<Self, String> {
+        let (program, args) = settings.command.split_first()
+            .ok_or("command 后端需要配置 command")?;
+        Ok(Self {
+            program: program.clone(),
+            args: args.to_vec(),
+            timeout: Duration::from_secs(settings.timeout_secs),
+        })
+    }
+
+    async fn run(&self, text: &str, target_lang: &str) -> Result<String, String> {
+        let mut child = Command::new(&self.program)
+            .args(self.args.iter().map(|arg| arg.replace(LANG_PLACEHOLDER, target_lang)))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("启动 {} 失败: {}", self.program, e))?;
+        let mut stdin = child.stdin.take().expect("已设置为管道");
+        stdin.write_all(text.as_bytes()).await
+            .map_err(|e| format!("写入原文失败: {}", e))?;
+        // 关闭标准输入，程序读到结尾后开始翻译
+        drop(stdin);
+
+        let output = child.wait_with_output().await
+            .map_err(|e| format!("等待 {} 结束失败: {}", self.program, e))?;
+        if !output.status.success() {
+            return Err(format!("{} 退出码 {}: {}", self.program, output.status, String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        let translated = String::from_utf8(output.stdout)
+            .map_err(|_| "译文不是有效的 UTF-8".to_string())?;
+        Ok(translated.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+impl Translator for CommandTranslator {
+    fn translate<'a>(&'a self, text: &'a str, target_lang: &'a str) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            tokio::time::timeout(self.timeout, self.run(text, target_lang)).await
+                .map_err(|_| format!("{} 超过 {} 秒未完成", self.program, self.timeout.as_secs()))?
+        })
+    }
+}
@@ -0,0 +1,57 @@
+//! 兼容 LibreTranslate 的 HTTP 翻译接口（自建或第三方服务）
+
+use futures_util::future::BoxFuture;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+use super::Translator;
+use crate::config::settings::TranslationSettings;
+
+#[derive(Deserialize)]
+struct TranslateResponse {
+    #[serde(rename = "translatedText")]
+    translated_text: String,
+}
+
+pub struct LibreTranslate {
+    client: reqwest::Client,
+    endpoint: String,
+    api_key: String,
+}
+
+impl LibreTranslate {
+    pub fn from_settings(settings: &TranslationSettings) -> Result<Self, String> {
+        if settings.url.is_empty() {
+            return Err("libretranslate 后端需要配置 url".into());
+        }
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(settings.timeout_secs))
+            .build()
+            .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+        Ok(Self {
+            client,
+            endpoint: format!("{}/translate", settings.url.trim_end_matches('/')),
+            api_key: settings.api_key.clone(),
+        })
+    }
+}
+
+impl Translator for LibreTranslate {
+    fn translate<'a>(&'a self, text: &'a str, target_lang: &'a str) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let mut body = json!({ "q": text, "source": "auto", "target": target_lang, "format": "text" });
+            if !self.api_key.is_empty() {
+                body["api_key"] = json!(self.api_key);
+            }
+            let response = self.client.post(&self.endpoint).json(&body).send().await
+                .map_err(|e| format!("请求翻译接口失败: {}", e))?;
+            if !response.status().is_success() {
+                return Err(format!("翻译接口返回 {}", response.status()));
+            }
+            let body: TranslateResponse = response.json().await
+                .map_err(|e| format!("解析翻译结果失败: {}", e))?;
+            Ok(body.translated_text)
+        })
+    }
+}
@@ -0,0 +1,35 @@
+//! 消息翻译：通过 Translator 抽象接入外部翻译 API 或本机模型，译文按 (消息, 语言) 缓存在数据库中
+
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+
+use crate::config::settings::TranslationSettings;
+
+pub mod command;
+pub mod libretranslate;
+
+/// 翻译后端（HTTP 翻译接口、本机程序或测试替身）
+pub trait Translator: Send + Sync {
+    /// 把 text 翻译为 target_lang（如 en、zh-CN），源语言由后端自动识别
+    fn translate<'a>(&'a self, text: &'a str, target_lang: &'a str) -> BoxFuture<'a, Result<String, String>>;
+}
+
+/// 按配置创建翻译后端，未配置 backend 时返回 None
+pub fn from_settings(settings: &TranslationSettings) -> Result<Option<Arc<dyn Translator>>, String> {
+    match settings.backend.as_str() {
+        "" => Ok(None),
+        "libretranslate" => Ok(Some(Arc::new(libretranslate::LibreTranslate::from_settings(settings)?))),
+        "command" => Ok(Some(Arc::new(command::CommandTranslator::from_settings(settings)?))),
+        other => Err(format!("未知的翻译后端 {}，可选: libretranslate、command", other)),
+    }
+}
+
+/// 目标语言是否为合法的语言标签：2 到 3 个字母的主语言，后面可以跟以 - 分隔的 2 到 8 位字母数字子标签
+pub fn is_valid_lang(lang: &str) -> bool {
+    let mut parts = lang.split('-');
+    let primary = parts.next().unwrap_or_default();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && lang.len() <= 35
+        && parts.all(|part| (2..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
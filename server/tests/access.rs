@@ -0,0 +1,101 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+fn app(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = "test-admin-token".into();
+    settings.access.trust_forwarded_for = true;
+    configure(&mut settings);
+    TestApp::with_settings(settings)
+}
+
+async fn admin_from(app: &TestApp, method: Method, body: Option<Value>, ip: &str) -> (StatusCode, Value) {
+    app.request_with_headers(method, "/admin/access", body, &[("authorization", ADMIN_AUTH), ("x-forwarded-for", ip)]).await
+}
+
+async fn health_from(app: &TestApp, ip: &str) -> StatusCode {
+    app.request_with_headers(Method::GET, "/health", None, &[("x-forwarded-for", ip)]).await.0
+}
+
+#[tokio::test]
+async fn deny_wins_over_allow_and_admin_has_its_own_list() {
+    let app = app(|settings| {
+        settings.access.allow = vec!["10.0.0.0/8".into(), "::1".into()];
+        settings.access.deny = vec!["10.0.0.66".into()];
+        settings.access.admin_allow = vec!["10.1.0.0/16".into()];
+    });
+
+    assert_eq!(health_from(&app, "10.2.3.4").await, StatusCode::OK);
+    assert_eq!(health_from(&app, "::1").await, StatusCode::OK);
+    assert_eq!(health_from(&app, "10.1.1.1, 10.0.0.66").await, StatusCode::FORBIDDEN);
+    assert_eq!(health_from(&app, "192.168.1.1").await, StatusCode::FORBIDDEN);
+    // 配置了规则却不知道来源地址时拒绝
+    assert_eq!(app.get("/health").await.0, StatusCode::FORBIDDEN);
+
+    assert_eq!(admin_from(&app, Method::GET, None, "10.2.3.4").await.0, StatusCode::FORBIDDEN);
+    let (status, body) = admin_from(&app, Method::GET, None, "10.1.2.3").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["access"]["deny"], json!(["10.0.0.66"]));
+}
+
+#[tokio::test]
+async fn rules_can_be_replaced_at_runtime() {
+    let app = app(|_| {});
+    assert_eq!(health_from(&app, "203.0.113.9").await, StatusCode::OK);
+
+    let (status, _) = admin_from(&app, Method::PUT, Some(json!({ "deny": ["not-a-network"], "trust_forwarded_for": true })), "127.0.0.1").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // 无效的规则不会生效
+    assert_eq!(health_from(&app, "203.0.113.9").await, StatusCode::OK);
+
+    let (status, body) = admin_from(&app, Method::PUT, Some(json!({ "deny": ["203.0.113.0/24"], "trust_forwarded_for": true })), "127.0.0.1").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(health_from(&app, "203.0.113.9").await, StatusCode::FORBIDDEN);
+    assert_eq!(health_from(&app, "198.51.100.1").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn forwarded_for_entries_written_by_the_client_are_ignored() {
+    let app = app(|settings| settings.access.admin_allow = vec!["10.1.0.0/16".into()]);
+
+    // 客户端自己写上管理网段的地址，反向代理在右边追加真实的对端地址
+    assert_eq!(admin_from(&app, Method::GET, None, "10.1.2.3, 192.168.1.1").await.0, StatusCode::FORBIDDEN);
+    assert_eq!(admin_from(&app, Method::GET, None, "192.168.1.1, 10.1.2.3").await.0, StatusCode::OK);
+
+    // 两层代理时取从右数第二个地址，最右边的是第一层代理的地址
+    let app = self::app(|settings| {
+        settings.access.admin_allow = vec!["10.1.0.0/16".into()];
+        settings.access.forwarded_hops = 2;
+    });
+    assert_eq!(admin_from(&app, Method::GET, None, "10.1.2.3, 192.168.1.1, 10.1.0.1").await.0, StatusCode::FORBIDDEN);
+    assert_eq!(admin_from(&app, Method::GET, None, "192.168.1.1, 10.1.2.3, 172.16.0.1").await.0, StatusCode::OK);
+    // 地址数不足时取最左边的
+    assert_eq!(admin_from(&app, Method::GET, None, "10.1.2.3").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn admin_token_requests_outside_admin_paths_follow_admin_allow() {
+    let app = app(|settings| {
+        settings.access.admin_allow = vec!["10.1.0.0/16".into()];
+        settings.registration.invites_per_user = 1;
+    });
+    let alice = app.register("alice", "secret").await;
+    let from = |ip: &'static str| [("authorization", ADMIN_AUTH), ("x-forwarded-for", ip)];
+
+    // 不在 /admin 下但凭管理令牌调用的接口同样只接受 admin_allow 内的来源
+    for (method, path) in [(Method::POST, "/invites".to_string()), (Method::POST, format!("/user/{alice}/restore"))] {
+        let (status, _) = app.request_with_headers(method.clone(), &path, None, &from("192.168.1.1")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN, "{path}");
+        let (status, body) = app.request_with_headers(method, &path, None, &from("10.1.2.3")).await;
+        assert_ne!(status, StatusCode::FORBIDDEN, "{path}: {body}");
+    }
+    // 普通用户的请求不受 admin_allow 限制
+    let (status, body) = app.request_with_headers(Method::POST, "/invites", None, &[("authorization", &app.session(&alice)), ("x-forwarded-for", "192.168.1.1")]).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
@@ -0,0 +1,65 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{settings::Settings, CryptoService};
+
+fn app_with_master_key() -> TestApp {
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    TestApp::with_settings(settings)
+}
+
+async fn settings(app: &TestApp, auth: &str, method: Method, body: Option<Value>) -> (StatusCode, Value) {
+    app.request_with_headers(method, "/account/settings", body, &[("authorization", auth)]).await
+}
+
+#[tokio::test]
+async fn settings_are_versioned_and_stored_encrypted() {
+    let app = app_with_master_key();
+    let (alice, auth) = app.login("alice").await;
+
+    let (status, body) = settings(&app, &auth, Method::GET, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((body["version"].as_i64(), body["settings"].is_null()), (Some(0), true));
+
+    let theme = json!({ "theme": "dark", "font_size": 14 });
+    let (status, body) = settings(&app, &auth, Method::PUT, Some(json!({ "version": 0, "settings": theme }))).await;
+    assert_eq!((status, body["version"].as_i64()), (StatusCode::OK, Some(1)));
+
+    // 另一台设备仍拿着旧版本，不能覆盖
+    let (status, body) = settings(&app, &auth, Method::PUT, Some(json!({ "version": 0, "settings": {} }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::CONFLICT, Some("account.settings_conflict")));
+
+    let (_, body) = settings(&app, &auth, Method::GET, None).await;
+    assert_eq!((body["version"].as_i64(), &body["settings"]), (Some(1), &theme));
+    let (status, body) = settings(&app, &auth, Method::PUT, Some(json!({ "version": 1, "settings": { "theme": "light" } }))).await;
+    assert_eq!((status, body["version"].as_i64()), (StatusCode::OK, Some(2)));
+
+    // 数据库中只有密文，且与用户绑定
+    let (ciphertext, version) = app.db.get_account_settings(&alice).unwrap().unwrap();
+    assert_eq!(version, 2);
+    assert!(!String::from_utf8_lossy(&ciphertext).contains("light"));
+    let crypto = CryptoService::new("test-master-key");
+    assert_eq!(crypto.decrypt(&ciphertext, alice.as_bytes()).unwrap(), br#"{"theme":"light"}"#);
+    assert!(crypto.decrypt(&ciphertext, b"someone-else").is_err());
+    assert!(CryptoService::new("other-key").decrypt(&ciphertext, alice.as_bytes()).is_err());
+}
+
+#[tokio::test]
+async fn settings_need_a_session_and_a_master_key() {
+    let app = app_with_master_key();
+    let (status, _) = app.get("/account/settings").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, auth) = app.login("alice").await;
+    let big = "x".repeat(64 * 1024);
+    let (status, body) = settings(&app, &auth, Method::PUT, Some(json!({ "version": 0, "settings": big }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("account.settings_too_large")));
+
+    let app = TestApp::new();
+    let (_, auth) = app.login("bob").await;
+    let (status, body) = settings(&app, &auth, Method::GET, None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::INTERNAL_SERVER_ERROR, Some("account.settings_no_master_key")));
+}
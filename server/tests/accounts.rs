@@ -0,0 +1,159 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::{json, Value};
+use server::{run_next_job, settings::Settings, AppState, DbPool, Email, EmailProvider};
+use std::sync::{Arc, Mutex};
+
+// 记录发出邮件的测试替身
+#[derive(Default)]
+struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl EmailProvider for RecordingMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.sent.lock().unwrap().push(email.clone());
+            Ok(())
+        })
+    }
+}
+
+fn verifying_state(mailer: &Arc<RecordingMailer>) -> AppState {
+    let mut settings = Settings::default();
+    settings.registration.email_required = true;
+    settings.registration.email_verification = true;
+    settings.registration.require_verified_email = true;
+    settings.registration.email_verify_link = "https://chat.example.com/verify?token={token}".into();
+    let mut state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    state.mailer = Some(mailer.clone());
+    state
+}
+
+async fn register(app: &TestApp, username: &str, email: &str) -> (StatusCode, Value) {
+    app.post("/register", json!({ "username": username, "password": "secret", "email": email })).await
+}
+
+// 从最后一封验证邮件的链接中取出令牌
+fn last_token(mailer: &RecordingMailer, to: &str) -> String {
+    let sent = mailer.sent.lock().unwrap();
+    let email = sent.last().unwrap();
+    assert_eq!(email.to, to);
+    let (_, rest) = email.body.split_once("token=").unwrap();
+    rest.split_whitespace().next().unwrap().to_string()
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str) -> (StatusCode, Value) {
+    app.post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": receiver, "content": "hi", "message_type": "private" })).await
+}
+
+#[tokio::test]
+async fn email_must_be_verified_before_messaging() {
+    let mailer = Arc::new(RecordingMailer::default());
+    let state = verifying_state(&mailer);
+    let app = TestApp::with_state(&state);
+
+    let (status, body) = app.post("/register", json!({ "username": "alice", "password": "secret" })).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "account.email_required"));
+    assert_eq!(register(&app, "alice", "not-an-email").await.1["code"], "account.invalid_email");
+
+    let (status, body) = register(&app, "alice", "alice@example.com").await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let alice = body["user_id"].as_str().unwrap().to_string();
+    let (status, body) = register(&app, "bob", "alice@example.com").await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::CONFLICT, "account.email_taken"));
+    let bob = register(&app, "bob", "bob@example.com").await.1["user_id"].as_str().unwrap().to_string();
+
+    // 验证邮件由任务队列发送
+    assert!(mailer.sent.lock().unwrap().is_empty());
+    assert!(run_next_job(&state).await.unwrap());
+    let token = last_token(&mailer, "alice@example.com");
+
+    let (status, body) = send(&app, &alice, &bob).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::FORBIDDEN, "account.verification_required"));
+
+    let (status, _) = app.post("/account/verify-email", json!({ "token": "yle_wrong" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = app.post("/account/verify-email", json!({ "token": token })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["user_id"], alice.as_str());
+    // 令牌只能使用一次
+    assert_eq!(app.post("/account/verify-email", json!({ "token": token })).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(send(&app, &alice, &bob).await.0, StatusCode::OK);
+
+    // 修改邮箱后需要重新验证
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{alice}"), Some(json!({ "username": "alice", "email": "alice@new.example.com" })), &[("authorization", &app.session(&alice))]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, &alice, &bob).await.0, StatusCode::FORBIDDEN);
+    while run_next_job(&state).await.unwrap() {}
+    let token = last_token(&mailer, "alice@new.example.com");
+    assert_eq!(app.post("/account/verify-email", json!({ "token": token })).await.0, StatusCode::OK);
+    assert_eq!(send(&app, &alice, &bob).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn resend_requires_a_session_and_replaces_the_token() {
+    let mailer = Arc::new(RecordingMailer::default());
+    let state = verifying_state(&mailer);
+    let app = TestApp::with_state(&state);
+    register(&app, "alice", "alice@example.com").await;
+    run_next_job(&state).await.unwrap();
+    let first = last_token(&mailer, "alice@example.com");
+
+    assert_eq!(app.post("/account/verify-email/resend", json!({})).await.0, StatusCode::UNAUTHORIZED);
+    let (_, body) = app.post("/login", json!({ "username": "alice", "password": "secret" })).await;
+    let session = format!("Bearer {}", body["token"].as_str().unwrap());
+    let (status, _) = app.request_with_headers(Method::POST, "/account/verify-email/resend", None, &[("authorization", session.as_str())]).await;
+    assert_eq!(status, StatusCode::OK);
+    run_next_job(&state).await.unwrap();
+    let second = last_token(&mailer, "alice@example.com");
+
+    assert_eq!(app.post("/account/verify-email", json!({ "token": first })).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(app.post("/account/verify-email", json!({ "token": second })).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn verification_is_off_by_default() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    assert_eq!(send(&app, &alice, &bob).await.0, StatusCode::OK);
+    assert_eq!(app.post("/account/verify-email/resend", json!({})).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn emails_are_stored_encrypted_but_still_unique() {
+    let mailer = Arc::new(RecordingMailer::default());
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_emails = true;
+    settings.registration.email_verification = true;
+    settings.registration.email_verify_link = "https://chat.example.com/verify?token={token}".into();
+    let mut state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    state.mailer = Some(mailer.clone());
+    let app = TestApp::with_state(&state);
+
+    // 开启加密之前保存的明文邮箱同样参与查重
+    app.db.0.lock().unwrap().execute(
+        "INSERT INTO users (id, username, username_normalized, email, password_hash, created_at) VALUES ('legacy', 'legacy', 'legacy', 'old@example.com', '', 1)",
+        [],
+    ).unwrap();
+    assert_eq!(register(&app, "bob", "old@example.com").await.1["code"], "account.email_taken");
+
+    let alice = register(&app, "alice", "alice@example.com").await.1["user_id"].as_str().unwrap().to_string();
+    let (status, body) = register(&app, "carol", "alice@example.com").await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::CONFLICT, "account.email_taken"));
+    let stored: String = app.db.0.lock().unwrap()
+        .query_row("SELECT email FROM users WHERE id = ?", [&alice], |row| row.get(0))
+        .unwrap();
+    assert!(stored.starts_with("yld1:") && !stored.contains("alice"));
+
+    // 验证邮件发往明文地址，核销时按密文比较
+    run_next_job(&state).await.unwrap();
+    let token = last_token(&mailer, "alice@example.com");
+    assert_eq!(app.post("/account/verify-email", json!({ "token": token })).await.0, StatusCode::OK);
+    assert_eq!(app.db.get_user_by_id(&alice).unwrap().email, "alice@example.com");
+}
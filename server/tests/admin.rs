@@ -0,0 +1,112 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use server::{backup, settings::Settings, DbPool};
+use std::path::{Path, PathBuf};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+// 每个测试使用独立的临时目录
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yueling-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn admin_app(backup_dir: &Path) -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.backup.dir = backup_dir.display().to_string();
+    settings.backup.keep = 2;
+    TestApp::with_settings(settings)
+}
+
+async fn post_backup(app: &TestApp, token: Option<&str>) -> (StatusCode, serde_json::Value) {
+    let header = token.map(|t| format!("Bearer {t}"));
+    let headers: Vec<(&str, &str)> = header.iter().map(|h| ("authorization", h.as_str())).collect();
+    app.request_with_headers(Method::POST, "/admin/backup", None, &headers).await
+}
+
+#[tokio::test]
+async fn admin_routes_disabled_without_token() {
+    let app = TestApp::new();
+    let (status, _) = post_backup(&app, Some("anything")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn backup_rejects_wrong_token() {
+    let dir = temp_dir("backup-auth");
+    let app = admin_app(&dir);
+    let (status, _) = post_backup(&app, Some("wrong")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // 令牌的前缀或加长的令牌同样无效
+    let (status, _) = post_backup(&app, Some(&ADMIN_TOKEN[..ADMIN_TOKEN.len() - 1])).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = post_backup(&app, Some(&format!("{ADMIN_TOKEN}x"))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = post_backup(&app, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn backup_then_restore() {
+    let dir = temp_dir("backup");
+    let app = admin_app(&dir);
+    let user_id = app.register("alice", "secret").await;
+
+    let (status, body) = post_backup(&app, Some(ADMIN_TOKEN)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let backup_path = PathBuf::from(body["path"].as_str().unwrap());
+    assert!(backup_path.exists());
+
+    // 恢复到一个新的数据库文件，用户数据应当存在
+    let restored = dir.join("restored.db");
+    backup::restore_from(restored.to_str().unwrap(), &backup_path, None).unwrap();
+    let db = DbPool::new(restored.to_str().unwrap()).unwrap();
+    assert!(db.user_exists_by_id(&user_id).unwrap());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prune_keeps_newest_backups() {
+    let dir = temp_dir("prune");
+    for ts in ["1700000001", "1700000002", "1700000003"] {
+        std::fs::write(dir.join(format!("server-{ts}.db")), b"").unwrap();
+    }
+    std::fs::write(dir.join("unrelated.txt"), b"").unwrap();
+
+    let removed = backup::prune_backups(&dir, 2).unwrap();
+    assert_eq!(removed, vec![dir.join("server-1700000001.db")]);
+    assert_eq!(backup::list_backups(&dir).unwrap().len(), 2);
+    assert!(dir.join("unrelated.txt").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backups_in_the_same_second_get_distinct_files() {
+    let dir = temp_dir("backup-burst");
+    let db = DbPool::new(":memory:").unwrap();
+    let paths: Vec<PathBuf> = (0..3).map(|_| db.backup_to_dir(&dir, None).unwrap()).collect();
+    assert_eq!(backup::list_backups(&dir).unwrap(), paths);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn prune_orders_second_and_millisecond_names_by_time() {
+    let dir = temp_dir("prune-mixed");
+    // 旧版本按秒命名的备份早于之后按毫秒命名的备份
+    for name in ["server-1700000001.db", "server-1700000000500.db", "server-1700000002000.db"] {
+        std::fs::write(dir.join(name), b"").unwrap();
+    }
+
+    let removed = backup::prune_backups(&dir, 1).unwrap();
+    assert_eq!(removed, vec![dir.join("server-1700000000500.db"), dir.join("server-1700000001.db")]);
+    assert_eq!(backup::list_backups(&dir).unwrap(), vec![dir.join("server-1700000002000.db")]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+use std::path::PathBuf;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+fn app_with_attachment_dir() -> (TestApp, PathBuf) {
+    let mut settings = Settings::default();
+    let dir = std::env::temp_dir().join(format!("yueling-attachment-gc-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    settings.attachments.gc_grace_secs = 0;
+    settings.admin.token = ADMIN_TOKEN.into();
+    (TestApp::with_settings(settings), dir)
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str, content: &str) {
+    let (status, body) = app
+        .post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": receiver, "content": content, "message_type": "private" }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+async fn collect(app: &TestApp) -> (StatusCode, Value) {
+    let auth = format!("Bearer {ADMIN_TOKEN}");
+    app.request_with_headers(Method::POST, "/admin/attachments/gc", None, &[("authorization", auth.as_str())]).await
+}
+
+// 附件文件在磁盘上的路径（内容相同的附件共用一个文件）
+fn blob_path(app: &TestApp, dir: &std::path::Path, attachment_id: &str) -> PathBuf {
+    let blob: String = app.db.0.lock().unwrap()
+        .query_row("SELECT blob FROM attachments WHERE id = ?", [attachment_id], |row| row.get(0))
+        .unwrap();
+    dir.join(blob)
+}
+
+// 模拟保留策略清理消息：删除引用该附件的消息记录
+fn purge_messages_referencing(app: &TestApp, attachment_id: &str) {
+    app.db.0.lock().unwrap().execute(
+        "DELETE FROM messages WHERE id IN (SELECT message_id FROM message_attachments WHERE attachment_id = ?)",
+        [attachment_id],
+    ).unwrap();
+}
+
+#[tokio::test]
+async fn attachments_of_deleted_messages_are_collected() {
+    let (app, dir) = app_with_attachment_dir();
+    let (alice, auth) = app.login("alice").await;
+    let (bob, _) = app.login("bob").await;
+
+    let expired = app.upload_id(&auth, "a.txt", "text/plain", b"expired attachment").await;
+    let live = app.upload_id(&auth, "a.txt", "text/plain", b"live attachment").await;
+    let unsent = app.upload_id(&auth, "a.txt", "text/plain", b"not sent yet").await;
+    let (expired_path, live_path, unsent_path) =
+        (blob_path(&app, &dir, &expired), blob_path(&app, &dir, &live), blob_path(&app, &dir, &unsent));
+    send(&app, &alice, &bob, &format!("看这个 {expired}")).await;
+    send(&app, &alice, &bob, &format!("还有 {live}，和过期的 {expired} 一样")).await;
+    purge_messages_referencing(&app, &live);
+    send(&app, &alice, &bob, &format!("[{live}]")).await;
+    purge_messages_referencing(&app, &expired);
+    std::fs::write(dir.join("leftover.part"), b"12345").unwrap();
+
+    let (status, body) = collect(&app).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("attachment.collected")));
+    let report = &body["report"];
+    assert_eq!(report["deleted"], 1);
+    assert_eq!(report["stray_files"], 1);
+    assert_eq!(report["reclaimed_bytes"], "expired attachment".len() + 5);
+    assert_eq!(report["verified"], 2);
+    assert_eq!(report["corrupted"], json!([]));
+
+    assert!(!expired_path.exists());
+    assert!(live_path.exists());
+    // 从未被消息引用的附件可能正要发送，不回收
+    assert!(unsent_path.exists());
+    let (status, _) = app.request_with_headers(Method::GET, &format!("/attachments/{expired}"), None, &[("authorization", auth.as_str())]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn corrupted_files_are_reported_but_kept() {
+    let (app, dir) = app_with_attachment_dir();
+    let (_, auth) = app.login("alice").await;
+    let damaged = app.upload_id(&auth, "a.txt", "text/plain", b"original content").await;
+    let missing = app.upload_id(&auth, "a.txt", "text/plain", b"will be removed").await;
+    let damaged_path = blob_path(&app, &dir, &damaged);
+    std::fs::write(&damaged_path, b"tampered content").unwrap();
+    std::fs::remove_file(blob_path(&app, &dir, &missing)).unwrap();
+
+    let (_, body) = collect(&app).await;
+    let mut corrupted: Vec<String> = serde_json::from_value(body["report"]["corrupted"].clone()).unwrap();
+    corrupted.sort();
+    let mut expected = vec![damaged.clone(), missing];
+    expected.sort();
+    assert_eq!(corrupted, expected);
+    assert_eq!(body["report"]["deleted"], 0);
+    assert!(damaged_path.exists());
+
+    let mut metrics = String::new();
+    server::attachment_gc::render(&mut metrics);
+    assert!(metrics.contains("yueling_attachment_gc_reclaimed_bytes_total"));
+}
+
+#[tokio::test]
+async fn identical_uploads_share_one_file_until_the_last_is_collected() {
+    let (app, dir) = app_with_attachment_dir();
+    let (alice, auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+
+    let first = app.upload_id(&auth, "a.txt", "text/plain", b"same bytes").await;
+    let second = app.upload_id(&bob_auth, "a.txt", "text/plain", b"same bytes").await;
+    assert_ne!(first, second);
+    assert_eq!(blob_path(&app, &dir, &first), blob_path(&app, &dir, &second));
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    // 文件名不是内容的明文哈希
+    let blob = blob_path(&app, &dir, &first);
+    let plain = hex::encode(<sha2::Sha256 as sha2::Digest>::digest(b"same bytes"));
+    assert_ne!(blob.file_name().unwrap().to_str().unwrap(), plain);
+
+    send(&app, &alice, &bob, &first).await;
+    send(&app, &bob, &alice, &second).await;
+    purge_messages_referencing(&app, &first);
+    let (_, body) = collect(&app).await;
+    assert_eq!(body["report"]["deleted"], 1);
+    assert_eq!(body["report"]["reclaimed_bytes"], 0);
+    assert!(blob.exists());
+    let (status, _) = app.request_with_headers(Method::GET, &format!("/attachments/{second}"), None, &[("authorization", bob_auth.as_str())]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    purge_messages_referencing(&app, &second);
+    let (_, body) = collect(&app).await;
+    assert_eq!(body["report"]["reclaimed_bytes"], "same bytes".len());
+    assert!(!blob.exists());
+}
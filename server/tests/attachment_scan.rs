@@ -0,0 +1,193 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::json;
+use server::{settings::Settings, AppState, DbPool, Scanner, Verdict};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tower::ServiceExt;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+const EICAR: &[u8] = b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+// 内容含 EICAR 的文件报告为病毒，含 BROKEN 的模拟扫描程序出错
+struct FakeScanner;
+
+impl Scanner for FakeScanner {
+    fn scan<'a>(&'a self, path: &'a Path) -> BoxFuture<'a, Result<Verdict, String>> {
+        Box::pin(async move {
+            let content = tokio::fs::read(path).await.map_err(|e| e.to_string())?;
+            let contains = |needle: &[u8]| content.windows(needle.len()).any(|w| w == needle);
+            if contains(b"BROKEN") {
+                Err("clamd 不可用".into())
+            } else if contains(b"EICAR") {
+                Ok(Verdict::Infected("Eicar-Test-Signature".into()))
+            } else {
+                Ok(Verdict::Clean)
+            }
+        })
+    }
+}
+
+fn settings(action: &str) -> (Settings, PathBuf) {
+    let mut settings = Settings::default();
+    let dir = std::env::temp_dir().join(format!("yueling-attachment-scan-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.scan.action = action.into();
+    (settings, dir)
+}
+
+fn scanning_app(action: &str) -> (TestApp, PathBuf) {
+    let (settings, dir) = settings(action);
+    let mut state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    state.scanner = Some(Arc::new(FakeScanner));
+    (TestApp::with_state(&state), dir)
+}
+
+async fn download(app: &TestApp, auth: &str, id: &str) -> StatusCode {
+    let request = Request::get(format!("/attachments/{id}")).header("authorization", auth).body(Body::empty()).unwrap();
+    app.router.clone().oneshot(request).await.unwrap().status()
+}
+
+fn file_count(dir: &Path) -> usize {
+    std::fs::read_dir(dir).map(|entries| entries.count()).unwrap_or(0)
+}
+
+#[tokio::test]
+async fn infected_uploads_are_rejected_and_not_kept() {
+    let (app, dir) = scanning_app("reject");
+    let auth = app.login("alice").await.1;
+
+    let (status, body) = app.upload(&auth, "a.bin", "application/octet-stream", EICAR).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("attachment.infected")));
+    assert!(body["message"].as_str().unwrap().contains("Eicar-Test-Signature"));
+    assert_eq!(file_count(&dir), 0);
+
+    let (status, body) = app.upload(&auth, "a.bin", "application/octet-stream", b"holiday photo").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["attachment"]["scan_status"], "clean");
+    let id = body["attachment"]["id"].as_str().unwrap();
+    assert_eq!(download(&app, &auth, id).await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn quarantined_uploads_wait_for_admin_review() {
+    let (app, dir) = scanning_app("quarantine");
+    let auth = app.login("alice").await.1;
+
+    let (status, body) = app.upload(&auth, "a.bin", "application/octet-stream", EICAR).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["attachment"]["scan_status"], "quarantined");
+    assert_eq!(body["attachment"]["scan_detail"], "Eicar-Test-Signature");
+    let infected = body["attachment"]["id"].as_str().unwrap().to_string();
+    // 扫描程序出错时也隔离，不放过未扫描的文件
+    let (_, body) = app.upload(&auth, "a.bin", "application/octet-stream", b"BROKEN scanner").await;
+    assert_eq!(body["attachment"]["scan_status"], "quarantined");
+    assert!(body["attachment"]["scan_detail"].as_str().unwrap().starts_with("扫描失败"));
+    let unscanned = body["attachment"]["id"].as_str().unwrap().to_string();
+
+    let (status, body) = app.request_with_headers(Method::GET, &format!("/attachments/{infected}"), None, &[("authorization", auth.as_str())]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("attachment.quarantined")));
+    let (status, _) = app.request(Method::GET, "/admin/attachments/quarantine", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/attachments/quarantine", None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("attachment.quarantine_listed")));
+    assert_eq!(body["attachments"].as_array().unwrap().len(), 2);
+
+    // 误报放行后可以下载
+    let (status, body) = app.call(ADMIN_AUTH, Method::POST, &format!("/admin/attachments/quarantine/{unscanned}/release"), None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("attachment.released")));
+    assert_eq!(download(&app, &auth, &unscanned).await, StatusCode::OK);
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/attachments/quarantine/{infected}"), None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("attachment.quarantine_deleted")));
+    assert_eq!(file_count(&dir), 1);
+    assert_eq!(download(&app, &auth, &infected).await, StatusCode::NOT_FOUND);
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/attachments/quarantine", None).await;
+    assert_eq!(body["attachments"], json!([]));
+    // 已放行的附件不在隔离区中
+    let (status, body) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/attachments/quarantine/{unscanned}"), None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("attachment.not_in_quarantine")));
+}
+
+// 模拟 clamd：读取 zINSTREAM 命令和分块数据，内容含 EICAR 时报告病毒
+async fn fake_clamd() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            let mut command = [0u8; 10];
+            stream.read_exact(&mut command).await.unwrap();
+            assert_eq!(&command, b"zINSTREAM\0");
+            let mut data = Vec::new();
+            loop {
+                let len = stream.read_u32().await.unwrap() as usize;
+                if len == 0 {
+                    break;
+                }
+                let mut chunk = vec![0u8; len];
+                stream.read_exact(&mut chunk).await.unwrap();
+                data.extend_from_slice(&chunk);
+            }
+            let reply: &[u8] = if data.windows(5).any(|w| w == b"EICAR") {
+                b"stream: Win.Test.EICAR_HDB-1 FOUND\0"
+            } else {
+                b"stream: OK\0"
+            };
+            stream.write_all(reply).await.unwrap();
+        }
+    });
+    address
+}
+
+#[tokio::test]
+async fn clamav_backend_speaks_instream() {
+    let (mut settings, _) = settings("reject");
+    settings.scan.backend = "clamav".into();
+    settings.scan.clamav_address = fake_clamd().await;
+    let app = TestApp::with_settings(settings);
+    let auth = app.login("alice").await.1;
+
+    let (status, body) = app.upload(&auth, "a.bin", "application/octet-stream", &[b'a'; 200 * 1024]).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["attachment"]["scan_status"], "clean");
+    let (status, body) = app.upload(&auth, "a.bin", "application/octet-stream", EICAR).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body["message"].as_str().unwrap().contains("Win.Test.EICAR_HDB-1"));
+
+    // clamd 不可用时隔离
+    let (mut settings, _) = self::settings("reject");
+    settings.scan.backend = "clamav".into();
+    settings.scan.clamav_address = "127.0.0.1:1".into();
+    let app = TestApp::with_settings(settings);
+    let auth = app.login("alice").await.1;
+    let (_, body) = app.upload(&auth, "a.bin", "application/octet-stream", b"hello").await;
+    assert_eq!(body["attachment"]["scan_status"], "quarantined");
+}
+
+#[test]
+fn invalid_scan_settings_stop_startup() {
+    // 启动时校验：未知的后端或处理方式、clamav 缺少地址都是错误，不会退回到不扫描
+    for configure in [
+        |s: &mut Settings| s.scan.backend = "avast".into(),
+        |s: &mut Settings| s.scan.action = "ignore".into(),
+        |s: &mut Settings| {
+            s.scan.backend = "clamav".into();
+            s.scan.clamav_address = String::new();
+        },
+    ] {
+        let mut settings = Settings::default();
+        configure(&mut settings);
+        assert!(server::attachment_scanner(&settings.scan).is_err());
+        let built = std::panic::catch_unwind(|| AppState::new(DbPool::in_memory().unwrap(), settings));
+        assert!(built.is_err());
+    }
+}
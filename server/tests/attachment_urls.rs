@@ -0,0 +1,104 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use common::TestApp;
+use hmac::{Hmac, Mac};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use server::{datetime::unix_now, settings::Settings};
+use sha2::Sha256;
+use tower::ServiceExt;
+
+fn app(ttl_secs: u64) -> TestApp {
+    let mut settings = Settings::default();
+    let dir = std::env::temp_dir().join(format!("yueling-attachment-urls-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    settings.attachments.signed_url_ttl_secs = ttl_secs;
+    TestApp::with_settings(settings)
+}
+
+async fn sign(app: &TestApp, auth: &str, id: &str) -> (StatusCode, Value) {
+    app.request_with_headers(Method::POST, &format!("/attachments/{id}/url"), None, &[("authorization", auth)]).await
+}
+
+// 不带会话令牌下载
+async fn fetch(app: &TestApp, url: &str, headers: &[(&str, &str)]) -> (StatusCode, http::HeaderMap, Vec<u8>) {
+    let mut builder = Request::get(url);
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    let response = app.router.clone().oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+    let (parts, body) = response.into_parts();
+    (parts.status, parts.headers, body.collect().await.unwrap().to_bytes().to_vec())
+}
+
+fn code(body: &[u8]) -> Option<String> {
+    serde_json::from_slice::<Value>(body).ok()?["code"].as_str().map(str::to_string)
+}
+
+#[tokio::test]
+async fn signed_urls_download_without_a_session() {
+    let app = app(600);
+    let auth = app.login("alice").await.1;
+    let id = app.upload_id(&auth, "a.txt", "text/plain", b"hello signed world").await;
+    let other = app.upload_id(&auth, "a.txt", "text/plain", b"another file").await;
+
+    let (status, _, _) = fetch(&app, &format!("/attachments/{id}"), &[]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = sign(&app, "Bearer wrong", &id).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{body}");
+
+    let (status, body) = sign(&app, &auth, &id).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("attachment.signed_url_created")));
+    let url = body["url"].as_str().unwrap().to_string();
+    let expires_at = body["expires_at"].as_i64().unwrap();
+    assert!(expires_at > unix_now() + 590 && expires_at <= unix_now() + 600);
+
+    let (status, headers, bytes) = fetch(&app, &url, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bytes, b"hello signed world");
+    let cache_control = headers["cache-control"].to_str().unwrap();
+    assert!(cache_control.starts_with("private, max-age="), "{cache_control}");
+    let (status, _, bytes) = fetch(&app, &url, &[("range", "bytes=6-11")]).await;
+    assert_eq!((status, bytes.as_slice()), (StatusCode::PARTIAL_CONTENT, b"signed".as_slice()));
+
+    // 签名只对签发时的附件和过期时间有效
+    let query = url.split_once('?').unwrap().1;
+    let (status, _, body) = fetch(&app, &format!("/attachments/{other}?{query}"), &[]).await;
+    assert_eq!((status, code(&body).as_deref()), (StatusCode::FORBIDDEN, Some("attachment.signed_url_invalid")));
+    let tampered = url.replace(&format!("expires={expires_at}"), &format!("expires={}", expires_at + 3600));
+    let (status, _, body) = fetch(&app, &tampered, &[]).await;
+    assert_eq!((status, code(&body).as_deref()), (StatusCode::FORBIDDEN, Some("attachment.signed_url_invalid")));
+}
+
+#[tokio::test]
+async fn expired_links_are_rejected() {
+    let app = app(600);
+    let auth = app.login("alice").await.1;
+    let id = app.upload_id(&auth, "a.txt", "text/plain", b"short lived").await;
+
+    // 用服务器的密钥为已经过去的时间签名
+    let key: Vec<u8> = app.db.0.lock().unwrap()
+        .query_row("SELECT value FROM server_secrets WHERE name = 'attachment_url_key'", [], |row| row.get(0))
+        .unwrap();
+    let expires = unix_now() - 1;
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&key).unwrap();
+    mac.update(format!("{id}:{expires}").as_bytes());
+    let signature = hex::encode(mac.finalize().into_bytes());
+    let (status, _, body) = fetch(&app, &format!("/attachments/{id}?expires={expires}&signature={signature}"), &[]).await;
+    assert_eq!((status, code(&body).as_deref()), (StatusCode::FORBIDDEN, Some("attachment.signed_url_expired")));
+}
+
+#[tokio::test]
+async fn signing_can_be_disabled() {
+    let app = app(0);
+    let auth = app.login("alice").await.1;
+    let id = app.upload_id(&auth, "a.txt", "text/plain", b"private").await;
+    let (status, body) = sign(&app, &auth, &id).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("attachment.signed_url_disabled")));
+    let (status, body) = sign(&app, &auth, "missing").await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{body}");
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::TestApp;
+use http_body_util::BodyExt;
+use server::settings::Settings;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tower::ServiceExt;
+
+fn app_with_attachment_dir(max_bytes: u64) -> (TestApp, PathBuf) {
+    let mut settings = Settings::default();
+    let dir = std::env::temp_dir().join(format!("yueling-attachments-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    settings.attachments.max_bytes = max_bytes;
+    (TestApp::with_settings(settings), dir)
+}
+
+async fn download(app: &TestApp, auth: &str, id: &str, headers: &[(&str, &str)]) -> (StatusCode, http::HeaderMap, Vec<u8>) {
+    let mut builder = Request::get(format!("/attachments/{id}")).header("authorization", auth);
+    for (name, value) in headers {
+        builder = builder.header(*name, *value);
+    }
+    let response = app.router.clone().oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+    let (parts, body) = response.into_parts();
+    (parts.status, parts.headers, body.collect().await.unwrap().to_bytes().to_vec())
+}
+
+#[tokio::test]
+async fn attachments_upload_and_download_in_ranges() {
+    let (app, _) = app_with_attachment_dir(1024 * 1024);
+    let auth = app.login("alice").await.1;
+    let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+
+    let (status, body) = app.upload(&auth, "../../报告.txt", "text/plain", &content).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("attachment.uploaded")));
+    let attachment = &body["attachment"];
+    let id = attachment["id"].as_str().unwrap();
+    assert_eq!(attachment["filename"], "报告.txt");
+    assert_eq!(attachment["size"], content.len());
+    assert_eq!(attachment["sha256"], hex::encode(Sha256::digest(&content)));
+
+    let (status, headers, bytes) = download(&app, &auth, id, &[]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bytes, content);
+    assert_eq!(headers["accept-ranges"], "bytes");
+    assert_eq!(headers["content-disposition"], "attachment; filename*=UTF-8''%E6%8A%A5%E5%91%8A.txt");
+
+    // 从中间继续下载；ETag 不暴露文件的 SHA-256
+    let etag = headers["etag"].to_str().unwrap().to_string();
+    assert!(!etag.contains(attachment["sha256"].as_str().unwrap()));
+    let (status, headers, bytes) = download(&app, &auth, id, &[("range", "bytes=150000-"), ("if-range", &etag)]).await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(headers["content-range"], "bytes 150000-199999/200000");
+    assert_eq!(bytes, &content[150_000..]);
+
+    // 音视频播放器拖动进度和读取末尾的索引
+    let (status, headers, bytes) = download(&app, &auth, id, &[("range", "bytes=100-199")]).await;
+    assert_eq!((status, headers["content-length"].to_str().unwrap()), (StatusCode::PARTIAL_CONTENT, "100"));
+    assert_eq!(bytes, &content[100..200]);
+    let (status, headers, bytes) = download(&app, &auth, id, &[("range", "bytes=-500")]).await;
+    assert_eq!((status, headers["content-range"].to_str().unwrap()), (StatusCode::PARTIAL_CONTENT, "bytes 199500-199999/200000"));
+    assert_eq!(bytes, &content[199_500..]);
+    let (status, _, bytes) = download(&app, &auth, id, &[("range", "bytes=-300000")]).await;
+    assert_eq!((status, bytes.len()), (StatusCode::PARTIAL_CONTENT, content.len()));
+    let (status, _, _) = download(&app, &auth, id, &[("range", "bytes=-0")]).await;
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+
+    // 播放器先用 HEAD 查询大小和是否支持区间请求
+    let request = Request::head(format!("/attachments/{id}")).header("authorization", &auth).body(Body::empty()).unwrap();
+    let response = app.router.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-length"], "200000");
+    assert_eq!(response.headers()["accept-ranges"], "bytes");
+    assert!(response.into_body().collect().await.unwrap().to_bytes().is_empty());
+
+    // If-Range 与当前文件不符时返回完整文件，越界的区间返回 416
+    let (status, _, bytes) = download(&app, &auth, id, &[("range", "bytes=150000-"), ("if-range", "\"stale\"")]).await;
+    assert_eq!((status, bytes.len()), (StatusCode::OK, content.len()));
+    let (status, headers, _) = download(&app, &auth, id, &[("range", "bytes=200000-")]).await;
+    assert_eq!((status, headers["content-range"].to_str().unwrap()), (StatusCode::RANGE_NOT_SATISFIABLE, "bytes */200000"));
+
+    let (status, _, _) = download(&app, &auth, "missing", &[]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn attachments_require_a_session_and_respect_the_size_limit() {
+    let (app, dir) = app_with_attachment_dir(1000);
+    let (status, _) = app.upload("Bearer wrong", "a.txt", "text/plain", b"hello").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let auth = app.login("alice").await.1;
+    let (status, body) = app.upload(&auth, "big.bin", "text/plain", &[0u8; 1001]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("attachment.too_large")));
+    let (status, body) = app.upload(&auth, "ok.bin", "text/plain", &[0u8; 1000]).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    // 超限的文件不留在磁盘上
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn only_the_uploader_and_conversation_participants_can_download() {
+    let (app, _) = app_with_attachment_dir(1024);
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let (carol, carol_auth) = app.login("carol").await;
+    let (_, mallory_auth) = app.login("mallory").await;
+    let private = app.upload_id(&alice_auth, "a.txt", "text/plain", b"private").await;
+    let shared = app.upload_id(&alice_auth, "b.txt", "text/plain", b"group").await;
+
+    // 还没有发出去的附件只有上传者能下载，其他人看到的与附件不存在相同
+    assert_eq!(download(&app, &alice_auth, &private, &[]).await.0, StatusCode::OK);
+    assert_eq!(download(&app, &bob_auth, &private, &[]).await.0, StatusCode::NOT_FOUND);
+    let (status, body) = app.call(&bob_auth, http::Method::POST, &format!("/attachments/{private}/url"), None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("attachment.not_found")));
+
+    // 私聊中发出后接收者可以下载，群里发出后群成员可以下载
+    app.post_as(&alice, "/send-message", serde_json::json!({ "receiver_id": bob, "content": format!("文件 {private}"), "message_type": "private" })).await;
+    let group = app.db.create_group(server::workspaces::DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    app.db.add_group_member(&group.id, &carol, "member").unwrap();
+    app.post_as(&alice, "/send-message", serde_json::json!({ "receiver_id": group.id, "content": shared, "message_type": "group" })).await;
+    for (auth, id, expected) in [
+        (&bob_auth, &private, StatusCode::OK),
+        (&carol_auth, &private, StatusCode::NOT_FOUND),
+        (&carol_auth, &shared, StatusCode::OK),
+        (&bob_auth, &shared, StatusCode::NOT_FOUND),
+        (&mallory_auth, &private, StatusCode::NOT_FOUND),
+    ] {
+        assert_eq!(download(&app, auth, id, &[]).await.0, expected);
+    }
+}
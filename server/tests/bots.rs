@@ -0,0 +1,101 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{settings::Settings, workspaces::DEFAULT_WORKSPACE};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+fn bot_app(rate_limit_per_minute: u32) -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.rate_limits.bot.requests_per_minute = rate_limit_per_minute;
+    TestApp::with_settings(settings)
+}
+
+async fn as_bot(app: &TestApp, key: &str, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let header = format!("Bearer {key}");
+    app.request_with_headers(method, path, body, &[("authorization", header.as_str())]).await
+}
+
+#[tokio::test]
+async fn bot_posts_into_group_with_send_key() {
+    let app = bot_app(0);
+    let alice = app.register("alice", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "构建通知", &alice).unwrap();
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::POST, "/admin/bots", Some(json!({ "name": "ci-bot", "scope": "send" }))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let bot_id = body["bot_id"].as_str().unwrap().to_string();
+    let send_key = body["api_key"].as_str().unwrap().to_string();
+
+    let message = json!({ "receiver_id": group.id, "content": "构建通过 ✅", "message_type": "group" });
+    // 加入群聊前不能发送
+    let (status, _) = as_bot(&app, &send_key, Method::POST, "/bot/messages", Some(message.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = app.call(ADMIN_AUTH, Method::PUT, &format!("/admin/bots/{bot_id}/groups/{}", group.id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = as_bot(&app, &send_key, Method::POST, "/bot/messages", Some(message)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // send 密钥不能读取；签发 read 密钥后可以读到群消息
+    let path = format!("/bot/messages?peer_id={}", group.id);
+    let (status, _) = as_bot(&app, &send_key, Method::GET, &path, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (_, body) = app.call(ADMIN_AUTH, Method::POST, &format!("/admin/bots/{bot_id}/keys"), Some(json!({ "scope": "read" }))).await;
+    let read_key = body["api_key"].as_str().unwrap().to_string();
+    let (status, body) = as_bot(&app, &read_key, Method::GET, &path, None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["messages"][0]["content"], "构建通过 ✅");
+    assert_eq!(body["messages"][0]["sender_id"], bot_id.as_str());
+
+    // 同一秒内的消息按 next_cursor 翻页时不会漏掉
+    let (status, _) = as_bot(&app, &send_key, Method::POST, "/bot/messages", Some(json!({ "receiver_id": group.id, "content": "部署完成", "message_type": "group" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    app.db.0.lock().unwrap().execute("UPDATE messages SET created_at = 1000", []).unwrap();
+    let (_, body) = as_bot(&app, &read_key, Method::GET, &format!("{path}&limit=1"), None).await;
+    assert_eq!(body["messages"][0]["content"], "部署完成");
+    let cursor = body["next_cursor"].as_str().unwrap();
+    let (_, body) = as_bot(&app, &read_key, Method::GET, &format!("{path}&limit=1&cursor={cursor}"), None).await;
+    assert_eq!(body["messages"][0]["content"], "构建通过 ✅");
+}
+
+#[tokio::test]
+async fn revoked_and_unknown_keys_are_rejected() {
+    let app = bot_app(0);
+    let (_, body) = app.call(ADMIN_AUTH, Method::POST, "/admin/bots", Some(json!({ "name": "notifier", "scope": "read" }))).await;
+    let key = body["api_key"].as_str().unwrap().to_string();
+    let key_id = body["key_id"].as_str().unwrap().to_string();
+
+    let (status, _) = as_bot(&app, &key, Method::GET, "/bot/messages?peer_id=nobody", None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/bots/keys/{key_id}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = as_bot(&app, &key, Method::GET, "/bot/messages?peer_id=nobody", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = as_bot(&app, "ylb_forged", Method::GET, "/bot/messages?peer_id=nobody", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 机器人账号不能用密码登录
+    let (status, _) = app.post("/login", json!({ "username": "notifier", "password": "" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn each_key_is_rate_limited() {
+    let app = bot_app(2);
+    let (_, body) = app.call(ADMIN_AUTH, Method::POST, "/admin/bots", Some(json!({ "name": "chatty", "scope": "read" }))).await;
+    let key = body["api_key"].as_str().unwrap().to_string();
+
+    for _ in 0..2 {
+        let (status, _) = as_bot(&app, &key, Method::GET, "/bot/messages?peer_id=nobody", None).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, _) = as_bot(&app, &key, Method::GET, "/bot/messages?peer_id=nobody", None).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+}
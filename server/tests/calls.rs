@@ -0,0 +1,138 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use common::e2e::{TestServer, WsClient};
+use common::TestApp;
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use server::{settings::Settings, AppState, DbPool};
+use sha1::Sha1;
+
+// 下一个指定类型的事件，跳过其间的其他推送
+async fn next_of(ws: &mut WsClient, event_type: &str) -> Value {
+    loop {
+        let event = ws.next_event().await;
+        if event["type"] == event_type {
+            return event;
+        }
+    }
+}
+
+fn offer(call_id: &str, receiver_id: &str) -> Value {
+    json!({ "type": "call_offer", "call_id": call_id, "receiver_id": receiver_id, "media": "video", "sdp": { "type": "offer", "sdp": "v=0" } })
+}
+
+#[tokio::test]
+async fn call_signaling_is_relayed_between_participants() {
+    let server = TestServer::start().await;
+    let alice = server.app().register("alice", "secret").await;
+    let bob = server.app().register("bob", "secret").await;
+    let carol = server.app().register("carol", "secret").await;
+    let mut alice_ws = server.ws(&alice).await;
+    let mut bob_ws = server.ws(&bob).await;
+    let mut carol_ws = server.ws(&carol).await;
+
+    alice_ws.send(offer("c1", &bob)).await;
+    let event = bob_ws.next_event().await;
+    assert_eq!((&event["type"], &event["caller_id"], &event["media"]), (&json!("call_offer"), &json!(alice), &json!("video")));
+    assert_eq!(event["sdp"]["sdp"], "v=0");
+    bob_ws.send(json!({ "type": "call_answer", "call_id": "c1", "sdp": { "type": "answer", "sdp": "v=0" } })).await;
+    assert_eq!(alice_ws.next_event().await["type"], "call_answer");
+    bob_ws.send(json!({ "type": "ice_candidate", "call_id": "c1", "candidate": { "candidate": "candidate:1 1 udp" } })).await;
+    assert_eq!(alice_ws.next_event().await["candidate"]["candidate"], "candidate:1 1 udp");
+
+    // 通话中的用户占线，不是参与者的连接不能介入
+    carol_ws.send(offer("c2", &bob)).await;
+    assert_eq!(carol_ws.next_event().await["reason"], "busy");
+    carol_ws.send(json!({ "type": "call_end", "call_id": "c1" })).await;
+    assert_eq!(next_of(&mut carol_ws, "error").await["code"], "call.not_found");
+    alice_ws.send(offer("c3", &carol)).await;
+    assert_eq!(alice_ws.next_event().await["code"], "call.already_in_call");
+
+    alice_ws.send(json!({ "type": "call_end", "call_id": "c1" })).await;
+    // bob 之前收到了 carol 的未接来电
+    assert_eq!(next_of(&mut bob_ws, "call_end").await, json!({ "type": "call_end", "call_id": "c1", "reason": "hangup" }));
+    assert_eq!(alice_ws.next_event().await["reason"], "hangup");
+    alice_ws.send(json!({ "type": "ice_candidate", "call_id": "c1", "candidate": {} })).await;
+    assert_eq!(alice_ws.next_event().await["code"], "call.not_found");
+
+    // 只订阅群聊、没有 identify 的连接不能发起通话
+    let mut anonymous = server.ws_raw("zh-CN").await;
+    anonymous.send(json!({ "list_of_group_chats": [] })).await;
+    anonymous.send(offer("c4", &bob)).await;
+    assert_eq!(anonymous.next_event().await["code"], "ws.not_identified");
+}
+
+#[tokio::test]
+async fn unanswered_calls_leave_missed_call_messages() {
+    let mut settings = Settings::default();
+    settings.calls.ring_timeout_secs = 1;
+    let server = TestServer::with_settings(settings).await;
+    let alice = server.app().register("alice", "secret").await;
+    let bob = server.app().register("bob", "secret").await;
+    let carol = server.app().register("carol", "secret").await;
+    let mut alice_ws = server.ws(&alice).await;
+    let mut bob_ws = server.ws(&bob).await;
+
+    // 超时未接：双方收到 call_end，被叫方收到未接来电消息
+    alice_ws.send(offer("c1", &bob)).await;
+    assert_eq!(bob_ws.next_event().await["type"], "call_offer");
+    assert_eq!(bob_ws.next_event().await["reason"], "timeout");
+    assert_eq!(alice_ws.next_event().await["reason"], "timeout");
+    let missed = next_of(&mut bob_ws, "message").await;
+    assert_eq!((&missed["sender_id"], &missed["content"]), (&json!(alice), &json!("未接视频通话")));
+    assert_eq!(missed["entities"], json!([{ "type": "call", "offset": 0, "length": 6, "call_id": "c1", "media": "video" }]));
+
+    // 被叫方拒接不留未接来电，对方不在线时立即结束并留下未接来电
+    alice_ws.send(offer("c2", &bob)).await;
+    assert_eq!(bob_ws.next_event().await["type"], "call_offer");
+    bob_ws.send(json!({ "type": "call_end", "call_id": "c2" })).await;
+    assert_eq!(next_of(&mut alice_ws, "call_end").await["reason"], "declined");
+    alice_ws.send(json!({ "type": "call_offer", "call_id": "c3", "receiver_id": carol })).await;
+    assert_eq!(next_of(&mut alice_ws, "call_end").await["reason"], "unavailable");
+
+    let db = &server.state.db_pool;
+    let to_bob = db.get_unread_messages("default", &bob).unwrap();
+    assert_eq!(to_bob.len(), 1);
+    let to_carol = db.get_unread_messages("default", &carol).unwrap();
+    assert_eq!((to_carol[0].content.as_str(), to_carol[0].entities[0].media.as_deref()), ("未接语音通话", Some("audio")));
+}
+
+#[tokio::test]
+async fn ice_servers_include_time_limited_turn_credentials() {
+    let mut settings = Settings::default();
+    settings.calls.stun_urls = vec!["stun:stun.example.com:3478".into()];
+    settings.calls.turn_urls = vec!["turn:turn.example.com:3478?transport=udp".into()];
+    settings.calls.turn_secret = "turn-secret".into();
+    settings.calls.turn_ttl_secs = 600;
+    let app = TestApp::with_state(&AppState::new(DbPool::in_memory().unwrap(), settings));
+    let alice = app.register("alice", "secret").await;
+    let (_, body) = app.post("/login", json!({ "username": "alice", "password": "secret" })).await;
+    let auth = format!("Bearer {}", body["token"].as_str().unwrap());
+
+    let (status, body) = app.request_with_headers(Method::GET, "/calls/ice-servers", None, &[("authorization", &auth)]).await;
+    assert_eq!((status, &body["ttl"]), (StatusCode::OK, &json!(600)));
+    assert_eq!(body["ice_servers"][0], json!({ "urls": ["stun:stun.example.com:3478"] }));
+    let turn = &body["ice_servers"][1];
+    assert_eq!(turn["urls"], json!(["turn:turn.example.com:3478?transport=udp"]));
+    // 用户名为过期时间和用户ID，TURN 服务器用共享密钥重新计算密码来验证
+    let username = turn["username"].as_str().unwrap();
+    let (expires_at, user_id) = username.split_once(':').unwrap();
+    assert_eq!(user_id, alice);
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
+    assert!((expires_at.parse::<i64>().unwrap() - now - 600).abs() <= 5);
+    let mut mac = Hmac::<Sha1>::new_from_slice(b"turn-secret").unwrap();
+    mac.update(username.as_bytes());
+    assert_eq!(turn["credential"], BASE64.encode(mac.finalize().into_bytes()));
+
+    let (status, _) = app.request(Method::GET, "/calls/ice-servers", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    // 没有配置 TURN 共享密钥时只下发 STUN
+    let app = TestApp::new();
+    app.register("bob", "secret").await;
+    let (_, body) = app.post("/login", json!({ "username": "bob", "password": "secret" })).await;
+    let auth = format!("Bearer {}", body["token"].as_str().unwrap());
+    let (_, body) = app.request_with_headers(Method::GET, "/calls/ice-servers", None, &[("authorization", &auth)]).await;
+    assert_eq!((&body["ice_servers"], &body["ttl"]), (&json!([]), &json!(0)));
+}
@@ -0,0 +1,64 @@
+use futures_util::future::BoxFuture;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use server::{outbound, settings::Settings, spawn_cluster, AppState, Cluster, DbPool, Envelope, MessageBus};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+// 进程内的总线替身，模拟同一个 Redis 频道
+struct MemoryBus(broadcast::Sender<Envelope>);
+
+impl MessageBus for MemoryBus {
+    fn publish<'a>(&'a self, envelope: &'a Envelope) -> BoxFuture<'a, Result<(), String>> {
+        let _ = self.0.send(envelope.clone());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn subscribe(&self) -> BoxFuture<'_, Result<BoxStream<'static, Envelope>, String>> {
+        let rx = self.0.subscribe();
+        Box::pin(async move {
+            let events = stream::unfold(rx, |mut rx| async move { rx.recv().await.ok().map(|e| (e, rx)) });
+            Ok(events.boxed())
+        })
+    }
+}
+
+// 共享数据库和总线的一个服务器实例
+fn instance(db: &DbPool, bus: &Arc<MemoryBus>) -> AppState {
+    let mut state = AppState::new(db.clone(), Settings::default());
+    state.cluster = Cluster::new(Some(bus.clone()), Duration::from_millis(50));
+    spawn_cluster(state.clone());
+    state
+}
+
+async fn wait_until(condition: impl Fn() -> bool) -> bool {
+    for _ in 0..40 {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn instances_share_fan_out_and_presence() {
+    let db = DbPool::in_memory().unwrap();
+    let bus = Arc::new(MemoryBus(broadcast::channel(64).0));
+    let a = instance(&db, &bus);
+    let b = instance(&db, &bus);
+
+    // bob 连接在实例 B 上，实例 A 通过心跳得知他在线
+    let (tx, mut rx) = outbound::channel(16);
+    b.attach_client("client-1", "bob", tx);
+    assert!(wait_until(|| a.is_online("bob")).await);
+
+    // 从实例 A 发给 bob 的推送经由总线送达
+    assert!(!a.send_to_user("bob", "你好".into()));
+    let received = tokio::time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap();
+    assert_eq!(received, "你好");
+
+    // 连接关闭后 B 的心跳不再包含 bob，A 上的在线状态随之过期
+    drop(rx);
+    assert!(wait_until(|| !a.is_online("bob")).await);
+}
@@ -0,0 +1,173 @@
+//! 端到端测试工具：在本地随机端口上启动完整的 HTTP + WebSocket 服务（数据库文件放在临时目录），
+//! 通过 Rust 客户端 `ApiClient` 和原始 WebSocket 连接驱动。新功能的端到端用例在 tests/e2e.rs 中扩展
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use server::{router, settings::Settings, AppState, DbPool};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{client::IntoClientRequest, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use yueling_client::{ApiClient, Session};
+
+use super::TestApp;
+
+/// 测试账号的统一密码
+pub const PASSWORD: &str = "secret";
+
+/// 监听本地随机端口的完整服务，离开作用域时删除临时数据库
+pub struct TestServer {
+    pub addr: SocketAddr,
+    pub state: AppState,
+    dir: Option<PathBuf>,
+}
+
+impl TestServer {
+    /// 使用默认配置和临时目录中的新数据库启动
+    pub async fn start() -> Self {
+        Self::with_settings(Settings::default()).await
+    }
+
+    /// 使用指定配置和临时目录中的新数据库启动
+    pub async fn with_settings(settings: Settings) -> Self {
+        let dir = std::env::temp_dir().join(format!("yueling-e2e-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("创建临时目录失败");
+        let db = DbPool::new(&dir.join("yueling.db").to_string_lossy()).expect("创建数据库失败");
+        let mut server = Self::with_state(AppState::new(db, settings)).await;
+        server.dir = Some(dir);
+        server
+    }
+
+    /// 在已有的应用状态上启动（需要替换推送提供方等组件时使用）
+    pub async fn with_state(state: AppState) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(state.clone()).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        Self { addr, state, dir: None }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// 未登录的客户端
+    pub fn client(&self) -> ApiClient {
+        ApiClient::new(self.url())
+    }
+
+    /// 不经网络直接调用路由，用于客户端没有封装的接口
+    pub fn app(&self) -> TestApp {
+        TestApp::with_state(&self.state)
+    }
+
+    /// 注册并登录，返回已带会话令牌的客户端
+    pub async fn signup(&self, username: &str) -> (ApiClient, Session) {
+        let mut client = self.client();
+        client.register(username, PASSWORD, None).await.expect("注册失败");
+        let session = client.login(username, PASSWORD).await.expect("登录失败");
+        (client, session)
+    }
+
+    /// 以 user_id 登记的 WebSocket 连接（直接在数据库中为其创建会话），返回前等待服务器处理完 identify
+    pub async fn ws(&self, user_id: &str) -> WsClient {
+        self.ws_identify(json!({ "type": "identify", "user_id": user_id, "token": self.token(user_id) })).await
+    }
+
+    /// 以某台设备的身份登记的 WebSocket 连接
+    pub async fn ws_device(&self, user_id: &str, device_id: &str) -> WsClient {
+        self.ws_identify(json!({ "type": "identify", "user_id": user_id, "device_id": device_id, "token": self.token(user_id) })).await
+    }
+
+    /// 为用户创建会话，返回会话令牌
+    pub fn token(&self, user_id: &str) -> String {
+        self.app().session(user_id).trim_start_matches("Bearer ").to_string()
+    }
+
+    /// 尚未发送任何帧的 WebSocket 连接，升级请求带上 Accept-Language
+    pub async fn ws_raw(&self, accept_language: &str) -> WsClient {
+        let mut request = format!("ws://{}/ws", self.addr).into_client_request().unwrap();
+        request.headers_mut().insert("accept-language", accept_language.parse().unwrap());
+        let (socket, _) = connect_async(request).await.unwrap();
+        WsClient { socket }
+    }
+
+    /// 连接 /ws 以外的 WebSocket 路径（如 /relay/{id}），升级请求带上指定的请求头；服务器拒绝升级时返回响应的状态码
+    pub async fn ws_at(&self, path: &str, headers: &[(&str, &str)]) -> Result<WsClient, u16> {
+        let mut request = format!("ws://{}{}", self.addr, path).into_client_request().unwrap();
+        for (name, value) in headers {
+            request.headers_mut().insert(http::HeaderName::from_bytes(name.as_bytes()).unwrap(), value.parse().unwrap());
+        }
+        match connect_async(request).await {
+            Ok((socket, _)) => Ok(WsClient { socket }),
+            Err(tokio_tungstenite::tungstenite::Error::Http(response)) => Err(response.status().as_u16()),
+            Err(e) => panic!("WebSocket 连接失败: {e}"),
+        }
+    }
+
+    async fn ws_identify(&self, identify: Value) -> WsClient {
+        let mut client = self.ws_raw("zh-CN").await;
+        client.send(identify).await;
+        // identify 在连接任务中异步处理
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(dir) = &self.dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// 原始 WebSocket 测试连接，按帧收发 JSON
+pub struct WsClient {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+}
+
+impl WsClient {
+    pub async fn send(&mut self, frame: Value) {
+        self.socket.send(Message::text(frame.to_string())).await.unwrap();
+    }
+
+    /// 发送原始帧（二进制帧、非 JSON 文本等）
+    pub async fn send_frame(&mut self, frame: Message) {
+        self.socket.send(frame).await.unwrap();
+    }
+
+    /// 下一个 JSON 事件，2 秒内没有则失败
+    pub async fn next_event(&mut self) -> Value {
+        loop {
+            if let Message::Text(text) = self.next_frame().await {
+                return serde_json::from_str(&text).unwrap();
+            }
+        }
+    }
+
+    /// 等待服务器发来的关闭帧，返回关闭码和原因
+    pub async fn close_frame(&mut self) -> (u16, String) {
+        loop {
+            if let Message::Close(Some(frame)) = self.next_frame().await {
+                return (frame.code.into(), frame.reason.to_string());
+            }
+        }
+    }
+
+    /// 200 毫秒内没有收到任何帧
+    pub async fn assert_silent(&mut self) {
+        assert!(tokio::time::timeout(Duration::from_millis(200), self.socket.next()).await.is_err());
+    }
+
+    /// 下一帧（包括二进制帧和关闭帧），2 秒内没有则失败
+    pub async fn next_frame(&mut self) -> Message {
+        tokio::time::timeout(Duration::from_secs(2), self.socket.next()).await.unwrap().unwrap().unwrap()
+    }
+}
@@ -0,0 +1,155 @@
+//! 集成测试公共工具：基于内存数据库启动完整路由；e2e 子模块在本地端口上启动真实服务
+
+#![allow(dead_code)]
+
+pub mod e2e;
+
+use axum::{
+    body::Body,
+    http::{request, Method, Request, StatusCode},
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use server::{datetime::unix_now, register_routes, router, settings::Settings, AppState, DbPool};
+use tower::ServiceExt;
+
+const BOUNDARY: &str = "yueling-test-boundary";
+
+/// 测试用应用：完整路由 + 内存数据库
+pub struct TestApp {
+    pub router: Router,
+    pub db: DbPool,
+}
+
+impl TestApp {
+    /// 使用全新的内存数据库和默认配置构建应用
+    pub fn new() -> Self {
+        Self::with_settings(Settings::default())
+    }
+
+    /// 使用全新的内存数据库和指定配置构建应用
+    pub fn with_settings(settings: Settings) -> Self {
+        let db = DbPool::in_memory().expect("创建内存数据库失败");
+        let router = register_routes(db.clone(), settings);
+        Self { router, db }
+    }
+
+    /// 基于已有的应用状态构建应用（测试需要直接驱动后台任务时使用）
+    pub fn with_state(state: &AppState) -> Self {
+        Self { router: router(state.clone()), db: state.db_pool.clone() }
+    }
+
+    /// 发送请求并解析JSON响应
+    pub async fn request(&self, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        self.request_with_headers(method, path, body, &[]).await
+    }
+
+    /// 携带额外请求头发送请求并解析JSON响应
+    pub async fn request_with_headers(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<Value>,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, Value) {
+        let mut builder = Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let request = match body {
+            Some(body) => builder
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => builder.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let json = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+        (status, json)
+    }
+
+    pub async fn get(&self, path: &str) -> (StatusCode, Value) {
+        self.request(Method::GET, path, None).await
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> (StatusCode, Value) {
+        self.request(Method::POST, path, Some(body)).await
+    }
+
+    /// 以该用户的身份发送POST请求：直接在数据库中为其创建会话，携带会话令牌。
+    /// 返回的 future 只借用应用，可以在闭包中以临时的用户ID调用
+    pub fn post_as<'a>(&'a self, user_id: &str, path: &str, body: Value) -> impl Future<Output = (StatusCode, Value)> + use<'a> {
+        let (auth, path) = (self.session(user_id), path.to_string());
+        async move { self.request_with_headers(Method::POST, &path, Some(body), &[("authorization", &auth)]).await }
+    }
+
+    /// 为用户创建会话，返回 Authorization 请求头的值
+    pub fn session(&self, user_id: &str) -> String {
+        let token = self.db.create_session(user_id, None, 3600, unix_now()).expect("创建会话失败");
+        format!("Bearer {token}")
+    }
+
+    /// 注册用户并返回用户ID
+    pub async fn register(&self, username: &str, password: &str) -> String {
+        let (status, body) = self
+            .post("/register", serde_json::json!({ "username": username, "password": password }))
+            .await;
+        assert_eq!(status, StatusCode::OK, "注册失败: {body}");
+        body["user_id"].as_str().unwrap().to_string()
+    }
+
+    /// 注册并登录（密码为 secret），返回用户ID和 Authorization 请求头的值
+    pub async fn login(&self, username: &str) -> (String, String) {
+        let user_id = self.register(username, "secret").await;
+        let (status, body) = self
+            .post("/login", serde_json::json!({ "username": username, "password": "secret" }))
+            .await;
+        assert_eq!(status, StatusCode::OK, "登录失败: {body}");
+        (user_id, format!("Bearer {}", body["token"].as_str().unwrap()))
+    }
+
+    /// 携带 Authorization 请求头发送请求
+    pub async fn call(&self, auth: &str, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+        self.request_with_headers(method, path, body, &[("authorization", auth)]).await
+    }
+
+    /// 以只含一个文件字段的 multipart 表单发送请求（方法、路径和请求头由 request 给出），返回状态码和JSON响应
+    pub async fn multipart(
+        &self,
+        request: request::Builder,
+        field: &str,
+        filename: &str,
+        content_type: &str,
+        content: &[u8],
+    ) -> (StatusCode, Value) {
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"{field}\"; filename=\"{filename}\"\r\nContent-Type: {content_type}\r\n\r\n"
+        ).into_bytes();
+        body.extend_from_slice(content);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+        let request = request
+            .header("content-type", format!("multipart/form-data; boundary={BOUNDARY}"))
+            .body(Body::from(body))
+            .unwrap();
+        let response = self.router.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    /// 上传附件，返回状态码和JSON响应
+    pub async fn upload(&self, auth: &str, filename: &str, content_type: &str, content: &[u8]) -> (StatusCode, Value) {
+        self.multipart(Request::post("/attachments").header("authorization", auth), "file", filename, content_type, content).await
+    }
+
+    /// 上传附件并返回附件ID
+    pub async fn upload_id(&self, auth: &str, filename: &str, content_type: &str, content: &[u8]) -> String {
+        let (status, body) = self.upload(auth, filename, content_type, content).await;
+        assert_eq!(status, StatusCode::OK, "上传附件失败: {body}");
+        body["attachment"]["id"].as_str().unwrap().to_string()
+    }
+}
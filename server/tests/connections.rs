@@ -0,0 +1,81 @@
+mod common;
+
+use std::time::Duration;
+
+use axum::http::{Method, StatusCode};
+use common::e2e::TestServer;
+use serde_json::{json, Value};
+use server::{outbound, settings::Settings};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+#[tokio::test]
+async fn admins_can_inspect_and_close_connections() {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    let server = TestServer::with_settings(settings).await;
+    let app = server.app();
+    let alice = app.register("alice", "secret").await;
+    let (_, body) = app.post("/login", json!({ "username": "alice", "password": "secret", "device_name": "Phone", "platform": "ios" })).await;
+    let device_id = body["device_id"].as_str().unwrap().to_string();
+
+    let mut phone = server.ws_device(&alice, &device_id).await;
+    let _anonymous = server.ws_raw("zh-CN").await;
+    phone.send(json!({ "type": "bogus" })).await;
+    assert_eq!(phone.next_event().await["type"], "error");
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/connections", None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("admin.connections_listed")));
+    let connections = body["connections"].as_array().unwrap();
+    assert_eq!(connections.len(), 2);
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, &format!("/admin/connections?user_id={alice}"), None).await;
+    let connection = &body["connections"][0];
+    assert_eq!((connection["user_id"].as_str(), connection["device_id"].as_str()), (Some(alice.as_str()), Some(device_id.as_str())));
+    assert_eq!(connection["ip"], "127.0.0.1");
+    // identify 和 bogus 两帧，回复一个错误事件
+    assert_eq!((connection["frames_received"].as_u64(), connection["frames_sent"].as_u64()), (Some(2), Some(1)));
+    assert!(connection["last_activity_at"].as_i64() >= connection["connected_at"].as_i64());
+    assert_eq!(connection["queued_events"], 0);
+
+    let client_id = connection["client_id"].as_str().unwrap();
+    let (status, body) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/connections/{client_id}"), None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("admin.connection_closed")));
+    assert_eq!(phone.close_frame().await, (4003, "closed_by_admin".to_string()));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/connections", None).await;
+    assert!(body["connections"].as_array().unwrap().iter().all(|c| c["client_id"] != client_id));
+    assert!(!server.state.is_online(&alice));
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/connections/{client_id}"), None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("admin.connection_not_found")));
+    let (status, _) = app.get("/admin/connections").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn full_send_queues_drop_low_priority_events_before_messages() {
+    let (tx, mut rx) = outbound::channel(2);
+    let event = |kind: &str, n: u32| json!({ "type": kind, "n": n }).to_string();
+    tx.send(event("profile_updated", 1)).unwrap();
+    tx.send(event("message", 2)).unwrap();
+    // 队列已满：新消息挤掉低优先级事件，新的低优先级事件直接丢弃
+    tx.send(event("message", 3)).unwrap();
+    tx.send(event("typing", 4)).unwrap();
+    assert_eq!(tx.len(), 2);
+    // 没有可以挤掉的事件时丢弃新消息
+    tx.send(event("message", 5)).unwrap();
+    let received: Vec<_> = [rx.recv().await.unwrap(), rx.recv().await.unwrap()].into_iter()
+        .map(|payload| serde_json::from_str::<Value>(&payload).unwrap()["n"].as_u64().unwrap())
+        .collect();
+    assert_eq!(received, [2, 3]);
+    // 不是 JSON 的负载（群聊转发的原始内容）按消息处理
+    tx.send("你好".into()).unwrap();
+    assert_eq!(rx.try_recv().unwrap(), "你好");
+    assert!(rx.try_recv().is_err());
+
+    // 写任务结束后不再接受事件
+    drop(rx);
+    assert!(tx.is_closed());
+    assert!(tx.send(event("message", 6)).is_err());
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{discovery::email_hash, settings::Settings, AppState, DbPool};
+
+async fn lookup(app: &TestApp, auth: &str, body: Value) -> (StatusCode, Value) {
+    app.request_with_headers(Method::POST, "/users/lookup", Some(body), &[("authorization", auth)]).await
+}
+
+async fn search(app: &TestApp, auth: &str, query: &str) -> (StatusCode, Value) {
+    app.request_with_headers(Method::POST, "/search-users", Some(json!({ "query": query })), &[("authorization", auth)]).await
+}
+
+#[tokio::test]
+async fn contacts_are_matched_by_username_and_email_hash() {
+    // 邮箱加密保存时也能按哈希匹配
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_emails = true;
+    let db = DbPool::in_memory().unwrap().with_encryption(&settings.security).unwrap();
+    let app = TestApp::with_state(&AppState::new(db, settings));
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, _) = app.login("bob").await;
+    app.db.update_user_info(&bob, "bob", "Bob@Example.com").unwrap();
+    let (carol, _) = app.login("carol").await;
+    app.db.delete_user(&carol, 0).unwrap();
+
+    let bob_hash = email_hash(" bob@example.COM ");
+    let (status, body) = lookup(&app, &alice_auth, json!({
+        "usernames": ["BOB", "nobody", "carol", "alice"],
+        "email_hashes": [bob_hash, email_hash("missing@example.com"), email_hash(&format!("{alice}@local"))],
+    })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["users"], json!([
+        { "handle": "BOB", "user_id": bob, "username": "bob", "display_name": null },
+        { "handle": bob_hash, "user_id": bob, "username": "bob", "display_name": null },
+    ]));
+
+    let (status, _) = app.request(Method::POST, "/users/lookup", Some(json!({ "usernames": ["bob"] }))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let too_many: Vec<String> = (0..1001).map(|i| format!("user{i}")).collect();
+    let (status, body) = lookup(&app, &alice_auth, json!({ "usernames": too_many })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("friend.too_many_handles")));
+}
+
+#[tokio::test]
+async fn discovery_privacy_hides_users_from_lookup_and_search() {
+    let app = TestApp::new();
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, _) = app.login("bob").await;
+    let (_, carol_auth) = app.login("carol").await;
+    let request = app.db.send_friend_request(&alice, "bob").unwrap();
+    app.db.respond_to_friend_request(&request.id, &bob, "accepted").unwrap();
+
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "discovery_privacy": "friends" })), &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = lookup(&app, &alice_auth, json!({ "usernames": ["bob"] })).await;
+    assert_eq!(body["users"][0]["user_id"], bob);
+    let (_, body) = lookup(&app, &carol_auth, json!({ "usernames": ["bob"] })).await;
+    assert_eq!(body["users"], json!([]));
+    // 按用户名搜索同样需要登录并遵守该设置
+    let (status, _) = app.post("/search-users", json!({ "query": "bob" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (_, body) = search(&app, &alice_auth, "bob").await;
+    assert_eq!(body["users"][0]["id"], bob);
+    let (_, body) = search(&app, &carol_auth, "bob").await;
+    assert_eq!(body["users"], json!([]));
+
+    app.request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "discovery_privacy": "nobody" })), &[("authorization", &app.session(&bob))]).await;
+    let (_, body) = lookup(&app, &alice_auth, json!({ "usernames": ["bob"] })).await;
+    assert_eq!(body["users"], json!([]));
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Method, Request, StatusCode},
+};
+use base64::Engine;
+use common::TestApp;
+use http_body_util::BodyExt;
+use serde_json::{json, Value};
+use server::settings::Settings;
+use server::workspaces::DEFAULT_WORKSPACE;
+use tower::ServiceExt;
+
+// 2023-11-14 22:13:20 UTC
+const SENT_AT: i64 = 1_700_000_000;
+
+fn app() -> TestApp {
+    let mut settings = Settings::default();
+    let dir = std::env::temp_dir().join(format!("yueling-conversation-export-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    settings.attachments.strip_image_metadata = false;
+    settings.attachments.export_inline_max_bytes = 1024;
+    settings.server.public_url = "https://chat.example.com/".into();
+    TestApp::with_settings(settings)
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str, content: &str, message_type: &str, format: &str) {
+    let (status, body) = app.post_as(sender, "/send-message", json!({
+        "sender_id": sender,
+        "receiver_id": receiver,
+        "content": content,
+        "message_type": message_type,
+        "format": format,
+    })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+}
+
+async fn export(app: &TestApp, auth: &str, peer_id: &str, query: &str) -> (StatusCode, http::HeaderMap, String) {
+    let request = Request::get(format!("/conversations/{peer_id}/export{query}"))
+        .header("authorization", auth)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.router.clone().oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let bytes = body.collect().await.unwrap().to_bytes();
+    (parts.status, parts.headers, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn private_conversations_export_to_standalone_html() {
+    let app = app();
+    let (alice, auth) = app.login("alice").await;
+    let (bob, _) = app.login("bob").await;
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{alice}/settings"), Some(json!({ "utc_offset": "+08:00" })), &[("authorization", &app.session(&alice))]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let photo = b"\x89PNG\r\n\x1a\nsmall picture".to_vec();
+    let photo_id = app.upload_id(&auth, "photo.png", "image/png", &photo).await;
+    let report_id = app.upload_id(&auth, "report.pdf", "application/pdf", &[7u8; 2048]).await;
+    send(&app, &alice, &bob, "**你好** <script>alert(1)</script> 见 [说明](https://example.com)", "private", "markdown").await;
+    send(&app, &bob, &alice, &format!("照片 {photo_id} 和报告 {report_id}"), "private", "plain").await;
+    app.db.0.lock().unwrap().execute("UPDATE messages SET created_at = ?", [SENT_AT]).unwrap();
+
+    let (status, headers, html) = export(&app, &auth, &bob, "").await;
+    assert_eq!(status, StatusCode::OK, "{html}");
+    assert_eq!(headers["content-type"], "text/html; charset=utf-8");
+    assert!(headers["content-disposition"].to_str().unwrap().starts_with("attachment; filename*=UTF-8''yueling-"));
+    assert!(html.contains("<title>与 bob 的聊天记录</title>"));
+    assert!(html.contains("共 2 条消息"));
+    // 时间按导出者的时区显示
+    assert!(html.contains("UTC+08:00"));
+    assert!(html.contains("<time>2023-11-15 06:13:20</time>"));
+    assert!(html.contains("<span class=\"sender\">alice</span>") && html.contains("<span class=\"sender\">bob</span>"));
+    // 格式按区间渲染，消息中的 HTML 被转义
+    assert!(html.contains("<strong>你好</strong> &lt;script&gt;alert(1)&lt;/script&gt; 见 <a href=\"https://example.com\""));
+    assert!(!html.contains("<script>"));
+    // 小图片内嵌，其他附件是带服务器地址的链接
+    let data = base64::engine::general_purpose::STANDARD.encode(&photo);
+    assert!(html.contains(&format!("<img src=\"data:image/png;base64,{data}\" alt=\"photo.png\">")));
+    assert!(html.contains(&format!("<a href=\"https://chat.example.com/attachments/{report_id}\">report.pdf</a>（2.0 KB）")));
+
+    // 同一会话也可以导出为 JSON
+    let (status, headers, body) = export(&app, &auth, &bob, "?format=json").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-type"], "application/json");
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((body["conversation_type"].as_str(), body["messages"].as_array().unwrap().len()), (Some("private"), 2));
+    let attachments: Vec<&str> = body["attachments"].as_array().unwrap().iter().map(|a| a["id"].as_str().unwrap()).collect();
+    assert_eq!(attachments.len(), 2);
+    assert!(attachments.contains(&photo_id.as_str()) && attachments.contains(&report_id.as_str()));
+    assert_eq!(body["attachments"][0]["message_id"], body["messages"][1]["id"]);
+
+    let (status, _, body) = export(&app, &auth, &bob, "?format=pdf").await;
+    let body: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.invalid_export_format")));
+    let (status, _, _) = export(&app, "Bearer wrong", &bob, "").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn group_exports_include_system_messages_for_members_only() {
+    let app = app();
+    let (alice, auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let (_, carol_auth) = app.login("carol").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "读书会 <b>", &alice).unwrap();
+    let (status, body) = app.request_with_headers(
+        Method::POST, &format!("/groups/{}/members", group.id), Some(json!({ "user_id": bob })), &[("authorization", &auth)],
+    ).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    send(&app, &bob, &group.id, "大家好", "group", "plain").await;
+
+    let (status, _, html) = export(&app, &bob_auth, &group.id, "?format=html").await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(html.contains("<title>群聊「读书会 &lt;b&gt;」的聊天记录</title>"));
+    assert!(html.contains("<p class=\"system\">bob 加入了群聊 · "));
+    assert!(html.contains("大家好"));
+    // 时区未设置时按 UTC 显示
+    assert!(html.contains("UTC+00:00"));
+
+    let (status, _, _) = export(&app, &carol_auth, &group.id, "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
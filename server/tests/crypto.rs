@@ -0,0 +1,77 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use server::{
+    conversation::SEALED_PREFIX,
+    CryptoError,
+    CryptoService,
+    ConversationKeys,
+    SEALED_FIELD_PREFIX,
+};
+
+#[test]
+fn tampered_containers_fail_the_integrity_check() {
+    let crypto = CryptoService::new("test-master-key");
+    let sealed = crypto.encrypt(b"{\"theme\":\"dark\"}", b"user-1");
+    assert_eq!(crypto.decrypt(&sealed, b"user-1").unwrap(), b"{\"theme\":\"dark\"}");
+
+    // 截断、拼接、改动任意字节、换附加数据或密钥都在解密前报告为完整性错误
+    let mut flipped = sealed.clone();
+    flipped[0] ^= 1;
+    let mut appended = sealed.clone();
+    appended.extend_from_slice(&sealed);
+    for (data, aad) in [
+        (&sealed[..sealed.len() - 1], &b"user-1"[..]),
+        (&sealed[1..], b"user-1"),
+        (&flipped, b"user-1"),
+        (&appended, b"user-1"),
+        (&sealed, b"user-2"),
+    ] {
+        assert!(matches!(crypto.decrypt(data, aad), Err(CryptoError::Integrity)));
+    }
+    assert!(matches!(CryptoService::new("other").decrypt(&sealed, b"user-1"), Err(CryptoError::Integrity)));
+    assert!(matches!(crypto.decrypt(b"short", b"user-1"), Err(CryptoError::Malformed)));
+}
+
+#[test]
+fn changing_the_epoch_header_is_detected() {
+    let keys = ConversationKeys::new("test-master-key", 0);
+    let sealed = keys.seal("private:a:b", 3, "m1", "你好");
+    assert_eq!(keys.open("private:a:b", "m1", &sealed).unwrap(), "你好");
+
+    let mut data = BASE64.decode(sealed.strip_prefix(SEALED_PREFIX).unwrap()).unwrap();
+    data[3] = 2;
+    let forged = format!("{}{}", SEALED_PREFIX, BASE64.encode(&data));
+    assert!(matches!(keys.open("private:a:b", "m1", &forged), Err(CryptoError::Integrity)));
+
+    // 伪造的超大纪元直接拒绝，不去派生密钥
+    data[..4].copy_from_slice(&u32::MAX.to_be_bytes());
+    let forged = format!("{}{}", SEALED_PREFIX, BASE64.encode(&data));
+    assert!(matches!(keys.open("private:a:b", "m1", &forged), Err(CryptoError::Malformed)));
+    assert!(matches!(keys.open("private:a:c", "m1", &sealed), Err(CryptoError::Integrity)));
+}
+
+#[test]
+fn field_encryption_is_deterministic_per_field_and_key() {
+    let crypto = CryptoService::new("test-master-key");
+    let sealed = crypto.seal_field("email", "alice@example.com");
+    assert!(sealed.starts_with(SEALED_FIELD_PREFIX) && !sealed.contains("alice"));
+    assert_eq!(crypto.seal_field("email", "alice@example.com"), sealed);
+    assert_eq!(crypto.open_field("email", &sealed).unwrap(), "alice@example.com");
+
+    // 不同字段、不同取值或不同主密钥得到的密文互不相同，也不能换字段解密
+    assert_ne!(crypto.seal_field("username", "alice@example.com"), sealed);
+    assert_ne!(crypto.seal_field("email", "bob@example.com"), sealed);
+    assert_ne!(CryptoService::new("other").seal_field("email", "alice@example.com"), sealed);
+    assert!(matches!(crypto.open_field("username", &sealed), Err(CryptoError::Integrity)));
+    // 没有前缀的旧数据原样返回
+    assert_eq!(crypto.open_field("email", "old@example.com").unwrap(), "old@example.com");
+}
+
+#[test]
+fn large_payloads_round_trip_with_fixed_overhead() {
+    let crypto = CryptoService::new("test-master-key");
+    // 基准测试覆盖的最大负载：nonce 12 字节、GCM 标签 16 字节、完整性标签 32 字节
+    let plaintext = vec![0x5a; 1024 * 1024];
+    let sealed = crypto.encrypt(&plaintext, b"user-1");
+    assert_eq!(sealed.len(), plaintext.len() + 12 + 16 + 32);
+    assert_eq!(crypto.decrypt(&sealed, b"user-1").unwrap(), plaintext);
+}
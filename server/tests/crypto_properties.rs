@@ -0,0 +1,61 @@
+use proptest::prelude::*;
+use server::{conversation::SEALED_PREFIX, ConversationKeys, CryptoError, CryptoService, SEALED_FIELD_PREFIX};
+
+// nonce、GCM 认证标签和容器完整性标签的固定开销
+const OVERHEAD: usize = 12 + 16 + 32;
+
+// 任意长度的负载，另外专门覆盖空负载和 AES 分组（16 字节）的整数倍
+fn payload() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![
+        Just(Vec::new()),
+        (1..=64usize).prop_flat_map(|blocks| prop::collection::vec(any::<u8>(), blocks * 16)),
+        prop::collection::vec(any::<u8>(), 0..4096),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn encrypt_round_trips_with_fixed_overhead(plaintext in payload(), aad in prop::collection::vec(any::<u8>(), 0..64)) {
+        let crypto = CryptoService::new("test-master-key");
+        let sealed = crypto.encrypt(&plaintext, &aad);
+        prop_assert_eq!(sealed.len(), plaintext.len() + OVERHEAD);
+        prop_assert_eq!(crypto.decrypt(&sealed, &aad).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn any_flipped_bit_fails_the_integrity_check(plaintext in payload(), position in any::<prop::sample::Index>(), bit in 0..8u8) {
+        let crypto = CryptoService::new("test-master-key");
+        let mut sealed = crypto.encrypt(&plaintext, b"user-1");
+        let index = position.index(sealed.len());
+        sealed[index] ^= 1 << bit;
+        prop_assert!(matches!(crypto.decrypt(&sealed, b"user-1"), Err(CryptoError::Integrity)));
+    }
+
+    #[test]
+    fn decrypting_arbitrary_bytes_fails_without_panicking(data in prop::collection::vec(any::<u8>(), 0..256), aad in prop::collection::vec(any::<u8>(), 0..16)) {
+        let crypto = CryptoService::new("test-master-key");
+        prop_assert!(crypto.decrypt(&data, &aad).is_err());
+    }
+
+    #[test]
+    fn sealed_fields_round_trip(field in "[a-z_]{1,16}", value in ".{0,200}") {
+        let crypto = CryptoService::new("test-master-key");
+        let sealed = crypto.seal_field(&field, &value);
+        prop_assert_eq!(crypto.open_field(&field, &sealed).unwrap(), value);
+    }
+
+    #[test]
+    fn opening_arbitrary_sealed_text_fails_without_panicking(encoded in "[A-Za-z0-9+/=_-]{0,200}") {
+        let crypto = CryptoService::new("test-master-key");
+        prop_assert!(crypto.open_field("email", &(SEALED_FIELD_PREFIX.to_string() + &encoded)).is_err());
+        let keys = ConversationKeys::new("test-master-key", 0);
+        prop_assert!(keys.open("private:a:b", "m1", &(SEALED_PREFIX.to_string() + &encoded)).is_err());
+    }
+
+    #[test]
+    fn conversation_messages_round_trip_in_any_epoch(epoch in 0..64u32, plaintext in ".{0,500}") {
+        let keys = ConversationKeys::new("test-master-key", 0);
+        let sealed = keys.seal("group:g1", epoch, "m1", &plaintext);
+        prop_assert_eq!(keys.open("group:g1", "m1", &sealed).unwrap(), plaintext);
+    }
+}
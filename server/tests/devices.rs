@@ -0,0 +1,153 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::{json, Value};
+use server::{settings::Settings, AppState, DbPool, Email, EmailProvider};
+use std::sync::{Arc, Mutex};
+
+// 记录发出邮件的测试替身
+#[derive(Default)]
+struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl EmailProvider for RecordingMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.sent.lock().unwrap().push(email.clone());
+            Ok(())
+        })
+    }
+}
+
+// 从最后一封邮件中取出 6 位验证码
+fn last_code(mailer: &RecordingMailer) -> String {
+    let sent = mailer.sent.lock().unwrap();
+    let body = &sent.last().unwrap().body;
+    body.split_whitespace()
+        .find(|word| word.len() == 6 && word.chars().all(|c| c.is_ascii_digit()))
+        .unwrap()
+        .to_string()
+}
+
+async fn login(app: &TestApp, device: Value) -> (StatusCode, Value) {
+    let mut body = json!({ "username": "alice", "password": "secret" });
+    body.as_object_mut().unwrap().extend(device.as_object().unwrap().clone());
+    app.post("/login", body).await
+}
+
+async fn as_user(app: &TestApp, token: &str, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let auth = format!("Bearer {token}");
+    app.request_with_headers(method, path, body, &[("authorization", auth.as_str())]).await
+}
+
+// 系统账号发给用户的安全提醒（解析后的事件）
+async fn notices(app: &TestApp, user_id: &str) -> Vec<Value> {
+    let (_, body) = app.post_as(user_id, "/messages/sync", json!({ "user_id": user_id, "last_sync_time": 0, "limit": 50 })).await;
+    body["messages"].as_array().unwrap().iter()
+        .filter(|m| m["sender_id"] == "system" && m["message_type"] == "system")
+        .map(|m| serde_json::from_str(m["content"].as_str().unwrap()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn devices_are_tracked_and_new_ones_trigger_a_notice() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+
+    // 不上报设备信息的旧客户端不记录设备
+    let (status, body) = login(&app, json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["device_id"].is_null());
+
+    let (_, body) = login(&app, json!({ "device_name": "Phone", "platform": "ios" })).await;
+    let phone = body["device_id"].as_str().unwrap().to_string();
+    let token = body["token"].as_str().unwrap().to_string();
+    assert!(notices(&app, &alice).await.is_empty(), "第一台设备不发提醒");
+
+    // 同一设备再次登录不算新设备
+    let (_, body) = login(&app, json!({ "device_id": phone })).await;
+    assert_eq!(body["device_id"], phone.as_str());
+    assert!(notices(&app, &alice).await.is_empty());
+
+    let (_, body) = login(&app, json!({ "device_name": "Laptop", "platform": "web" })).await;
+    let laptop = body["device_id"].as_str().unwrap().to_string();
+    assert_eq!(notices(&app, &alice).await, [json!({
+        "event": "new_device_login", "device_id": laptop, "device_name": "Laptop", "platform": "web",
+    })]);
+
+    let (_, body) = as_user(&app, &token, Method::GET, "/account/devices", None).await;
+    let devices = body["devices"].as_array().unwrap();
+    assert_eq!(devices.len(), 2);
+    assert!(devices.iter().all(|d| d["trusted"] == true));
+
+    let path = format!("/account/devices/{laptop}");
+    let (status, _) = as_user(&app, &token, Method::PUT, &path, Some(json!({ "name": "Work laptop" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = as_user(&app, &token, Method::PUT, &path, Some(json!({ "name": " " }))).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "device.name_empty"));
+    let (_, body) = as_user(&app, &token, Method::GET, "/account/devices", None).await;
+    assert!(body["devices"].as_array().unwrap().iter().any(|d| d["name"] == "Work laptop"));
+
+    // 系统账号不能登录
+    let (status, _) = app.post("/login", json!({ "username": "system", "password": "" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn revoking_a_device_ends_its_sessions() {
+    let app = TestApp::new();
+    app.register("alice", "secret").await;
+    let (_, body) = login(&app, json!({ "device_name": "Phone", "push_token": "tok-1" })).await;
+    let phone_token = body["token"].as_str().unwrap().to_string();
+    let phone = body["device_id"].as_str().unwrap().to_string();
+    let (_, body) = login(&app, json!({ "device_name": "Laptop" })).await;
+    let laptop_token = body["token"].as_str().unwrap().to_string();
+
+    let path = format!("/account/devices/{phone}");
+    let (status, _) = as_user(&app, &laptop_token, Method::DELETE, &path, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = as_user(&app, &phone_token, Method::GET, "/account/devices", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = as_user(&app, &laptop_token, Method::DELETE, &path, None).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::NOT_FOUND, "device.not_found"));
+
+    // 移除的设备再次登录时按新设备登记
+    let (_, body) = login(&app, json!({ "device_id": phone })).await;
+    assert_ne!(body["device_id"], phone.as_str());
+}
+
+#[tokio::test]
+async fn new_devices_need_an_emailed_code() {
+    let mailer = Arc::new(RecordingMailer::default());
+    let mut settings = Settings::default();
+    settings.devices.verify_new_devices = true;
+    let mut state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    state.mailer = Some(mailer.clone());
+    let app = TestApp::with_state(&state);
+    let (status, _) = app.post("/register", json!({ "username": "alice", "password": "secret", "email": "alice@example.com" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = login(&app, json!({ "device_name": "Phone" })).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!((body["device_verification_required"].as_bool(), body["token"].as_str()), (Some(true), None));
+    let phone = body["device_id"].as_str().unwrap().to_string();
+    assert_eq!(mailer.sent.lock().unwrap().last().unwrap().to, "alice@example.com");
+    let code = last_code(&mailer);
+
+    let (status, body) = login(&app, json!({ "device_id": phone, "device_code": "000000x" })).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::UNAUTHORIZED, "device.code_invalid"));
+    let (status, body) = login(&app, json!({ "device_id": phone, "device_code": code })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body["token"].is_string());
+
+    // 已验证的设备之后无需验证码
+    let (status, _) = login(&app, json!({ "device_id": phone })).await;
+    assert_eq!(status, StatusCode::OK);
+    let sent = mailer.sent.lock().unwrap().len();
+    let (status, _) = login(&app, json!({})).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "开启验证后不上报设备也按新设备处理");
+    assert_eq!(mailer.sent.lock().unwrap().len(), sent + 1);
+}
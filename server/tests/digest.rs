@@ -0,0 +1,98 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::json;
+use server::{send_digests, settings::DigestSettings, Email, EmailProvider, PresenceCheck};
+use std::sync::{Arc, Mutex};
+
+// 记录发出邮件的测试替身
+#[derive(Default)]
+struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl EmailProvider for RecordingMailer {
+    fn send<'a>(&'a self, email: &'a Email) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.sent.lock().unwrap().push(email.clone());
+            Ok(())
+        })
+    }
+}
+
+// 一天之后执行摘要任务，此时所有用户都已离线超过 offline_secs
+fn a_day_later() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+        + 86400 * 2
+}
+
+// alice 给设置了真实邮箱的 bob 发一条私聊消息
+async fn setup(app: &TestApp) -> String {
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    app.request_with_headers(Method::PUT, &format!("/user/{bob}"), Some(json!({ "username": "bob", "email": "bob@example.com" })), &[("authorization", &app.session(&bob))])
+        .await;
+    app.post_as(&alice, "/send-message", json!({
+        "sender_id": alice,
+        "receiver_id": bob,
+        "content": "今晚吃什么",
+        "message_type": "private"
+    }))
+    .await;
+    bob
+}
+
+#[tokio::test]
+async fn digest_is_sent_once_to_offline_users() {
+    let app = TestApp::new();
+    setup(&app).await;
+    let mailer = RecordingMailer::default();
+    let offline: PresenceCheck = Arc::new(|_: &str| false);
+    let settings = DigestSettings::default();
+
+    let sent = send_digests(&app.db, &mailer, &settings, &offline, a_day_later()).await.unwrap();
+    assert_eq!(sent, 1);
+    {
+        let emails = mailer.sent.lock().unwrap();
+        assert_eq!(emails[0].to, "bob@example.com");
+        assert!(emails[0].body.contains("alice: 今晚吃什么"), "{}", emails[0].body);
+    }
+
+    // 没有新消息时不会重复发送；alice 仍是占位邮箱，不会收到邮件
+    let sent = send_digests(&app.db, &mailer, &settings, &offline, a_day_later()).await.unwrap();
+    assert_eq!(sent, 0);
+
+    // 在线用户也不发送
+    let online: PresenceCheck = Arc::new(|_: &str| true);
+    let fresh = TestApp::new();
+    setup(&fresh).await;
+    let sent = send_digests(&fresh.db, &mailer, &settings, &online, a_day_later()).await.unwrap();
+    assert_eq!(sent, 0);
+}
+
+#[tokio::test]
+async fn users_can_opt_out_of_digests() {
+    let app = TestApp::new();
+    let bob = setup(&app).await;
+
+    let (status, _) = app
+        .request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "email_digest": "sometimes" })), &[("authorization", &app.session(&bob))])
+        .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = app
+        .request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "email_digest": "off" })), &[("authorization", &app.session(&bob))])
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (_, body) = app.request_with_headers(Method::GET, &format!("/user/{bob}/settings"), None, &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(body["settings"]["email_digest"], "off");
+
+    let mailer = RecordingMailer::default();
+    let offline: PresenceCheck = Arc::new(|_: &str| false);
+    let sent = send_digests(&app.db, &mailer, &DigestSettings::default(), &offline, a_day_later()).await.unwrap();
+    assert_eq!(sent, 0);
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::json;
+use server::{
+    jobs::JOB_PUSH, settings::Settings, user_settings::DndSchedule, AppState, DbPool, PushDispatcher, PushError,
+    PushNotification, PushProvider,
+};
+
+struct NoopProvider;
+
+impl PushProvider for NoopProvider {
+    fn platform(&self) -> &'static str {
+        "fcm"
+    }
+
+    fn send<'a>(&'a self, _token: &'a str, _notification: &'a PushNotification) -> BoxFuture<'a, Result<(), PushError>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+// 以当前 UTC 时间为中心前后偏移若干分钟的 "HH:MM-HH:MM"
+fn schedule_around_now(from_minutes: i64, to_minutes: i64) -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    let minute = now.rem_euclid(86_400) / 60;
+    let format = |offset: i64| {
+        let m = (minute + offset).rem_euclid(1440);
+        format!("{:02}:{:02}", m / 60, m % 60)
+    };
+    format!("{}-{}", format(from_minutes), format(to_minutes))
+}
+
+#[test]
+fn schedules_wrap_past_midnight_in_the_users_timezone() {
+    let settings: HashMap<String, String> = [("dnd_schedule", "22:00-07:00"), ("utc_offset", "+08:00")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    let schedule = DndSchedule::from_settings(&settings).unwrap();
+    let midnight_utc = 1_704_067_200; // 2024-01-01 00:00 UTC，即当地 08:00
+
+    assert_eq!(schedule.active_until(midnight_utc), None);
+    // UTC 15:30 为当地 23:30，免打扰持续到当地 07:00（UTC 23:00）
+    assert_eq!(schedule.active_until(midnight_utc + 15 * 3600 + 1800), Some(midnight_utc + 23 * 3600));
+    assert_eq!(schedule.active_until(midnight_utc + 23 * 3600), None);
+
+    let off: HashMap<String, String> = [("dnd_schedule".to_string(), "off".to_string())].into();
+    assert_eq!(DndSchedule::from_settings(&off), None);
+}
+
+#[tokio::test]
+async fn dnd_is_validated_and_shown_in_presence() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let settings_path = format!("/user/{alice}/settings");
+    let auth = app.session(&alice);
+
+    for invalid in [json!({ "dnd_schedule": "25:00-07:00" }), json!({ "dnd_schedule": "07:00-07:00" }), json!({ "utc_offset": "08:00" })] {
+        let (status, body) = app.request_with_headers(Method::PUT, &settings_path, Some(invalid), &[("authorization", &auth)]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+    }
+
+    let (_, body) = app.get(&format!("/user/{alice}/presence")).await;
+    assert_eq!((body["code"].as_str(), &body["online"], &body["dnd"]), (Some("user.presence_fetched"), &json!(false), &json!(false)));
+
+    let (status, _) = app.request_with_headers(Method::PUT, &settings_path, Some(json!({ "dnd_schedule": schedule_around_now(-60, 60) })), &[("authorization", &auth)]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.get(&format!("/user/{alice}/presence")).await;
+    assert_eq!(body["dnd"], true);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+    assert!((body["dnd_until"].as_i64().unwrap() - (now + 3600)).abs() <= 60);
+
+    app.request_with_headers(Method::PUT, &settings_path, Some(json!({ "dnd_schedule": schedule_around_now(60, 120) })), &[("authorization", &auth)]).await;
+    let (_, body) = app.get(&format!("/user/{alice}/presence")).await;
+    assert_eq!((&body["dnd"], &body["dnd_until"]), (&json!(false), &json!(null)));
+
+    let (status, _) = app.get("/user/missing/presence").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn push_is_suppressed_during_dnd_but_messages_are_stored() {
+    let mut state = AppState::new(DbPool::in_memory().unwrap(), Settings::default());
+    state.push = PushDispatcher::new(state.db_pool.clone(), vec![Arc::new(NoopProvider)]);
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    app.request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "dnd_schedule": schedule_around_now(-60, 60) })), &[("authorization", &app.session(&bob))]).await;
+
+    let message = json!({ "sender_id": alice, "receiver_id": bob, "content": "睡了吗", "message_type": "private" });
+    let (status, _) = app.post_as(&alice, "/send-message", message).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!state.db_pool.has_unfinished_job(JOB_PUSH).unwrap());
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!(body["messages"][0]["content"], "睡了吗");
+
+    // 免打扰结束后恢复推送
+    app.request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "dnd_schedule": "off" })), &[("authorization", &app.session(&bob))]).await;
+    app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "早", "message_type": "private" })).await;
+    assert!(state.db_pool.has_unfinished_job(JOB_PUSH).unwrap());
+}
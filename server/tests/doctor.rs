@@ -0,0 +1,94 @@
+use server::{
+    doctor::{diagnose, Status},
+    migrations,
+    settings::Settings,
+    DbPool,
+};
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yueling-{}-{}", name, uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// 所有路径都放在临时目录中，端口交给系统分配
+fn doctor_settings(dir: &std::path::Path) -> Settings {
+    let mut settings = Settings::default();
+    settings.server.host = "127.0.0.1".into();
+    settings.server.port = 0;
+    settings.database.path = dir.join("server.db").display().to_string();
+    settings.attachments.dir = dir.join("attachments").display().to_string();
+    settings.backup.dir = dir.join("backups").display().to_string();
+    settings
+}
+
+fn status_of(checks: &[server::doctor::Check], name: &str) -> Status {
+    checks.iter().find(|c| c.name == name).unwrap_or_else(|| panic!("缺少检查项 {name}")).status
+}
+
+#[test]
+fn unreadable_config_reports_only_config() {
+    let checks = diagnose(Err("解析配置文件 config.toml 失败"));
+    assert_eq!(checks.len(), 1);
+    assert_eq!(checks[0].status, Status::Fail);
+    assert!(checks[0].detail.contains("config.toml"));
+}
+
+#[test]
+fn fresh_install_passes_with_warnings_and_creates_nothing() {
+    let dir = temp_dir("doctor-fresh");
+    let settings = doctor_settings(&dir);
+    let checks = diagnose(Ok(&settings));
+
+    assert!(checks.iter().all(|c| c.status != Status::Fail), "{checks:?}");
+    assert_eq!(status_of(&checks, "数据库"), Status::Warn);
+    assert_eq!(status_of(&checks, "附件目录"), Status::Warn);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+}
+
+#[test]
+fn migrated_database_reports_schema_version() {
+    let dir = temp_dir("doctor-db");
+    let settings = doctor_settings(&dir);
+    DbPool::with_settings(&settings.database, None).unwrap();
+    std::fs::create_dir_all(&settings.attachments.dir).unwrap();
+
+    let checks = diagnose(Ok(&settings));
+    let database = checks.iter().find(|c| c.name == "数据库").unwrap();
+    assert_eq!(database.status, Status::Ok);
+    assert!(database.detail.contains(&migrations::latest_version().to_string()));
+    assert_eq!(status_of(&checks, "附件目录"), Status::Ok);
+}
+
+#[test]
+fn encryption_without_master_key_fails() {
+    let dir = temp_dir("doctor-keys");
+    let mut settings = doctor_settings(&dir);
+    settings.security.encrypt_messages = true;
+    assert_eq!(status_of(&diagnose(Ok(&settings)), "消息加密"), Status::Fail);
+
+    settings.security.master_key = "doctor-master-key".into();
+    assert_eq!(status_of(&diagnose(Ok(&settings)), "消息加密"), Status::Ok);
+}
+
+#[test]
+fn occupied_port_fails() {
+    let dir = temp_dir("doctor-port");
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut settings = doctor_settings(&dir);
+    settings.server.port = listener.local_addr().unwrap().port();
+
+    let checks = diagnose(Ok(&settings));
+    let port = checks.iter().find(|c| c.name == "HTTP 端口").unwrap();
+    assert_eq!(port.status, Status::Fail);
+    assert!(port.detail.contains("server.port"));
+}
+
+#[test]
+fn file_in_place_of_directory_fails() {
+    let dir = temp_dir("doctor-dirs");
+    let settings = doctor_settings(&dir);
+    std::fs::write(&settings.backup.dir, b"").unwrap();
+    assert_eq!(status_of(&diagnose(Ok(&settings)), "备份目录"), Status::Fail);
+}
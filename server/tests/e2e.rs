@@ -0,0 +1,79 @@
+mod common;
+
+use std::time::Duration;
+
+use common::e2e::TestServer;
+use yueling_client::{ApiClient, Event, Message};
+
+// 等待服务器异步处理完 WebSocket 帧后，从发送方的历史中读取这条消息
+async fn wait_for_status(client: &ApiClient, user_id: &str, peer_id: &str, status: &str) -> Message {
+    for _ in 0..20 {
+        let history = client.conversation_history(user_id, peer_id, None, 10).await.unwrap();
+        if history[0].status == status {
+            return history[0].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("消息状态没有变成 {status}");
+}
+
+#[tokio::test]
+async fn register_login_send_receive_and_read() {
+    let server = TestServer::start().await;
+    let (alice_client, alice) = server.signup("alice").await;
+    let (bob_client, bob) = server.signup("bob").await;
+    let mut bob_events = bob_client.connect_events(&bob.user_id, &[]).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let ack = alice_client
+        .send_message_with_client_id(&alice.user_id, &bob.user_id, "你好", "private", "tmp-1")
+        .await
+        .unwrap();
+    assert_eq!((ack.client_message_id.as_str(), ack.seq), ("tmp-1", Some(1)));
+
+    // 接收方实时收到推送，确认送达后发送方看到送达时间
+    let event = tokio::time::timeout(Duration::from_secs(2), bob_events.next()).await.unwrap().unwrap().unwrap();
+    let Event::Json(event) = event else { panic!("私聊推送应为 JSON：{event:?}") };
+    assert_eq!((event["type"].as_str(), event["message_id"].as_str()), (Some("message"), Some(ack.message_id.as_str())));
+    assert_eq!(event["content"], "你好");
+    bob_events.ack_delivered(&[&ack.message_id]).await.unwrap();
+    let delivered = wait_for_status(&alice_client, &alice.user_id, &bob.user_id, "delivered").await;
+    assert!(delivered.delivered_at.is_some() && delivered.read_at.is_none());
+    assert!(server.state.db_pool.pending_outbox(&bob.user_id).unwrap().is_empty());
+
+    let unread = bob_client.unread_messages(&bob.user_id).await.unwrap();
+    assert_eq!(unread.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), [ack.message_id.as_str()]);
+    bob_client.mark_read(&[&ack.message_id]).await.unwrap();
+    let read = wait_for_status(&alice_client, &alice.user_id, &bob.user_id, "read").await;
+    assert!(read.is_read && read.read_at.is_some());
+    assert!(bob_client.unread_messages(&bob.user_id).await.unwrap().is_empty());
+
+    bob_events.close().await.unwrap();
+}
+
+#[tokio::test]
+async fn offline_receiver_catches_up_by_sync_and_seq() {
+    let server = TestServer::start().await;
+    let (alice_client, alice) = server.signup("alice").await;
+    let (bob_client, bob) = server.signup("bob").await;
+
+    for content in ["第一条", "第二条", "第三条"] {
+        alice_client.send_message(&alice.user_id, &bob.user_id, content, "private").await.unwrap();
+    }
+    // 离线时的推送留在发件箱中，等待送达确认
+    assert_eq!(server.state.db_pool.pending_outbox(&bob.user_id).unwrap().len(), 3);
+
+    let synced = bob_client.sync_messages(&bob.user_id, 0, 10).await.unwrap();
+    assert_eq!(synced.messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["第一条", "第二条", "第三条"]);
+
+    // 只收到第一条的客户端从序号 2 开始补齐
+    let range = bob_client.messages_from_seq(&alice.user_id, 2, 10).await.unwrap();
+    assert_eq!(range.messages.iter().map(|m| m.seq).collect::<Vec<_>>(), [Some(2), Some(3)]);
+    assert_eq!((range.last_seq, range.next_seq), (3, None));
+
+    let ids: Vec<&str> = synced.messages.iter().map(|m| m.id.as_str()).collect();
+    bob_client.mark_delivered(&ids).await.unwrap();
+    assert!(server.state.db_pool.pending_outbox(&bob.user_id).unwrap().is_empty());
+    let history = alice_client.conversation_history(&alice.user_id, &bob.user_id, None, 10).await.unwrap();
+    assert!(history.iter().all(|m| m.status == "delivered" && m.delivered_at.is_some()));
+}
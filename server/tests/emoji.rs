@@ -0,0 +1,95 @@
+mod common;
+
+use axum::http::{Method, Request, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+fn emoji_app() -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    let dir = std::env::temp_dir().join(format!("yueling-emoji-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    TestApp::with_settings(settings)
+}
+
+async fn put_emoji(app: &TestApp, name: &str, content_type: &str) -> (StatusCode, Value) {
+    let request = Request::put(format!("/admin/workspaces/default/emoji/{name}")).header("authorization", format!("Bearer {ADMIN_TOKEN}"));
+    app.multipart(request, "file", &format!("{name}.png"), content_type, b"\x89PNG fake image").await
+}
+
+async fn send_sticker(app: &TestApp, sender: &str, receiver: &str, name: &str) -> (StatusCode, Value) {
+    app.post_as(sender, "/send-message", json!({
+        "sender_id": sender,
+        "receiver_id": receiver,
+        "content": name,
+        "message_type": "private",
+        "format": "sticker",
+    })).await
+}
+
+#[tokio::test]
+async fn admins_manage_workspace_emoji() {
+    let app = emoji_app();
+    let (status, body) = put_emoji(&app, "party_parrot", "image/png").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("emoji.added")));
+    let first = body["emoji"]["attachment_id"].as_str().unwrap().to_string();
+    let (_, body) = put_emoji(&app, "party_parrot", "image/gif").await;
+    assert_eq!(body["code"], "emoji.updated");
+    assert_ne!(body["emoji"]["attachment_id"], first);
+
+    let (status, body) = put_emoji(&app, "Party_Parrot", "image/png").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("emoji.invalid_name")));
+    let (status, body) = put_emoji(&app, "script", "text/html").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("attachment.wrong_type")));
+
+    // 服务器信息带上默认工作区的表情，客户端据此显示
+    let (_, body) = app.get("/server-info").await;
+    let emoji = body["custom_emoji"].as_array().unwrap();
+    assert_eq!(emoji.len(), 1);
+    assert_eq!((&emoji[0]["name"], &emoji[0]["content_type"]), (&json!("party_parrot"), &json!("image/gif")));
+
+    app.register("alice", "secret").await;
+    let (_, body) = app.post("/login", json!({ "username": "alice", "password": "secret" })).await;
+    let auth = format!("Bearer {}", body["token"].as_str().unwrap());
+    let (status, body) = app.request_with_headers(Method::GET, "/emoji", None, &[("authorization", auth.as_str())]).await;
+    assert_eq!((status, body["emoji"][0]["name"].as_str()), (StatusCode::OK, Some("party_parrot")));
+    let (status, _) = app.request(Method::GET, "/emoji", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let path = "/admin/workspaces/default/emoji/party_parrot";
+    let admin = format!("Bearer {ADMIN_TOKEN}");
+    let (status, body) = app.request_with_headers(Method::DELETE, path, None, &[("authorization", admin.as_str())]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("emoji.deleted")));
+    let (status, _) = app.request_with_headers(Method::DELETE, path, None, &[("authorization", admin.as_str())]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.request_with_headers(Method::DELETE, path, None, &[("authorization", "Bearer wrong")]).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert!(app.db.custom_emoji("default").unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn stickers_reference_registered_emoji() {
+    let app = emoji_app();
+    let (_, body) = put_emoji(&app, "thumbs-up", "image/png").await;
+    let attachment_id = body["emoji"]["attachment_id"].clone();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (status, _) = send_sticker(&app, &alice, &bob, ":thumbs-up:").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = send_sticker(&app, &alice, &bob, "missing").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("emoji.not_found")));
+
+    // 贴纸保存为 :name: 和引用表情图片的 sticker 区间，表情删除后已发送的贴纸仍能显示
+    app.db.delete_custom_emoji("default", "thumbs-up").unwrap();
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["content"], ":thumbs-up:");
+    assert_eq!(messages[0]["entities"], json!([
+        { "type": "sticker", "offset": 0, "length": 11, "emoji": "thumbs-up", "attachment_id": attachment_id },
+    ]));
+}
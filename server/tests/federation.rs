@@ -0,0 +1,193 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::{local_message_id, settings::Settings, FederatedMessage, ServerKey};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const LOCAL_SEED: [u8; 32] = [1; 32];
+const PEER_SEED: [u8; 32] = [2; 32];
+
+// 启用联邦的本服 local.example，白名单中登记了 peer.example
+async fn federated_app() -> TestApp {
+    let key_file = std::env::temp_dir().join(format!("yueling-federation-{}.key", uuid::Uuid::new_v4()));
+    std::fs::write(&key_file, hex::encode(LOCAL_SEED)).unwrap();
+
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.federation.enabled = true;
+    settings.federation.server_name = "local.example".into();
+    settings.federation.signing_key_file = key_file.display().to_string();
+    let app = TestApp::with_settings(settings);
+
+    let auth = format!("Bearer {ADMIN_TOKEN}");
+    let (status, body) = app
+        .request_with_headers(
+            Method::POST,
+            "/admin/federation/peers",
+            Some(json!({
+                "host": "peer.example",
+                "base_url": "https://peer.example",
+                "public_key": ServerKey::from_seed(PEER_SEED).public_key()
+            })),
+            &[("authorization", auth.as_str())],
+        )
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    app
+}
+
+// 以 origin 的身份用 key 签名并投递一条消息
+async fn deliver(app: &TestApp, origin: &str, key: &ServerKey, message: &FederatedMessage) -> StatusCode {
+    // 签名覆盖的是请求体原文，按测试客户端实际发送的序列化结果签名
+    let body = serde_json::to_value(message).unwrap();
+    let signature = key.sign(body.to_string().as_bytes());
+    let (status, _) = app
+        .request_with_headers(
+            Method::POST,
+            "/federation/messages",
+            Some(body),
+            &[("x-yueling-origin", origin), ("x-yueling-signature", signature.as_str())],
+        )
+        .await;
+    status
+}
+
+fn message_to(recipient: &str) -> FederatedMessage {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    FederatedMessage {
+        message_id: uuid::Uuid::now_v7().to_string(),
+        sender: "alice".into(),
+        recipient: recipient.into(),
+        content: "来自远方的问候".into(),
+        created_at: now,
+        sent_at: now,
+    }
+}
+
+#[tokio::test]
+async fn publishes_server_key_and_resolves_remote_addresses() {
+    let app = federated_app().await;
+    let (_, body) = app.get("/federation/key").await;
+    assert_eq!(body["server_name"], "local.example");
+    assert_eq!(body["public_key"], ServerKey::from_seed(LOCAL_SEED).public_key());
+
+    // 解析地址需要登录
+    let (status, _) = app.post("/federation/resolve", json!({ "address": "carol@peer.example" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let alice = app.register("alice", "secret").await;
+    let (status, body) = app.post_as(&alice, "/federation/resolve", json!({ "address": "alice@local.example" })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["user_id"], alice.as_str());
+
+    // 还没有影子账号时返回地址本身，解析不会创建账号
+    let (status, body) = app.post_as(&alice, "/federation/resolve", json!({ "address": "carol@peer.example" })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["user_id"], "carol@peer.example");
+    assert!(app.db.user_credentials("carol@peer.example").unwrap().is_none());
+
+    // 第一条私聊消息创建影子账号，之后解析返回它的ID
+    let (status, body) = app.post_as(&alice, "/send-message", json!({
+        "receiver_id": "carol@peer.example", "content": "你好", "message_type": "private"
+    })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (_, body) = app.post_as(&alice, "/federation/resolve", json!({ "address": "carol@peer.example" })).await;
+    let carol = body["user_id"].as_str().unwrap().to_string();
+    assert_ne!(carol, "carol@peer.example");
+    let (_, body) = app.get(&format!("/user/{carol}")).await;
+    assert_eq!(body["user"]["username"], "carol@peer.example");
+    let (_, body) = app.post_as(&alice, "/federation/resolve", json!({ "address": "carol@peer.example" })).await;
+    assert_eq!(body["user_id"], carol.as_str());
+
+    let (status, _) = app.post_as(&alice, "/federation/resolve", json!({ "address": "dave@unknown.example" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.post_as(&alice, "/send-message", json!({
+        "receiver_id": "dave@unknown.example", "content": "你好", "message_type": "private"
+    })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 本地用户名不能包含 @
+    let (status, _) = app.post("/register", json!({ "username": "eve@peer.example", "password": "secret" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn accepts_signed_messages_from_allowed_peers() {
+    let app = federated_app().await;
+    let bob = app.register("bob", "secret").await;
+    let peer_key = ServerKey::from_seed(PEER_SEED);
+    let message = message_to("bob");
+
+    // 签名不匹配、来源不在白名单都会被拒绝
+    assert_eq!(deliver(&app, "peer.example", &ServerKey::from_seed([3; 32]), &message).await, StatusCode::UNAUTHORIZED);
+    assert_eq!(deliver(&app, "other.example", &peer_key, &message).await, StatusCode::FORBIDDEN);
+    let stale = FederatedMessage { sent_at: message.sent_at - 3600, ..message.clone() };
+    assert_eq!(deliver(&app, "peer.example", &peer_key, &stale).await, StatusCode::UNAUTHORIZED);
+
+    // 对端重试时同一消息只保存一次
+    assert_eq!(deliver(&app, "peer.example", &peer_key, &message).await, StatusCode::OK);
+    assert_eq!(deliver(&app, "peer.example", &peer_key, &message).await, StatusCode::OK);
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    let local_id = local_message_id("peer.example", &message.message_id);
+    assert_eq!(messages[0]["id"], local_id.as_str());
+
+    let sender_id = messages[0]["sender_id"].as_str().unwrap();
+    let (_, body) = app.get(&format!("/user/{sender_id}")).await;
+    assert_eq!(body["user"]["username"], "alice@peer.example");
+    // 与本地私聊一样进入发件箱，等待接收者确认送达
+    assert_eq!(app.db.pending_outbox(&bob).unwrap(), vec![local_id]);
+}
+
+#[tokio::test]
+async fn remote_senders_follow_dm_privacy() {
+    let app = federated_app().await;
+    let bob = app.register("bob", "secret").await;
+    let peer_key = ServerKey::from_seed(PEER_SEED);
+
+    // 只允许好友私聊时，不是好友的远端用户发来的消息被拒绝，也不保存
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{bob}/settings"), Some(json!({ "dm_privacy": "friends" })), &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(deliver(&app, "peer.example", &peer_key, &message_to("bob")).await, StatusCode::FORBIDDEN);
+    assert!(app.db.get_unread_messages(server::workspaces::DEFAULT_WORKSPACE, &bob).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn peers_cannot_reuse_local_message_ids() {
+    let app = federated_app().await;
+    let (alice, _) = app.login("alice").await;
+    let bob = app.register("bob", "secret").await;
+    let (_, sent) = app.post_as(&alice, "/send-message", json!({
+        "receiver_id": bob, "content": "本地消息", "message_type": "private",
+    })).await;
+    let local_id = sent["message_id"].as_str().unwrap();
+
+    // 对端带着本地消息的ID投递时另存为一条新消息，本地消息不受影响，也不会被当作重复投递吞掉
+    let message = FederatedMessage { message_id: local_id.into(), ..message_to("bob") };
+    assert_eq!(deliver(&app, "peer.example", &ServerKey::from_seed(PEER_SEED), &message).await, StatusCode::OK);
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!((messages[0]["id"].as_str(), messages[0]["content"].as_str()), (Some(local_id), Some("本地消息")));
+    assert_ne!(messages[1]["id"].as_str(), Some(local_id));
+    assert_eq!(messages[1]["content"], "来自远方的问候");
+}
+#[test]
+fn generated_signing_keys_are_private_to_the_owner() {
+    let key_file = std::env::temp_dir().join(format!("yueling-federation-{}.key", uuid::Uuid::new_v4()));
+    let created = ServerKey::load_or_create(&key_file).unwrap();
+    assert_eq!(ServerKey::load_or_create(&key_file).unwrap().public_key(), created.public_key());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(std::fs::metadata(&key_file).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+    std::fs::remove_file(&key_file).unwrap();
+}
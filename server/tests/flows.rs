@@ -0,0 +1,187 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::settings::Settings;
+
+#[tokio::test]
+async fn register_then_login() {
+    let app = TestApp::new();
+    let user_id = app.register("alice", "secret").await;
+
+    let (status, body) = app
+        .post("/login", json!({ "username": "alice", "password": "secret" }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["user_id"], user_id.as_str());
+    assert_eq!(body["username"], "alice");
+}
+
+#[tokio::test]
+async fn duplicate_registration_is_rejected() {
+    let app = TestApp::new();
+    app.register("alice", "secret").await;
+
+    let (status, body) = app
+        .post("/register", json!({ "username": "alice", "password": "other" }))
+        .await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["success"], false);
+}
+
+#[tokio::test]
+async fn login_with_wrong_password_fails() {
+    let app = TestApp::new();
+    app.register("alice", "secret").await;
+
+    let (status, _) = app
+        .post("/login", json!({ "username": "alice", "password": "wrong" }))
+        .await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn send_then_read_message() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (status, body) = app
+        .post_as(&alice, "/send-message", json!({
+            "sender_id": alice,
+            "receiver_id": bob,
+            "content": "你好",
+            "message_type": "private"
+        }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let message_id = body["message_id"].as_str().unwrap().to_string();
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    let messages = body["messages"].as_array().unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0]["content"], "你好");
+
+    let (status, _) = app
+        .post_as(&bob, "/messages/read", json!({ "message_ids": [message_id] }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert!(body["messages"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn batch_send_is_all_or_nothing() {
+    let mut settings = Settings::default();
+    settings.admin.token = "test-admin-token".into();
+    let app = TestApp::with_settings(settings);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (status, body) = app
+        .post_as(&alice, "/messages/batch", json!({ "messages": [
+            { "sender_id": alice, "receiver_id": bob, "content": "一", "message_type": "private" },
+            { "sender_id": alice, "receiver_id": bob, "content": "二", "message_type": "private" }
+        ]}))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["message_ids"].as_array().unwrap().len(), 2);
+
+    // 会话用户只能以自己的名义发送
+    let batch = json!({ "messages": [
+        { "sender_id": alice, "receiver_id": bob, "content": "三", "message_type": "private" },
+        { "sender_id": "missing", "receiver_id": bob, "content": "四", "message_type": "private" }
+    ]});
+    let (status, body) = app.post_as(&alice, "/messages/batch", batch.clone()).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.acting_as_other_user")));
+
+    // 管理令牌可以代任意用户发送；第二条的发送者不存在，违反外键约束，整批回滚
+    let (status, _) = app
+        .request_with_headers(Method::POST, "/messages/batch", Some(batch), &[("authorization", "Bearer test-admin-token")])
+        .await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    let contents: Vec<&str> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+    assert_eq!(contents, vec!["一", "二"]);
+
+    let (status, _) = app.post("/messages/batch", json!({ "messages": [] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn deleted_message_becomes_tombstone_and_can_be_restored() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (_, body) = app
+        .post_as(&alice, "/send-message", json!({
+            "sender_id": alice,
+            "receiver_id": bob,
+            "content": "撤回我",
+            "message_type": "private"
+        }))
+        .await;
+    let message_id = body["message_id"].as_str().unwrap().to_string();
+
+    // 只有发送者可以删除
+    let (status, _) = app
+        .post_as(&bob, "/messages/delete", json!({ "message_id": message_id, "user_id": bob }))
+        .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = app
+        .post_as(&alice, "/messages/delete", json!({ "message_id": message_id, "user_id": alice }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app
+        .post_as(&bob, "/messages/sync", json!({ "user_id": bob, "last_sync_time": 0, "limit": 50 }))
+        .await;
+    assert!(body["messages"].as_array().unwrap().is_empty());
+    assert_eq!(body["tombstones"][0]["message_id"], message_id.as_str());
+
+    let (status, _) = app
+        .post_as(&alice, "/messages/restore", json!({ "message_id": message_id, "user_id": alice }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!(body["messages"][0]["content"], "撤回我");
+}
+
+#[tokio::test]
+async fn deleted_user_cannot_login_until_restored() {
+    let mut settings = Settings::default();
+    settings.admin.token = "test-admin-token".into();
+    let app = TestApp::with_settings(settings);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let login = json!({ "username": "alice", "password": "secret" });
+    let admin = [("authorization", "Bearer test-admin-token")];
+
+    // 只有本人或管理员可以删除
+    let path = format!("/user/{alice}");
+    let (status, _) = app.request(Method::DELETE, &path, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.request_with_headers(Method::DELETE, &path, None, &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.request_with_headers(Method::DELETE, &path, None, &[("authorization", &app.session(&alice))]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post("/login", login.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.request_with_headers(Method::DELETE, &format!("/user/{bob}"), None, &admin).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 恢复需要管理令牌
+    let restore = format!("/user/{alice}/restore");
+    let (status, _) = app.post(&restore, json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.request_with_headers(Method::POST, &restore, Some(json!({})), &admin).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post("/login", login).await;
+    assert_eq!(status, StatusCode::OK);
+}
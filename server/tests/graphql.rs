@@ -0,0 +1,101 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+async fn login(app: &TestApp, username: &str) -> String {
+    let (status, body) = app.post("/login", json!({ "username": username, "password": "secret" })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body["token"].as_str().unwrap().to_string()
+}
+
+async fn graphql(app: &TestApp, token: Option<&str>, query: &str) -> (StatusCode, Value) {
+    let header = token.map(|token| format!("Bearer {token}"));
+    let headers: Vec<(&str, &str)> = header.iter().map(|h| ("authorization", h.as_str())).collect();
+    app.request_with_headers(Method::POST, "/graphql", Some(json!({ "query": query })), &headers).await
+}
+
+#[tokio::test]
+async fn conversations_and_paginated_history_in_one_query() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    for content in ["一", "二", "三"] {
+        app.post_as(&bob, "/send-message", json!({
+            "sender_id": bob, "receiver_id": alice, "content": content, "message_type": "private"
+        }))
+        .await;
+        // 三条消息落在同一秒，游标靠消息ID区分
+        app.db.0.lock().unwrap()
+            .execute("UPDATE messages SET created_at = 1000 WHERE content = ?1", [content])
+            .unwrap();
+    }
+    let token = login(&app, "alice").await;
+
+    let (status, body) = graphql(&app, Some(&token), r#"{
+        me { id username email }
+        conversations {
+            peerId unreadCount
+            peer { username }
+            messages(first: 2) { messages { content sender { username } } nextCursor }
+        }
+    }"#).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["errors"].is_null(), "{body}");
+    assert_eq!(body["data"]["me"]["id"], alice.as_str());
+    let conversation = &body["data"]["conversations"][0];
+    assert_eq!(conversation["peerId"], bob.as_str());
+    assert_eq!(conversation["unreadCount"], 3);
+    assert_eq!(conversation["peer"]["username"], "bob");
+    let page = &conversation["messages"];
+    assert_eq!(page["messages"][0]["content"], "三");
+    assert_eq!(page["messages"][1]["sender"]["username"], "bob");
+    let cursor = page["nextCursor"].as_str().unwrap();
+    assert!(cursor.starts_with("1000:"), "{cursor}");
+
+    let (_, body) = graphql(&app, Some(&token), &format!(
+        r#"{{ messages(peerId: "{bob}", cursor: "{cursor}", first: 2) {{ messages {{ content }} nextCursor }} }}"#
+    )).await;
+    assert_eq!(body["data"]["messages"]["messages"], json!([{ "content": "一" }]));
+    assert!(body["data"]["messages"]["nextCursor"].is_null());
+}
+
+#[tokio::test]
+async fn private_fields_require_owner_or_admin() {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    let app = TestApp::with_settings(settings);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let token = login(&app, "alice").await;
+
+    // 别人的公开字段可以看，邮箱不行
+    let (_, body) = graphql(&app, Some(&token), &format!(r#"{{ user(id: "{bob}") {{ username email }} }}"#)).await;
+    assert_eq!(body["data"]["user"]["username"], "bob");
+    assert!(body["data"]["user"]["email"].is_null());
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+    assert_eq!(body["errors"][0]["path"], json!(["user", "email"]));
+
+    // 不能冒充其他用户查看会话
+    let (_, body) = graphql(&app, Some(&token), &format!(r#"{{ conversations(userId: "{bob}") {{ peerId }} }}"#)).await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "FORBIDDEN");
+
+    let (_, body) = graphql(&app, None, "{ me { id } }").await;
+    assert_eq!(body["errors"][0]["extensions"]["code"], "UNAUTHENTICATED");
+    let (status, _) = graphql(&app, Some("yls_invalid"), "{ me { id } }").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (_, body) = graphql(&app, Some(ADMIN_TOKEN), &format!(r#"{{ user(id: "{alice}") {{ email }} }}"#)).await;
+    assert!(body["errors"].is_null(), "{body}");
+
+    // 注销后令牌失效
+    let header = format!("Bearer {token}");
+    let (status, _) = app.request_with_headers(Method::POST, "/logout", None, &[("authorization", header.as_str())]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = graphql(&app, Some(&token), "{ me { id } }").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
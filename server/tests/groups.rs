@@ -0,0 +1,204 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{e2e::TestServer, TestApp};
+use serde_json::{json, Value};
+use server::{settings::RetentionSettings, workspaces::DEFAULT_WORKSPACE};
+
+#[tokio::test]
+async fn owners_can_transfer_ownership() {
+    let app = TestApp::new();
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let (dave, dave_auth) = app.login("dave").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    app.db.add_group_member(&group.id, &bob, "member").unwrap();
+    let path = format!("/groups/{}/owner", group.id);
+
+    let (status, body) = app.call(&bob_auth, Method::PUT, &path, Some(json!({ "user_id": bob }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("group.owner_only")));
+    let (status, _) = app.call(&dave_auth, Method::PUT, &path, Some(json!({ "user_id": dave }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, body) = app.call(&alice_auth, Method::PUT, &path, Some(json!({ "user_id": dave }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("group.new_owner_not_member")));
+    let (status, body) = app.call(&alice_auth, Method::PUT, &path, Some(json!({ "user_id": alice }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("group.already_owner")));
+
+    // 转让前签发的删除令牌随转让作废
+    let (_, body) = app.call(&alice_auth, Method::POST, &format!("/groups/{}/deletion-token", group.id), None).await;
+    let stale_token = body["confirmation_token"].as_str().unwrap().to_string();
+    let (status, body) = app.call(&alice_auth, Method::PUT, &path, Some(json!({ "user_id": bob }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("group.owner_transferred")));
+    assert_eq!(app.db.group_owner(&group.id).unwrap().as_deref(), Some(bob.as_str()));
+    let roles: Vec<(String, String)> = app.db.get_group_members(&group.id).unwrap().into_iter().map(|m| (m.user_id, m.role)).collect();
+    assert!(roles.contains(&(alice.clone(), "member".into())) && roles.contains(&(bob.clone(), "owner".into())));
+    assert!(!app.db.consume_group_deletion_token(&group.id, &alice, &stale_token, 0).unwrap());
+
+    let (_, body) = app.call(&bob_auth, Method::GET, &format!("/conversations/{}/messages", group.id), None).await;
+    let event: Value = serde_json::from_str(body["messages"][0]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(event, json!({ "event": "owner_changed", "old_owner": alice, "new_owner": bob }));
+    let (status, _) = app.call(&alice_auth, Method::PUT, &path, Some(json!({ "user_id": alice }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn deleting_a_group_requires_confirmation_and_notifies_members() {
+    let server = TestServer::start().await;
+    let app = server.app();
+    let (alice, alice_auth) = app.login("alice").await;
+    let (carol, carol_auth) = app.login("carol").await;
+    let group = server.state.db_pool.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    server.state.db_pool.add_group_member(&group.id, &carol, "member").unwrap();
+    let mut carol_ws = server.ws(&carol).await;
+    let token_path = format!("/groups/{}/deletion-token", group.id);
+    let group_path = format!("/groups/{}", group.id);
+
+    let (status, _) = app.call(&carol_auth, Method::POST, &token_path, None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = app.call(&alice_auth, Method::POST, &token_path, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let token = body["confirmation_token"].as_str().unwrap().to_string();
+    assert!(token.starts_with("ylg_"));
+
+    let (status, body) = app.call(&alice_auth, Method::DELETE, &group_path, Some(json!({ "confirmation_token": "ylg_wrong" }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("group.deletion_token_invalid")));
+    let (status, body) = app.call(&alice_auth, Method::DELETE, &group_path, Some(json!({ "confirmation_token": token }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("group.deleted")));
+    let deleted_at = body["purge_at"].as_i64().unwrap() - 30 * 24 * 60 * 60;
+
+    let event = carol_ws.next_event().await;
+    assert_eq!((event["type"].as_str(), event["event"]["event"].as_str()), (Some("system_message"), Some("group_deleted")));
+    assert_eq!(event["event"]["deleted_by"], alice.as_str());
+
+    // 删除后成员列表、发消息和再次删除都找不到群
+    let (status, _) = app.call(&alice_auth, Method::GET, &format!("/groups/{}/members", group.id), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.post_as(&carol, "/send-message", json!({ "sender_id": carol, "receiver_id": group.id, "content": "还在吗", "message_type": "group" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.call(&alice_auth, Method::DELETE, &group_path, Some(json!({ "confirmation_token": token }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert!(server.state.db_pool.is_group_deleted(&group.id).unwrap());
+
+    // 过了清理期限后消息和群一起清除
+    let db = &server.state.db_pool;
+    let settings = RetentionSettings { purge_deleted_days: 30, ..Default::default() };
+    assert_eq!(db.apply_retention(&settings, deleted_at + 29 * 24 * 60 * 60).unwrap().groups_purged, 0);
+    let report = db.apply_retention(&settings, deleted_at + 31 * 24 * 60 * 60).unwrap();
+    assert_eq!((report.groups_purged, report.messages_deleted), (1, 1));
+    assert!(!db.is_group_deleted(&group.id).unwrap());
+}
+
+#[tokio::test]
+async fn public_groups_can_be_discovered_and_joined() {
+    let server = TestServer::start().await;
+    let app = server.app();
+    let db = &server.state.db_pool;
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let open = db.create_group(DEFAULT_WORKSPACE, "Rust 学习群", &alice).unwrap();
+    let reviewed = db.create_group(DEFAULT_WORKSPACE, "rust 核心组", &alice).unwrap();
+    let hidden = db.create_group(DEFAULT_WORKSPACE, "Rust 私密群", &alice).unwrap();
+    let visibility = |group_id: &str, body: Value| {
+        let (app, auth, path) = (&app, alice_auth.clone(), format!("/groups/{group_id}/visibility"));
+        async move { app.call(&auth, Method::PUT, &path, Some(body)).await }
+    };
+    let (status, body) = visibility(&open.id, json!({ "visibility": "everyone" })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("group.invalid_visibility")));
+    assert_eq!(visibility(&open.id, json!({ "visibility": "public" })).await.0, StatusCode::OK);
+    assert_eq!(visibility(&reviewed.id, json!({ "visibility": "public", "join_approval": true })).await.0, StatusCode::OK);
+
+    // 私有群不出现在目录中，按群名搜索不区分大小写，按创建时间倒序分页
+    let (_, body) = app.call(&bob_auth, Method::GET, "/groups/discover?query=RUST&limit=1", None).await;
+    assert_eq!(body["groups"].as_array().unwrap().len(), 1);
+    let first = body["groups"][0]["id"].as_str().unwrap().to_string();
+    let cursor = body["next_cursor"].as_str().unwrap().to_string();
+    let (_, body) = app.call(&bob_auth, Method::GET, &format!("/groups/discover?query=RUST&limit=1&cursor={cursor}"), None).await;
+    let mut listed = vec![first, body["groups"][0]["id"].as_str().unwrap().to_string()];
+    listed.sort();
+    let mut expected = vec![open.id.clone(), reviewed.id.clone()];
+    expected.sort();
+    assert_eq!(listed, expected);
+    let (_, body) = app.call(&bob_auth, Method::GET, "/groups/discover?query=学习", None).await;
+    assert_eq!((body["groups"][0]["member_count"].as_i64(), body["groups"][0]["join_approval"].as_bool()), (Some(1), Some(false)));
+
+    let join = |group_id: &str| {
+        let (app, auth, path) = (&app, bob_auth.clone(), format!("/groups/{group_id}/join"));
+        async move { app.call(&auth, Method::POST, &path, None).await }
+    };
+    assert_eq!(join(&hidden.id).await.0, StatusCode::NOT_FOUND);
+    let (_, body) = join(&open.id).await;
+    assert_eq!((body["joined"].as_bool(), body["pending"].as_bool()), (Some(true), Some(false)));
+    assert!(db.is_group_member(&open.id, &bob).unwrap());
+
+    // 需要审批的群先通知群主，批准后申请人入群并收到结果
+    let mut alice_ws = server.ws(&alice).await;
+    let mut bob_ws = server.ws(&bob).await;
+    let (_, body) = join(&reviewed.id).await;
+    assert_eq!((body["code"].as_str(), body["pending"].as_bool()), (Some("group.join_requested"), Some(true)));
+    assert!(!db.is_group_member(&reviewed.id, &bob).unwrap());
+    let event = alice_ws.next_event().await;
+    assert_eq!((event["type"].as_str(), event["user_id"].as_str()), (Some("group_join_requested"), Some(bob.as_str())));
+    let requests_path = format!("/groups/{}/join-requests", reviewed.id);
+    let (status, _) = app.call(&bob_auth, Method::GET, &requests_path, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = app.call(&alice_auth, Method::GET, &requests_path, None).await;
+    assert_eq!(body["requests"][0]["username"], "bob");
+    let resolve_path = format!("{requests_path}/{bob}");
+    let (status, body) = app.call(&alice_auth, Method::POST, &resolve_path, Some(json!({ "approve": true }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("group.join_approved")));
+    assert!(db.is_group_member(&reviewed.id, &bob).unwrap());
+    let event = bob_ws.next_event().await;
+    assert_eq!((event["type"].as_str(), event["approved"].as_bool()), (Some("group_join_resolved"), Some(true)));
+    let (status, body) = app.call(&alice_auth, Method::POST, &resolve_path, Some(json!({ "approve": true }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("group.join_request_not_found")));
+}
+
+#[tokio::test]
+async fn muted_members_cannot_send_until_the_mute_ends() {
+    let server = TestServer::start().await;
+    let app = server.app();
+    let db = &server.state.db_pool;
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let group = db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    db.add_group_member(&group.id, &bob, "member").unwrap();
+    let mute_path = format!("/groups/{}/members/{bob}/mute", group.id);
+    let send = |sender: &str| app.post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": group.id, "content": "大家好", "message_type": "group" }));
+
+    let (status, _) = app.call(&bob_auth, Method::PUT, &format!("/groups/{}/members/{alice}/mute", group.id), Some(json!({ "duration_secs": 60 }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, body) = app.call(&alice_auth, Method::PUT, &mute_path, Some(json!({ "duration_secs": 0 }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("group.mute_duration_invalid")));
+    let (status, body) = app.call(&alice_auth, Method::PUT, &format!("/groups/{}/members/{alice}/mute", group.id), Some(json!({ "duration_secs": 60 }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("group.cannot_mute_owner")));
+
+    let mut bob_ws = server.ws(&bob).await;
+    let (status, body) = app.call(&alice_auth, Method::PUT, &mute_path, Some(json!({ "duration_secs": 600 }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let muted_until = body["muted_until"].as_i64().unwrap();
+    let event = bob_ws.next_event().await;
+    assert_eq!((event["type"].as_str(), event["muted_until"].as_i64()), (Some("group_member_muted"), Some(muted_until)));
+
+    // 发送被拒绝时带上禁言结束时间，REST 和 WebSocket 一致
+    let (status, body) = send(&bob).await;
+    assert_eq!((status, body["code"].as_str(), body["muted_until"].as_i64()), (StatusCode::FORBIDDEN, Some("group.member_muted"), Some(muted_until)));
+    bob_ws.send(json!({ "type": "group_chat", "group_id": group.id, "content": "大家好", "client_message_id": "c1" })).await;
+    let event = bob_ws.next_event().await;
+    assert_eq!((event["code"].as_str(), event["muted_until"].as_i64()), (Some("group.member_muted"), Some(muted_until)));
+    assert_eq!(send(&alice).await.0, StatusCode::OK);
+
+    let (_, body) = app.call(&alice_auth, Method::GET, &format!("/groups/{}/members", group.id), None).await;
+    let bob_entry = body["members"].as_array().unwrap().iter().find(|m| m["user_id"] == bob.as_str()).unwrap().clone();
+    assert_eq!(bob_entry["muted_until"].as_i64(), Some(muted_until));
+
+    // 到期的禁言不再生效，也不再出现在成员列表中
+    db.set_member_muted_until(&group.id, &bob, Some(1)).unwrap();
+    let (_, body) = app.call(&alice_auth, Method::GET, &format!("/groups/{}/members", group.id), None).await;
+    assert!(body["members"].as_array().unwrap().iter().all(|m| m["muted_until"].is_null()));
+    assert_eq!(send(&bob).await.0, StatusCode::OK);
+
+    app.call(&alice_auth, Method::PUT, &mute_path, Some(json!({ "duration_secs": 600 }))).await;
+    let (status, body) = app.call(&alice_auth, Method::DELETE, &mute_path, None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("group.unmuted")));
+    assert_eq!(send(&bob).await.0, StatusCode::OK);
+}
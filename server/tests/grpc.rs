@@ -0,0 +1,200 @@
+use server::grpc::{self, proto::chat_client::ChatClient, proto::*};
+use server::{settings::Settings, system_messages::SystemEvent, workspaces::DEFAULT_WORKSPACE, AppState, DbPool};
+use std::time::Duration;
+use tonic::transport::Channel;
+use tonic::{Code, Request};
+
+// 在本地随机端口启动 gRPC 服务并返回客户端
+async fn start(settings: Settings) -> ChatClient<Channel> {
+    serve(&AppState::new(DbPool::in_memory().unwrap(), settings)).await
+}
+
+async fn serve(state: &AppState) -> ChatClient<Channel> {
+    let state = state.clone();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(grpc::serve(state, listener));
+    ChatClient::connect(format!("http://{addr}")).await.unwrap()
+}
+
+async fn register(client: &mut ChatClient<Channel>, username: &str) -> String {
+    let reply = client.register(RegisterRequest { username: username.into(), password: "secret".into(), ..Default::default() }).await.unwrap();
+    reply.into_inner().user_id
+}
+
+// 带 x-session-token 元数据的请求
+fn as_session<T>(token: &str, message: T) -> Request<T> {
+    let mut request = Request::new(message);
+    request.metadata_mut().insert("x-session-token", token.parse().unwrap());
+    request
+}
+
+#[tokio::test]
+async fn register_login_send_and_stream() {
+    let mut client = start(Settings::default()).await;
+    let alice = register(&mut client, "alice").await;
+    let bob = register(&mut client, "bob").await;
+
+    let status = client.register(RegisterRequest { username: "alice".into(), password: "x".into(), ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::AlreadyExists);
+    let login = client.login(LoginRequest { username: "alice".into(), password: "secret".into(), ..Default::default() }).await.unwrap().into_inner();
+    assert_eq!(login.user_id, alice);
+    let alice_token = login.token;
+    let bob_token = client.login(LoginRequest { username: "bob".into(), password: "secret".into(), ..Default::default() }).await.unwrap().into_inner().token;
+    let status = client.login(LoginRequest { username: "alice".into(), password: "wrong".into(), ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let mut events = client.stream_events(as_session(&bob_token, StreamEventsRequest { user_id: bob.clone() })).await.unwrap().into_inner();
+    let sent = client.send_message(as_session(&alice_token, SendMessageRequest {
+        sender_id: alice.clone(),
+        receiver_id: bob.clone(),
+        content: "你好".into(),
+        message_type: String::new(),
+        workspace: String::new(),
+        client_message_id: "tmp-1".into(),
+        format: String::new(),
+    }))
+    .await
+    .unwrap()
+    .into_inner();
+    assert_eq!(sent.client_message_id, "tmp-1");
+
+    let event = tokio::time::timeout(Duration::from_secs(2), events.message()).await.unwrap().unwrap().unwrap();
+    let payload: serde_json::Value = serde_json::from_str(&event.payload).unwrap();
+    assert_eq!(payload["message_id"], sent.message_id.as_str());
+    assert_eq!(payload["content"], "你好");
+
+    // 重发同一临时ID得到同一条消息，不再推送；sender_id 可以留空
+    let resent = client.send_message(as_session(&alice_token, SendMessageRequest {
+        receiver_id: bob.clone(),
+        content: "你好".into(),
+        client_message_id: "tmp-1".into(),
+        format: String::new(),
+        ..Default::default()
+    }))
+    .await
+    .unwrap()
+    .into_inner();
+    assert_eq!((resent.message_id, resent.created_at), (sent.message_id.clone(), sent.created_at));
+    assert!(tokio::time::timeout(Duration::from_millis(200), events.message()).await.is_err());
+
+}
+
+#[tokio::test]
+async fn acting_user_comes_from_the_session_token() {
+    let mut client = start(Settings::default()).await;
+    let alice = register(&mut client, "alice").await;
+    let bob = register(&mut client, "bob").await;
+    let alice_token = client.login(LoginRequest { username: "alice".into(), password: "secret".into(), ..Default::default() }).await.unwrap().into_inner().token;
+    let message = SendMessageRequest { sender_id: bob.clone(), receiver_id: alice.clone(), content: "冒充".into(), ..Default::default() };
+
+    // 没有会话令牌时不能发消息或订阅事件，以 alice 的会话也不能冒充 bob
+    let status = client.send_message(message.clone()).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let status = client.stream_events(StreamEventsRequest { user_id: bob.clone() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+    let status = client.send_message(as_session(&alice_token, message)).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let status = client.stream_events(as_session(&alice_token, StreamEventsRequest { user_id: bob })).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let status = client.stream_events(as_session("bogus", StreamEventsRequest::default())).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn token_is_required_when_configured() {
+    let mut settings = Settings::default();
+    settings.grpc.token = "grpc-token".into();
+    let mut client = start(settings).await;
+
+    let status = client.register(RegisterRequest { username: "alice".into(), password: "secret".into(), ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::Unauthenticated);
+
+    let mut request = Request::new(RegisterRequest { username: "alice".into(), password: "secret".into(), ..Default::default() });
+    request.metadata_mut().insert("authorization", "Bearer grpc-token".parse().unwrap());
+    assert!(client.register(request).await.is_ok());
+}
+
+#[tokio::test]
+async fn login_checks_devices_like_rest() {
+    let state = AppState::new(DbPool::in_memory().unwrap(), Settings::default());
+    let mut client = serve(&state).await;
+    let alice = register(&mut client, "alice").await;
+    let login = |device_name: &str| LoginRequest {
+        username: "alice".into(),
+        password: "secret".into(),
+        device_name: device_name.into(),
+        platform: "server".into(),
+        ..Default::default()
+    };
+
+    // 上报设备信息时登记设备，新设备登录给用户发提醒，与 REST 登录相同
+    let first = client.login(login("Phone")).await.unwrap().into_inner();
+    assert!(!first.device_id.is_empty() && !first.token.is_empty() && !first.device_verification_required);
+    let notices = || state.db_pool.sync_messages(DEFAULT_WORKSPACE, &alice, 0, 50).unwrap().into_iter()
+        .filter_map(|m| (m.sender_id == "system").then(|| serde_json::from_str::<SystemEvent>(&m.content).unwrap()))
+        .collect::<Vec<_>>();
+    assert!(notices().is_empty());
+    let second = client.login(login("Laptop")).await.unwrap().into_inner();
+    assert_ne!(second.device_id, first.device_id);
+    assert_eq!(notices(), [SystemEvent::NewDeviceLogin {
+        device_id: second.device_id.clone(),
+        device_name: "Laptop".into(),
+        platform: "server".into(),
+    }]);
+    assert_eq!(state.db_pool.list_devices(&alice).unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn register_requires_the_configured_challenge() {
+    let mut settings = Settings::default();
+    settings.registration.challenge = "pow".into();
+    settings.registration.pow_difficulty = 0;
+    let state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    let mut client = serve(&state).await;
+
+    // 与 REST 注册一样，没有凭据或题目无效时拒绝
+    let request = |challenge: &str| RegisterRequest {
+        username: "alice".into(),
+        password: "secret".into(),
+        pow_challenge: Some(challenge.into()),
+        pow_nonce: Some("0".into()),
+        ..Default::default()
+    };
+    let status = client.register(RegisterRequest { username: "alice".into(), password: "secret".into(), ..Default::default() }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+    let status = client.register(request("伪造的题目")).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+
+    let (challenge, _) = state.db_pool.issue_registration_challenge(60, server::datetime::unix_now()).unwrap();
+    assert!(!client.register(request(&challenge)).await.unwrap().into_inner().user_id.is_empty());
+    // 题目只能用一次
+    let status = client.register(RegisterRequest { username: "bob".into(), ..request(&challenge) }).await.unwrap_err();
+    assert_eq!(status.code(), Code::PermissionDenied);
+}
+
+#[tokio::test]
+async fn group_messages_are_broadcast_as_message_events() {
+    let state = AppState::new(DbPool::in_memory().unwrap(), Settings::default());
+    let mut client = serve(&state).await;
+    let alice = register(&mut client, "alice").await;
+    let token = client.login(LoginRequest { username: "alice".into(), password: "secret".into(), ..Default::default() }).await.unwrap().into_inner().token;
+    let group = state.db_pool.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    let mut broadcast = state.group_chat_broadcast_channel_map.lock().unwrap()
+        .entry(group.id.clone())
+        .or_insert_with(|| tokio::sync::broadcast::channel(16).0)
+        .subscribe();
+
+    let sent = client.send_message(as_session(&token, SendMessageRequest {
+        receiver_id: group.id.clone(),
+        content: "大家好".into(),
+        message_type: "group".into(),
+        ..Default::default()
+    })).await.unwrap().into_inner();
+
+    // 与私聊推送相同的事件，而不是裸的消息内容
+    let payload: serde_json::Value = serde_json::from_str(&broadcast.recv().await.unwrap()).unwrap();
+    assert_eq!((payload["type"].as_str(), payload["message_id"].as_str()), (Some("message"), Some(sent.message_id.as_str())));
+    assert_eq!((payload["sender_id"].as_str(), payload["receiver_id"].as_str()), (Some(alice.as_str()), Some(group.id.as_str())));
+    assert_eq!((payload["content"].as_str(), payload["seq"].as_i64()), (Some("大家好"), Some(sent.seq)));
+}
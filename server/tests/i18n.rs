@@ -0,0 +1,64 @@
+mod common;
+
+use std::collections::{BTreeSet, HashSet};
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+
+fn codes(catalog: &str) -> BTreeSet<String> {
+    let table: toml::Table = toml::from_str(catalog).unwrap();
+    table
+        .iter()
+        .flat_map(|(section, entries)| {
+            entries.as_table().unwrap().keys().map(move |key| format!("{section}.{key}"))
+        })
+        .collect()
+}
+
+#[test]
+fn catalogs_cover_the_same_codes() {
+    let zh = include_str!("../locales/zh-CN.toml");
+    let en = include_str!("../locales/en-US.toml");
+    assert_eq!(codes(zh), codes(en));
+
+    // 源语言提示语用来反查 code，不能重复
+    let table: toml::Table = toml::from_str(zh).unwrap();
+    let mut seen = HashSet::new();
+    for entries in table.values() {
+        for text in entries.as_table().unwrap().values() {
+            assert!(seen.insert(text.as_str().unwrap()), "重复的提示语: {text}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn messages_follow_accept_language() {
+    let app = TestApp::new();
+    let register = json!({ "username": "alice", "password": "secret" });
+
+    let (status, body) = app.request_with_headers(
+        Method::POST, "/register", Some(register.clone()), &[("accept-language", "en-GB,en;q=0.9,zh;q=0.5")],
+    ).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["code"], "user.registered");
+    assert_eq!(body["message"], "Registration successful");
+
+    // 错误也带 code，默认仍是中文
+    let (status, body) = app.post("/register", register).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["code"], "user.exists");
+    assert_eq!(body["message"], "用户名已存在");
+
+    // 带参数的提示语
+    let (status, body) = app.request_with_headers(
+        Method::POST, "/messages/unread", Some(json!({ "user_id": "x" })), &[("x-workspace", "acme"), ("accept-language", "en-US")],
+    ).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["code"], "workspace.not_found");
+    assert_eq!(body["message"], "Workspace acme not found");
+
+    // 不支持的语言回落到中文
+    let (_, body) = app.request_with_headers(Method::GET, "/health", None, &[("accept-language", "fr-FR")]).await;
+    assert_eq!(body["message"], "服务器运行正常");
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use common::TestApp;
+use http_body_util::BodyExt;
+use server::settings::Settings;
+use tower::ServiceExt;
+
+const GPS: &[u8] = b"GPS 31.2304N 121.4737E";
+
+fn app(strip: bool) -> TestApp {
+    let mut settings = Settings::default();
+    let dir = std::env::temp_dir().join(format!("yueling-image-metadata-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    settings.attachments.strip_image_metadata = strip;
+    TestApp::with_settings(settings)
+}
+
+// 上传后下载回来的内容，同时检查元数据中的大小和 SHA-256 与清理后的文件一致
+async fn round_trip(app: &TestApp, auth: &str, content_type: &str, content: &[u8]) -> Vec<u8> {
+    let (status, body) = app.upload(auth, "photo", content_type, content).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let attachment = &body["attachment"];
+    let request = Request::get(format!("/attachments/{}", attachment["id"].as_str().unwrap()))
+        .header("authorization", auth)
+        .body(Body::empty())
+        .unwrap();
+    let response = app.router.clone().oneshot(request).await.unwrap();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes().to_vec();
+    assert_eq!(attachment["size"], bytes.len());
+    assert_eq!(attachment["sha256"], hex::encode(<sha2::Sha256 as sha2::Digest>::digest(&bytes)));
+    bytes
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+    let mut segment = vec![0xFF, marker];
+    segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(payload);
+    segment
+}
+
+// 小端 EXIF：IFD0 中有拍摄方向（6，需顺时针旋转 90 度）和一个指向 GPS 信息的条目
+fn exif() -> Vec<u8> {
+    let mut exif = b"Exif\0\0II*\0".to_vec();
+    exif.extend_from_slice(&8u32.to_le_bytes());
+    exif.extend_from_slice(&2u16.to_le_bytes());
+    exif.extend_from_slice(&[0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0]);
+    exif.extend_from_slice(&[0x25, 0x88, 4, 0, 1, 0, 0, 0, 38, 0, 0, 0]);
+    exif.extend_from_slice(&0u32.to_le_bytes());
+    exif.extend_from_slice(GPS);
+    exif
+}
+
+const SCAN_DATA: &[u8] = &[0x12, 0xFF, 0x00, 0x34, 0xFF, 0xD0, 0x56];
+
+fn jpeg() -> Vec<u8> {
+    let mut jpeg = vec![0xFF, 0xD8];
+    jpeg.extend(segment(0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"));
+    jpeg.extend(segment(0xE1, &exif()));
+    jpeg.extend(segment(0xE1, b"http://ns.adobe.com/xap/1.0/\0<x:xmpmeta/>"));
+    jpeg.extend(segment(0xED, b"Photoshop 3.0\0IPTC"));
+    jpeg.extend(segment(0xFE, b"taken at home"));
+    jpeg.extend(segment(0xDB, &[0u8; 65]));
+    jpeg.extend(segment(0xDA, &[1, 1, 0, 0, 63, 0]));
+    jpeg.extend_from_slice(SCAN_DATA);
+    jpeg.extend_from_slice(&[0xFF, 0xD9]);
+    jpeg
+}
+
+#[tokio::test]
+async fn jpeg_metadata_is_stripped_but_orientation_kept() {
+    let app = app(true);
+    let auth = app.login("alice").await.1;
+
+    let stripped = round_trip(&app, &auth, "image/jpeg", &jpeg()).await;
+    assert!(!contains(&stripped, GPS));
+    assert!(!contains(&stripped, b"xmpmeta"));
+    assert!(!contains(&stripped, b"IPTC"));
+    assert!(!contains(&stripped, b"taken at home"));
+    assert!(contains(&stripped, b"JFIF"));
+    // 只含拍摄方向的 EXIF
+    assert!(contains(&stripped, b"Exif\0\0MM\0*\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06"));
+    assert!(stripped.ends_with(&[&[0xFF, 0xDA, 0, 8, 1, 1, 0, 0, 63, 0], SCAN_DATA, &[0xFF, 0xD9]].concat()));
+
+    // 不是以图片上传的文件保持原样
+    assert_eq!(round_trip(&app, &auth, "application/octet-stream", &jpeg()).await, jpeg());
+    let (status, body) = app.upload(&auth, "photo", "image/jpeg", &jpeg()[..40]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("attachment.invalid_image")));
+}
+
+fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(&[0, 0, 0, 0]);
+    chunk
+}
+
+fn riff_chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+#[tokio::test]
+async fn png_and_webp_metadata_is_stripped() {
+    let app = app(true);
+    let auth = app.login("alice").await.1;
+
+    let ihdr = chunk(b"IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]);
+    let idat = chunk(b"IDAT", b"pixels");
+    let png = [
+        b"\x89PNG\r\n\x1a\n".to_vec(), ihdr.clone(), chunk(b"tEXt", GPS), chunk(b"eXIf", &exif()[6..]),
+        idat.clone(), chunk(b"IEND", b""),
+    ].concat();
+    let stripped = round_trip(&app, &auth, "image/png", &png).await;
+    assert_eq!(stripped, [b"\x89PNG\r\n\x1a\n".to_vec(), ihdr, idat, chunk(b"IEND", b"")].concat());
+
+    let vp8 = riff_chunk(b"VP8 ", b"frame");
+    let body = [b"WEBP".to_vec(), riff_chunk(b"VP8X", &[0x08, 0, 0, 0, 0, 0, 0, 0, 0, 0]), vp8.clone(), riff_chunk(b"EXIF", &exif()[6..])].concat();
+    let webp = [b"RIFF".to_vec(), (body.len() as u32).to_le_bytes().to_vec(), body].concat();
+    let stripped = round_trip(&app, &auth, "image/webp", &webp).await;
+    let body = [b"WEBP".to_vec(), riff_chunk(b"VP8X", &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]), vp8].concat();
+    assert_eq!(stripped, [b"RIFF".to_vec(), (body.len() as u32).to_le_bytes().to_vec(), body].concat());
+}
+
+#[tokio::test]
+async fn stripping_can_be_disabled() {
+    let app = app(false);
+    let auth = app.login("alice").await.1;
+    assert_eq!(round_trip(&app, &auth, "image/jpeg", &jpeg()).await, jpeg());
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use common::TestApp;
+use server::{
+    conversation::conversation_id,
+    datetime::parse_timestamp,
+    import::{self, CsvColumns, ImportReport},
+    settings::Settings,
+    workspaces::DEFAULT_WORKSPACE,
+    AppState, DbPool,
+};
+
+// 开启落库加密的应用，导入的内容同样加密
+fn app() -> TestApp {
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_messages = true;
+    let db = DbPool::in_memory().unwrap().with_encryption(&settings.security).unwrap();
+    TestApp::with_state(&AppState::new(db, settings))
+}
+
+// 会话中的 (序号, 时间, 内容)
+fn conversation(app: &TestApp, message_type: &str, a: &str, b: &str) -> Vec<(i64, i64, String)> {
+    let range = app.db.messages_from_seq(DEFAULT_WORKSPACE, &conversation_id(message_type, a, b), 1, 100).unwrap();
+    range.messages.into_iter().map(|m| (m.seq.unwrap(), m.created_at, m.content)).collect()
+}
+
+#[test]
+fn timestamps_are_parsed_with_and_without_zones() {
+    assert_eq!(parse_timestamp("1704153600", 0), Some(1_704_153_600));
+    assert_eq!(parse_timestamp("2024-01-02T00:00:00Z", 0), Some(1_704_153_600));
+    assert_eq!(parse_timestamp("2024-01-02 08:00", 8 * 3600), Some(1_704_153_600));
+    assert_eq!(parse_timestamp("2024-01-02T05:30:00.250+05:30", 0), Some(1_704_153_600));
+    assert_eq!(parse_timestamp("2024-02-29 00:00:00-01:00", 0), Some(1_709_168_400));
+    for invalid in ["2023-02-29 00:00:00", "2024-13-01 00:00", "2024-01-02 24:00", "2024-01-02", "yesterday"] {
+        assert_eq!(parse_timestamp(invalid, 0), None, "{invalid}");
+    }
+}
+
+#[tokio::test]
+async fn own_json_exports_import_with_original_ids_and_times() {
+    let app = app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    app.db.send_message(DEFAULT_WORKSPACE, &alice, &bob, "第一条", "private").unwrap();
+    app.db.send_message(DEFAULT_WORKSPACE, &bob, &alice, "第二条", "private").unwrap();
+    app.db.0.lock().unwrap().execute("UPDATE messages SET created_at = created_at - 86400", []).unwrap();
+    let original = conversation(&app, "private", &alice, &bob);
+
+    // 清空后从 export-user 的导出文件恢复
+    let export = serde_json::to_string(&app.db.export_user(&alice).unwrap()).unwrap();
+    app.db.0.lock().unwrap().execute_batch("DELETE FROM messages; DELETE FROM conversation_seqs;").unwrap();
+    let records = import::parse_json(&export).unwrap();
+    let report = app.db.import_messages(DEFAULT_WORKSPACE, records.clone(), 2_000_000_000).unwrap();
+    assert_eq!(report, ImportReport { imported: 2, skipped: 0 });
+    assert_eq!(conversation(&app, "private", &alice, &bob), original);
+    // 落库的是重新加密后的密文
+    let stored: Vec<u8> = app.db.0.lock().unwrap().query_row("SELECT content FROM messages LIMIT 1", [], |row| row.get(0)).unwrap();
+    assert!(!String::from_utf8_lossy(&stored).contains("第"));
+
+    // 重复导入同一文件时按原消息ID跳过
+    let report = app.db.import_messages(DEFAULT_WORKSPACE, records, 2_000_000_000).unwrap();
+    assert_eq!(report, ImportReport { imported: 0, skipped: 2 });
+
+    // 与 POST /messages/batch 相同的消息数组，没有时间的按导入时间
+    let batch = format!(r#"[{{ "sender_id": "{alice}", "receiver_id": "{bob}", "content": "补充", "message_type": "private" }}]"#);
+    app.db.import_messages(DEFAULT_WORKSPACE, import::parse_json(&batch).unwrap(), 2_000_000_000).unwrap();
+    assert_eq!(conversation(&app, "private", &alice, &bob)[2], (3, 2_000_000_000, "补充".to_string()));
+}
+
+#[tokio::test]
+async fn csv_columns_are_mapped_and_rows_ordered_by_time() {
+    let app = app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "读书会", &alice).unwrap();
+
+    // 列顺序任意，未映射的列忽略；引号中可以有逗号、换行和双引号
+    let csv = format!(
+        "\u{feff}Time,From,To,Text,Extra\r\n\
+         2024-01-02 08:00:05,bob,alice,\"收到，谢谢\",x\r\n\
+         2024-01-02 08:00:00,alice,bob,\"他说：\"\"明天\n见\"\"\",y\r\n\
+         \r\n\
+         1704153600,bob,{},群里的消息,z\r\n",
+        group.id,
+    );
+    let columns = CsvColumns::parse("sender=From, receiver=To, content=Text, created_at=Time").unwrap();
+    let records = import::parse_csv(&csv, &columns, 8 * 3600).unwrap();
+    assert_eq!(records.len(), 3);
+    let report = app.db.import_messages(DEFAULT_WORKSPACE, records, 2_000_000_000).unwrap();
+    assert_eq!(report.imported, 3);
+
+    // 按时间先后分配序号，用户名换成了用户ID
+    assert_eq!(conversation(&app, "private", &alice, &bob), vec![
+        (1, 1_704_153_600, "他说：\"明天\n见\"".to_string()),
+        (2, 1_704_153_605, "收到，谢谢".to_string()),
+    ]);
+    let messages = app.db.messages_from_seq(DEFAULT_WORKSPACE, &conversation_id("group", &bob, &group.id), 1, 10).unwrap().messages;
+    assert_eq!((messages[0].sender_id.as_str(), messages[0].message_type.as_str()), (bob.as_str(), "group"));
+
+    // 有无法识别的用户时不写入任何消息
+    let csv = "sender,receiver,content\nalice,bob,会被回滚吗\nalice,nobody,找不到\n";
+    let records = import::parse_csv(csv, &CsvColumns::default(), 0).unwrap();
+    let error = app.db.import_messages(DEFAULT_WORKSPACE, records, 2_000_000_000).unwrap_err();
+    assert_eq!(error, "第 2 条消息的接收者 nobody 不存在");
+    assert_eq!(conversation(&app, "private", &alice, &bob).len(), 2);
+
+    assert!(import::parse_csv("From,To\nalice,bob\n", &CsvColumns::default(), 0).unwrap_err().contains("sender"));
+    assert!(import::parse_csv("sender,receiver,content,created_at\nalice,bob,hi,明天\n", &CsvColumns::default(), 0)
+        .unwrap_err().contains("第 2 行"));
+    assert!(CsvColumns::parse("author=From").is_err());
+}
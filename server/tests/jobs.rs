@@ -0,0 +1,67 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::{run_next_job, settings::Settings, AppState, DbPool};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+fn job_state(export_dir: &str) -> AppState {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.jobs.export_dir = export_dir.into();
+    AppState::new(DbPool::in_memory().unwrap(), settings)
+}
+
+#[tokio::test]
+async fn export_runs_in_background_worker() {
+    let dir = std::env::temp_dir().join(format!("yueling-export-{}", uuid::Uuid::new_v4()));
+    let state = job_state(dir.to_str().unwrap());
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::POST, &format!("/admin/users/{alice}/export"), None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let job_id = body["job_id"].as_str().unwrap().to_string();
+
+    // 接口只负责入队，导出文件由工作线程生成
+    assert!(!dir.exists());
+    assert!(run_next_job(&state).await.unwrap());
+    assert!(!run_next_job(&state).await.unwrap());
+
+    let job = app.db.get_job(&job_id).unwrap().unwrap();
+    assert_eq!(job.status, "done");
+    let exported: Vec<_> = std::fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(exported.len(), 1);
+    let (status, _) = app.call(ADMIN_AUTH, Method::POST, "/admin/users/nobody/export", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn failed_jobs_become_dead_and_can_be_retried() {
+    let state = job_state("unused");
+    let app = TestApp::with_state(&state);
+    let job_id = app.db.enqueue_job("unknown", &json!({}), 1, 0).unwrap();
+
+    assert!(run_next_job(&state).await.unwrap());
+    let (status, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/jobs?status=dead", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["jobs"][0]["id"], job_id.as_str());
+    assert_eq!(body["jobs"][0]["attempts"], 1);
+    assert!(body["jobs"][0]["last_error"].as_str().unwrap().contains("unknown"));
+
+    let (status, _) = app.call(ADMIN_AUTH, Method::GET, "/admin/jobs?status=lost", None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app.call(ADMIN_AUTH, Method::POST, &format!("/admin/jobs/{job_id}/retry"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let job = app.db.get_job(&job_id).unwrap().unwrap();
+    assert_eq!((job.status.as_str(), job.attempts), ("pending", 0));
+
+    // 只有死信任务可以手动重试
+    let (status, _) = app.call(ADMIN_AUTH, Method::POST, &format!("/admin/jobs/{job_id}/retry"), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::{escrow, settings::Settings};
+
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+const MASTER_KEY: &str = "test-master-key";
+const PASSPHRASE: &str = "correct horse battery staple";
+
+fn app(master_key: &str) -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = "test-admin-token".into();
+    settings.security.master_key = master_key.into();
+    TestApp::with_settings(settings)
+}
+
+#[tokio::test]
+async fn exported_keyring_restores_the_master_key_on_another_host() {
+    let old_host = app(MASTER_KEY);
+    let (status, body) = old_host.call(ADMIN_AUTH, Method::POST, "/admin/keyring/export", Some(json!({ "passphrase": "short" }))).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "admin.keyring_weak_passphrase"));
+    let (status, body) = old_host.call(ADMIN_AUTH, Method::POST, "/admin/keyring/export", Some(json!({ "passphrase": PASSPHRASE }))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let keyring = body["keyring"].clone();
+    assert_eq!(keyring["fingerprint"], escrow::fingerprint(MASTER_KEY));
+    assert!(!keyring.to_string().contains(MASTER_KEY));
+
+    // 新主机上校验密钥包，并用 import 恢复出同一把主密钥
+    let new_host = app("other-master-key");
+    let (status, body) = new_host.call(ADMIN_AUTH, Method::POST, "/admin/keyring/verify", Some(json!({ "keyring": keyring, "passphrase": PASSPHRASE }))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!((body["fingerprint"].clone(), body["matches_current"].clone()), (keyring["fingerprint"].clone(), json!(false)));
+    let bundle: escrow::KeyringBundle = serde_json::from_value(keyring.clone()).unwrap();
+    assert_eq!(escrow::import(&bundle, PASSPHRASE).unwrap(), MASTER_KEY);
+
+    let (status, body) = new_host.call(ADMIN_AUTH, Method::POST, "/admin/keyring/verify", Some(json!({ "keyring": keyring, "passphrase": "wrong passphrase!" }))).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "admin.keyring_wrong_passphrase"));
+    let (_, body) = app("").call(ADMIN_AUTH, Method::POST, "/admin/keyring/export", Some(json!({ "passphrase": PASSPHRASE }))).await;
+    assert_eq!(body["code"], "admin.keyring_no_master_key");
+}
+
+#[test]
+fn keyring_headers_are_authenticated() {
+    let bundle = escrow::export(MASTER_KEY, PASSPHRASE, 1_700_000_000).unwrap();
+    assert_eq!(escrow::import(&bundle, PASSPHRASE).unwrap(), MASTER_KEY);
+
+    // 改动指纹、时间或 KDF 参数都无法解开
+    let mut forged = bundle.clone();
+    forged.fingerprint = escrow::fingerprint("other");
+    assert!(matches!(escrow::import(&forged, PASSPHRASE), Err(escrow::EscrowError::WrongPassphrase)));
+    let mut forged = bundle.clone();
+    forged.created_at += 1;
+    assert!(matches!(escrow::import(&forged, PASSPHRASE), Err(escrow::EscrowError::WrongPassphrase)));
+    // 超出上限的 KDF 参数直接拒绝，不去执行派生
+    let mut forged = bundle.clone();
+    forged.kdf.memory_kib = u32::MAX;
+    assert!(matches!(escrow::import(&forged, PASSPHRASE), Err(escrow::EscrowError::Malformed)));
+    let mut forged = bundle;
+    forged.format = "yueling-keyring-v0".into();
+    assert!(matches!(escrow::import(&forged, PASSPHRASE), Err(escrow::EscrowError::Unsupported(_))));
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::{e2e::TestServer, TestApp};
+use serde_json::{json, Value};
+use server::{settings::Settings, AppState, DbPool};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+async fn login(app: &TestApp, username: &str) -> (StatusCode, Value) {
+    app.post("/login", json!({ "username": username, "password": "secret" })).await
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str) -> (StatusCode, Value) {
+    app.post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": receiver, "content": "你好", "message_type": "private" })).await
+}
+
+#[tokio::test]
+async fn maintenance_blocks_everyone_but_exempt_users() {
+    // 豁免名单需要用户ID，先在同一个数据库上注册
+    let db = DbPool::in_memory().unwrap();
+    let setup = TestApp::with_state(&AppState::new(db.clone(), Settings::default()));
+    let ops = setup.register("ops", "secret").await;
+    let alice = setup.register("alice", "secret").await;
+    let bob = setup.register("bob", "secret").await;
+
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.maintenance.exempt_user_ids = vec![ops.clone()];
+    let server = TestServer::with_state(AppState::new(db, settings)).await;
+    let app = server.app();
+    let mut ops_ws = server.ws(&ops).await;
+    let mut alice_ws = server.ws(&alice).await;
+    // 只订阅群聊、没有 identify 的连接也会断开
+    let mut anonymous = server.ws_raw("zh-CN").await;
+    anonymous.send(json!({ "list_of_group_chats": [] })).await;
+    anonymous.send(json!({ "type": "bogus" })).await;
+    assert_eq!(anonymous.next_event().await["type"], "error");
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::PUT, "/admin/maintenance", Some(json!({ "notice": "升级数据库", "duration_secs": 600 }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("server.maintenance_enabled")));
+    assert_eq!(body["disconnected"], 2);
+
+    // 所有连接都收到通知，非豁免的连接随后断开
+    for ws in [&mut ops_ws, &mut alice_ws, &mut anonymous] {
+        let event = ws.next_event().await;
+        assert_eq!((event["type"].as_str(), event["active"].as_bool()), (Some("maintenance"), Some(true)));
+        assert_eq!((event["code"].as_str(), event["notice"].as_str()), (Some("server.maintenance"), Some("升级数据库")));
+        assert_eq!(event["duration_secs"], 600);
+        assert_eq!(event["expected_end_at"].as_i64().unwrap() - event["started_at"].as_i64().unwrap(), 600);
+    }
+    assert_eq!(alice_ws.close_frame().await, (4004, "maintenance".to_string()));
+    assert_eq!(anonymous.close_frame().await, (4004, "maintenance".to_string()));
+    ops_ws.assert_silent().await;
+
+    let (status, body) = login(&app, "alice").await;
+    assert_eq!((status, body["code"].as_str(), body["message"].as_str()), (StatusCode::SERVICE_UNAVAILABLE, Some("server.maintenance"), Some("升级数据库")));
+    let (status, body) = send(&app, &alice, &bob).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::SERVICE_UNAVAILABLE, Some("server.maintenance")));
+    // 重新连接的非豁免用户 identify 后立即断开
+    let mut alice_ws = server.ws(&alice).await;
+    assert_eq!(alice_ws.next_event().await["type"], "maintenance");
+    assert_eq!(alice_ws.close_frame().await, (4004, "maintenance".to_string()));
+
+    let (status, _) = login(&app, "ops").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&app, &ops, &bob).await;
+    assert_eq!(status, StatusCode::OK);
+    // 豁免用户的连接照常收到自己消息的回显
+    assert_eq!(ops_ws.next_event().await["type"], "own_message");
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/maintenance", None).await;
+    assert_eq!(body["maintenance"]["notice"], "升级数据库");
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::DELETE, "/admin/maintenance", None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("server.maintenance_disabled")));
+    assert_eq!(ops_ws.next_event().await, json!({ "type": "maintenance", "active": false }));
+    let (status, _) = login(&app, "alice").await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/maintenance", None).await;
+    assert!(body["maintenance"].is_null());
+}
+
+#[tokio::test]
+async fn default_notice_is_localized_and_duration_is_validated() {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    let server = TestServer::with_settings(settings).await;
+    let app = server.app();
+    app.register("alice", "secret").await;
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::PUT, "/admin/maintenance", Some(json!({ "duration_secs": -1 }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("server.maintenance_negative_duration")));
+    let (status, body) = app.call(ADMIN_AUTH, Method::PUT, "/admin/maintenance", Some(json!({}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body["maintenance"]["expected_end_at"].is_null());
+
+    let (status, body) = app.request_with_headers(
+        Method::POST,
+        "/login",
+        Some(json!({ "username": "alice", "password": "secret" })),
+        &[("accept-language", "en-US")],
+    ).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!((body["code"].as_str(), body["message"].as_str()), (Some("server.maintenance"), Some("The server is under maintenance, please try again later")));
+}
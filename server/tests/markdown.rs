@@ -0,0 +1,104 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{
+    markdown::{parse, EntityKind, FormattedText, TextEntity, UnsafeLink},
+    outbound,
+    settings::Settings,
+    AppState, DbPool,
+};
+
+#[test]
+fn markdown_subset_is_parsed_into_text_and_entities() {
+    let parsed = parse("**重要** 见 [文档](https://example.com/a_b) 和 `cargo *test*`，_注意_").unwrap();
+    assert_eq!(parsed.text, "重要 见 文档 和 cargo *test*，注意");
+    assert_eq!(parsed.entities, vec![
+        TextEntity::new(EntityKind::Bold, 0, 2),
+        TextEntity { url: Some("https://example.com/a_b".into()), ..TextEntity::new(EntityKind::Link, 5, 2) },
+        TextEntity::new(EntityKind::Code, 10, 12),
+        TextEntity::new(EntityKind::Italic, 23, 2),
+    ]);
+
+    // 没有配对的标记、词中间的下划线和转义的标记都原样保留
+    let plain = "2 * 3 = 6, snake_case_name, \\*不是斜体\\*, **未闭合";
+    assert_eq!(parse(plain).unwrap(), FormattedText {
+        text: "2 * 3 = 6, snake_case_name, *不是斜体*, **未闭合".into(),
+        entities: vec![],
+    });
+    // HTML 不被解释，只是普通文本
+    assert_eq!(parse("<script>alert(1)</script> *x*").unwrap().text, "<script>alert(1)</script> x");
+}
+
+#[test]
+fn links_are_limited_to_safe_schemes() {
+    assert!(parse("[邮件](mailto:a@example.com)").is_ok());
+    for url in ["javascript:alert", "JavaScript:void", "data:text/html,<b>x</b>", "//example.com", "https://"] {
+        assert_eq!(parse(&format!("[点我]({url})")), Err(UnsafeLink(url.into())));
+    }
+    // 不构成链接语法的方括号不检查
+    assert_eq!(parse("[注] (javascript:x)").unwrap().entities, vec![]);
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str, content: &str, format: &str) -> (StatusCode, Value) {
+    app.post_as(sender, "/send-message", json!({
+        "sender_id": sender,
+        "receiver_id": receiver,
+        "content": content,
+        "message_type": "private",
+        "format": format,
+    })).await
+}
+
+#[tokio::test]
+async fn markdown_messages_are_stored_with_entities() {
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_messages = true;
+    let db = DbPool::in_memory().unwrap().with_encryption(&settings.security).unwrap();
+    let state = AppState::new(db, settings);
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let (tx, mut rx) = outbound::channel(16);
+    state.attach_client("bob-phone", &bob, tx);
+
+    let (status, body) = send(&app, &alice, &bob, "**发布** 见 [说明](https://example.com)", "markdown").await;
+    assert_eq!(status, StatusCode::OK);
+    let message_id = body["message_id"].as_str().unwrap().to_string();
+    let expected = json!([
+        { "type": "bold", "offset": 0, "length": 2 },
+        { "type": "link", "offset": 5, "length": 2, "url": "https://example.com" },
+    ]);
+    let event: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+    assert_eq!((&event["content"], &event["entities"]), (&json!("发布 见 说明"), &expected));
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!((&body["messages"][0]["content"], &body["messages"][0]["entities"]), (&json!("发布 见 说明"), &expected));
+    // 格式区间与内容一样加密保存，链接地址不以明文落库
+    let stored: String = app.db.0.lock().unwrap()
+        .query_row("SELECT entities FROM messages WHERE id = ?", [&message_id], |row| row.get(0))
+        .unwrap();
+    assert!(!stored.contains("example.com"));
+
+    // 纯文本消息原样保存，不带 entities
+    send(&app, &alice, &bob, "**不解析**", "plain").await;
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!(body["messages"][1]["content"], "**不解析**");
+    assert!(body["messages"][1].get("entities").is_none());
+}
+
+#[tokio::test]
+async fn unsafe_markdown_is_rejected() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (status, body) = send(&app, &alice, &bob, "[点我](javascript:alert(1))", "markdown").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.unsafe_link")));
+    let (status, body) = send(&app, &alice, &bob, "<b>hi</b>", "html").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.invalid_format")));
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!(body["messages"], json!([]));
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{outbound, settings::Settings, AppState, DbPool};
+
+#[tokio::test]
+async fn client_message_id_is_echoed_and_deduplicates_resends() {
+    let state = AppState::new(DbPool::in_memory().unwrap(), Settings::default());
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    // alice 的 WebSocket 连接
+    let (tx, mut alice_rx) = outbound::channel(10);
+    state.attach_client("client-1", &alice, tx);
+
+    let send = json!({ "sender_id": alice, "receiver_id": bob, "content": "你好", "message_type": "private", "client_message_id": "tmp-1" });
+    let (status, body) = app.post_as(&alice, "/send-message", send.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!(body["client_message_id"], "tmp-1");
+    let message_id = body["message_id"].as_str().unwrap().to_string();
+
+    let ack: Value = serde_json::from_str(&alice_rx.try_recv().unwrap()).unwrap();
+    assert_eq!(ack["client_message_id"], "tmp-1");
+    assert_eq!((&ack["message_id"], &ack["created_at"], &ack["sent_at"]), (&json!(message_id), &body["created_at"], &body["sent_at"]));
+    let echo: Value = serde_json::from_str(&alice_rx.try_recv().unwrap()).unwrap();
+    assert_eq!(echo["type"], "own_message");
+
+    // 没收到确认而重发：返回同一条消息，不会保存第二份
+    let (_, resent) = app.post_as(&alice, "/send-message", send).await;
+    assert_eq!(resent["message_id"], message_id.as_str());
+    // 重发只再次确认，不再回显
+    assert_eq!(serde_json::from_str::<Value>(&alice_rx.try_recv().unwrap()).unwrap()["type"], "message_ack");
+    assert!(alice_rx.try_recv().is_err());
+    let (_, history) = app.post_as(&bob, "/messages/history", json!({ "user_id": bob, "peer_id": alice, "limit": 10 })).await;
+    assert_eq!(history["messages"].as_array().unwrap().len(), 1);
+
+    // 临时ID只在同一发送者内去重
+    let (_, reply) = app.post_as(&bob, "/send-message", json!({ "sender_id": bob, "receiver_id": alice, "content": "你也好", "message_type": "private", "client_message_id": "tmp-1" })).await;
+    assert_ne!(reply["message_id"], message_id.as_str());
+
+    // 不带临时ID时响应中没有该字段
+    let (_, plain) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "hi", "message_type": "private" })).await;
+    assert!(plain.get("client_message_id").is_none());
+
+    let (status, body) = app
+        .post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "x", "message_type": "private", "client_message_id": "x".repeat(65) }))
+        .await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.invalid_client_id")));
+}
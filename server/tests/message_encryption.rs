@@ -0,0 +1,159 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::json;
+use server::{
+    conversation::{conversation_id, sealed_bytes_epoch},
+    settings::Settings,
+    workspaces::DEFAULT_WORKSPACE,
+    ConversationKeys, DbPool,
+};
+
+const MASTER_KEY: &str = "test-master-key";
+
+fn encrypted_app() -> TestApp {
+    let mut settings = Settings::default();
+    settings.security.master_key = MASTER_KEY.into();
+    settings.security.encrypt_messages = true;
+    TestApp::with_settings(settings)
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str, content: &str) -> String {
+    let (status, body) = app
+        .post_as(sender, "/send-message", json!({
+            "sender_id": sender,
+            "receiver_id": receiver,
+            "content": content,
+            "message_type": "private"
+        }))
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    body["message_id"].as_str().unwrap().to_string()
+}
+
+// 加密的内容以 BLOB 保存
+fn stored_content(app: &TestApp, message_id: &str) -> Vec<u8> {
+    let conn = app.db.0.lock().unwrap();
+    conn.query_row("SELECT content FROM messages WHERE id = ?", [message_id], |row| row.get(0)).unwrap()
+}
+
+#[tokio::test]
+async fn messages_are_stored_encrypted_per_conversation() {
+    let app = encrypted_app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let carol = app.register("carol", "secret").await;
+
+    // 开启加密之前保存的明文消息仍然可读
+    app.db.0.lock().unwrap().execute(
+        "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES ('legacy', ?1, ?2, '旧消息', 'private', 1)",
+        [&alice, &bob],
+    ).unwrap();
+    let to_bob = send(&app, &alice, &bob, "你好 bob").await;
+    let to_carol = send(&app, &alice, &carol, "你好 carol").await;
+
+    let (_, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    let contents: Vec<_> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].clone()).collect();
+    assert_eq!(contents, vec![json!("旧消息"), json!("你好 bob")]);
+
+    let stored = stored_content(&app, &to_bob);
+    assert!(!stored.windows(3).any(|w| w == b"bob"));
+    assert_eq!(sealed_bytes_epoch(&stored), Some(0));
+
+    // 每个会话的密钥不同，密文也绑定消息ID
+    let keys = ConversationKeys::new(MASTER_KEY, 0);
+    let alice_bob = conversation_id("private", &bob, &alice);
+    assert_eq!(keys.open_bytes(&alice_bob, &to_bob, &stored).unwrap(), "你好 bob");
+    assert!(keys.open_bytes(&conversation_id("private", &alice, &carol), &to_bob, &stored).is_err());
+    assert!(keys.open_bytes(&alice_bob, &to_carol, &stored).is_err());
+    assert!(ConversationKeys::new("other-key", 0).open_bytes(&alice_bob, &to_bob, &stored).is_err());
+}
+
+#[tokio::test]
+async fn conversation_keys_ratchet_forward() {
+    let app = encrypted_app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let first = send(&app, &alice, &bob, "第一条").await;
+    assert_eq!(app.db.conversation_epoch("private", &alice, &bob).unwrap(), Some(0));
+
+    // 纪元开始已超过轮换间隔，下一条消息使用新纪元的密钥
+    app.db.0.lock().unwrap().execute("UPDATE conversation_keys SET started_at = 0", []).unwrap();
+    let second = send(&app, &bob, &alice, "第二条").await;
+    assert_eq!(app.db.conversation_epoch("private", &alice, &bob).unwrap(), Some(1));
+    assert_eq!(sealed_bytes_epoch(&stored_content(&app, &first)), Some(0));
+    assert_eq!(sealed_bytes_epoch(&stored_content(&app, &second)), Some(1));
+
+    let (_, body) = app
+        .post_as(&alice, "/messages/sync", json!({ "user_id": alice, "last_sync_time": 0, "limit": 50 }))
+        .await;
+    // 两条消息可能在同一秒内发出，只比较内容
+    let mut contents: Vec<_> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap().to_string()).collect();
+    contents.sort();
+    assert_eq!(contents, ["第一条", "第二条"]);
+
+    // 缺少主密钥时不能开启消息加密
+    let mut settings = Settings::default();
+    settings.security.encrypt_messages = true;
+    assert!(ConversationKeys::from_settings(&settings.security).is_err());
+}
+
+#[tokio::test]
+async fn decrypted_contents_are_cached_until_a_key_rotates() {
+    let app = encrypted_app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let first = send(&app, &alice, &bob, "第一条").await;
+    let sync = || app.post_as(&alice, "/messages/sync", json!({ "user_id": alice, "last_sync_time": 0, "limit": 50 }));
+    assert_eq!(sync().await.1["messages"][0]["content"], "第一条");
+
+    // 缓存命中时不再解密：换成同一纪元的无效密文仍返回缓存的明文
+    app.db.0.lock().unwrap().execute("UPDATE messages SET content = 'ylm1:AAAAAAAAbroken' WHERE id = ?", [&first]).unwrap();
+    let (status, body) = sync().await;
+    assert_eq!((status, body["messages"][0]["content"].as_str().unwrap()), (StatusCode::OK, "第一条"));
+
+    // 会话换到新纪元后缓存清空，无效密文重新解密失败，同步结果中跳过该条
+    app.db.0.lock().unwrap().execute("UPDATE conversation_keys SET started_at = 0", []).unwrap();
+    send(&app, &bob, &alice, "第二条").await;
+    let (_, body) = sync().await;
+    let contents: Vec<_> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].clone()).collect();
+    assert_eq!(contents, vec![json!("第二条")]);
+}
+
+#[test]
+fn base64_sealed_contents_are_migrated_to_blobs() {
+    let path = std::env::temp_dir().join(format!("yueling-blobs-{}.db", uuid::Uuid::new_v4()));
+    let path_str = path.to_str().unwrap();
+    let keys = ConversationKeys::new(MASTER_KEY, 0);
+    let bob = {
+        // 模拟改为 BLOB 之前的数据库：密文以加前缀的 base64 文本保存
+        let db = DbPool::new(path_str).unwrap();
+        let alice = db.register_user("alice", "", "secret").unwrap().id;
+        let bob = db.register_user("bob", "", "secret").unwrap().id;
+        let sealed = keys.seal(&conversation_id("private", &alice, &bob), 0, "m1", "旧密文");
+        let conn = db.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES ('m1', ?1, ?2, ?3, 'private', 1)",
+            [&alice, &bob, &sealed],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES ('m2', ?1, ?2, '明文', 'private', 2)",
+            [&alice, &bob],
+        ).unwrap();
+        conn.pragma_update(None, "user_version", 38).unwrap();
+        bob
+    };
+
+    let db = DbPool::new(path_str).unwrap().with_message_keys(keys, 0);
+    let types: Vec<String> = {
+        let conn = db.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT typeof(content) FROM messages ORDER BY id").unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<Result<_, _>>().unwrap()
+    };
+    assert_eq!(types, ["blob", "text"]);
+    let contents: Vec<_> = db.get_unread_messages(DEFAULT_WORKSPACE, &bob).unwrap().into_iter().map(|m| m.content).collect();
+    assert_eq!(contents, ["旧密文", "明文"]);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use common::e2e::TestServer;
+use serde_json::json;
+use server::settings::Settings;
+
+// 某一序列的取值，如 yueling_crypto_operation_seconds_count{operation="encrypt",size="1024"}
+fn sample(body: &str, series: &str) -> f64 {
+    body.lines()
+        .find_map(|line| line.strip_prefix(series).and_then(|rest| rest.strip_prefix(' ')))
+        .unwrap_or_else(|| panic!("缺少序列 {series}"))
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn crypto_latency_histograms_are_exported_as_openmetrics() {
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_messages = true;
+    settings.metrics.enabled = true;
+    settings.metrics.token = "scrape-token".into();
+    let server = TestServer::with_settings(settings).await;
+    let (_, alice) = server.signup("alice").await;
+    let (client, bob) = server.signup("bob").await;
+    let (status, _) = server.app().post_as(&alice.user_id, "/send-message", json!({
+        "sender_id": alice.user_id, "receiver_id": bob.user_id, "content": "你好", "message_type": "private",
+    })).await;
+    assert!(status.is_success());
+    client.unread_messages(&bob.user_id).await.unwrap();
+
+    let http = reqwest::Client::new();
+    let url = format!("{}/metrics", server.url());
+    let response = http.get(&url).bearer_auth("scrape-token").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.headers()["content-type"], "application/openmetrics-text; version=1.0.0; charset=utf-8");
+    let body = response.text().await.unwrap();
+    assert!(body.starts_with("# TYPE yueling_crypto_operation_seconds histogram\n"));
+    assert!(body.ends_with("# EOF\n"));
+    // 登录校验了两次密码，消息内容加密保存、读取时解密
+    let series = |kind: &str, operation: &str| format!("yueling_crypto_operation_seconds_{kind}{{operation=\"{operation}\",size=\"1024\"}}");
+    assert!(sample(&body, &series("count", "bcrypt_verify")) >= 2.0);
+    assert!(sample(&body, &series("sum", "bcrypt_verify")) > 0.0);
+    assert!(sample(&body, &series("count", "encrypt")) >= 1.0);
+    assert!(sample(&body, &series("count", "decrypt")) >= 1.0);
+    // 各档累计，+Inf 档等于总次数
+    let count = sample(&body, &series("count", "bcrypt_verify"));
+    let inf = sample(&body, "yueling_crypto_operation_seconds_bucket{operation=\"bcrypt_verify\",size=\"1024\",le=\"+Inf\"}");
+    assert_eq!(inf, count);
+    assert_eq!(sample(&body, "yueling_crypto_operation_seconds_count{operation=\"encrypt\",size=\"+Inf\"}"), 0.0);
+    // WebSocket 发送队列的长度和丢弃数
+    assert!(sample(&body, "yueling_ws_send_queue_events") >= 0.0);
+    assert!(sample(&body, "yueling_ws_send_queue_dropped_total{priority=\"low\"}") >= 0.0);
+    assert!(sample(&body, "yueling_http_panics_total") >= 0.0);
+
+    let response = http.get(&url).bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(response.status(), 401);
+    let disabled = TestServer::start().await;
+    let response = http.get(format!("{}/metrics", disabled.url())).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{e2e::TestServer, TestApp};
+use serde_json::{json, Value};
+use server::settings::Settings;
+
+async fn device_login(app: &TestApp, username: &str) -> String {
+    let (_, body) = app.post("/login", json!({ "username": username, "password": "secret", "device_name": "Phone", "platform": "ios" })).await;
+    body["device_id"].as_str().unwrap().to_string()
+}
+
+async fn security_notices(app: &TestApp, user_id: &str) -> Vec<Value> {
+    let (_, body) = app.post_as(user_id, "/messages/sync", json!({ "user_id": user_id, "last_sync_time": 0, "limit": 50 })).await;
+    body["messages"].as_array().unwrap().iter()
+        .filter(|m| m["sender_id"] == "system")
+        .map(|m| serde_json::from_str(m["content"].as_str().unwrap()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn messages_are_echoed_to_the_senders_other_devices() {
+    let server = TestServer::start().await;
+    let app = server.app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let mut phone = server.ws(&alice).await;
+    let mut laptop = server.ws(&alice).await;
+    let mut bob_socket = server.ws(&bob).await;
+
+    // 手机经 WebSocket 发送：手机收到确认，电脑收到回显，bob 收到消息
+    phone.send(json!({
+        "type": "message", "sender_id": alice, "receiver_id": bob, "content": "你好", "client_message_id": "tmp-1",
+    })).await;
+    let ack = phone.next_event().await;
+    assert_eq!(ack["type"], "message_ack");
+    let echo = laptop.next_event().await;
+    assert_eq!(echo["type"], "own_message");
+    assert_eq!((&echo["message_id"], &echo["client_message_id"]), (&ack["message_id"], &json!("tmp-1")));
+    assert_eq!((echo["receiver_id"].as_str(), echo["content"].as_str()), (Some(bob.as_str()), Some("你好")));
+    assert_eq!(bob_socket.next_event().await["content"], "你好");
+    phone.assert_silent().await;
+
+    // 经 HTTP 发送时没有可排除的连接，两台设备都收到回显
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "在吗", "message_type": "private" })).await;
+    for device in [&mut phone, &mut laptop] {
+        let echo = device.next_event().await;
+        assert_eq!((echo["type"].as_str(), &echo["message_id"]), (Some("own_message"), &body["message_id"]));
+    }
+
+    // 电脑断开后手机仍在线，回显照常送达
+    drop(laptop);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(server.state.is_online(&alice));
+    app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "再见", "message_type": "private" })).await;
+    assert_eq!(phone.next_event().await["content"], "再见");
+
+    // 增量同步同样包含自己发出的消息
+    let (_, sync) = app.post_as(&alice, "/messages/sync", json!({ "user_id": alice, "last_sync_time": 0, "limit": 50 })).await;
+    let contents: Vec<&str> = sync["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+    assert_eq!(contents, ["你好", "在吗", "再见"]);
+}
+
+#[tokio::test]
+async fn a_new_connection_from_the_same_device_replaces_the_old_one() {
+    let server = TestServer::start().await;
+    let app = server.app();
+    let alice = app.register("alice", "secret").await;
+    let phone = device_login(&app, "alice").await;
+
+    let mut old = server.ws_device(&alice, &phone).await;
+    // 不带设备ID的连接不受影响
+    let mut web = server.ws(&alice).await;
+    let mut new = server.ws_device(&alice, &phone).await;
+
+    assert_eq!(old.close_frame().await, (4001, "replaced_by_new_login".to_string()));
+    // 新连接和其他连接都收到安全提醒
+    for socket in [&mut new, &mut web] {
+        let notice = socket.next_event().await;
+        assert_eq!((notice["type"].as_str(), notice["sender_id"].as_str(), notice["receiver_id"].as_str()), (Some("system_message"), Some("system"), Some(alice.as_str())));
+        assert_eq!((notice["event"]["event"].as_str(), notice["event"]["replaced_old"].as_bool()), (Some("duplicate_login"), Some(true)));
+    }
+    let notices = security_notices(&app, &alice).await;
+    assert_eq!(notices.len(), 1);
+    assert_eq!((notices[0]["device_id"].as_str(), notices[0]["device_name"].as_str()), (Some(phone.as_str()), Some("Phone")));
+}
+
+#[tokio::test]
+async fn deny_new_keeps_the_existing_connection() {
+    let mut settings = Settings::default();
+    settings.devices.duplicate_login = "deny_new".into();
+    let server = TestServer::with_settings(settings).await;
+    let app = server.app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let phone = device_login(&app, "alice").await;
+
+    let mut old = server.ws_device(&alice, &phone).await;
+    let mut new = server.ws_device(&alice, &phone).await;
+    assert_eq!(new.close_frame().await, (4002, "duplicate_login".to_string()));
+    assert_eq!(old.next_event().await["event"]["replaced_old"], false);
+
+    // 原连接照常收发消息
+    assert!(server.state.is_online(&alice));
+    old.send(json!({
+        "type": "message", "sender_id": alice, "receiver_id": bob, "content": "你好", "client_message_id": "tmp-1",
+    })).await;
+    assert_eq!(old.next_event().await["type"], "message_ack");
+    assert_eq!(security_notices(&app, &alice).await.len(), 1);
+}
@@ -0,0 +1,151 @@
+mod common;
+
+use axum::{
+    extract::Form,
+    http::{HeaderMap, Method, StatusCode},
+    routing::{get, post},
+    Json, Router,
+};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::{OAuthProviderSettings, Settings};
+use std::collections::HashMap;
+
+// 模拟的身份提供方：授权码原样作为访问令牌，令牌形如 "sub|用户名|邮箱"，以 gh: 开头时返回 GitHub 格式
+async fn start_provider() -> String {
+    let app = Router::new()
+        .route("/token", post(|Form(form): Form<HashMap<String, String>>| async move {
+            let valid = form.get("client_secret").map(String::as_str) == Some("secret")
+                && form.get("grant_type").map(String::as_str) == Some("authorization_code")
+                && form.get("code_verifier").is_some_and(|v| v.len() >= 43);
+            let code = form.get("code").cloned().unwrap_or_default();
+            if !valid || code == "bad" {
+                return Json(json!({ "error": "invalid_grant", "error_description": "bad code" }));
+            }
+            Json(json!({ "access_token": code, "token_type": "bearer" }))
+        }))
+        .route("/userinfo", get(|headers: HeaderMap| async move {
+            let token = headers["authorization"].to_str().unwrap().strip_prefix("Bearer ").unwrap().to_string();
+            let fields: Vec<&str> = token.split('|').collect();
+            if let Some(id) = fields[0].strip_prefix("gh:") {
+                return Json(json!({ "id": id.parse::<i64>().unwrap(), "login": fields[1], "email": null }));
+            }
+            Json(json!({ "sub": fields[0], "preferred_username": fields[1], "email": fields[2], "email_verified": true }))
+        }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+async fn oauth_app() -> TestApp {
+    let base = start_provider().await;
+    let mut settings = Settings::default();
+    for (name, kind) in [("corp", "oidc"), ("github", "github")] {
+        settings.oauth.providers.insert(name.into(), OAuthProviderSettings {
+            kind: kind.into(),
+            client_id: "cid".into(),
+            client_secret: "secret".into(),
+            redirect_uri: format!("https://chat.example.com/oauth/{name}/callback"),
+            authorize_url: format!("{base}/authorize"),
+            token_url: format!("{base}/token"),
+            userinfo_url: format!("{base}/userinfo"),
+            scopes: Vec::new(),
+        });
+    }
+    TestApp::with_settings(settings)
+}
+
+// 走完一次授权流程，返回回调的响应
+async fn sign_in(app: &TestApp, provider: &str, code: &str, session: Option<&str>) -> (StatusCode, Value) {
+    let auth = session.map(|token| format!("Bearer {token}"));
+    let headers: Vec<(&str, &str)> = auth.iter().map(|value| ("authorization", value.as_str())).collect();
+    let (status, body) = app.request_with_headers(Method::GET, &format!("/oauth/{provider}/authorize"), None, &headers).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let state = body["state"].as_str().unwrap();
+    let code = code.replace('|', "%7C").replace('@', "%40");
+    app.get(&format!("/oauth/{provider}/callback?code={code}&state={state}")).await
+}
+
+async fn login(app: &TestApp, username: &str) -> String {
+    let (_, body) = app.post("/login", json!({ "username": username, "password": "secret" })).await;
+    body["token"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn authorize_url_uses_pkce_and_single_use_state() {
+    let app = oauth_app().await;
+    let (status, body) = app.get("/oauth/corp/authorize").await;
+    assert_eq!(status, StatusCode::OK);
+    let url = body["authorize_url"].as_str().unwrap();
+    for param in ["response_type=code", "client_id=cid", "code_challenge_method=S256", "code_challenge=", "scope=openid+email+profile"] {
+        assert!(url.contains(param), "{url}");
+    }
+    let state = body["state"].as_str().unwrap();
+    assert!(url.contains(&format!("state={state}")));
+
+    let (status, _) = app.get(&format!("/oauth/corp/callback?code=x%7Cxavier%7Cx%40idp.example&state={state}")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.get(&format!("/oauth/corp/callback?code=x%7Cxavier%7Cx%40idp.example&state={state}")).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "oauth.state_invalid"));
+
+    let (status, body) = sign_in(&app, "corp", "bad", None).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::FORBIDDEN, "oauth.denied"));
+    assert_eq!(app.get("/oauth/unknown/authorize").await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn sign_up_then_sign_in_with_the_same_identity() {
+    let app = oauth_app().await;
+    let (status, body) = sign_in(&app, "corp", "sub-1|carol|carol@idp.example", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!((body["created"].as_bool(), body["username"].as_str()), (Some(true), Some("carol")));
+    let carol = body["user_id"].as_str().unwrap().to_string();
+
+    let (_, body) = sign_in(&app, "corp", "sub-1|carol|carol@idp.example", None).await;
+    assert_eq!((body["created"].as_bool(), body["user_id"].as_str()), (Some(false), Some(carol.as_str())));
+    let session = format!("Bearer {}", body["token"].as_str().unwrap());
+    let (_, body) = app.request_with_headers(Method::GET, "/account/identities", None, &[("authorization", session.as_str())]).await;
+    assert_eq!(body["identities"][0]["provider"], "corp");
+    assert_eq!(body["identities"][0]["external_id"], "sub-1");
+
+    // 注册时使用的关联不能解除
+    let (status, body) = app.request_with_headers(Method::DELETE, "/account/identities/corp", None, &[("authorization", session.as_str())]).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::FORBIDDEN, "oauth.signup_identity"));
+
+    // 用户名被占用时加后缀，GitHub 使用数字 id
+    let (status, body) = sign_in(&app, "github", "gh:42|carol", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert!(body["username"].as_str().unwrap().starts_with("carol_"), "{body}");
+}
+
+#[tokio::test]
+async fn existing_accounts_link_instead_of_signing_up() {
+    let app = oauth_app().await;
+    let (status, _) = app.post("/register", json!({ "username": "alice", "password": "secret", "email": "alice@example.com" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let alice = app.post("/login", json!({ "username": "alice", "password": "secret" })).await.1["user_id"].as_str().unwrap().to_string();
+
+    // 邮箱相同也不会自动关联，避免被冒用
+    let (status, body) = sign_in(&app, "corp", "sub-a|alice|alice@example.com", None).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::CONFLICT, "oauth.email_registered"));
+
+    let token = login(&app, "alice").await;
+    let (status, body) = sign_in(&app, "corp", "sub-a|alice|alice@example.com", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    assert_eq!((body["linked"].as_bool(), body["user_id"].as_str()), (Some(true), Some(alice.as_str())));
+    let (_, body) = sign_in(&app, "corp", "sub-a|alice|alice@example.com", None).await;
+    assert_eq!(body["user_id"], alice.as_str());
+
+    // 同一第三方账号不能再关联给别人
+    app.register("bob", "secret").await;
+    let bob_token = login(&app, "bob").await;
+    let (status, body) = sign_in(&app, "corp", "sub-a|alice|alice@example.com", Some(&bob_token)).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::CONFLICT, "oauth.linked_to_other_user"));
+
+    let session = format!("Bearer {token}");
+    let (status, _) = app.request_with_headers(Method::DELETE, "/account/identities/corp", None, &[("authorization", session.as_str())]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.request_with_headers(Method::GET, "/account/identities", None, &[("authorization", session.as_str())]).await;
+    assert_eq!(body["identities"], json!([]));
+}
@@ -0,0 +1,96 @@
+mod common;
+
+use std::time::Duration;
+
+use common::{e2e::TestServer, TestApp};
+use serde_json::{json, Value};
+use server::{outbound, redeliver_outbox, settings::Settings, workspaces::DEFAULT_WORKSPACE, AppState, DbPool};
+
+// 推送后立即到期，便于测试重发
+fn state_with_outbox(max_attempts: i64) -> AppState {
+    let mut settings = Settings::default();
+    settings.outbox.redeliver_after_secs = -1;
+    settings.outbox.max_attempts = max_attempts;
+    AppState::new(DbPool::in_memory().unwrap(), settings)
+}
+
+fn event(rx: &mut outbound::Receiver) -> Value {
+    serde_json::from_str(&rx.try_recv().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn unacknowledged_pushes_survive_a_lost_fan_out() {
+    let state = state_with_outbox(5);
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    // 模拟消息提交后、推送前崩溃：直接写库，不经过推送
+    let message = state.db_pool.send_message(DEFAULT_WORKSPACE, &alice, &bob, "你好", "private").unwrap();
+    assert_eq!(state.db_pool.pending_outbox(&bob).unwrap(), vec![message.id.clone()]);
+    // 自己发给自己的消息不进发件箱
+    state.db_pool.send_message(DEFAULT_WORKSPACE, &alice, &alice, "备忘", "private").unwrap();
+    assert!(state.db_pool.pending_outbox(&alice).unwrap().is_empty());
+
+    // 接收方不在线时不重发，也不计入次数
+    assert_eq!(redeliver_outbox(&state).await.unwrap(), 0);
+
+    let (tx, mut rx) = outbound::channel(16);
+    state.attach_client("bob-phone", &bob, tx);
+    assert_eq!(redeliver_outbox(&state).await.unwrap(), 1);
+    let pushed = event(&mut rx);
+    assert_eq!((pushed["type"].as_str(), pushed["message_id"].as_str()), (Some("message"), Some(message.id.as_str())));
+    assert_eq!((pushed["content"].as_str(), pushed["seq"].as_i64()), (Some("你好"), Some(1)));
+
+    // 确认送达后不再重发
+    let (_, body) = app.post_as(&bob, "/messages/delivered", json!({ "message_ids": [message.id] })).await;
+    assert_eq!(body["success"], true);
+    assert!(state.db_pool.pending_outbox(&bob).unwrap().is_empty());
+    assert_eq!(redeliver_outbox(&state).await.unwrap(), 0);
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn sends_push_immediately_and_give_up_after_max_attempts() {
+    let state = state_with_outbox(2);
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let (tx, mut rx) = outbound::channel(16);
+    state.attach_client("bob-phone", &bob, tx);
+
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "在吗", "message_type": "private" })).await;
+    let message_id = body["message_id"].as_str().unwrap().to_string();
+    assert_eq!(event(&mut rx)["message_id"], message_id.as_str());
+
+    for _ in 0..2 {
+        assert_eq!(redeliver_outbox(&state).await.unwrap(), 1);
+        assert_eq!(event(&mut rx)["message_id"], message_id.as_str());
+    }
+    assert!(state.db_pool.pending_outbox(&bob).unwrap().is_empty());
+    assert_eq!(redeliver_outbox(&state).await.unwrap(), 0);
+
+    // 已读同样视为确认
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "睡了吗", "message_type": "private" })).await;
+    app.post_as(&bob, "/messages/read", json!({ "message_ids": [body["message_id"]] })).await;
+    assert!(state.db_pool.pending_outbox(&bob).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn websocket_delivered_frames_acknowledge_pushes() {
+    let server = TestServer::with_state(state_with_outbox(5)).await;
+    let app = server.app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let mut socket = server.ws(&bob).await;
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "你好", "message_type": "private" })).await;
+    let pushed = socket.next_event().await;
+    assert_eq!(pushed["message_id"], body["message_id"]);
+    socket.send(json!({ "type": "delivered", "message_ids": [pushed["message_id"]] })).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert!(server.state.db_pool.pending_outbox(&bob).unwrap().is_empty());
+    let (_, history) = app.post_as(&bob, "/messages/history", json!({ "user_id": bob, "peer_id": alice, "limit": 10 })).await;
+    assert!(history["messages"][0]["delivered_at"].is_i64());
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::workspaces::DEFAULT_WORKSPACE;
+
+fn insert_message(app: &TestApp, id: &str, sender: &str, receiver: &str, message_type: &str, created_at: i64) {
+    app.db.0.lock().unwrap().execute(
+        "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES (?1, ?2, ?3, ?1, ?4, ?5)",
+        rusqlite::params![id, sender, receiver, message_type, created_at],
+    ).unwrap();
+}
+
+// 沿着 next_cursor 取完所有页，返回每一项中 field 的值
+async fn follow(app: &TestApp, auth: &str, path: &str, list: &str, field: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let url = match &cursor {
+            Some(cursor) => format!("{path}&cursor={cursor}"),
+            None => path.to_string(),
+        };
+        let (status, body) = app.request_with_headers(Method::GET, &url, None, &[("authorization", auth)]).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        items.extend(body[list].as_array().unwrap().iter().map(|item| item[field].as_str().unwrap().to_string()));
+        match body["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => return items,
+        }
+    }
+}
+
+#[tokio::test]
+async fn history_pages_do_not_skip_messages_sent_in_the_same_second() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    insert_message(&app, "m0", &alice, &bob, "private", 50);
+    for id in ["m1", "m2", "m3", "m4", "m5"] {
+        insert_message(&app, id, &bob, &alice, "private", 100);
+    }
+
+    let mut seen = Vec::new();
+    let mut cursor = Value::Null;
+    loop {
+        let (status, body) = app
+            .post_as(&alice, "/messages/history", json!({ "user_id": alice, "peer_id": bob, "cursor": cursor, "limit": 2 }))
+            .await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        seen.extend(body["messages"].as_array().unwrap().iter().map(|m| m["id"].as_str().unwrap().to_string()));
+        cursor = body["next_cursor"].clone();
+        if cursor.is_null() {
+            break;
+        }
+    }
+    assert_eq!(seen, ["m5", "m4", "m3", "m2", "m1", "m0"]);
+
+    // 只传 before 时仍按时间戳分页
+    let (_, body) = app.post_as(&alice, "/messages/history", json!({ "user_id": alice, "peer_id": bob, "before": 100, "limit": 10 })).await;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+
+    let (status, body) = app
+        .post_as(&alice, "/messages/history", json!({ "user_id": alice, "peer_id": bob, "cursor": "oops", "limit": 2 }))
+        .await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.invalid_cursor")));
+}
+
+#[tokio::test]
+async fn conversations_and_group_members_follow_cursors() {
+    let app = TestApp::new();
+    let (alice, auth) = app.login("alice").await;
+    let bob = app.register("bob", "secret").await;
+    let carol = app.register("carol", "secret").await;
+    let (_, dave_auth) = app.login("dave").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "测试群", &alice).unwrap();
+    app.db.add_group_member(&group.id, &bob, "member").unwrap();
+    app.db.add_group_member(&group.id, &carol, "member").unwrap();
+
+    insert_message(&app, "m1", &alice, &bob, "private", 100);
+    insert_message(&app, "m2", &carol, &alice, "private", 200);
+    insert_message(&app, "m3", &bob, &group.id, "group", 200);
+
+    let peers = follow(&app, &auth, "/conversations?limit=1", "conversations", "peer_id").await;
+    let mut latest = [carol.clone(), group.id.clone()];
+    latest.sort_by(|a, b| b.cmp(a));
+    assert_eq!(peers, [latest[0].clone(), latest[1].clone(), bob.clone()]);
+
+    // 群消息可以通过同一个历史接口读取
+    let (_, body) = app.post_as(&bob, "/messages/history", json!({ "user_id": bob, "peer_id": group.id, "limit": 10 })).await;
+    assert_eq!(body["messages"][0]["id"], "m3");
+
+    let path = format!("/groups/{}/members?limit=2", group.id);
+    let mut members = [alice, bob, carol];
+    members.sort();
+    assert_eq!(follow(&app, &auth, &path, "members", "user_id").await, members);
+
+    // 非群成员看不到成员列表
+    let (status, _) = app.request_with_headers(Method::GET, &path, None, &[("authorization", dave_auth.as_str())]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn group_members_can_be_searched_by_name() {
+    let app = TestApp::new();
+    let (alice, auth) = app.login("alice").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "大群", &alice).unwrap();
+    let mut ids = Vec::new();
+    for name in ["carol", "Caroline", "dave", "erin", "fr_ank"] {
+        let user_id = app.register(name, "secret").await;
+        app.db.add_group_member(&group.id, &user_id, "member").unwrap();
+        ids.push(user_id);
+    }
+    let (dave, erin) = (ids[2].clone(), ids[3].clone());
+    app.db.set_display_name(&dave, Some("50% off"), 0).unwrap();
+    app.db.set_display_name(&erin, Some("Karo"), 0).unwrap();
+
+    let path = format!("/groups/{}/members?query=%20CARO%20&limit=1", group.id);
+    let mut names = follow(&app, &auth, &path, "members", "username").await;
+    names.sort();
+    assert_eq!(names, ["Caroline", "carol"]);
+    let path = format!("/groups/{}/members?query=aro&limit=10", group.id);
+    let (_, body) = app.request_with_headers(Method::GET, &path, None, &[("authorization", auth.as_str())]).await;
+    let erin_entry = body["members"].as_array().unwrap().iter().find(|m| m["user_id"] == erin.as_str()).unwrap();
+    assert_eq!((erin_entry["display_name"].as_str(), erin_entry["role"].as_str()), (Some("Karo"), Some("member")));
+    assert!(erin_entry["joined_at"].as_i64().unwrap() > 0);
+
+    // % 和 _ 按字面匹配
+    let path = format!("/groups/{}/members?query=%25&limit=10", group.id);
+    assert_eq!(follow(&app, &auth, &path, "members", "username").await, ["dave"]);
+    let path = format!("/groups/{}/members?query=_&limit=10", group.id);
+    assert_eq!(follow(&app, &auth, &path, "members", "username").await, ["fr_ank"]);
+    let path = format!("/groups/{}/members?query={}", group.id, "x".repeat(65));
+    let (status, body) = app.request_with_headers(Method::GET, &path, None, &[("authorization", auth.as_str())]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("group.member_query_too_long")));
+}
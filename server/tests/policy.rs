@@ -0,0 +1,238 @@
+mod common;
+
+use axum::http::{Method, Request, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::workspaces::DEFAULT_WORKSPACE;
+
+#[tokio::test]
+async fn body_user_ids_must_match_the_session() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let (_, sent) = app.post_as(&alice, "/send-message", json!({
+        "sender_id": alice, "receiver_id": bob, "content": "只给 bob 看", "message_type": "private",
+    })).await;
+
+    // 不带会话令牌时不再接受请求体中的用户ID
+    let (status, _) = app.post("/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 以 alice 的会话读取 bob 的未读消息、删除 bob 名义下的消息都被拒绝
+    for (path, body) in [
+        ("/messages/unread", json!({ "user_id": bob })),
+        ("/messages/sync", json!({ "user_id": bob, "last_sync_time": 0, "limit": 50 })),
+        ("/messages/history", json!({ "user_id": bob, "peer_id": alice, "limit": 10 })),
+        ("/messages/delete", json!({ "message_id": sent["message_id"], "user_id": bob })),
+        ("/messages/batch", json!({ "messages": [{ "sender_id": bob, "receiver_id": alice, "content": "冒充", "message_type": "private" }] })),
+    ] {
+        let (status, body) = app.post_as(&alice, path, body).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.acting_as_other_user")), "{path}");
+    }
+
+    let (status, body) = app.post_as(&bob, "/messages/unread", json!({ "user_id": bob })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["messages"][0]["content"], "只给 bob 看");
+}
+
+#[tokio::test]
+async fn sender_is_taken_from_the_session() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let message = json!({ "receiver_id": bob, "content": "不用写发送者", "message_type": "private" });
+
+    let (status, _) = app.post("/send-message", message.clone()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = app.post_as(&alice, "/send-message", message).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // 旧客户端仍可带 sender_id，但不能冒充他人
+    let (status, body) = app.post_as(&alice, "/send-message", json!({
+        "sender_id": bob, "receiver_id": alice, "content": "冒充", "message_type": "private",
+    })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.acting_as_other_user")));
+
+    let messages = app.db.get_unread_messages(DEFAULT_WORKSPACE, &bob).unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!((messages[0].sender_id.as_str(), messages[0].content.as_str()), (alice.as_str(), "不用写发送者"));
+    assert!(app.db.get_unread_messages(DEFAULT_WORKSPACE, &alice).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn group_checks_hide_groups_from_non_members() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let carol = app.register("carol", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    app.db.add_group_member(&group.id, &bob, "member").unwrap();
+
+    // 不在群中的用户不能发群消息，也看不到群的历史
+    let (status, body) = app.post_as(&carol, "/send-message", json!({
+        "sender_id": carol, "receiver_id": group.id, "content": "我不在群里", "message_type": "group",
+    })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("group.not_found")));
+    let (status, body) = app.post_as(&carol, "/messages/history", json!({ "user_id": carol, "peer_id": group.id, "limit": 10 })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("group.not_found")));
+
+    // 群成员可以看但不能执行群主操作，非成员看不到群
+    let path = format!("/groups/{}/members", group.id);
+    let (status, _) = app.request_with_headers(Method::GET, &path, None, &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.request_with_headers(Method::GET, &path, None, &[("authorization", &app.session(&carol))]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let transfer = format!("/groups/{}/owner", group.id);
+    for (user, expected) in [(&bob, StatusCode::FORBIDDEN), (&carol, StatusCode::NOT_FOUND)] {
+        let (status, _) = app.request_with_headers(
+            Method::PUT, &transfer, Some(json!({ "user_id": user })), &[("authorization", &app.session(user))],
+        ).await;
+        assert_eq!(status, expected);
+    }
+}
+
+#[tokio::test]
+async fn batch_messages_get_the_same_checks_as_single_sends() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let carol = app.register("carol", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    app.db.add_group_member(&group.id, &bob, "member").unwrap();
+    let batch = |sender: &str, receiver: &str, message_type: &str| json!({ "messages": [
+        { "sender_id": sender, "receiver_id": alice, "content": "**第一条**", "message_type": "private", "format": "markdown" },
+        { "sender_id": sender, "receiver_id": receiver, "content": "第二条", "message_type": message_type },
+    ] });
+
+    // 任一条不通过检查时整批都不保存
+    let (status, body) = app.post_as(&carol, "/messages/batch", batch(&carol, &group.id, "group")).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("group.not_found")));
+    app.db.set_member_muted_until(&group.id, &bob, Some(i64::MAX)).unwrap();
+    let (status, body) = app.post_as(&bob, "/messages/batch", batch(&bob, &group.id, "group")).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("group.member_muted")));
+    assert!(app.db.get_unread_messages(DEFAULT_WORKSPACE, &alice).unwrap().is_empty());
+
+    // 通过检查的批量消息与单条发送一样按 format 处理
+    let (status, body) = app.post_as(&carol, "/messages/batch", batch(&carol, &bob, "private")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (_, body) = app.post_as(&alice, "/messages/unread", json!({ "user_id": alice })).await;
+    assert_eq!((&body["messages"][0]["content"], &body["messages"][0]["entities"]), (
+        &json!("第一条"),
+        &json!([{ "type": "bold", "offset": 0, "length": 3 }]),
+    ));
+    // 私聊消息与单条发送一样进入发件箱
+    assert_eq!(app.db.pending_outbox(&alice).unwrap().len(), 1);
+    assert_eq!(app.db.pending_outbox(&bob).unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn user_settings_belong_to_their_owner() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let path = format!("/user/{alice}/settings");
+    let body = json!({ "dm_privacy": "nobody" });
+
+    // 不带会话令牌、或以 bob 的会话都不能查看或修改 alice 的设置
+    let (status, _) = app.request(Method::PUT, &path, Some(body.clone())).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.get(&path).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let bob_auth = app.session(&bob);
+    for (method, body) in [(Method::GET, None), (Method::PUT, Some(body.clone()))] {
+        let (status, response) = app.request_with_headers(method, &path, body, &[("authorization", &bob_auth)]).await;
+        assert_eq!((status, response["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.acting_as_other_user")));
+    }
+
+    let alice_auth = app.session(&alice);
+    let (status, _) = app.request_with_headers(Method::PUT, &path, Some(body), &[("authorization", &alice_auth)]).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, response) = app.request_with_headers(Method::GET, &path, None, &[("authorization", &alice_auth)]).await;
+    assert_eq!(response["settings"]["dm_privacy"], "nobody");
+}
+
+#[tokio::test]
+async fn only_recipients_can_mark_messages() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let carol = app.register("carol", "secret").await;
+    let (_, sent) = app.post_as(&alice, "/send-message", json!({ "receiver_id": bob, "content": "给 bob", "message_type": "private" })).await;
+    let ids = json!({ "message_ids": [sent["message_id"]] });
+
+    // 发送方和无关用户都不能把私聊标为已读或已送达
+    for (user, path) in [(&alice, "/messages/read"), (&carol, "/messages/read"), (&carol, "/messages/delivered")] {
+        let (status, body) = app.post_as(user, path, ids.clone()).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.not_recipient")), "{path}");
+    }
+    assert_eq!(app.db.get_unread_messages(DEFAULT_WORKSPACE, &bob).unwrap().len(), 1);
+
+    let (status, _) = app.post_as(&bob, "/messages/read", ids).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(app.db.get_unread_messages(DEFAULT_WORKSPACE, &bob).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn account_info_and_workspaces_belong_to_their_owner() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let bob_auth = app.session(&bob);
+    let rename = json!({ "username": "alice2", "email": "alice@example.com" });
+
+    let (status, body) = app.request_with_headers(Method::PUT, &format!("/user/{alice}"), Some(rename), &[("authorization", &bob_auth)]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.acting_as_other_user")));
+    let (status, _) = app.request_with_headers(Method::GET, &format!("/user/{alice}/workspaces"), None, &[("authorization", &bob_auth)]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.get(&format!("/user/{alice}/workspaces")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(app.db.get_user_by_id(&alice).unwrap().username, "alice");
+}
+
+#[tokio::test]
+async fn friend_requests_act_as_the_session_user() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let mallory = app.register("mallory", "secret").await;
+    let (status, body) = app.post_as(&mallory, "/send-friend-request", json!({ "from_user_id": mallory, "to_username": "alice" })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let request_id = body["request_id"].as_str().unwrap().to_string();
+
+    // 不能以 alice 的身份接受请求、发送请求或查看她的好友
+    let (status, _) = app.post("/respond-to-friend-request", json!({ "request_id": request_id, "user_id": alice, "response": "accepted" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    for (path, body) in [
+        ("/respond-to-friend-request", json!({ "request_id": request_id, "user_id": alice, "response": "accepted" })),
+        ("/send-friend-request", json!({ "from_user_id": alice, "to_username": "bob" })),
+        ("/get-friend-requests", json!({ "user_id": alice })),
+        ("/get-friends", json!({ "user_id": alice })),
+        ("/remove-friend", json!({ "user_id": alice, "friend_id": bob })),
+    ] {
+        let (status, body) = app.post_as(&mallory, path, body).await;
+        assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.acting_as_other_user")), "{path}");
+    }
+
+    let (_, body) = app.post_as(&alice, "/get-friend-requests", json!({ "user_id": alice })).await;
+    assert_eq!(body["requests"].as_array().unwrap().len(), 1);
+    let (status, _) = app.post_as(&alice, "/respond-to-friend-request", json!({ "request_id": request_id, "user_id": alice, "response": "accepted" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, body) = app.post_as(&mallory, "/get-friends", json!({ "user_id": mallory })).await;
+    assert_eq!(body["friends"][0]["id"], alice.as_str());
+}
+
+#[tokio::test]
+async fn avatars_are_uploaded_by_their_owner() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let path = format!("/user/{alice}/avatar");
+
+    // 头像接口在写入文件之前校验会话，不能替他人上传
+    let (status, _) = app.multipart(Request::post(&path), "avatar", "a.png", "image/png", b"png").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let request = Request::post(&path).header("authorization", app.session(&bob));
+    let (status, _) = app.multipart(request, "avatar", "a.png", "image/png", b"png").await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert!(app.db.get_user_by_id(&alice).unwrap().avatar_url.is_empty());
+}
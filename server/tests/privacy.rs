@@ -0,0 +1,89 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::workspaces::DEFAULT_WORKSPACE;
+
+async fn set_privacy(app: &TestApp, user_id: &str, key: &str, value: &str) {
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{user_id}/settings"), Some(json!({ key: value })), &[("authorization", &app.session(user_id))]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str) -> (StatusCode, Value) {
+    app.post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": receiver, "content": "你好", "message_type": "private" })).await
+}
+
+fn befriend(app: &TestApp, a: &str, b_name: &str, b: &str) {
+    let request = app.db.send_friend_request(a, b_name).unwrap();
+    app.db.respond_to_friend_request(&request.id, b, "accepted").unwrap();
+}
+
+#[tokio::test]
+async fn direct_messages_and_presence_follow_privacy_settings() {
+    let app = TestApp::new();
+    let (alice, _) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let (carol, carol_auth) = app.login("carol").await;
+    befriend(&app, &bob, "alice", &alice);
+
+    let (status, body) = app.request_with_headers(Method::PUT, &format!("/user/{alice}/settings"), Some(json!({ "dm_privacy": "strangers" })), &[("authorization", &app.session(&alice))]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST, "{body}");
+
+    set_privacy(&app, &alice, "dm_privacy", "friends").await;
+    let (status, body) = send(&app, &carol, &alice).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("user.dm_forbidden")));
+    assert_eq!(send(&app, &bob, &alice).await.0, StatusCode::OK);
+    // 设置只限制别人发给自己，alice 仍然可以给 carol 发
+    assert_eq!(send(&app, &alice, &carol).await.0, StatusCode::OK);
+    set_privacy(&app, &alice, "dm_privacy", "nobody").await;
+    assert_eq!(send(&app, &bob, &alice).await.0, StatusCode::FORBIDDEN);
+
+    app.db.touch_last_seen(&alice, 1_700_000_000).unwrap();
+    set_privacy(&app, &alice, "last_seen_privacy", "friends").await;
+    let presence_path = format!("/user/{alice}/presence");
+    let (_, body) = app.get(&presence_path).await;
+    assert!(body["last_seen_at"].is_null());
+    let (_, body) = app.request_with_headers(Method::GET, &presence_path, None, &[("authorization", carol_auth.as_str())]).await;
+    assert!(body["last_seen_at"].is_null());
+    let (_, body) = app.request_with_headers(Method::GET, &presence_path, None, &[("authorization", bob_auth.as_str())]).await;
+    assert_eq!(body["last_seen_at"], 1_700_000_000);
+}
+
+#[tokio::test]
+async fn group_additions_follow_privacy_settings() {
+    let app = TestApp::new();
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, _) = app.login("bob").await;
+    let (carol, carol_auth) = app.login("carol").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    let path = format!("/groups/{}/members", group.id);
+    let add = |auth: String, user_id: String| {
+        let (app, path) = (&app, &path);
+        async move { app.request_with_headers(Method::POST, path, Some(json!({ "user_id": user_id })), &[("authorization", auth.as_str())]).await }
+    };
+
+    set_privacy(&app, &carol, "group_add_privacy", "friends").await;
+    let (status, body) = add(alice_auth.clone(), carol.clone()).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("user.group_add_forbidden")));
+    befriend(&app, &alice, "carol", &carol);
+    let (status, body) = add(alice_auth.clone(), carol.clone()).await;
+    assert_eq!((status, body["code"].as_str(), body["joined"].as_bool()), (StatusCode::OK, Some("group.member_added"), Some(true)));
+    let (_, body) = add(alice_auth.clone(), carol.clone()).await;
+    assert_eq!(body["joined"], false);
+
+    // 非群成员不能拉人，加入后群里出现系统消息
+    let (status, _) = add(carol_auth.clone(), bob.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    let members = app.db.get_group_members(&group.id).unwrap();
+    assert_eq!(members.len(), 3);
+    let (_, outsider_auth) = app.login("dave").await;
+    let (status, _) = add(outsider_auth, bob.clone()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, body) = app.request_with_headers(Method::GET, &format!("/conversations/{}/messages", group.id), None, &[("authorization", alice_auth.as_str())]).await;
+    let joined: Vec<_> = body["messages"].as_array().unwrap().iter()
+        .filter(|m| m["message_type"] == "system")
+        .map(|m| serde_json::from_str::<Value>(m["content"].as_str().unwrap()).unwrap()["user_id"].clone())
+        .collect();
+    assert_eq!(joined, [json!(carol), json!(bob)]);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::e2e::TestServer;
+use serde_json::json;
+use server::{settings::Settings, workspaces::DEFAULT_WORKSPACE};
+use yueling_client::ClientError;
+
+#[tokio::test]
+async fn display_name_changes_are_recorded_broadcast_and_rate_limited() {
+    let mut settings = Settings::default();
+    settings.username.display_name_cooldown_secs = 3600;
+    let server = TestServer::with_settings(settings).await;
+    let app = server.app();
+    let (alice_client, alice) = server.signup("alice").await;
+    let (_, bob) = server.signup("bob").await;
+    let (_, carol) = server.signup("carol").await;
+
+    // bob 是 alice 的好友，carol 和 alice 在同一个群
+    let request = server.state.db_pool.send_friend_request(&bob.user_id, "alice").unwrap();
+    server.state.db_pool.respond_to_friend_request(&request.id, &alice.user_id, "accepted").unwrap();
+    let group = server.state.db_pool.create_group(DEFAULT_WORKSPACE, "项目组", &alice.user_id).unwrap();
+    server.state.db_pool.add_group_member(&group.id, &carol.user_id, "member").unwrap();
+    let mut bob_ws = server.ws(&bob.user_id).await;
+    let mut carol_ws = server.ws_raw("zh-CN").await;
+    carol_ws.send(json!({ "list_of_group_chats": [group.id], "token": server.token(&carol.user_id) })).await;
+    carol_ws.send(json!({ "type": "bogus" })).await;
+    assert_eq!(carol_ws.next_event().await["type"], "error");
+
+    let profile = alice_client.update_display_name(&alice.user_id, Some("  小爱 ")).await.unwrap();
+    assert_eq!(profile.display_name.as_deref(), Some("小爱"));
+    let next_change_at = profile.next_change_at.unwrap();
+    for ws in [&mut bob_ws, &mut carol_ws] {
+        let event = ws.next_event().await;
+        assert_eq!((event["type"].as_str(), event["user_id"].as_str()), (Some("profile_updated"), Some(alice.user_id.as_str())));
+        assert_eq!((event["username"].as_str(), event["display_name"].as_str()), (Some("alice"), Some("小爱")));
+        assert_eq!(event["updated_at"].as_i64().unwrap() + 3600, next_change_at);
+    }
+
+    // 冷却期内不能再改，提交相同的名称不算修改
+    match alice_client.update_display_name(&alice.user_id, Some("阿丽")).await {
+        Err(ClientError::Api { status, code, .. }) => assert_eq!((status, code.as_str()), (429, "user.display_name_cooldown")),
+        other => panic!("应当被冷却拒绝: {other:?}"),
+    }
+    let profile = alice_client.update_display_name(&alice.user_id, Some("小爱")).await.unwrap();
+    assert_eq!(profile.next_change_at, Some(next_change_at));
+    bob_ws.assert_silent().await;
+
+    let history = server.client().display_name_history(&alice.user_id).await.unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!((history[0].old_name.as_deref(), history[0].new_name.as_deref()), (None, Some("小爱")));
+    let (_, body) = app.get(&format!("/user/{}", alice.user_id)).await;
+    assert_eq!(body["user"]["display_name"], "小爱");
+
+    // 只能修改自己的资料
+    let (status, body) = app.request_with_headers(
+        Method::PATCH,
+        &format!("/user/{}/profile", bob.user_id),
+        Some(json!({ "display_name": "bob" })),
+        &[("authorization", format!("Bearer {}", alice.token).as_str())],
+    ).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("user.profile_forbidden")));
+}
+
+#[tokio::test]
+async fn display_names_follow_the_username_rules() {
+    let mut settings = Settings::default();
+    settings.username.display_name_max_length = 4;
+    settings.username.display_name_cooldown_secs = 0;
+    let server = TestServer::with_settings(settings).await;
+    let (client, session) = server.signup("alice").await;
+
+    for (name, code) in [("ADMIN", "user.display_name_length"), ("Root", "user.display_name_reserved"), ("a\u{7}b", "user.display_name_control")] {
+        match client.update_display_name(&session.user_id, Some(name)).await {
+            Err(ClientError::Api { status, code: actual, .. }) => assert_eq!((status, actual.as_str()), (400, code)),
+            other => panic!("{name} 应当被拒绝: {other:?}"),
+        }
+    }
+
+    // 不限制冷却时可以连续修改，清除后显示名称为空
+    client.update_display_name(&session.user_id, Some("爱丽丝")).await.unwrap();
+    let profile = client.update_display_name(&session.user_id, None).await.unwrap();
+    assert_eq!((profile.display_name, profile.next_change_at), (None, None));
+    let history = client.display_name_history(&session.user_id).await.unwrap();
+    assert_eq!(history.iter().map(|change| change.new_name.as_deref()).collect::<Vec<_>>(), [None, Some("爱丽丝")]);
+}
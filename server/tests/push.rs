@@ -0,0 +1,84 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::json;
+use server::{PushDispatcher, PushError, PushNotification, PushProvider};
+use std::sync::{Arc, Mutex};
+
+// 记录发送请求的测试替身，令牌 "stale" 视为已失效
+#[derive(Default)]
+struct RecordingProvider {
+    sent: Mutex<Vec<(String, String)>>,
+}
+
+impl PushProvider for RecordingProvider {
+    fn platform(&self) -> &'static str {
+        "fcm"
+    }
+
+    fn send<'a>(&'a self, token: &'a str, notification: &'a PushNotification) -> BoxFuture<'a, Result<(), PushError>> {
+        Box::pin(async move {
+            if token == "stale" {
+                return Err(PushError::InvalidToken);
+            }
+            self.sent.lock().unwrap().push((token.to_string(), notification.body.clone()));
+            Ok(())
+        })
+    }
+}
+
+#[tokio::test]
+async fn register_push_token_validates_input() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+
+    let bob = app.register("bob", "secret").await;
+
+    let (status, _) = app.post_as(&alice, "/push/register", json!({ "user_id": alice, "platform": "pager", "token": "t" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    // 需要会话令牌，且只能为本人注册
+    let (status, _) = app.post("/push/register", json!({ "user_id": alice, "platform": "fcm", "token": "t" })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = app.post_as(&bob, "/push/register", json!({ "user_id": alice, "platform": "fcm", "token": "t" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = app.post_as(&alice, "/push/register", json!({ "user_id": alice, "platform": "apns", "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 其他用户不能把 alice 的令牌转到自己名下，也不能注销它
+    let (status, body) = app.post_as(&bob, "/push/register", json!({ "user_id": bob, "platform": "apns", "token": "t" })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("push.taken")));
+    let (status, _) = app.post_as(&bob, "/push/unregister", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(app.db.push_tokens_for(std::slice::from_ref(&alice)).unwrap().len(), 1);
+
+    let (status, _) = app.post_as(&alice, "/push/unregister", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post_as(&alice, "/push/unregister", json!({ "token": "t" })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn dispatcher_sends_to_devices_and_drops_stale_tokens() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    for token in ["phone", "stale"] {
+        app.post_as(&alice, "/push/register", json!({ "user_id": alice, "platform": "fcm", "token": token })).await;
+    }
+    // 没有对应平台实现的令牌会被跳过
+    app.post_as(&alice, "/push/register", json!({ "user_id": alice, "platform": "apns", "token": "ipad" })).await;
+
+    let provider = Arc::new(RecordingProvider::default());
+    let dispatcher = PushDispatcher::new(app.db.clone(), vec![provider.clone()]);
+    let notification = PushNotification::new_message("bob", &"长".repeat(150), "m1", "bob-id");
+    assert_eq!(notification.body.chars().count(), 101);
+
+    let sent = dispatcher.notify(vec![alice.clone()], notification).await;
+    assert_eq!(sent, 1);
+    assert_eq!(provider.sent.lock().unwrap()[0].0, "phone");
+
+    let tokens: Vec<String> = app.db.push_tokens_for(&[alice]).unwrap().into_iter().map(|t| t.token).collect();
+    assert!(!tokens.contains(&"stale".to_string()));
+    assert_eq!(tokens.len(), 2);
+}
@@ -0,0 +1,102 @@
+use server::{migrations, queries, DbPool};
+
+// 返回 EXPLAIN QUERY PLAN 的 detail 列
+fn query_plan(db: &DbPool, sql: &str) -> Vec<String> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn.prepare(&format!("EXPLAIN QUERY PLAN {}", sql)).unwrap();
+    let count = stmt.parameter_count();
+    let params: Vec<i64> = vec![0; count];
+    stmt.query_map(rusqlite::params_from_iter(params), |row| row.get::<_, String>(3))
+        .unwrap()
+        .map(Result::unwrap)
+        .collect()
+}
+
+// 断言查询命中了指定索引且没有全表扫描
+fn assert_uses_index(sql: &str, index: &str) {
+    let db = DbPool::in_memory().unwrap();
+    let plan = query_plan(&db, sql);
+    assert!(
+        plan.iter().any(|line| line.contains(index)),
+        "查询未使用索引 {index}: {plan:?}"
+    );
+    assert!(
+        !plan.iter().any(|line| line.starts_with("SCAN messages")),
+        "查询出现全表扫描: {plan:?}"
+    );
+}
+
+#[test]
+fn migrations_are_applied() {
+    let db = DbPool::in_memory().unwrap();
+    let conn = db.0.lock().unwrap();
+    assert_eq!(migrations::current_version(&conn).unwrap(), migrations::latest_version());
+}
+
+#[test]
+fn unread_messages_uses_receiver_unread_index() {
+    assert_uses_index(queries::UNREAD_MESSAGES, "idx_messages_receiver_unread");
+}
+
+#[test]
+fn conversation_history_uses_conversation_index() {
+    assert_uses_index(queries::CONVERSATION_HISTORY, "idx_messages_conversation");
+}
+
+#[test]
+fn group_history_uses_group_index() {
+    assert_uses_index(queries::GROUP_HISTORY, "idx_messages_group_created");
+}
+
+#[test]
+fn sync_messages_avoids_full_scan() {
+    let db = DbPool::in_memory().unwrap();
+    let plan = query_plan(&db, queries::SYNC_MESSAGES);
+    assert!(
+        !plan.iter().any(|line| line.starts_with("SCAN messages")),
+        "查询出现全表扫描: {plan:?}"
+    );
+}
+
+#[test]
+fn conversations_avoids_full_scan() {
+    let db = DbPool::in_memory().unwrap();
+    let plan = query_plan(&db, queries::CONVERSATIONS);
+    assert!(
+        !plan.iter().any(|line| line.starts_with("SCAN messages") || line.starts_with("SCAN m ")),
+        "查询出现全表扫描: {plan:?}"
+    );
+}
+
+#[test]
+fn messages_from_seq_uses_conversation_seq_index() {
+    assert_uses_index(queries::MESSAGES_FROM_SEQ, "idx_messages_conversation_seq");
+}
+
+#[test]
+fn group_members_page_uses_covering_index() {
+    let db = DbPool::in_memory().unwrap();
+    let plan = query_plan(&db, queries::GROUP_MEMBERS_PAGE);
+    assert!(plan.iter().any(|line| line.contains("idx_group_members_page")), "查询未使用索引 idx_group_members_page: {plan:?}");
+    assert!(!plan.iter().any(|line| line.starts_with("SCAN")), "查询出现全表扫描: {plan:?}");
+}
+
+#[test]
+fn group_directory_uses_directory_index() {
+    let db = DbPool::in_memory().unwrap();
+    let plan = query_plan(&db, queries::GROUP_DIRECTORY);
+    assert!(plan.iter().any(|line| line.contains("idx_groups_directory")), "查询未使用索引 idx_groups_directory: {plan:?}");
+    assert!(!plan.iter().any(|line| line.starts_with("SCAN")), "查询出现全表扫描: {plan:?}");
+}
+
+#[test]
+fn batch_mark_read_looks_up_messages_by_primary_key() {
+    let db = DbPool::in_memory().unwrap();
+    for sql in [queries::MARK_MESSAGES_READ, queries::MARK_MESSAGES_DELIVERED] {
+        let plan = query_plan(&db, sql);
+        assert!(
+            !plan.iter().any(|line| line.starts_with("SCAN messages")),
+            "查询出现全表扫描: {plan:?}"
+        );
+    }
+}
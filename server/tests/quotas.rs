@@ -0,0 +1,98 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::{settings::Settings, workspaces::DEFAULT_WORKSPACE};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+fn app_with_quotas(max_message_chars: u64, max_storage_bytes: u64) -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.quotas.max_message_chars = max_message_chars;
+    settings.quotas.max_storage_bytes = max_storage_bytes;
+    let dir = std::env::temp_dir().join(format!("yueling-quotas-{}", uuid::Uuid::new_v4()));
+    settings.attachments.dir = dir.to_string_lossy().into_owned();
+    TestApp::with_settings(settings)
+}
+
+#[tokio::test]
+async fn message_length_follows_user_and_group_overrides() {
+    let app = app_with_quotas(5, 0);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    let send = |receiver: &str, message_type: &str, content: &str| {
+        json!({ "sender_id": alice, "receiver_id": receiver, "content": content, "message_type": message_type })
+    };
+
+    // 按字符而不是字节计数
+    let (status, _) = app.post_as(&alice, "/send-message", send(&bob, "private", "月灵你好呀")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.post_as(&alice, "/send-message", send(&bob, "private", "月灵你好呀!")).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.too_long")));
+    let (status, _) = app.post_as(&alice, "/messages/batch", json!({ "messages": [send(&bob, "private", "123456")] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = app.call(ADMIN_AUTH, Method::PUT, &format!("/admin/quotas/{alice}"), Some(json!({ "max_message_chars": 10 }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post_as(&alice, "/send-message", send(&bob, "private", "123456")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 群的覆盖决定发往该群的消息长度
+    let (status, _) = app.call(ADMIN_AUTH, Method::PUT, &format!("/admin/quotas/{}", group.id), Some(json!({ "max_message_chars": 3 }))).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.post_as(&alice, "/send-message", send(&group.id, "group", "1234")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.post_as(&alice, "/send-message", send(&group.id, "group", "123")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/quotas", None).await;
+    assert_eq!(body["defaults"]["max_message_chars"], 5);
+    assert_eq!(body["overrides"].as_array().unwrap().len(), 2);
+
+    let (status, _) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/quotas/{alice}"), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = app.call(ADMIN_AUTH, Method::DELETE, &format!("/admin/quotas/{alice}"), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.post_as(&alice, "/send-message", send(&bob, "private", "123456")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn overrides_are_validated() {
+    let app = app_with_quotas(100, 0);
+    let alice = app.register("alice", "secret").await;
+
+    let (status, body) = app.call(ADMIN_AUTH, Method::PUT, "/admin/quotas/missing", Some(json!({ "max_message_chars": 10 }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("admin.quota_subject_not_found")));
+    let (status, _) = app.call(ADMIN_AUTH, Method::PUT, &format!("/admin/quotas/{alice}"), Some(json!({ "max_storage_bytes": -1 }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = app.request(Method::GET, "/admin/quotas", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn storage_quota_limits_uploads_and_usage_is_reported() {
+    let app = app_with_quotas(100, 10);
+    let (alice, auth) = app.login("alice").await;
+    let bob = app.register("bob", "secret").await;
+    app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "hi", "message_type": "private" })).await;
+
+    let (status, _) = app.upload(&auth, "a.txt", "text/plain", b"123456").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = app.upload(&auth, "a.txt", "text/plain", b"123456").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("attachment.storage_quota")));
+
+    let (_, body) = app.request_with_headers(Method::GET, "/account/usage", None, &[("authorization", auth.as_str())]).await;
+    assert_eq!(body["code"], "account.usage_fetched");
+    assert_eq!(body["usage"], json!({ "message_count": 1, "attachment_count": 1, "attachment_bytes": 6 }));
+    assert_eq!(body["quota"]["max_storage_bytes"], 10);
+
+    // 单独放宽后可以继续上传
+    app.call(ADMIN_AUTH, Method::PUT, &format!("/admin/quotas/{alice}"), Some(json!({ "max_storage_bytes": 0 }))).await;
+    let (status, _) = app.upload(&auth, "a.txt", "text/plain", b"123456").await;
+    assert_eq!(status, StatusCode::OK);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{settings::Settings, AppState, DbPool};
+
+async fn login(app: &TestApp, username: &str) -> String {
+    let (_, body) = app.post("/login", json!({ "username": username, "password": "secret" })).await;
+    format!("Bearer {}", body["token"].as_str().unwrap())
+}
+
+async fn limits(app: &TestApp, auth: &str) -> (StatusCode, Value) {
+    app.request_with_headers(Method::GET, "/account/limits", None, &[("authorization", auth)]).await
+}
+
+async fn send(app: &TestApp, sender: &str, receiver: &str) -> (StatusCode, Value) {
+    app.post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": receiver, "content": "你好", "message_type": "private" })).await
+}
+
+// 在同一个数据库上先注册用户，再按给定的限流配置创建应用（管理员档位需要用户ID）
+async fn setup(configure: impl FnOnce(&mut Settings, &str)) -> (TestApp, String, String) {
+    let db = DbPool::in_memory().unwrap();
+    let setup = TestApp::with_state(&AppState::new(db.clone(), Settings::default()));
+    let alice = setup.register("alice", "secret").await;
+    let bob = setup.register("bob", "secret").await;
+    let mut settings = Settings::default();
+    configure(&mut settings, &alice);
+    (TestApp::with_state(&AppState::new(db, settings)), alice, bob)
+}
+
+#[tokio::test]
+async fn user_tier_limits_messages_and_requests() {
+    let (app, alice, bob) = setup(|settings, _| {
+        settings.rate_limits.user.requests_per_minute = 6;
+        settings.rate_limits.user.messages_per_minute = 2;
+    })
+    .await;
+    let auth = login(&app, "alice").await;
+
+    let (status, body) = limits(&app, &auth).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("account.limits_fetched")));
+    assert_eq!(body["tier"], "user");
+    assert_eq!(body["requests"]["per_minute"], 6);
+    assert_eq!(body["requests"]["remaining"], 5);
+    assert_eq!(body["messages"]["remaining"], 2);
+
+    for _ in 0..2 {
+        let (status, _) = send(&app, &alice, &bob).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    let (status, body) = send(&app, &alice, &bob).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::TOO_MANY_REQUESTS, Some("message.rate_limited")));
+    // 其他用户的额度不受影响
+    let (status, _) = send(&app, &bob, &alice).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 发送消息带着会话令牌，同样计入请求额度
+    let (_, body) = limits(&app, &auth).await;
+    assert_eq!(body["requests"]["remaining"], 1);
+    assert_eq!(body["messages"]["remaining"], 0);
+    assert!(body["messages"]["reset_secs"].as_u64().unwrap() > 0);
+    limits(&app, &auth).await;
+    let (status, body) = limits(&app, &auth).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::TOO_MANY_REQUESTS, Some("account.too_many_requests")));
+}
+
+#[tokio::test]
+async fn admin_tier_is_unlimited_by_default() {
+    let (app, alice, bob) = setup(|settings, alice| {
+        settings.rate_limits.user.messages_per_minute = 1;
+        settings.rate_limits.admin_user_ids = vec![alice.to_string()];
+    })
+    .await;
+    let auth = login(&app, "alice").await;
+
+    let (_, body) = limits(&app, &auth).await;
+    assert_eq!(body["tier"], "admin");
+    assert_eq!(body["requests"]["per_minute"], 0);
+    assert_eq!(body["requests"]["remaining"], Value::Null);
+    for _ in 0..3 {
+        let (status, _) = send(&app, &alice, &bob).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
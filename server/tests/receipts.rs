@@ -0,0 +1,61 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::workspaces::DEFAULT_WORKSPACE;
+
+fn names(users: &Value) -> Vec<&str> {
+    users.as_array().unwrap().iter().map(|user| user["username"].as_str().unwrap()).collect()
+}
+
+#[tokio::test]
+async fn group_receipts_count_members_and_respect_privacy() {
+    let app = TestApp::new();
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let (carol, carol_auth) = app.login("carol").await;
+    let (dave, dave_auth) = app.login("dave").await;
+    let (_, eve_auth) = app.login("eve").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    for member in [&bob, &carol, &dave] {
+        app.db.add_group_member(&group.id, member, "member").unwrap();
+    }
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": group.id, "content": "周会改到三点", "message_type": "group" })).await;
+    let message_id = body["message_id"].as_str().unwrap().to_string();
+    let path = format!("/messages/{message_id}/receipts");
+
+    // 回执按请求携带的会话令牌记在对应成员名下；不带令牌或不在群中时不能标记
+    let (status, _) = app.post("/messages/delivered", json!({ "message_ids": [message_id] })).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = app.call(&eve_auth, Method::POST, "/messages/read", Some(json!({ "message_ids": [message_id] }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.not_recipient")));
+    app.call(&carol_auth, Method::POST, "/messages/delivered", Some(json!({ "message_ids": [message_id] }))).await;
+    app.call(&bob_auth, Method::POST, "/messages/read", Some(json!({ "message_ids": [message_id] }))).await;
+    app.call(&dave_auth, Method::POST, "/messages/read", Some(json!({ "message_ids": [message_id] }))).await;
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{dave}/settings"), Some(json!({ "read_receipt_privacy": "nobody" })), &[("authorization", &app.session(&dave))]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = app.call(&alice_auth, Method::GET, &path, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((&body["member_count"], &body["delivered_count"], &body["read_count"]), (&json!(3), &json!(3), &json!(1)));
+    assert_eq!(names(&body["read_by"]), ["bob"]);
+    // 不公开已读的成员只显示为已送达
+    let mut delivered = names(&body["delivered_to"]);
+    delivered.sort();
+    assert_eq!(delivered, ["carol", "dave"]);
+
+    // 本人总能看到自己的已读，limit 限制每类返回的人数
+    let (_, body) = app.call(&dave_auth, Method::GET, &format!("{path}?limit=1"), None).await;
+    assert_eq!(body["read_count"], 2);
+    assert_eq!(body["read_by"].as_array().unwrap().len(), 1);
+
+    let (status, _) = app.call(&eve_auth, Method::GET, &path, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = app.request(Method::GET, &path, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "私聊", "message_type": "private" })).await;
+    let private_path = format!("/messages/{}/receipts", body["message_id"].as_str().unwrap());
+    let (status, body) = app.call(&alice_auth, Method::GET, &private_path, None).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.receipts_group_only")));
+}
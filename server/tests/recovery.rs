@@ -0,0 +1,77 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    middleware,
+    routing::get,
+    Router,
+};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use server::{recovery, router, settings::Settings, AppState, DbPool};
+use tower::ServiceExt;
+
+async fn boom() -> &'static str {
+    panic!("处理器出错")
+}
+
+fn panicking_router() -> Router {
+    Router::new()
+        .route("/boom", get(boom))
+        .route("/ok", get(|| async { "ok" }))
+        .layer(middleware::from_fn(recovery::catch_panic))
+}
+
+fn panics_total() -> u64 {
+    let mut out = String::new();
+    recovery::render(&mut out);
+    out.lines()
+        .find_map(|line| line.strip_prefix("yueling_http_panics_total "))
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+async fn get_path(app: Router, path: &str, request_id: Option<&str>) -> (StatusCode, String, Value) {
+    let mut builder = Request::builder().uri(path);
+    if let Some(id) = request_id {
+        builder = builder.header(recovery::REQUEST_ID_HEADER, id);
+    }
+    let response = app.oneshot(builder.body(Body::empty()).unwrap()).await.unwrap();
+    let status = response.status();
+    let id = response.headers()[recovery::REQUEST_ID_HEADER].to_str().unwrap().to_string();
+    let bytes = response.into_body().collect().await.unwrap().to_bytes();
+    (status, id, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+}
+
+#[tokio::test]
+async fn handler_panic_becomes_500_with_request_id() {
+    let before = panics_total();
+    let (status, id, body) = get_path(panicking_router(), "/boom", None).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["success"], false);
+    assert_eq!(body["code"], "error.internal");
+    assert_eq!(body["request_id"], id.as_str());
+    assert!(uuid::Uuid::parse_str(&id).is_ok());
+    assert!(panics_total() > before);
+}
+
+#[tokio::test]
+async fn client_request_id_is_echoed() {
+    let (status, id, _) = get_path(panicking_router(), "/ok", Some("trace-42")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(id, "trace-42");
+
+    // 含有非法字符的请求ID不采用，改为生成新的
+    let (_, id, body) = get_path(panicking_router(), "/boom", Some("bad id!")).await;
+    assert_ne!(id, "bad id!");
+    assert_eq!(body["request_id"], id.as_str());
+}
+
+#[tokio::test]
+async fn every_route_returns_request_id() {
+    let app = router(AppState::new(DbPool::in_memory().unwrap(), Settings::default()));
+    let (status, id, _) = get_path(app, "/server-info", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!id.is_empty());
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use axum::{extract::Form, http::StatusCode, routing::post, Json, Router};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+fn app(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    let mut settings = Settings::default();
+    configure(&mut settings);
+    TestApp::with_settings(settings)
+}
+
+fn solve(challenge: &str, difficulty: u32) -> String {
+    (0u64..)
+        .map(|nonce| nonce.to_string())
+        .find(|nonce| {
+            let hash = Sha256::digest(format!("{challenge}:{nonce}"));
+            let zeros = hash.iter().position(|b| *b != 0).unwrap_or(hash.len());
+            let bits = zeros as u32 * 8 + hash.get(zeros).map_or(0, |b| b.leading_zeros());
+            bits >= difficulty
+        })
+        .unwrap()
+}
+
+// 只接受令牌 "pass" 的 hCaptcha 校验服务
+async fn start_siteverify() -> String {
+    let app = Router::new().route("/siteverify", post(|Form(form): Form<HashMap<String, String>>| async move {
+        Json(json!({ "success": form.get("secret").map(String::as_str) == Some("s3cret") && form.get("response").map(String::as_str) == Some("pass") }))
+    }));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}/siteverify")
+}
+
+#[tokio::test]
+async fn proof_of_work_is_required_and_single_use() {
+    let app = app(|settings| {
+        settings.registration.challenge = "pow".into();
+        settings.registration.pow_difficulty = 8;
+    });
+
+    let (status, body) = app.post("/register", json!({ "username": "alice", "password": "secret" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["code"], "registration.challenge_failed");
+
+    let (status, body) = app.get("/register/challenge").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["kind"], "pow");
+    assert_eq!(body["difficulty"], 8);
+    let challenge = body["challenge"].as_str().unwrap().to_string();
+    let nonce = solve(&challenge, 8);
+
+    let register = |username: &str| json!({ "username": username, "password": "secret", "pow_challenge": challenge, "pow_nonce": nonce });
+    let (status, body) = app.post("/register", register("alice")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // 同一道题不能再用
+    let (status, _) = app.post("/register", register("bob")).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // 没有出过的题不被接受
+    let forged = "00".repeat(16);
+    let request = json!({ "username": "bob", "password": "secret", "pow_challenge": forged, "pow_nonce": solve(&forged, 8) });
+    assert_eq!(app.post("/register", request).await.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn hcaptcha_tokens_are_verified() {
+    let url = start_siteverify().await;
+    let app = app(|settings| {
+        settings.registration.challenge = "hcaptcha".into();
+        settings.registration.hcaptcha_sitekey = "site-key".into();
+        settings.registration.hcaptcha_secret = "s3cret".into();
+        settings.registration.hcaptcha_verify_url = url;
+    });
+
+    let (_, body) = app.get("/register/challenge").await;
+    assert_eq!(body, json!({ "success": true, "message": "获取注册验证成功", "code": "registration.challenge_fetched", "kind": "hcaptcha", "sitekey": "site-key" }));
+
+    let register = |token: &str| -> Value { json!({ "username": "alice", "password": "secret", "captcha_token": token }) };
+    assert_eq!(app.post("/register", register("fail")).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(app.post("/register", register("pass")).await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn challenge_is_off_by_default() {
+    let app = TestApp::new();
+    let (_, body) = app.get("/register/challenge").await;
+    assert_eq!(body["kind"], "off");
+    assert_eq!(app.post("/register", json!({ "username": "alice", "password": "secret" })).await.0, StatusCode::OK);
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{conversation::sealed_bytes_epoch, run_next_job, settings::Settings, AppState, DbPool};
+
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+fn encrypted_state() -> AppState {
+    let mut settings = Settings::default();
+    settings.admin.token = "test-admin-token".into();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_messages = true;
+    settings.security.encrypt_emails = true;
+    settings.jobs.rekey_batch_size = 2;
+    settings.jobs.rekey_batch_delay_ms = 0;
+    AppState::new(DbPool::in_memory().unwrap(), settings)
+}
+
+fn stored<T: rusqlite::types::FromSql>(app: &TestApp, sql: &str, id: &str) -> T {
+    app.db.0.lock().unwrap().query_row(sql, [id], |row| row.get(0)).unwrap()
+}
+
+#[tokio::test]
+async fn rekey_moves_old_and_plaintext_data_to_the_current_key() {
+    let state = encrypted_state();
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let (_, body) = app.post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "加密的", "message_type": "private" })).await;
+    let sealed = body["message_id"].as_str().unwrap().to_string();
+    {
+        // 开启加密前保存的明文消息和邮箱，以及一条无法解密的消息
+        let conn = app.db.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES ('0-legacy', ?1, ?2, '明文', 'private', 1)",
+            [&alice, &bob],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES ('0-broken', ?1, ?2, 'ylm1:AAAAAAAAbroken', 'private', 1)",
+            [&alice, &bob],
+        ).unwrap();
+        conn.execute("UPDATE users SET email = 'alice@example.com' WHERE id = ?", [&alice]).unwrap();
+        // 会话纪元已到期，重新加密时前进到纪元 1
+        conn.execute("UPDATE conversation_keys SET started_at = 0", []).unwrap();
+    }
+
+    assert_eq!(app.call(ADMIN_AUTH, Method::GET, "/admin/rekey", None).await.1["run"], Value::Null);
+    let (status, body) = app.call(ADMIN_AUTH, Method::POST, "/admin/rekey", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (status, body) = app.call(ADMIN_AUTH, Method::POST, "/admin/rekey", None).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::CONFLICT, "jobs.rekey_running"));
+    assert!(run_next_job(&state).await.unwrap());
+
+    let (_, body) = app.call(ADMIN_AUTH, Method::GET, "/admin/rekey", None).await;
+    let run = &body["run"];
+    assert_eq!(
+        (run["total"].as_i64(), run["processed"].as_i64(), run["rewritten"].as_i64(), run["failed"].as_i64(), run["emails_rewritten"].as_i64()),
+        (Some(3), Some(3), Some(2), Some(1), Some(1)),
+    );
+    assert!(run["finished_at"].is_i64());
+
+    let select_content = "SELECT content FROM messages WHERE id = ?";
+    // 重新加密的内容以 BLOB 保存
+    assert_eq!(sealed_bytes_epoch(&stored::<Vec<u8>>(&app, select_content, &sealed)), Some(1));
+    assert_eq!(sealed_bytes_epoch(&stored::<Vec<u8>>(&app, select_content, "0-legacy")), Some(1));
+    assert!(stored::<String>(&app, "SELECT email FROM users WHERE id = ?", &alice).starts_with("yld1:"));
+    let (_, body) = app.post_as(&bob, "/messages/sync", json!({ "user_id": bob, "last_sync_time": 0, "limit": 50 })).await;
+    let mut contents: Vec<_> = body["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap().to_string()).collect();
+    contents.sort();
+    assert_eq!(contents, ["加密的", "明文"]);
+}
+
+#[tokio::test]
+async fn rekey_requires_encryption() {
+    let mut settings = Settings::default();
+    settings.admin.token = "test-admin-token".into();
+    let app = TestApp::with_settings(settings);
+    let (status, body) = app.call(ADMIN_AUTH, Method::POST, "/admin/rekey", None).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::BAD_REQUEST, "jobs.rekey_not_needed"));
+}
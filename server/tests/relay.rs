@@ -0,0 +1,111 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use axum::http::{Method, StatusCode};
+use common::e2e::{TestServer, WsClient};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+use tokio_tungstenite::tungstenite::Message;
+use yueling_client::Session;
+
+fn relay_settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.relay.enabled = true;
+    settings.relay.bytes_per_sec = 100_000;
+    settings.relay.max_chunk_bytes = 50_000;
+    settings
+}
+
+async fn create(app: &TestApp, session: &Session, peer_id: &str) -> (StatusCode, Value) {
+    let auth = format!("Bearer {}", session.token);
+    app.request_with_headers(Method::POST, "/relay", Some(json!({ "peer_id": peer_id })), &[("authorization", &auth)]).await
+}
+
+// 以请求头中的会话令牌连接
+async fn join(server: &TestServer, relay_id: &str, session: &Session) -> Result<WsClient, u16> {
+    let auth = format!("Bearer {}", session.token);
+    server.ws_at(&format!("/relay/{relay_id}"), &[("authorization", &auth)]).await
+}
+
+async fn close_code(ws: &mut WsClient) -> u16 {
+    ws.close_frame().await.0
+}
+
+#[tokio::test]
+async fn relay_forwards_frames_between_both_sides_with_rate_limit() {
+    let server = TestServer::with_settings(relay_settings()).await;
+    let (_, alice) = server.signup("alice").await;
+    let (_, bob) = server.signup("bob").await;
+    let app = server.app();
+
+    let (status, body) = create(&app, &alice, &bob.user_id).await;
+    assert_eq!((status, &body["max_chunk_bytes"], &body["expires_in"]), (StatusCode::OK, &json!(50_000), &json!(60)));
+    let relay_id = body["relay_id"].as_str().unwrap();
+    let mut alice_ws = join(&server, relay_id, &alice).await.unwrap();
+    // 浏览器不能设置请求头，令牌也可以放在查询参数中
+    let mut bob_ws = server.ws_at(&format!("/relay/{relay_id}?token={}", bob.token), &[]).await.unwrap();
+    assert_eq!(join(&server, relay_id, &bob).await.err(), Some(409));
+
+    alice_ws.send_frame(Message::binary(vec![1u8, 2, 3])).await;
+    assert_eq!(bob_ws.next_frame().await, Message::binary(vec![1u8, 2, 3]));
+    bob_ws.send_frame(Message::text("收到")).await;
+    assert_eq!(alice_ws.next_frame().await, Message::text("收到"));
+
+    // 每秒 100000 字节：三帧 50000 字节的数据至少要一秒才能全部转发
+    let started = Instant::now();
+    for _ in 0..3 {
+        alice_ws.send_frame(Message::binary(vec![7u8; 50_000])).await;
+    }
+    for _ in 0..3 {
+        assert_eq!(bob_ws.next_frame().await.len(), 50_000);
+    }
+    assert!(started.elapsed() >= Duration::from_millis(900));
+
+    // 任一方断开时中转结束，对方收到关闭码
+    alice_ws.send_frame(Message::Close(None)).await;
+    assert_eq!(close_code(&mut bob_ws).await, 4006);
+    assert_eq!(join(&server, relay_id, &alice).await.err(), Some(404));
+}
+
+#[tokio::test]
+async fn oversized_chunks_and_missing_peers_end_the_relay() {
+    let mut settings = relay_settings();
+    settings.relay.join_timeout_secs = 1;
+    let server = TestServer::with_settings(settings).await;
+    let (_, alice) = server.signup("alice").await;
+    let (_, bob) = server.signup("bob").await;
+    let (_, carol) = server.signup("carol").await;
+    let app = server.app();
+
+    let (_, body) = create(&app, &alice, &bob.user_id).await;
+    let relay_id = body["relay_id"].as_str().unwrap();
+    assert_eq!(join(&server, relay_id, &carol).await.err(), Some(404));
+    let mut alice_ws = join(&server, relay_id, &alice).await.unwrap();
+    let mut bob_ws = join(&server, relay_id, &bob).await.unwrap();
+    alice_ws.send_frame(Message::binary(vec![0u8; 50_001])).await;
+    assert_eq!(close_code(&mut alice_ws).await, 1009);
+    assert_eq!(close_code(&mut bob_ws).await, 1009);
+
+    // 对方没有在时限内连上
+    let (_, body) = create(&app, &alice, &bob.user_id).await;
+    let mut alice_ws = join(&server, body["relay_id"].as_str().unwrap(), &alice).await.unwrap();
+    assert_eq!(close_code(&mut alice_ws).await, 4005);
+
+    let (status, body) = create(&app, &alice, &alice.user_id).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("relay.self_relay")));
+    create(&app, &alice, &bob.user_id).await;
+    create(&app, &alice, &carol.user_id).await;
+    let (status, body) = create(&app, &alice, &bob.user_id).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::TOO_MANY_REQUESTS, Some("relay.too_many")));
+    let (status, _) = app.request(Method::POST, "/relay", Some(json!({ "peer_id": bob.user_id }))).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // 默认不开启
+    let server = TestServer::start().await;
+    let (_, alice) = server.signup("alice").await;
+    let (_, bob) = server.signup("bob").await;
+    let (status, body) = create(&server.app(), &alice, &bob.user_id).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("relay.disabled")));
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{settings::Settings, AppState, DbPool};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+fn admin_settings() -> Settings {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings
+}
+
+async fn admin(app: &TestApp, method: Method, body: Option<Value>) -> (StatusCode, Value) {
+    let auth = format!("Bearer {ADMIN_TOKEN}");
+    app.request_with_headers(method, "/admin/runtime", body, &[("authorization", auth.as_str())]).await
+}
+
+#[tokio::test]
+async fn flags_survive_restart() {
+    let db = DbPool::in_memory().unwrap();
+    let app = TestApp::with_state(&AppState::new(db.clone(), admin_settings()));
+
+    let (status, _) = app.request(Method::GET, "/admin/runtime", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, body) = admin(&app, Method::GET, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["log_level"], "info");
+    assert_eq!(body["flags"], json!({ "e2ee": true, "registration": true, "translation": true }));
+
+    let (status, body) = admin(&app, Method::PUT, Some(json!({ "log_level": "debug", "flags": { "registration": false } }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("admin.runtime_updated")));
+    assert_eq!(body["log_level"], "debug");
+    assert_eq!(body["config"]["server"]["log_level"], "debug");
+
+    // 在同一个数据库上重新创建应用状态，相当于重启
+    let restarted = TestApp::with_state(&AppState::new(db, admin_settings()));
+    let (_, body) = admin(&restarted, Method::GET, None).await;
+    assert_eq!(body["log_level"], "debug");
+    assert_eq!(body["flags"], json!({ "e2ee": true, "registration": false, "translation": true }));
+}
+
+#[tokio::test]
+async fn invalid_changes_are_rejected_without_saving_anything() {
+    let app = TestApp::with_settings(admin_settings());
+
+    let (status, body) = admin(&app, Method::PUT, Some(json!({ "log_level": "trace" }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("admin.runtime_invalid_log_level")));
+    let (status, body) = admin(&app, Method::PUT, Some(json!({ "log_level": "warn", "flags": { "link_previews": false } }))).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("admin.runtime_unknown_flag")));
+
+    let (_, body) = admin(&app, Method::GET, None).await;
+    assert_eq!(body["log_level"], "info");
+}
+
+#[tokio::test]
+async fn paused_registration_and_e2ee_are_enforced() {
+    let app = TestApp::with_settings(admin_settings());
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    admin(&app, Method::PUT, Some(json!({ "flags": { "registration": false, "e2ee": false } }))).await;
+    let (status, body) = app.post("/register", json!({ "username": "carol", "password": "secret" })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("user.registration_paused")));
+    let (_, body) = app.get("/server-info").await;
+    assert_eq!(body["registration"]["open"], false);
+    assert_eq!(body["features"]["e2ee"], false);
+
+    let send = |content: &str| json!({ "sender_id": alice, "receiver_id": bob, "content": content, "message_type": "private" });
+    let (status, body) = app.post_as(&alice, "/send-message", send("yle2e1:AAAA")).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("message.e2ee_disabled")));
+    let (status, _) = app.post_as(&alice, "/send-message", send("你好")).await;
+    assert_eq!(status, StatusCode::OK);
+
+    admin(&app, Method::PUT, Some(json!({ "flags": { "registration": true } }))).await;
+    app.register("carol", "secret").await;
+}
+
+#[tokio::test]
+async fn effective_config_hides_secrets() {
+    let mut settings = admin_settings();
+    settings.security.master_key = "00".repeat(32);
+    settings.email.smtp_password = "hunter2".into();
+    settings.translation.api_key = "sk-123".into();
+    let app = TestApp::with_settings(settings);
+
+    let (_, body) = admin(&app, Method::GET, None).await;
+    let config = &body["config"];
+    assert_eq!(config["admin"]["token"], "***");
+    assert_eq!(config["security"]["master_key"], "***");
+    assert_eq!(config["email"]["smtp_password"], "***");
+    assert_eq!(config["translation"]["api_key"], "***");
+    // 未配置的项保持为空，便于看出是否已配置
+    assert_eq!(config["registration"]["hcaptcha_secret"], "");
+    assert_eq!(config["server"]["port"], Settings::default().server.port);
+    assert!(!body.to_string().contains("hunter2"));
+}
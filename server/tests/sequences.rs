@@ -0,0 +1,76 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::workspaces::DEFAULT_WORKSPACE;
+
+async fn send(app: &TestApp, sender: &str, receiver: &str, message_type: &str) -> Value {
+    let (status, body) = app
+        .post_as(sender, "/send-message", json!({ "sender_id": sender, "receiver_id": receiver, "content": "hi", "message_type": message_type }))
+        .await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    body
+}
+
+async fn from_seq(app: &TestApp, auth: &str, peer_id: &str, query: &str) -> (StatusCode, Value) {
+    let path = format!("/conversations/{peer_id}/messages?{query}");
+    app.request_with_headers(Method::GET, &path, None, &[("authorization", auth)]).await
+}
+
+#[tokio::test]
+async fn seqs_are_contiguous_per_conversation() {
+    let app = TestApp::new();
+    let (alice, auth) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let (carol, carol_auth) = app.login("carol").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "测试群", &alice).unwrap();
+    app.db.add_group_member(&group.id, &bob, "member").unwrap();
+
+    // 双方发出的私聊共用一个序列，其他会话各自从 1 开始
+    let seqs = [
+        send(&app, &alice, &bob, "private").await["seq"].clone(),
+        send(&app, &bob, &alice, "private").await["seq"].clone(),
+        send(&app, &alice, &carol, "private").await["seq"].clone(),
+        send(&app, &bob, &group.id, "group").await["seq"].clone(),
+        send(&app, &alice, &bob, "private").await["seq"].clone(),
+    ];
+    assert_eq!(seqs, [json!(1), json!(2), json!(1), json!(1), json!(3)]);
+
+    // 同一会话从双方的角度读取结果相同
+    let (status, body) = from_seq(&app, &auth, &bob, "from_seq=2").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("message.seq_range_fetched")));
+    let read: Vec<&Value> = body["messages"].as_array().unwrap().iter().map(|m| &m["seq"]).collect();
+    assert_eq!(read, [&json!(2), &json!(3)]);
+    assert_eq!((&body["last_seq"], &body["next_seq"]), (&json!(3), &Value::Null));
+    let (_, body) = from_seq(&app, &bob_auth, &alice, "from_seq=1&limit=2").await;
+    assert_eq!((body["messages"][0]["seq"].as_i64(), body["next_seq"].as_i64()), (Some(1), Some(3)));
+
+    let (_, body) = from_seq(&app, &bob_auth, &group.id, "").await;
+    assert_eq!((body["messages"][0]["seq"].as_i64(), body["last_seq"].as_i64()), (Some(1), Some(1)));
+    // 不是群成员时看不到群消息
+    let (status, _) = from_seq(&app, &carol_auth, &group.id, "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn deleted_messages_fill_their_seq() {
+    let app = TestApp::new();
+    let (alice, auth) = app.login("alice").await;
+    let bob = app.register("bob", "secret").await;
+    send(&app, &alice, &bob, "private").await;
+    let second = send(&app, &alice, &bob, "private").await;
+    send(&app, &alice, &bob, "private").await;
+
+    let (status, _) = app.post_as(&alice, "/messages/delete", json!({ "message_id": second["message_id"], "user_id": alice })).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 删除的消息不返回内容，但序号出现在 deleted_seqs 中，客户端不会当作缺口
+    let (_, body) = from_seq(&app, &auth, &bob, "from_seq=1").await;
+    let seqs: Vec<i64> = body["messages"].as_array().unwrap().iter().map(|m| m["seq"].as_i64().unwrap()).collect();
+    assert_eq!(seqs, [1, 3]);
+    assert_eq!(body["deleted_seqs"], json!([2]));
+
+    // 序号不随删除回收
+    assert_eq!(send(&app, &alice, &bob, "private").await["seq"], 4);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use axum::http::StatusCode;
+use common::e2e::TestServer;
+use server::settings::{OAuthProviderSettings, Settings};
+use yueling_client::PROTOCOL_VERSION;
+
+#[tokio::test]
+async fn server_info_reflects_the_instance_configuration() {
+    let mut settings = Settings::default();
+    settings.attachments.max_bytes = 1024;
+    settings.registration.challenge = "pow".into();
+    settings.oauth.providers.insert("github".into(), OAuthProviderSettings { kind: "github".into(), ..Default::default() });
+    let server = TestServer::with_settings(settings).await;
+
+    let info = server.client().server_info().await.unwrap();
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.protocol_version, PROTOCOL_VERSION);
+    assert!(info.is_compatible());
+    assert!(info.features.e2ee && !info.features.encryption_at_rest);
+    assert_eq!(info.features.attachments_max_bytes, 1024);
+    assert_eq!((info.features.federation, info.features.federation_server_name), (false, None));
+    assert_eq!(info.features.oauth_providers, ["github"]);
+    assert!(!info.features.grpc && !info.features.device_verification);
+    assert_eq!(info.cipher_suites, ["aes-256-gcm-hmac-sha256"]);
+    assert!(info.registration.open);
+    assert_eq!(info.registration.challenge, "pow");
+    assert!(!info.maintenance);
+
+    // 默认工作区改为受邀注册后不再开放注册
+    server.state.db_pool.set_workspace_invite_only("default", true).unwrap();
+    assert!(!server.client().server_info().await.unwrap().registration.open);
+    let (status, body) = server.app().get("/server-info").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("server.info")));
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::TestApp;
+use server::{settings::Settings, slow_query, slow_requests, DbPool};
+use std::time::Duration;
+
+fn counter(render: fn(&mut String), series: &str) -> u64 {
+    let mut out = String::new();
+    render(&mut out);
+    out.lines()
+        .find_map(|line| line.strip_prefix(series).and_then(|rest| rest.strip_prefix(' ')))
+        .map(|value| value.parse().unwrap())
+        .unwrap_or(0)
+}
+
+#[test]
+fn sensitive_statements_hide_all_string_parameters() {
+    let scrubbed = slow_query::scrub(
+        "UPDATE users SET password_hash = ?1 WHERE id = ?2",
+        "UPDATE users SET password_hash = '$2b$12$secret' WHERE id = 'user-1'",
+    );
+    assert_eq!(scrubbed, "UPDATE users SET password_hash = '***' WHERE id = '***'");
+}
+
+#[test]
+fn other_statements_keep_parameters_but_hide_blobs() {
+    let scrubbed = slow_query::scrub(
+        "SELECT * FROM friendships WHERE user_id = ?1 AND note = ?2 AND data = ?3 AND n = ?4",
+        "SELECT * FROM friendships WHERE user_id = 'user-1' AND note = 'it''s' AND data = x'0a0b0c' AND n = 42",
+    );
+    assert_eq!(
+        scrubbed,
+        "SELECT * FROM friendships WHERE user_id = 'user-1' AND note = 'it''s' AND data = x'***'(3 字节) AND n = 42"
+    );
+
+    let long = "a".repeat(100);
+    let scrubbed = slow_query::scrub("SELECT ?1", &format!("SELECT '{long}'"));
+    assert_eq!(scrubbed, format!("SELECT '{}…'", "a".repeat(64)));
+}
+
+#[test]
+fn slow_statements_are_counted() {
+    let db = DbPool::in_memory().unwrap();
+    let before = counter(slow_query::render, "yueling_slow_queries_total");
+    slow_query::set_threshold(Duration::from_micros(1));
+    db.0.lock()
+        .unwrap()
+        .query_row(
+            "WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n WHERE i < 100000) SELECT count(*) FROM n",
+            [],
+            |row| row.get::<_, i64>(0),
+        )
+        .unwrap();
+    slow_query::set_threshold(Duration::ZERO);
+    assert!(counter(slow_query::render, "yueling_slow_queries_total") > before);
+}
+
+#[test]
+fn query_strings_hide_secrets() {
+    assert_eq!(
+        slow_requests::scrub_query("token=abc&limit=20&api_key=xyz&flag"),
+        "token=***&limit=20&api_key=***&flag"
+    );
+}
+
+#[tokio::test]
+async fn slow_requests_are_counted_by_route() {
+    let mut settings = Settings::default();
+    settings.server.slow_request_ms = 1;
+    let app = TestApp::with_settings(settings);
+    let series = "yueling_slow_requests_total{route=\"/register\"}";
+    let before = counter(slow_requests::render, series);
+
+    // 注册要计算密码哈希，远超 1 毫秒
+    app.register("alice", "password123").await;
+    assert!(counter(slow_requests::render, series) > before);
+}
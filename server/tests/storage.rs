@@ -0,0 +1,362 @@
+use rusqlite::params;
+use server::{workspaces::DEFAULT_WORKSPACE, DbPool};
+
+#[test]
+fn with_tx_rolls_back_on_error() {
+    let db = DbPool::in_memory().unwrap();
+
+    let result: rusqlite::Result<()> = db.with_tx(|tx| {
+        tx.execute(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params!["u1", "alice", "u1@local", "hash", 0],
+        )?;
+        // 第二条插入违反 UNIQUE(username)，整个事务应回滚
+        tx.execute(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params!["u2", "alice", "u2@local", "hash", 0],
+        )?;
+        Ok(())
+    });
+    assert!(result.is_err());
+    assert!(!db.user_exists_by_id("u1").unwrap());
+}
+
+#[test]
+fn create_group_adds_creator_as_owner() {
+    let db = DbPool::in_memory().unwrap();
+    db.with_tx(|tx| {
+        tx.execute(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params!["u1", "alice", "u1@local", "hash", 0],
+        )
+    })
+    .unwrap();
+
+    let group = db.create_group(DEFAULT_WORKSPACE, "测试群", "u1").unwrap();
+    let members = db.get_group_members(&group.id).unwrap();
+    assert_eq!(members.len(), 1);
+    assert_eq!(members[0].user_id, "u1");
+    assert_eq!(members[0].role, "owner");
+}
+
+#[test]
+fn create_group_with_unknown_creator_leaves_nothing_behind() {
+    let db = DbPool::in_memory().unwrap();
+    assert!(db.create_group(DEFAULT_WORKSPACE, "孤儿群", "missing").is_err());
+
+    let conn = db.0.lock().unwrap();
+    let groups: i64 = conn.query_row("SELECT COUNT(*) FROM groups", [], |row| row.get(0)).unwrap();
+    assert_eq!(groups, 0);
+}
+
+// 直接插入指定时间的消息，绕过 send_message 的当前时间戳
+fn insert_message_at(db: &DbPool, id: &str, sender: &str, receiver: &str, created_at: i64) {
+    let conn = db.0.lock().unwrap();
+    conn.execute(
+        "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES (?1, ?2, ?3, 'hi', 'private', ?4)",
+        params![id, sender, receiver, created_at],
+    )
+    .unwrap();
+}
+
+#[test]
+fn retention_respects_default_and_overrides() {
+    let db = DbPool::in_memory().unwrap();
+    db.with_tx(|tx| {
+        tx.execute(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u1', 'alice', 'u1@local', 'hash', 0)",
+            [],
+        )
+    })
+    .unwrap();
+
+    let day = 24 * 60 * 60;
+    let now = 100 * day;
+    insert_message_at(&db, "old-private", "u1", "u2", now - 40 * day);
+    insert_message_at(&db, "new-private", "u1", "u2", now - 10 * day);
+    insert_message_at(&db, "old-archive", "u1", "archive-group", now - 40 * day);
+    insert_message_at(&db, "old-short", "u1", "short-group", now - 5 * day);
+
+    // archive-group 永久保留，short-group 只保留 3 天
+    db.set_retention_override("archive-group", 0).unwrap();
+    db.set_retention_override("short-group", 3).unwrap();
+
+    let settings = server::settings::RetentionSettings {
+        message_days: 30,
+        ..Default::default()
+    };
+    let report = db.apply_retention(&settings, now).unwrap();
+    assert_eq!(report.messages_deleted, 2);
+
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT id FROM messages ORDER BY id").unwrap();
+    let remaining: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+    assert_eq!(remaining, vec!["new-private", "old-archive"]);
+}
+
+#[test]
+fn message_ids_are_v7_and_legacy_ids_are_migrated() {
+    let path = std::env::temp_dir().join(format!("yueling-v7-{}.db", uuid::Uuid::new_v4()));
+    let path_str = path.to_str().unwrap();
+    let legacy_id = uuid::Uuid::new_v4().to_string();
+    {
+        let db = DbPool::new(path_str).unwrap();
+        let conn = db.0.lock().unwrap();
+        conn.execute(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u1', 'alice', 'u1@local', 'hash', 0)",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO messages (id, sender_id, receiver_id, content, message_type, created_at) VALUES (?1, 'u1', 'u2', 'hi', 'private', 1700000000)",
+            [&legacy_id],
+        )
+        .unwrap();
+        // 模拟 v7 迁移之前的数据库（同时撤销之后给已有表加列的迁移）
+        conn.execute_batch(
+            "DROP INDEX idx_messages_deleted;
+             DROP INDEX idx_messages_client_id;
+             DROP INDEX idx_users_deleted;
+             DROP INDEX idx_users_username_normalized;
+             ALTER TABLE sessions DROP COLUMN device_id;
+             ALTER TABLE users DROP COLUMN username_normalized;
+             ALTER TABLE users DROP COLUMN email_verified_at;
+             ALTER TABLE messages DROP COLUMN client_message_id;
+             ALTER TABLE messages DROP COLUMN sent_at;
+             ALTER TABLE messages DROP COLUMN delivered_at;
+             ALTER TABLE messages DROP COLUMN read_at;
+             DROP INDEX idx_messages_conversation_seq;
+             DROP TABLE conversation_seqs;
+             ALTER TABLE messages DROP COLUMN conversation_id;
+             ALTER TABLE messages DROP COLUMN seq;
+             ALTER TABLE messages DROP COLUMN deleted_at;
+             ALTER TABLE users DROP COLUMN deleted_at;
+             ALTER TABLE users DROP COLUMN last_seen_at;
+             ALTER TABLE users DROP COLUMN last_digest_at;
+             DROP INDEX idx_workspace_invites_creator;
+             ALTER TABLE workspace_invites DROP COLUMN created_by;
+             ALTER TABLE users DROP COLUMN display_name;
+             ALTER TABLE groups DROP COLUMN deleted_at;
+             DROP INDEX idx_groups_directory;
+             ALTER TABLE groups DROP COLUMN visibility;
+             ALTER TABLE groups DROP COLUMN join_approval;
+             DROP INDEX idx_group_members_page;
+             ALTER TABLE group_members DROP COLUMN muted_until;
+             ALTER TABLE messages DROP COLUMN entities;
+             ALTER TABLE messages DROP COLUMN workspace_id;
+             ALTER TABLE groups DROP COLUMN workspace_id;",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 2).unwrap();
+    }
+
+    let db = DbPool::new(path_str).unwrap();
+    let (new_id, created_at): (String, i64) = {
+        let conn = db.0.lock().unwrap();
+        conn.query_row(
+            "SELECT new_id, m.created_at FROM message_id_map JOIN messages m ON m.id = new_id WHERE old_id = ?",
+            [&legacy_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap()
+    };
+    let new_id = uuid::Uuid::parse_str(&new_id).unwrap();
+    assert_eq!(new_id.get_version_num(), 7);
+    let (secs, _) = new_id.get_timestamp().unwrap().to_unix();
+    assert_eq!(secs as i64, created_at);
+
+    let message = db.send_message(DEFAULT_WORKSPACE, "u1", "u2", "new", "private").unwrap();
+    let sent_id = uuid::Uuid::parse_str(&message.id).unwrap();
+    assert_eq!(sent_id.get_version_num(), 7);
+    assert!(sent_id > new_id);
+    // 迁移给旧消息编了序号，新消息接着编号
+    let legacy_seq: i64 = db.0.lock().unwrap()
+        .query_row("SELECT seq FROM messages WHERE id = ?", [new_id.to_string()], |row| row.get(0))
+        .unwrap();
+    assert_eq!((legacy_seq, message.seq), (1, Some(2)));
+
+    drop(db);
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn cached_user_is_invalidated_on_update() {
+    let db = DbPool::in_memory().unwrap();
+    db.with_tx(|tx| {
+        tx.execute(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u1', 'alice', 'u1@local', 'hash', 0)",
+            [],
+        )
+    })
+    .unwrap();
+
+    assert_eq!(db.get_user_by_id("u1").unwrap().username, "alice");
+    db.update_user_info("u1", "alice2", "u1@local").unwrap();
+    assert_eq!(db.get_user_by_id("u1").unwrap().username, "alice2");
+    db.update_user_avatar("u1", "/uploads/avatars/a.png").unwrap();
+    assert_eq!(db.get_user_by_id("u1").unwrap().avatar_url, "/uploads/avatars/a.png");
+}
+
+#[test]
+fn soft_deleted_data_is_purged_after_deadline() {
+    let db = DbPool::in_memory().unwrap();
+    db.with_tx(|tx| {
+        tx.execute_batch(
+            "INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u1', 'alice', 'u1@local', 'hash', 0);
+             INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u2', 'bob', 'u2@local', 'hash', 0);",
+        )
+    })
+    .unwrap();
+
+    let day = 24 * 60 * 60;
+    let now = 100 * day;
+    insert_message_at(&db, "kept", "u1", "group-1", now - day);
+    insert_message_at(&db, "deleted-old", "u1", "group-1", now - 50 * day);
+    insert_message_at(&db, "deleted-recent", "u1", "group-1", now - 50 * day);
+    insert_message_at(&db, "from-bob", "u2", "u1", now - day);
+    db.delete_message("deleted-old", "u1", now - 40 * day).unwrap();
+    db.delete_message("deleted-recent", "u1", now - day).unwrap();
+    let group = db.create_group(DEFAULT_WORKSPACE, "项目组", "u1").unwrap();
+    db.add_group_member(&group.id, "u2", "member").unwrap();
+    assert!(db.delete_user("u2", now - 40 * day).unwrap());
+    assert!(!db.user_exists_by_id("u2").unwrap());
+    // 成员列表已进入缓存
+    assert_eq!(db.get_group_members(&group.id).unwrap().len(), 2);
+
+    let settings = server::settings::RetentionSettings {
+        purge_deleted_days: 30,
+        ..Default::default()
+    };
+    let report = db.apply_retention(&settings, now).unwrap();
+    assert_eq!(report.users_purged, 1);
+    // 清除用户后群成员缓存随之失效
+    let members = db.get_group_members(&group.id).unwrap();
+    assert_eq!(members.iter().map(|m| m.user_id.as_str()).collect::<Vec<_>>(), ["u1"]);
+
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn.prepare("SELECT id FROM messages ORDER BY id").unwrap();
+    let remaining: Vec<String> = stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+    assert_eq!(remaining, vec!["deleted-recent", "kept"]);
+}
+
+#[test]
+fn integrity_check_rejects_newer_schema_unless_read_only() {
+    use server::integrity::{self, IntegrityStatus};
+
+    let path = std::env::temp_dir().join(format!("yueling-integrity-{}.db", uuid::Uuid::new_v4()));
+    let mut settings = server::settings::DatabaseSettings {
+        path: path.to_str().unwrap().to_string(),
+        ..Default::default()
+    };
+    {
+        let db = DbPool::with_settings(&settings, None).unwrap();
+        assert!(matches!(integrity::verify(&settings, None), Ok(IntegrityStatus::Healthy)));
+        // 模拟由更新版本程序迁移过的数据库
+        let conn = db.0.lock().unwrap();
+        conn.pragma_update(None, "user_version", server::migrations::latest_version() + 1).unwrap();
+    }
+
+    assert!(integrity::verify(&settings, None).is_err());
+
+    settings.on_integrity_failure = "read_only".into();
+    let Ok(IntegrityStatus::Degraded(problems)) = integrity::verify(&settings, None) else {
+        panic!("只读模式下应当降级启动");
+    };
+    assert_eq!(problems.len(), 1);
+
+    let db = DbPool::open_read_only(&settings, None).unwrap();
+    assert!(!db.user_exists_by_id("u1").unwrap());
+    assert!(db.register_user("alice", "", "secret").is_err());
+
+    drop(db);
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+    }
+}
+
+#[test]
+fn integrity_check_rejects_unreadable_file() {
+    let path = std::env::temp_dir().join(format!("yueling-garbage-{}.db", uuid::Uuid::new_v4()));
+    std::fs::write(&path, vec![0x5a; 8192]).unwrap();
+    let settings = server::settings::DatabaseSettings {
+        path: path.to_str().unwrap().to_string(),
+        on_integrity_failure: "read_only".into(),
+        ..Default::default()
+    };
+
+    // 文件头无法识别时即使配置了只读模式也必须拒绝启动
+    assert!(server::integrity::verify(&settings, None).is_err());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn export_user_includes_messages_without_password_hash() {
+    let db = DbPool::in_memory().unwrap();
+    let alice = db.register_user("alice", "", "secret").unwrap();
+    let bob = db.register_user("bob", "", "secret").unwrap();
+    db.send_message(DEFAULT_WORKSPACE, &alice.id, &bob.id, "给 bob", "private").unwrap();
+    db.send_message(DEFAULT_WORKSPACE, &bob.id, &alice.id, "给 alice", "private").unwrap();
+    db.send_message(DEFAULT_WORKSPACE, &bob.id, "someone-else", "无关", "private").unwrap();
+
+    let export = db.export_user(&alice.id).unwrap();
+    assert_eq!(export.user.username, "alice");
+    let contents: Vec<&str> = export.messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, vec!["给 bob", "给 alice"]);
+
+    let json = serde_json::to_value(&export).unwrap();
+    assert!(json["user"].get("password_hash").is_none());
+}
+
+#[test]
+fn seed_generates_requested_volume() {
+    let db = DbPool::in_memory().unwrap();
+    let options = server::seed::SeedOptions {
+        users: 8,
+        groups: 2,
+        group_size: 4,
+        friends_per_user: 2,
+        messages: 300,
+        ..Default::default()
+    };
+    let report = db.seed_demo_data(&options, 100 * 24 * 60 * 60).unwrap();
+    assert_eq!(report.users, 8);
+    assert_eq!(report.friendships, 16);
+    assert_eq!(report.groups, 2);
+    assert_eq!(report.messages, 300);
+
+    let conn = db.0.lock().unwrap();
+    let count: i64 = conn.query_row("SELECT count(*) FROM messages", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 300);
+    drop(conn);
+
+    // 用户名重复时整体回滚
+    assert!(db.seed_demo_data(&options, 0).is_err());
+    let conn = db.0.lock().unwrap();
+    let count: i64 = conn.query_row("SELECT count(*) FROM users", [], |row| row.get(0)).unwrap();
+    assert_eq!(count, 8);
+}
+
+#[test]
+fn mark_messages_as_read_updates_the_whole_batch() {
+    let db = DbPool::in_memory().unwrap();
+    let alice = db.register_user("alice", "", "secret").unwrap().id;
+    let bob = db.register_user("bob", "", "secret").unwrap().id;
+    let ids: Vec<String> = (0..3)
+        .map(|i| db.send_message(DEFAULT_WORKSPACE, &alice, &bob, &format!("第 {i} 条"), "private").unwrap().id)
+        .collect();
+
+    // 只标记前两条，不存在的ID忽略；确认后发件箱不再重发
+    db.mark_messages_as_read(&[ids[0].clone(), ids[1].clone(), "missing".into()], 100).unwrap();
+    let unread: Vec<_> = db.get_unread_messages(DEFAULT_WORKSPACE, &bob).unwrap().into_iter().map(|m| m.id).collect();
+    assert_eq!(unread, [ids[2].clone()]);
+    let pending: Vec<String> = {
+        let conn = db.0.lock().unwrap();
+        let mut stmt = conn.prepare("SELECT message_id FROM ws_outbox").unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+    };
+    assert_eq!(pending, [ids[2].clone()]);
+    let read_at: Option<i64> = db.0.lock().unwrap()
+        .query_row("SELECT read_at FROM messages WHERE id = ?", [&ids[0]], |row| row.get(0))
+        .unwrap();
+    assert_eq!(read_at, Some(100));
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{outbound, settings::Settings, system_messages::SystemEvent, workspaces::DEFAULT_WORKSPACE, AppState, DbPool};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+async fn group_history(app: &TestApp, user_id: &str, group_id: &str) -> Vec<Value> {
+    let (_, body) = app.post_as(user_id, "/messages/history", json!({ "user_id": user_id, "peer_id": group_id, "limit": 10 })).await;
+    body["messages"].as_array().unwrap().clone()
+}
+
+#[tokio::test]
+async fn joins_and_renames_are_posted_to_the_group() {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    let state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    let app = TestApp::with_state(&state);
+    let alice = app.register("alice", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    let (tx, mut rx) = outbound::channel(16);
+    state.attach_client("alice-phone", &alice, tx);
+    let admin = format!("Bearer {ADMIN_TOKEN}");
+
+    let (_, body) = app.request_with_headers(
+        Method::POST, "/admin/bots", Some(json!({ "name": "ci-bot", "scope": "send" })), &[("authorization", admin.as_str())],
+    ).await;
+    let bot_id = body["bot_id"].as_str().unwrap().to_string();
+    let join = format!("/admin/bots/{bot_id}/groups/{}", group.id);
+    for _ in 0..2 {
+        let (status, _) = app.request_with_headers(Method::PUT, &join, None, &[("authorization", admin.as_str())]).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    // 群成员实时收到结构化的系统事件，重复加入不再生成
+    let event: Value = serde_json::from_str(&rx.try_recv().unwrap()).unwrap();
+    assert_eq!((event["type"].as_str(), event["seq"].as_i64()), (Some("system_message"), Some(1)));
+    assert_eq!(event["event"], json!({ "event": "member_joined", "user_id": bot_id }));
+    assert!(rx.try_recv().is_err());
+
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{alice}"), Some(json!({ "username": "alice2", "email": "alice@example.com" })), &[("authorization", &app.session(&alice))]).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // 系统消息与普通消息一起出现在群聊历史中，content 是可解析的事件
+    let history = group_history(&app, &alice, &group.id).await;
+    assert_eq!(history.len(), 2);
+    assert_eq!((history[0]["message_type"].as_str(), history[0]["seq"].as_i64()), (Some("system"), Some(2)));
+    let renamed: SystemEvent = serde_json::from_str(history[0]["content"].as_str().unwrap()).unwrap();
+    assert_eq!(renamed, SystemEvent::NameChanged { user_id: alice.clone(), old_name: "alice".into(), new_name: "alice2".into() });
+    assert_eq!(history[0]["sender_id"], alice.as_str());
+
+    // 群出现在会话列表中
+    let (_, body) = app.post("/login", json!({ "username": "alice2", "password": "secret" })).await;
+    let auth = format!("Bearer {}", body["token"].as_str().unwrap());
+    let (_, body) = app.request_with_headers(Method::GET, "/conversations", None, &[("authorization", auth.as_str())]).await;
+    assert_eq!(body["conversations"][0]["peer_id"], group.id.as_str());
+}
+
+#[tokio::test]
+async fn clients_cannot_send_system_messages() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+
+    let message = json!({ "sender_id": alice, "receiver_id": group.id, "content": "{\"event\":\"member_joined\"}", "message_type": "system" });
+    let (status, body) = app.post_as(&alice, "/send-message", message.clone()).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("message.system_type_reserved")));
+    let (status, _) = app.post_as(&alice, "/messages/batch", json!({ "messages": [message] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(group_history(&app, &alice, &group.id).await.is_empty());
+}
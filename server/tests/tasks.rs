@@ -0,0 +1,108 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use server::{settings::Settings, AppState, DbPool, Supervisor, TaskState, TaskStatus};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+fn status(supervisor: &Supervisor, name: &str) -> Option<TaskStatus> {
+    supervisor.statuses().into_iter().find(|s| s.name == name)
+}
+
+// 轮询直到任务满足条件，最多等两秒
+async fn wait_for(supervisor: &Supervisor, name: &str, done: impl Fn(&TaskStatus) -> bool) -> TaskStatus {
+    for _ in 0..200 {
+        if let Some(status) = status(supervisor, name).filter(|s| done(s)) {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("任务 {name} 没有达到预期状态: {:?}", status(supervisor, name));
+}
+
+#[tokio::test]
+async fn panicking_task_is_restarted() {
+    let supervisor = Supervisor::new(Duration::from_millis(5));
+    let runs = Arc::new(AtomicU32::new(0));
+    let counter = runs.clone();
+    supervisor.spawn("flaky", move || {
+        let runs = counter.clone();
+        async move {
+            if runs.fetch_add(1, Ordering::SeqCst) < 2 {
+                panic!("boom");
+            }
+            std::future::pending::<()>().await;
+        }
+    });
+
+    let status = wait_for(&supervisor, "flaky", |s| s.restarts == 2 && s.state == TaskState::Running).await;
+    assert_eq!(status.last_panic.as_deref(), Some("boom"));
+    assert!(status.last_panic_at.is_some());
+    assert_eq!(runs.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn finished_task_is_not_restarted() {
+    let supervisor = Supervisor::new(Duration::from_millis(5));
+    let runs = Arc::new(AtomicU32::new(0));
+    let counter = runs.clone();
+    supervisor.spawn("once", move || {
+        let runs = counter.clone();
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    let status = wait_for(&supervisor, "once", |s| s.state == TaskState::Finished).await;
+    assert_eq!(status.restarts, 0);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn shutdown_stops_tasks_before_running_hooks() {
+    let supervisor = Supervisor::new(Duration::from_millis(5));
+    supervisor.spawn("forever", std::future::pending::<()>);
+    wait_for(&supervisor, "forever", |s| s.state == TaskState::Running).await;
+
+    let hook_ran = Arc::new(AtomicBool::new(false));
+    let observer = supervisor.clone();
+    let flag = hook_ran.clone();
+    supervisor.on_shutdown("check", async move {
+        // 钩子执行时任务已经停止
+        assert_eq!(status(&observer, "forever").unwrap().state, TaskState::Stopped);
+        flag.store(true, Ordering::SeqCst);
+    });
+
+    supervisor.shutdown(Duration::from_secs(1)).await;
+    assert!(hook_ran.load(Ordering::SeqCst));
+    assert_eq!(status(&supervisor, "forever").unwrap().state, TaskState::Stopped);
+}
+
+#[tokio::test]
+async fn admin_lists_tasks() {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    let state = AppState::new(DbPool::in_memory().unwrap(), settings);
+    state.tasks.spawn("example", std::future::pending::<()>);
+    wait_for(&state.tasks, "example", |s| s.state == TaskState::Running).await;
+    let app = TestApp::with_state(&state);
+
+    let (status, _) = app.request(Method::GET, "/admin/tasks", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let auth = format!("Bearer {ADMIN_TOKEN}");
+    let (status, body) = app
+        .request_with_headers(Method::GET, "/admin/tasks", None, &[("authorization", &auth)])
+        .await;
+    assert_eq!(status, StatusCode::OK);
+    let tasks = body["tasks"].as_array().unwrap();
+    assert_eq!(tasks.len(), 1);
+    assert_eq!(tasks[0]["name"], "example");
+    assert_eq!(tasks[0]["state"], "running");
+    assert_eq!(tasks[0]["restarts"], 0);
+}
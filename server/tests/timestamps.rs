@@ -0,0 +1,63 @@
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::http::StatusCode;
+use common::TestApp;
+use serde_json::{json, Value};
+
+async fn history(app: &TestApp, user_id: &str, peer_id: &str) -> Value {
+    let (_, body) = app.post_as(user_id, "/messages/history", json!({ "user_id": user_id, "peer_id": peer_id, "limit": 10 })).await;
+    body["messages"][0].clone()
+}
+
+#[tokio::test]
+async fn server_time_is_close_to_the_local_clock() {
+    let app = TestApp::new();
+    let (status, body) = app.get("/time").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("server.time")));
+
+    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as i64;
+    let server_ms = body["server_time_ms"].as_i64().unwrap();
+    assert!((now_ms - server_ms).abs() < 5_000);
+    assert_eq!(body["server_time"].as_i64().unwrap(), server_ms / 1000);
+}
+
+#[tokio::test]
+async fn delivery_and_read_times_are_recorded_once() {
+    let app = TestApp::new();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (_, sent) = app
+        .post_as(&alice, "/send-message", json!({ "sender_id": alice, "receiver_id": bob, "content": "你好", "message_type": "private" }))
+        .await;
+    assert_eq!(sent["sent_at"], sent["created_at"]);
+    let message = history(&app, &bob, &alice).await;
+    assert_eq!(message["sent_at"], sent["sent_at"]);
+    assert!(message["delivered_at"].is_null() && message["read_at"].is_null());
+
+    let ids = json!({ "message_ids": [sent["message_id"]] });
+    app.post_as(&bob, "/messages/delivered", ids.clone()).await;
+    let delivered = history(&app, &bob, &alice).await;
+    assert_eq!(delivered["status"], "delivered");
+    assert!(delivered["delivered_at"].as_i64().unwrap() >= delivered["sent_at"].as_i64().unwrap());
+    assert!(delivered["read_at"].is_null());
+
+    // 已读不改写送达时间，再次标记送达也不会把状态退回
+    app.post_as(&bob, "/messages/read", ids.clone()).await;
+    app.post_as(&bob, "/messages/delivered", ids).await;
+    let read = history(&app, &bob, &alice).await;
+    assert_eq!(read["status"], "read");
+    assert_eq!(read["delivered_at"], delivered["delivered_at"]);
+    assert!(read["read_at"].as_i64().unwrap() >= read["delivered_at"].as_i64().unwrap());
+
+    // 未标记送达就直接已读时，送达时间与已读时间相同
+    let (_, sent) = app
+        .post_as(&bob, "/send-message", json!({ "sender_id": bob, "receiver_id": alice, "content": "在", "message_type": "private" }))
+        .await;
+    app.post_as(&alice, "/messages/read", json!({ "message_ids": [sent["message_id"]] })).await;
+    let (_, body) = app.post_as(&alice, "/messages/sync", json!({ "user_id": alice, "last_sync_time": 0, "limit": 50 })).await;
+    let reply = body["messages"].as_array().unwrap().iter().find(|m| m["id"] == sent["message_id"]).unwrap();
+    assert_eq!(reply["delivered_at"], reply["read_at"]);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use futures_util::future::BoxFuture;
+use serde_json::Value;
+use server::{settings::Settings, system_messages::SystemEvent, workspaces::DEFAULT_WORKSPACE, AppState, DbPool, Translator};
+use std::sync::{Arc, Mutex};
+
+// 记录翻译请求的测试替身，译文为 "[语言] 原文"
+#[derive(Default)]
+struct RecordingTranslator {
+    calls: Mutex<Vec<(String, String)>>,
+}
+
+impl Translator for RecordingTranslator {
+    fn translate<'a>(&'a self, text: &'a str, target_lang: &'a str) -> BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            self.calls.lock().unwrap().push((text.to_string(), target_lang.to_string()));
+            Ok(format!("[{}] {}", target_lang, text))
+        })
+    }
+}
+
+fn translating_app(settings: Settings, translator: &Arc<RecordingTranslator>) -> TestApp {
+    let db = DbPool::in_memory().unwrap().with_encryption(&settings.security).unwrap();
+    let mut state = AppState::new(db, settings);
+    state.translator = Some(translator.clone());
+    TestApp::with_state(&state)
+}
+
+async fn translate(app: &TestApp, auth: &str, message_id: &str, lang: &str) -> (StatusCode, Value) {
+    let path = format!("/messages/{message_id}/translate?lang={lang}");
+    app.request_with_headers(Method::POST, &path, None, &[("authorization", auth)]).await
+}
+
+#[tokio::test]
+async fn translations_are_cached_per_message_and_language() {
+    let mut settings = Settings::default();
+    settings.security.master_key = "test-master-key".into();
+    settings.security.encrypt_messages = true;
+    let translator = Arc::new(RecordingTranslator::default());
+    let app = translating_app(settings, &translator);
+    let (alice, _) = app.login("alice").await;
+    let (bob, bob_auth) = app.login("bob").await;
+    let message = app.db.send_message(DEFAULT_WORKSPACE, &alice, &bob, "你好", "private").unwrap();
+
+    let (status, body) = translate(&app, &bob_auth, &message.id, "en").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::OK, Some("translation.translated")));
+    assert_eq!((body["translated"].as_str(), body["cached"].as_bool()), (Some("[en] 你好"), Some(false)));
+    let (_, body) = translate(&app, &bob_auth, &message.id, "en").await;
+    assert_eq!((body["translated"].as_str(), body["cached"].as_bool()), (Some("[en] 你好"), Some(true)));
+    let (_, body) = translate(&app, &bob_auth, &message.id, "ja").await;
+    assert_eq!(body["cached"].as_bool(), Some(false));
+    assert_eq!(*translator.calls.lock().unwrap(), vec![("你好".to_string(), "en".to_string()), ("你好".to_string(), "ja".to_string())]);
+
+    // 开启静态加密时译文和原文一样以密文保存
+    let stored: String = app.db.0.lock().unwrap()
+        .query_row("SELECT content FROM message_translations WHERE message_id = ?1 AND lang = 'en'", [&message.id], |row| row.get(0))
+        .unwrap();
+    assert!(!stored.contains("你好"));
+}
+
+#[tokio::test]
+async fn only_participants_can_translate_user_messages() {
+    let translator = Arc::new(RecordingTranslator::default());
+    let app = translating_app(Settings::default(), &translator);
+    let (alice, alice_auth) = app.login("alice").await;
+    let (bob, _) = app.login("bob").await;
+    let (_, carol_auth) = app.login("carol").await;
+    let message = app.db.send_message(DEFAULT_WORKSPACE, &alice, &bob, "你好", "private").unwrap();
+
+    let (status, body) = translate(&app, &carol_auth, &message.id, "en").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("translation.message_not_found")));
+    let (status, body) = translate(&app, &alice_auth, &message.id, "english!").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("translation.invalid_lang")));
+    let (status, _) = app.request(Method::POST, &format!("/messages/{}/translate?lang=en", message.id), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let group = app.db.create_group(DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    let event = app.db.post_system_message(DEFAULT_WORKSPACE, &group.id, &SystemEvent::MemberJoined { user_id: bob.clone() }).unwrap();
+    let (status, body) = translate(&app, &alice_auth, &event.id, "en").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::BAD_REQUEST, Some("translation.system_message")));
+    assert!(translator.calls.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn translation_is_unavailable_without_a_backend() {
+    let app = TestApp::new();
+    let (alice, alice_auth) = app.login("alice").await;
+    let message = app.db.send_message(DEFAULT_WORKSPACE, &alice, &alice, "备忘", "private").unwrap();
+
+    let (status, body) = translate(&app, &alice_auth, &message.id, "en").await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::NOT_FOUND, Some("translation.disabled")));
+    let (_, body) = app.get("/server-info").await;
+    assert_eq!(body["features"]["translation"].as_bool(), Some(false));
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::json;
+use server::{settings::Settings, usernames::normalize_username, DbPool};
+
+async fn register_status(app: &TestApp, username: &str) -> (StatusCode, String) {
+    let (status, body) = app.post("/register", json!({ "username": username, "password": "secret" })).await;
+    (status, body["code"].as_str().unwrap_or_default().to_string())
+}
+
+#[test]
+fn normalization_folds_case_width_and_confusables() {
+    assert_eq!(normalize_username("Alice"), normalize_username("alice"));
+    assert_eq!(normalize_username("ａｌｉｃｅ"), normalize_username("alice"));
+    // 西里尔字母 а 与拉丁字母 a 形近
+    assert_eq!(normalize_username("\u{430}lice"), normalize_username("alice"));
+    assert_ne!(normalize_username("alice"), normalize_username("alicia"));
+}
+
+#[tokio::test]
+async fn registration_enforces_policy() {
+    let mut settings = Settings::default();
+    settings.username.blocked_words = vec!["badword".into()];
+    let app = TestApp::with_settings(settings);
+
+    assert_eq!(register_status(&app, "ab").await, (StatusCode::BAD_REQUEST, "user.length".into()));
+    assert_eq!(register_status(&app, "ADMIN").await, (StatusCode::BAD_REQUEST, "user.reserved".into()));
+    assert_eq!(register_status(&app, "xBadWordx").await, (StatusCode::BAD_REQUEST, "user.blocked_word".into()));
+    assert_eq!(register_status(&app, "bob smith").await, (StatusCode::BAD_REQUEST, "user.invalid_char".into()));
+    assert_eq!(register_status(&app, "\u{430}lice").await, (StatusCode::BAD_REQUEST, "user.mixed_scripts".into()));
+
+    app.register("alice", "secret").await;
+    assert_eq!(register_status(&app, "Alice").await, (StatusCode::CONFLICT, "user.exists".into()));
+    assert_eq!(register_status(&app, "ａｌｉｃｅ").await, (StatusCode::CONFLICT, "user.exists".into()));
+    // 单一文字的非拉丁用户名可以注册
+    assert_eq!(register_status(&app, "月灵用户").await.0, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn allowed_ranges_and_rename() {
+    let mut settings = Settings::default();
+    settings.username.allowed_ranges = vec!["0061-007A".into(), "0030-0039".into()];
+    let app = TestApp::with_settings(settings);
+
+    assert_eq!(register_status(&app, "Alice").await.1, "user.invalid_char");
+    app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let rename = |username: &str| json!({ "username": username, "email": format!("{username}@example.com") });
+    let (status, body) = app.request_with_headers(Method::PUT, &format!("/user/{bob}"), Some(rename("alice")), &[("authorization", &app.session(&bob))]).await;
+    assert_eq!((status, body["code"].as_str().unwrap()), (StatusCode::CONFLICT, "user.exists"));
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{bob}"), Some(rename("root")), &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, body) = app.request_with_headers(Method::PUT, &format!("/user/{bob}"), Some(rename("bobby")), &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    // 改回原名（只与自己冲突）
+    let (status, _) = app.request_with_headers(Method::PUT, &format!("/user/{bob}"), Some(rename("bob")), &[("authorization", &app.session(&bob))]).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[test]
+fn existing_users_are_backfilled() {
+    let path = std::env::temp_dir().join(format!("yueling-usernames-{}.db", uuid::Uuid::new_v4()));
+    let path_str = path.to_str().unwrap();
+    {
+        let db = DbPool::new(path_str).unwrap();
+        let conn = db.0.lock().unwrap();
+        // 模拟归一化迁移之前的数据库，其中有两个只差大小写的用户
+        conn.execute_batch(
+            "DROP INDEX idx_users_username_normalized;
+             DROP INDEX idx_messages_client_id;
+             ALTER TABLE messages DROP COLUMN client_message_id;
+             ALTER TABLE messages DROP COLUMN sent_at;
+             ALTER TABLE messages DROP COLUMN delivered_at;
+             ALTER TABLE messages DROP COLUMN read_at;
+             DROP INDEX idx_messages_conversation_seq;
+             DROP TABLE conversation_seqs;
+             ALTER TABLE messages DROP COLUMN conversation_id;
+             ALTER TABLE messages DROP COLUMN seq;
+             ALTER TABLE sessions DROP COLUMN device_id;
+             DROP INDEX idx_workspace_invites_creator;
+             ALTER TABLE workspace_invites DROP COLUMN created_by;
+             ALTER TABLE users DROP COLUMN display_name;
+             ALTER TABLE groups DROP COLUMN deleted_at;
+             DROP INDEX idx_groups_directory;
+             ALTER TABLE groups DROP COLUMN visibility;
+             ALTER TABLE groups DROP COLUMN join_approval;
+             DROP INDEX idx_group_members_page;
+             ALTER TABLE group_members DROP COLUMN muted_until;
+             ALTER TABLE messages DROP COLUMN entities;
+             ALTER TABLE users DROP COLUMN username_normalized;
+             ALTER TABLE users DROP COLUMN email_verified_at;
+             INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u1', 'Alice', 'u1@local', 'hash', 1);
+             INSERT INTO users (id, username, email, password_hash, created_at) VALUES ('u2', 'alice', 'u2@local', 'hash', 2);",
+        )
+        .unwrap();
+        conn.pragma_update(None, "user_version", 14).unwrap();
+    }
+
+    let db = DbPool::new(path_str).unwrap();
+    {
+        let conn = db.0.lock().unwrap();
+        let normalized = |id: &str| -> Option<String> {
+            conn.query_row("SELECT username_normalized FROM users WHERE id = ?", [id], |row| row.get(0)).unwrap()
+        };
+        assert_eq!(normalized("u1").as_deref(), Some("alice"));
+        // 较晚注册的冲突用户留空
+        assert_eq!(normalized("u2"), None);
+    }
+    drop(db);
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,123 @@
+mod common;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, Method, StatusCode},
+    routing::post,
+    Router,
+};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::{settings::{Settings, WebhookSettings}, webhook_dispatcher};
+use std::sync::{Arc, Mutex};
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+
+type Received = Arc<Mutex<Vec<(HeaderMap, String)>>>;
+
+// 在本地随机端口启动一个记录所有请求的接收端
+async fn start_receiver() -> (String, Received) {
+    let received: Received = Arc::default();
+    let app = Router::new()
+        .route("/hook", post(|State(received): State<Received>, headers: HeaderMap, body: String| async move {
+            received.lock().unwrap().push((headers, body));
+            StatusCode::OK
+        }))
+        .with_state(received.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (format!("http://{addr}/hook"), received)
+}
+
+fn admin_app() -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    TestApp::with_settings(settings)
+}
+
+async fn admin(app: &TestApp, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+    let header = format!("Bearer {ADMIN_TOKEN}");
+    app.request_with_headers(method, path, body, &[("authorization", header.as_str())]).await
+}
+
+#[tokio::test]
+async fn events_are_signed_and_delivered() {
+    let (url, received) = start_receiver().await;
+    let app = admin_app();
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+
+    let (status, body) = admin(&app, Method::POST, "/admin/webhooks", Some(json!({
+        "url": url, "event": "message.sent", "channel_id": bob
+    }))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let webhook_id = body["webhook"]["id"].as_str().unwrap().to_string();
+    let secret = body["secret"].as_str().unwrap().to_string();
+    assert!(body["webhook"].get("secret").is_none());
+
+    // 只有发给 bob 的消息会触发
+    for receiver in [&bob, &alice] {
+        app.post_as(&alice, "/send-message", json!({
+            "sender_id": alice, "receiver_id": receiver, "content": "你好", "message_type": "private"
+        }))
+        .await;
+    }
+
+    let client = reqwest::Client::new();
+    let delivered = webhook_dispatcher::dispatch_due(&app.db, &client, &WebhookSettings::default()).await.unwrap();
+    assert_eq!(delivered, 1);
+
+    let received = received.lock().unwrap().clone();
+    let (headers, body) = &received[0];
+    assert_eq!(headers["x-yueling-event"], "message.sent");
+    assert_eq!(headers["x-yueling-signature"], webhook_dispatcher::sign(&secret, body).as_str());
+    let payload: Value = serde_json::from_str(body).unwrap();
+    assert_eq!(payload["data"]["receiver_id"], bob.as_str());
+
+    let (_, body) = admin(&app, Method::GET, &format!("/admin/webhooks/{webhook_id}/deliveries"), None).await;
+    assert_eq!(body["deliveries"][0]["status"], "delivered");
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_then_marked_failed() {
+    let app = admin_app();
+    // 绑定后立即释放端口，保证连接被拒绝
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+    let (_, body) = admin(&app, Method::POST, "/admin/webhooks", Some(json!({
+        "url": format!("http://{closed}/hook"), "event": "user.registered"
+    }))).await;
+    let webhook_id = body["webhook"]["id"].as_str().unwrap().to_string();
+    app.register("carol", "secret").await;
+
+    let settings = WebhookSettings { max_attempts: 2, ..Default::default() };
+    let client = reqwest::Client::new();
+    webhook_dispatcher::dispatch_due(&app.db, &client, &settings).await.unwrap();
+
+    let (_, body) = admin(&app, Method::GET, &format!("/admin/webhooks/{webhook_id}/deliveries"), None).await;
+    let delivery = &body["deliveries"][0];
+    assert_eq!(delivery["status"], "pending");
+    assert_eq!(delivery["attempts"], 1);
+    assert!(delivery["last_error"].is_string());
+
+    // 退避时间未到，不会再次尝试；手动把时间拨到期后第二次失败即放弃
+    assert_eq!(webhook_dispatcher::dispatch_due(&app.db, &client, &settings).await.unwrap(), 0);
+    app.db.0.lock().unwrap().execute("UPDATE webhook_deliveries SET next_attempt_at = 0", []).unwrap();
+    webhook_dispatcher::dispatch_due(&app.db, &client, &settings).await.unwrap();
+
+    let (_, body) = admin(&app, Method::GET, &format!("/admin/webhooks/{webhook_id}/deliveries"), None).await;
+    assert_eq!(body["deliveries"][0]["status"], "failed");
+    assert_eq!(body["deliveries"][0]["attempts"], 2);
+}
+
+#[tokio::test]
+async fn unknown_events_are_rejected() {
+    let app = admin_app();
+    let (status, _) = admin(&app, Method::POST, "/admin/webhooks", Some(json!({
+        "url": "https://example.com/hook", "event": "user.exploded"
+    }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = admin(&app, Method::DELETE, "/admin/webhooks/missing", None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,134 @@
+mod common;
+
+use axum::http::{Method, StatusCode};
+use common::TestApp;
+use serde_json::{json, Value};
+use server::settings::Settings;
+
+const ADMIN_TOKEN: &str = "test-admin-token";
+const ADMIN_AUTH: &str = "Bearer test-admin-token";
+
+fn app() -> TestApp {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    TestApp::with_settings(settings)
+}
+
+async fn admin(app: &TestApp, method: Method, path: &str, body: Option<Value>) -> (StatusCode, Value) {
+    app.request_with_headers(method, path, body, &[("authorization", ADMIN_AUTH)]).await
+}
+
+// 以该用户的会话在工作区中发送请求
+async fn in_workspace(app: &TestApp, slug: &str, user_id: &str, path: &str, body: Value) -> (StatusCode, Value) {
+    let auth = app.session(user_id);
+    app.request_with_headers(Method::POST, path, Some(body), &[("x-workspace", slug), ("authorization", &auth)]).await
+}
+
+#[tokio::test]
+async fn messages_are_isolated_per_workspace() {
+    let app = app();
+    let (status, body) = admin(&app, Method::POST, "/admin/workspaces", Some(json!({ "slug": "acme", "name": "Acme" }))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let alice = app.register("alice", "secret").await;
+    let bob = app.register("bob", "secret").await;
+    let carol = app.register("carol", "secret").await;
+    for user in [&alice, &bob] {
+        let (status, body) = admin(&app, Method::PUT, &format!("/admin/workspaces/acme/members/{user}"), Some(json!({}))).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+    }
+
+    let send = |content: &str| json!({ "sender_id": alice, "receiver_id": bob, "content": content, "message_type": "private" });
+    let (status, body) = in_workspace(&app, "acme", &alice, "/send-message", send("工作区消息")).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    app.post_as(&alice, "/send-message", send("默认工作区消息")).await;
+
+    // 各工作区只看得到自己的消息
+    let history = json!({ "user_id": bob, "peer_id": alice, "limit": 10 });
+    let (_, body) = in_workspace(&app, "acme", &bob, "/messages/history", history.clone()).await;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(body["messages"][0]["content"], "工作区消息");
+    let (_, body) = app.post_as(&bob, "/messages/history", history).await;
+    assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    assert_eq!(body["messages"][0]["content"], "默认工作区消息");
+
+    // 非成员既不能发也不能收
+    let (status, _) = in_workspace(&app, "acme", &carol, "/send-message", json!({
+        "sender_id": carol, "receiver_id": bob, "content": "越界", "message_type": "private"
+    })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = in_workspace(&app, "acme", &alice, "/send-message", json!({
+        "sender_id": alice, "receiver_id": carol, "content": "越界", "message_type": "private"
+    })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = in_workspace(&app, "acme", &carol, "/messages/unread", json!({ "user_id": carol })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = in_workspace(&app, "missing", &alice, "/messages/unread", json!({ "user_id": alice })).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, body) = app.request_with_headers(Method::GET, &format!("/user/{alice}/workspaces"), None, &[("authorization", &app.session(&alice))]).await;
+    let slugs: Vec<&str> = body["workspaces"].as_array().unwrap().iter().map(|w| w["slug"].as_str().unwrap()).collect();
+    assert_eq!(slugs, ["default", "acme"]);
+}
+
+#[tokio::test]
+async fn invite_only_workspace_requires_single_use_code() {
+    let app = app();
+    admin(&app, Method::POST, "/admin/workspaces", Some(json!({ "slug": "acme", "name": "Acme", "invite_only": true }))).await;
+
+    let (status, _) = app.post("/register", json!({ "username": "alice", "password": "secret", "workspace": "acme" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, body) = admin(&app, Method::POST, "/admin/workspaces/acme/invites", None).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let code = body["invite_code"].as_str().unwrap().to_string();
+    assert!(code.starts_with("ylw_"));
+
+    let (status, body) = app.post("/register", json!({
+        "username": "alice", "password": "secret", "workspace": "acme", "invite_code": code
+    })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let alice = body["user_id"].as_str().unwrap().to_string();
+    let (_, body) = app.request_with_headers(Method::GET, &format!("/user/{alice}/workspaces"), None, &[("authorization", &app.session(&alice))]).await;
+    assert_eq!(body["workspaces"][1]["slug"], "acme");
+
+    // 邀请码只能用一次，失败的注册不会留下用户
+    let (status, _) = app.post("/register", json!({
+        "username": "bob", "password": "secret", "workspace": "acme", "invite_code": code
+    })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let users: i64 = app.db.0.lock().unwrap()
+        .query_row("SELECT COUNT(*) FROM users WHERE username = 'bob'", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(users, 0);
+}
+
+#[tokio::test]
+async fn closed_registration_accepts_admin_and_user_invites() {
+    let mut settings = Settings::default();
+    settings.admin.token = ADMIN_TOKEN.into();
+    settings.registration.invite_only = true;
+    settings.registration.invites_per_user = 1;
+    let app = TestApp::with_settings(settings);
+
+    let (status, body) = app.post("/register", json!({ "username": "alice", "password": "secret" })).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::FORBIDDEN, Some("user.invite_required_by_config")));
+    let (status, body) = admin(&app, Method::POST, "/invites", Some(json!({ "expires_in_secs": 60 }))).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let (status, body) = app.post("/register", json!({ "username": "alice", "password": "secret", "invite_code": body["invite_code"] })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    // 用户按名额签发邀请码
+    let (_, body) = app.post("/login", json!({ "username": "alice", "password": "secret" })).await;
+    let auth = format!("Bearer {}", body["token"].as_str().unwrap());
+    let (status, body) = app.request_with_headers(Method::POST, "/invites", None, &[("authorization", auth.as_str())]).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+    let code = body["invite_code"].as_str().unwrap().to_string();
+    let (status, body) = app.request_with_headers(Method::POST, "/invites", None, &[("authorization", auth.as_str())]).await;
+    assert_eq!((status, body["code"].as_str()), (StatusCode::TOO_MANY_REQUESTS, Some("workspace.invite_quota_exhausted")));
+    let (status, body) = app.post("/register", json!({ "username": "bob", "password": "secret", "invite_code": code })).await;
+    assert_eq!(status, StatusCode::OK, "{body}");
+
+    let (status, _) = app.post("/invites", json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,155 @@
+mod common;
+
+use common::e2e::TestServer;
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::Message;
+
+fn error_of(event: &Value) -> (&str, &str, &Value) {
+    assert_eq!(event["type"], "error", "{event}");
+    (event["code"].as_str().unwrap(), event["message"].as_str().unwrap(), &event["client_message_id"])
+}
+
+#[tokio::test]
+async fn malformed_frames_get_typed_error_events() {
+    let server = TestServer::start().await;
+    let alice = server.app().register("alice", "secret").await;
+    let bob = server.app().register("bob", "secret").await;
+    let mut ws = server.ws(&alice).await;
+
+    ws.send_frame(Message::binary(vec![1, 2, 3])).await;
+    assert_eq!(error_of(&ws.next_event().await), ("ws.non_text_frame", "只支持文本帧", &Value::Null));
+    ws.send_frame(Message::text("不是 JSON")).await;
+    assert_eq!(error_of(&ws.next_event().await).0, "ws.invalid_json");
+    ws.send(json!({ "receiver_id": bob })).await;
+    assert_eq!(error_of(&ws.next_event().await).0, "ws.missing_type");
+
+    // 出错请求的 client_message_id 原样带回
+    ws.send(json!({ "type": "bogus", "client_message_id": "c1" })).await;
+    assert_eq!(error_of(&ws.next_event().await), ("ws.unknown_type", "不支持的帧类型: bogus", &json!("c1")));
+    ws.send(json!({ "type": "message", "sender_id": alice, "content": "你好", "client_message_id": "c2" })).await;
+    assert_eq!(error_of(&ws.next_event().await), ("ws.missing_field", "帧缺少字段: receiver_id", &json!("c2")));
+    ws.send(json!({ "type": "delivered", "message_ids": "m1" })).await;
+    assert_eq!(error_of(&ws.next_event().await).1, "帧缺少字段: message_ids");
+
+    // 保存失败时给出具体原因
+    ws.send(json!({ "type": "message", "sender_id": alice, "receiver_id": bob, "content": "长".repeat(10_001), "client_message_id": "c3" })).await;
+    assert_eq!(error_of(&ws.next_event().await), ("message.too_long", "消息不能超过 10000 个字符", &json!("c3")));
+
+    // 出错后连接照常可用
+    ws.send(json!({ "type": "message", "sender_id": alice, "receiver_id": bob, "content": "你好", "client_message_id": "c4" })).await;
+    let ack = ws.next_event().await;
+    assert_eq!((ack["type"].as_str(), ack["client_message_id"].as_str()), (Some("message_ack"), Some("c4")));
+}
+
+#[tokio::test]
+async fn errors_follow_the_accept_language_of_the_upgrade_request() {
+    let server = TestServer::start().await;
+    let mut ws = server.ws_raw("en-US,en;q=0.9").await;
+
+    // 第一帧无效时连接仍然建立
+    ws.send_frame(Message::text("{")).await;
+    assert_eq!(error_of(&ws.next_event().await), ("ws.invalid_json", "The frame is not valid JSON", &Value::Null));
+    ws.send(json!({ "type": "voice_call_end" })).await;
+    assert_eq!(error_of(&ws.next_event().await).1, "The frame is missing field: remote_user_id");
+}
+
+#[tokio::test]
+async fn identify_requires_the_session_of_the_claimed_user() {
+    let server = TestServer::start().await;
+    let alice = server.app().register("alice", "secret").await;
+    let bob = server.app().register("bob", "secret").await;
+    let mut ws = server.ws_raw("zh-CN").await;
+
+    // 没有会话令牌、或以 alice 的令牌冒充 bob 都不会登记连接
+    ws.send(json!({ "type": "identify", "user_id": bob })).await;
+    assert_eq!(error_of(&ws.next_event().await).0, "session.missing");
+    ws.send(json!({ "type": "identify", "user_id": bob, "token": server.token(&alice) })).await;
+    assert_eq!(error_of(&ws.next_event().await).0, "message.acting_as_other_user");
+    ws.send(json!({ "type": "identify", "token": "bogus" })).await;
+    assert_eq!(error_of(&ws.next_event().await).0, "session.invalid");
+    ws.send(json!({ "type": "call_offer", "callee_id": alice })).await;
+    assert_eq!(error_of(&ws.next_event().await).0, "ws.not_identified");
+
+    // 令牌也可以放在升级请求的 Authorization 请求头中，此时帧中不必带用户ID
+    let token = format!("Bearer {}", server.token(&bob));
+    let mut bob_ws = server.ws_at("/ws", &[("authorization", &token)]).await.unwrap();
+    bob_ws.send(json!({ "type": "identify" })).await;
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    server.app().post_as(&alice, "/send-message", json!({ "receiver_id": bob, "content": "你好", "message_type": "private" })).await;
+    assert_eq!(bob_ws.next_event().await["type"], "message");
+    ws.assert_silent().await;
+}
+
+#[tokio::test]
+async fn frame_sender_is_the_identified_user() {
+    let server = TestServer::start().await;
+    let alice = server.app().register("alice", "secret").await;
+    let bob = server.app().register("bob", "secret").await;
+    let mut alice_ws = server.ws(&alice).await;
+    let mut bob_ws = server.ws(&bob).await;
+
+    // 不写 sender_id 时发送者就是连接的用户，转发的通话邀请由服务器填入 sender_id
+    alice_ws.send(json!({ "type": "message", "receiver_id": bob, "content": "你好" })).await;
+    let event = bob_ws.next_event().await;
+    assert_eq!((event["type"].as_str(), event["sender_id"].as_str()), (Some("message"), Some(alice.as_str())));
+    alice_ws.send(json!({ "type": "voice_call_offer", "receiver_id": bob, "sdp": "offer" })).await;
+    let event = bob_ws.next_event().await;
+    assert_eq!((event["type"].as_str(), event["sender_id"].as_str()), (Some("voice_call_offer"), Some(alice.as_str())));
+    for frame_type in ["voice_call_answer", "ice_candidate", "voice_call_end"] {
+        bob_ws.send(json!({ "type": frame_type, "remote_user_id": alice })).await;
+        let event = alice_ws.next_event().await;
+        assert_eq!((event["type"].as_str(), event["sender_id"].as_str()), (Some(frame_type), Some(bob.as_str())));
+    }
+
+    // 冒充其他用户的帧被拒绝，也不会转发
+    for frame in [
+        json!({ "type": "message", "sender_id": bob, "receiver_id": alice, "content": "冒充", "client_message_id": "c1" }),
+        json!({ "type": "voice_call_offer", "sender_id": bob, "receiver_id": alice, "client_message_id": "c2" }),
+        json!({ "type": "voice_call_end", "sender_id": bob, "remote_user_id": bob, "client_message_id": "c3" }),
+    ] {
+        alice_ws.send(frame.clone()).await;
+        assert_eq!(error_of(&alice_ws.next_event().await), ("message.acting_as_other_user", "不能以其他用户的身份操作", &frame["client_message_id"]));
+    }
+    // 只订阅群聊、没有 identify 的连接不能发消息
+    let mut anonymous = server.ws_raw("zh-CN").await;
+    anonymous.send(json!({ "list_of_group_chats": [] })).await;
+    anonymous.send(json!({ "type": "message", "sender_id": alice, "receiver_id": bob, "content": "匿名" })).await;
+    assert_eq!(error_of(&anonymous.next_event().await).0, "ws.not_identified");
+    anonymous.send(json!({ "type": "voice_call_answer", "remote_user_id": bob, "sdp": "answer" })).await;
+    assert_eq!(error_of(&anonymous.next_event().await).0, "ws.not_identified");
+    bob_ws.assert_silent().await;
+    assert!(server.state.db_pool.get_unread_messages(server::workspaces::DEFAULT_WORKSPACE, &alice).unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn group_subscriptions_and_broadcasts_need_a_member_session() {
+    let server = TestServer::start().await;
+    let alice = server.app().register("alice", "secret").await;
+    let bob = server.app().register("bob", "secret").await;
+    let mallory = server.app().register("mallory", "secret").await;
+    let group = server.state.db_pool.create_group(server::workspaces::DEFAULT_WORKSPACE, "项目组", &alice).unwrap();
+    server.state.db_pool.add_group_member(&group.id, &bob, "member").unwrap();
+
+    // 没有会话令牌不能订阅，非成员订阅其他群被拒绝
+    let mut anonymous = server.ws_raw("zh-CN").await;
+    anonymous.send(json!({ "list_of_group_chats": [group.id] })).await;
+    assert_eq!(error_of(&anonymous.next_event().await).0, "session.missing");
+    let mut mallory_ws = server.ws_raw("zh-CN").await;
+    mallory_ws.send(json!({ "list_of_group_chats": [group.id], "token": server.token(&mallory) })).await;
+    assert_eq!(error_of(&mallory_ws.next_event().await).0, "group.not_found");
+
+    // 成员在首帧中带会话令牌订阅
+    let mut bob_ws = server.ws_raw("zh-CN").await;
+    bob_ws.send(json!({ "list_of_group_chats": [group.id], "token": server.token(&bob) })).await;
+    let mut alice_ws = server.ws(&alice).await;
+    alice_ws.send(json!({ "type": "group_chat", "group_id": group.id, "content": "大家好" })).await;
+    assert_eq!(bob_ws.next_frame().await, Message::text("大家好"));
+
+    // 未标识的连接和非成员都不能向群广播
+    anonymous.send(json!({ "type": "group_chat", "group_id": group.id, "content": "匿名" })).await;
+    assert_eq!(error_of(&anonymous.next_event().await).0, "ws.not_identified");
+    let mut mallory_ws = server.ws(&mallory).await;
+    mallory_ws.send(json!({ "type": "group_chat", "group_id": group.id, "content": "广告" })).await;
+    assert_eq!(error_of(&mallory_ws.next_event().await).0, "group.not_found");
+    bob_ws.assert_silent().await;
+}